@@ -1,7 +1,10 @@
 #[cfg(target_os = "linux")]
+mod mounts_linux;
+#[cfg(target_os = "linux")]
 mod run_linux;
+mod shell;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // Import MountConfig from the appropriate source
 #[cfg(target_os = "linux")]
@@ -10,21 +13,44 @@ pub use agentfs_sandbox::MountConfig;
 #[cfg(not(target_os = "linux"))]
 pub use crate::non_linux::MountConfig;
 
-pub async fn handle_run_command(
-    mounts: Vec<MountConfig>,
-    strace: bool,
-    command: PathBuf,
-    args: Vec<String>,
-) {
+/// Options of the `run` command
+pub struct RunOptions {
+    pub config: Option<PathBuf>,
+    pub mounts: Vec<MountConfig>,
+    pub strace: bool,
+    pub net_log: Option<PathBuf>,
+    pub trace_writes: Option<PathBuf>,
+    pub capture_output: Option<PathBuf>,
+    pub allow_connect: Vec<String>,
+    pub deny_unix_sockets: bool,
+    pub process_tree: bool,
+    pub max_cpu_seconds: Option<u64>,
+    pub max_file_size: Option<u64>,
+    pub max_open_files: Option<u64>,
+    pub sync_on_close: bool,
+    pub dump_fds_on_signal: bool,
+    pub summary: Option<PathBuf>,
+    pub command: PathBuf,
+    pub args: Vec<String>,
+}
+
+/// Run a command in the sandbox and return the exit code for `agentfs run`
+pub async fn handle_run_command(options: RunOptions) -> i32 {
     #[cfg(target_os = "linux")]
     {
-        run_linux::run_sandbox(mounts, strace, command, args).await;
+        match run_linux::run_sandbox(options).await {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                1
+            }
+        }
     }
 
     #[cfg(not(target_os = "linux"))]
     {
         // Suppress unused variable warnings on non-Linux platforms
-        let _ = (mounts, strace, command, args);
+        let _ = options;
 
         eprintln!("Error: Sandbox is available only on Linux.");
         eprintln!();
@@ -35,6 +61,40 @@ pub async fn handle_run_command(
         eprintln!("  - 'agentfs init' to create a new agent filesystem");
         eprintln!("  - 'agentfs fs ls' to list files");
         eprintln!("  - 'agentfs fs cat' to view file contents");
-        std::process::exit(1);
+        1
+    }
+}
+
+/// Print how paths resolve against mount specifications
+pub async fn handle_mounts_command(mounts: Vec<MountConfig>, paths: Vec<PathBuf>) -> i32 {
+    #[cfg(target_os = "linux")]
+    {
+        match mounts_linux::check_mounts(mounts, paths).await {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                1
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        // Suppress unused variable warnings on non-Linux platforms
+        let _ = (mounts, paths);
+
+        eprintln!("Error: Mounts are available only on Linux.");
+        1
+    }
+}
+
+/// Run an interactive shell over an agent filesystem
+pub async fn handle_shell_command(filesystem: &Path) -> i32 {
+    match shell::run_shell(filesystem).await {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
     }
 }
@@ -0,0 +1,49 @@
+use super::run_linux::describe_mount;
+use agentfs_sandbox::{build_mount_table, MountConfig};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Print how paths resolve against a set of mounts, without running a guest
+///
+/// Without explicit paths, the destination of every mount is resolved.
+pub async fn check_mounts(mounts: Vec<MountConfig>, paths: Vec<PathBuf>) -> Result<()> {
+    let mount_table = build_mount_table(&mounts).await?;
+    let describe = |dst: &Path| {
+        mounts
+            .iter()
+            .find(|m| m.dst == dst)
+            .map(describe_mount)
+            .unwrap_or_else(|| dst.display().to_string())
+    };
+
+    println!("Mount table (longest prefix first):");
+    for mount in mount_table.mounts() {
+        println!(" - {}", describe(&mount.sandbox_path));
+    }
+
+    let paths = if paths.is_empty() {
+        mounts.iter().map(|m| m.dst.clone()).collect()
+    } else {
+        paths
+    };
+
+    println!();
+    println!("Resolution:");
+    for path in paths {
+        println!("{}", path.display());
+        match mount_table.resolve_mount(&path) {
+            Some((mount, translated)) => {
+                println!("  mount: {}", describe(&mount.sandbox_path));
+                if mount.vfs.is_virtual() {
+                    // Virtual mounts see the path relative to the mount point
+                    let relative = path.strip_prefix(&mount.sandbox_path).unwrap_or(&path);
+                    println!("  path:  {}", Path::new("/").join(relative).display());
+                } else {
+                    println!("  path:  {}", translated.display());
+                }
+            }
+            None => println!("  not mounted, passed through to the host"),
+        }
+    }
+    Ok(())
+}
@@ -1,75 +1,159 @@
+use super::RunOptions;
 use agentfs_sandbox::{
-    init_fd_tables, init_mount_table, init_strace, BindVfs, MountConfig, MountTable, Sandbox,
-    SqliteVfs,
+    ConnectRule, ExitStatus, MountConfig, MountType, Resource, RunSummary, SandboxBuilder,
+    SandboxConfig,
 };
-use reverie_process::Command;
-use reverie_ptrace::TracerBuilder;
-use std::path::PathBuf;
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Run the command in the sandbox and return its exit code
+pub async fn run_sandbox(options: RunOptions) -> Result<i32> {
+    let RunOptions {
+        config,
+        mounts,
+        strace,
+        net_log,
+        trace_writes,
+        capture_output,
+        allow_connect,
+        deny_unix_sockets,
+        process_tree,
+        max_cpu_seconds,
+        max_file_size,
+        max_open_files,
+        sync_on_close,
+        dump_fds_on_signal,
+        summary,
+        command,
+        args,
+    } = options;
+
+    let mut config = match config {
+        Some(path) => SandboxConfig::load(path)?,
+        None => SandboxConfig::default(),
+    };
+    config.override_mounts(mounts);
+    config.strace |= strace;
 
-pub async fn run_sandbox(
-    mut mounts: Vec<MountConfig>,
-    strace: bool,
-    command: PathBuf,
-    args: Vec<String>,
-) {
     eprintln!("Welcome to AgentFS!");
     eprintln!();
 
-    let mut mount_table = MountTable::new();
-
     // If no mounts specified, add default agent.db mount at /agent
-    if mounts.is_empty() {
-        mounts.push(MountConfig {
-            mount_type: agentfs_sandbox::MountType::Sqlite {
+    if config.mounts.is_empty() {
+        config.mounts.push(MountConfig {
+            mount_type: MountType::Sqlite {
                 src: PathBuf::from("agent.db"),
             },
             dst: PathBuf::from("/agent"),
+            uid: None,
+            gid: None,
         });
     }
 
     eprintln!("The following mount points are sandboxed:");
-    for mount_config in &mounts {
-        match &mount_config.mount_type {
-            agentfs_sandbox::MountType::Bind { src } => {
-                eprintln!(
-                    " - {} -> {} (host)",
-                    mount_config.dst.display(),
-                    src.display()
-                );
-
-                // Create a BindVfs for this bind mount
-                let vfs = Arc::new(BindVfs::new(src.clone(), mount_config.dst.clone()));
-                mount_table.add_mount(mount_config.dst.clone(), vfs);
-            }
-            agentfs_sandbox::MountType::Sqlite { src } => {
-                eprintln!(
-                    " - {} -> {} (sqlite)",
-                    mount_config.dst.display(),
-                    src.display()
-                );
+    for mount_config in &config.mounts {
+        eprintln!(" - {}", describe_mount(mount_config));
+    }
+    eprintln!();
 
-                // Create a SqliteVfs for this sqlite mount
-                let vfs = SqliteVfs::new(src, mount_config.dst.clone())
-                    .await
-                    .expect("Failed to create SQLite VFS");
-                mount_table.add_mount(mount_config.dst.clone(), Arc::new(vfs));
-            }
+    let mut builder = SandboxBuilder::new(command)
+        .args(args)
+        .config(config)
+        .deny_unix_sockets(deny_unix_sockets)
+        .sync_on_close(sync_on_close)
+        .dump_fds_on_signal(dump_fds_on_signal);
+    if let Some(net_log) = net_log {
+        builder = builder.net_log(net_log);
+    }
+    if let Some(trace_writes) = trace_writes {
+        builder = builder.trace_writes(trace_writes);
+    }
+    if let Some(capture_output) = capture_output {
+        builder = builder.capture_output(capture_output);
+    }
+    let limits = [
+        (Resource::Cpu, max_cpu_seconds),
+        (Resource::FileSize, max_file_size),
+        (Resource::OpenFiles, max_open_files),
+    ];
+    for (resource, limit) in limits {
+        if let Some(limit) = limit {
+            builder = builder.limit(resource, limit, limit);
         }
     }
-    eprintln!();
+    for rule in allow_connect {
+        let rule = rule.parse::<ConnectRule>().map_err(anyhow::Error::msg)?;
+        builder = builder.allow_connect(rule);
+    }
+    let (status, tree, run_summary) = match &summary {
+        Some(_) => builder.run_with_summary().await?,
+        None => {
+            let (status, tree) = builder.run_with_process_tree().await?;
+            (status, tree, RunSummary::default())
+        }
+    };
+    if process_tree {
+        eprintln!();
+        eprintln!("Process tree:");
+        eprint!("{}", tree);
+    }
+    let code = exit_code(status);
+    if let Some(path) = summary {
+        write_summary(&path, code, &run_summary)?;
+    }
+    Ok(code)
+}
 
-    init_mount_table(mount_table);
-    init_fd_tables();
-    init_strace(strace);
+/// The JSON object written by `--summary`
+#[derive(Serialize)]
+struct SummaryReport<'a> {
+    exit_code: i32,
+    #[serde(flatten)]
+    summary: &'a RunSummary,
+}
 
-    let mut cmd = Command::new(command);
-    for arg in args {
-        cmd.arg(arg);
+/// Write the run summary as JSON to `path`, or to stdout if it is `-`
+fn write_summary(path: &Path, exit_code: i32, summary: &RunSummary) -> Result<()> {
+    let report = SummaryReport { exit_code, summary };
+    let mut json = serde_json::to_string_pretty(&report)?;
+    json.push('\n');
+    if path == Path::new("-") {
+        std::io::stdout().write_all(json.as_bytes())?;
+    } else {
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write summary to {}", path.display()))?;
     }
+    Ok(())
+}
 
-    let tracer = TracerBuilder::<Sandbox>::new(cmd).spawn().await.unwrap();
+/// Describe a mount as shown in the mount point banner
+pub(super) fn describe_mount(mount_config: &MountConfig) -> String {
+    let dst = mount_config.dst.display();
+    match &mount_config.mount_type {
+        MountType::Bind { src } => format!("{} -> {} (host)", dst, src.display()),
+        MountType::ReadonlyBind { src } => {
+            format!("{} -> {} (host, read-only)", dst, src.display())
+        }
+        MountType::Sqlite { src } => format!("{} -> {} (sqlite)", dst, src.display()),
+        MountType::ReadonlySqlite { src } => {
+            format!("{} -> {} (sqlite, read-only)", dst, src.display())
+        }
+        MountType::Mem => format!("{} (memory)", dst),
+        MountType::Http { base_url, .. } => format!("{} -> {} (http)", dst, base_url),
+        MountType::S3 { bucket, prefix } => format!("{} -> s3://{}/{} (s3)", dst, bucket, prefix),
+        MountType::Git { repo, revision } => {
+            format!("{} -> {} at {} (git)", dst, repo.display(), revision)
+        }
+    }
+}
 
-    let (status, _) = tracer.wait().await.unwrap();
-    status.raise_or_exit()
+/// Map the exit status of the command to an exit code the way shells do:
+/// a command killed by a signal exits with 128 plus the signal number.
+fn exit_code(status: ExitStatus) -> i32 {
+    match status {
+        ExitStatus::Exited(code) => code,
+        ExitStatus::Signaled(signal, _) => 128 + signal as i32,
+    }
 }
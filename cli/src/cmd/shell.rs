@@ -0,0 +1,191 @@
+use agentfs_sdk::Filesystem;
+use anyhow::{Context, Result};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const HELP: &str = "\
+Commands:
+  ls [PATH]           List a directory
+  cat PATH            Print a file
+  cd [PATH]           Change the current directory (default: /)
+  pwd                 Print the current directory
+  stat PATH           Show file metadata
+  write PATH TEXT...  Write a line of text to a file, replacing its contents
+  truncate PATH LEN   Shrink or extend a file to LEN bytes
+  rm PATH             Remove a file or empty directory
+  mkdir PATH          Create a directory
+  help                Show this help
+  exit                Leave the shell";
+
+/// Read commands from stdin and run them against an agent filesystem
+///
+/// A prompt is printed only when stdin is a terminal, so scripts piped into
+/// the shell produce just the output of their commands. A failing command
+/// prints an error and the shell continues with the next one.
+pub async fn run_shell(db_path: &Path) -> Result<()> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    let fs = Filesystem::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")?;
+
+    let interactive = std::io::stdin().is_terminal();
+    let mut shell = Shell {
+        fs,
+        cwd: "/".to_string(),
+    };
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        if interactive {
+            print!("agentfs:{}> ", shell.cwd);
+            std::io::stdout().flush()?;
+        }
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if command == "exit" || command == "quit" {
+            break;
+        }
+        if let Err(e) = shell.run(command, args.trim()).await {
+            eprintln!("{}: {}", command, e);
+        }
+    }
+
+    Ok(())
+}
+
+struct Shell {
+    fs: Filesystem,
+    /// Current directory, always absolute and normalized
+    cwd: String,
+}
+
+impl Shell {
+    async fn run(&mut self, command: &str, args: &str) -> Result<()> {
+        match command {
+            "ls" => {
+                let path = self.resolve(args);
+                let entries = self
+                    .fs
+                    .readdir_entries(&path)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("{}: No such directory", path))?;
+                for entry in entries {
+                    if entry.is_directory() {
+                        println!("{}/", entry.name);
+                    } else {
+                        println!("{}", entry.name);
+                    }
+                }
+            }
+            "cat" => {
+                let path = self.resolve(required(args)?);
+                let data = self
+                    .fs
+                    .read_file(&path)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("{}: No such file", path))?;
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&data)?;
+                stdout.flush()?;
+            }
+            "cd" => {
+                let path = self.resolve(if args.is_empty() { "/" } else { args });
+                match self.fs.stat(&path).await? {
+                    Some(stats) if stats.is_directory() => self.cwd = path,
+                    Some(_) => anyhow::bail!("{}: Not a directory", path),
+                    None => anyhow::bail!("{}: No such directory", path),
+                }
+            }
+            "pwd" => println!("{}", self.cwd),
+            "stat" => {
+                let path = self.resolve(required(args)?);
+                let stats = self
+                    .fs
+                    .lstat(&path)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("{}: No such file or directory", path))?;
+                let kind = if stats.is_directory() {
+                    "directory"
+                } else if stats.is_symlink() {
+                    "symlink"
+                } else {
+                    "file"
+                };
+                println!("path: {}", path);
+                println!("type: {}", kind);
+                println!("ino: {}", stats.ino);
+                println!("mode: {:o}", stats.mode & 0o7777);
+                println!("nlink: {}", stats.nlink);
+                println!("uid: {}", stats.uid);
+                println!("gid: {}", stats.gid);
+                println!("size: {}", stats.size);
+                println!("mtime: {}", stats.mtime);
+            }
+            "write" => {
+                let (path, text) = args
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((required(args)?, ""));
+                let path = self.resolve(path);
+                self.fs
+                    .write_file(&path, format!("{}\n", text.trim_start()).as_bytes())
+                    .await?;
+            }
+            "truncate" => {
+                let (path, len) = args
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| anyhow::anyhow!("Missing length"))?;
+                let len = len.trim().parse().context("Invalid length")?;
+                let path = self.resolve(path);
+                self.fs.truncate(&path, len).await?;
+            }
+            "rm" => {
+                let path = self.resolve(required(args)?);
+                self.fs.remove(&path).await?;
+            }
+            "mkdir" => {
+                let path = self.resolve(required(args)?);
+                self.fs.mkdir(&path).await?;
+            }
+            "help" => println!("{}", HELP),
+            _ => anyhow::bail!("Unknown command, type 'help' for a list of commands"),
+        }
+        Ok(())
+    }
+
+    /// Resolve a path against the current directory, handling `.` and `..`
+    fn resolve(&self, path: &str) -> String {
+        let joined = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.cwd, path)
+        };
+        let mut components = Vec::new();
+        for component in joined.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    components.pop();
+                }
+                name => components.push(name),
+            }
+        }
+        format!("/{}", components.join("/"))
+    }
+}
+
+fn required(args: &str) -> Result<&str> {
+    if args.is_empty() {
+        anyhow::bail!("Missing path");
+    }
+    Ok(args)
+}
@@ -28,18 +28,30 @@ mod non_linux {
     }
 }
 
-use agentfs_sdk::AgentFS;
+use agentfs_sdk::{
+    AgentFS, DirEntry, Filesystem, ImportPolicy, IntegrityProblem, KvStore, OrphanAction, Stats,
+    ToolCalls,
+};
 use anyhow::{Context, Result as AnyhowResult};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use cmd::MountConfig;
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing_subscriber::filter::LevelFilter;
 use turso::{Builder, Value};
 
 #[derive(Parser, Debug)]
 #[command(name = "agentfs")]
 #[command(about = "A sandbox for agents that intercepts filesystem operations", long_about = None)]
 struct Args {
+    /// Diagnostic log level (off, error, warn, info, debug, trace)
+    #[arg(long = "log-level", global = true, default_value = "warn")]
+    log_level: LevelFilter,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -55,13 +67,84 @@ enum Commands {
         /// Overwrite existing file if it exists
         #[arg(long)]
         force: bool,
+
+        /// Directory to create after initialization, along with any missing
+        /// parents (repeatable)
+        #[arg(long = "mkdir", value_name = "PATH")]
+        mkdir: Vec<String>,
+    },
+    /// Show how paths resolve against mount specifications
+    ///
+    /// Builds the mount table without running a command and prints which
+    /// mount each path resolves to, using longest-prefix matching.
+    Mounts {
+        /// Mount specifications to check (format: `type=bind,src=<host_path>,dst=<sandbox_path>`)
+        #[arg(long = "check", value_name = "MOUNT_SPEC", required = true, num_args = 1..)]
+        check: Vec<MountConfig>,
+
+        /// Path to resolve (repeatable, default: the destination of each mount)
+        #[arg(long = "path", value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
+    /// Run an interactive shell over an agent filesystem
+    ///
+    /// Reads commands such as `ls`, `cd`, and `cat` from stdin and runs them
+    /// against the filesystem, keeping track of a current directory.
+    Shell {
+        /// Filesystem to use (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+    },
+    /// Check the integrity of an agent filesystem
+    ///
+    /// Reports directory entries, data chunks, symlink targets, and extended
+    /// attributes of missing inodes, symlinks without a target, overlapping
+    /// data chunks, files whose size differs from their data, and orphan
+    /// inodes that are in no directory. Exits with status 1 if problems are
+    /// found and not repaired.
+    Fsck {
+        /// Filesystem to check (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// Fix the problems found, linking orphan inodes into /lost+found
+        #[arg(long)]
+        repair: bool,
+
+        /// Delete orphan inodes instead of linking them into /lost+found
+        #[arg(long, requires = "repair")]
+        delete_orphans: bool,
+    },
+    /// Delete old tool call records
+    ///
+    /// Removes completed tool calls that completed longer ago than the
+    /// retention period. Pending tool calls are always kept.
+    Prune {
+        /// Filesystem to prune (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// How long to keep completed tool calls, as a number followed by
+        /// `s`, `m`, `h`, or `d` (for example `30d`)
+        #[arg(long, value_parser = parse_retention)]
+        retention: Duration,
     },
     /// Filesystem operations
     Fs {
         #[command(subcommand)]
         command: FsCommands,
     },
+    /// Key-value store operations
+    Kv {
+        #[command(subcommand)]
+        command: KvCommands,
+    },
     Run {
+        /// Load mounts, strace, env, and workdir settings from a JSON file
+        /// (command-line flags override the file)
+        #[arg(long = "config", value_name = "FILE")]
+        config: Option<PathBuf>,
+
         /// Mount configuration (format: type=bind,src=<host_path>,dst=<sandbox_path>)
         #[arg(long = "mount", value_name = "MOUNT_SPEC")]
         mounts: Vec<MountConfig>,
@@ -70,6 +153,57 @@ enum Commands {
         #[arg(long = "strace")]
         strace: bool,
 
+        /// Record outbound network connections to a SQLite database
+        #[arg(long = "net-log", value_name = "DB")]
+        net_log: Option<PathBuf>,
+
+        /// Record the bytes written to files in mounts to a SQLite database
+        #[arg(long = "trace-writes", value_name = "DB")]
+        trace_writes: Option<PathBuf>,
+
+        /// Record the command's stdout and stderr to a SQLite database
+        #[arg(long = "capture-output", value_name = "DB")]
+        capture_output: Option<PathBuf>,
+
+        /// Allow outbound connections only to matching destinations
+        /// (format: <addr>[/<prefix>]:<port|*>, IPv6 in brackets)
+        #[arg(long = "allow-connect", value_name = "RULE")]
+        allow_connect: Vec<String>,
+
+        /// Refuse connections to Unix sockets
+        #[arg(long = "deny-unix-sockets")]
+        deny_unix_sockets: bool,
+
+        /// Print the tree of processes started in the sandbox on exit
+        #[arg(long = "process-tree")]
+        process_tree: bool,
+
+        /// Limit the CPU time of each process, in seconds
+        #[arg(long = "max-cpu-seconds", value_name = "SECONDS")]
+        max_cpu_seconds: Option<u64>,
+
+        /// Limit the size of files the command writes, in bytes
+        #[arg(long = "max-file-size", value_name = "BYTES")]
+        max_file_size: Option<u64>,
+
+        /// Limit the number of files each process can have open
+        #[arg(long = "max-open-files", value_name = "COUNT")]
+        max_open_files: Option<u64>,
+
+        /// Fsync files written through bind mounts when they are closed
+        #[arg(long = "sync-on-close")]
+        sync_on_close: bool,
+
+        /// Print the virtual file descriptors of each process to stderr
+        /// when agentfs receives SIGUSR1
+        #[arg(long = "dump-fds-on-signal")]
+        dump_fds_on_signal: bool,
+
+        /// Write a JSON summary of the files the command opened and wrote
+        /// to a file on exit (`-` for stdout)
+        #[arg(long = "summary", value_name = "PATH")]
+        summary: Option<PathBuf>,
+
         /// Command to execute
         command: PathBuf,
 
@@ -77,6 +211,13 @@ enum Commands {
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
+    /// Print a shell completion script
+    ///
+    /// For example, `agentfs completion bash > /etc/bash_completion.d/agentfs`.
+    Completion {
+        /// Shell to generate the script for
+        shell: clap_complete::Shell,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -100,9 +241,165 @@ enum FsCommands {
         /// Path to the file
         path: String,
     },
+    /// Change the permission bits of a file
+    Chmod {
+        /// Filesystem to use (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// Permission bits in octal, such as 644
+        #[arg(value_parser = parse_mode)]
+        mode: u32,
+
+        /// Path to the file
+        path: String,
+    },
+    /// Change the owner and group of a file
+    Chown {
+        /// Filesystem to use (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// New owner as UID[:GID], where an omitted UID or GID is unchanged
+        #[arg(value_parser = parse_owner)]
+        owner: (u32, u32),
+
+        /// Path to the file
+        path: String,
+    },
+    /// Show disk usage of each entry in a directory and the total
+    Du {
+        /// Filesystem to use (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// Path to measure (default: /)
+        #[arg(default_value = "/")]
+        path: String,
+
+        /// Print sizes in KiB, MiB, and GiB
+        #[arg(long = "human")]
+        human: bool,
+    },
+    /// Find files by name, modification time, or size
+    Find {
+        /// Filesystem to use (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// Directory to search (default: /)
+        #[arg(default_value = "/")]
+        path: String,
+
+        /// Only match names matching a glob pattern (`*` and `?`)
+        #[arg(long = "name")]
+        name: Option<String>,
+
+        /// Only match entries modified after a Unix timestamp
+        #[arg(long = "newer-than")]
+        newer_than: Option<i64>,
+
+        /// Only match entries larger than a number of bytes
+        #[arg(long = "larger-than")]
+        larger_than: Option<i64>,
+    },
+    /// Display a directory tree
+    Tree {
+        /// Filesystem to use (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// Directory to display (default: /)
+        #[arg(default_value = "/")]
+        path: String,
+
+        /// Descend at most this many levels below the directory
+        #[arg(long = "max-depth")]
+        max_depth: Option<usize>,
+    },
+    /// Compare two filesystems and list the entries that differ
+    ///
+    /// Files are compared by size and then by content hash.
+    Diff {
+        /// Filesystem to compare from
+        left: PathBuf,
+
+        /// Filesystem to compare to
+        right: PathBuf,
+
+        /// Directory to compare (default: /)
+        #[arg(default_value = "/")]
+        path: String,
+    },
+    /// Merge two filesystems into a new one
+    ///
+    /// Copies `base` and then applies `overlay` on top of it. Directories
+    /// in both are merged, and any other entry in both is a conflict
+    /// resolved by `--strategy`.
+    Merge {
+        /// Filesystem to start from
+        base: PathBuf,
+
+        /// Filesystem to apply on top of the base
+        overlay: PathBuf,
+
+        /// SQLite file to create with the result
+        output: PathBuf,
+
+        /// How to resolve entries that differ between the filesystems
+        #[arg(long = "strategy", value_enum, default_value_t = MergeStrategy::OverlayWins)]
+        strategy: MergeStrategy,
+
+        /// Print the entries the overlay would add or replace without
+        /// creating the output
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
 }
 
-async fn init_database(db_path: &Path, force: bool) -> AnyhowResult<()> {
+#[derive(Subcommand, Debug)]
+enum KvCommands {
+    /// Export the key-value store as JSON
+    ///
+    /// Writes an array of keys with their values and timestamps. Binary
+    /// values are base64-encoded.
+    Export {
+        /// Filesystem to use (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// File to write (default: stdout)
+        output: Option<PathBuf>,
+    },
+    /// Import keys written by `kv export`
+    ///
+    /// Existing keys are replaced unless `--skip-existing` is given.
+    Import {
+        /// Filesystem to use (default: agent.db)
+        #[arg(long = "filesystem", default_value = "agent.db")]
+        filesystem: PathBuf,
+
+        /// File to read (default: stdin)
+        input: Option<PathBuf>,
+
+        /// Keep keys that already exist instead of replacing them
+        #[arg(long = "skip-existing")]
+        skip_existing: bool,
+    },
+}
+
+/// How `fs merge` resolves an entry present in both filesystems
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum MergeStrategy {
+    /// Keep the entry from the overlay
+    OverlayWins,
+    /// Keep the entry from the base
+    BaseWins,
+    /// Stop with an error
+    Fail,
+}
+
+async fn init_database(db_path: &Path, force: bool, dirs: &[String]) -> AnyhowResult<()> {
     // Check if file already exists
     if db_path.exists() && !force {
         anyhow::bail!(
@@ -115,10 +412,18 @@ async fn init_database(db_path: &Path, force: bool) -> AnyhowResult<()> {
 
     // Use the SDK to initialize the database - this ensures consistency
     // with how `agentfs run` initializes the database
-    AgentFS::new(db_path_str)
+    let agentfs = AgentFS::new(db_path_str)
         .await
         .context("Failed to initialize database")?;
 
+    for dir in dirs {
+        agentfs
+            .fs
+            .mkdir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create directory '{}'", dir))?;
+    }
+
     eprintln!("Created agent filesystem: {}", db_path.display());
 
     Ok(())
@@ -258,13 +563,16 @@ async fn cat_filesystem(db_path: &Path, path: &str) -> AnyhowResult<()> {
         }
     }
 
-    let query = format!("SELECT mode FROM fs_inode WHERE ino = {}", current_ino);
+    let query = format!(
+        "SELECT mode, size FROM fs_inode WHERE ino = {}",
+        current_ino
+    );
     let mut rows = conn
         .query(&query, ())
         .await
         .context("Failed to query inode")?;
 
-    if let Some(row) = rows.next().await.context("Failed to fetch row")? {
+    let size = if let Some(row) = rows.next().await.context("Failed to fetch row")? {
         let mode: u32 = row
             .get_value(0)
             .ok()
@@ -280,12 +588,17 @@ async fn cat_filesystem(db_path: &Path, path: &str) -> AnyhowResult<()> {
         } else if mode & S_IFMT != S_IFREG {
             anyhow::bail!("'{}' is not a regular file", path);
         }
+
+        row.get_value(1)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .unwrap_or(0)
     } else {
         anyhow::bail!("File not found: {}", path);
-    }
+    };
 
     let query = format!(
-        "SELECT data FROM fs_data WHERE ino = {} ORDER BY offset",
+        "SELECT offset, data FROM fs_data WHERE ino = {} ORDER BY offset",
         current_ino
     );
 
@@ -298,10 +611,17 @@ async fn cat_filesystem(db_path: &Path, path: &str) -> AnyhowResult<()> {
     let stdout = std::io::stdout();
     let mut handle = stdout.lock();
 
+    // Holes between chunks and past the last one read as zeros
+    let mut written: i64 = 0;
     while let Some(row) = rows.next().await.context("Failed to fetch row")? {
-        let data: Vec<u8> = row
+        let offset = row
             .get_value(0)
             .ok()
+            .and_then(|v| v.as_integer().copied())
+            .ok_or_else(|| anyhow::anyhow!("Invalid chunk offset"))?;
+        let data: Vec<u8> = row
+            .get_value(1)
+            .ok()
             .and_then(|v| {
                 if let Value::Blob(b) = v {
                     Some(b.clone())
@@ -313,26 +633,712 @@ async fn cat_filesystem(db_path: &Path, path: &str) -> AnyhowResult<()> {
             })
             .ok_or_else(|| anyhow::anyhow!("Invalid file data"))?;
 
+        if offset > written {
+            write_zeros(&mut handle, (offset - written) as u64)?;
+            written = offset;
+        }
         handle
             .write_all(&data)
             .context("Failed to write to stdout")?;
+        written += data.len() as i64;
+    }
+    if size > written {
+        write_zeros(&mut handle, (size - written) as u64)?;
+    }
+
+    Ok(())
+}
+
+fn write_zeros(out: &mut impl std::io::Write, len: u64) -> AnyhowResult<()> {
+    std::io::copy(&mut std::io::Read::take(std::io::repeat(0), len), out)
+        .context("Failed to write to stdout")?;
+    Ok(())
+}
+
+/// Parse the permission bits of `fs chmod` in octal
+fn parse_mode(s: &str) -> Result<u32, String> {
+    match u32::from_str_radix(s, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("invalid mode '{}'", s)),
+    }
+}
+
+/// Parse the owner of `fs chown` as `UID[:GID]`
+///
+/// An omitted UID or GID is returned as `u32::MAX`, which leaves it unchanged.
+fn parse_owner(s: &str) -> Result<(u32, u32), String> {
+    let (uid, gid) = s.split_once(':').unwrap_or((s, ""));
+    let id = |id: &str| {
+        if id.is_empty() {
+            return Ok(u32::MAX);
+        }
+        match id.parse::<u32>() {
+            Ok(id) if id != u32::MAX => Ok(id),
+            _ => Err(format!("invalid owner '{}'", s)),
+        }
+    };
+    Ok((id(uid)?, id(gid)?))
+}
+
+async fn chmod_filesystem(db_path: &Path, path: &str, mode: u32) -> AnyhowResult<()> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    let fs = Filesystem::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")?;
+    if fs.stat(path).await?.is_none() {
+        anyhow::bail!("File not found: {}", path);
+    }
+    fs.chmod(path, mode).await
+}
+
+async fn chown_filesystem(db_path: &Path, path: &str, uid: u32, gid: u32) -> AnyhowResult<()> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    let fs = Filesystem::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")?;
+    if fs.stat(path).await?.is_none() {
+        anyhow::bail!("File not found: {}", path);
+    }
+    fs.chown(path, uid, gid).await
+}
+
+async fn du_filesystem(db_path: &Path, path: &str, human: bool) -> AnyhowResult<()> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    let fs = Filesystem::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")?;
+
+    let stats = fs
+        .lstat(path)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("File not found: {}", path))?;
+
+    // Each entry counts its own hard links once, and the total counts every
+    // inode in the tree once, like du(1)
+    if stats.is_directory() {
+        let entries = fs.readdir(path).await?.unwrap_or_default();
+        for name in entries {
+            let entry_path = format!("{}/{}", path.trim_end_matches('/'), name);
+            let size = fs.disk_usage(&entry_path).await?.unwrap_or(0);
+            println!("{}\t{}", format_size(size, human), entry_path);
+        }
+    }
+    let total = fs.disk_usage(path).await?.unwrap_or(0);
+    println!("{}\ttotal", format_size(total, human));
+
+    Ok(())
+}
+
+fn format_size(bytes: u64, human: bool) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+
+    if !human || bytes < 1024 {
+        return if human {
+            format!("{} B", bytes)
+        } else {
+            bytes.to_string()
+        };
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+async fn find_filesystem(
+    db_path: &Path,
+    path: &str,
+    name: Option<&str>,
+    newer_than: Option<i64>,
+    larger_than: Option<i64>,
+) -> AnyhowResult<()> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    let fs = Filesystem::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")?;
+
+    let mut pending = vec![path.trim_end_matches('/').to_string()];
+    if fs.readdir_entries(path).await?.is_none() {
+        anyhow::bail!("Directory not found: {}", path);
+    }
+
+    while let Some(dir) = pending.pop() {
+        let dir_path = if dir.is_empty() { "/" } else { dir.as_str() };
+        let entries = fs.readdir_entries(dir_path).await?.unwrap_or_default();
+        // Visit subdirectories in name order
+        for entry in entries.iter().rev() {
+            if entry.is_directory() {
+                pending.push(format!("{}/{}", dir, entry.name));
+            }
+        }
+        for entry in entries {
+            let entry_path = format!("{}/{}", dir, entry.name);
+            let Some(stats) = fs.lstat(&entry_path).await? else {
+                continue;
+            };
+            let matches = name.is_none_or(|pattern| glob_match(pattern, &entry.name))
+                && newer_than.is_none_or(|ts| stats.mtime > ts)
+                && larger_than.is_none_or(|bytes| stats.size > bytes);
+            if matches {
+                println!("{}", entry_path);
+            }
+        }
     }
 
     Ok(())
 }
 
+async fn tree_filesystem(db_path: &Path, path: &str, max_depth: Option<usize>) -> AnyhowResult<()> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    let fs = Filesystem::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")?;
+
+    let root = path.trim_end_matches('/').to_string();
+    let entries = fs
+        .readdir_entries(path)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Directory not found: {}", path))?;
+    println!("{}", path);
+
+    // Entries still to print: (path, entry, prefix, is last in its directory, depth)
+    let mut pending = Vec::new();
+    let push_children =
+        |pending: &mut Vec<_>, dir: &str, entries: Vec<DirEntry>, prefix: &str, depth| {
+            let count = entries.len();
+            for (i, entry) in entries.into_iter().enumerate().rev() {
+                let entry_path = format!("{}/{}", dir, entry.name);
+                pending.push((entry_path, entry, prefix.to_string(), i + 1 == count, depth));
+            }
+        };
+    if max_depth != Some(0) {
+        push_children(&mut pending, &root, entries, "", 1);
+    }
+
+    while let Some((entry_path, entry, prefix, last, depth)) = pending.pop() {
+        let branch = if last { "└── " } else { "├── " };
+        let label = if entry.is_symlink() {
+            let target = fs.readlink(&entry_path).await?.unwrap_or_default();
+            format!("{} -> {}", entry.name, target)
+        } else if entry.is_directory() {
+            entry.name.clone()
+        } else {
+            let size = fs.lstat(&entry_path).await?.map_or(0, |s| s.size);
+            format!("{} ({} bytes)", entry.name, size)
+        };
+        println!("{}{}{}", prefix, branch, label);
+
+        if entry.is_directory() && max_depth.is_none_or(|max| depth < max) {
+            let children = fs.readdir_entries(&entry_path).await?.unwrap_or_default();
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            push_children(&mut pending, &entry_path, children, &prefix, depth + 1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a filesystem, returning whether it is free of unrepaired problems
+async fn fsck_filesystem(db_path: &Path, repair: bool, delete_orphans: bool) -> AnyhowResult<bool> {
+    let fs = open_filesystem(db_path).await?;
+    let mut problems = Vec::new();
+    if delete_orphans {
+        for ino in fs.recover_orphans(OrphanAction::Delete).await? {
+            problems.push(IntegrityProblem::OrphanInode { ino });
+        }
+    }
+    problems.extend(fs.check(repair).await?);
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if problems.is_empty() {
+        println!("No problems found");
+    } else if repair {
+        println!("{} problems found and repaired", problems.len());
+    } else {
+        println!("{} problems found", problems.len());
+    }
+    Ok(problems.is_empty() || repair)
+}
+
+/// Parse a retention period such as `90s`, `45m`, `12h`, or `30d`
+fn parse_retention(s: &str) -> Result<Duration, String> {
+    let unit_start = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(unit_start);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid retention period '{}'", s))?;
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("invalid retention unit in '{}'", s)),
+    };
+    value
+        .checked_mul(secs)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("retention period '{}' is too long", s))
+}
+
+async fn prune_tool_calls(db_path: &Path, retention: Duration) -> AnyhowResult<()> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    let tools = ToolCalls::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")?;
+    let deleted = tools.prune(retention).await?;
+    println!("Pruned {} tool calls", deleted);
+    Ok(())
+}
+
+async fn open_kv_store(db_path: &Path) -> AnyhowResult<KvStore> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    KvStore::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")
+}
+
+async fn export_kv(db_path: &Path, output: Option<&Path>) -> AnyhowResult<()> {
+    let kv = open_kv_store(db_path).await?;
+    match output {
+        Some(output) => {
+            let file = std::fs::File::create(output)
+                .with_context(|| format!("Failed to create '{}'", output.display()))?;
+            let mut writer = std::io::BufWriter::new(file);
+            let count = kv.export_json(&mut writer).await?;
+            writer.write_all(b"\n")?;
+            writer.flush()?;
+            eprintln!("Exported {} keys", count);
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            kv.export_json(&mut stdout).await?;
+            stdout.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+async fn import_kv(db_path: &Path, input: Option<&Path>, skip_existing: bool) -> AnyhowResult<()> {
+    let kv = open_kv_store(db_path).await?;
+    let policy = if skip_existing {
+        ImportPolicy::Skip
+    } else {
+        ImportPolicy::Overwrite
+    };
+    let count = match input {
+        Some(input) => {
+            let file = std::fs::File::open(input)
+                .with_context(|| format!("Failed to open '{}'", input.display()))?;
+            kv.import_json(std::io::BufReader::new(file), policy)
+                .await?
+        }
+        None => kv.import_json(std::io::stdin().lock(), policy).await?,
+    };
+    println!("Imported {} keys", count);
+    Ok(())
+}
+
+/// Open an existing filesystem database
+async fn open_filesystem(db_path: &Path) -> AnyhowResult<Filesystem> {
+    if !db_path.exists() {
+        anyhow::bail!("Filesystem '{}' does not exist", db_path.display());
+    }
+
+    let db_path_str = db_path.to_str().context("Invalid filesystem path")?;
+    Filesystem::new(db_path_str)
+        .await
+        .context("Failed to open filesystem")
+}
+
+async fn diff_filesystem(left_path: &Path, right_path: &Path, path: &str) -> AnyhowResult<()> {
+    let left = open_filesystem(left_path).await?;
+    let right = open_filesystem(right_path).await?;
+
+    let root = path.trim_end_matches('/').to_string();
+    // Changes as (status, path), where the status is one of `A` (added),
+    // `D` (removed), `M` (modified), and `T` (type changed). An added or
+    // removed directory is reported once, without its contents.
+    let mut changes = Vec::new();
+    let mut pending = vec![root];
+    while let Some(dir) = pending.pop() {
+        let dir_path = if dir.is_empty() { "/" } else { dir.as_str() };
+        let left_entries = left
+            .readdir_lstats(dir_path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Directory not found: {}", dir_path))?;
+        let right_entries = right
+            .readdir_lstats(dir_path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Directory not found: {}", dir_path))?;
+
+        // Both listings are sorted by name, so walk them side by side
+        let mut left_iter = left_entries.into_iter().peekable();
+        let mut right_iter = right_entries.into_iter().peekable();
+        loop {
+            let order = match (left_iter.peek(), right_iter.peek()) {
+                (Some((l, _)), Some((r, _))) => l.cmp(r),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match order {
+                Ordering::Less => {
+                    let (name, _) = left_iter.next().unwrap();
+                    changes.push(('D', format!("{}/{}", dir, name)));
+                }
+                Ordering::Greater => {
+                    let (name, _) = right_iter.next().unwrap();
+                    changes.push(('A', format!("{}/{}", dir, name)));
+                }
+                Ordering::Equal => {
+                    let (name, l) = left_iter.next().unwrap();
+                    let (_, r) = right_iter.next().unwrap();
+                    let entry_path = format!("{}/{}", dir, name);
+                    if l.is_directory() != r.is_directory()
+                        || l.is_file() != r.is_file()
+                        || l.is_symlink() != r.is_symlink()
+                    {
+                        changes.push(('T', entry_path));
+                    } else if l.is_directory() {
+                        pending.push(entry_path);
+                    } else if l.is_symlink() {
+                        if left.readlink(&entry_path).await? != right.readlink(&entry_path).await? {
+                            changes.push(('M', entry_path));
+                        }
+                    } else if l.is_file()
+                        && (l.size != r.size
+                            || left.content_hash(&entry_path).await?
+                                != right.content_hash(&entry_path).await?)
+                    {
+                        changes.push(('M', entry_path));
+                    }
+                }
+            }
+        }
+    }
+
+    changes.sort_by(|a, b| a.1.cmp(&b.1));
+    for (status, entry_path) in &changes {
+        println!("{} {}", status, entry_path);
+    }
+    let count = |status| changes.iter().filter(|(s, _)| *s == status).count();
+    println!(
+        "{} added, {} removed, {} modified, {} type changed",
+        count('A'),
+        count('D'),
+        count('M'),
+        count('T')
+    );
+
+    Ok(())
+}
+
+async fn merge_filesystem(
+    base_path: &Path,
+    overlay_path: &Path,
+    output_path: &Path,
+    strategy: MergeStrategy,
+    dry_run: bool,
+) -> AnyhowResult<()> {
+    let base = open_filesystem(base_path).await?;
+    let overlay = open_filesystem(overlay_path).await?;
+    if output_path.exists() {
+        anyhow::bail!("File '{}' already exists", output_path.display());
+    }
+
+    if dry_run {
+        // Run the same merge against an in-memory copy so conflicts are
+        // detected exactly as in the real merge
+        let output = AgentFS::new(":memory:")
+            .await
+            .context("Failed to create in-memory filesystem")?;
+        merge_tree(&base, &output.fs, MergeStrategy::Fail, false).await?;
+        return merge_tree(&overlay, &output.fs, strategy, true).await;
+    }
+
+    let output_str = output_path.to_str().context("Invalid output path")?;
+    let output = AgentFS::new(output_str)
+        .await
+        .context("Failed to create output filesystem")?;
+    let result = async {
+        // The output starts empty, so copying the base never conflicts
+        merge_tree(&base, &output.fs, MergeStrategy::Fail, false).await?;
+        merge_tree(&overlay, &output.fs, strategy, false).await
+    }
+    .await;
+    if result.is_err() {
+        // Don't leave a half-merged filesystem behind
+        let _ = std::fs::remove_file(output_path);
+        let _ = std::fs::remove_file(format!("{}-wal", output_str));
+    }
+    result
+}
+
+/// Copy every entry of `src` into `dst`, resolving conflicts with `strategy`.
+/// With `report`, each entry added, replaced or kept is printed.
+///
+/// Copied entries keep their mode, owner, times and extended attributes, and
+/// hard links within `src` stay linked in `dst`.
+async fn merge_tree(
+    src: &Filesystem,
+    dst: &Filesystem,
+    strategy: MergeStrategy,
+    report: bool,
+) -> AnyhowResult<()> {
+    // Inodes of `dst` that entries of `src` with several links were copied to
+    let mut linked: HashMap<i64, i64> = HashMap::new();
+    // Directories created by the merge, whose times are set once they are filled
+    let mut created_dirs = Vec::new();
+    let mut pending = vec![String::new()];
+    while let Some(dir) = pending.pop() {
+        let dir_path = if dir.is_empty() { "/" } else { dir.as_str() };
+        let entries = src.readdir_lstats(dir_path).await?.unwrap_or_default();
+        for (name, stats) in entries {
+            let entry_path = format!("{}/{}", dir, name);
+            if let Some(existing) = dst.lstat(&entry_path).await? {
+                if existing.is_directory() && stats.is_directory() {
+                    pending.push(entry_path);
+                    continue;
+                }
+                if same_entry(src, dst, &entry_path, &stats, &existing).await? {
+                    continue;
+                }
+                match strategy {
+                    MergeStrategy::OverlayWins => {
+                        if report {
+                            println!("replace {}", entry_path);
+                        }
+                        remove_tree(dst, &entry_path).await?
+                    }
+                    MergeStrategy::BaseWins => {
+                        if report {
+                            println!("keep {}", entry_path);
+                        }
+                        continue;
+                    }
+                    MergeStrategy::Fail => anyhow::bail!("Conflicting entry: {}", entry_path),
+                }
+            } else if report {
+                println!("add {}", entry_path);
+            }
+
+            if !stats.is_directory() && stats.nlink > 1 {
+                if let Some(&ino) = linked.get(&stats.ino) {
+                    dst.link_inode(ino, &entry_path).await?;
+                    continue;
+                }
+            }
+
+            if stats.is_directory() {
+                dst.mkdir(&entry_path).await?;
+                pending.push(entry_path.clone());
+            } else if stats.is_symlink() {
+                let target = src.readlink(&entry_path).await?.unwrap_or_default();
+                dst.symlink(&target, &entry_path).await?;
+            } else if stats.is_file() {
+                let mut reader = src
+                    .open_reader(&entry_path)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("File not found: {}", entry_path))?;
+                let mut writer = dst.open_writer(&entry_path).await?;
+                tokio::io::copy(&mut reader, &mut writer).await?;
+                writer.shutdown().await?;
+            } else {
+                dst.mknod(&entry_path, stats.mode, stats.rdev).await?;
+            }
+
+            let ino = copy_metadata(src, dst, &entry_path, &stats).await?;
+            if stats.is_directory() {
+                created_dirs.push((ino, stats));
+            } else if stats.nlink > 1 {
+                linked.insert(stats.ino, ino);
+            }
+        }
+    }
+    // Adding entries to a directory changes its times
+    for (ino, stats) in created_dirs {
+        dst.set_times_inode(ino, Some(stats.atime), Some(stats.mtime))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Give the entry copied to `path` in `dst` the mode, owner, times and
+/// extended attributes of the entry at `path` in `src`, returning its inode
+async fn copy_metadata(
+    src: &Filesystem,
+    dst: &Filesystem,
+    path: &str,
+    stats: &Stats,
+) -> AnyhowResult<i64> {
+    let ino = dst
+        .lstat(path)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("File not found: {}", path))?
+        .ino;
+    if !stats.is_symlink() {
+        dst.chmod_inode(ino, stats.mode).await?;
+    }
+    dst.chown_inode(ino, stats.uid, stats.gid).await?;
+    for name in src.listxattr(path).await?.unwrap_or_default() {
+        if let Some(value) = src.getxattr(path, &name).await? {
+            dst.setxattr(path, &name, &value).await?;
+        }
+    }
+    dst.set_times_inode(ino, Some(stats.atime), Some(stats.mtime))
+        .await?;
+    Ok(ino)
+}
+
+/// Whether two non-directory entries at `path` are the same file or the
+/// same symlink, so merging them is not a conflict
+async fn same_entry(
+    src: &Filesystem,
+    dst: &Filesystem,
+    path: &str,
+    src_stats: &Stats,
+    dst_stats: &Stats,
+) -> AnyhowResult<bool> {
+    if src_stats.is_file() && dst_stats.is_file() {
+        Ok(src_stats.size == dst_stats.size
+            && src.content_hash(path).await? == dst.content_hash(path).await?)
+    } else if src_stats.is_symlink() && dst_stats.is_symlink() {
+        Ok(src.readlink(path).await? == dst.readlink(path).await?)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Remove an entry and, for a directory, everything below it
+async fn remove_tree(fs: &Filesystem, path: &str) -> AnyhowResult<()> {
+    // Collect parents before their children, then remove in reverse
+    let mut paths = vec![path.to_string()];
+    let mut i = 0;
+    while i < paths.len() {
+        // Symlinks are removed, not followed
+        if fs.lstat(&paths[i]).await?.is_some_and(|s| s.is_directory()) {
+            let entries = fs.readdir_lstats(&paths[i]).await?.unwrap_or_default();
+            for (name, _) in entries {
+                paths.push(format!("{}/{}", paths[i], name));
+            }
+        }
+        i += 1;
+    }
+    for path in paths.iter().rev() {
+        fs.remove(path).await?;
+    }
+    Ok(())
+}
+
+/// Match a name against a glob pattern where `*` matches any run of
+/// characters and `?` matches a single character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            backtrack = Some((p, n));
+        } else if let Some((star_p, star_n)) = backtrack {
+            p = star_p;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
+    // Diagnostic logs go to stderr, separate from strace output
+    tracing_subscriber::fmt()
+        .with_max_level(args.log_level)
+        .with_writer(std::io::stderr)
+        .init();
+
     match args.command {
-        Commands::Init { filename, force } => {
-            if let Err(e) = init_database(&filename, force).await {
+        Commands::Init {
+            filename,
+            force,
+            mkdir,
+        } => {
+            if let Err(e) = init_database(&filename, force, &mkdir).await {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
             std::process::exit(0);
         }
+        Commands::Mounts { check, paths } => {
+            let code = cmd::handle_mounts_command(check, paths).await;
+            std::process::exit(code);
+        }
+        Commands::Shell { filesystem } => {
+            let code = cmd::handle_shell_command(&filesystem).await;
+            std::process::exit(code);
+        }
+        Commands::Fsck {
+            filesystem,
+            repair,
+            delete_orphans,
+        } => match fsck_filesystem(&filesystem, repair, delete_orphans).await {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Prune {
+            filesystem,
+            retention,
+        } => {
+            if let Err(e) = prune_tool_calls(&filesystem, retention).await {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
         Commands::Fs { command } => match command {
             FsCommands::Ls { filesystem, path } => {
                 if let Err(e) = ls_filesystem(&filesystem, &path).await {
@@ -348,14 +1354,152 @@ async fn main() {
                 }
                 std::process::exit(0);
             }
+            FsCommands::Chmod {
+                filesystem,
+                mode,
+                path,
+            } => {
+                if let Err(e) = chmod_filesystem(&filesystem, &path, mode).await {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
+            FsCommands::Chown {
+                filesystem,
+                owner: (uid, gid),
+                path,
+            } => {
+                if let Err(e) = chown_filesystem(&filesystem, &path, uid, gid).await {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
+            FsCommands::Du {
+                filesystem,
+                path,
+                human,
+            } => {
+                if let Err(e) = du_filesystem(&filesystem, &path, human).await {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
+            FsCommands::Tree {
+                filesystem,
+                path,
+                max_depth,
+            } => {
+                if let Err(e) = tree_filesystem(&filesystem, &path, max_depth).await {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
+            FsCommands::Diff { left, right, path } => {
+                if let Err(e) = diff_filesystem(&left, &right, &path).await {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
+            FsCommands::Merge {
+                base,
+                overlay,
+                output,
+                strategy,
+                dry_run,
+            } => {
+                if let Err(e) = merge_filesystem(&base, &overlay, &output, strategy, dry_run).await
+                {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
+            FsCommands::Find {
+                filesystem,
+                path,
+                name,
+                newer_than,
+                larger_than,
+            } => {
+                if let Err(e) =
+                    find_filesystem(&filesystem, &path, name.as_deref(), newer_than, larger_than)
+                        .await
+                {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
         },
+        Commands::Kv { command } => {
+            let result = match command {
+                KvCommands::Export { filesystem, output } => {
+                    export_kv(&filesystem, output.as_deref()).await
+                }
+                KvCommands::Import {
+                    filesystem,
+                    input,
+                    skip_existing,
+                } => import_kv(&filesystem, input.as_deref(), skip_existing).await,
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
         Commands::Run {
+            config,
             mounts,
             strace,
+            net_log,
+            trace_writes,
+            capture_output,
+            allow_connect,
+            deny_unix_sockets,
+            process_tree,
+            max_cpu_seconds,
+            max_file_size,
+            max_open_files,
+            sync_on_close,
+            dump_fds_on_signal,
+            summary,
             command,
             args,
         } => {
-            cmd::handle_run_command(mounts, strace, command, args).await;
+            let code = cmd::handle_run_command(cmd::RunOptions {
+                config,
+                mounts,
+                strace,
+                net_log,
+                trace_writes,
+                capture_output,
+                allow_connect,
+                deny_unix_sockets,
+                process_tree,
+                max_cpu_seconds,
+                max_file_size,
+                max_open_files,
+                sync_on_close,
+                dump_fds_on_signal,
+                summary,
+                command,
+                args,
+            })
+            .await;
+            std::process::exit(code);
+        }
+        Commands::Completion { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Args::command(),
+                "agentfs",
+                &mut std::io::stdout(),
+            );
         }
     }
 }
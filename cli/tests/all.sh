@@ -3,7 +3,24 @@
 DIR="$(dirname "$0")"
 
 "$DIR/test-init.sh"
+"$DIR/test-completion.sh"
 "$DIR/ls.sh"
+"$DIR/test-chmod.sh"
+"$DIR/test-du.sh"
+"$DIR/test-find.sh"
+"$DIR/test-tree.sh"
+"$DIR/test-diff.sh"
+"$DIR/test-merge.sh"
+"$DIR/test-fsck.sh"
+"$DIR/test-prune.sh"
+"$DIR/test-kv.sh"
+"$DIR/test-shell.sh"
 "$DIR/test-syscalls.sh"
 "$DIR/test-mount.sh"
+"$DIR/test-mounts.sh"
 "$DIR/test-run-bash.sh"
+"$DIR/test-exit-code.sh"
+"$DIR/test-run-summary.sh"
+"$DIR/test-log-level.sh"
+"$DIR/test-unix-socket.sh"
+"$DIR/test-allow-connect.sh"
@@ -28,6 +28,7 @@ int main(int argc, char *argv[]) {
         {"fstat", test_fstat},
         {"lstat", test_lstat},
         {"getdents64", test_getdents64},
+        {"renameat2", test_renameat2},
     };
 
     int num_tests = sizeof(tests) / sizeof(tests[0]);
@@ -0,0 +1,70 @@
+#define _GNU_SOURCE
+#include "test-common.h"
+#include <fcntl.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#ifndef RENAME_NOREPLACE
+#define RENAME_NOREPLACE (1 << 0)
+#endif
+
+static int write_file(const char *path, const char *data) {
+    int fd = open(path, O_CREAT | O_WRONLY | O_TRUNC, 0644);
+    if (fd < 0) {
+        return -1;
+    }
+    ssize_t len = (ssize_t)strlen(data);
+    ssize_t n = write(fd, data, len);
+    close(fd);
+    return n == len ? 0 : -1;
+}
+
+static int read_file(const char *path, char *buf, size_t size) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return -1;
+    }
+    ssize_t n = read(fd, buf, size - 1);
+    close(fd);
+    if (n < 0) {
+        return -1;
+    }
+    buf[n] = '\0';
+    return 0;
+}
+
+int test_renameat2(const char *base_path) {
+    char tmp_path[512], free_path[512], taken_path[512], buf[64];
+    int result;
+
+    snprintf(tmp_path, sizeof(tmp_path), "%s/rename_tmp.txt", base_path);
+    snprintf(free_path, sizeof(free_path), "%s/rename_free.txt", base_path);
+    snprintf(taken_path, sizeof(taken_path), "%s/rename_taken.txt", base_path);
+    unlink(free_path);
+
+    TEST_ASSERT_ERRNO(write_file(tmp_path, "new") == 0, "creating source should succeed");
+    TEST_ASSERT_ERRNO(write_file(taken_path, "old") == 0, "creating destination should succeed");
+
+    /* Test 1: RENAME_NOREPLACE to a free name succeeds */
+    result = syscall(SYS_renameat2, AT_FDCWD, tmp_path, AT_FDCWD, free_path, RENAME_NOREPLACE);
+    TEST_ASSERT_ERRNO(result == 0, "renameat2 to a free name should succeed");
+    TEST_ASSERT(access(tmp_path, F_OK) < 0, "source should be gone after rename");
+    TEST_ASSERT_ERRNO(read_file(free_path, buf, sizeof(buf)) == 0, "reading renamed file should succeed");
+    TEST_ASSERT(strcmp(buf, "new") == 0, "renamed file should keep its data");
+
+    /* Test 2: RENAME_NOREPLACE to an existing name fails with EEXIST */
+    result = syscall(SYS_renameat2, AT_FDCWD, free_path, AT_FDCWD, taken_path, RENAME_NOREPLACE);
+    TEST_ASSERT(result < 0 && errno == EEXIST, "renameat2 to an existing name should fail with EEXIST");
+    TEST_ASSERT_ERRNO(read_file(taken_path, buf, sizeof(buf)) == 0, "reading destination should succeed");
+    TEST_ASSERT(strcmp(buf, "old") == 0, "destination should not be clobbered");
+    TEST_ASSERT(access(free_path, F_OK) == 0, "source should still exist");
+
+    /* Test 3: Without flags, the destination is replaced */
+    result = syscall(SYS_renameat2, AT_FDCWD, free_path, AT_FDCWD, taken_path, 0);
+    TEST_ASSERT_ERRNO(result == 0, "renameat2 without flags should succeed");
+    TEST_ASSERT_ERRNO(read_file(taken_path, buf, sizeof(buf)) == 0, "reading destination should succeed");
+    TEST_ASSERT(strcmp(buf, "new") == 0, "destination should be replaced");
+
+    unlink(taken_path);
+    return 0;
+}
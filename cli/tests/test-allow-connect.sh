@@ -0,0 +1,46 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST allow-connect refuses UDP datagrams... "
+
+BIN_DIR=$(mktemp -d)
+cc -Wall -O2 -o "$BIN_DIR/test_udp_sendto" tests/test_udp_sendto.c
+
+cleanup() {
+    rm -rf "$BIN_DIR"
+}
+
+# Datagrams to an allowed destination are sent, and a sendmmsg batch stops
+# at the first datagram to another destination
+if ! output=$(cargo run -- run --allow-connect 127.0.0.1:9 "$BIN_DIR/test_udp_sendto" 9 allowed 2>&1); then
+    echo "FAILED"
+    echo "Output was: $output"
+    cleanup
+    exit 1
+fi
+
+echo "$output" | grep -q "UDP sendto test passed!" || {
+    echo "FAILED: 'UDP sendto test passed!' not found"
+    echo "Output was: $output"
+    cleanup
+    exit 1
+}
+
+# Datagrams to any other destination fail with ECONNREFUSED
+if ! output=$(cargo run -- run --allow-connect 127.0.0.1:9 "$BIN_DIR/test_udp_sendto" 10 refused 2>&1); then
+    echo "FAILED"
+    echo "Output was: $output"
+    cleanup
+    exit 1
+fi
+
+echo "$output" | grep -q "UDP sendto test passed!" || {
+    echo "FAILED: 'UDP sendto test passed!' not found"
+    echo "Output was: $output"
+    cleanup
+    exit 1
+}
+
+cleanup
+
+echo "OK"
@@ -0,0 +1,57 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST chmod and chown... "
+
+if ! command -v sqlite3 > /dev/null; then
+    echo "SKIPPED (sqlite3 not found)"
+    exit 0
+fi
+
+TEST_DIR=$(mktemp -d)
+TEST_DB="$TEST_DIR/agent.db"
+
+cargo run -- init "$TEST_DB" > /dev/null 2>&1
+cargo run -- shell --filesystem "$TEST_DB" > /dev/null 2>&1 <<'SCRIPT'
+write readme.txt hello
+SCRIPT
+
+inode() {
+    sqlite3 "$TEST_DB" "SELECT printf('%o %d %d', mode, uid, gid) FROM fs_inode WHERE ino = (SELECT ino FROM fs_dentry WHERE name = 'readme.txt')"
+}
+
+cargo run -- fs chmod --filesystem "$TEST_DB" 600 /readme.txt > /dev/null 2>&1
+cargo run -- fs chown --filesystem "$TEST_DB" 1000:100 /readme.txt > /dev/null 2>&1
+output=$(inode)
+if [ "$output" != "100600 1000 100" ]; then
+    echo "FAILED: expected the mode and owner to change"
+    echo "Got: $output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+# An omitted ID is left unchanged
+cargo run -- fs chown --filesystem "$TEST_DB" :200 /readme.txt > /dev/null 2>&1
+output=$(inode)
+if [ "$output" != "100600 1000 200" ]; then
+    echo "FAILED: expected only the group to change"
+    echo "Got: $output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+if cargo run -- fs chmod --filesystem "$TEST_DB" 999 /readme.txt > /dev/null 2>&1; then
+    echo "FAILED: chmod should reject a mode that is not octal"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+if cargo run -- fs chown --filesystem "$TEST_DB" 0 /missing.txt > /dev/null 2>&1; then
+    echo "FAILED: chown should fail on a missing file"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
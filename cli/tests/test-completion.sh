@@ -0,0 +1,22 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST completion... "
+
+output=$(cargo run -- completion bash 2>/dev/null)
+
+for subcommand in init run fs; do
+    if ! echo "$output" | grep -qw "$subcommand"; then
+        echo "FAILED: subcommand '$subcommand' missing from bash completion"
+        echo "Output was: $output"
+        exit 1
+    fi
+done
+
+# Unknown shells are rejected
+if cargo run -- completion nosuchshell > /dev/null 2>&1; then
+    echo "FAILED: completion should fail for an unknown shell"
+    exit 1
+fi
+
+echo "OK"
@@ -0,0 +1,50 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST fs diff... "
+
+TEST_DIR=$(mktemp -d)
+LEFT="$TEST_DIR/left.db"
+RIGHT="$TEST_DIR/right.db"
+
+for DB in "$LEFT" "$RIGHT"; do
+    cargo run -- init "$DB" > /dev/null 2>&1
+    cargo run -- shell --filesystem "$DB" > /dev/null 2>&1 <<'SCRIPT'
+mkdir notes
+write notes/todo.txt buy milk
+write notes/done.txt call bob
+write readme.txt hello
+SCRIPT
+done
+
+cargo run -- shell --filesystem "$RIGHT" > /dev/null 2>&1 <<'SCRIPT'
+write notes/todo.txt buy eggs
+write notes/new.txt fresh
+SCRIPT
+
+output=$(cargo run -- fs diff "$LEFT" "$RIGHT" 2>/dev/null)
+expected="A /notes/new.txt
+M /notes/todo.txt
+1 added, 0 removed, 1 modified, 0 type changed"
+if [ "$output" != "$expected" ]; then
+    echo "FAILED"
+    echo "Expected:"
+    echo "$expected"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+output=$(cargo run -- fs diff "$LEFT" "$LEFT" 2>/dev/null)
+if [ "$output" != "0 added, 0 removed, 0 modified, 0 type changed" ]; then
+    echo "FAILED: a filesystem should not differ from itself"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
@@ -0,0 +1,40 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST fs du... "
+
+TEST_DIR=$(mktemp -d)
+DB="$TEST_DIR/agent.db"
+
+cargo run -- init "$DB" > /dev/null 2>&1
+cargo run -- run --mount type=sqlite,src="$DB",dst=/agent -- /bin/sh -c \
+    'head -c 3145728 /dev/zero > /agent/big && head -c 100 /dev/zero > /agent/one && head -c 1536 /dev/zero > /agent/small' \
+    > /dev/null 2>&1
+
+output=$(cargo run -- fs du --filesystem "$DB" 2>/dev/null)
+expected=$(printf '3145728\t/big\n100\t/one\n1536\t/small\n3147364\ttotal')
+if [ "$output" != "$expected" ]; then
+    echo "FAILED"
+    echo "Expected:"
+    echo "$expected"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+output=$(cargo run -- fs du --filesystem "$DB" --human / 2>/dev/null)
+expected=$(printf '3.0 MiB\t/big\n100 B\t/one\n1.5 KiB\t/small\n3.0 MiB\ttotal')
+if [ "$output" != "$expected" ]; then
+    echo "FAILED: human-readable sizes"
+    echo "Expected:"
+    echo "$expected"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
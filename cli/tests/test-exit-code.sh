@@ -0,0 +1,38 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST run exit code... "
+
+TEST_DB="test_agent.db"
+
+# Clean up any existing test database
+rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm"
+
+# Initialize the database using agentfs init
+cargo run -- init "$TEST_DB" > /dev/null 2>&1
+
+cleanup() {
+    rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm"
+}
+
+# A command that exits non-zero makes `agentfs run` exit with the same code
+status=0
+cargo run -- run --mount type=sqlite,src="$TEST_DB",dst=/agent /bin/sh -c 'exit 42' > /dev/null 2>&1 || status=$?
+if [ "$status" -ne 42 ]; then
+    echo "FAILED: expected exit code 42, got $status"
+    cleanup
+    exit 1
+fi
+
+# A command killed by a signal exits with 128 plus the signal number
+status=0
+cargo run -- run --mount type=sqlite,src="$TEST_DB",dst=/agent /bin/sh -c 'kill -TERM $$' > /dev/null 2>&1 || status=$?
+if [ "$status" -ne 143 ]; then
+    echo "FAILED: expected exit code 143, got $status"
+    cleanup
+    exit 1
+fi
+
+cleanup
+
+echo "OK"
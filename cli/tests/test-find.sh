@@ -0,0 +1,36 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST fs find... "
+
+TEST_DIR=$(mktemp -d)
+DB="$TEST_DIR/agent.db"
+
+cargo run -- init "$DB" > /dev/null 2>&1
+cargo run -- run --mount type=sqlite,src="$DB",dst=/agent -- /bin/sh -c \
+    'head -c 2000 /dev/zero > /agent/app.log && head -c 10 /dev/zero > /agent/debug.log && head -c 5000 /dev/zero > /agent/notes.txt' \
+    > /dev/null 2>&1
+
+check() {
+    expected="$1"
+    shift
+    output=$(cargo run -- fs find --filesystem "$DB" "$@" 2>/dev/null)
+    if [ "$output" != "$expected" ]; then
+        echo "FAILED: fs find $*"
+        echo "Expected:"
+        echo "$expected"
+        echo "Got:"
+        echo "$output"
+        rm -rf "$TEST_DIR"
+        exit 1
+    fi
+}
+
+check "$(printf '/app.log\n/debug.log')" --name '*.log'
+check "$(printf '/app.log\n/notes.txt')" --larger-than 1000
+check "/app.log" --name '*.log' --larger-than 1000
+check "" --newer-than 4000000000
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
@@ -0,0 +1,71 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST fsck... "
+
+if ! command -v sqlite3 > /dev/null; then
+    echo "SKIPPED (sqlite3 not found)"
+    exit 0
+fi
+
+TEST_DIR=$(mktemp -d)
+TEST_DB="$TEST_DIR/agent.db"
+
+cargo run -- init "$TEST_DB" > /dev/null 2>&1
+cargo run -- shell --filesystem "$TEST_DB" > /dev/null 2>&1 <<'SCRIPT'
+write readme.txt hello
+SCRIPT
+
+output=$(cargo run -- fsck --filesystem "$TEST_DB" 2>/dev/null)
+if [ "$output" != "No problems found" ]; then
+    echo "FAILED: expected a healthy filesystem"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+# A file may end in a hole, which reads as zeros
+sqlite3 "$TEST_DB" "UPDATE fs_inode SET size = size + 3 WHERE ino = (SELECT ino FROM fs_dentry WHERE name = 'readme.txt')"
+
+output=$(cargo run -- fs cat --filesystem "$TEST_DB" /readme.txt 2>/dev/null | od -An -c | tr -s ' ')
+if [ "$output" != " h e l l o \n \0 \0 \0" ]; then
+    echo "FAILED: expected a hole to read as zeros"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+output=$(cargo run -- fsck --filesystem "$TEST_DB" 2>/dev/null)
+if [ "$output" != "No problems found" ]; then
+    echo "FAILED: expected a file ending in a hole to be healthy"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+# Inject a directory entry that refers to a missing inode
+sqlite3 "$TEST_DB" "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES ('ghost', 1, 999, 8)"
+
+if output=$(cargo run -- fsck --filesystem "$TEST_DB" 2>/dev/null); then
+    echo "FAILED: fsck should exit with an error on a dangling entry"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+expected="dangling directory entry 'ghost' in inode 1 refers to inode 999
+1 problems found"
+if [ "$output" != "$expected" ]; then
+    echo "FAILED"
+    echo "Expected:"
+    echo "$expected"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
@@ -53,6 +53,24 @@ echo "$output" | grep -q "Created agent filesystem: agent.db" || {
     exit 1
 }
 
+# Test: init --mkdir creates the given directories
+if ! output=$(cargo run -- init --force --mkdir /work --mkdir /tmp/cache 2>&1); then
+    echo "FAILED: init --mkdir command failed"
+    echo "Output was: $output"
+    rm -f agent.db agent.db-shm agent.db-wal
+    exit 1
+fi
+
+output=$(cargo run -- fs ls / 2>&1)
+for dir in work tmp tmp/cache; do
+    echo "$output" | grep -q "^d $dir$" || {
+        echo "FAILED: Expected directory /$dir not found"
+        echo "Output was: $output"
+        rm -f agent.db agent.db-shm agent.db-wal
+        exit 1
+    }
+done
+
 # Cleanup
 rm -f agent.db agent.db-shm agent.db-wal
 
@@ -0,0 +1,46 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST kv export/import... "
+
+TEST_DIR=$(mktemp -d)
+SOURCE_DB="$TEST_DIR/source.db"
+TARGET_DB="$TEST_DIR/target.db"
+
+cargo run -- init "$SOURCE_DB" > /dev/null 2>&1
+cargo run -- init "$TARGET_DB" > /dev/null 2>&1
+
+fail() {
+    echo "FAILED: $1"
+    rm -rf "$TEST_DIR"
+    exit 1
+}
+
+cat > "$TEST_DIR/keys.json" <<'JSON'
+[
+  {"key": "blob", "bytes": "AJ//Cg==", "created_at": 1000, "updated_at": 2000},
+  {"key": "config", "value": {"theme": "dark"}, "created_at": 1000, "updated_at": 3000}
+]
+JSON
+
+output=$(cargo run -- kv import --filesystem "$SOURCE_DB" "$TEST_DIR/keys.json" 2>/dev/null)
+[ "$output" = "Imported 2 keys" ] || fail "expected 2 keys to be imported, got: $output"
+
+# A round trip through export and import keeps every key as it was
+cargo run -- kv export --filesystem "$SOURCE_DB" > "$TEST_DIR/source.json" 2>/dev/null
+cargo run -- kv import --filesystem "$TARGET_DB" < "$TEST_DIR/source.json" > /dev/null 2>&1
+cargo run -- kv export --filesystem "$TARGET_DB" "$TEST_DIR/target.json" > /dev/null 2>&1
+cmp -s "$TEST_DIR/source.json" "$TEST_DIR/target.json" || fail "exports of source and target differ"
+grep -q '"bytes": "AJ//Cg=="' "$TEST_DIR/target.json" || fail "binary value was not base64-encoded"
+
+# Existing keys are kept with --skip-existing
+output=$(cargo run -- kv import --filesystem "$TARGET_DB" --skip-existing "$TEST_DIR/source.json" 2>/dev/null)
+[ "$output" = "Imported 0 keys" ] || fail "expected existing keys to be skipped, got: $output"
+
+if cargo run -- kv import --filesystem "$TARGET_DB" "$TEST_DIR/missing.json" > /dev/null 2>&1; then
+    fail "import should fail for a missing file"
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
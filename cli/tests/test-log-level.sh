@@ -0,0 +1,36 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST log level... "
+
+TEST_DB="test_log_level.db"
+
+rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm"
+
+cargo run -- init "$TEST_DB" > /dev/null 2>&1
+
+# Debug logging should emit events inside the per-syscall span for openat
+output=$(cargo run -- run --log-level debug --mount type=sqlite,src="$TEST_DB",dst=/agent /bin/bash -c '
+echo "hello" > /agent/hello.txt
+' 2>&1)
+
+echo "$output" | grep "syscall{" | grep -q "openat" || {
+    echo "FAILED: debug logging did not produce openat span events"
+    echo "$output"
+    rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm"
+    exit 1
+}
+
+# The default log level should not emit debug events
+output=$(cargo run -- run --mount type=sqlite,src="$TEST_DB",dst=/agent /bin/cat /agent/hello.txt 2>&1)
+
+if echo "$output" | grep -q "syscall{"; then
+    echo "FAILED: debug events emitted without --log-level debug"
+    echo "$output"
+    rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm"
+    exit 1
+fi
+
+rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm"
+
+echo "OK"
@@ -0,0 +1,100 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST fs merge... "
+
+TEST_DIR=$(mktemp -d)
+BASE="$TEST_DIR/base.db"
+OVERLAY="$TEST_DIR/overlay.db"
+
+fail() {
+    echo "FAILED: $1"
+    rm -rf "$TEST_DIR"
+    exit 1
+}
+
+cargo run -- init "$BASE" > /dev/null 2>&1
+cargo run -- shell --filesystem "$BASE" > /dev/null 2>&1 <<'SCRIPT'
+mkdir src
+write src/shared.txt from base
+write src/base.txt base only
+SCRIPT
+
+cargo run -- init "$OVERLAY" > /dev/null 2>&1
+cargo run -- shell --filesystem "$OVERLAY" > /dev/null 2>&1 <<'SCRIPT'
+mkdir src
+write src/shared.txt from overlay
+write src/overlay.txt overlay only
+SCRIPT
+
+for strategy in overlay-wins base-wins; do
+    OUT="$TEST_DIR/$strategy.db"
+    cargo run -- fs merge --strategy "$strategy" "$BASE" "$OVERLAY" "$OUT" > /dev/null 2>&1 \
+        || fail "merge with $strategy failed"
+
+    # Directories merge, so entries only on one side are kept
+    for file in base.txt overlay.txt; do
+        cargo run -- fs cat --filesystem "$OUT" "/src/$file" > /dev/null 2>&1 \
+            || fail "/src/$file missing after merge with $strategy"
+    done
+
+    output=$(cargo run -- fs cat --filesystem "$OUT" /src/shared.txt 2>/dev/null)
+    case "$strategy" in
+        overlay-wins) expected="from overlay" ;;
+        base-wins) expected="from base" ;;
+    esac
+    if [ "$output" != "$expected" ]; then
+        fail "with $strategy, expected '$expected' but got '$output'"
+    fi
+done
+
+OUT="$TEST_DIR/fail.db"
+if cargo run -- fs merge --strategy fail "$BASE" "$OVERLAY" "$OUT" > /dev/null 2>&1; then
+    fail "merge with fail should stop at the conflicting file"
+fi
+if [ -e "$OUT" ]; then
+    fail "a failed merge should not leave the output behind"
+fi
+
+# A dry run prints the planned changes and creates nothing
+output=$(cargo run -- fs merge --dry-run "$BASE" "$OVERLAY" "$OUT" 2>/dev/null) \
+    || fail "dry-run merge failed"
+if [ -e "$OUT" ]; then
+    fail "a dry-run merge should not create the output"
+fi
+echo "$output" | grep -qx "replace /src/shared.txt" \
+    || fail "dry run should report replacing /src/shared.txt, got '$output'"
+echo "$output" | grep -qx "add /src/overlay.txt" \
+    || fail "dry run should report adding /src/overlay.txt, got '$output'"
+if echo "$output" | grep -q "base.txt"; then
+    fail "dry run should not report entries only in the base, got '$output'"
+fi
+output=$(cargo run -- fs merge --dry-run --strategy base-wins "$BASE" "$OVERLAY" "$OUT" 2>/dev/null)
+echo "$output" | grep -qx "keep /src/shared.txt" \
+    || fail "dry run with base-wins should report keeping /src/shared.txt, got '$output'"
+if cargo run -- fs merge --dry-run --strategy fail "$BASE" "$OVERLAY" "$OUT" > /dev/null 2>&1; then
+    fail "dry-run merge with fail should report the conflict"
+fi
+
+# Identical files are not conflicts
+cargo run -- fs merge --strategy fail "$BASE" "$BASE" "$OUT" > /dev/null 2>&1 \
+    || fail "merging a filesystem with itself should not conflict"
+
+# Copied files keep their mode and owner
+cargo run -- shell --filesystem "$OVERLAY" > /dev/null 2>&1 <<'SCRIPT'
+write src/tool.sh echo hello
+SCRIPT
+cargo run -- fs chmod --filesystem "$OVERLAY" 755 /src/tool.sh > /dev/null 2>&1
+cargo run -- fs chown --filesystem "$OVERLAY" 1000:100 /src/tool.sh > /dev/null 2>&1
+OUT="$TEST_DIR/metadata.db"
+cargo run -- fs merge "$BASE" "$OVERLAY" "$OUT" > /dev/null 2>&1 \
+    || fail "merge of an executable failed"
+output=$(echo "stat src/tool.sh" | cargo run -- shell --filesystem "$OUT" 2>/dev/null)
+for field in "mode: 755" "uid: 1000" "gid: 100"; do
+    echo "$output" | grep -qx "$field" \
+        || fail "expected '$field' after merge, got '$output'"
+done
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
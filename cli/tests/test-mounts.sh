@@ -0,0 +1,48 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST mounts --check... "
+
+TEST_DIR=$(mktemp -d)
+mkdir -p "$TEST_DIR/agent" "$TEST_DIR/special"
+
+output=$(cargo run -- mounts \
+    --check type=bind,src="$TEST_DIR/agent",dst=/agent type=bind,src="$TEST_DIR/special",dst=/agent/special \
+    --path /agent/special/file --path /agent/other --path /etc/passwd 2>/dev/null)
+
+expected="Mount table (longest prefix first):
+ - /agent/special -> $TEST_DIR/special (host)
+ - /agent -> $TEST_DIR/agent (host)
+
+Resolution:
+/agent/special/file
+  mount: /agent/special -> $TEST_DIR/special (host)
+  path:  $TEST_DIR/special/file
+/agent/other
+  mount: /agent -> $TEST_DIR/agent (host)
+  path:  $TEST_DIR/agent/other
+/etc/passwd
+  not mounted, passed through to the host"
+
+if [ "$output" != "$expected" ]; then
+    echo "FAILED"
+    echo "Expected:"
+    echo "$expected"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+# Duplicate destinations are reported as errors
+if cargo run -- mounts --check type=bind,src="$TEST_DIR/agent",dst=/agent type=mem,dst=/agent 2>&1 | grep -q "already mounted"; then
+    :  # Expected to fail with this error
+else
+    echo "FAILED: Duplicate destination should produce error"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
@@ -0,0 +1,47 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST prune... "
+
+if ! command -v sqlite3 > /dev/null; then
+    echo "SKIPPED (sqlite3 not found)"
+    exit 0
+fi
+
+TEST_DIR=$(mktemp -d)
+TEST_DB="$TEST_DIR/agent.db"
+
+cargo run -- init "$TEST_DB" > /dev/null 2>&1
+
+# One tool call from 1970, one from now, and one still pending
+sqlite3 "$TEST_DB" "INSERT INTO tool_calls (name, status, started_at, completed_at, duration_ms) VALUES
+    ('old', 'success', 1000, 1001, 1000),
+    ('recent', 'error', strftime('%s', 'now'), strftime('%s', 'now'), 0)"
+sqlite3 "$TEST_DB" "INSERT INTO tool_calls (name, status, started_at) VALUES ('pending', 'pending', 1000)"
+
+output=$(cargo run -- prune --filesystem "$TEST_DB" --retention 30d 2>/dev/null)
+if [ "$output" != "Pruned 1 tool calls" ]; then
+    echo "FAILED: expected one tool call to be pruned"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+remaining=$(sqlite3 "$TEST_DB" "SELECT name FROM tool_calls ORDER BY id" | tr '\n' ' ')
+if [ "$remaining" != "recent pending " ]; then
+    echo "FAILED: expected the recent and pending tool calls to remain"
+    echo "Got: $remaining"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+if cargo run -- prune --filesystem "$TEST_DB" --retention 30x > /dev/null 2>&1; then
+    echo "FAILED: prune should reject an invalid retention period"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
@@ -0,0 +1,49 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST run summary... "
+
+TEST_DB="test_agent.db"
+SUMMARY="test_summary.json"
+
+# Clean up any existing test database
+rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm" "$SUMMARY"
+
+# Initialize the database using agentfs init
+cargo run -- init "$TEST_DB" > /dev/null 2>&1
+
+cleanup() {
+    rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm" "$SUMMARY"
+}
+
+fail() {
+    echo "FAILED: $1"
+    cat "$SUMMARY"
+    cleanup
+    exit 1
+}
+
+# Write two files in the mount and exit non-zero
+status=0
+cargo run -- run --summary "$SUMMARY" --mount type=sqlite,src="$TEST_DB",dst=/agent \
+    /bin/sh -c 'echo hello > /agent/a; echo hi > /agent/b; exit 3' > /dev/null 2>&1 || status=$?
+if [ "$status" -ne 3 ]; then
+    echo "FAILED: expected exit code 3, got $status"
+    cleanup
+    exit 1
+fi
+
+grep -q '"exit_code": 3' "$SUMMARY" || fail "exit code missing from summary"
+grep -q '"files_written": 2' "$SUMMARY" || fail "expected 2 files written"
+grep -q '"/agent": 9' "$SUMMARY" || fail "expected 9 bytes written to /agent"
+grep -q '"files_opened": [1-9]' "$SUMMARY" || fail "expected files to be opened"
+grep -q '"peak_fds": [1-9]' "$SUMMARY" || fail "expected open file descriptors"
+
+# `-` writes the summary to stdout
+OUTPUT=$(cargo run -- run --summary - --mount type=sqlite,src="$TEST_DB",dst=/agent \
+    /bin/true 2>/dev/null)
+echo "$OUTPUT" | grep -q '"exit_code": 0' || fail "expected summary on stdout"
+
+cleanup
+
+echo "OK"
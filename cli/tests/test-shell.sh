@@ -0,0 +1,58 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST shell... "
+
+TEST_DIR=$(mktemp -d)
+DB="$TEST_DIR/agent.db"
+
+cargo run -- init "$DB" > /dev/null 2>&1
+
+output=$(cargo run -- shell --filesystem "$DB" 2>&1 <<'SCRIPT'
+mkdir notes
+cd notes
+write todo.txt buy milk
+pwd
+ls
+cat todo.txt
+cd ..
+ls
+stat notes/todo.txt
+truncate notes/todo.txt 3
+cat notes/todo.txt
+rm notes/todo.txt
+ls notes
+cat missing.txt
+SCRIPT
+)
+
+expected="/notes
+todo.txt
+buy milk
+notes/
+path: /notes/todo.txt
+type: file
+ino: 3
+mode: 644
+nlink: 1
+uid: 0
+gid: 0
+size: 9
+buycat: /missing.txt: No such file"
+
+# mtime changes from run to run
+output=$(echo "$output" | grep -v '^mtime: ')
+
+if [ "$output" != "$expected" ]; then
+    echo "FAILED"
+    echo "Expected:"
+    echo "$expected"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
@@ -0,0 +1,40 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST fs tree... "
+
+TEST_DIR=$(mktemp -d)
+DB="$TEST_DIR/agent.db"
+
+cargo run -- init "$DB" > /dev/null 2>&1
+cargo run -- run --mount type=sqlite,src="$DB",dst=/agent -- /bin/sh -c \
+    'printf hello > /agent/hello.txt && head -c 2048 /dev/zero > /agent/zeros && ln -s hello.txt /agent/link' \
+    > /dev/null 2>&1
+
+output=$(cargo run -- fs tree --filesystem "$DB" 2>/dev/null)
+expected="/
+├── hello.txt (5 bytes)
+├── link -> hello.txt
+└── zeros (2048 bytes)"
+if [ "$output" != "$expected" ]; then
+    echo "FAILED"
+    echo "Expected:"
+    echo "$expected"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+output=$(cargo run -- fs tree --filesystem "$DB" --max-depth 0 2>/dev/null)
+if [ "$output" != "/" ]; then
+    echo "FAILED: --max-depth 0 should only print the root"
+    echo "Got:"
+    echo "$output"
+    rm -rf "$TEST_DIR"
+    exit 1
+fi
+
+rm -rf "$TEST_DIR"
+
+echo "OK"
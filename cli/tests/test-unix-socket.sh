@@ -0,0 +1,38 @@
+#!/bin/sh
+set -e
+
+echo -n "TEST unix socket under bind mount... "
+
+BIN_DIR=$(mktemp -d)
+cc -Wall -O2 -o "$BIN_DIR/test_unix_socket" tests/test_unix_socket.c
+
+mkdir -p sandbox
+
+cleanup() {
+    rm -rf sandbox "$BIN_DIR"
+}
+
+if ! output=$(cargo run -- run --mount type=bind,src=sandbox,dst=/sandbox "$BIN_DIR/test_unix_socket" /sandbox/test.sock 2>&1); then
+    echo "FAILED"
+    echo "Output was: $output"
+    cleanup
+    exit 1
+fi
+
+echo "$output" | grep -q "Unix socket test passed!" || {
+    echo "FAILED: 'Unix socket test passed!' not found"
+    echo "Output was: $output"
+    cleanup
+    exit 1
+}
+
+# The socket must have been created under the mount source on the host
+if [ ! -S sandbox/test.sock ]; then
+    echo "FAILED: socket was not created under the bind mount source"
+    cleanup
+    exit 1
+fi
+
+cleanup
+
+echo "OK"
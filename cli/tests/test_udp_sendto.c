@@ -0,0 +1,102 @@
+#define _GNU_SOURCE
+#include <arpa/inet.h>
+#include <errno.h>
+#include <netinet/in.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+static int check(const char *name, ssize_t n, int refused) {
+    if (refused) {
+        if (n >= 0 || errno != ECONNREFUSED) {
+            fprintf(stderr, "FAIL: %s was not refused\n", name);
+            return 1;
+        }
+    } else if (n != 4) {
+        perror(name);
+        return 1;
+    }
+    return 0;
+}
+
+// With "allowed", <port> must be the only destination the sandbox allows
+int main(int argc, char *argv[]) {
+    struct sockaddr_in addr, other;
+    struct msghdr msg;
+    struct mmsghdr msgs[2];
+    struct iovec iov;
+    int sock, refused;
+    ssize_t n;
+
+    if (argc != 3 || (strcmp(argv[2], "allowed") != 0 && strcmp(argv[2], "refused") != 0)) {
+        fprintf(stderr, "Usage: %s <port> allowed|refused\n", argv[0]);
+        return 1;
+    }
+    refused = strcmp(argv[2], "refused") == 0;
+
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(atoi(argv[1]));
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+
+    sock = socket(AF_INET, SOCK_DGRAM, 0);
+    if (sock < 0) {
+        perror("FAIL: socket");
+        return 1;
+    }
+
+    // Test 1: Send a datagram with sendto
+    n = sendto(sock, "ping", 4, 0, (struct sockaddr *)&addr, sizeof(addr));
+    if (check("sendto", n, refused)) {
+        return 1;
+    }
+
+    // Test 2: Send a datagram with sendmsg
+    iov.iov_base = "ping";
+    iov.iov_len = 4;
+    memset(&msg, 0, sizeof(msg));
+    msg.msg_name = &addr;
+    msg.msg_namelen = sizeof(addr);
+    msg.msg_iov = &iov;
+    msg.msg_iovlen = 1;
+    n = sendmsg(sock, &msg, 0);
+    if (check("sendmsg", n, refused)) {
+        return 1;
+    }
+
+    // Test 3: Send a batch of datagrams with sendmmsg
+    memset(msgs, 0, sizeof(msgs));
+    for (int i = 0; i < 2; i++) {
+        msgs[i].msg_hdr.msg_name = &addr;
+        msgs[i].msg_hdr.msg_namelen = sizeof(addr);
+        msgs[i].msg_hdr.msg_iov = &iov;
+        msgs[i].msg_hdr.msg_iovlen = 1;
+    }
+    n = sendmmsg(sock, msgs, 2, 0);
+    if (refused) {
+        if (check("sendmmsg", n, refused)) {
+            return 1;
+        }
+    } else if (n != 2 || msgs[0].msg_len != 4 || msgs[1].msg_len != 4) {
+        fprintf(stderr, "FAIL: sendmmsg sent %zd datagrams\n", n);
+        return 1;
+    }
+
+    // Test 4: A batch stops at the first refused destination
+    if (!refused) {
+        other = addr;
+        other.sin_port = htons(atoi(argv[1]) + 1);
+        msgs[1].msg_hdr.msg_name = &other;
+        n = sendmmsg(sock, msgs, 2, 0);
+        if (n != 1) {
+            fprintf(stderr, "FAIL: sendmmsg sent %zd datagrams, expected 1\n", n);
+            return 1;
+        }
+    }
+
+    close(sock);
+    printf("UDP sendto test passed!\n");
+    return 0;
+}
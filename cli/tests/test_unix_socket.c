@@ -0,0 +1,71 @@
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <sys/un.h>
+#include <unistd.h>
+
+int main(int argc, char *argv[]) {
+    struct sockaddr_un addr;
+    int server, client, conn;
+    char buf[16];
+    ssize_t n;
+
+    if (argc != 2) {
+        fprintf(stderr, "Usage: %s <socket_path>\n", argv[0]);
+        return 1;
+    }
+
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strncpy(addr.sun_path, argv[1], sizeof(addr.sun_path) - 1);
+    unlink(argv[1]);
+
+    // Test 1: Bind and listen on a path under the mount
+    server = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (server < 0) {
+        perror("FAIL: socket");
+        return 1;
+    }
+    if (bind(server, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        perror("FAIL: bind");
+        return 1;
+    }
+    if (listen(server, 1) < 0) {
+        perror("FAIL: listen");
+        return 1;
+    }
+
+    // Test 2: Connect to the same path
+    client = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (client < 0) {
+        perror("FAIL: socket");
+        return 1;
+    }
+    if (connect(client, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        perror("FAIL: connect");
+        return 1;
+    }
+
+    // Test 3: Exchange data over the connection
+    conn = accept(server, NULL, NULL);
+    if (conn < 0) {
+        perror("FAIL: accept");
+        return 1;
+    }
+    if (write(client, "ping", 4) != 4) {
+        perror("FAIL: write");
+        return 1;
+    }
+    n = read(conn, buf, sizeof(buf));
+    if (n != 4 || memcmp(buf, "ping", 4) != 0) {
+        fprintf(stderr, "FAIL: read returned unexpected data\n");
+        return 1;
+    }
+
+    close(conn);
+    close(client);
+    close(server);
+
+    printf("Unix socket test passed!\n");
+    return 0;
+}
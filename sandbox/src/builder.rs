@@ -0,0 +1,568 @@
+use crate::{
+    config::SandboxConfig,
+    netlog::NetLog,
+    outputlog::{self, OutputLog, OutputStream},
+    proctree::ProcessTree,
+    sandbox::{Sandbox, Session, SessionOptions},
+    summary::RunSummary,
+    syscall::net::{ConnectPolicy, ConnectRule},
+    vfs::{
+        bind::BindVfs,
+        git::GitVfs,
+        http::HttpVfs,
+        mem::MemVfs,
+        mount::{MountConfig, MountTable, MountType},
+        sqlite::SqliteVfs,
+    },
+    writelog::WriteLog,
+};
+use anyhow::{Context, Result};
+use reverie_process::{Command, Errno, ExitStatus};
+use reverie_ptrace::TracerBuilder;
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// A resource whose limit can be set on the sandboxed process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    /// CPU time in seconds (`RLIMIT_CPU`)
+    Cpu,
+    /// Maximum file size in bytes (`RLIMIT_FSIZE`)
+    FileSize,
+    /// Maximum number of open file descriptors (`RLIMIT_NOFILE`)
+    OpenFiles,
+    /// Maximum number of processes (`RLIMIT_NPROC`)
+    Processes,
+    /// Maximum size of the address space in bytes (`RLIMIT_AS`)
+    AddressSpace,
+}
+
+/// A resource limit applied to the sandboxed process before it executes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimit {
+    pub resource: Resource,
+    pub soft: u64,
+    pub hard: u64,
+}
+
+impl ResourceLimit {
+    /// Apply the limit to the calling process
+    fn apply(&self) -> Result<(), Errno> {
+        let resource = match self.resource {
+            Resource::Cpu => libc::RLIMIT_CPU,
+            Resource::FileSize => libc::RLIMIT_FSIZE,
+            Resource::OpenFiles => libc::RLIMIT_NOFILE,
+            Resource::Processes => libc::RLIMIT_NPROC,
+            Resource::AddressSpace => libc::RLIMIT_AS,
+        };
+        let rlim = libc::rlimit {
+            rlim_cur: self.soft as libc::rlim_t,
+            rlim_max: self.hard as libc::rlim_t,
+        };
+        if unsafe { libc::setrlimit(resource, &rlim) } < 0 {
+            return Err(last_errno());
+        }
+        Ok(())
+    }
+}
+
+/// Get the error of the last failed libc call
+fn last_errno() -> Errno {
+    let errno = std::io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or(libc::EINVAL);
+    Errno::new(errno)
+}
+
+/// Builder for running a command inside a sandbox
+///
+/// This is the library entry point to the sandbox: it builds the mount table,
+/// registers a [`Session`], and drives the traced process to completion.
+///
+/// ```no_run
+/// use agentfs_sandbox::{MountConfig, SandboxBuilder};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let status = SandboxBuilder::new("/bin/sh")
+///     .arg("-c")
+///     .arg("ls /data")
+///     .mount("type=bind,src=/tmp,dst=/data".parse::<MountConfig>().unwrap())
+///     .env("HOME", "/data")
+///     .run()
+///     .await?;
+/// assert!(status.success());
+/// # Ok(())
+/// # }
+/// ```
+pub struct SandboxBuilder {
+    command: PathBuf,
+    args: Vec<OsString>,
+    mounts: Vec<MountConfig>,
+    strace: bool,
+    env: Vec<(OsString, OsString)>,
+    env_clear: bool,
+    current_dir: Option<PathBuf>,
+    limits: Vec<ResourceLimit>,
+    net_log: Option<PathBuf>,
+    trace_writes: Option<PathBuf>,
+    capture_output: Option<PathBuf>,
+    connect_policy: ConnectPolicy,
+    sync_on_close: bool,
+    dump_fds_on_signal: bool,
+}
+
+impl SandboxBuilder {
+    /// Create a builder for running `command` in a sandbox
+    pub fn new(command: impl Into<PathBuf>) -> Self {
+        Self {
+            command: command.into(),
+            args: Vec::new(),
+            mounts: Vec::new(),
+            strace: false,
+            env: Vec::new(),
+            env_clear: false,
+            current_dir: None,
+            limits: Vec::new(),
+            net_log: None,
+            trace_writes: None,
+            capture_output: None,
+            connect_policy: ConnectPolicy::default(),
+            sync_on_close: false,
+            dump_fds_on_signal: false,
+        }
+    }
+
+    /// Add an argument to the command
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Add arguments to the command
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add a mount point
+    pub fn mount(mut self, mount: MountConfig) -> Self {
+        self.mounts.push(mount);
+        self
+    }
+
+    /// Add mount points
+    pub fn mounts(mut self, mounts: impl IntoIterator<Item = MountConfig>) -> Self {
+        self.mounts.extend(mounts);
+        self
+    }
+
+    /// Enable strace-like output of intercepted syscalls
+    pub fn strace(mut self, enabled: bool) -> Self {
+        self.strace = enabled;
+        self
+    }
+
+    /// Set an environment variable for the command
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Clear the inherited environment of the command
+    pub fn env_clear(mut self) -> Self {
+        self.env_clear = true;
+        self
+    }
+
+    /// Set the working directory of the command
+    ///
+    /// A directory under a bind mount is translated to its host path.
+    /// Directories under virtual mounts are not supported.
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Apply the mounts, strace setting, environment, and working directory
+    /// of a configuration file
+    ///
+    /// Settings applied after this call override the configured ones.
+    pub fn config(mut self, config: SandboxConfig) -> Self {
+        self = self.mounts(config.mounts).strace(config.strace);
+        for (key, value) in config.env {
+            self = self.env(key, value);
+        }
+        if let Some(workdir) = config.workdir {
+            self = self.current_dir(workdir);
+        }
+        self
+    }
+
+    /// Set a resource limit for the command
+    ///
+    /// The open files limit also applies to virtualized files, which
+    /// do not consume kernel file descriptors.
+    pub fn limit(mut self, resource: Resource, soft: u64, hard: u64) -> Self {
+        self.limits.push(ResourceLimit {
+            resource,
+            soft,
+            hard,
+        });
+        self
+    }
+
+    /// Record outbound network connections in the `net_connections` table
+    /// of the database at `path`
+    pub fn net_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.net_log = Some(path.into());
+        self
+    }
+
+    /// Record every successful write to a file in the `file_writes` table
+    /// of the database at `path`
+    ///
+    /// Each record holds the sandbox path, the file offset, and the bytes
+    /// written, so that the modifications can be replayed in order.
+    pub fn trace_writes(mut self, path: impl Into<PathBuf>) -> Self {
+        self.trace_writes = Some(path.into());
+        self
+    }
+
+    /// Record the command's stdout and stderr in the `output_log` table of
+    /// the database at `path`
+    ///
+    /// The output is still copied to the terminal.
+    pub fn capture_output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.capture_output = Some(path.into());
+        self
+    }
+
+    /// Allow outbound connections to destinations matching `rule`
+    ///
+    /// Once a rule is added, connections and datagrams sent with `sendto`,
+    /// `sendmsg` or `sendmmsg` to IP destinations that match no rule are
+    /// refused with `ECONNREFUSED`.
+    pub fn allow_connect(mut self, rule: ConnectRule) -> Self {
+        self.connect_policy
+            .allow
+            .get_or_insert_with(Vec::new)
+            .push(rule);
+        self
+    }
+
+    /// Refuse connections to Unix sockets
+    pub fn deny_unix_sockets(mut self, deny: bool) -> Self {
+        self.connect_policy.deny_unix = deny;
+        self
+    }
+
+    /// Fsync writable files in bind mounts before closing them
+    ///
+    /// Output written through a bind mount is then durable on the host once
+    /// the command closes the file, instead of whenever the host flushes it.
+    pub fn sync_on_close(mut self, enabled: bool) -> Self {
+        self.sync_on_close = enabled;
+        self
+    }
+
+    /// Print the virtual FDs of every sandboxed process to stderr whenever
+    /// the current process receives `SIGUSR1`
+    ///
+    /// See [`Session::dump_fds`] for the format.
+    pub fn dump_fds_on_signal(mut self, enabled: bool) -> Self {
+        self.dump_fds_on_signal = enabled;
+        self
+    }
+
+    /// Run the command in the sandbox and wait for it to exit
+    pub async fn run(self) -> Result<ExitStatus> {
+        let (status, _) = self.run_with_process_tree().await?;
+        Ok(status)
+    }
+
+    /// Run the command in the sandbox and wait for it to exit, returning
+    /// the tree of processes it started along with its exit status
+    pub async fn run_with_process_tree(self) -> Result<(ExitStatus, ProcessTree)> {
+        let (status, process_tree, _) = self.run_session(false).await?;
+        Ok((status, process_tree))
+    }
+
+    /// Run the command in the sandbox and wait for it to exit, returning
+    /// its exit status, the tree of processes it started, and a summary of
+    /// their file activity
+    ///
+    /// Counting the bytes written to bind-mounted files makes each of
+    /// those writes a round trip through the sandbox.
+    pub async fn run_with_summary(self) -> Result<(ExitStatus, ProcessTree, RunSummary)> {
+        self.run_session(true).await
+    }
+
+    async fn run_session(self, summary: bool) -> Result<(ExitStatus, ProcessTree, RunSummary)> {
+        let mount_table = build_mount_table(&self.mounts).await?;
+        let current_dir = match &self.current_dir {
+            Some(dir) => Some(host_dir(&mount_table, dir)?),
+            None => None,
+        };
+        // The kernel reports the host directory, so getcwd needs the sandbox path
+        let cwd = self
+            .current_dir
+            .clone()
+            .filter(|dir| dir.is_absolute() && mount_table.resolve(dir).is_some());
+        let (net_log, net_log_writer) = match &self.net_log {
+            Some(path) => {
+                let (log, writer) = NetLog::open(path)
+                    .await
+                    .context("Failed to open network log")?;
+                (Some(log), Some(writer))
+            }
+            None => (None, None),
+        };
+        let (write_log, write_log_writer) = match &self.trace_writes {
+            Some(path) => {
+                let (log, writer) = WriteLog::open(path)
+                    .await
+                    .context("Failed to open write log")?;
+                (Some(log), Some(writer))
+            }
+            None => (None, None),
+        };
+        let (output_log, output_log_writer) = match &self.capture_output {
+            Some(path) => {
+                let (log, writer) = OutputLog::open(path)
+                    .await
+                    .context("Failed to open output log")?;
+                (Some(log), Some(writer))
+            }
+            None => (None, None),
+        };
+        // Pipes replacing stdout and stderr of the command, as
+        // (stream, read end, write end, target FD)
+        let mut output_pipes = Vec::new();
+        if output_log.is_some() {
+            for (stream, target) in [
+                (OutputStream::Stdout, libc::STDOUT_FILENO),
+                (OutputStream::Stderr, libc::STDERR_FILENO),
+            ] {
+                let (reader, writer) = outputlog::pipe().context("Failed to create output pipe")?;
+                output_pipes.push((stream, reader, writer, target));
+            }
+        }
+        let mut session = Session::new(
+            mount_table,
+            SessionOptions {
+                strace: self.strace,
+                net_log,
+                write_log,
+                connect_policy: self.connect_policy,
+                max_open_files: self
+                    .limits
+                    .iter()
+                    .rev()
+                    .find(|limit| limit.resource == Resource::OpenFiles)
+                    .map(|limit| limit.soft),
+                sync_on_close: self.sync_on_close,
+                cwd,
+                summary,
+            },
+        );
+
+        if self.dump_fds_on_signal {
+            session
+                .dump_fds_on_signal()
+                .context("Failed to install SIGUSR1 handler")?;
+        }
+
+        let mut cmd = Command::new(&self.command);
+        cmd.args(&self.args);
+        if self.env_clear {
+            cmd.env_clear();
+        }
+        cmd.envs(self.env.iter().map(|(k, v)| (k, v)));
+        if let Some(dir) = &current_dir {
+            cmd.current_dir(dir);
+        }
+        let redirects: Vec<(i32, i32)> = output_pipes
+            .iter()
+            .map(|(_, _, writer, target)| (writer.as_raw_fd(), *target))
+            .collect();
+        if !self.limits.is_empty() || !redirects.is_empty() {
+            let limits = self.limits;
+            // SAFETY: The closure only calls dup2 and setrlimit, which are
+            // async-signal-safe.
+            unsafe {
+                cmd.pre_exec(move || {
+                    for &(fd, target) in &redirects {
+                        if libc::dup2(fd, target) < 0 {
+                            return Err(last_errno());
+                        }
+                    }
+                    limits.iter().try_for_each(ResourceLimit::apply)
+                });
+            }
+        }
+
+        let tracer = TracerBuilder::<Sandbox>::new(cmd)
+            .config(session.id())
+            .spawn()
+            .await
+            .context("Failed to spawn sandboxed process")?;
+
+        // Only the sandboxed process may hold the write ends, so that the
+        // readers see end-of-file once it exits
+        let tees: Vec<_> = output_pipes
+            .into_iter()
+            .filter_map(|(stream, reader, writer, _)| {
+                drop(writer);
+                output_log.as_ref().map(|log| log.tee(stream, reader))
+            })
+            .collect();
+        drop(output_log);
+
+        let (status, _) = tracer
+            .wait()
+            .await
+            .context("Failed to wait for sandboxed process")?;
+        for tee in tees {
+            tee.await.context("Output capture panicked")??;
+        }
+        let process_tree = session.process_tree();
+        let summary = session.summary();
+        drop(session);
+        if let Some(writer) = net_log_writer {
+            writer.finish().await?;
+        }
+        if let Some(writer) = write_log_writer {
+            writer.finish().await?;
+        }
+        if let Some(writer) = output_log_writer {
+            writer.finish().await?;
+        }
+        Ok((status, process_tree, summary))
+    }
+}
+
+/// Translate a sandbox directory to the host directory it is backed by
+fn host_dir(mount_table: &MountTable, dir: &Path) -> Result<PathBuf> {
+    match mount_table.resolve(dir) {
+        Some((vfs, _)) if vfs.is_virtual() => {
+            anyhow::bail!("working directory {} is in a virtual mount", dir.display())
+        }
+        Some((_, translated)) => Ok(translated),
+        None => Ok(dir.to_path_buf()),
+    }
+}
+
+/// Build a mount table from mount configurations
+///
+/// Fails if two mounts have the same destination, before any VFS is created.
+/// SQLite databases are opened, and created if they do not exist. A database
+/// mounted at several destinations is opened once, and its mounts share the
+/// connection, so a write through one is immediately visible through the others.
+pub async fn build_mount_table(mounts: &[MountConfig]) -> Result<MountTable> {
+    for (i, mount_config) in mounts.iter().enumerate() {
+        if let Some(existing) = mounts[..i].iter().find(|m| m.dst == mount_config.dst) {
+            anyhow::bail!("destination {} is already mounted", existing.dst.display());
+        }
+    }
+
+    let mut mount_table = MountTable::new();
+    // Opened SQLite databases by canonical path and whether read-only
+    let mut databases: HashMap<(PathBuf, bool), SqliteVfs> = HashMap::new();
+    for mount_config in mounts {
+        match &mount_config.mount_type {
+            MountType::Bind { src } => {
+                let vfs = Arc::new(
+                    BindVfs::new(src.clone(), mount_config.dst.clone())
+                        .with_id_map(mount_config.id_map()),
+                );
+                mount_table.add_mount(mount_config.dst.clone(), vfs);
+            }
+            MountType::ReadonlyBind { src } => {
+                let vfs = Arc::new(
+                    BindVfs::new_read_only(src.clone(), mount_config.dst.clone())
+                        .with_id_map(mount_config.id_map()),
+                );
+                mount_table.add_mount(mount_config.dst.clone(), vfs);
+            }
+            MountType::Sqlite { src } => {
+                let shared = std::fs::canonicalize(src)
+                    .ok()
+                    .and_then(|path| databases.get(&(path, false)));
+                let vfs = match shared {
+                    Some(vfs) => vfs.with_mount_point(mount_config.dst.clone()),
+                    None => {
+                        let vfs = SqliteVfs::new(src, mount_config.dst.clone())
+                            .await
+                            .context("Failed to create SQLite VFS")?;
+                        databases.insert((std::fs::canonicalize(src)?, false), vfs.clone());
+                        vfs
+                    }
+                };
+                let vfs = vfs.with_id_map(mount_config.id_map());
+                mount_table.add_mount(mount_config.dst.clone(), Arc::new(vfs));
+            }
+            MountType::ReadonlySqlite { src } => {
+                let shared = std::fs::canonicalize(src)
+                    .ok()
+                    .and_then(|path| databases.get(&(path, true)));
+                let vfs = match shared {
+                    Some(vfs) => vfs.with_mount_point(mount_config.dst.clone()),
+                    None => {
+                        let vfs = SqliteVfs::new_read_only(src, mount_config.dst.clone())
+                            .await
+                            .context("Failed to open SQLite VFS")?;
+                        databases.insert((std::fs::canonicalize(src)?, true), vfs.clone());
+                        vfs
+                    }
+                };
+                let vfs = vfs.with_id_map(mount_config.id_map());
+                mount_table.add_mount(mount_config.dst.clone(), Arc::new(vfs));
+            }
+            MountType::Mem => {
+                let vfs = Arc::new(MemVfs::new(mount_config.dst.clone()));
+                mount_table.add_mount(mount_config.dst.clone(), vfs);
+            }
+            MountType::Http { base_url, cache } => {
+                let mut vfs = HttpVfs::new(base_url, mount_config.dst.clone());
+                if let Some(cache) = cache {
+                    let path = cache
+                        .to_str()
+                        .context("HTTP cache path is not valid UTF-8")?;
+                    let fs = agentfs_sdk::Filesystem::new(path)
+                        .await
+                        .context("Failed to open HTTP cache database")?;
+                    vfs = vfs.with_cache(fs);
+                }
+                mount_table.add_mount(mount_config.dst.clone(), Arc::new(vfs));
+            }
+            MountType::Git { repo, revision } => {
+                let vfs = GitVfs::new(repo, revision, mount_config.dst.clone())
+                    .context("Failed to create Git VFS")?;
+                mount_table.add_mount(mount_config.dst.clone(), Arc::new(vfs));
+            }
+            #[cfg(feature = "s3")]
+            MountType::S3 { bucket, prefix } => {
+                let vfs = crate::vfs::s3::S3Vfs::new(bucket, prefix, mount_config.dst.clone())
+                    .context("Failed to create S3 VFS")?;
+                mount_table.add_mount(mount_config.dst.clone(), Arc::new(vfs));
+            }
+            #[cfg(not(feature = "s3"))]
+            MountType::S3 { .. } => {
+                anyhow::bail!(
+                    "cannot mount {}: S3 mounts require building with the 's3' feature",
+                    mount_config.dst.display()
+                );
+            }
+        }
+    }
+    Ok(mount_table)
+}
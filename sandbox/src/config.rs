@@ -0,0 +1,172 @@
+use crate::vfs::mount::MountConfig;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Sandbox settings loaded from a JSON file
+///
+/// Mounts use the serialized form of [`MountConfig`]:
+///
+/// ```json
+/// {
+///   "mounts": [
+///     { "mount_type": { "Bind": { "src": "/home/user/project" } }, "dst": "/project" },
+///     { "mount_type": { "Sqlite": { "src": "agent.db" } }, "dst": "/agent" }
+///   ],
+///   "strace": false,
+///   "env": { "HOME": "/project" },
+///   "workdir": "/project"
+/// }
+/// ```
+///
+/// All fields are optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SandboxConfig {
+    /// Mount points
+    pub mounts: Vec<MountConfig>,
+    /// Enable strace-like output
+    pub strace: bool,
+    /// Environment variables to set for the command
+    pub env: BTreeMap<String, String>,
+    /// Working directory of the command
+    pub workdir: Option<PathBuf>,
+}
+
+impl SandboxConfig {
+    /// Load a configuration file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Add mounts that override the configured ones
+    ///
+    /// A mount replaces a configured mount with the same destination.
+    pub fn override_mounts(&mut self, mounts: Vec<MountConfig>) {
+        self.mounts
+            .retain(|configured| mounts.iter().all(|m| m.dst != configured.dst));
+        self.mounts.extend(mounts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::build_mount_table;
+    use crate::vfs::mount::MountType;
+
+    #[tokio::test]
+    async fn test_load_config_with_two_mounts() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let config_path = dir.path().join("sandbox.json");
+        let config = serde_json::json!({
+            "mounts": [
+                { "mount_type": { "Bind": { "src": "/tmp" } }, "dst": "/data" },
+                { "mount_type": { "Sqlite": { "src": db_path } }, "dst": "/agent" }
+            ],
+            "strace": true,
+            "env": { "GREETING": "hello" },
+            "workdir": "/data"
+        });
+        std::fs::write(&config_path, config.to_string()).unwrap();
+
+        let config = SandboxConfig::load(&config_path).unwrap();
+        assert!(config.strace);
+        assert_eq!(
+            config.env.get("GREETING").map(String::as_str),
+            Some("hello")
+        );
+        assert_eq!(config.workdir, Some(PathBuf::from("/data")));
+
+        let mount_table = build_mount_table(&config.mounts).await.unwrap();
+        let mut dsts: Vec<_> = mount_table
+            .mounts()
+            .iter()
+            .map(|m| m.sandbox_path.clone())
+            .collect();
+        dsts.sort();
+        assert_eq!(dsts, vec![PathBuf::from("/agent"), PathBuf::from("/data")]);
+
+        let (vfs, translated) = mount_table.resolve(Path::new("/data/file")).unwrap();
+        assert!(!vfs.is_virtual());
+        assert_eq!(translated, PathBuf::from("/tmp/file"));
+        let (vfs, _) = mount_table.resolve(Path::new("/agent/file")).unwrap();
+        assert!(vfs.is_virtual());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_database_mounted_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let mounts: Vec<MountConfig> = vec![
+            format!("type=sqlite,src={},dst=/agent", db_path.display())
+                .parse()
+                .unwrap(),
+            format!("type=sqlite,src={},dst=/home/agent", db_path.display())
+                .parse()
+                .unwrap(),
+        ];
+        let mount_table = build_mount_table(&mounts).await.unwrap();
+
+        let (agent, _) = mount_table.resolve(Path::new("/agent/file")).unwrap();
+        let (home, _) = mount_table.resolve(Path::new("/home/agent/file")).unwrap();
+        let file = agent
+            .open(
+                Path::new("/agent/file"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"hello").await.unwrap();
+        file.close().await.unwrap();
+
+        let stat = home.stat(Path::new("/home/agent/file")).await.unwrap();
+        assert_eq!(stat.st_size, 5);
+        let file = home
+            .open(Path::new("/home/agent/file"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let mut buf = [0; 16];
+        let n = file.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"hello");
+
+        // Changes through one mount invalidate the stats cached by the other
+        home.unlink(Path::new("/home/agent/file")).await.unwrap();
+        assert!(agent.stat(Path::new("/agent/file")).await.is_err());
+    }
+
+    #[test]
+    fn test_override_mounts() {
+        let mut config = SandboxConfig {
+            mounts: vec![
+                "type=bind,src=/tmp,dst=/data".parse().unwrap(),
+                "type=bind,src=/tmp,dst=/other".parse().unwrap(),
+            ],
+            ..Default::default()
+        };
+        config.override_mounts(vec!["type=sqlite,src=agent.db,dst=/data".parse().unwrap()]);
+
+        assert_eq!(config.mounts.len(), 2);
+        assert_eq!(config.mounts[0].dst, PathBuf::from("/other"));
+        assert_eq!(config.mounts[1].dst, PathBuf::from("/data"));
+        assert!(matches!(
+            config.mounts[1].mount_type,
+            MountType::Sqlite { .. }
+        ));
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("sandbox.json");
+        std::fs::write(&config_path, r#"{ "mount": [] }"#).unwrap();
+        assert!(SandboxConfig::load(&config_path).is_err());
+    }
+}
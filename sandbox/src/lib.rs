@@ -1,16 +1,49 @@
 #[cfg(target_os = "linux")]
+pub mod builder;
+#[cfg(target_os = "linux")]
+pub mod config;
+#[cfg(target_os = "linux")]
+pub mod netlog;
+#[cfg(target_os = "linux")]
+pub mod outputlog;
+#[cfg(target_os = "linux")]
+pub mod proctree;
+#[cfg(target_os = "linux")]
 pub mod sandbox;
 #[cfg(target_os = "linux")]
+pub mod summary;
+#[cfg(target_os = "linux")]
 pub mod syscall;
 #[cfg(target_os = "linux")]
 pub mod vfs;
+#[cfg(target_os = "linux")]
+pub mod writelog;
 
 #[cfg(target_os = "linux")]
-pub use sandbox::{init_fd_tables, init_mount_table, init_strace, Sandbox};
+pub use builder::{build_mount_table, Resource, ResourceLimit, SandboxBuilder};
+#[cfg(target_os = "linux")]
+pub use config::SandboxConfig;
+#[cfg(target_os = "linux")]
+pub use netlog::{NetConnection, NetLog, NetLogWriter};
+#[cfg(target_os = "linux")]
+pub use outputlog::{OutputChunk, OutputLog, OutputLogWriter, OutputStream};
+#[cfg(target_os = "linux")]
+pub use proctree::ProcessTree;
+#[cfg(target_os = "linux")]
+pub use reverie_process::ExitStatus;
+#[cfg(target_os = "linux")]
+pub use sandbox::{InitError, Sandbox, SandboxGlobal, Session, SessionId, SessionOptions};
+#[cfg(target_os = "linux")]
+pub use summary::RunSummary;
+#[cfg(target_os = "linux")]
+pub use syscall::net::{ConnectPolicy, ConnectRule, SockAddr};
 #[cfg(target_os = "linux")]
 pub use vfs::{
     bind::BindVfs,
+    mem::MemVfs,
     mount::{MountConfig, MountTable, MountType},
     sqlite::SqliteVfs,
-    Vfs, VfsError, VfsResult,
+    IdMap, Vfs, VfsError, VfsResult,
 };
+#[cfg(target_os = "linux")]
+pub use writelog::{FileWrite, WriteLog, WriteLogWriter};
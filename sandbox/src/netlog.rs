@@ -0,0 +1,177 @@
+use crate::syscall::net::SockAddr;
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::{sync::mpsc, task::JoinHandle};
+use turso::{Builder, Connection, Value};
+
+/// An outbound connection made by a sandboxed process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetConnection {
+    /// Process that called `connect`
+    pub pid: i32,
+    /// Time of the call, in seconds since the Unix epoch
+    pub ts: i64,
+    /// Destination address
+    pub addr: SockAddr,
+    /// Return value of `connect` (0 or a negated errno)
+    pub result: i64,
+}
+
+/// Log of network connections made by sandboxed processes
+///
+/// Records are sent over a channel to a background task that appends them to
+/// the `net_connections` table, so that syscall handlers never block on the
+/// database.
+pub struct NetLog {
+    tx: mpsc::UnboundedSender<NetConnection>,
+}
+
+/// Background task writing records to the `net_connections` table
+pub struct NetLogWriter {
+    handle: JoinHandle<Result<()>>,
+}
+
+impl NetLog {
+    /// Open the connection log in the database at `path`
+    ///
+    /// The `net_connections` table is created if it does not exist.
+    pub async fn open(path: impl AsRef<Path>) -> Result<(NetLog, NetLogWriter)> {
+        let path = path.as_ref().to_str().context("Invalid database path")?;
+        let db = Builder::new_local(path).build().await?;
+        let conn = db.connect()?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS net_connections (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                pid INTEGER NOT NULL,
+                ts INTEGER NOT NULL,
+                family TEXT NOT NULL,
+                addr TEXT,
+                port INTEGER,
+                result INTEGER NOT NULL
+            )",
+            (),
+        )
+        .await?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(write_records(conn, rx));
+        Ok((NetLog { tx }, NetLogWriter { handle }))
+    }
+
+    /// Record a connection made by `pid`
+    pub(crate) fn record(&self, pid: i32, addr: SockAddr, result: i64) {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let record = NetConnection {
+            pid,
+            ts,
+            addr,
+            result,
+        };
+        if self.tx.send(record).is_err() {
+            tracing::warn!("Network log writer has stopped, dropping record");
+        }
+    }
+}
+
+impl NetLogWriter {
+    /// Wait for all pending records to be written
+    ///
+    /// Completes once every [`NetLog`] handle has been dropped.
+    pub async fn finish(self) -> Result<()> {
+        self.handle.await.context("Network log writer panicked")?
+    }
+}
+
+async fn write_records(
+    conn: Connection,
+    mut rx: mpsc::UnboundedReceiver<NetConnection>,
+) -> Result<()> {
+    while let Some(record) = rx.recv().await {
+        let (addr, port) = match &record.addr {
+            SockAddr::Inet(addr) => (Value::Text(addr.ip().to_string()), Some(addr.port())),
+            SockAddr::Inet6(addr) => (Value::Text(addr.ip().to_string()), Some(addr.port())),
+            SockAddr::Unix(path) => (Value::Text(path.display().to_string()), None),
+            SockAddr::UnixAbstract(name) => (
+                Value::Text(format!("@{}", String::from_utf8_lossy(name))),
+                None,
+            ),
+            SockAddr::UnixUnnamed | SockAddr::Other(_) => (Value::Null, None),
+        };
+        let port = port.map_or(Value::Null, |port| Value::Integer(port as i64));
+        conn.execute(
+            "INSERT INTO net_connections (pid, ts, family, addr, port, result)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            (
+                Value::Integer(record.pid as i64),
+                Value::Integer(record.ts),
+                Value::Text(record.addr.family_name()),
+                addr,
+                port,
+                Value::Integer(record.result),
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    #[tokio::test]
+    async fn test_records_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("net.db");
+
+        let (log, writer) = NetLog::open(&db_path).await.unwrap();
+        let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8080);
+        log.record(42, SockAddr::Inet(addr), 0);
+        log.record(
+            42,
+            SockAddr::Inet(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 1)),
+            -libc::ECONNREFUSED as i64,
+        );
+        drop(log);
+        writer.finish().await.unwrap();
+
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+        let mut rows = conn
+            .query(
+                "SELECT pid, family, addr, port, result FROM net_connections ORDER BY id",
+                (),
+            )
+            .await
+            .unwrap();
+
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap(), Value::Integer(42));
+        assert_eq!(
+            row.get_value(1).unwrap(),
+            Value::Text("AF_INET".to_string())
+        );
+        assert_eq!(
+            row.get_value(2).unwrap(),
+            Value::Text("127.0.0.1".to_string())
+        );
+        assert_eq!(row.get_value(3).unwrap(), Value::Integer(8080));
+        assert_eq!(row.get_value(4).unwrap(), Value::Integer(0));
+
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(3).unwrap(), Value::Integer(1));
+        assert_eq!(
+            row.get_value(4).unwrap(),
+            Value::Integer(-libc::ECONNREFUSED as i64)
+        );
+        assert!(rows.next().await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::{sync::mpsc, task::JoinHandle};
+use turso::{Builder, Connection, Value};
+
+/// An output stream of the sandboxed process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    /// Name of the stream as stored in the `output_log` table
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputStream::Stdout => "stdout",
+            OutputStream::Stderr => "stderr",
+        }
+    }
+}
+
+/// A chunk of output written by the sandboxed process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputChunk {
+    /// Time the chunk was read, in seconds since the Unix epoch
+    pub ts: i64,
+    /// Stream the chunk was written to
+    pub stream: OutputStream,
+    /// Bytes written
+    pub data: Vec<u8>,
+}
+
+/// Log of the output of the sandboxed process
+///
+/// Like the network log, chunks are sent over a channel to a background task
+/// that appends them to the `output_log` table.
+#[derive(Clone)]
+pub struct OutputLog {
+    tx: mpsc::UnboundedSender<OutputChunk>,
+}
+
+/// Background task writing chunks to the `output_log` table
+pub struct OutputLogWriter {
+    handle: JoinHandle<Result<()>>,
+}
+
+impl OutputLog {
+    /// Open the output log in the database at `path`
+    ///
+    /// The `output_log` table is created if it does not exist.
+    pub async fn open(path: impl AsRef<Path>) -> Result<(OutputLog, OutputLogWriter)> {
+        let path = path.as_ref().to_str().context("Invalid database path")?;
+        let db = Builder::new_local(path).build().await?;
+        let conn = db.connect()?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS output_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts INTEGER NOT NULL,
+                stream TEXT NOT NULL,
+                data BLOB NOT NULL
+            )",
+            (),
+        )
+        .await?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(write_chunks(conn, rx));
+        Ok((OutputLog { tx }, OutputLogWriter { handle }))
+    }
+
+    /// Record a chunk of output
+    pub(crate) fn record(&self, stream: OutputStream, data: &[u8]) {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let chunk = OutputChunk {
+            ts,
+            stream,
+            data: data.to_vec(),
+        };
+        if self.tx.send(chunk).is_err() {
+            tracing::warn!("Output log writer has stopped, dropping output");
+        }
+    }
+
+    /// Copy everything read from `reader` to the terminal and the log
+    ///
+    /// Each stream is read on its own blocking thread, so a process that
+    /// fills one pipe while the other is idle cannot deadlock. The task
+    /// completes when every writer of the pipe has closed it.
+    pub(crate) fn tee(&self, stream: OutputStream, reader: OwnedFd) -> JoinHandle<Result<()>> {
+        let log = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut reader = File::from(reader);
+            let mut buf = vec![0u8; 64 * 1024];
+            loop {
+                let n = match reader.read(&mut buf) {
+                    Ok(0) => return Ok(()),
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e).context("Failed to read output"),
+                };
+                let data = &buf[..n];
+                // The terminal going away must not stop the capture
+                let _ = match stream {
+                    OutputStream::Stdout => {
+                        let mut out = std::io::stdout().lock();
+                        out.write_all(data).and_then(|_| out.flush())
+                    }
+                    OutputStream::Stderr => std::io::stderr().lock().write_all(data),
+                };
+                log.record(stream, data);
+            }
+        })
+    }
+}
+
+impl OutputLogWriter {
+    /// Wait for all pending output to be written
+    ///
+    /// Completes once every [`OutputLog`] handle has been dropped.
+    pub async fn finish(self) -> Result<()> {
+        self.handle.await.context("Output log writer panicked")?
+    }
+}
+
+/// Create a pipe whose ends are closed on exec
+pub(crate) fn pipe() -> std::io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: pipe2 returned two new file descriptors that nothing else owns.
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+async fn write_chunks(
+    conn: Connection,
+    mut rx: mpsc::UnboundedReceiver<OutputChunk>,
+) -> Result<()> {
+    while let Some(chunk) = rx.recv().await {
+        conn.execute(
+            "INSERT INTO output_log (ts, stream, data) VALUES (?, ?, ?)",
+            (
+                Value::Integer(chunk.ts),
+                Value::Text(chunk.stream.name().to_string()),
+                Value::Blob(chunk.data),
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tee_records_pipe_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("output.db");
+
+        let (log, writer) = OutputLog::open(&db_path).await.unwrap();
+        let (reader, pipe_writer) = pipe().unwrap();
+        let tee = log.tee(OutputStream::Stderr, reader);
+        drop(log);
+
+        let mut pipe_writer = File::from(pipe_writer);
+        pipe_writer.write_all(b"captured output\n").unwrap();
+        drop(pipe_writer);
+        tee.await.unwrap().unwrap();
+        writer.finish().await.unwrap();
+
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+        let mut rows = conn
+            .query("SELECT stream, data FROM output_log ORDER BY id", ())
+            .await
+            .unwrap();
+        let mut data = Vec::new();
+        while let Some(row) = rows.next().await.unwrap() {
+            assert_eq!(row.get_value(0).unwrap(), Value::Text("stderr".to_string()));
+            if let Value::Blob(chunk) = row.get_value(1).unwrap() {
+                data.extend(chunk);
+            }
+        }
+        assert_eq!(data, b"captured output\n");
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A process started in the sandbox
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ProcessInfo {
+    parent: Option<i32>,
+    children: Vec<i32>,
+    command: Option<Vec<String>>,
+}
+
+/// Tree of the processes started in a sandbox
+///
+/// Children are recorded by the fork, vfork, and clone handlers, and the
+/// command line of a process is recorded every time it executes a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessTree {
+    processes: BTreeMap<i32, ProcessInfo>,
+}
+
+impl ProcessTree {
+    /// Record that `pid` exists, without a known parent
+    pub(crate) fn add_process(&mut self, pid: i32) {
+        self.processes.entry(pid).or_default();
+    }
+
+    /// Record that `parent` started `child`
+    pub(crate) fn add_child(&mut self, parent: i32, child: i32) {
+        self.processes
+            .entry(parent)
+            .or_default()
+            .children
+            .push(child);
+        self.processes.entry(child).or_default().parent = Some(parent);
+    }
+
+    /// Record the command line that `pid` executed
+    ///
+    /// A later exec by the same process replaces the command.
+    pub(crate) fn set_command(&mut self, pid: i32, command: Vec<String>) {
+        self.processes.entry(pid).or_default().command = Some(command);
+    }
+
+    /// All recorded pids, in ascending order
+    pub fn pids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.processes.keys().copied()
+    }
+
+    /// Processes without a recorded parent
+    pub fn roots(&self) -> impl Iterator<Item = i32> + '_ {
+        self.processes
+            .iter()
+            .filter(|(_, info)| info.parent.is_none())
+            .map(|(pid, _)| *pid)
+    }
+
+    /// Parent of `pid`, if it was started in the sandbox
+    pub fn parent(&self, pid: i32) -> Option<i32> {
+        self.processes.get(&pid).and_then(|info| info.parent)
+    }
+
+    /// Children of `pid`, in the order they were started
+    pub fn children(&self, pid: i32) -> &[i32] {
+        self.processes
+            .get(&pid)
+            .map_or(&[], |info| info.children.as_slice())
+    }
+
+    /// Command line that `pid` last executed
+    pub fn command(&self, pid: i32) -> Option<&[String]> {
+        self.processes
+            .get(&pid)
+            .and_then(|info| info.command.as_deref())
+    }
+
+    fn fmt_process(&self, f: &mut fmt::Formatter<'_>, pid: i32, depth: usize) -> fmt::Result {
+        write!(f, "{:indent$}{}", "", pid, indent = depth * 2)?;
+        if let Some(command) = self.command(pid) {
+            write!(f, " {}", command.join(" "))?;
+        }
+        writeln!(f)?;
+        for &child in self.children(pid) {
+            self.fmt_process(f, child, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ProcessTree {
+    /// Format the tree with one process per line, children indented below
+    /// their parent
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for root in self.roots() {
+            self.fmt_process(f, root, 0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the command line of a running process from `/proc`
+pub(crate) fn read_cmdline(pid: i32) -> std::io::Result<Vec<String>> {
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid))?;
+    Ok(cmdline
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect())
+}
+
+/// Check whether `tid` is a thread of another process rather than a process
+/// of its own
+pub(crate) fn is_thread(tid: i32) -> bool {
+    let Ok(status) = std::fs::read_to_string(format!("/proc/{}/status", tid)) else {
+        return false;
+    };
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Tgid:"))
+        .and_then(|tgid| tgid.trim().parse::<i32>().ok())
+        .is_some_and(|tgid| tgid != tid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_tree() {
+        let mut tree = ProcessTree::default();
+        tree.add_process(10);
+        tree.set_command(10, vec!["/bin/sh".into(), "-c".into(), "a; b".into()]);
+        tree.add_child(10, 11);
+        tree.add_child(10, 12);
+        tree.set_command(11, vec!["a".into()]);
+        tree.add_child(11, 13);
+
+        assert_eq!(tree.pids().collect::<Vec<_>>(), vec![10, 11, 12, 13]);
+        assert_eq!(tree.roots().collect::<Vec<_>>(), vec![10]);
+        assert_eq!(tree.children(10), &[11, 12]);
+        assert_eq!(tree.parent(13), Some(11));
+        assert_eq!(tree.command(12), None);
+        assert_eq!(
+            tree.to_string(),
+            "10 /bin/sh -c a; b\n  11 a\n    13\n  12\n"
+        );
+    }
+
+    #[test]
+    fn test_read_cmdline_of_self() {
+        let cmdline = read_cmdline(std::process::id() as i32).unwrap();
+        assert!(!cmdline.is_empty());
+        assert!(!is_thread(std::process::id() as i32));
+    }
+}
@@ -1,72 +1,375 @@
 use crate::{
-    syscall,
+    netlog::NetLog,
+    proctree::{self, ProcessTree},
+    summary::{RunSummary, SummaryRecorder},
+    syscall::{self, net::ConnectPolicy},
     vfs::{fdtable::FdTable, mount::MountTable},
+    writelog::WriteLog,
 };
-use reverie::{syscalls::Syscall, Error, Guest, Tool};
+use reverie::{
+    syscalls::{Syscall, SyscallInfo},
+    Error, GlobalTool, Guest, Pid, Tool,
+};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Mutex, OnceLock,
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, OnceLock,
 };
+use tracing::Instrument;
 
-/// Global mount table shared across all threads
-static MOUNT_TABLE: OnceLock<MountTable> = OnceLock::new();
-
-/// Global FD tables, one per process (keyed by pid)
-static FD_TABLES: OnceLock<Mutex<HashMap<i32, FdTable>>> = OnceLock::new();
+/// Registry of live sandbox sessions, keyed by session ID
+///
+/// Reverie only hands tools a serializable configuration, so the
+/// per-sandbox state is looked up here when a tool instance is created.
+static SESSIONS: OnceLock<Mutex<HashMap<SessionId, Arc<SandboxState>>>> = OnceLock::new();
 
-/// Global flag to enable strace-like output
-static STRACE_ENABLED: AtomicBool = AtomicBool::new(false);
+/// Next session ID to hand out
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
 
-/// Initialize the global mount table
-///
-/// This must be called before spawning the traced process.
-pub fn init_mount_table(table: MountTable) {
-    MOUNT_TABLE
-        .set(table)
-        .expect("Mount table already initialized");
+fn sessions() -> &'static Mutex<HashMap<SessionId, Arc<SandboxState>>> {
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
-/// Get a reference to the global mount table
-fn get_mount_table() -> &'static MountTable {
-    MOUNT_TABLE.get().expect("Mount table not initialized")
+/// Error returned when a sandbox is used without a valid session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitError {
+    /// No session is registered under this ID
+    UnknownSession(SessionId),
 }
 
-/// Initialize the global FD tables
-///
-/// This must be called before spawning the traced process.
-pub fn init_fd_tables() {
-    FD_TABLES
-        .set(Mutex::new(HashMap::new()))
-        .expect("FD tables already initialized");
+impl std::fmt::Display for InitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitError::UnknownSession(id) => write!(f, "Unknown sandbox session {}", id.0),
+        }
+    }
 }
 
-/// Initialize strace mode
+impl std::error::Error for InitError {}
+
+/// Identifies a sandbox session
 ///
-/// This must be called before spawning the traced process.
-pub fn init_strace(enabled: bool) {
-    STRACE_ENABLED.store(enabled, Ordering::Relaxed);
+/// This is the reverie configuration of the [`Sandbox`] tool. Pass it to
+/// `TracerBuilder::config` so that traced processes find their session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SessionId(u64);
+
+/// State shared by all processes of one sandbox
+pub struct SandboxState {
+    /// Mount table shared across all threads
+    mount_table: MountTable,
+    /// FD tables, one per process (keyed by pid)
+    fd_tables: Mutex<HashMap<i32, FdTable>>,
+    /// Working directories in the sandbox of processes that changed it,
+    /// `None` for a directory outside every mount (keyed by pid)
+    cwds: Mutex<HashMap<i32, Option<PathBuf>>>,
+    /// Working directory of the command when it is inside a mount
+    initial_cwd: Option<PathBuf>,
+    /// Enable strace-like output
+    ///
+    /// Strace output is written directly to stderr and is independent of the
+    /// diagnostic logs emitted through `tracing`.
+    strace: bool,
+    /// Log of outbound network connections
+    net_log: Option<NetLog>,
+    /// Log of the bytes written to files
+    write_log: Option<WriteLog>,
+    /// Policy for outbound connections
+    connect_policy: ConnectPolicy,
+    /// Processes started in the sandbox
+    processes: Mutex<ProcessTree>,
+    /// Limit on virtual FD numbers
+    max_open_files: Option<u64>,
+    /// Fsync writable bind-mounted files before closing them
+    sync_on_close: bool,
+    /// Counters of file activity, if a summary is collected
+    summary: Option<Mutex<SummaryRecorder>>,
 }
 
-/// Check if strace is enabled
-fn is_strace_enabled() -> bool {
-    STRACE_ENABLED.load(Ordering::Relaxed)
+impl SandboxState {
+    /// Get the mount table
+    pub(crate) fn mount_table(&self) -> &MountTable {
+        &self.mount_table
+    }
+
+    /// Check if strace is enabled
+    pub(crate) fn is_strace_enabled(&self) -> bool {
+        self.strace
+    }
+
+    /// Check if writable bind-mounted files are synced before they are closed
+    pub(crate) fn sync_on_close(&self) -> bool {
+        self.sync_on_close
+    }
+
+    /// Get the network connection log, if enabled
+    pub(crate) fn net_log(&self) -> Option<&NetLog> {
+        self.net_log.as_ref()
+    }
+
+    /// Get the file write log, if enabled
+    pub(crate) fn write_log(&self) -> Option<&WriteLog> {
+        self.write_log.as_ref()
+    }
+
+    /// Check if a summary of file activity is collected
+    pub(crate) fn summary_enabled(&self) -> bool {
+        self.summary.is_some()
+    }
+
+    /// Count a successfully opened file in the summary
+    pub(crate) fn record_open(&self) {
+        if let Some(summary) = &self.summary {
+            summary.lock().unwrap().record_open();
+        }
+    }
+
+    /// Count `len` bytes written to the file at the sandbox path `path` in
+    /// the summary
+    ///
+    /// Writes to files outside every mount are not counted.
+    pub(crate) fn record_written(&self, path: &Path, len: usize) {
+        let Some(summary) = &self.summary else {
+            return;
+        };
+        if let Some((mount, _)) = self.mount_table.resolve_mount(path) {
+            summary
+                .lock()
+                .unwrap()
+                .record_write(&mount.sandbox_path, path, len as u64);
+        }
+    }
+
+    /// Get the policy for outbound connections
+    pub(crate) fn connect_policy(&self) -> &ConnectPolicy {
+        &self.connect_policy
+    }
+
+    /// Get or create an FD table for a specific process
+    fn fd_table(&self, pid: i32) -> FdTable {
+        let mut tables = self.fd_tables.lock().unwrap();
+
+        tables
+            .entry(pid)
+            .or_insert_with(|| match self.max_open_files {
+                Some(max) => FdTable::with_max_fds(max.min(i32::MAX as u64) as i32),
+                None => FdTable::new(),
+            })
+            .clone()
+    }
+
+    /// Get the working directory of a process as seen in the sandbox, if it
+    /// is inside a mount
+    ///
+    /// The kernel only knows the host directory a mount is backed by, so
+    /// `getcwd` reports this path instead.
+    pub(crate) fn cwd(&self, pid: i32) -> Option<PathBuf> {
+        let cwds = self.cwds.lock().unwrap();
+        match cwds.get(&pid) {
+            Some(cwd) => cwd.clone(),
+            None => self.initial_cwd.clone(),
+        }
+    }
+
+    /// Record that a process changed its working directory to `cwd`, or to a
+    /// directory outside every mount if `None`
+    pub(crate) fn set_cwd(&self, pid: i32, cwd: Option<PathBuf>) {
+        self.cwds.lock().unwrap().insert(pid, cwd);
+    }
+
+    /// Give `child` the working directory of `parent` (used for fork/clone)
+    pub(crate) fn inherit_cwd(&self, parent: i32, child: i32) {
+        let cwd = self.cwd(parent);
+        self.set_cwd(child, cwd);
+    }
+
+    /// Insert an FD table for a specific process (used for fork/clone)
+    pub(crate) fn insert_fd_table(&self, pid: i32, fd_table: FdTable) {
+        let mut tables = self.fd_tables.lock().unwrap();
+
+        tables.insert(pid, fd_table);
+    }
+
+    /// Describe the FD table of every process, one FD per line
+    ///
+    /// Each line shows the pid, the virtual FD, the kernel FD it maps to or
+    /// `virtual` for a file that exists only in a VFS, the open flags in
+    /// octal, and the path the FD was opened with.
+    fn dump_fds(&self) -> String {
+        let tables = self.fd_tables.lock().unwrap();
+        let mut pids: Vec<_> = tables.keys().copied().collect();
+        pids.sort_unstable();
+
+        let mut dump = String::new();
+        for pid in pids {
+            for (vfd, entry) in tables[&pid].entries() {
+                let target = match entry.kernel_fd() {
+                    Some(kernel_fd) => format!("kernel {}", kernel_fd),
+                    None => "virtual".to_string(),
+                };
+                let path = entry
+                    .path()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                dump.push_str(&format!(
+                    "[{}] fd {} -> {} flags {:o} {}\n",
+                    pid,
+                    vfd,
+                    target,
+                    entry.flags(),
+                    path
+                ));
+            }
+        }
+        dump
+    }
+
+    /// Record that `parent` started the process `child` (used for fork/clone)
+    pub(crate) fn record_child(&self, parent: i32, child: i32) {
+        self.processes.lock().unwrap().add_child(parent, child);
+    }
+
+    /// Record the command line of a process that executed a program
+    ///
+    /// With strace enabled, the command line is also printed, since the
+    /// `execve` arguments only show up as guest addresses.
+    fn record_exec(&self, pid: i32) {
+        let command = match proctree::read_cmdline(pid) {
+            Ok(command) => command,
+            Err(e) => {
+                tracing::warn!("Failed to read command line of {}: {}", pid, e);
+                Vec::new()
+            }
+        };
+        if self.strace {
+            eprintln!("[{}] exec {}", pid, command.join(" "));
+        }
+        let mut processes = self.processes.lock().unwrap();
+        processes.add_process(pid);
+        processes.set_command(pid, command);
+    }
 }
 
-/// Get or create an FD table for a specific process
-fn get_fd_table(pid: i32) -> FdTable {
-    let tables = FD_TABLES.get().expect("FD tables not initialized");
-    let mut tables = tables.lock().unwrap();
+/// Options of a sandbox session
+#[derive(Default)]
+pub struct SessionOptions {
+    /// Enable strace-like output
+    pub strace: bool,
+    /// Log outbound network connections
+    pub net_log: Option<NetLog>,
+    /// Log the bytes written to files
+    pub write_log: Option<WriteLog>,
+    /// Policy for outbound connections
+    pub connect_policy: ConnectPolicy,
+    /// Maximum number of open files per process, enforced for virtual FDs
+    pub max_open_files: Option<u64>,
+    /// Fsync writable bind-mounted files before closing them
+    pub sync_on_close: bool,
+    /// Working directory of the command as seen in the sandbox, if it is
+    /// inside a mount
+    pub cwd: Option<PathBuf>,
+    /// Count opened files, bytes written, and open FDs for
+    /// [`Session::summary`]
+    pub summary: bool,
+}
 
-    tables.entry(pid).or_default().clone()
+/// A registered sandbox session
+///
+/// The session owns the mount table, FD tables, and options of one
+/// sandbox. Several sessions can be live at the same time, each with its own
+/// configuration. The session is unregistered when dropped, so it must
+/// outlive the traced process.
+pub struct Session {
+    id: SessionId,
+    state: Arc<SandboxState>,
+    /// Task printing the FD tables on `SIGUSR1`
+    fd_dumper: Option<tokio::task::JoinHandle<()>>,
 }
 
-/// Insert an FD table for a specific process (used for fork/clone)
-pub(crate) fn insert_fd_table(pid: i32, fd_table: FdTable) {
-    let tables = FD_TABLES.get().expect("FD tables not initialized");
-    let mut tables = tables.lock().unwrap();
+impl Session {
+    /// Register a new sandbox session
+    pub fn new(mount_table: MountTable, options: SessionOptions) -> Self {
+        let id = SessionId(NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed));
+        let state = Arc::new(SandboxState {
+            mount_table,
+            fd_tables: Mutex::new(HashMap::new()),
+            cwds: Mutex::new(HashMap::new()),
+            initial_cwd: options.cwd,
+            strace: options.strace,
+            net_log: options.net_log,
+            write_log: options.write_log,
+            connect_policy: options.connect_policy,
+            processes: Mutex::new(ProcessTree::default()),
+            max_open_files: options.max_open_files,
+            sync_on_close: options.sync_on_close,
+            summary: options
+                .summary
+                .then(|| Mutex::new(SummaryRecorder::default())),
+        });
+        sessions().lock().unwrap().insert(id, state.clone());
+        Self {
+            id,
+            state,
+            fd_dumper: None,
+        }
+    }
+
+    /// Get the session ID to pass as the tracer configuration
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    /// Get a snapshot of the processes started in the sandbox so far
+    pub fn process_tree(&self) -> ProcessTree {
+        self.state.processes.lock().unwrap().clone()
+    }
 
-    tables.insert(pid, fd_table);
+    /// Get the file activity counted so far
+    ///
+    /// The counters stay at zero unless [`SessionOptions::summary`] is set.
+    pub fn summary(&self) -> RunSummary {
+        match &self.state.summary {
+            Some(summary) => summary.lock().unwrap().summary(),
+            None => RunSummary::default(),
+        }
+    }
+
+    /// Describe the virtual FDs of every process in the sandbox
+    ///
+    /// This is a debugging aid for FD virtualization: each line maps a
+    /// process's virtual FD to its kernel FD or virtual file, with the open
+    /// flags and path.
+    pub fn dump_fds(&self) -> String {
+        self.state.dump_fds()
+    }
+
+    /// Print [`Session::dump_fds`] to stderr whenever this process receives
+    /// `SIGUSR1`, until the session is dropped
+    ///
+    /// Must be called from a tokio runtime.
+    pub fn dump_fds_on_signal(&mut self) -> std::io::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut signals = signal(SignalKind::user_defined1())?;
+        let state = self.state.clone();
+        self.fd_dumper = Some(tokio::spawn(async move {
+            while signals.recv().await.is_some() {
+                eprint!("{}", state.dump_fds());
+            }
+        }));
+        Ok(())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(fd_dumper) = self.fd_dumper.take() {
+            fd_dumper.abort();
+        }
+        sessions().lock().unwrap().remove(&self.id);
+    }
 }
 
 /// Format a syscall for strace-like output
@@ -80,41 +383,106 @@ fn format_result(value: i64) -> String {
     format!("{}", value)
 }
 
+/// Global state of the Sandbox tool
+///
+/// Carries the session ID as configuration; all sandbox state lives in the
+/// session itself.
+#[derive(Default)]
+pub struct SandboxGlobal;
+
+#[reverie::global_tool]
+impl GlobalTool for SandboxGlobal {
+    type Request = ();
+    type Response = ();
+    type Config = SessionId;
+
+    async fn init_global_state(_: &Self::Config) -> Self {
+        SandboxGlobal
+    }
+
+    async fn receive_rpc(&self, _from: Pid, _message: Self::Request) -> Self::Response {}
+}
+
 /// The Sandbox tool
 ///
 /// This implements the Reverie Tool trait and intercepts syscalls
 /// to provide filesystem virtualization.
-#[derive(Default)]
-pub struct Sandbox {}
+pub struct Sandbox {
+    session: Result<Arc<SandboxState>, InitError>,
+}
+
+impl Default for Sandbox {
+    /// A sandbox without a session, which fails every syscall
+    fn default() -> Self {
+        Self {
+            session: Err(InitError::UnknownSession(SessionId::default())),
+        }
+    }
+}
 
 #[reverie::tool]
 impl Tool for Sandbox {
-    type GlobalState = ();
+    type GlobalState = SandboxGlobal;
     type ThreadState = ();
 
+    fn new(_pid: Pid, cfg: &SessionId) -> Self {
+        let session = sessions()
+            .lock()
+            .unwrap()
+            .get(cfg)
+            .cloned()
+            .ok_or(InitError::UnknownSession(*cfg));
+        Self { session }
+    }
+
+    async fn handle_post_exec<T: Guest<Self>>(
+        &self,
+        guest: &mut T,
+    ) -> Result<(), reverie::syscalls::Errno> {
+        if let Ok(state) = &self.session {
+            state.record_exec(guest.pid().as_raw());
+        }
+        Ok(())
+    }
+
     async fn handle_syscall_event<T: Guest<Self>>(
         &self,
         guest: &mut T,
         syscall: Syscall,
     ) -> Result<i64, Error> {
-        let mount_table = get_mount_table();
+        let state = match &self.session {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::error!("Cannot handle syscall: {}", e);
+                return Err(Error::Errno(reverie::syscalls::Errno::EIO));
+            }
+        };
         let pid = guest.pid().as_raw();
-        let fd_table = get_fd_table(pid);
+        let fd_table = state.fd_table(pid);
 
-        if is_strace_enabled() {
+        if state.is_strace_enabled() {
             eprintln!("[{}] {}", pid, format_syscall(&syscall));
         }
 
-        let result = match syscall::dispatch_syscall(guest, syscall, mount_table, &fd_table).await {
+        let span = tracing::debug_span!("syscall", pid, name = syscall.name());
+        let dispatched = syscall::dispatch_syscall(guest, syscall, state, &fd_table)
+            .instrument(span)
+            .await;
+
+        if let Some(summary) = &state.summary {
+            summary.lock().unwrap().record_fds(fd_table.len());
+        }
+
+        let result = match dispatched {
             Ok(syscall::SyscallResult::Value(value)) => {
-                if is_strace_enabled() {
+                if state.is_strace_enabled() {
                     eprintln!("[{}] = {}", pid, format_result(value));
                 }
                 Ok(value)
             }
             Ok(syscall::SyscallResult::Syscall(syscall)) => guest.tail_inject(syscall).await,
             Err(e) => {
-                if is_strace_enabled() {
+                if state.is_strace_enabled() {
                     if let Error::Errno(errno) = &e {
                         eprintln!("[{}] = -1 {}", pid, errno);
                     } else {
@@ -128,3 +496,166 @@ impl Tool for Sandbox {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::{bind::BindVfs, fdtable::FdEntry, mem::MemVfs, Vfs};
+    use std::path::{Path, PathBuf};
+
+    fn bind_mount(host: &str) -> MountTable {
+        let mut table = MountTable::new();
+        table.add_mount(
+            PathBuf::from("/data"),
+            Arc::new(BindVfs::new(PathBuf::from(host), PathBuf::from("/data"))),
+        );
+        table
+    }
+
+    #[test]
+    fn test_sessions_are_independent() {
+        let a = Session::new(
+            bind_mount("/host/a"),
+            SessionOptions {
+                strace: true,
+                ..Default::default()
+            },
+        );
+        let b = Session::new(bind_mount("/host/b"), SessionOptions::default());
+        assert_ne!(a.id(), b.id());
+
+        // Both sandboxes are live at the same time and resolve the same
+        // sandbox path through their own mount table.
+        let (id_a, id_b) = (a.id(), b.id());
+        let handles = [(id_a, "/host/a/file"), (id_b, "/host/b/file")].map(|(id, expected)| {
+            std::thread::spawn(move || {
+                let tool = Sandbox::new(Pid::from_raw(1), &id);
+                let state = tool.session.as_ref().unwrap();
+                let (_, translated) = state
+                    .mount_table()
+                    .resolve(Path::new("/data/file"))
+                    .unwrap();
+                assert_eq!(translated, PathBuf::from(expected));
+            })
+        });
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let tool_a = Sandbox::new(Pid::from_raw(1), &id_a);
+        let tool_b = Sandbox::new(Pid::from_raw(1), &id_b);
+        let state_a = tool_a.session.as_ref().unwrap();
+        let state_b = tool_b.session.as_ref().unwrap();
+        assert!(state_a.is_strace_enabled());
+        assert!(!state_b.is_strace_enabled());
+
+        // FD tables are per session, even for the same pid
+        let entry = FdEntry::Passthrough {
+            kernel_fd: 0,
+            flags: 0,
+            path: None,
+        };
+        let fd = state_a.fd_table(1).allocate(entry).unwrap();
+        assert!(state_a.fd_table(1).get(fd).is_some());
+        assert!(state_b.fd_table(1).get(fd).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dump_fds() {
+        let session = Session::new(bind_mount("/host"), SessionOptions::default());
+        let table = session.state.fd_table(42);
+        let file = MemVfs::new(PathBuf::from("/mem"))
+            .open(Path::new("/mem/file"), libc::O_RDWR | libc::O_CREAT, 0o644)
+            .await
+            .unwrap();
+        table.allocate_at(
+            3,
+            FdEntry::Passthrough {
+                kernel_fd: 7,
+                flags: libc::O_RDONLY,
+                path: Some(PathBuf::from("/data/file")),
+            },
+        );
+        table.allocate_at(
+            4,
+            FdEntry::Virtual {
+                file_ops: file,
+                flags: libc::O_RDWR,
+                path: Some(PathBuf::from("/mem/file")),
+            },
+        );
+
+        assert_eq!(
+            session.dump_fds(),
+            "[42] fd 0 -> kernel 0 flags 0 -\n\
+             [42] fd 1 -> kernel 1 flags 0 -\n\
+             [42] fd 2 -> kernel 2 flags 0 -\n\
+             [42] fd 3 -> kernel 7 flags 0 /data/file\n\
+             [42] fd 4 -> virtual flags 2 /mem/file\n"
+        );
+    }
+
+    #[test]
+    fn test_cwd_tracking() {
+        let session = Session::new(
+            bind_mount("/host"),
+            SessionOptions {
+                cwd: Some(PathBuf::from("/data")),
+                ..Default::default()
+            },
+        );
+        let state = &session.state;
+        assert_eq!(state.cwd(1), Some(PathBuf::from("/data")));
+
+        // Children start in the directory of their parent
+        state.set_cwd(1, Some(PathBuf::from("/data/sub")));
+        state.inherit_cwd(1, 2);
+        state.set_cwd(1, None);
+        assert_eq!(state.cwd(1), None);
+        assert_eq!(state.cwd(2), Some(PathBuf::from("/data/sub")));
+        state.inherit_cwd(1, 3);
+        assert_eq!(state.cwd(3), None);
+    }
+
+    #[test]
+    fn test_summary() {
+        let session = Session::new(
+            bind_mount("/host"),
+            SessionOptions {
+                summary: true,
+                ..Default::default()
+            },
+        );
+        let state = &session.state;
+        state.record_open();
+        state.record_written(Path::new("/data/a"), 4);
+        state.record_written(Path::new("/data/a"), 2);
+        state.record_written(Path::new("/data/sub/b"), 1);
+        // Files outside every mount are not counted
+        state.record_written(Path::new("/tmp/c"), 100);
+
+        let summary = session.summary();
+        assert_eq!(summary.files_opened, 1);
+        assert_eq!(summary.files_written, 2);
+        assert_eq!(
+            summary.bytes_written,
+            std::collections::BTreeMap::from([(PathBuf::from("/data"), 7)])
+        );
+
+        // Nothing is counted without a summary
+        let session = Session::new(bind_mount("/host"), SessionOptions::default());
+        session.state.record_open();
+        session.state.record_written(Path::new("/data/a"), 4);
+        assert_eq!(session.summary(), RunSummary::default());
+    }
+
+    #[test]
+    fn test_unknown_session_returns_error() {
+        let session = Session::new(MountTable::new(), SessionOptions::default());
+        let id = session.id();
+        drop(session);
+
+        let tool = Sandbox::new(Pid::from_raw(1), &id);
+        assert_eq!(tool.session.err(), Some(InitError::UnknownSession(id)));
+    }
+}
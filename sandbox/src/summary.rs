@@ -0,0 +1,82 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Counters of what the processes of a sandbox did with files
+///
+/// Collected by [`crate::SandboxBuilder::run_with_summary`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RunSummary {
+    /// Files opened successfully, in mounts or not
+    pub files_opened: u64,
+    /// Distinct files in mounts that were written to
+    pub files_written: u64,
+    /// Bytes written to files in each mount, by mount point
+    pub bytes_written: BTreeMap<PathBuf, u64>,
+    /// Most file descriptors a single process had open at once
+    pub peak_fds: u64,
+}
+
+/// Accumulates a [`RunSummary`] as the syscall handlers report activity
+#[derive(Debug, Default)]
+pub(crate) struct SummaryRecorder {
+    summary: RunSummary,
+    /// Files counted in `files_written`
+    written: HashSet<PathBuf>,
+}
+
+impl SummaryRecorder {
+    /// Record that a file was opened
+    pub(crate) fn record_open(&mut self) {
+        self.summary.files_opened += 1;
+    }
+
+    /// Record that `len` bytes were written to the file at `path` in the
+    /// mount at `mount_point`
+    pub(crate) fn record_write(&mut self, mount_point: &Path, path: &Path, len: u64) {
+        *self
+            .summary
+            .bytes_written
+            .entry(mount_point.to_path_buf())
+            .or_default() += len;
+        if self.written.insert(path.to_path_buf()) {
+            self.summary.files_written += 1;
+        }
+    }
+
+    /// Record that a process has `count` file descriptors open
+    pub(crate) fn record_fds(&mut self, count: usize) {
+        self.summary.peak_fds = self.summary.peak_fds.max(count as u64);
+    }
+
+    /// Get the counters recorded so far
+    pub(crate) fn summary(&self) -> RunSummary {
+        self.summary.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_recorder() {
+        let mut recorder = SummaryRecorder::default();
+        recorder.record_open();
+        recorder.record_open();
+        recorder.record_write(Path::new("/agent"), Path::new("/agent/a"), 5);
+        recorder.record_write(Path::new("/agent"), Path::new("/agent/a"), 3);
+        recorder.record_write(Path::new("/data"), Path::new("/data/b"), 7);
+        recorder.record_fds(5);
+        recorder.record_fds(3);
+
+        let summary = recorder.summary();
+        assert_eq!(summary.files_opened, 2);
+        assert_eq!(summary.files_written, 2);
+        assert_eq!(
+            summary.bytes_written,
+            BTreeMap::from([(PathBuf::from("/agent"), 8), (PathBuf::from("/data"), 7)])
+        );
+        assert_eq!(summary.peak_fds, 5);
+    }
+}
@@ -1,17 +1,129 @@
 use crate::{
-    sandbox::Sandbox,
-    syscall::translate_path,
+    sandbox::{Sandbox, SandboxState},
+    syscall::{
+        check_fd_writable, check_writable, check_writable_at,
+        net::{copy_msg_name, copy_sockaddr, push_msghdr, push_sockaddr, SockAddr},
+        path_at,
+        stat::{fstat_virtual, remap_stat},
+        translate_path,
+    },
     vfs::{
         fdtable::{FdEntry, FdTable},
-        mount::MountTable,
+        file::BoxedFileOps,
+        follow_symlinks,
+        mount::{MountPoint, MountTable},
+        Vfs,
     },
 };
 use reverie::{
-    syscalls::{MemoryAccess, ReadAddr, Syscall},
+    syscalls::{AtFlags, MemoryAccess, ReadAddr, RenameFlags, Syscall},
     Error, Guest, Stack,
 };
 use std::mem::MaybeUninit;
 
+/// Largest buffer allocated for one step of a virtual `read` or `write`
+///
+/// The guest controls the length, so longer requests are served in steps
+/// instead of allocating the whole length up front.
+const MAX_IO_CHUNK: usize = 1024 * 1024;
+
+/// Sandbox path to record writes to a passthrough file under, if writes are
+/// traced or summarized and the file is in a mount
+fn traced_path(state: &SandboxState, path: Option<&std::path::Path>) -> Option<std::path::PathBuf> {
+    if state.write_log().is_none() && !state.summary_enabled() {
+        return None;
+    }
+    let path = path?;
+    state.mount_table().resolve(path)?;
+    Some(path.to_path_buf())
+}
+
+/// Record `len` bytes a process wrote from `buf_addr` at `offset` of the file
+/// at `path` in the summary and the write log
+///
+/// The bytes are read back from guest memory and recorded in steps of at most
+/// `MAX_IO_CHUNK` bytes.
+async fn record_write<T: Guest<Sandbox>>(
+    guest: &mut T,
+    state: &SandboxState,
+    path: &std::path::Path,
+    offset: i64,
+    buf_addr: reverie::syscalls::Addr<'_, u8>,
+    len: usize,
+) -> Result<(), Error> {
+    state.record_written(path, len);
+    let Some(write_log) = state.write_log() else {
+        return Ok(());
+    };
+    let mut buf = vec![0u8; len.min(MAX_IO_CHUNK)];
+    let mut done = 0;
+    while done < len {
+        let want = (len - done).min(buf.len());
+        let addr = unsafe { buf_addr.add(done) };
+        guest.memory().read_exact(addr, &mut buf[..want])?;
+        let chunk_offset = if offset < 0 { -1 } else { offset + done as i64 };
+        write_log
+            .record(guest.pid().as_raw(), path, chunk_offset, &buf[..want])
+            .await;
+        done += want;
+    }
+    Ok(())
+}
+
+/// Check if `open` flags ask for write access or may modify the file
+fn opens_for_writing(flags: i32) -> bool {
+    // O_PATH ignores the access mode, O_CREAT and O_TRUNC
+    if flags & libc::O_PATH != 0 {
+        return false;
+    }
+    flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0
+}
+
+/// Translate the directory FD of an `*at` system call to the kernel FD
+///
+/// `AT_FDCWD` and FDs missing from the table are passed on unchanged.
+fn kernel_dirfd(dirfd: i32, fd_table: &FdTable) -> i32 {
+    if dirfd == libc::AT_FDCWD {
+        dirfd
+    } else {
+        fd_table.translate(dirfd).unwrap_or(dirfd)
+    }
+}
+
+/// Allocate a virtual FD for `entry`.
+///
+/// If the FD table is full, the entry is closed and `-EMFILE` is returned instead.
+async fn allocate_fd<T: Guest<Sandbox>>(
+    guest: &mut T,
+    fd_table: &FdTable,
+    entry: FdEntry,
+) -> Result<i64, Error> {
+    match fd_table.allocate(entry.clone()) {
+        Some(virtual_fd) => Ok(virtual_fd as i64),
+        None => {
+            close_entry(guest, entry).await?;
+            Ok(-libc::EMFILE as i64)
+        }
+    }
+}
+
+/// Close the kernel FD or file operations behind an FD entry.
+async fn close_entry<T: Guest<Sandbox>>(guest: &mut T, entry: FdEntry) -> Result<(), Error> {
+    match entry {
+        FdEntry::Passthrough { kernel_fd, .. } => {
+            guest
+                .inject(Syscall::Close(
+                    reverie::syscalls::Close::new().with_fd(kernel_fd),
+                ))
+                .await?;
+        }
+        FdEntry::Virtual { file_ops, .. } => {
+            let _ = file_ops.close().await;
+        }
+    }
+    Ok(())
+}
+
 /// The `openat` system call.
 ///
 /// This intercepts `openat` system calls and translates paths according to the mount table,
@@ -36,19 +148,20 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
         } else if path.is_relative() {
             // For relative paths, resolve against dirfd
             if let Some(dir_entry) = fd_table.get(dirfd) {
-                // Check if this is a passthrough directory with a kernel FD first
-                if let Some(kfd) = dir_entry.kernel_fd() {
-                    // Passthrough directory - use the kernel FD and keep path as-is
-                    kfd
-                } else if let Some(dir_path) = dir_entry.path() {
-                    // Virtual directory - resolve relative path against the directory's path
+                let kernel_dirfd = dir_entry.kernel_fd();
+                if let Some(dir_path) = dir_entry.path() {
+                    // Resolve the relative path against the directory's path,
+                    // so that it is matched against the mount table even if
+                    // it crosses into a virtual mount below the directory
                     path = dir_path.join(&path);
-                    // For virtual directories, we'll use AT_FDCWD since we have the full path now
-                    libc::AT_FDCWD
-                } else {
+                } else if kernel_dirfd.is_none() {
                     // Virtual file without a path - this shouldn't happen for directories
                     return Ok(Some(-libc::EBADF as i64));
                 }
+                // Passthrough directory - use the kernel FD and keep the
+                // original path for the kernel. For virtual directories, use
+                // AT_FDCWD since we have the full path now
+                kernel_dirfd.unwrap_or(libc::AT_FDCWD)
             } else {
                 // dirfd not in table - will likely fail
                 dirfd
@@ -60,10 +173,14 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
 
         // Check if this path matches a mount point
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
+            if vfs.is_read_only() && opens_for_writing(args.flags().bits()) {
+                return Ok(Some(-libc::EROFS as i64));
+            }
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
                 // For virtual VFS, open the file directly without going to the kernel
                 let mode = args.mode().map(|m| m.bits()).unwrap_or(0o644);
+                tracing::debug!(path = %path.display(), flags = args.flags().bits(), "openat (virtual)");
                 match vfs.open(&path, args.flags().bits(), mode).await {
                     Ok(file_ops) => {
                         // Store the path with the FD entry for directories
@@ -72,14 +189,19 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
                             flags: args.flags().bits(),
                             path: Some(path.clone()),
                         };
-                        let virtual_fd = fd_table.allocate(entry);
-                        return Ok(Some(virtual_fd as i64));
+                        return Ok(Some(allocate_fd(guest, fd_table, entry).await?));
                     }
                     Err(e) => {
                         // Map VFS errors to errno
                         let errno = match e {
                             crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                             crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                            crate::vfs::VfsError::SymlinkLoop => -libc::ELOOP as i64,
+                            crate::vfs::VfsError::NotSupported => -libc::EOPNOTSUPP as i64,
+                            crate::vfs::VfsError::InvalidInput(_) => -libc::EINVAL as i64,
+                            crate::vfs::VfsError::IoError(e) => {
+                                -e.raw_os_error().unwrap_or(libc::EIO) as i64
+                            }
                             _ => -libc::EIO as i64,
                         };
                         return Ok(Some(errno));
@@ -87,6 +209,7 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
                 }
             } else {
                 // For passthrough VFS, translate the path and call the kernel
+                tracing::debug!(path = %path.display(), "openat (passthrough)");
                 let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
 
                 let new_syscall = reverie::syscalls::Openat::new()
@@ -104,8 +227,7 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
                         flags: args.flags().bits(),
                         path: Some(path.clone()),
                     };
-                    let virtual_fd = fd_table.allocate(entry);
-                    return Ok(Some(virtual_fd as i64));
+                    return Ok(Some(allocate_fd(guest, fd_table, entry).await?));
                 } else {
                     return Ok(Some(kernel_fd));
                 }
@@ -127,8 +249,7 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
                     flags: args.flags().bits(),
                     path: Some(path.clone()),
                 };
-                let virtual_fd = fd_table.allocate(entry);
-                return Ok(Some(virtual_fd as i64));
+                return Ok(Some(allocate_fd(guest, fd_table, entry).await?));
             } else {
                 return Ok(Some(kernel_fd));
             }
@@ -170,27 +291,41 @@ pub async fn handle_read<T: Guest<Sandbox>>(
                     None => return Ok(crate::syscall::SyscallResult::Value(-libc::EFAULT as i64)),
                 };
 
+                // Read in bounded steps so a huge length does not allocate a huge buffer
                 let buf_len = args.len();
-                let mut buf = vec![0u8; buf_len];
-
-                match file_ops.read(&mut buf).await {
-                    Ok(n) => {
-                        // Write the data back to guest memory
-                        if n > 0 {
-                            guest.memory().write_exact(buf_addr, &buf[..n])?;
+                let mut buf = vec![0u8; buf_len.min(MAX_IO_CHUNK)];
+                let mut total = 0;
+                while total < buf_len {
+                    let want = (buf_len - total).min(buf.len());
+                    match file_ops.read(&mut buf[..want]).await {
+                        Ok(n) => {
+                            // Write the data back to guest memory
+                            if n > 0 {
+                                let addr = unsafe { buf_addr.add(total) };
+                                guest.memory().write_exact(addr, &buf[..n])?;
+                            }
+                            total += n;
+                            if n < want {
+                                break;
+                            }
+                        }
+                        // Report the bytes read before the error, like a short read
+                        Err(_) if total > 0 => break,
+                        Err(e) => {
+                            // Map VFS errors to errno
+                            let errno = match e {
+                                crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
+                                crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                                crate::vfs::VfsError::IoError(e) => {
+                                    -e.raw_os_error().unwrap_or(libc::EIO) as i64
+                                }
+                                _ => -libc::EIO as i64,
+                            };
+                            return Ok(crate::syscall::SyscallResult::Value(errno));
                         }
-                        return Ok(crate::syscall::SyscallResult::Value(n as i64));
-                    }
-                    Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
-                        return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
+                return Ok(crate::syscall::SyscallResult::Value(total as i64));
             }
         }
     }
@@ -202,11 +337,14 @@ pub async fn handle_read<T: Guest<Sandbox>>(
 /// The `write` system call.
 ///
 /// This intercepts `write` system calls and translates virtual FDs to kernel FDs,
-/// or calls FileOps directly for virtual files.
+/// or calls FileOps directly for virtual files. With write tracing enabled, the
+/// bytes written to files in mounts are also recorded in the write log, and with
+/// a summary they are counted.
 pub async fn handle_write<T: Guest<Sandbox>>(
     guest: &mut T,
     syscall: Syscall,
     args: &reverie::syscalls::Write,
+    state: &SandboxState,
     fd_table: &FdTable,
 ) -> Result<crate::syscall::SyscallResult, Error> {
     let virtual_fd = args.fd();
@@ -214,44 +352,93 @@ pub async fn handle_write<T: Guest<Sandbox>>(
     // Get the FD entry
     if let Some(entry) = fd_table.get(virtual_fd) {
         match entry {
-            FdEntry::Passthrough { kernel_fd, .. } => {
-                // Passthrough file - rewrite FD and return modified syscall for tail_inject
+            FdEntry::Passthrough {
+                kernel_fd, path, ..
+            } => {
                 let new_syscall = reverie::syscalls::Write::new()
                     .with_fd(kernel_fd)
                     .with_buf(args.buf())
                     .with_len(args.len());
 
+                if let (Some(path), Some(buf_addr)) =
+                    (traced_path(state, path.as_deref()), args.buf())
+                {
+                    let result = guest.inject(Syscall::Write(new_syscall)).await?;
+                    if result > 0 {
+                        // The write left the file position right after the bytes
+                        let lseek = reverie::syscalls::Lseek::new()
+                            .with_fd(kernel_fd)
+                            .with_offset(0)
+                            .with_whence(reverie::syscalls::Whence::SEEK_CUR);
+                        let offset = match guest.inject(Syscall::Lseek(lseek)).await {
+                            Ok(end) => end - result,
+                            Err(_) => -1,
+                        };
+                        record_write(guest, state, &path, offset, buf_addr, result as usize)
+                            .await?;
+                    }
+                    return Ok(crate::syscall::SyscallResult::Value(result));
+                }
+
+                // Passthrough file - rewrite FD and return modified syscall for tail_inject
                 return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Write(
                     new_syscall,
                 )));
             }
-            FdEntry::Virtual { file_ops, .. } => {
+            FdEntry::Virtual { file_ops, path, .. } => {
                 // Virtual file - use FileOps directly
                 let buf_addr = match args.buf() {
                     Some(addr) => addr,
                     None => return Ok(crate::syscall::SyscallResult::Value(-libc::EFAULT as i64)),
                 };
 
+                // Write in bounded steps so a huge length does not allocate a huge buffer
                 let buf_len = args.len();
-                let mut buf = vec![0u8; buf_len];
-
-                // Read data from guest memory
-                guest.memory().read_exact(buf_addr, &mut buf)?;
-
-                match file_ops.write(&buf).await {
-                    Ok(n) => {
-                        return Ok(crate::syscall::SyscallResult::Value(n as i64));
-                    }
-                    Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
-                        return Ok(crate::syscall::SyscallResult::Value(errno));
+                let mut buf = vec![0u8; buf_len.min(MAX_IO_CHUNK)];
+                let mut total = 0;
+                while total < buf_len {
+                    let want = (buf_len - total).min(buf.len());
+                    // Read data from guest memory
+                    let addr = unsafe { buf_addr.add(total) };
+                    guest.memory().read_exact(addr, &mut buf[..want])?;
+
+                    match file_ops.write(&buf[..want]).await {
+                        Ok(n) => {
+                            if let Some(path) = &path {
+                                state.record_written(path, n);
+                            }
+                            if let (Some(write_log), Some(path)) = (state.write_log(), &path) {
+                                // The write left the file position right after the bytes
+                                let offset = match file_ops.seek(0, libc::SEEK_CUR).await {
+                                    Ok(end) => end - n as i64,
+                                    Err(_) => -1,
+                                };
+                                write_log
+                                    .record(guest.pid().as_raw(), path, offset, &buf[..n])
+                                    .await;
+                            }
+                            total += n;
+                            if n < want {
+                                break;
+                            }
+                        }
+                        // Report the bytes written before the error, like a short write
+                        Err(_) if total > 0 => break,
+                        Err(e) => {
+                            // Map VFS errors to errno
+                            let errno = match e {
+                                crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
+                                crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                                crate::vfs::VfsError::IoError(e) => {
+                                    -e.raw_os_error().unwrap_or(libc::EIO) as i64
+                                }
+                                _ => -libc::EIO as i64,
+                            };
+                            return Ok(crate::syscall::SyscallResult::Value(errno));
+                        }
                     }
                 }
+                return Ok(crate::syscall::SyscallResult::Value(total as i64));
             }
         }
     }
@@ -263,11 +450,13 @@ pub async fn handle_write<T: Guest<Sandbox>>(
 /// The `close` system call.
 ///
 /// This intercepts `close` system calls, translates virtual FDs to kernel FDs,
-/// and cleans up the FD mapping.
+/// and cleans up the FD mapping. With sync-on-close enabled, writable files in
+/// bind mounts are fsynced first.
 pub async fn handle_close<T: Guest<Sandbox>>(
-    _guest: &mut T,
+    guest: &mut T,
     syscall: Syscall,
     args: &reverie::syscalls::Close,
+    state: &SandboxState,
     fd_table: &FdTable,
 ) -> Result<crate::syscall::SyscallResult, Error> {
     let virtual_fd = args.fd();
@@ -275,7 +464,25 @@ pub async fn handle_close<T: Guest<Sandbox>>(
     // Translate and deallocate the virtual FD
     if let Some(entry) = fd_table.deallocate(virtual_fd) {
         match entry {
-            FdEntry::Passthrough { kernel_fd, .. } => {
+            FdEntry::Passthrough {
+                kernel_fd,
+                flags,
+                path,
+            } => {
+                if state.sync_on_close() && opens_for_writing(flags) {
+                    let bind_mounted = path
+                        .as_deref()
+                        .and_then(|path| state.mount_table().resolve(path))
+                        .is_some_and(|(vfs, _)| !vfs.is_virtual());
+                    if bind_mounted {
+                        // Best effort: a failed fsync must not keep the FD open
+                        let fsync = reverie::syscalls::Fsync::new().with_fd(kernel_fd);
+                        if let Err(e) = guest.inject(Syscall::Fsync(fsync)).await {
+                            tracing::debug!(fd = kernel_fd, error = %e, "fsync before close failed");
+                        }
+                    }
+                }
+
                 // Passthrough file - rewrite FD and return modified syscall for tail_inject
                 let new_syscall = reverie::syscalls::Close::new().with_fd(kernel_fd);
 
@@ -330,8 +537,7 @@ pub async fn handle_dup<T: Guest<Sandbox>>(
                 };
 
                 // Allocate a new virtual FD
-                let new_vfd = fd_table.allocate(entry);
-                return Ok(Some(new_vfd as i64));
+                return Ok(Some(allocate_fd(guest, fd_table, entry).await?));
             }
             FdEntry::Virtual { .. } => {
                 // Virtualized file - just duplicate the virtual FD
@@ -359,6 +565,11 @@ pub async fn handle_dup2<T: Guest<Sandbox>>(
 
     // Get the entry for the old virtual FD
     if let Some(old_entry) = fd_table.get(old_vfd) {
+        // Like the kernel, refuse a target at or above the open files limit
+        if !fd_table.within_limit(new_vfd) {
+            return Ok(Some(-libc::EBADF as i64));
+        }
+
         // Get the entry at new_vfd if it exists (we need to close its kernel FD)
         let old_new_entry = fd_table.get(new_vfd);
 
@@ -447,6 +658,11 @@ pub async fn handle_dup3<T: Guest<Sandbox>>(
 
     // Get the entry for the old virtual FD
     if let Some(old_entry) = fd_table.get(old_vfd) {
+        // Like the kernel, refuse a target at or above the open files limit
+        if !fd_table.within_limit(new_vfd) {
+            return Ok(Some(-libc::EBADF as i64));
+        }
+
         // Get the entry at new_vfd if it exists (we need to close its kernel FD)
         let old_new_entry = fd_table.get(new_vfd);
 
@@ -585,6 +801,11 @@ pub async fn handle_fcntl<T: Guest<Sandbox>>(
                 // 1. Execute the syscall with the kernel FD to get a new kernel FD
                 // 2. Allocate a new virtual FD for the result
 
+                // Like the kernel, refuse a minimum at or above the open files limit
+                if !fd_table.within_limit(arg) {
+                    return Ok(Some(-libc::EINVAL as i64));
+                }
+
                 let is_cloexec = matches!(args.cmd(), FcntlCmd::F_DUPFD_CLOEXEC(_));
                 // O_CLOEXEC = 0o2000000 on Linux
                 let flags = if is_cloexec { 0o2000000 } else { 0 };
@@ -618,8 +839,13 @@ pub async fn handle_fcntl<T: Guest<Sandbox>>(
                         path: fd_path.cloned(),
                     };
                     // Allocate virtual FD at or above the requested minimum
-                    let new_vfd = fd_table.allocate_min(arg, entry);
-                    return Ok(Some(new_vfd as i64));
+                    return match fd_table.allocate_min(arg, entry.clone()) {
+                        Some(new_vfd) => Ok(Some(new_vfd as i64)),
+                        None => {
+                            close_entry(guest, entry).await?;
+                            Ok(Some(-libc::EMFILE as i64))
+                        }
+                    };
                 } else {
                     // Return the error code as-is
                     return Ok(Some(new_kernel_fd));
@@ -977,7 +1203,9 @@ pub async fn handle_getdents64<T: Guest<Sandbox>>(
                 )));
             }
             FdEntry::Virtual { file_ops, .. } => {
-                // Virtual file - use FileOps::getdents()
+                // Virtual file - use FileOps::getdents(), remembering where
+                // the listing starts so d_off values are seekable offsets
+                let start = file_ops.seek(0, libc::SEEK_CUR).await.unwrap_or(0);
                 match file_ops.getdents().await {
                     Ok(entries) => {
                         // Format as linux_dirent64 structures
@@ -992,7 +1220,9 @@ pub async fn handle_getdents64<T: Guest<Sandbox>>(
                         let count = args.count() as usize;
 
                         let mut buf = Vec::new();
-                        let mut offset = 1i64;
+                        let mut offset = start + 1;
+                        let total = entries.len();
+                        let mut emitted = 0;
 
                         for (ino, name, d_type) in entries {
                             // Calculate record length (aligned to 8 bytes)
@@ -1017,6 +1247,18 @@ pub async fn handle_getdents64<T: Guest<Sandbox>>(
                             }
 
                             offset += 1;
+                            emitted += 1;
+                        }
+
+                        if emitted < total {
+                            if emitted == 0 {
+                                // Buffer too small for even one entry
+                                return Ok(crate::syscall::SyscallResult::Value(
+                                    -libc::EINVAL as i64,
+                                ));
+                            }
+                            // Leave the entries that did not fit for the next call
+                            let _ = file_ops.seek(start + emitted as i64, libc::SEEK_SET).await;
                         }
 
                         // Write to guest memory
@@ -1026,6 +1268,11 @@ pub async fn handle_getdents64<T: Guest<Sandbox>>(
 
                         return Ok(crate::syscall::SyscallResult::Value(buf.len() as i64));
                     }
+                    Err(crate::vfs::VfsError::IoError(e)) => {
+                        // Such as EBADF for O_PATH handles
+                        let errno = e.raw_os_error().unwrap_or(libc::EIO);
+                        return Ok(crate::syscall::SyscallResult::Value(-errno as i64));
+                    }
                     Err(_) => {
                         // Not a directory or error
                         return Ok(crate::syscall::SyscallResult::Value(-libc::ENOTDIR as i64));
@@ -1047,6 +1294,7 @@ pub async fn handle_fstat<T: Guest<Sandbox>>(
     guest: &mut T,
     syscall: Syscall,
     args: &reverie::syscalls::Fstat,
+    mount_table: &MountTable,
     fd_table: &FdTable,
 ) -> Result<crate::syscall::SyscallResult, Error> {
     let virtual_fd = args.fd();
@@ -1054,45 +1302,36 @@ pub async fn handle_fstat<T: Guest<Sandbox>>(
     // Get the FD entry
     if let Some(entry) = fd_table.get(virtual_fd) {
         match entry {
-            FdEntry::Passthrough { kernel_fd, .. } => {
-                // Passthrough file - rewrite FD and return modified syscall for tail_inject
+            FdEntry::Passthrough {
+                kernel_fd, path, ..
+            } => {
+                // Passthrough file - rewrite FD
                 let new_syscall = reverie::syscalls::Fstat::new()
                     .with_fd(kernel_fd)
                     .with_stat(args.stat());
 
-                return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Fstat(
-                    new_syscall,
-                )));
+                let id_map = path
+                    .as_deref()
+                    .and_then(|path| mount_table.resolve(path))
+                    .map(|(vfs, _)| vfs.id_map())
+                    .unwrap_or_default();
+                if id_map.is_identity() {
+                    // Nothing to rewrite, return modified syscall for tail_inject
+                    return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Fstat(
+                        new_syscall,
+                    )));
+                }
+
+                let result = guest.inject(Syscall::Fstat(new_syscall)).await?;
+                if result == 0 {
+                    remap_stat(guest, args.stat(), id_map)?;
+                }
+                return Ok(crate::syscall::SyscallResult::Value(result));
             }
             FdEntry::Virtual { file_ops, .. } => {
                 // Virtual file - use FileOps::fstat()
-                match file_ops.fstat().await {
-                    Ok(stat_buf) => {
-                        // Write the stat result to guest memory
-                        if let Some(stat_addr) = args.stat() {
-                            // Convert stat struct to bytes and write
-                            let stat_bytes: &[u8] = unsafe {
-                                std::slice::from_raw_parts(
-                                    &stat_buf as *const _ as *const u8,
-                                    std::mem::size_of::<libc::stat>(),
-                                )
-                            };
-                            guest
-                                .memory()
-                                .write_exact(stat_addr.0.cast::<u8>(), stat_bytes)?;
-                        }
-                        return Ok(crate::syscall::SyscallResult::Value(0)); // Success
-                    }
-                    Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
-                        return Ok(crate::syscall::SyscallResult::Value(errno));
-                    }
-                }
+                let result = fstat_virtual(guest, &file_ops, args.stat()).await?;
+                return Ok(crate::syscall::SyscallResult::Value(result));
             }
         }
     }
@@ -1130,9 +1369,12 @@ pub async fn handle_pread64<T: Guest<Sandbox>>(
 /// The `pwrite64` system call.
 ///
 /// This intercepts `pwrite64` system calls and translates virtual FDs to kernel FDs.
+/// With write tracing enabled, the bytes written to files in mounts are also
+/// recorded in the write log, and with a summary they are counted.
 pub async fn handle_pwrite64<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Pwrite64,
+    state: &SandboxState,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     let virtual_fd = args.fd();
@@ -1146,6 +1388,20 @@ pub async fn handle_pwrite64<T: Guest<Sandbox>>(
             .with_offset(args.offset());
 
         let result = guest.inject(Syscall::Pwrite64(new_syscall)).await?;
+        let path = fd_table
+            .get(virtual_fd)
+            .and_then(|entry| traced_path(state, entry.path().map(|path| path.as_path())));
+        if let (true, Some(path), Some(buf_addr)) = (result > 0, path, args.buf()) {
+            record_write(
+                guest,
+                state,
+                &path,
+                args.offset(),
+                buf_addr,
+                result as usize,
+            )
+            .await?;
+        }
         return Ok(Some(result));
     }
 
@@ -1200,6 +1456,10 @@ pub async fn handle_lseek<T: Guest<Sandbox>>(
                         let errno = match e {
                             crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                             crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                            crate::vfs::VfsError::InvalidInput(_) => -libc::EINVAL as i64,
+                            crate::vfs::VfsError::IoError(e) => {
+                                -e.raw_os_error().unwrap_or(libc::EIO) as i64
+                            }
                             _ => -libc::EIO as i64,
                         };
                         return Ok(crate::syscall::SyscallResult::Value(errno));
@@ -1267,6 +1527,38 @@ pub async fn handle_access<T: Guest<Sandbox>>(
     Ok(None)
 }
 
+/// The `faccessat` system call.
+///
+/// This intercepts `faccessat` system calls, translates the path according to
+/// the mount table, and virtualizes the dirfd.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_faccessat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Faccessat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    let dirfd = args.dirfd();
+    if args.mode() & libc::W_OK != 0 {
+        check_writable_at(guest, dirfd, path_addr, mount_table, fd_table)?;
+    }
+
+    let kernel_dirfd = kernel_dirfd(dirfd, fd_table);
+    let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+    if new_path_addr.is_some() || kernel_dirfd != dirfd {
+        let new_syscall = reverie::syscalls::Faccessat::new()
+            .with_dirfd(kernel_dirfd)
+            .with_path(new_path_addr.or(Some(path_addr)))
+            .with_mode(args.mode());
+        return Ok(Some(guest.inject(Syscall::Faccessat(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
 /// The `faccessat2` system call.
 ///
 /// This intercepts `faccessat2` system calls, translates paths according to the mount table,
@@ -1285,25 +1577,18 @@ pub async fn handle_faccessat2<T: Guest<Sandbox>>(
     let mode = syscall_args.arg2 as i32;
     let flags = syscall_args.arg3 as i32;
 
-    // Check if dirfd needs virtualization
-    let dirfd_needs_translation = dirfd != libc::AT_FDCWD && fd_table.translate(dirfd).is_some();
+    if mode & libc::W_OK != 0 {
+        check_writable_at(guest, dirfd, pathname_addr, mount_table, fd_table)?;
+    }
 
-    // Check if path needs virtualization
+    let kernel_dirfd = kernel_dirfd(dirfd, fd_table);
     let translated_path_opt = translate_path(guest, pathname_addr, mount_table).await?;
-    let path_needs_translation = translated_path_opt.is_some();
 
     // If nothing needs virtualization, let the original syscall pass through
-    if !dirfd_needs_translation && !path_needs_translation {
+    if kernel_dirfd == dirfd && translated_path_opt.is_none() {
         return Ok(None);
     }
 
-    // Virtualize the dirfd if needed
-    let kernel_dirfd = if dirfd == libc::AT_FDCWD {
-        dirfd
-    } else {
-        fd_table.translate(dirfd).unwrap_or(dirfd)
-    };
-
     let new_path_addr = translated_path_opt.unwrap_or(pathname_addr);
     let new_path_raw: usize = unsafe { std::mem::transmute(new_path_addr) };
 
@@ -1328,123 +1613,1114 @@ pub async fn handle_faccessat2<T: Guest<Sandbox>>(
 /// The `rename` system call.
 ///
 /// This intercepts `rename` system calls and translates both paths according to the mount table.
+/// In a virtual filesystem, the VFS renames the entry.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
 pub async fn handle_rename<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Rename,
     mount_table: &MountTable,
-) -> Result<Option<Syscall>, Error> {
-    // Only translate if we need to - otherwise pass through unchanged
-    let oldpath_needs_translation = args.oldpath().is_some();
-    let newpath_needs_translation = args.newpath().is_some();
-
-    if !oldpath_needs_translation && !newpath_needs_translation {
+) -> Result<Option<i64>, Error> {
+    let (Some(oldpath_addr), Some(newpath_addr)) = (args.oldpath(), args.newpath()) else {
         return Ok(None);
+    };
+    check_writable(guest, oldpath_addr, mount_table)?;
+    check_writable(guest, newpath_addr, mount_table)?;
+    let oldpath: std::path::PathBuf = oldpath_addr.read(&guest.memory())?;
+    let newpath: std::path::PathBuf = newpath_addr.read(&guest.memory())?;
+    if let Some(result) =
+        virtual_rename(mount_table, &oldpath, &newpath, RenameFlags::empty()).await
+    {
+        return Ok(Some(result));
     }
 
-    // Build new syscall with translated paths
-    let mut new_syscall = reverie::syscalls::Rename::new();
+    let mut new_syscall = reverie::syscalls::Rename::new()
+        .with_oldpath(Some(oldpath_addr))
+        .with_newpath(Some(newpath_addr));
     let mut modified = false;
-
-    // Translate oldpath
-    if let Some(oldpath_addr) = args.oldpath() {
-        if let Some(new_path_addr) = translate_path(guest, oldpath_addr, mount_table).await? {
-            new_syscall = new_syscall.with_oldpath(Some(new_path_addr));
-            modified = true;
-        } else {
-            new_syscall = new_syscall.with_oldpath(Some(oldpath_addr));
-        }
+    if let Some(new_path_addr) = translate_path(guest, oldpath_addr, mount_table).await? {
+        new_syscall = new_syscall.with_oldpath(Some(new_path_addr));
+        modified = true;
     }
-
-    // Translate newpath
-    if let Some(newpath_addr) = args.newpath() {
-        if let Some(new_path_addr) = translate_path(guest, newpath_addr, mount_table).await? {
-            new_syscall = new_syscall.with_newpath(Some(new_path_addr));
-            modified = true;
-        } else {
-            new_syscall = new_syscall.with_newpath(Some(newpath_addr));
-        }
+    if let Some(new_path_addr) = translate_path(guest, newpath_addr, mount_table).await? {
+        new_syscall = new_syscall.with_newpath(Some(new_path_addr));
+        modified = true;
     }
 
     if modified {
-        Ok(Some(Syscall::Rename(new_syscall)))
+        Ok(Some(guest.inject(Syscall::Rename(new_syscall)).await?))
     } else {
         Ok(None)
     }
 }
 
-/// The `unlink` system call.
+/// The `renameat` system call.
 ///
-/// This intercepts `unlink` system calls and translates paths according to the mount table.
-pub async fn handle_unlink<T: Guest<Sandbox>>(
+/// This intercepts `renameat` system calls, translates both paths according to
+/// the mount table, and virtualizes both dirfds.
+/// In a virtual filesystem, the VFS renames the entry.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_renameat<T: Guest<Sandbox>>(
     guest: &mut T,
-    args: &reverie::syscalls::Unlink,
+    args: &reverie::syscalls::Renameat,
     mount_table: &MountTable,
-) -> Result<Option<Syscall>, Error> {
-    if let Some(path_addr) = args.path() {
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
-            let new_syscall = reverie::syscalls::Unlink::new().with_path(Some(new_path_addr));
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let (Some(oldpath_addr), Some(newpath_addr)) = (args.oldpath(), args.newpath()) else {
+        return Ok(None);
+    };
+    let (olddirfd, newdirfd) = (args.olddirfd(), args.newdirfd());
+    check_writable_at(guest, olddirfd, oldpath_addr, mount_table, fd_table)?;
+    check_writable_at(guest, newdirfd, newpath_addr, mount_table, fd_table)?;
+    let oldpath = path_at(guest, olddirfd, oldpath_addr, fd_table)?;
+    let newpath = path_at(guest, newdirfd, newpath_addr, fd_table)?;
+    if let Some(result) =
+        virtual_rename(mount_table, &oldpath, &newpath, RenameFlags::empty()).await
+    {
+        return Ok(Some(result));
+    }
 
-            return Ok(Some(Syscall::Unlink(new_syscall)));
-        }
+    let kernel_olddirfd = kernel_dirfd(olddirfd, fd_table);
+    let kernel_newdirfd = kernel_dirfd(newdirfd, fd_table);
+    let mut modified = kernel_olddirfd != olddirfd || kernel_newdirfd != newdirfd;
+    let mut new_syscall = reverie::syscalls::Renameat::new()
+        .with_olddirfd(kernel_olddirfd)
+        .with_oldpath(Some(oldpath_addr))
+        .with_newdirfd(kernel_newdirfd)
+        .with_newpath(Some(newpath_addr));
+    if let Some(new_path_addr) = translate_path(guest, oldpath_addr, mount_table).await? {
+        new_syscall = new_syscall.with_oldpath(Some(new_path_addr));
+        modified = true;
+    }
+    if let Some(new_path_addr) = translate_path(guest, newpath_addr, mount_table).await? {
+        new_syscall = new_syscall.with_newpath(Some(new_path_addr));
+        modified = true;
+    }
+
+    if modified {
+        Ok(Some(guest.inject(Syscall::Renameat(new_syscall)).await?))
+    } else {
+        Ok(None)
     }
-    Ok(None)
 }
 
-/// The `readv` system call.
+/// The `renameat2` system call.
 ///
-/// This intercepts `readv` system calls and translates virtual FDs to kernel FDs.
-pub async fn handle_readv<T: Guest<Sandbox>>(
+/// Like `renameat`, but also passes on the `RENAME_*` flags. In a virtual
+/// filesystem, only `RENAME_NOREPLACE` is supported.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_renameat2<T: Guest<Sandbox>>(
     guest: &mut T,
-    args: &reverie::syscalls::Readv,
+    args: &reverie::syscalls::Renameat2,
+    mount_table: &MountTable,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
-    let virtual_fd = args.fd();
+    let (Some(oldpath_addr), Some(newpath_addr)) = (args.oldpath(), args.newpath()) else {
+        return Ok(None);
+    };
+    let (olddirfd, newdirfd) = (args.olddirfd(), args.newdirfd());
+    check_writable_at(guest, olddirfd, oldpath_addr, mount_table, fd_table)?;
+    check_writable_at(guest, newdirfd, newpath_addr, mount_table, fd_table)?;
+    let oldpath = path_at(guest, olddirfd, oldpath_addr, fd_table)?;
+    let newpath = path_at(guest, newdirfd, newpath_addr, fd_table)?;
+    if let Some(result) = virtual_rename(mount_table, &oldpath, &newpath, args.flags()).await {
+        return Ok(Some(result));
+    }
 
-    // Translate virtual FD to kernel FD
-    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
-        let new_syscall = reverie::syscalls::Readv::new()
-            .with_fd(kernel_fd)
-            .with_iov(args.iov());
+    // The kernel honors the flags on the host paths
+    let kernel_olddirfd = kernel_dirfd(olddirfd, fd_table);
+    let kernel_newdirfd = kernel_dirfd(newdirfd, fd_table);
+    let mut modified = kernel_olddirfd != olddirfd || kernel_newdirfd != newdirfd;
+    let mut new_syscall = reverie::syscalls::Renameat2::new()
+        .with_olddirfd(kernel_olddirfd)
+        .with_oldpath(Some(oldpath_addr))
+        .with_newdirfd(kernel_newdirfd)
+        .with_newpath(Some(newpath_addr))
+        .with_flags(args.flags());
+    if let Some(new_path_addr) = translate_path(guest, oldpath_addr, mount_table).await? {
+        new_syscall = new_syscall.with_oldpath(Some(new_path_addr));
+        modified = true;
+    }
+    if let Some(new_path_addr) = translate_path(guest, newpath_addr, mount_table).await? {
+        new_syscall = new_syscall.with_newpath(Some(new_path_addr));
+        modified = true;
+    }
 
-        let result = guest.inject(Syscall::Readv(new_syscall)).await?;
-        return Ok(Some(result));
+    if modified {
+        Ok(Some(guest.inject(Syscall::Renameat2(new_syscall)).await?))
+    } else {
+        Ok(None)
     }
+}
 
-    // FD not in table, let the original syscall through (will likely fail with EBADF)
-    Ok(None)
+/// Rename an entry of a virtual filesystem for `rename`, `renameat` and
+/// `renameat2`
+///
+/// Returns `None` if neither path is in a virtual filesystem, so the kernel
+/// should do the rename, or else 0 or a negated errno. As between
+/// filesystems on the host, renaming to another mount fails with `EXDEV`.
+async fn virtual_rename(
+    mount_table: &MountTable,
+    oldpath: &std::path::Path,
+    newpath: &std::path::Path,
+    flags: RenameFlags,
+) -> Option<i64> {
+    let old_mount = mount_table.resolve_mount(oldpath).map(|(mount, _)| mount);
+    let new_mount = mount_table.resolve_mount(newpath).map(|(mount, _)| mount);
+    let is_virtual = |mount: Option<&MountPoint>| mount.is_some_and(|m| m.vfs.is_virtual());
+    if !is_virtual(old_mount) && !is_virtual(new_mount) {
+        return None;
+    }
+    let vfs = match (old_mount, new_mount) {
+        (Some(old), Some(new)) if std::ptr::eq(old, new) => &old.vfs,
+        _ => return Some(-libc::EXDEV as i64),
+    };
+    if !(flags - RenameFlags::RENAME_NOREPLACE).is_empty() {
+        return Some(-libc::EINVAL as i64);
+    }
+    let noreplace = flags.contains(RenameFlags::RENAME_NOREPLACE);
+    Some(vfs_errno(vfs.rename(oldpath, newpath, noreplace).await))
 }
 
-/// The `writev` system call.
+/// The `unlinkat` system call.
 ///
-/// This intercepts `writev` system calls and translates virtual FDs to kernel FDs.
-pub async fn handle_writev<T: Guest<Sandbox>>(
+/// This intercepts `unlinkat` system calls, translates the path according to
+/// the mount table, and virtualizes the dirfd, so that a name relative to a
+/// directory opened in a mount is removed from that directory. In a virtual
+/// filesystem, the VFS removes the entry, or the directory with `AT_REMOVEDIR`.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_unlinkat<T: Guest<Sandbox>>(
     guest: &mut T,
-    args: &reverie::syscalls::Writev,
+    args: &reverie::syscalls::Unlinkat,
+    mount_table: &MountTable,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
-    let virtual_fd = args.fd();
-
-    // Translate virtual FD to kernel FD
-    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
-        let new_syscall = reverie::syscalls::Writev::new()
-            .with_fd(kernel_fd)
-            .with_iov(args.iov());
-
-        let result = guest.inject(Syscall::Writev(new_syscall)).await?;
-        return Ok(Some(result));
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    let dirfd = args.dirfd();
+    check_writable_at(guest, dirfd, path_addr, mount_table, fd_table)?;
+    let path = path_at(guest, dirfd, path_addr, fd_table)?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            let result = if args.flags().contains(AtFlags::AT_REMOVEDIR) {
+                vfs.rmdir(&path).await
+            } else {
+                vfs.unlink(&path).await
+            };
+            return Ok(Some(vfs_errno(result)));
+        }
     }
 
-    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    let kernel_dirfd = kernel_dirfd(dirfd, fd_table);
+    let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+    if new_path_addr.is_some() || kernel_dirfd != dirfd {
+        let new_syscall = reverie::syscalls::Unlinkat::new()
+            .with_dirfd(kernel_dirfd)
+            .with_path(new_path_addr.or(Some(path_addr)))
+            .with_flags(args.flags());
+        return Ok(Some(guest.inject(Syscall::Unlinkat(new_syscall)).await?));
+    }
     Ok(None)
 }
 
-/// The `pipe2` system call.
+/// The `unlink` system call.
 ///
-/// This intercepts `pipe2` system calls and virtualizes the returned file descriptors.
-pub async fn handle_pipe2<T: Guest<Sandbox>>(
+/// This intercepts `unlink` system calls and translates paths according to the mount table.
+/// In a virtual filesystem, the VFS removes the entry.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_unlink<T: Guest<Sandbox>>(
     guest: &mut T,
-    args: &reverie::syscalls::Pipe2,
-    fd_table: &FdTable,
+    args: &reverie::syscalls::Unlink,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    check_writable(guest, path_addr, mount_table)?;
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            return Ok(Some(vfs_errno(vfs.unlink(&path).await)));
+        }
+    }
+
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = reverie::syscalls::Unlink::new().with_path(Some(new_path_addr));
+        return Ok(Some(guest.inject(Syscall::Unlink(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `mkdirat` system call.
+///
+/// This intercepts `mkdirat` system calls, translates the path according to
+/// the mount table, and virtualizes the dirfd. In a virtual filesystem, the
+/// VFS creates the directory.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_mkdirat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Mkdirat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    let dirfd = args.dirfd();
+    check_writable_at(guest, dirfd, path_addr, mount_table, fd_table)?;
+    let path = path_at(guest, dirfd, path_addr, fd_table)?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            return Ok(Some(vfs_errno(vfs.mkdir(&path, args.mode().bits()).await)));
+        }
+    }
+
+    let kernel_dirfd = kernel_dirfd(dirfd, fd_table);
+    let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+    if new_path_addr.is_some() || kernel_dirfd != dirfd {
+        let new_syscall = reverie::syscalls::Mkdirat::new()
+            .with_dirfd(kernel_dirfd)
+            .with_path(new_path_addr.or(Some(path_addr)))
+            .with_mode(args.mode());
+        return Ok(Some(guest.inject(Syscall::Mkdirat(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `mkdir` system call.
+///
+/// This intercepts `mkdir` system calls and translates the path according to
+/// the mount table. In a virtual filesystem, the VFS creates the directory.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_mkdir<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Mkdir,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    check_writable(guest, path_addr, mount_table)?;
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            return Ok(Some(vfs_errno(vfs.mkdir(&path, args.mode().bits()).await)));
+        }
+    }
+
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = reverie::syscalls::Mkdir::new()
+            .with_path(Some(new_path_addr))
+            .with_mode(args.mode());
+        return Ok(Some(guest.inject(Syscall::Mkdir(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `rmdir` system call.
+///
+/// This intercepts `rmdir` system calls and translates the path according to
+/// the mount table. In a virtual filesystem, the VFS removes the directory.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_rmdir<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Rmdir,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    check_writable(guest, path_addr, mount_table)?;
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            return Ok(Some(vfs_errno(vfs.rmdir(&path).await)));
+        }
+    }
+
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = reverie::syscalls::Rmdir::new().with_path(Some(new_path_addr));
+        return Ok(Some(guest.inject(Syscall::Rmdir(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// Map the result of a VFS operation that modifies the filesystem to 0 or a
+/// negated errno
+fn vfs_errno(result: crate::vfs::VfsResult<()>) -> i64 {
+    match result {
+        Ok(()) => 0,
+        Err(crate::vfs::VfsError::NotFound) => -libc::ENOENT as i64,
+        Err(crate::vfs::VfsError::AlreadyExists) => -libc::EEXIST as i64,
+        Err(crate::vfs::VfsError::PermissionDenied) => -libc::EACCES as i64,
+        Err(crate::vfs::VfsError::SymlinkLoop) => -libc::ELOOP as i64,
+        Err(crate::vfs::VfsError::InvalidInput(_)) => -libc::EINVAL as i64,
+        Err(crate::vfs::VfsError::NotSupported) => -libc::EPERM as i64,
+        Err(crate::vfs::VfsError::IoError(e)) => -e.raw_os_error().unwrap_or(libc::EIO) as i64,
+        Err(_) => -libc::EIO as i64,
+    }
+}
+
+/// Change the mode of a file in a virtual filesystem, following symlinks.
+///
+/// Returns 0 or a negated errno.
+async fn virtual_chmod(vfs: &dyn Vfs, path: &std::path::Path, mode: u32) -> i64 {
+    match follow_symlinks(vfs, path).await {
+        Ok(path) => vfs_errno(vfs.chmod(&path, mode).await),
+        Err(e) => vfs_errno(Err(e)),
+    }
+}
+
+/// Change the owner of a file in a virtual filesystem.
+///
+/// Returns 0 or a negated errno.
+async fn virtual_chown(
+    vfs: &dyn Vfs,
+    path: &std::path::Path,
+    uid: u32,
+    gid: u32,
+    follow: bool,
+) -> i64 {
+    let path = if follow {
+        match follow_symlinks(vfs, path).await {
+            Ok(path) => path,
+            Err(e) => return vfs_errno(Err(e)),
+        }
+    } else {
+        path.to_path_buf()
+    };
+    vfs_errno(vfs.chown(&path, uid, gid).await)
+}
+
+/// The `chmod` system call.
+///
+/// This intercepts `chmod` system calls and translates the path according to
+/// the mount table. In a virtual filesystem, the VFS changes the mode.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_chmod<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Chmod,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    check_writable(guest, path_addr, mount_table)?;
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            return Ok(Some(virtual_chmod(&*vfs, &path, args.mode().bits()).await));
+        }
+    }
+
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = reverie::syscalls::Chmod::new()
+            .with_path(Some(new_path_addr))
+            .with_mode(args.mode());
+        return Ok(Some(guest.inject(Syscall::Chmod(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `fchmodat` system call.
+///
+/// This intercepts `fchmodat` system calls, translates the path according to
+/// the mount table, and virtualizes the dirfd. In a virtual filesystem, the
+/// VFS changes the mode.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_fchmodat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fchmodat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    let dirfd = args.dirfd();
+    check_writable_at(guest, dirfd, path_addr, mount_table, fd_table)?;
+    let path = path_at(guest, dirfd, path_addr, fd_table)?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            return Ok(Some(virtual_chmod(&*vfs, &path, args.mode().bits()).await));
+        }
+    }
+
+    let kernel_dirfd = kernel_dirfd(dirfd, fd_table);
+    let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+    if new_path_addr.is_some() || kernel_dirfd != dirfd {
+        let new_syscall = reverie::syscalls::Fchmodat::new()
+            .with_dirfd(kernel_dirfd)
+            .with_path(new_path_addr.or(Some(path_addr)))
+            .with_mode(args.mode());
+        return Ok(Some(guest.inject(Syscall::Fchmodat(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `fchmod` system call.
+///
+/// This intercepts `fchmod` system calls and translates the file descriptor.
+/// The mode of a virtual file is changed by the VFS of the file's mount.
+pub async fn handle_fchmod<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fchmod,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough {
+            kernel_fd, path, ..
+        }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let new_syscall = reverie::syscalls::Fchmod::new()
+                .with_fd(kernel_fd)
+                .with_mode(args.mode());
+            Ok(guest.inject(Syscall::Fchmod(new_syscall)).await?)
+        }
+        Some(FdEntry::Virtual { path, .. }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let Some((vfs, path)) =
+                path.and_then(|path| Some((mount_table.resolve(&path)?.0, path)))
+            else {
+                return Ok(-libc::EPERM as i64);
+            };
+            Ok(virtual_chmod(&*vfs, &path, args.mode().bits()).await)
+        }
+        None => Ok(-libc::EBADF as i64),
+    }
+}
+
+/// The `truncate` system call.
+///
+/// This intercepts `truncate` system calls and translates the path according
+/// to the mount table. In a virtual filesystem, the VFS resizes the file.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_truncate<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Truncate,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    check_writable(guest, path_addr, mount_table)?;
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            if args.length() < 0 {
+                return Ok(Some(-libc::EINVAL as i64));
+            }
+            return Ok(Some(vfs_errno(vfs.truncate(&path, args.length()).await)));
+        }
+    }
+
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = reverie::syscalls::Truncate::new()
+            .with_path(Some(new_path_addr))
+            .with_length(args.length());
+        return Ok(Some(guest.inject(Syscall::Truncate(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `ftruncate` system call.
+///
+/// This intercepts `ftruncate` system calls and translates the file
+/// descriptor. A virtual file is resized through its file operations, so
+/// that writes other FDs buffered past the new end are dropped with it.
+pub async fn handle_ftruncate<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Ftruncate,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough {
+            kernel_fd, path, ..
+        }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let new_syscall = reverie::syscalls::Ftruncate::new()
+                .with_fd(kernel_fd)
+                .with_length(args.length());
+            Ok(guest.inject(Syscall::Ftruncate(new_syscall)).await?)
+        }
+        Some(FdEntry::Virtual { file_ops, path, .. }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            if args.length() < 0 {
+                return Ok(-libc::EINVAL as i64);
+            }
+            Ok(vfs_errno(file_ops.set_len(args.length()).await))
+        }
+        None => Ok(-libc::EBADF as i64),
+    }
+}
+
+/// The `chown` system call.
+///
+/// This intercepts `chown` system calls and translates the path according to
+/// the mount table. In a virtual filesystem, the VFS changes the owner.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_chown<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Chown,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    check_writable(guest, path_addr, mount_table)?;
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            return Ok(Some(
+                virtual_chown(&*vfs, &path, args.owner(), args.group(), true).await,
+            ));
+        }
+    }
+
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = reverie::syscalls::Chown::new()
+            .with_path(Some(new_path_addr))
+            .with_owner(args.owner())
+            .with_group(args.group());
+        return Ok(Some(guest.inject(Syscall::Chown(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `lchown` system call.
+///
+/// Like `chown`, but a symlink itself is changed instead of its target.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_lchown<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Lchown,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    check_writable(guest, path_addr, mount_table)?;
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            return Ok(Some(
+                virtual_chown(&*vfs, &path, args.owner(), args.group(), false).await,
+            ));
+        }
+    }
+
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = reverie::syscalls::Lchown::new()
+            .with_path(Some(new_path_addr))
+            .with_owner(args.owner())
+            .with_group(args.group());
+        return Ok(Some(guest.inject(Syscall::Lchown(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `fchownat` system call.
+///
+/// This intercepts `fchownat` system calls, translates the path according to
+/// the mount table, and virtualizes the dirfd. In a virtual filesystem, the
+/// VFS changes the owner. With `AT_EMPTY_PATH` and an empty path, the dirfd
+/// itself is changed, like with `fchown`.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_fchownat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fchownat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    let dirfd = args.dirfd();
+    let flags = args.flags();
+    let relative: std::path::PathBuf = path_addr.read(&guest.memory())?;
+    if relative.as_os_str().is_empty() && flags.contains(AtFlags::AT_EMPTY_PATH) {
+        return fchown_entry(
+            guest,
+            dirfd,
+            args.owner(),
+            args.group(),
+            mount_table,
+            fd_table,
+        )
+        .await
+        .map(Some);
+    }
+    check_writable_at(guest, dirfd, path_addr, mount_table, fd_table)?;
+    let path = path_at(guest, dirfd, path_addr, fd_table)?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            let follow = !flags.contains(AtFlags::AT_SYMLINK_NOFOLLOW);
+            return Ok(Some(
+                virtual_chown(&*vfs, &path, args.owner(), args.group(), follow).await,
+            ));
+        }
+    }
+
+    let kernel_dirfd = kernel_dirfd(dirfd, fd_table);
+    let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+    if new_path_addr.is_some() || kernel_dirfd != dirfd {
+        let new_syscall = reverie::syscalls::Fchownat::new()
+            .with_dirfd(kernel_dirfd)
+            .with_path(new_path_addr.or(Some(path_addr)))
+            .with_owner(args.owner())
+            .with_group(args.group())
+            .with_flags(flags);
+        return Ok(Some(guest.inject(Syscall::Fchownat(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `fchown` system call.
+///
+/// This intercepts `fchown` system calls and translates the file descriptor.
+/// The owner of a virtual file is changed by the VFS of the file's mount.
+pub async fn handle_fchown<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fchown,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    fchown_entry(
+        guest,
+        args.fd(),
+        args.owner(),
+        args.group(),
+        mount_table,
+        fd_table,
+    )
+    .await
+}
+
+/// Change the owner of the file open as `fd`, for `fchown` and `fchownat`
+/// with `AT_EMPTY_PATH`.
+async fn fchown_entry<T: Guest<Sandbox>>(
+    guest: &mut T,
+    fd: i32,
+    uid: u32,
+    gid: u32,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    match fd_table.get(fd) {
+        Some(FdEntry::Passthrough {
+            kernel_fd, path, ..
+        }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let new_syscall = reverie::syscalls::Fchown::new()
+                .with_fd(kernel_fd)
+                .with_owner(uid)
+                .with_group(gid);
+            Ok(guest.inject(Syscall::Fchown(new_syscall)).await?)
+        }
+        Some(FdEntry::Virtual { path, .. }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let Some((vfs, path)) =
+                path.and_then(|path| Some((mount_table.resolve(&path)?.0, path)))
+            else {
+                return Ok(-libc::EPERM as i64);
+            };
+            Ok(virtual_chown(&*vfs, &path, uid, gid, true).await)
+        }
+        None => Ok(-libc::EBADF as i64),
+    }
+}
+
+/// Set the times of `path` in a virtual filesystem, following symlinks if `follow` is set.
+async fn virtual_set_times(
+    vfs: &dyn Vfs,
+    path: &std::path::Path,
+    atime: Option<i64>,
+    mtime: Option<i64>,
+    follow: bool,
+) -> i64 {
+    let path = if follow {
+        match follow_symlinks(vfs, path).await {
+            Ok(path) => path,
+            Err(e) => return vfs_errno(Err(e)),
+        }
+    } else {
+        path.to_path_buf()
+    };
+    vfs_errno(vfs.set_times(&path, atime, mtime).await)
+}
+
+/// Read the access and modification times of a `utimensat` call as seconds,
+/// with `None` for `UTIME_OMIT`.
+///
+/// A null `times` sets both to the current time, as does `UTIME_NOW`. Returns
+/// `None` if a nanosecond field is out of range.
+fn utimensat_times<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Utimensat,
+) -> Result<Option<[Option<i64>; 2]>, Error> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let Some(times_addr) = args.times() else {
+        return Ok(Some([Some(now); 2]));
+    };
+    let times: [reverie::syscalls::Timespec; 2] = guest.memory().read_value(times_addr)?;
+    let mut secs = [None; 2];
+    for (secs, time) in secs.iter_mut().zip(times) {
+        *secs = match time.tv_nsec {
+            libc::UTIME_OMIT => None,
+            libc::UTIME_NOW => Some(now),
+            0..=999_999_999 => Some(time.tv_sec),
+            _ => return Ok(None),
+        };
+    }
+    Ok(Some(secs))
+}
+
+/// The `utimensat` system call.
+///
+/// This intercepts `utimensat` system calls and translates the path according to
+/// the mount table. In a virtual filesystem, the VFS sets the times, which it keeps
+/// in whole seconds. A null path, as `futimens` passes, or an empty path with
+/// `AT_EMPTY_PATH` sets the times of the file open as `dirfd`.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_utimensat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Utimensat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let dirfd = args.dirfd();
+    let flags = args.flags();
+    let path_addr = match args.path() {
+        Some(path_addr) => {
+            let relative: std::path::PathBuf = path_addr.read(&guest.memory())?;
+            if relative.as_os_str().is_empty() && flags.contains(AtFlags::AT_EMPTY_PATH) {
+                None
+            } else {
+                Some(path_addr)
+            }
+        }
+        None => None,
+    };
+    let Some(path_addr) = path_addr else {
+        return futimens_entry(guest, args, mount_table, fd_table)
+            .await
+            .map(Some);
+    };
+    check_writable_at(guest, dirfd, path_addr, mount_table, fd_table)?;
+    let path = path_at(guest, dirfd, path_addr, fd_table)?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            let Some([atime, mtime]) = utimensat_times(guest, args)? else {
+                return Ok(Some(-libc::EINVAL as i64));
+            };
+            let follow = !flags.contains(AtFlags::AT_SYMLINK_NOFOLLOW);
+            return Ok(Some(
+                virtual_set_times(&*vfs, &path, atime, mtime, follow).await,
+            ));
+        }
+    }
+
+    let kernel_dirfd = kernel_dirfd(dirfd, fd_table);
+    let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+    if new_path_addr.is_some() || kernel_dirfd != dirfd {
+        let new_syscall = reverie::syscalls::Utimensat::new()
+            .with_dirfd(kernel_dirfd)
+            .with_path(new_path_addr.or(Some(path_addr)))
+            .with_times(args.times())
+            .with_flags(flags);
+        return Ok(Some(guest.inject(Syscall::Utimensat(new_syscall)).await?));
+    }
+
+    Ok(None)
+}
+
+/// Set the times of the file open as the `dirfd` of a `utimensat` call, for
+/// `futimens` and `AT_EMPTY_PATH`.
+async fn futimens_entry<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Utimensat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    match fd_table.get(args.dirfd()) {
+        Some(FdEntry::Passthrough {
+            kernel_fd, path, ..
+        }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let new_syscall = reverie::syscalls::Utimensat::new()
+                .with_dirfd(kernel_fd)
+                .with_path(args.path())
+                .with_times(args.times())
+                .with_flags(args.flags());
+            Ok(guest.inject(Syscall::Utimensat(new_syscall)).await?)
+        }
+        Some(FdEntry::Virtual { path, .. }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let Some((vfs, path)) =
+                path.and_then(|path| Some((mount_table.resolve(&path)?.0, path)))
+            else {
+                return Ok(-libc::EPERM as i64);
+            };
+            let Some([atime, mtime]) = utimensat_times(guest, args)? else {
+                return Ok(-libc::EINVAL as i64);
+            };
+            Ok(virtual_set_times(&*vfs, &path, atime, mtime, true).await)
+        }
+        None => Ok(-libc::EBADF as i64),
+    }
+}
+
+/// The `linkat` system call.
+///
+/// This intercepts `linkat` system calls and translates both paths according to
+/// the mount table. With `AT_EMPTY_PATH` and a virtual FD, such as an `O_TMPFILE`
+/// file, the FD's inode is linked at `newpath` by the VFS of the FD's mount.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_linkat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Linkat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let Some(newpath_addr) = args.newpath() else {
+        return Ok(None);
+    };
+    check_writable(guest, newpath_addr, mount_table)?;
+    let newpath: std::path::PathBuf = newpath_addr.read(&guest.memory())?;
+
+    if args.flags().contains(AtFlags::AT_EMPTY_PATH) {
+        if let Some(FdEntry::Virtual {
+            file_ops,
+            flags,
+            path,
+        }) = fd_table.get(args.olddirfd())
+        {
+            return Ok(Some(
+                link_virtual_fd(&file_ops, flags, path.as_deref(), &newpath, mount_table).await,
+            ));
+        }
+    }
+
+    // Hard links by path are not supported in virtual filesystems
+    let mut paths = vec![newpath];
+    if let Some(oldpath_addr) = args.oldpath() {
+        paths.push(oldpath_addr.read(&guest.memory())?);
+    }
+    if paths.iter().any(|path| {
+        mount_table
+            .resolve(path)
+            .is_some_and(|(vfs, _)| vfs.is_virtual())
+    }) {
+        return Ok(Some(-libc::EPERM as i64));
+    }
+
+    let mut new_syscall = reverie::syscalls::Linkat::new()
+        .with_olddirfd(kernel_dirfd(args.olddirfd(), fd_table))
+        .with_oldpath(args.oldpath())
+        .with_newdirfd(kernel_dirfd(args.newdirfd(), fd_table))
+        .with_newpath(Some(newpath_addr))
+        .with_flags(args.flags());
+    if let Some(oldpath_addr) = args.oldpath() {
+        if let Some(new_path_addr) = translate_path(guest, oldpath_addr, mount_table).await? {
+            new_syscall = new_syscall.with_oldpath(Some(new_path_addr));
+        }
+    }
+    if let Some(new_path_addr) = translate_path(guest, newpath_addr, mount_table).await? {
+        new_syscall = new_syscall.with_newpath(Some(new_path_addr));
+    }
+
+    Ok(Some(guest.inject(Syscall::Linkat(new_syscall)).await?))
+}
+
+/// Link the file of a virtual FD opened at `path` with `flags` at `newpath`
+///
+/// Returns 0 or a negated errno.
+async fn link_virtual_fd(
+    file_ops: &BoxedFileOps,
+    flags: i32,
+    path: Option<&std::path::Path>,
+    newpath: &std::path::Path,
+    mount_table: &MountTable,
+) -> i64 {
+    let Some((mount, _)) = path.and_then(|path| mount_table.resolve_mount(path)) else {
+        return -libc::EBADF as i64;
+    };
+    match mount_table.resolve_mount(newpath) {
+        Some((new_mount, _)) if std::ptr::eq(mount, new_mount) => {}
+        _ => return -libc::EXDEV as i64,
+    }
+
+    // Store buffered writes so the contents are visible under the new name
+    if file_ops.fsync().await.is_err() {
+        return -libc::EIO as i64;
+    }
+    let stat = match file_ops.fstat().await {
+        Ok(stat) => stat,
+        Err(_) => return -libc::EIO as i64,
+    };
+    // O_TMPFILE | O_EXCL files can never be linked
+    if stat.st_nlink == 0 && flags & libc::O_EXCL != 0 {
+        return -libc::ENOENT as i64;
+    }
+
+    match mount.vfs.link_inode(stat.st_ino, newpath).await {
+        Ok(()) => 0,
+        Err(crate::vfs::VfsError::NotFound) => -libc::ENOENT as i64,
+        Err(crate::vfs::VfsError::AlreadyExists) => -libc::EEXIST as i64,
+        Err(crate::vfs::VfsError::NotSupported) => -libc::EPERM as i64,
+        Err(crate::vfs::VfsError::SymlinkLoop) => -libc::ELOOP as i64,
+        Err(_) => -libc::EIO as i64,
+    }
+}
+
+/// The `mknodat` system call.
+///
+/// This intercepts `mknodat` system calls and translates the path according to
+/// the mount table and virtualizes the dirfd. In a virtual filesystem, the VFS
+/// creates the file, which supports regular files, named pipes, sockets, and
+/// device nodes.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_mknodat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Mknodat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    check_writable_at(guest, args.dirfd(), path_addr, mount_table, fd_table)?;
+    let path = path_at(guest, args.dirfd(), path_addr, fd_table)?;
+
+    if let Some((vfs, _)) = mount_table.resolve(&path) {
+        if vfs.is_virtual() {
+            let result = match vfs.mknod(&path, args.mode().bits(), args.dev()).await {
+                Ok(()) => 0,
+                Err(crate::vfs::VfsError::NotFound) => -libc::ENOENT as i64,
+                Err(crate::vfs::VfsError::AlreadyExists) => -libc::EEXIST as i64,
+                Err(crate::vfs::VfsError::PermissionDenied) => -libc::EACCES as i64,
+                Err(crate::vfs::VfsError::InvalidInput(_)) => -libc::EINVAL as i64,
+                // Like creating a device node without privileges
+                Err(crate::vfs::VfsError::NotSupported) => -libc::EPERM as i64,
+                Err(_) => -libc::EIO as i64,
+            };
+            return Ok(Some(result));
+        }
+    }
+
+    let dirfd = args.dirfd();
+    let kernel_dirfd = kernel_dirfd(dirfd, fd_table);
+    let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+    if new_path_addr.is_some() || kernel_dirfd != dirfd {
+        let new_syscall = reverie::syscalls::Mknodat::new()
+            .with_dirfd(kernel_dirfd)
+            .with_path(new_path_addr.or(Some(path_addr)))
+            .with_mode(args.mode())
+            .with_dev(args.dev());
+        return Ok(Some(guest.inject(Syscall::Mknodat(new_syscall)).await?));
+    }
+    Ok(None)
+}
+
+/// The `readv` system call.
+///
+/// This intercepts `readv` system calls and translates virtual FDs to kernel FDs.
+pub async fn handle_readv<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Readv,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let virtual_fd = args.fd();
+
+    // Translate virtual FD to kernel FD
+    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        let new_syscall = reverie::syscalls::Readv::new()
+            .with_fd(kernel_fd)
+            .with_iov(args.iov());
+
+        let result = guest.inject(Syscall::Readv(new_syscall)).await?;
+        return Ok(Some(result));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
+/// The `writev` system call.
+///
+/// This intercepts `writev` system calls and translates virtual FDs to kernel FDs.
+/// The bytes written are counted in the summary, but not recorded in the write log.
+pub async fn handle_writev<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Writev,
+    state: &SandboxState,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let virtual_fd = args.fd();
+
+    // Translate virtual FD to kernel FD
+    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        let new_syscall = reverie::syscalls::Writev::new()
+            .with_fd(kernel_fd)
+            .with_iov(args.iov());
+
+        let result = guest.inject(Syscall::Writev(new_syscall)).await?;
+        if result > 0 {
+            if let Some(path) = fd_table
+                .get(virtual_fd)
+                .and_then(|entry| entry.path().cloned())
+            {
+                state.record_written(&path, result as usize);
+            }
+        }
+        return Ok(Some(result));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
+/// The `pipe2` system call.
+///
+/// This intercepts `pipe2` system calls and virtualizes the returned file descriptors.
+pub async fn handle_pipe2<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Pipe2,
+    fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     use reverie::syscalls::MemoryAccess;
 
@@ -1470,8 +2746,20 @@ pub async fn handle_pipe2<T: Guest<Sandbox>>(
             };
 
             // Allocate virtual FDs for both pipe ends (pipes don't have paths)
-            let virtual_read_fd = fd_table.allocate(read_entry);
-            let virtual_write_fd = fd_table.allocate(write_entry);
+            let virtual_read_fd = allocate_fd(guest, fd_table, read_entry).await?;
+            if virtual_read_fd < 0 {
+                close_entry(guest, write_entry).await?;
+                return Ok(Some(virtual_read_fd));
+            }
+            let virtual_write_fd = allocate_fd(guest, fd_table, write_entry).await?;
+            if virtual_write_fd < 0 {
+                if let Some(entry) = fd_table.deallocate(virtual_read_fd as i32) {
+                    close_entry(guest, entry).await?;
+                }
+                return Ok(Some(virtual_write_fd));
+            }
+            let (virtual_read_fd, virtual_write_fd) =
+                (virtual_read_fd as i32, virtual_write_fd as i32);
 
             // Write each FD individually as bytes to avoid alignment issues
             let read_bytes = virtual_read_fd.to_ne_bytes();
@@ -1510,31 +2798,72 @@ pub async fn handle_socket<T: Guest<Sandbox>>(
             flags: 0,
             path: None,
         };
-        let virtual_fd = fd_table.allocate(entry);
-        Ok(Some(virtual_fd as i64))
+        Ok(Some(allocate_fd(guest, fd_table, entry).await?))
     } else {
         // Return the error code as-is
         Ok(Some(kernel_fd))
     }
 }
 
+/// Refuse a destination that the connect policy does not allow.
+///
+/// Returns the result to fail the syscall with, after recording the refusal
+/// in the network log, or `None` if the destination is allowed.
+fn refuse_destination(pid: i32, state: &SandboxState, sockaddr: &SockAddr) -> Option<i64> {
+    if state.connect_policy().allows(sockaddr) {
+        return None;
+    }
+    tracing::debug!(addr = %sockaddr, "destination refused by policy");
+    let result = -libc::ECONNREFUSED as i64;
+    if let Some(net_log) = state.net_log() {
+        net_log.record(pid, sockaddr.clone(), result);
+    }
+    Some(result)
+}
+
 /// The `sendto` system call.
 ///
 /// This intercepts `sendto` system calls and translates virtual FDs to kernel FDs.
+/// A destination address is checked against the connect policy like the
+/// address passed to `connect`.
 pub async fn handle_sendto<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Sendto,
+    state: &SandboxState,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     let virtual_fd = args.fd();
 
+    let sockaddr = match copy_sockaddr(guest, args.addr(), args.addrlen() as libc::socklen_t) {
+        Ok(sockaddr) => sockaddr,
+        Err(errno) => return Ok(Some(-(errno.into_raw() as i64))),
+    };
+    if let Some(addr) = sockaddr
+        .as_ref()
+        .and_then(|sockaddr| sockaddr.addr.as_ref())
+    {
+        if let Some(result) = refuse_destination(guest.pid().as_raw(), state, addr) {
+            return Ok(Some(result));
+        }
+    }
+
     // Translate virtual FD to kernel FD
     if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        let (addr, addrlen) = match &sockaddr {
+            Some(sockaddr) => {
+                let (addr, addrlen) = push_sockaddr(guest, sockaddr, state.mount_table()).await?;
+                (Some(addr), addrlen)
+            }
+            None => (None, 0),
+        };
+
         let new_syscall = reverie::syscalls::Sendto::new()
             .with_fd(kernel_fd)
             .with_buf(args.buf())
+            .with_len(args.len())
             .with_flags(args.flags())
-            .with_addr(args.addr());
+            .with_addr(addr)
+            .with_addrlen(addrlen as _);
 
         let result = guest.inject(Syscall::Sendto(new_syscall)).await?;
         return Ok(Some(result));
@@ -1544,23 +2873,275 @@ pub async fn handle_sendto<T: Guest<Sandbox>>(
     Ok(None)
 }
 
+/// The `sendmsg` system call.
+///
+/// This intercepts `sendmsg` system calls and translates virtual FDs to kernel FDs.
+/// A destination address in `msg_name` is checked against the connect policy
+/// like the address passed to `connect`.
+pub async fn handle_sendmsg<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Sendmsg,
+    state: &SandboxState,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let virtual_fd = args.fd();
+
+    let msg: Option<libc::msghdr> = match args.msg() {
+        Some(addr) => match guest.memory().read_value(addr) {
+            Ok(msg) => Some(msg),
+            Err(_) => return Ok(Some(-libc::EFAULT as i64)),
+        },
+        None => None,
+    };
+    let name = match &msg {
+        Some(msg) => match copy_msg_name(guest, msg) {
+            Ok(name) => name,
+            Err(errno) => return Ok(Some(-(errno.into_raw() as i64))),
+        },
+        None => None,
+    };
+    if let Some(addr) = name.as_ref().and_then(|name| name.addr.as_ref()) {
+        if let Some(result) = refuse_destination(guest.pid().as_raw(), state, addr) {
+            return Ok(Some(result));
+        }
+    }
+
+    // Translate virtual FD to kernel FD
+    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        // Inject a copy of the header, so that the name the kernel sees is
+        // the one that was checked
+        let msg = match &msg {
+            Some(msg) => Some(push_msghdr(guest, msg, name.as_ref(), state.mount_table()).await?),
+            None => None,
+        };
+
+        let new_syscall = reverie::syscalls::Sendmsg::new()
+            .with_fd(kernel_fd)
+            .with_msg(msg)
+            .with_flags(args.flags());
+
+        let result = guest.inject(Syscall::Sendmsg(new_syscall)).await?;
+        return Ok(Some(result));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
+/// The `sendmmsg` system call.
+///
+/// The messages are sent with one `sendmsg` each, as the kernel does, so that
+/// every destination is checked against the connect policy and the kernel is
+/// given the copy that was checked. Like the kernel, a failure after the first
+/// message ends the batch, and the number of messages sent is returned.
+pub async fn handle_sendmmsg<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Sendmmsg,
+    state: &SandboxState,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    // Even an FD that is not in the table must not skip the policy, so it is
+    // passed on as is and left for the kernel to reject
+    let kernel_fd = fd_table.translate(args.fd()).unwrap_or(args.fd());
+    let msgvec = args.msgvec();
+    let vlen = args.vlen().min(libc::UIO_MAXIOV as u32) as usize;
+
+    let mut sent = 0;
+    for i in 0..vlen {
+        let result = send_mmsghdr(guest, args, state, kernel_fd, msgvec, i).await?;
+        if result < 0 {
+            return Ok(Some(if sent == 0 { result } else { sent }));
+        }
+        sent += 1;
+    }
+    Ok(Some(sent))
+}
+
+/// Send message `i` of a `sendmmsg` batch, storing the number of bytes sent in
+/// its `msg_len`.
+///
+/// Returns the result of the `sendmsg`, or a negated errno if the message
+/// cannot be read or its destination is refused.
+async fn send_mmsghdr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Sendmmsg,
+    state: &SandboxState,
+    kernel_fd: i32,
+    msgvec: Option<reverie::syscalls::AddrMut<'_, libc::mmsghdr>>,
+    i: usize,
+) -> Result<i64, Error> {
+    let addr = msgvec.and_then(|msgvec| {
+        reverie::syscalls::AddrMut::<libc::mmsghdr>::from_raw(
+            msgvec.as_raw() + i * std::mem::size_of::<libc::mmsghdr>(),
+        )
+    });
+    let Some(addr) = addr else {
+        return Ok(-libc::EFAULT as i64);
+    };
+    let mut entry: libc::mmsghdr = match guest.memory().read_value(addr) {
+        Ok(entry) => entry,
+        Err(_) => return Ok(-libc::EFAULT as i64),
+    };
+    let name = match copy_msg_name(guest, &entry.msg_hdr) {
+        Ok(name) => name,
+        Err(errno) => return Ok(-(errno.into_raw() as i64)),
+    };
+    if let Some(dest) = name.as_ref().and_then(|name| name.addr.as_ref()) {
+        if let Some(result) = refuse_destination(guest.pid().as_raw(), state, dest) {
+            return Ok(result);
+        }
+    }
+
+    let msg = push_msghdr(guest, &entry.msg_hdr, name.as_ref(), state.mount_table()).await?;
+    let new_syscall = reverie::syscalls::Sendmsg::new()
+        .with_fd(kernel_fd)
+        .with_msg(Some(msg))
+        .with_flags(args.flags());
+    let result = guest.inject(Syscall::Sendmsg(new_syscall)).await?;
+    if result >= 0 {
+        entry.msg_len = result as libc::c_uint;
+        if guest.memory().write_value(addr, &entry).is_err() {
+            return Ok(-libc::EFAULT as i64);
+        }
+    }
+    Ok(result)
+}
+
 /// The `connect` system call.
 ///
 /// This intercepts `connect` system calls and translates virtual FDs to kernel FDs.
+/// The destination address is copied from guest memory so that it can be
+/// reported in strace output and checked against the connect policy, and the
+/// copy is what the kernel is given.
 pub async fn handle_connect<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Connect,
+    state: &SandboxState,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     let virtual_fd = args.fd();
 
+    let sockaddr = match copy_sockaddr(guest, args.uservaddr(), args.addrlen()) {
+        Ok(sockaddr) => sockaddr,
+        Err(errno) => return Ok(Some(-(errno.into_raw() as i64))),
+    };
+    let addr = sockaddr.as_ref().and_then(|sockaddr| sockaddr.addr.clone());
+    if state.is_strace_enabled() {
+        if let Some(addr) = &addr {
+            eprintln!(
+                "[{}] connect({}, {})",
+                guest.pid().as_raw(),
+                virtual_fd,
+                addr
+            );
+        }
+    }
+
+    // Refuse destinations not allowed by the connect policy
+    if let Some(addr) = &addr {
+        if let Some(result) = refuse_destination(guest.pid().as_raw(), state, addr) {
+            return Ok(Some(result));
+        }
+    }
+
     // Translate virtual FD to kernel FD
     if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        // Inject the copy that was checked, with AF_UNIX socket paths
+        // translated through the mount table
+        let (uservaddr, addrlen) = match &sockaddr {
+            Some(sockaddr) => {
+                let (addr, addrlen) = push_sockaddr(guest, sockaddr, state.mount_table()).await?;
+                (Some(addr), addrlen)
+            }
+            None => (None, args.addrlen()),
+        };
+
         let new_syscall = reverie::syscalls::Connect::new()
             .with_fd(kernel_fd)
-            .with_addrlen(args.addrlen());
+            .with_uservaddr(uservaddr)
+            .with_addrlen(addrlen);
+
+        let result = guest.inject(Syscall::Connect(new_syscall)).await;
+        if let (Some(net_log), Some(addr)) = (state.net_log(), addr) {
+            let value = match &result {
+                Ok(value) => *value,
+                Err(errno) => -(errno.into_raw() as i64),
+            };
+            net_log.record(guest.pid().as_raw(), addr, value);
+        }
+        return Ok(Some(result?));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
+/// The `bind` system call.
+///
+/// This intercepts `bind` system calls, translates virtual FDs to kernel FDs, and
+/// translates `AF_UNIX` socket paths according to the mount table.
+pub async fn handle_bind<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Bind,
+    state: &SandboxState,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let virtual_fd = args.fd();
+
+    let sockaddr = match copy_sockaddr(guest, args.umyaddr(), args.addrlen()) {
+        Ok(sockaddr) => sockaddr,
+        Err(errno) => return Ok(Some(-(errno.into_raw() as i64))),
+    };
+    if state.is_strace_enabled() {
+        if let Some(addr) = sockaddr
+            .as_ref()
+            .and_then(|sockaddr| sockaddr.addr.as_ref())
+        {
+            eprintln!("[{}] bind({}, {})", guest.pid().as_raw(), virtual_fd, addr);
+        }
+    }
+
+    // Translate virtual FD to kernel FD
+    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        // Translate AF_UNIX socket paths through the mount table
+        let (addr, addrlen) = match &sockaddr {
+            Some(sockaddr) => {
+                let (addr, addrlen) = push_sockaddr(guest, sockaddr, state.mount_table()).await?;
+                (Some(addr), addrlen)
+            }
+            None => (None, args.addrlen()),
+        };
+
+        let new_syscall = reverie::syscalls::Bind::new()
+            .with_fd(kernel_fd)
+            .with_umyaddr(addr)
+            .with_addrlen(addrlen);
+
+        let result = guest.inject(Syscall::Bind(new_syscall)).await?;
+        return Ok(Some(result));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
+/// The `listen` system call.
+///
+/// This intercepts `listen` system calls and translates virtual FDs to kernel FDs.
+pub async fn handle_listen<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Listen,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let virtual_fd = args.fd();
+
+    // Translate virtual FD to kernel FD
+    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        let new_syscall = reverie::syscalls::Listen::new()
+            .with_fd(kernel_fd)
+            .with_backlog(args.backlog());
 
-        let result = guest.inject(Syscall::Connect(new_syscall)).await?;
+        let result = guest.inject(Syscall::Listen(new_syscall)).await?;
         return Ok(Some(result));
     }
 
@@ -1568,6 +3149,79 @@ pub async fn handle_connect<T: Guest<Sandbox>>(
     Ok(None)
 }
 
+/// Virtualize the kernel FD returned by `accept` or `accept4`.
+async fn virtualize_accepted_fd<T: Guest<Sandbox>>(
+    guest: &mut T,
+    kernel_fd: i64,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    if kernel_fd >= 0 {
+        // Create passthrough FD entry (sockets don't have paths)
+        let entry = FdEntry::Passthrough {
+            kernel_fd: kernel_fd as i32,
+            flags: 0,
+            path: None,
+        };
+        allocate_fd(guest, fd_table, entry).await
+    } else {
+        // Return the error code as-is
+        Ok(kernel_fd)
+    }
+}
+
+/// The `accept` system call.
+///
+/// This intercepts `accept` system calls, translates the listening socket's virtual FD
+/// to a kernel FD, and virtualizes the returned connection FD.
+pub async fn handle_accept<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Accept,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let virtual_fd = args.sockfd();
+
+    // Translate virtual FD to kernel FD
+    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        let new_syscall = reverie::syscalls::Accept::new()
+            .with_sockfd(kernel_fd)
+            .with_sockaddr(args.sockaddr())
+            .with_addrlen(args.addrlen());
+
+        let result = guest.inject(Syscall::Accept(new_syscall)).await?;
+        return Ok(Some(virtualize_accepted_fd(guest, result, fd_table).await?));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
+/// The `accept4` system call.
+///
+/// This intercepts `accept4` system calls, translates the listening socket's virtual FD
+/// to a kernel FD, and virtualizes the returned connection FD.
+pub async fn handle_accept4<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Accept4,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let virtual_fd = args.sockfd();
+
+    // Translate virtual FD to kernel FD
+    if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
+        let new_syscall = reverie::syscalls::Accept4::new()
+            .with_sockfd(kernel_fd)
+            .with_sockaddr(args.sockaddr())
+            .with_addrlen(args.addrlen())
+            .with_flags(args.flags());
+
+        let result = guest.inject(Syscall::Accept4(new_syscall)).await?;
+        return Ok(Some(virtualize_accepted_fd(guest, result, fd_table).await?));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
 /// The `getpeername` system call.
 ///
 /// This intercepts `getpeername` system calls and translates virtual FDs to kernel FDs.
@@ -1,10 +1,11 @@
 pub mod file;
+pub mod net;
 pub mod process;
 pub mod stat;
 pub mod xattr;
 
 use crate::{
-    sandbox::Sandbox,
+    sandbox::{Sandbox, SandboxState},
     vfs::{fdtable::FdTable, mount::MountTable},
 };
 use reverie::{
@@ -58,8 +59,20 @@ pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
         None => return Ok(None), // No mount point matches, use original path
     };
 
-    // Convert translated path to a C string for the syscall
-    let new_path_str = translated_path.to_string_lossy().to_string();
+    write_path(guest, &translated_path).await.map(Some)
+}
+
+/// Write `path` to the guest stack as a C string, for use as the path
+/// argument of an injected syscall.
+///
+/// Like the paths from [`translate_path`], the string is cleaned up when the
+/// guest unwinds its stack after the syscall.
+pub(crate) async fn write_path<'a, T: Guest<Sandbox>>(
+    guest: &'a mut T,
+    path: &std::path::Path,
+) -> Result<PathPtr<'a>, Error> {
+    // Convert the path to a C string for the syscall
+    let new_path_str = path.to_string_lossy().to_string();
     let new_path_cstr = CString::new(new_path_str).map_err(|_| reverie::syscalls::Errno::EINVAL)?;
 
     // Allocate space on the guest stack and write the new path
@@ -78,11 +91,83 @@ pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
     // 3. Reverie treats these pointer types as thin wrappers around raw pointers
     // 4. PathPtr is a newtype around CStrPtr, which is compatible with a char* pointer
     // 5. The guest will read this as a const char* pointer for the syscall path argument
-    Ok(Some(unsafe {
+    Ok(unsafe {
         std::mem::transmute::<reverie::syscalls::AddrMut<'_, u8>, reverie::syscalls::PathPtr<'_>>(
             byte_addr,
         )
-    }))
+    })
+}
+
+/// Read a path argument, resolving a relative path against the path
+/// `dirfd` was opened with.
+///
+/// A relative path is returned as is with `AT_FDCWD` or a `dirfd` without a
+/// path.
+pub(crate) fn path_at<T: Guest<Sandbox>>(
+    guest: &T,
+    dirfd: i32,
+    path_addr: PathPtr<'_>,
+    fd_table: &FdTable,
+) -> Result<PathBuf, Error> {
+    let path: PathBuf = path_addr.read(&guest.memory())?;
+    let dir = if dirfd == libc::AT_FDCWD || path.is_absolute() {
+        None
+    } else {
+        fd_table.get(dirfd).and_then(|entry| entry.path().cloned())
+    };
+    Ok(match dir {
+        Some(dir) => dir.join(path),
+        None => path,
+    })
+}
+
+/// Fail with `EROFS` if a path resolves to a read-only mount.
+///
+/// Handlers of syscalls that modify the filesystem call this before
+/// translating the path, so all read-only mounts share one enforcement path.
+pub(crate) fn check_writable<T: Guest<Sandbox>>(
+    guest: &T,
+    path_addr: PathPtr<'_>,
+    mount_table: &MountTable,
+) -> Result<(), Error> {
+    let path: PathBuf = path_addr.read(&guest.memory())?;
+    if mount_table.is_read_only(&path) {
+        tracing::debug!(path = %path.display(), "write to read-only mount");
+        return Err(Error::Errno(reverie::syscalls::Errno::EROFS));
+    }
+    Ok(())
+}
+
+/// Fail with `EROFS` if the path of an `*at` system call resolves to a
+/// read-only mount.
+///
+/// Like [`check_writable`], but a relative path is resolved against the
+/// path `dirfd` was opened with, so a directory FD opened in a read-only
+/// mount cannot be used to modify it.
+pub(crate) fn check_writable_at<T: Guest<Sandbox>>(
+    guest: &T,
+    dirfd: i32,
+    path_addr: PathPtr<'_>,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<(), Error> {
+    let path = path_at(guest, dirfd, path_addr, fd_table)?;
+    if mount_table.is_read_only(&path) {
+        tracing::debug!(path = %path.display(), "write to read-only mount");
+        return Err(Error::Errno(reverie::syscalls::Errno::EROFS));
+    }
+    Ok(())
+}
+
+/// Fail with `EROFS` if a file descriptor's path is on a read-only mount.
+pub(crate) fn check_fd_writable(
+    path: Option<&std::path::Path>,
+    mount_table: &MountTable,
+) -> Result<(), Error> {
+    if path.is_some_and(|path| mount_table.is_read_only(path)) {
+        return Err(Error::Errno(reverie::syscalls::Errno::EROFS));
+    }
+    Ok(())
 }
 
 /// System call dispatch.
@@ -96,21 +181,25 @@ pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
 pub async fn dispatch_syscall<T: Guest<Sandbox>>(
     guest: &mut T,
     syscall: Syscall,
-    mount_table: &MountTable,
+    state: &SandboxState,
     fd_table: &FdTable,
 ) -> Result<SyscallResult, Error> {
+    let mount_table = state.mount_table();
     // FIXME: We need to intercept all system calls that use a path or file descriptor.
     match &syscall {
         Syscall::Openat(args) => {
             if let Some(result) = file::handle_openat(guest, args, mount_table, fd_table).await? {
+                if result >= 0 {
+                    state.record_open();
+                }
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
         Syscall::Read(args) => file::handle_read(guest, syscall, args, fd_table).await,
-        Syscall::Write(args) => file::handle_write(guest, syscall, args, fd_table).await,
-        Syscall::Close(args) => file::handle_close(guest, syscall, args, fd_table).await,
+        Syscall::Write(args) => file::handle_write(guest, syscall, args, state, fd_table).await,
+        Syscall::Close(args) => file::handle_close(guest, syscall, args, state, fd_table).await,
         Syscall::Dup(args) => {
             if let Some(result) = file::handle_dup(guest, args, fd_table).await? {
                 Ok(SyscallResult::Value(result))
@@ -133,28 +222,45 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
             }
         }
         Syscall::Fork(args) => {
-            if let Some(result) = process::handle_fork(guest, args, fd_table).await? {
+            if let Some(result) = process::handle_fork(guest, args, state, fd_table).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
         Syscall::Vfork(args) => {
-            if let Some(result) = process::handle_vfork(guest, args, fd_table).await? {
+            if let Some(result) = process::handle_vfork(guest, args, state, fd_table).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
         Syscall::Clone(args) => {
-            if let Some(result) = process::handle_clone(guest, args, fd_table).await? {
+            if let Some(result) = process::handle_clone(guest, args, state, fd_table).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
         Syscall::Clone3(args) => {
-            if let Some(result) = process::handle_clone3(guest, args, fd_table).await? {
+            if let Some(result) = process::handle_clone3(guest, args, state, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Chdir(args) => {
+            if let Some(result) = process::handle_chdir(guest, args, state).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fchdir(args) => Ok(SyscallResult::Value(
+            process::handle_fchdir(guest, args, state, fd_table).await?,
+        )),
+        Syscall::Getcwd(args) => {
+            if let Some(result) = process::handle_getcwd(guest, args, state).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
@@ -214,20 +320,88 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
+        Syscall::Linkat(args) => {
+            if let Some(result) = file::handle_linkat(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Mknodat(args) => {
+            if let Some(result) = file::handle_mknodat(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Getxattr(args) => {
+            if let Some(result) = xattr::handle_getxattr(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Setxattr(args) => {
+            if let Some(result) = xattr::handle_setxattr(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Listxattr(args) => {
+            if let Some(result) = xattr::handle_listxattr(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Removexattr(args) => {
+            if let Some(result) = xattr::handle_removexattr(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
         Syscall::Llistxattr(args) => {
-            if let Some(modified) = xattr::handle_llistxattr(guest, args, mount_table).await? {
-                Ok(SyscallResult::Syscall(modified))
+            if let Some(result) = xattr::handle_llistxattr(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
         Syscall::Lgetxattr(args) => {
-            if let Some(modified) = xattr::handle_lgetxattr(guest, args, mount_table).await? {
-                Ok(SyscallResult::Syscall(modified))
+            if let Some(result) = xattr::handle_lgetxattr(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
+        Syscall::Lsetxattr(args) => {
+            if let Some(result) = xattr::handle_lsetxattr(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Lremovexattr(args) => {
+            if let Some(result) = xattr::handle_lremovexattr(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fgetxattr(args) => Ok(SyscallResult::Value(
+            xattr::handle_fgetxattr(guest, args, mount_table, fd_table).await?,
+        )),
+        Syscall::Fsetxattr(args) => Ok(SyscallResult::Value(
+            xattr::handle_fsetxattr(guest, args, mount_table, fd_table).await?,
+        )),
+        Syscall::Flistxattr(args) => Ok(SyscallResult::Value(
+            xattr::handle_flistxattr(guest, args, mount_table, fd_table).await?,
+        )),
+        Syscall::Fremovexattr(args) => Ok(SyscallResult::Value(
+            xattr::handle_fremovexattr(guest, args, mount_table, fd_table).await?,
+        )),
         Syscall::Ioctl(args) => {
             if let Some(result) = file::handle_ioctl(guest, args, fd_table).await? {
                 Ok(SyscallResult::Value(result))
@@ -257,7 +431,9 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
             }
         }
         Syscall::Getdents64(args) => file::handle_getdents64(guest, syscall, args, fd_table).await,
-        Syscall::Fstat(args) => file::handle_fstat(guest, syscall, args, fd_table).await,
+        Syscall::Fstat(args) => {
+            file::handle_fstat(guest, syscall, args, mount_table, fd_table).await
+        }
         Syscall::Pread64(args) => {
             if let Some(result) = file::handle_pread64(guest, args, fd_table).await? {
                 Ok(SyscallResult::Value(result))
@@ -266,7 +442,7 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
             }
         }
         Syscall::Pwrite64(args) => {
-            if let Some(result) = file::handle_pwrite64(guest, args, fd_table).await? {
+            if let Some(result) = file::handle_pwrite64(guest, args, state, fd_table).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
@@ -281,7 +457,7 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
             }
         }
         Syscall::Writev(args) => {
-            if let Some(result) = file::handle_writev(guest, args, fd_table).await? {
+            if let Some(result) = file::handle_writev(guest, args, state, fd_table).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
@@ -302,14 +478,56 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
             }
         }
         Syscall::Sendto(args) => {
-            if let Some(result) = file::handle_sendto(guest, args, fd_table).await? {
+            if let Some(result) = file::handle_sendto(guest, args, state, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Sendmsg(args) => {
+            if let Some(result) = file::handle_sendmsg(guest, args, state, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Sendmmsg(args) => {
+            if let Some(result) = file::handle_sendmmsg(guest, args, state, fd_table).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
         Syscall::Connect(args) => {
-            if let Some(result) = file::handle_connect(guest, args, fd_table).await? {
+            if let Some(result) = file::handle_connect(guest, args, state, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Bind(args) => {
+            if let Some(result) = file::handle_bind(guest, args, state, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Listen(args) => {
+            if let Some(result) = file::handle_listen(guest, args, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Accept(args) => {
+            if let Some(result) = file::handle_accept(guest, args, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Accept4(args) => {
+            if let Some(result) = file::handle_accept4(guest, args, fd_table).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
@@ -366,15 +584,125 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
             }
         }
         Syscall::Rename(args) => {
-            if let Some(modified) = file::handle_rename(guest, args, mount_table).await? {
-                Ok(SyscallResult::Syscall(modified))
+            if let Some(result) = file::handle_rename(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
         Syscall::Unlink(args) => {
-            if let Some(modified) = file::handle_unlink(guest, args, mount_table).await? {
-                Ok(SyscallResult::Syscall(modified))
+            if let Some(result) = file::handle_unlink(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Mkdir(args) => {
+            if let Some(result) = file::handle_mkdir(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Mkdirat(args) => {
+            if let Some(result) = file::handle_mkdirat(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Rmdir(args) => {
+            if let Some(result) = file::handle_rmdir(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Faccessat(args) => {
+            if let Some(result) = file::handle_faccessat(guest, args, mount_table, fd_table).await?
+            {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Renameat(args) => {
+            if let Some(result) = file::handle_renameat(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Renameat2(args) => {
+            if let Some(result) = file::handle_renameat2(guest, args, mount_table, fd_table).await?
+            {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Unlinkat(args) => {
+            if let Some(result) = file::handle_unlinkat(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Chmod(args) => {
+            if let Some(result) = file::handle_chmod(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fchmodat(args) => {
+            if let Some(result) = file::handle_fchmodat(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fchmod(args) => Ok(SyscallResult::Value(
+            file::handle_fchmod(guest, args, mount_table, fd_table).await?,
+        )),
+        Syscall::Truncate(args) => {
+            if let Some(result) = file::handle_truncate(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Ftruncate(args) => Ok(SyscallResult::Value(
+            file::handle_ftruncate(guest, args, mount_table, fd_table).await?,
+        )),
+        Syscall::Chown(args) => {
+            if let Some(result) = file::handle_chown(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Lchown(args) => {
+            if let Some(result) = file::handle_lchown(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fchownat(args) => {
+            if let Some(result) = file::handle_fchownat(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fchown(args) => Ok(SyscallResult::Value(
+            file::handle_fchown(guest, args, mount_table, fd_table).await?,
+        )),
+        Syscall::Utimensat(args) => {
+            if let Some(result) = file::handle_utimensat(guest, args, mount_table, fd_table).await?
+            {
+                Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
@@ -425,13 +753,13 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                     }
                 }
                 _ => {
-                    eprintln!("WARNING: Unsupported syscall: {:?}", num);
+                    tracing::warn!("Unsupported syscall: {:?}", num);
                     Err(Error::Errno(reverie::syscalls::Errno::ENOSYS))
                 }
             }
         }
         _ => {
-            eprintln!("WARNING: Unsupported syscall: {:?}", syscall);
+            tracing::warn!("Unsupported syscall: {:?}", syscall);
             Err(Error::Errno(reverie::syscalls::Errno::ENOSYS))
         }
     }
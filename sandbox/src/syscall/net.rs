@@ -0,0 +1,674 @@
+use crate::{sandbox::Sandbox, vfs::mount::MountTable};
+use reverie::{
+    syscalls::{Addr, Errno, MemoryAccess},
+    Error, Guest, Stack,
+};
+use std::{
+    ffi::OsStr,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+/// Offset of the address payload in every `sockaddr` variant (after `sa_family`).
+const FAMILY_LEN: usize = std::mem::size_of::<libc::sa_family_t>();
+
+/// A socket address decoded from a guest `sockaddr` buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SockAddr {
+    /// An `AF_INET` address.
+    Inet(SocketAddrV4),
+    /// An `AF_INET6` address.
+    Inet6(SocketAddrV6),
+    /// An `AF_UNIX` socket bound to a filesystem path.
+    Unix(PathBuf),
+    /// An `AF_UNIX` socket in the abstract namespace (name without the leading NUL).
+    UnixAbstract(Vec<u8>),
+    /// An unnamed `AF_UNIX` socket.
+    UnixUnnamed,
+    /// An address family the sandbox does not decode.
+    Other(libc::sa_family_t),
+}
+
+impl SockAddr {
+    /// Decode a `sockaddr` from raw bytes as passed to `connect` or `bind`.
+    ///
+    /// The buffer must contain exactly `addrlen` bytes. Returns `None` if the
+    /// buffer is too short for the address family it claims to hold.
+    pub fn parse(buf: &[u8]) -> Option<SockAddr> {
+        if buf.len() < FAMILY_LEN {
+            return None;
+        }
+        let family = libc::sa_family_t::from_ne_bytes(buf[..FAMILY_LEN].try_into().ok()?);
+        match family as i32 {
+            libc::AF_INET => {
+                if buf.len() < std::mem::size_of::<libc::sockaddr_in>() {
+                    return None;
+                }
+                let port = u16::from_be_bytes([buf[2], buf[3]]);
+                let ip = Ipv4Addr::new(buf[4], buf[5], buf[6], buf[7]);
+                Some(SockAddr::Inet(SocketAddrV4::new(ip, port)))
+            }
+            libc::AF_INET6 => {
+                if buf.len() < std::mem::size_of::<libc::sockaddr_in6>() {
+                    return None;
+                }
+                let port = u16::from_be_bytes([buf[2], buf[3]]);
+                let flowinfo = u32::from_be_bytes(buf[4..8].try_into().ok()?);
+                let octets: [u8; 16] = buf[8..24].try_into().ok()?;
+                let scope_id = u32::from_ne_bytes(buf[24..28].try_into().ok()?);
+                Some(SockAddr::Inet6(SocketAddrV6::new(
+                    Ipv6Addr::from(octets),
+                    port,
+                    flowinfo,
+                    scope_id,
+                )))
+            }
+            libc::AF_UNIX => {
+                let path = &buf[FAMILY_LEN..];
+                if path.is_empty() {
+                    return Some(SockAddr::UnixUnnamed);
+                }
+                if path[0] == 0 {
+                    return Some(SockAddr::UnixAbstract(path[1..].to_vec()));
+                }
+                let end = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+                Some(SockAddr::Unix(PathBuf::from(OsStr::from_bytes(
+                    &path[..end],
+                ))))
+            }
+            _ => Some(SockAddr::Other(family)),
+        }
+    }
+}
+
+impl SockAddr {
+    /// Name of the address family, e.g. `AF_INET`
+    pub fn family_name(&self) -> String {
+        match self {
+            SockAddr::Inet(_) => "AF_INET".to_string(),
+            SockAddr::Inet6(_) => "AF_INET6".to_string(),
+            SockAddr::Unix(_) | SockAddr::UnixAbstract(_) | SockAddr::UnixUnnamed => {
+                "AF_UNIX".to_string()
+            }
+            SockAddr::Other(family) => family.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SockAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SockAddr::Inet(addr) => write!(f, "AF_INET {}", addr),
+            SockAddr::Inet6(addr) => write!(f, "AF_INET6 {}", addr),
+            SockAddr::Unix(path) => write!(f, "AF_UNIX {}", path.display()),
+            SockAddr::UnixAbstract(name) => {
+                write!(f, "AF_UNIX @{}", String::from_utf8_lossy(name))
+            }
+            SockAddr::UnixUnnamed => write!(f, "AF_UNIX (unnamed)"),
+            SockAddr::Other(family) => write!(f, "family {}", family),
+        }
+    }
+}
+
+/// A destination allowed by a [`ConnectPolicy`]
+///
+/// Parsed from `<addr>:<port>`, where the address is an IP address or a CIDR
+/// block (IPv6 in brackets) and the port is a number or `*` for any port:
+/// `127.0.0.1:80`, `10.0.0.0/8:*`, `[::1]:443`, `[fd00::/8]:*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectRule {
+    addr: IpAddr,
+    prefix_len: u8,
+    port: Option<u16>,
+}
+
+impl ConnectRule {
+    /// Check if the rule matches a destination address and port
+    pub fn matches(&self, ip: IpAddr, port: u16) -> bool {
+        if self.port.is_some_and(|p| p != port) {
+            return false;
+        }
+        match (self.addr, canonical_ip(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_matches(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Map IPv4-mapped IPv6 addresses to IPv4 so that IPv4 rules apply to them.
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// Check if the first `prefix_len` bits of two addresses are equal.
+fn prefix_matches(net: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let full_bytes = (prefix_len / 8) as usize;
+    if net[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+    let rem_bits = prefix_len % 8;
+    if rem_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rem_bits);
+    net[full_bytes] & mask == ip[full_bytes] & mask
+}
+
+impl std::str::FromStr for ConnectRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid connect rule '{}'. Expected format: <addr>[/<prefix>]:<port|*>.",
+                s
+            )
+        };
+
+        let (host, port) = s.rsplit_once(':').ok_or_else(invalid)?;
+        let port = match port {
+            "*" => None,
+            port => Some(port.parse::<u16>().map_err(|_| invalid())?),
+        };
+        // IPv6 addresses must be in brackets to separate them from the port
+        let (host, bracketed) = match host.strip_prefix('[') {
+            Some(host) => (host.strip_suffix(']').ok_or_else(invalid)?, true),
+            None => (host, false),
+        };
+        let (addr, prefix_len) = match host.split_once('/') {
+            Some((addr, prefix_len)) => (
+                addr.parse::<IpAddr>().map_err(|_| invalid())?,
+                Some(prefix_len.parse::<u8>().map_err(|_| invalid())?),
+            ),
+            None => (host.parse::<IpAddr>().map_err(|_| invalid())?, None),
+        };
+        if bracketed != addr.is_ipv6() {
+            return Err(invalid());
+        }
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = prefix_len.unwrap_or(max_prefix_len);
+        if prefix_len > max_prefix_len {
+            return Err(invalid());
+        }
+
+        Ok(ConnectRule {
+            addr,
+            prefix_len,
+            port,
+        })
+    }
+}
+
+/// Policy for outbound connections
+///
+/// With an allow-list, IP destinations are allowed only if they match one of
+/// its rules. Unix sockets are allowed unless `deny_unix` is set. Other address
+/// families are not restricted. The default policy allows everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectPolicy {
+    /// Allowed IP destinations, or `None` to allow all
+    pub allow: Option<Vec<ConnectRule>>,
+    /// Deny connections to Unix sockets
+    pub deny_unix: bool,
+}
+
+impl ConnectPolicy {
+    /// Check if a connection to `addr` is allowed
+    pub fn allows(&self, addr: &SockAddr) -> bool {
+        match addr {
+            SockAddr::Inet(addr) => self.allows_ip(IpAddr::V4(*addr.ip()), addr.port()),
+            SockAddr::Inet6(addr) => self.allows_ip(IpAddr::V6(*addr.ip()), addr.port()),
+            SockAddr::Unix(_) | SockAddr::UnixAbstract(_) | SockAddr::UnixUnnamed => {
+                !self.deny_unix
+            }
+            SockAddr::Other(_) => true,
+        }
+    }
+
+    fn allows_ip(&self, ip: IpAddr, port: u16) -> bool {
+        match &self.allow {
+            Some(rules) => rules.iter().any(|rule| rule.matches(ip, port)),
+            None => true,
+        }
+    }
+}
+
+/// A `sockaddr` copied out of guest memory.
+///
+/// Syscalls are injected with this copy rather than the guest's buffer, so
+/// the kernel acts on the address that was checked even if another thread
+/// changes the buffer in the meantime.
+pub(crate) struct GuestSockAddr {
+    /// The decoded address, or `None` if the bytes don't hold a valid one.
+    pub addr: Option<SockAddr>,
+    storage: libc::sockaddr_storage,
+    len: libc::socklen_t,
+}
+
+/// Copy a `sockaddr` of `addrlen` bytes out of guest memory.
+///
+/// Returns `Ok(None)` if the address pointer is NULL. Fails the way the
+/// kernel does when it copies the address in: with `EINVAL` if `addrlen` is
+/// larger than `sockaddr_storage`, and with `EFAULT` if the buffer cannot be
+/// read.
+pub(crate) fn copy_sockaddr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    addr: Option<Addr<libc::sockaddr>>,
+    addrlen: libc::socklen_t,
+) -> Result<Option<GuestSockAddr>, Errno> {
+    let addr = match addr {
+        Some(addr) => addr,
+        None => return Ok(None),
+    };
+    let len = addrlen as usize;
+    if len > std::mem::size_of::<libc::sockaddr_storage>() {
+        return Err(Errno::EINVAL);
+    }
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let buf = unsafe { std::slice::from_raw_parts_mut(&mut storage as *mut _ as *mut u8, len) };
+    guest
+        .memory()
+        .read_exact(addr.cast::<u8>(), buf)
+        .map_err(|_| Errno::EFAULT)?;
+    Ok(Some(GuestSockAddr {
+        addr: SockAddr::parse(buf),
+        storage,
+        len: addrlen,
+    }))
+}
+
+/// Copy the destination address in the `msg_name` of a `msghdr` out of
+/// guest memory.
+///
+/// Returns `Ok(None)` if there is no name. Like the kernel, a `msg_namelen`
+/// larger than `sockaddr_storage` is truncated rather than rejected.
+pub(crate) fn copy_msg_name<T: Guest<Sandbox>>(
+    guest: &mut T,
+    msg: &libc::msghdr,
+) -> Result<Option<GuestSockAddr>, Errno> {
+    let len = msg
+        .msg_namelen
+        .min(std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t);
+    copy_sockaddr(guest, Addr::from_raw(msg.msg_name as usize), len)
+}
+
+/// Encode a pathname `AF_UNIX` address.
+///
+/// Returns the `sockaddr_un` together with the address length to pass to the
+/// kernel, or `ENAMETOOLONG` if the path does not fit in `sun_path`.
+fn unix_sockaddr(path: &Path) -> Result<(libc::sockaddr_un, libc::socklen_t), Errno> {
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    let bytes = path.as_os_str().as_bytes();
+    // Leave room for the terminating NUL
+    if bytes.len() >= addr.sun_path.len() {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+    let len = FAMILY_LEN + bytes.len() + 1;
+    Ok((addr, len as libc::socklen_t))
+}
+
+/// Translate a pathname `AF_UNIX` address through the mount table.
+///
+/// Returns `Ok(None)` if the address needs no rewriting: it is not a pathname
+/// `AF_UNIX` address (abstract and unnamed sockets pass through unchanged) or
+/// no mount point matches. Sockets cannot live in a virtual filesystem, so a
+/// path under a virtual mount fails with `EOPNOTSUPP`.
+pub(crate) fn translate_unix_sockaddr(
+    sockaddr: &SockAddr,
+    mount_table: &MountTable,
+) -> Result<Option<(libc::sockaddr_un, libc::socklen_t)>, Errno> {
+    let path = match sockaddr {
+        SockAddr::Unix(path) => path,
+        _ => return Ok(None),
+    };
+    let (vfs, translated) = match mount_table.resolve(path) {
+        Some(result) => result,
+        None => return Ok(None),
+    };
+    if vfs.is_virtual() {
+        return Err(Errno::EOPNOTSUPP);
+    }
+    unix_sockaddr(&translated).map(Some)
+}
+
+impl GuestSockAddr {
+    /// The address to hand the kernel and its length.
+    ///
+    /// A pathname `AF_UNIX` address under a mount point is translated through
+    /// the mount table, and any other address is the copy that was decoded.
+    fn translate(
+        &self,
+        mount_table: &MountTable,
+    ) -> Result<(libc::sockaddr_storage, libc::socklen_t), Errno> {
+        let translated = match &self.addr {
+            Some(addr) => translate_unix_sockaddr(addr, mount_table)?,
+            None => None,
+        };
+        let (sockaddr_un, len) = match translated {
+            Some(result) => result,
+            None => return Ok((self.storage, self.len)),
+        };
+        if let Some(addr) = &self.addr {
+            tracing::debug!(addr = %addr, "translated unix socket path");
+        }
+
+        let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                &sockaddr_un as *const _ as *const u8,
+                &mut storage as *mut _ as *mut u8,
+                std::mem::size_of::<libc::sockaddr_un>(),
+            );
+        }
+        Ok((storage, len))
+    }
+}
+
+/// Push the `sockaddr` to inject in place of the guest's onto the guest stack.
+///
+/// The pushed address is [`GuestSockAddr`]'s copy, with `AF_UNIX` paths
+/// translated through the mount table. Returns the address and length to
+/// inject.
+pub(crate) async fn push_sockaddr<'a, T: Guest<Sandbox>>(
+    guest: &mut T,
+    sockaddr: &GuestSockAddr,
+    mount_table: &MountTable,
+) -> Result<(Addr<'a, libc::sockaddr>, libc::socklen_t), Error> {
+    let (storage, len) = sockaddr.translate(mount_table)?;
+
+    let mut stack = guest.stack().await;
+    let new_addr = stack.push(storage);
+    stack.commit()?;
+
+    Ok((new_addr.cast::<libc::sockaddr>().into(), len))
+}
+
+/// Push the `msghdr` to inject in place of the guest's onto the guest stack.
+///
+/// The pushed header is a copy of `msg` whose `msg_name` points to a pushed
+/// copy of `name`, as for [`push_sockaddr`], or is NULL if there is no name.
+pub(crate) async fn push_msghdr<'a, T: Guest<Sandbox>>(
+    guest: &mut T,
+    msg: &libc::msghdr,
+    name: Option<&GuestSockAddr>,
+    mount_table: &MountTable,
+) -> Result<Addr<'a, libc::msghdr>, Error> {
+    let name = match name {
+        Some(name) => Some(name.translate(mount_table)?),
+        None => None,
+    };
+
+    let mut stack = guest.stack().await;
+    let mut msg = *msg;
+    match name {
+        Some((storage, len)) => {
+            msg.msg_name = stack.push(storage).as_raw() as *mut libc::c_void;
+            msg.msg_namelen = len;
+        }
+        None => {
+            msg.msg_name = std::ptr::null_mut();
+            msg.msg_namelen = 0;
+        }
+    }
+    let new_msg = stack.push(msg);
+    stack.commit()?;
+
+    Ok(new_msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn as_bytes<T>(value: &T) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>())
+        }
+    }
+
+    #[test]
+    fn test_parse_inet() {
+        let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        addr.sin_family = libc::AF_INET as libc::sa_family_t;
+        addr.sin_port = 8080u16.to_be();
+        addr.sin_addr.s_addr = u32::from_ne_bytes([127, 0, 0, 1]);
+
+        let parsed = SockAddr::parse(as_bytes(&addr)).unwrap();
+        assert_eq!(
+            parsed,
+            SockAddr::Inet(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8080))
+        );
+        assert_eq!(parsed.to_string(), "AF_INET 127.0.0.1:8080");
+    }
+
+    #[test]
+    fn test_parse_inet_truncated() {
+        let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        addr.sin_family = libc::AF_INET as libc::sa_family_t;
+
+        assert_eq!(SockAddr::parse(&as_bytes(&addr)[..6]), None);
+        assert_eq!(SockAddr::parse(&[]), None);
+    }
+
+    #[test]
+    fn test_parse_inet6() {
+        let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+        addr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+        addr.sin6_port = 443u16.to_be();
+        addr.sin6_addr.s6_addr = Ipv6Addr::LOCALHOST.octets();
+
+        let parsed = SockAddr::parse(as_bytes(&addr)).unwrap();
+        assert_eq!(
+            parsed,
+            SockAddr::Inet6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 443, 0, 0))
+        );
+        assert_eq!(parsed.to_string(), "AF_INET6 [::1]:443");
+    }
+
+    #[test]
+    fn test_parse_unix_path() {
+        let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        for (dst, src) in addr.sun_path.iter_mut().zip(b"/tmp/test.sock") {
+            *dst = *src as libc::c_char;
+        }
+
+        // Full-size buffer, path is NUL-terminated
+        let parsed = SockAddr::parse(as_bytes(&addr)).unwrap();
+        assert_eq!(parsed, SockAddr::Unix(PathBuf::from("/tmp/test.sock")));
+        assert_eq!(parsed.to_string(), "AF_UNIX /tmp/test.sock");
+
+        // Exact-length buffer without a terminating NUL
+        let len = FAMILY_LEN + "/tmp/test.sock".len();
+        let parsed = SockAddr::parse(&as_bytes(&addr)[..len]).unwrap();
+        assert_eq!(parsed, SockAddr::Unix(PathBuf::from("/tmp/test.sock")));
+
+        // Paths need not be UTF-8
+        addr.sun_path[5] = 0xff_u8 as libc::c_char;
+        let parsed = SockAddr::parse(as_bytes(&addr)).unwrap();
+        assert_eq!(
+            parsed,
+            SockAddr::Unix(PathBuf::from(OsStr::from_bytes(b"/tmp/\xffest.sock")))
+        );
+    }
+
+    #[test]
+    fn test_parse_unix_abstract() {
+        let mut buf = (libc::AF_UNIX as libc::sa_family_t).to_ne_bytes().to_vec();
+        buf.extend_from_slice(b"\0agent");
+
+        let parsed = SockAddr::parse(&buf).unwrap();
+        assert_eq!(parsed, SockAddr::UnixAbstract(b"agent".to_vec()));
+        assert_eq!(parsed.to_string(), "AF_UNIX @agent");
+    }
+
+    #[test]
+    fn test_parse_unix_unnamed() {
+        let buf = (libc::AF_UNIX as libc::sa_family_t).to_ne_bytes();
+        assert_eq!(SockAddr::parse(&buf), Some(SockAddr::UnixUnnamed));
+    }
+
+    fn bind_mount() -> MountTable {
+        use crate::vfs::bind::BindVfs;
+        use std::sync::Arc;
+
+        let mut table = MountTable::new();
+        table.add_mount(
+            PathBuf::from("/sandbox"),
+            Arc::new(BindVfs::new(
+                PathBuf::from("/host/dir"),
+                PathBuf::from("/sandbox"),
+            )),
+        );
+        table
+    }
+
+    #[test]
+    fn test_translate_unix_path_under_mount() {
+        let sockaddr = SockAddr::Unix(PathBuf::from("/sandbox/test.sock"));
+        let (addr, len) = translate_unix_sockaddr(&sockaddr, &bind_mount())
+            .unwrap()
+            .unwrap();
+
+        let buf = &as_bytes(&addr)[..len as usize];
+        assert_eq!(
+            SockAddr::parse(buf),
+            Some(SockAddr::Unix(PathBuf::from("/host/dir/test.sock")))
+        );
+        assert_eq!(len as usize, FAMILY_LEN + "/host/dir/test.sock".len() + 1);
+    }
+
+    #[test]
+    fn test_translate_unix_path_outside_mount() {
+        let sockaddr = SockAddr::Unix(PathBuf::from("/tmp/test.sock"));
+        assert!(translate_unix_sockaddr(&sockaddr, &bind_mount())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_translate_unix_abstract_unchanged() {
+        let sockaddr = SockAddr::UnixAbstract(b"sandbox/test.sock".to_vec());
+        assert!(translate_unix_sockaddr(&sockaddr, &bind_mount())
+            .unwrap()
+            .is_none());
+
+        let sockaddr = SockAddr::Inet(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 80));
+        assert!(translate_unix_sockaddr(&sockaddr, &bind_mount())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_translate_unix_path_too_long() {
+        let name = "x".repeat(100);
+        let sockaddr = SockAddr::Unix(PathBuf::from(format!("/sandbox/{}", name)));
+        assert!(matches!(
+            translate_unix_sockaddr(&sockaddr, &bind_mount()),
+            Err(Errno::ENAMETOOLONG)
+        ));
+    }
+
+    fn inet(addr: &str) -> SockAddr {
+        SockAddr::Inet(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn test_connect_policy_allows_matching_port() {
+        let policy = ConnectPolicy {
+            allow: Some(vec!["127.0.0.1:80".parse().unwrap()]),
+            deny_unix: false,
+        };
+        assert!(policy.allows(&inet("127.0.0.1:80")));
+        assert!(!policy.allows(&inet("127.0.0.1:81")));
+        assert!(!policy.allows(&inet("127.0.0.2:80")));
+    }
+
+    #[test]
+    fn test_connect_policy_cidr_and_wildcard_port() {
+        let policy = ConnectPolicy {
+            allow: Some(vec![
+                "10.0.0.0/8:*".parse().unwrap(),
+                "192.168.1.0/25:443".parse().unwrap(),
+                "[fd00::/8]:*".parse().unwrap(),
+            ]),
+            deny_unix: false,
+        };
+        assert!(policy.allows(&inet("10.1.2.3:22")));
+        assert!(!policy.allows(&inet("11.0.0.1:22")));
+        assert!(policy.allows(&inet("192.168.1.127:443")));
+        assert!(!policy.allows(&inet("192.168.1.128:443")));
+        assert!(!policy.allows(&inet("192.168.1.1:80")));
+        assert!(policy.allows(&SockAddr::Inet6("[fd12::1]:8080".parse().unwrap())));
+        assert!(!policy.allows(&SockAddr::Inet6("[fe80::1]:8080".parse().unwrap())));
+    }
+
+    #[test]
+    fn test_connect_policy_ipv4_mapped() {
+        let policy = ConnectPolicy {
+            allow: Some(vec!["127.0.0.1:80".parse().unwrap()]),
+            deny_unix: false,
+        };
+        assert!(policy.allows(&SockAddr::Inet6("[::ffff:127.0.0.1]:80".parse().unwrap())));
+    }
+
+    #[test]
+    fn test_connect_policy_unix_sockets() {
+        let unix = SockAddr::Unix(PathBuf::from("/tmp/test.sock"));
+        let mut policy = ConnectPolicy {
+            allow: Some(Vec::new()),
+            deny_unix: false,
+        };
+        assert!(policy.allows(&unix));
+        assert!(policy.allows(&SockAddr::UnixAbstract(b"agent".to_vec())));
+        assert!(!policy.allows(&inet("127.0.0.1:80")));
+
+        policy.deny_unix = true;
+        assert!(!policy.allows(&unix));
+    }
+
+    #[test]
+    fn test_connect_policy_default_allows_all() {
+        let policy = ConnectPolicy::default();
+        assert!(policy.allows(&inet("1.2.3.4:443")));
+        assert!(policy.allows(&SockAddr::Unix(PathBuf::from("/tmp/test.sock"))));
+    }
+
+    #[test]
+    fn test_connect_rule_parse_errors() {
+        for rule in [
+            "127.0.0.1",
+            "127.0.0.1:http",
+            "localhost:80",
+            "10.0.0.0/33:*",
+            "[::1:80",
+            "::1:80",
+        ] {
+            assert!(rule.parse::<ConnectRule>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn test_parse_other_family() {
+        let buf = (libc::AF_NETLINK as libc::sa_family_t).to_ne_bytes();
+        assert_eq!(
+            SockAddr::parse(&buf),
+            Some(SockAddr::Other(libc::AF_NETLINK as libc::sa_family_t))
+        );
+    }
+}
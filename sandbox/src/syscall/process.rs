@@ -1,5 +1,17 @@
-use crate::{sandbox, sandbox::Sandbox, vfs::fdtable::FdTable};
-use reverie::{syscalls::Syscall, Error, Guest};
+use super::write_path;
+use crate::{
+    proctree,
+    sandbox::{Sandbox, SandboxState},
+    vfs::{
+        fdtable::{FdEntry, FdTable},
+        normalize_path,
+    },
+};
+use reverie::{
+    syscalls::{MemoryAccess, ReadAddr, Syscall},
+    Error, Guest,
+};
+use std::{os::unix::ffi::OsStrExt, path::PathBuf};
 
 /// The `fork` system call.
 ///
@@ -8,6 +20,7 @@ use reverie::{syscalls::Syscall, Error, Guest};
 pub async fn handle_fork<T: Guest<Sandbox>>(
     guest: &mut T,
     _args: &reverie::syscalls::Fork,
+    state: &SandboxState,
     parent_fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     // Execute the fork syscall
@@ -19,7 +32,9 @@ pub async fn handle_fork<T: Guest<Sandbox>>(
         // We are in the parent process - result is the child PID
         // Create a deep copy of our FD table for the child
         let child_fd_table = parent_fd_table.deep_clone();
-        sandbox::insert_fd_table(result as i32, child_fd_table);
+        state.insert_fd_table(result as i32, child_fd_table);
+        state.inherit_cwd(guest.pid().as_raw(), result as i32);
+        state.record_child(guest.pid().as_raw(), result as i32);
     }
     // If result == 0, we're in the child - the FD table was already set up by the parent
     // If result < 0, fork failed - no action needed
@@ -37,6 +52,7 @@ pub async fn handle_fork<T: Guest<Sandbox>>(
 pub async fn handle_vfork<T: Guest<Sandbox>>(
     guest: &mut T,
     _args: &reverie::syscalls::Vfork,
+    state: &SandboxState,
     parent_fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     // Execute the vfork syscall
@@ -49,7 +65,9 @@ pub async fn handle_vfork<T: Guest<Sandbox>>(
         // (Even though vfork semantics suggest sharing, it's safer to deep copy
         // since the child will exec or exit, and we need independent FD tracking)
         let child_fd_table = parent_fd_table.deep_clone();
-        sandbox::insert_fd_table(result as i32, child_fd_table);
+        state.insert_fd_table(result as i32, child_fd_table);
+        state.inherit_cwd(guest.pid().as_raw(), result as i32);
+        state.record_child(guest.pid().as_raw(), result as i32);
     }
 
     Ok(Some(result))
@@ -66,6 +84,7 @@ pub async fn handle_vfork<T: Guest<Sandbox>>(
 pub async fn handle_clone<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Clone,
+    state: &SandboxState,
     parent_fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     // Execute the clone syscall
@@ -75,18 +94,24 @@ pub async fn handle_clone<T: Guest<Sandbox>>(
         // We are in the parent process - result is the child PID/TID
         // Check if CLONE_FILES flag is set (0x00000400)
         const CLONE_FILES: i32 = 0x00000400;
+        // CLONE_THREAD (0x00010000) creates a thread, not a new process
+        const CLONE_THREAD: i32 = 0x00010000;
 
         let flags = args.flags();
         let share_fds = flags.bits() & CLONE_FILES != 0;
+        state.inherit_cwd(guest.pid().as_raw(), result as i32);
+        if flags.bits() & CLONE_THREAD == 0 {
+            state.record_child(guest.pid().as_raw(), result as i32);
+        }
 
         if share_fds {
             // CLONE_FILES set - share the FD table (shallow copy)
             // Both parent and child will see the same FdTable Arc
-            sandbox::insert_fd_table(result as i32, parent_fd_table.clone());
+            state.insert_fd_table(result as i32, parent_fd_table.clone());
         } else {
             // CLONE_FILES not set - create independent FD table (deep copy)
             let child_fd_table = parent_fd_table.deep_clone();
-            sandbox::insert_fd_table(result as i32, child_fd_table);
+            state.insert_fd_table(result as i32, child_fd_table);
         }
     }
     // If result == 0, we're in the child - FD table already set up by parent
@@ -102,6 +127,7 @@ pub async fn handle_clone<T: Guest<Sandbox>>(
 pub async fn handle_clone3<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Clone3,
+    state: &SandboxState,
     parent_fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     // Execute the clone3 syscall
@@ -113,8 +139,137 @@ pub async fn handle_clone3<T: Guest<Sandbox>>(
         // to get the flags. For now, we default to deep copy (safer).
         // TODO: Parse clone_args to check CLONE_FILES flag
         let child_fd_table = parent_fd_table.deep_clone();
-        sandbox::insert_fd_table(result as i32, child_fd_table);
+        state.insert_fd_table(result as i32, child_fd_table);
+        state.inherit_cwd(guest.pid().as_raw(), result as i32);
+        // Without the flags, ask the kernel whether the child is a thread
+        if !proctree::is_thread(result as i32) {
+            state.record_child(guest.pid().as_raw(), result as i32);
+        }
     }
 
     Ok(Some(result))
 }
+
+/// The `chdir` system call.
+///
+/// This intercepts `chdir` system calls to track the working directory as
+/// seen in the sandbox. A directory in a bind mount is entered through the
+/// host directory it is backed by, and remembered for [`handle_getcwd`].
+/// The kernel cannot enter a directory of a virtual mount, so that fails
+/// with `EACCES`. Relative paths are resolved against the tracked working
+/// directory, without following symlinks.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_chdir<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Chdir,
+    state: &SandboxState,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+    let path: PathBuf = path_addr.read(&guest.memory())?;
+    let pid = guest.pid().as_raw();
+    let cwd = state.cwd(pid);
+    let target = if path.is_absolute() {
+        normalize_path(&path)
+    } else {
+        // Outside mounts, the kernel's working directory is the one seen in the sandbox
+        let base = match &cwd {
+            Some(cwd) => cwd.clone(),
+            None => match std::fs::read_link(format!("/proc/{}/cwd", pid)) {
+                Ok(base) => base,
+                Err(_) => return Ok(None),
+            },
+        };
+        normalize_path(&base.join(&path))
+    };
+
+    match state.mount_table().resolve(&target) {
+        Some((vfs, _)) if vfs.is_virtual() => {
+            tracing::debug!(path = %target.display(), "chdir into a virtual mount");
+            Ok(Some(-libc::EACCES as i64))
+        }
+        Some((_, translated)) => {
+            let new_path_addr = write_path(guest, &translated).await?;
+            let new_syscall = reverie::syscalls::Chdir::new().with_path(Some(new_path_addr));
+            let result = guest.inject(Syscall::Chdir(new_syscall)).await?;
+            if result == 0 {
+                state.set_cwd(pid, Some(target));
+            }
+            Ok(Some(result))
+        }
+        None => {
+            // A relative path must not be resolved against the host
+            // directory of the mount the process is in
+            let new_path_addr = if cwd.is_some() && path.is_relative() {
+                write_path(guest, &target).await?
+            } else {
+                path_addr
+            };
+            let new_syscall = reverie::syscalls::Chdir::new().with_path(Some(new_path_addr));
+            let result = guest.inject(Syscall::Chdir(new_syscall)).await?;
+            if result == 0 {
+                state.set_cwd(pid, None);
+            }
+            Ok(Some(result))
+        }
+    }
+}
+
+/// The `fchdir` system call.
+///
+/// This intercepts `fchdir` system calls to translate the virtual FD and
+/// track the working directory like [`handle_chdir`], using the path the
+/// directory was opened with.
+pub async fn handle_fchdir<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fchdir,
+    state: &SandboxState,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    let (kernel_fd, path) = match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough {
+            kernel_fd, path, ..
+        }) => (kernel_fd, path),
+        Some(FdEntry::Virtual { .. }) => return Ok(-libc::EACCES as i64),
+        None => return Ok(-libc::EBADF as i64),
+    };
+    let new_syscall = reverie::syscalls::Fchdir::new().with_fd(kernel_fd);
+    let result = guest.inject(Syscall::Fchdir(new_syscall)).await?;
+    if result == 0 {
+        let cwd = path
+            .filter(|path| path.is_absolute())
+            .map(|path| normalize_path(&path))
+            .filter(|path| state.mount_table().resolve(path).is_some());
+        state.set_cwd(guest.pid().as_raw(), cwd);
+    }
+    Ok(result)
+}
+
+/// The `getcwd` system call.
+///
+/// This intercepts `getcwd` system calls so that a working directory inside
+/// a mount is reported as the sandbox path tracked by [`handle_chdir`]
+/// rather than the host directory the kernel knows.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_getcwd<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Getcwd,
+    state: &SandboxState,
+) -> Result<Option<i64>, Error> {
+    let Some(cwd) = state.cwd(guest.pid().as_raw()) else {
+        return Ok(None);
+    };
+    let Some(buf) = args.buf() else {
+        return Ok(Some(-libc::EFAULT as i64));
+    };
+    let mut bytes = cwd.as_os_str().as_bytes().to_vec();
+    bytes.push(0);
+    if bytes.len() > args.size() {
+        return Ok(Some(-libc::ERANGE as i64));
+    }
+    guest.memory().write_exact(buf.cast::<u8>(), &bytes)?;
+    Ok(Some(bytes.len() as i64))
+}
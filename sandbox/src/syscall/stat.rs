@@ -1,12 +1,98 @@
 use crate::{
     sandbox::Sandbox,
-    syscall::translate_path,
-    vfs::{fdtable::FdTable, mount::MountTable},
+    syscall::{check_writable, translate_path},
+    vfs::{
+        fdtable::{FdEntry, FdTable},
+        file::BoxedFileOps,
+        mount::MountTable,
+        IdMap, Vfs, VfsError,
+    },
 };
 use reverie::{
-    syscalls::{MemoryAccess, ReadAddr, Syscall, AtFlags},
+    syscalls::{AddrMut, AtFlags, MemoryAccess, ReadAddr, StatPtr, StatxFlags, StatxPtr, Syscall},
     Error, Guest,
 };
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Rewrite the owner of a `stat` result the kernel wrote to guest memory.
+pub(crate) fn remap_stat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    stat_addr: Option<StatPtr>,
+    id_map: IdMap,
+) -> Result<(), Error> {
+    if let Some(stat_addr) = stat_addr.filter(|_| !id_map.is_identity()) {
+        let mut stat: libc::stat = guest.memory().read_value(stat_addr.0)?;
+        id_map.apply(&mut stat);
+        guest.memory().write_value(stat_addr.0, &stat)?;
+    }
+    Ok(())
+}
+
+/// Rewrite the owner of a `statx` result the kernel wrote to guest memory.
+fn remap_statx<T: Guest<Sandbox>>(
+    guest: &mut T,
+    statx_addr: Option<StatxPtr>,
+    id_map: IdMap,
+) -> Result<(), Error> {
+    if let Some(statx_addr) = statx_addr.filter(|_| !id_map.is_identity()) {
+        let mut statx: libc::statx = guest.memory().read_value(statx_addr.0)?;
+        id_map.apply_statx(&mut statx);
+        guest.memory().write_value(statx_addr.0, &statx)?;
+    }
+    Ok(())
+}
+
+/// Write the `stat` of a virtual file to guest memory.
+///
+/// Returns 0 or a negated errno, like `fstat`.
+pub(crate) async fn fstat_virtual<T: Guest<Sandbox>>(
+    guest: &mut T,
+    file_ops: &BoxedFileOps,
+    stat_addr: Option<StatPtr<'_>>,
+) -> Result<i64, Error> {
+    match file_ops.fstat().await {
+        Ok(stat_buf) => {
+            if let Some(stat_addr) = stat_addr {
+                guest.memory().write_value(stat_addr.0, &stat_buf)?;
+            }
+            Ok(0)
+        }
+        Err(e) => {
+            // Map VFS errors to errno
+            let errno = match e {
+                VfsError::NotFound => -libc::ENOENT as i64,
+                VfsError::PermissionDenied => -libc::EACCES as i64,
+                _ => -libc::EIO as i64,
+            };
+            Ok(errno)
+        }
+    }
+}
+
+/// Get the FD table entry a `*at` syscall operates on with `AT_EMPTY_PATH`,
+/// if `path` is empty and the flag is set.
+fn empty_path_entry(
+    path: &Path,
+    at_empty_path: bool,
+    dirfd: i32,
+    fd_table: &FdTable,
+) -> Option<FdEntry> {
+    if path.as_os_str().is_empty() && at_empty_path {
+        fd_table.get(dirfd)
+    } else {
+        None
+    }
+}
+
+/// Get the ID mapping of the mount a file descriptor was opened in.
+fn entry_id_map(entry: &FdEntry, mount_table: &MountTable) -> IdMap {
+    entry
+        .path()
+        .and_then(|path| mount_table.resolve(path))
+        .map(|(vfs, _)| vfs.id_map())
+        .unwrap_or_default()
+}
 
 /// The `statx` system call.
 ///
@@ -33,24 +119,38 @@ pub async fn handle_statx<T: Guest<Sandbox>>(
         let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
 
         // Check if this path matches a mount point
-        if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
+        let mut id_map = IdMap::default();
+        let at_empty_path = args.flags().contains(StatxFlags::AT_EMPTY_PATH);
+        if let Some(entry) = empty_path_entry(&path, at_empty_path, dirfd, fd_table) {
+            // AT_EMPTY_PATH operates on the dirfd itself
+            if matches!(entry, FdEntry::Virtual { .. }) {
+                return Ok(Some(-libc::ENOSYS as i64));
+            }
+            id_map = entry_id_map(&entry, mount_table);
+        } else if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
                 // For virtual VFS, statx is not supported - return ENOSYS
                 // The caller will fall back to newfstatat
                 return Ok(Some(-libc::ENOSYS as i64));
             }
+            id_map = vfs.id_map();
         }
 
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+        // A path relative to a translated dirfd needs the kernel FD too
+        if new_path_addr.is_some() || kernel_dirfd != dirfd {
             let new_syscall = reverie::syscalls::Statx::new()
                 .with_dirfd(kernel_dirfd)
-                .with_path(Some(new_path_addr))
+                .with_path(new_path_addr.or(Some(path_addr)))
                 .with_flags(args.flags())
                 .with_mask(args.mask())
                 .with_statx(args.statx());
 
             let result = guest.inject(Syscall::Statx(new_syscall)).await?;
+            if result == 0 {
+                remap_statx(guest, args.statx(), id_map)?;
+            }
             return Ok(Some(result));
         }
     }
@@ -82,7 +182,16 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
         let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
 
         // Check if this path matches a mount point
-        if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
+        let mut id_map = IdMap::default();
+        let at_empty_path = args.flags().contains(AtFlags::AT_EMPTY_PATH);
+        if let Some(entry) = empty_path_entry(&path, at_empty_path, dirfd, fd_table) {
+            // AT_EMPTY_PATH operates on the dirfd itself, like fstat
+            if let FdEntry::Virtual { file_ops, .. } = &entry {
+                return fstat_virtual(guest, file_ops, args.stat()).await.map(Some);
+            }
+            id_map = entry_id_map(&entry, mount_table);
+        } else if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
+            id_map = vfs.id_map();
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
                 let flags = args.flags();
@@ -116,6 +225,7 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
                         let errno = match e {
                             crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                             crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
+                            crate::vfs::VfsError::SymlinkLoop => -libc::ELOOP as i64,
                             _ => -libc::EIO as i64,
                         };
                         return Ok(Some(errno));
@@ -124,14 +234,19 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
             }
         }
 
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+        // A path relative to a translated dirfd needs the kernel FD too
+        if new_path_addr.is_some() || kernel_dirfd != dirfd {
             let new_syscall = reverie::syscalls::Newfstatat::new()
                 .with_dirfd(kernel_dirfd)
-                .with_path(Some(new_path_addr))
+                .with_path(new_path_addr.or(Some(path_addr)))
                 .with_stat(args.stat())
                 .with_flags(args.flags());
 
             let result = guest.inject(Syscall::Newfstatat(new_syscall)).await?;
+            if result == 0 {
+                remap_stat(guest, args.stat(), id_map)?;
+            }
             return Ok(Some(result));
         }
     }
@@ -158,6 +273,42 @@ pub async fn handle_statfs<T: Guest<Sandbox>>(
     Ok(None)
 }
 
+/// Read the target of a symlink in a virtual VFS into a guest buffer.
+///
+/// Like the kernel, the target is truncated to the buffer size without a NUL
+/// terminator, and a path that is not a symlink fails with `EINVAL`.
+async fn readlink_virtual<T: Guest<Sandbox>>(
+    guest: &mut T,
+    vfs: &dyn Vfs,
+    path: &Path,
+    buf: Option<AddrMut<'_, u8>>,
+    bufsize: usize,
+) -> Result<i64, Error> {
+    if bufsize == 0 {
+        return Ok(-libc::EINVAL as i64);
+    }
+    let target = match vfs.readlink(path).await {
+        Ok(target) => target,
+        Err(e) => {
+            // Map VFS errors to errno
+            let errno = match e {
+                VfsError::NotFound => -libc::ENOENT as i64,
+                VfsError::PermissionDenied => -libc::EACCES as i64,
+                VfsError::SymlinkLoop => -libc::ELOOP as i64,
+                _ => -libc::EINVAL as i64,
+            };
+            return Ok(errno);
+        }
+    };
+    let Some(buf) = buf else {
+        return Ok(-libc::EFAULT as i64);
+    };
+    let target = target.as_os_str().as_bytes();
+    let len = target.len().min(bufsize);
+    guest.memory().write_exact(buf, &target[..len])?;
+    Ok(len as i64)
+}
+
 /// The `readlink` system call.
 ///
 /// This intercepts `readlink` system calls and translates paths according to the mount table.
@@ -173,35 +324,10 @@ pub async fn handle_readlink<T: Guest<Sandbox>>(
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
-                // Call VFS readlink method directly
-                match vfs.readlink(&path).await {
-                    Ok(target) => {
-                        // Write the target to the user's buffer
-                        if let Some(buf_addr) = args.buf() {
-                            let bufsize = args.bufsize();
-                            let target_str = target.to_string_lossy();
-                            let target_bytes = target_str.as_bytes();
-                            let bytes_to_write = std::cmp::min(target_bytes.len(), bufsize);
-
-                            guest.memory().write_exact(
-                                buf_addr.cast::<u8>(),
-                                &target_bytes[..bytes_to_write],
-                            )?;
-
-                            return Ok(Some(bytes_to_write as i64));
-                        }
-                        return Ok(Some(0));
-                    }
-                    Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EINVAL as i64,
-                        };
-                        return Ok(Some(errno));
-                    }
-                }
+                let buf = args.buf().map(|addr| addr.cast::<u8>());
+                return readlink_virtual(guest, &*vfs, &path, buf, args.bufsize())
+                    .await
+                    .map(Some);
             }
         }
 
@@ -243,35 +369,10 @@ pub async fn handle_readlinkat<T: Guest<Sandbox>>(
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
-                // Call VFS readlink method directly
-                match vfs.readlink(&path).await {
-                    Ok(target) => {
-                        // Write the target to the user's buffer
-                        if let Some(buf_addr) = args.buf() {
-                            let bufsize = args.buf_len();
-                            let target_str = target.to_string_lossy();
-                            let target_bytes = target_str.as_bytes();
-                            let bytes_to_write = std::cmp::min(target_bytes.len(), bufsize);
-
-                            guest.memory().write_exact(
-                                buf_addr.cast::<u8>(),
-                                &target_bytes[..bytes_to_write],
-                            )?;
-
-                            return Ok(Some(bytes_to_write as i64));
-                        }
-                        return Ok(Some(0));
-                    }
-                    Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EINVAL as i64,
-                        };
-                        return Ok(Some(errno));
-                    }
-                }
+                let buf = args.buf().map(|addr| addr.cast::<u8>());
+                return readlink_virtual(guest, &*vfs, &path, buf, args.buf_len())
+                    .await
+                    .map(Some);
             }
         }
 
@@ -302,6 +403,7 @@ pub async fn handle_symlink<T: Guest<Sandbox>>(
 ) -> Result<Option<i64>, Error> {
     // Read the linkpath from guest memory
     if let Some(linkpath_addr) = args.linkpath() {
+        check_writable(guest, linkpath_addr, mount_table)?;
         let linkpath: std::path::PathBuf = linkpath_addr.read(&guest.memory())?;
 
         // Read the target from guest memory
@@ -367,6 +469,7 @@ pub async fn handle_symlinkat<T: Guest<Sandbox>>(
 
     // Read linkpath and target from guest memory
     if let Some(linkpath_addr) = args.linkpath() {
+        check_writable(guest, linkpath_addr, mount_table)?;
         let linkpath: std::path::PathBuf = linkpath_addr.read(&guest.memory())?;
 
         if let Some(target_addr) = args.target() {
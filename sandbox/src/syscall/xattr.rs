@@ -1,24 +1,212 @@
-use crate::{sandbox::Sandbox, syscall::translate_path, vfs::mount::MountTable};
-use reverie::{syscalls::Syscall, Error, Guest};
+use crate::{
+    sandbox::Sandbox,
+    syscall::{check_fd_writable, check_writable, translate_path},
+    vfs::{
+        fdtable::{FdEntry, FdTable},
+        follow_symlinks,
+        mount::MountTable,
+        Vfs, VfsError,
+    },
+};
+use reverie::{
+    syscalls::{Addr, AddrMut, CStrPtr, MemoryAccess, ReadAddr, Syscall},
+    Error, Guest,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-/// The `llistxattr` system call.
+/// Largest attribute value Linux accepts (`XATTR_SIZE_MAX`)
+const XATTR_SIZE_MAX: usize = 65536;
+
+/// Map a VFS error of an xattr operation to a negated errno.
+fn xattr_errno(err: VfsError) -> i64 {
+    let errno = match err {
+        VfsError::NotFound => libc::ENOENT,
+        VfsError::PermissionDenied => libc::EACCES,
+        VfsError::AlreadyExists => libc::EEXIST,
+        VfsError::NotSupported => libc::ENOTSUP,
+        VfsError::SymlinkLoop => libc::ELOOP,
+        VfsError::InvalidInput(_) => libc::EINVAL,
+        _ => libc::EIO,
+    };
+    -errno as i64
+}
+
+/// Copy an attribute value or name list to a guest buffer.
 ///
-/// This intercepts llistxattr syscalls and translates paths according to the mount table.
-/// Returns Some(syscall) if the path was translated and should be injected,
-/// or None if the original syscall should be used.
-pub async fn handle_llistxattr<T: Guest<Sandbox>>(
+/// Follows the xattr syscall convention: a zero `size` asks for the size the
+/// buffer needs, and a buffer that is too small fails with `ERANGE`.
+fn write_xattr_buffer<T: Guest<Sandbox>>(
     guest: &mut T,
-    args: &reverie::syscalls::Llistxattr,
+    buf: Option<AddrMut<u8>>,
+    size: usize,
+    data: &[u8],
+) -> Result<i64, Error> {
+    if size == 0 {
+        return Ok(data.len() as i64);
+    }
+    if size < data.len() {
+        return Ok(-libc::ERANGE as i64);
+    }
+    if let Some(buf) = buf {
+        if !data.is_empty() {
+            guest.memory().write_exact(buf, data)?;
+        }
+    }
+    Ok(data.len() as i64)
+}
+
+/// Read the attribute name argument of an xattr syscall.
+fn read_xattr_name<T: Guest<Sandbox>>(
+    guest: &T,
+    name_addr: Option<CStrPtr>,
+) -> Result<Option<String>, Error> {
+    match name_addr {
+        Some(name_addr) => {
+            let name = name_addr.read(&guest.memory())?;
+            Ok(Some(name.to_string_lossy().into_owned()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Read an attribute of a file in a virtual VFS into a guest buffer.
+async fn virtual_getxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    vfs: &dyn Vfs,
+    path: &Path,
+    name_addr: Option<CStrPtr<'_>>,
+    buf: Option<AddrMut<'_, u8>>,
+    size: usize,
+) -> Result<i64, Error> {
+    let Some(name) = read_xattr_name(guest, name_addr)? else {
+        return Ok(-libc::EFAULT as i64);
+    };
+    let value = match vfs.getxattr(path, &name).await {
+        Ok(Some(value)) => value,
+        Ok(None) => return Ok(-libc::ENODATA as i64),
+        Err(e) => return Ok(xattr_errno(e)),
+    };
+    write_xattr_buffer(guest, buf, size, &value)
+}
+
+/// Store an attribute of a file in a virtual VFS, honoring `XATTR_CREATE`
+/// and `XATTR_REPLACE`.
+async fn virtual_setxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    vfs: &dyn Vfs,
+    path: &Path,
+    name_addr: Option<CStrPtr<'_>>,
+    value_addr: Option<Addr<'_, u8>>,
+    size: usize,
+    flags: i32,
+) -> Result<i64, Error> {
+    let Some(name) = read_xattr_name(guest, name_addr)? else {
+        return Ok(-libc::EFAULT as i64);
+    };
+    // Like the kernel, refuse values that are too large before allocating
+    if size > XATTR_SIZE_MAX {
+        return Ok(-libc::E2BIG as i64);
+    }
+    let mut value = vec![0u8; size];
+    if let Some(value_addr) = value_addr {
+        if !value.is_empty() {
+            guest.memory().read_exact(value_addr, &mut value)?;
+        }
+    }
+
+    if flags & (libc::XATTR_CREATE | libc::XATTR_REPLACE) != 0 {
+        let exists = match vfs.getxattr(path, &name).await {
+            Ok(value) => value.is_some(),
+            Err(e) => return Ok(xattr_errno(e)),
+        };
+        if exists && flags & libc::XATTR_CREATE != 0 {
+            return Ok(-libc::EEXIST as i64);
+        }
+        if !exists && flags & libc::XATTR_REPLACE != 0 {
+            return Ok(-libc::ENODATA as i64);
+        }
+    }
+
+    match vfs.setxattr(path, &name, &value).await {
+        Ok(()) => Ok(0),
+        Err(e) => Ok(xattr_errno(e)),
+    }
+}
+
+/// List the attribute names of a file in a virtual VFS into a guest buffer.
+async fn virtual_listxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    vfs: &dyn Vfs,
+    path: &Path,
+    buf: Option<AddrMut<'_, u8>>,
+    size: usize,
+) -> Result<i64, Error> {
+    let names = match vfs.listxattr(path).await {
+        Ok(names) => names,
+        Err(e) => return Ok(xattr_errno(e)),
+    };
+    // Names are stored back to back, each with a NUL terminator
+    let mut list = Vec::new();
+    for name in names {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+    }
+    write_xattr_buffer(guest, buf, size, &list)
+}
+
+/// Remove an attribute of a file in a virtual VFS.
+async fn virtual_removexattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    vfs: &dyn Vfs,
+    path: &Path,
+    name_addr: Option<CStrPtr<'_>>,
+) -> Result<i64, Error> {
+    let Some(name) = read_xattr_name(guest, name_addr)? else {
+        return Ok(-libc::EFAULT as i64);
+    };
+    match vfs.removexattr(path, &name).await {
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(-libc::ENODATA as i64),
+        Err(e) => Ok(xattr_errno(e)),
+    }
+}
+
+/// The `getxattr` system call.
+///
+/// This intercepts getxattr syscalls and translates paths according to the mount table.
+/// Attributes of files in virtual mounts are read from the VFS after following symlinks.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_getxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Getxattr,
     mount_table: &MountTable,
-) -> Result<Option<Syscall>, Error> {
+) -> Result<Option<i64>, Error> {
     if let Some(path_addr) = args.path() {
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                let path = match follow_symlinks(&*vfs, &path).await {
+                    Ok(path) => path,
+                    Err(e) => return Ok(Some(xattr_errno(e))),
+                };
+                let buf = args.value().map(|addr| addr.cast::<u8>());
+                return virtual_getxattr(guest, &*vfs, &path, args.name(), buf, args.size())
+                    .await
+                    .map(Some);
+            }
+        }
+
         if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
-            let new_syscall = reverie::syscalls::Llistxattr::new()
+            let new_syscall = reverie::syscalls::Getxattr::new()
                 .with_path(Some(new_path_addr))
-                .with_list(args.list())
+                .with_name(args.name())
+                .with_value(args.value())
                 .with_size(args.size());
 
-            return Ok(Some(Syscall::Llistxattr(new_syscall)));
+            let result = guest.inject(Syscall::Getxattr(new_syscall)).await?;
+            return Ok(Some(result));
         }
     }
     Ok(None)
@@ -27,14 +215,25 @@ pub async fn handle_llistxattr<T: Guest<Sandbox>>(
 /// The `lgetxattr` system call.
 ///
 /// This intercepts lgetxattr syscalls and translates paths according to the mount table.
-/// Returns Some(syscall) if the path was translated and should be injected,
-/// or None if the original syscall should be used.
+/// Attributes of files in virtual mounts are read from the VFS.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
 pub async fn handle_lgetxattr<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Lgetxattr,
     mount_table: &MountTable,
-) -> Result<Option<Syscall>, Error> {
+) -> Result<Option<i64>, Error> {
     if let Some(path_addr) = args.path() {
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                let buf = args.value().map(|addr| addr.cast::<u8>());
+                return virtual_getxattr(guest, &*vfs, &path, args.name(), buf, args.size())
+                    .await
+                    .map(Some);
+            }
+        }
+
         if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
             let new_syscall = reverie::syscalls::Lgetxattr::new()
                 .with_path(Some(new_path_addr))
@@ -42,8 +241,399 @@ pub async fn handle_lgetxattr<T: Guest<Sandbox>>(
                 .with_value(args.value())
                 .with_size(args.size());
 
-            return Ok(Some(Syscall::Lgetxattr(new_syscall)));
+            let result = guest.inject(Syscall::Lgetxattr(new_syscall)).await?;
+            return Ok(Some(result));
+        }
+    }
+    Ok(None)
+}
+
+/// The `setxattr` system call.
+///
+/// This intercepts setxattr syscalls and translates paths according to the mount table.
+/// Attributes of files in virtual mounts are stored by the VFS after following symlinks.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_setxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Setxattr,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    if let Some(path_addr) = args.path() {
+        check_writable(guest, path_addr, mount_table)?;
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                let path = match follow_symlinks(&*vfs, &path).await {
+                    Ok(path) => path,
+                    Err(e) => return Ok(Some(xattr_errno(e))),
+                };
+                let value = args.value().map(|addr| addr.cast::<u8>());
+                return virtual_setxattr(
+                    guest,
+                    &*vfs,
+                    &path,
+                    args.name(),
+                    value,
+                    args.size(),
+                    args.flags(),
+                )
+                .await
+                .map(Some);
+            }
+        }
+
+        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+            let new_syscall = reverie::syscalls::Setxattr::new()
+                .with_path(Some(new_path_addr))
+                .with_name(args.name())
+                .with_value(args.value())
+                .with_size(args.size())
+                .with_flags(args.flags());
+
+            let result = guest.inject(Syscall::Setxattr(new_syscall)).await?;
+            return Ok(Some(result));
+        }
+    }
+    Ok(None)
+}
+
+/// The `lsetxattr` system call.
+///
+/// This intercepts lsetxattr syscalls and translates paths according to the mount table.
+/// Attributes of files in virtual mounts are stored by the VFS.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_lsetxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Lsetxattr,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    if let Some(path_addr) = args.path() {
+        check_writable(guest, path_addr, mount_table)?;
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                let value = args.value().map(|addr| addr.cast::<u8>());
+                return virtual_setxattr(
+                    guest,
+                    &*vfs,
+                    &path,
+                    args.name(),
+                    value,
+                    args.size(),
+                    args.flags(),
+                )
+                .await
+                .map(Some);
+            }
+        }
+
+        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+            let new_syscall = reverie::syscalls::Lsetxattr::new()
+                .with_path(Some(new_path_addr))
+                .with_name(args.name())
+                .with_value(args.value())
+                .with_size(args.size())
+                .with_flags(args.flags());
+
+            let result = guest.inject(Syscall::Lsetxattr(new_syscall)).await?;
+            return Ok(Some(result));
         }
     }
     Ok(None)
 }
+
+/// The `listxattr` system call.
+///
+/// This intercepts listxattr syscalls and translates paths according to the mount table.
+/// Attributes of files in virtual mounts are listed by the VFS after following symlinks.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_listxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Listxattr,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    if let Some(path_addr) = args.path() {
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                let path = match follow_symlinks(&*vfs, &path).await {
+                    Ok(path) => path,
+                    Err(e) => return Ok(Some(xattr_errno(e))),
+                };
+                let buf = args.list().map(|addr| addr.cast::<u8>());
+                return virtual_listxattr(guest, &*vfs, &path, buf, args.size())
+                    .await
+                    .map(Some);
+            }
+        }
+
+        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+            let new_syscall = reverie::syscalls::Listxattr::new()
+                .with_path(Some(new_path_addr))
+                .with_list(args.list())
+                .with_size(args.size());
+
+            let result = guest.inject(Syscall::Listxattr(new_syscall)).await?;
+            return Ok(Some(result));
+        }
+    }
+    Ok(None)
+}
+
+/// The `llistxattr` system call.
+///
+/// This intercepts llistxattr syscalls and translates paths according to the mount table.
+/// Attributes of files in virtual mounts are listed by the VFS.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_llistxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Llistxattr,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    if let Some(path_addr) = args.path() {
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                let buf = args.list().map(|addr| addr.cast::<u8>());
+                return virtual_listxattr(guest, &*vfs, &path, buf, args.size())
+                    .await
+                    .map(Some);
+            }
+        }
+
+        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+            let new_syscall = reverie::syscalls::Llistxattr::new()
+                .with_path(Some(new_path_addr))
+                .with_list(args.list())
+                .with_size(args.size());
+
+            let result = guest.inject(Syscall::Llistxattr(new_syscall)).await?;
+            return Ok(Some(result));
+        }
+    }
+    Ok(None)
+}
+
+/// The `removexattr` system call.
+///
+/// This intercepts removexattr syscalls and translates paths according to the mount table.
+/// Attributes of files in virtual mounts are removed by the VFS after following symlinks.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_removexattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Removexattr,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    if let Some(path_addr) = args.path() {
+        check_writable(guest, path_addr, mount_table)?;
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                let path = match follow_symlinks(&*vfs, &path).await {
+                    Ok(path) => path,
+                    Err(e) => return Ok(Some(xattr_errno(e))),
+                };
+                return virtual_removexattr(guest, &*vfs, &path, args.name())
+                    .await
+                    .map(Some);
+            }
+        }
+
+        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+            let new_syscall = reverie::syscalls::Removexattr::new()
+                .with_path(Some(new_path_addr))
+                .with_name(args.name());
+
+            let result = guest.inject(Syscall::Removexattr(new_syscall)).await?;
+            return Ok(Some(result));
+        }
+    }
+    Ok(None)
+}
+
+/// The `lremovexattr` system call.
+///
+/// This intercepts lremovexattr syscalls and translates paths according to the mount table.
+/// Attributes of files in virtual mounts are removed by the VFS.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_lremovexattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Lremovexattr,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    if let Some(path_addr) = args.path() {
+        check_writable(guest, path_addr, mount_table)?;
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                return virtual_removexattr(guest, &*vfs, &path, args.name())
+                    .await
+                    .map(Some);
+            }
+        }
+
+        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+            let new_syscall = reverie::syscalls::Lremovexattr::new()
+                .with_path(Some(new_path_addr))
+                .with_name(args.name());
+
+            let result = guest.inject(Syscall::Lremovexattr(new_syscall)).await?;
+            return Ok(Some(result));
+        }
+    }
+    Ok(None)
+}
+
+/// Resolve the VFS and path of a virtual file descriptor for an `f*xattr` syscall.
+fn virtual_fd_target(
+    path: Option<&Path>,
+    mount_table: &MountTable,
+) -> Option<(Arc<dyn Vfs>, PathBuf)> {
+    let path = path?;
+    let (vfs, _) = mount_table.resolve(path)?;
+    Some((vfs, path.to_path_buf()))
+}
+
+/// The `fgetxattr` system call.
+///
+/// This intercepts fgetxattr syscalls and translates the file descriptor.
+/// Attributes of virtual files are read from the VFS of the file's mount.
+pub async fn handle_fgetxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fgetxattr,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => {
+            let new_syscall = reverie::syscalls::Fgetxattr::new()
+                .with_fd(kernel_fd)
+                .with_name(args.name())
+                .with_value(args.value())
+                .with_size(args.size());
+
+            Ok(guest.inject(Syscall::Fgetxattr(new_syscall)).await?)
+        }
+        Some(FdEntry::Virtual { path, .. }) => {
+            let Some((vfs, path)) = virtual_fd_target(path.as_deref(), mount_table) else {
+                return Ok(-libc::ENOTSUP as i64);
+            };
+            let buf = args.value().map(|addr| addr.cast::<u8>());
+            virtual_getxattr(guest, &*vfs, &path, args.name(), buf, args.size()).await
+        }
+        None => Ok(-libc::EBADF as i64),
+    }
+}
+
+/// The `fsetxattr` system call.
+///
+/// This intercepts fsetxattr syscalls and translates the file descriptor.
+/// Attributes of virtual files are stored by the VFS of the file's mount.
+pub async fn handle_fsetxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fsetxattr,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough {
+            kernel_fd, path, ..
+        }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let new_syscall = reverie::syscalls::Fsetxattr::new()
+                .with_fd(kernel_fd)
+                .with_name(args.name())
+                .with_value(args.value())
+                .with_size(args.size())
+                .with_flags(args.flags());
+
+            Ok(guest.inject(Syscall::Fsetxattr(new_syscall)).await?)
+        }
+        Some(FdEntry::Virtual { path, .. }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let Some((vfs, path)) = virtual_fd_target(path.as_deref(), mount_table) else {
+                return Ok(-libc::ENOTSUP as i64);
+            };
+            let value = args.value().map(|addr| addr.cast::<u8>());
+            virtual_setxattr(
+                guest,
+                &*vfs,
+                &path,
+                args.name(),
+                value,
+                args.size(),
+                args.flags(),
+            )
+            .await
+        }
+        None => Ok(-libc::EBADF as i64),
+    }
+}
+
+/// The `flistxattr` system call.
+///
+/// This intercepts flistxattr syscalls and translates the file descriptor.
+/// Attributes of virtual files are listed by the VFS of the file's mount.
+pub async fn handle_flistxattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Flistxattr,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => {
+            let new_syscall = reverie::syscalls::Flistxattr::new()
+                .with_fd(kernel_fd)
+                .with_list(args.list())
+                .with_size(args.size());
+
+            Ok(guest.inject(Syscall::Flistxattr(new_syscall)).await?)
+        }
+        Some(FdEntry::Virtual { path, .. }) => {
+            let Some((vfs, path)) = virtual_fd_target(path.as_deref(), mount_table) else {
+                return Ok(-libc::ENOTSUP as i64);
+            };
+            let buf = args.list().map(|addr| addr.cast::<u8>());
+            virtual_listxattr(guest, &*vfs, &path, buf, args.size()).await
+        }
+        None => Ok(-libc::EBADF as i64),
+    }
+}
+
+/// The `fremovexattr` system call.
+///
+/// This intercepts fremovexattr syscalls and translates the file descriptor.
+/// Attributes of virtual files are removed by the VFS of the file's mount.
+pub async fn handle_fremovexattr<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fremovexattr,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<i64, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough {
+            kernel_fd, path, ..
+        }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let new_syscall = reverie::syscalls::Fremovexattr::new()
+                .with_fd(kernel_fd)
+                .with_name(args.name());
+
+            Ok(guest.inject(Syscall::Fremovexattr(new_syscall)).await?)
+        }
+        Some(FdEntry::Virtual { path, .. }) => {
+            check_fd_writable(path.as_deref(), mount_table)?;
+            let Some((vfs, path)) = virtual_fd_target(path.as_deref(), mount_table) else {
+                return Ok(-libc::ENOTSUP as i64);
+            };
+            virtual_removexattr(guest, &*vfs, &path, args.name()).await
+        }
+        None => Ok(-libc::EBADF as i64),
+    }
+}
@@ -1,4 +1,4 @@
-use super::{Vfs, VfsError, VfsResult};
+use super::{IdMap, Vfs, VfsError, VfsResult};
 use std::path::{Path, PathBuf};
 
 /// A bind mount VFS that maps a sandbox path to a host directory
@@ -14,6 +14,10 @@ pub struct BindVfs {
     host_root: PathBuf,
     /// The virtual path as seen by the sandboxed process
     sandbox_root: PathBuf,
+    /// Whether modifications through this mount are rejected
+    read_only: bool,
+    /// Ownership reported for files in the mount
+    id_map: IdMap,
 }
 
 impl BindVfs {
@@ -26,9 +30,28 @@ impl BindVfs {
         Self {
             host_root,
             sandbox_root,
+            read_only: false,
+            id_map: IdMap::default(),
         }
     }
 
+    /// Create a new read-only bind mount VFS
+    ///
+    /// # Arguments
+    /// * `host_root` - The real directory on the host filesystem
+    /// * `sandbox_root` - The virtual path seen by the guest
+    pub fn new_read_only(host_root: PathBuf, sandbox_root: PathBuf) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(host_root, sandbox_root)
+        }
+    }
+
+    /// Report files in the mount with the ownership of `id_map`
+    pub fn with_id_map(self, id_map: IdMap) -> Self {
+        Self { id_map, ..self }
+    }
+
     /// Get the host root path
     pub fn host_root(&self) -> &Path {
         &self.host_root
@@ -78,6 +101,14 @@ impl Vfs for BindVfs {
         // Bind mounts are not virtual - they use real kernel file descriptors
         false
     }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn id_map(&self) -> IdMap {
+        self.id_map
+    }
 }
 
 #[cfg(test)]
@@ -124,4 +155,15 @@ mod tests {
         let vfs = BindVfs::new(PathBuf::from("/tmp/agent"), PathBuf::from("/agent"));
         assert!(!vfs.is_virtual());
     }
+
+    #[test]
+    fn test_read_only() {
+        let vfs = BindVfs::new(PathBuf::from("/tmp/agent"), PathBuf::from("/agent"));
+        assert!(!vfs.is_read_only());
+
+        let vfs = BindVfs::new_read_only(PathBuf::from("/tmp/agent"), PathBuf::from("/agent"));
+        assert!(vfs.is_read_only());
+        let result = vfs.translate_path(Path::new("/agent/file.txt")).unwrap();
+        assert_eq!(result, PathBuf::from("/tmp/agent/file.txt"));
+    }
 }
@@ -67,6 +67,8 @@ struct FdTableInner {
     next_vfd: i32,
     /// Min-heap of freed FDs available for reuse (stored as negative for min-heap behavior)
     free_fds: BinaryHeap<std::cmp::Reverse<i32>>,
+    /// Virtual FDs must be below this limit (like `RLIMIT_NOFILE`)
+    max_fds: Option<i32>,
 }
 
 /// Per-process file descriptor table that virtualizes file descriptors
@@ -118,10 +120,21 @@ impl FdTable {
                 entries,
                 next_vfd: FIRST_USER_FD,
                 free_fds: BinaryHeap::new(),
+                max_fds: None,
             })),
         }
     }
 
+    /// Create a new FD table that allocates only FDs below `max_fds`
+    ///
+    /// Virtualized files do not consume kernel FDs, so the kernel cannot
+    /// enforce `RLIMIT_NOFILE` for them and the table does it instead.
+    pub fn with_max_fds(max_fds: i32) -> Self {
+        let table = Self::new();
+        table.inner.lock().unwrap().max_fds = Some(max_fds);
+        table
+    }
+
     /// Create a deep clone of this FD table (for fork/clone syscalls)
     ///
     /// This creates a completely independent copy of the FD table,
@@ -137,6 +150,7 @@ impl FdTable {
                 entries: inner.entries.clone(),
                 next_vfd: inner.next_vfd,
                 free_fds: inner.free_fds.clone(),
+                max_fds: inner.max_fds,
             })),
         }
     }
@@ -144,12 +158,22 @@ impl FdTable {
     /// Allocate a new virtual FD for the given FdEntry
     ///
     /// This uses the lowest available FD number, as required by POSIX.
-    pub fn allocate(&self, entry: FdEntry) -> i32 {
+    /// Returns None if the lowest available FD is not below the table's limit,
+    /// in which case the caller should fail with `EMFILE`.
+    pub fn allocate(&self, entry: FdEntry) -> Option<i32> {
         let mut inner = self
             .inner
             .lock()
             .unwrap_or_else(|poisoned| poisoned.into_inner());
 
+        let lowest = match inner.free_fds.peek() {
+            Some(std::cmp::Reverse(free_fd)) => *free_fd,
+            None => inner.next_vfd,
+        };
+        if inner.max_fds.is_some_and(|max_fds| lowest >= max_fds) {
+            return None;
+        }
+
         // Try to reuse a freed FD first (POSIX requires lowest available FD)
         let vfd = if let Some(std::cmp::Reverse(free_fd)) = inner.free_fds.pop() {
             free_fd
@@ -169,13 +193,24 @@ impl FdTable {
         };
 
         inner.entries.insert(vfd, entry);
-        vfd
+        Some(vfd)
+    }
+
+    /// Whether `vfd` is a valid FD number below the table's limit
+    pub fn within_limit(&self, vfd: i32) -> bool {
+        let inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        vfd >= 0 && inner.max_fds.is_none_or(|max_fds| vfd < max_fds)
     }
 
     /// Allocate a new virtual FD at or above the specified minimum
     ///
     /// This is used for fcntl F_DUPFD and F_DUPFD_CLOEXEC commands.
-    pub fn allocate_min(&self, min_vfd: i32, entry: FdEntry) -> i32 {
+    /// Returns None if no FD at or above `min_vfd` is below the table's
+    /// limit, in which case the caller should fail with `EMFILE`.
+    pub fn allocate_min(&self, min_vfd: i32, entry: FdEntry) -> Option<i32> {
         let mut inner = self
             .inner
             .lock()
@@ -185,6 +220,9 @@ impl FdTable {
         let vfd = (min_vfd..i32::MAX)
             .find(|fd| !inner.entries.contains_key(fd))
             .expect("File descriptor table exhausted");
+        if inner.max_fds.is_some_and(|max_fds| vfd >= max_fds) {
+            return None;
+        }
 
         // Update next_vfd if we allocated beyond it
         if vfd >= inner.next_vfd {
@@ -200,13 +238,14 @@ impl FdTable {
             .collect();
 
         inner.entries.insert(vfd, entry);
-        vfd
+        Some(vfd)
     }
 
     /// Allocate a specific virtual FD (used for dup2)
     ///
     /// Returns the old FdEntry if the VFD was already allocated, which the caller
-    /// should close if needed.
+    /// should close if needed. The table's limit is not checked, so callers
+    /// allocating on behalf of the guest should check [`FdTable::within_limit`].
     pub fn allocate_at(&self, vfd: i32, entry: FdEntry) -> Option<FdEntry> {
         let mut inner = self
             .inner
@@ -269,11 +308,37 @@ impl FdTable {
         Some(entry)
     }
 
+    /// Get all entries, ordered by virtual FD
+    pub fn entries(&self) -> Vec<(i32, FdEntry)> {
+        let inner = self.inner.lock().unwrap();
+        let mut entries: Vec<_> = inner
+            .entries
+            .iter()
+            .map(|(vfd, entry)| (*vfd, entry.clone()))
+            .collect();
+        entries.sort_by_key(|(vfd, _)| *vfd);
+        entries
+    }
+
+    /// Get the number of open virtual FDs
+    pub fn len(&self) -> usize {
+        let inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        inner.entries.len()
+    }
+
+    /// Check if no virtual FDs are open
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Duplicate a virtual FD (for dup syscall)
     pub fn duplicate(&self, old_vfd: i32) -> Option<i32> {
         let entry = self.get(old_vfd)?;
         // Allocate a new virtual FD pointing to the same file operations
-        Some(self.allocate(entry))
+        self.allocate(entry)
     }
 
     /// Duplicate a virtual FD to a specific new FD (for dup2 syscall)
@@ -324,7 +389,7 @@ mod tests {
             flags: 0,
             path: None,
         };
-        let vfd1 = table.allocate(entry1);
+        let vfd1 = table.allocate(entry1).unwrap();
         assert_eq!(vfd1, 3); // First non-standard FD
         assert_eq!(table.translate(3), Some(100));
 
@@ -333,7 +398,7 @@ mod tests {
             flags: 0,
             path: None,
         };
-        let vfd2 = table.allocate(entry2);
+        let vfd2 = table.allocate(entry2).unwrap();
         assert_eq!(vfd2, 4);
         assert_eq!(table.translate(4), Some(101));
     }
@@ -347,7 +412,7 @@ mod tests {
             flags: 0,
             path: None,
         };
-        let vfd = table.allocate(entry);
+        let vfd = table.allocate(entry).unwrap();
         assert_eq!(table.translate(vfd), Some(100));
 
         let entry = table.deallocate(vfd);
@@ -366,7 +431,7 @@ mod tests {
             flags: 0,
             path: None,
         };
-        let vfd1 = table.allocate(entry);
+        let vfd1 = table.allocate(entry).unwrap();
         let vfd2 = table.duplicate(vfd1).unwrap();
 
         assert_ne!(vfd1, vfd2);
@@ -383,7 +448,7 @@ mod tests {
             flags: 0,
             path: None,
         };
-        let vfd1 = table.allocate(entry);
+        let vfd1 = table.allocate(entry).unwrap();
         let result = table.duplicate_at(vfd1, 10);
 
         // duplicate_at returns the old FdEntry that was at new_vfd (if any)
@@ -391,4 +456,34 @@ mod tests {
         assert!(result.is_none());
         assert_eq!(table.translate(10), Some(100));
     }
+
+    #[test]
+    fn test_allocate_respects_max_fds() {
+        let table = FdTable::with_max_fds(5);
+        let entry = FdEntry::Passthrough {
+            kernel_fd: 100,
+            flags: 0,
+            path: None,
+        };
+
+        assert_eq!(table.allocate(entry.clone()), Some(3));
+        assert_eq!(table.allocate(entry.clone()), Some(4));
+        assert_eq!(table.allocate(entry.clone()), None);
+        assert_eq!(table.duplicate(3), None);
+
+        // Freed FDs below the limit can be reused
+        table.deallocate(3);
+        assert_eq!(table.allocate(entry.clone()), Some(3));
+
+        // Duplicates at a chosen FD are limited too
+        assert!(table.within_limit(4));
+        assert!(!table.within_limit(5));
+        assert!(!table.within_limit(-1));
+        table.deallocate(4);
+        assert_eq!(table.allocate_min(4, entry.clone()), Some(4));
+        assert_eq!(table.allocate_min(2, entry.clone()), None);
+
+        // Forked processes inherit the limit
+        assert_eq!(table.deep_clone().allocate(entry), None);
+    }
 }
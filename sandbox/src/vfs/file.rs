@@ -1,4 +1,4 @@
-use super::VfsResult;
+use super::{VfsError, VfsResult};
 use async_trait::async_trait;
 use std::os::unix::io::RawFd;
 use std::sync::Arc;
@@ -28,6 +28,13 @@ pub trait FileOps: Send + Sync {
     /// Sync file data (but not metadata) to storage
     async fn fdatasync(&self) -> VfsResult<()>;
 
+    /// Truncate or extend the file to `len` bytes, as with `ftruncate`
+    ///
+    /// Returns an error if the file is not a regular file open for writing.
+    async fn set_len(&self, _len: i64) -> VfsResult<()> {
+        Err(VfsError::InvalidInput("Not a writable file".to_string()))
+    }
+
     /// Perform file control operations
     fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64>;
 
@@ -60,3 +67,50 @@ pub trait FileOps: Send + Sync {
 
 /// A boxed FileOps trait object for dynamic dispatch
 pub type BoxedFileOps = Arc<dyn FileOps>;
+
+/// Compute the new offset of a virtual file of `size` bytes for `lseek`
+///
+/// Holes are not reported, as POSIX allows, so `SEEK_DATA` returns `offset`
+/// and `SEEK_HOLE` the end of the file, and both fail with `ENXIO` at or past
+/// the end.
+pub(crate) fn file_seek_position(
+    current: i64,
+    size: i64,
+    offset: i64,
+    whence: i32,
+) -> VfsResult<i64> {
+    let new_offset = match whence {
+        libc::SEEK_SET => offset,
+        libc::SEEK_CUR => current + offset,
+        libc::SEEK_END => size + offset,
+        libc::SEEK_DATA | libc::SEEK_HOLE if offset >= size => {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::ENXIO,
+            )))
+        }
+        libc::SEEK_DATA => offset,
+        libc::SEEK_HOLE => size,
+        _ => return Err(VfsError::InvalidInput("Invalid whence".to_string())),
+    };
+    if new_offset < 0 {
+        return Err(VfsError::InvalidInput("Invalid offset".to_string()));
+    }
+    Ok(new_offset)
+}
+
+/// Compute the new position of a virtual directory stream for `lseek`
+///
+/// Directory offsets are indices into the entry listing, which is what
+/// getdents64 reports in `d_off`, so `telldir`/`seekdir` round-trip.
+/// `SEEK_END` is not supported on directories.
+pub(crate) fn dir_seek_position(position: usize, offset: i64, whence: i32) -> VfsResult<usize> {
+    let new_position = match whence {
+        libc::SEEK_SET => offset,
+        libc::SEEK_CUR => position as i64 + offset,
+        _ => return Err(VfsError::InvalidInput("Invalid whence".to_string())),
+    };
+    if new_position < 0 {
+        return Err(VfsError::InvalidInput("Invalid offset".to_string()));
+    }
+    Ok(new_position as usize)
+}
@@ -0,0 +1,718 @@
+use super::file::{dir_seek_position, file_seek_position, BoxedFileOps, FileOps};
+use super::{components, Vfs, VfsError, VfsResult, MAX_SYMLINKS};
+use git2::{ObjectType, Oid, Repository};
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::OsString;
+use std::hash::{Hash, Hasher};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Inode number of the root directory
+const ROOT_INO: u64 = 1;
+
+/// Git file mode of an executable file
+const GIT_MODE_EXECUTABLE: i32 = 0o100755;
+
+/// Type alias for directory entry list: (inode, name, type)
+type DirEntryList = Vec<(u64, String, u8)>;
+
+/// What a path in the tree refers to
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Tree(Oid),
+    /// A file, with its Git file mode
+    Blob(Oid, i32),
+    Symlink(Oid),
+    /// A submodule, which shows as an empty directory as in a checkout
+    /// where it was not initialized
+    Submodule,
+}
+
+/// A read-only virtual filesystem of the tree of a Git commit
+///
+/// The mount shows the files of the commit (or tree) that a revision
+/// resolves to when the mount is created, read straight from the object
+/// database without a checkout. Trees are directories, blobs are files,
+/// and symlinks are symlinks. All times are the commit time.
+#[derive(Clone)]
+pub struct GitVfs {
+    /// git2 repositories can't be shared between threads
+    repo: Arc<Mutex<Repository>>,
+    tree: Oid,
+    /// Commit time, reported as the time of every file
+    time: i64,
+    /// The virtual path as seen by the sandboxed process
+    mount_point: PathBuf,
+}
+
+impl GitVfs {
+    /// Create a VFS for a repository at a revision
+    ///
+    /// # Arguments
+    /// * `repo` - Path to the repository, or any directory in its work tree
+    /// * `revision` - Revision to show (e.g., "HEAD", "v1.0", or a commit id)
+    /// * `mount_point` - The virtual path seen by the guest (e.g., "/repo")
+    pub fn new(repo: &Path, revision: &str, mount_point: PathBuf) -> VfsResult<Self> {
+        let repo = Repository::discover(repo).map_err(|e| {
+            VfsError::Other(format!(
+                "Failed to open Git repository {}: {}",
+                repo.display(),
+                e.message()
+            ))
+        })?;
+        let (tree, time) = resolve_revision(&repo, revision)?;
+        Ok(Self {
+            repo: Arc::new(Mutex::new(repo)),
+            tree,
+            time,
+            mount_point,
+        })
+    }
+
+    /// Get the mount point path
+    pub fn mount_point(&self) -> &Path {
+        &self.mount_point
+    }
+
+    /// Translate a sandbox path to a path relative to the mount point
+    fn translate_to_relative(&self, path: &Path) -> VfsResult<String> {
+        let relative = path
+            .strip_prefix(&self.mount_point)
+            .map_err(|_| VfsError::NotFound)?;
+        relative
+            .to_str()
+            .map(str::to_string)
+            .ok_or_else(|| VfsError::InvalidInput("Invalid path".to_string()))
+    }
+
+    /// Resolve a path relative to the mount point
+    ///
+    /// Symbolic links in intermediate components are always followed; the
+    /// last component is followed only if `follow` is set. Absolute link
+    /// targets are sandbox paths and must point into this mount. Returns
+    /// the node and its normalized path.
+    fn lookup(&self, repo: &Repository, path: &str, follow: bool) -> VfsResult<(Node, String)> {
+        let mut pending = components(path);
+        // Trees from the root to the current directory, with their names
+        let mut dirs: Vec<(String, Oid)> = Vec::new();
+        let mut node = Node::Tree(self.tree);
+        let mut links = 0;
+
+        while let Some(name) = pending.pop() {
+            let tree = match node {
+                Node::Tree(oid) => Some(repo.find_tree(oid).map_err(git_error)?),
+                // An uninitialized submodule is empty
+                Node::Submodule => None,
+                _ => return Err(VfsError::Other("Not a directory".to_string())),
+            };
+            match name.as_str() {
+                "." => continue,
+                ".." => {
+                    dirs.pop();
+                    node = Node::Tree(dirs.last().map_or(self.tree, |(_, oid)| *oid));
+                    continue;
+                }
+                _ => {}
+            }
+
+            let entry = tree
+                .as_ref()
+                .and_then(|tree| tree.get_name(&name))
+                .ok_or(VfsError::NotFound)?;
+            let child = match entry.kind() {
+                Some(ObjectType::Tree) => Node::Tree(entry.id()),
+                Some(ObjectType::Blob) if entry.filemode() == 0o120000 => Node::Symlink(entry.id()),
+                Some(ObjectType::Blob) => Node::Blob(entry.id(), entry.filemode()),
+                Some(ObjectType::Commit) => Node::Submodule,
+                _ => return Err(VfsError::NotFound),
+            };
+            if let Node::Symlink(oid) = child {
+                if follow || !pending.is_empty() {
+                    links += 1;
+                    if links > MAX_SYMLINKS {
+                        return Err(VfsError::SymlinkLoop);
+                    }
+                    let target = read_blob(repo, oid)?;
+                    let target = String::from_utf8(target).map_err(|_| {
+                        VfsError::InvalidInput("Invalid symlink target".to_string())
+                    })?;
+                    if target.starts_with('/') {
+                        let relative = Path::new(&target)
+                            .strip_prefix(&self.mount_point)
+                            .map_err(|_| VfsError::NotFound)?;
+                        let relative = relative.to_str().ok_or_else(|| {
+                            VfsError::InvalidInput("Invalid symlink target".to_string())
+                        })?;
+                        dirs.clear();
+                        node = Node::Tree(self.tree);
+                        pending.extend(components(relative));
+                    } else {
+                        pending.extend(components(&target));
+                    }
+                    continue;
+                }
+            }
+            // Only the trees are needed, to go back up with ".."
+            let oid = match child {
+                Node::Tree(oid) => oid,
+                _ => Oid::zero(),
+            };
+            dirs.push((name, oid));
+            node = child;
+        }
+
+        let resolved = dirs
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join("/");
+        Ok((node, format!("/{}", resolved)))
+    }
+
+    /// The status of a resolved node
+    fn node_stat(&self, repo: &Repository, node: Node, path: &str) -> VfsResult<libc::stat> {
+        let ino = path_ino(path);
+        Ok(match node {
+            Node::Tree(_) | Node::Submodule => {
+                make_stat(ino, libc::S_IFDIR | 0o755, 2, 4096, self.time)
+            }
+            Node::Blob(oid, mode) => {
+                let size = blob_size(repo, oid)?;
+                let perm = if mode == GIT_MODE_EXECUTABLE {
+                    0o755
+                } else {
+                    0o644
+                };
+                make_stat(ino, libc::S_IFREG | perm, 1, size, self.time)
+            }
+            Node::Symlink(oid) => {
+                let size = blob_size(repo, oid)?;
+                make_stat(ino, libc::S_IFLNK | 0o777, 1, size, self.time)
+            }
+        })
+    }
+
+    /// List a directory, including `.` and `..`
+    fn list(&self, repo: &Repository, node: Node, path: &str) -> VfsResult<DirEntryList> {
+        let parent = Path::new(path)
+            .parent()
+            .and_then(Path::to_str)
+            .unwrap_or("/");
+        let mut entries = vec![
+            (path_ino(path), ".".to_string(), libc::DT_DIR),
+            (path_ino(parent), "..".to_string(), libc::DT_DIR),
+        ];
+        let Node::Tree(oid) = node else {
+            return Ok(entries);
+        };
+
+        let child_prefix = if path == "/" { "" } else { path };
+        let tree = repo.find_tree(oid).map_err(git_error)?;
+        for entry in tree.iter() {
+            let Some(name) = entry.name() else {
+                continue;
+            };
+            let d_type = match entry.kind() {
+                Some(ObjectType::Tree) | Some(ObjectType::Commit) => libc::DT_DIR,
+                Some(ObjectType::Blob) if entry.filemode() == 0o120000 => libc::DT_LNK,
+                _ => libc::DT_REG,
+            };
+            let ino = path_ino(&format!("{}/{}", child_prefix, name));
+            entries.push((ino, name.to_string(), d_type));
+        }
+        Ok(entries)
+    }
+}
+
+#[async_trait::async_trait]
+impl Vfs for GitVfs {
+    fn translate_path(&self, path: &Path) -> VfsResult<PathBuf> {
+        // For virtual VFS, we just validate the path is under our mount point
+        if path.starts_with(&self.mount_point) {
+            Ok(path.to_path_buf())
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    async fn open(&self, path: &Path, flags: i32, _mode: u32) -> VfsResult<BoxedFileOps> {
+        let relative_path = self.translate_to_relative(path)?;
+        tracing::debug!(path = %relative_path, flags, "git open");
+        if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0
+        {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EROFS,
+            )));
+        }
+
+        let repo = self.repo.lock().unwrap();
+        let follow = flags & libc::O_NOFOLLOW == 0;
+        let (node, resolved) = self.lookup(&repo, &relative_path, follow)?;
+        let stat = self.node_stat(&repo, node, &resolved)?;
+        match node {
+            Node::Tree(_) | Node::Submodule => Ok(Arc::new(GitDirectoryOps {
+                entries: self.list(&repo, node, &resolved)?,
+                stat,
+                flags: Mutex::new(flags),
+                position: Mutex::new(0),
+            })),
+            Node::Blob(oid, _) => {
+                if flags & libc::O_DIRECTORY != 0 {
+                    return Err(VfsError::Other("Not a directory".to_string()));
+                }
+                Ok(Arc::new(GitFileOps {
+                    data: read_blob(&repo, oid)?,
+                    stat,
+                    offset: Mutex::new(0),
+                    flags: Mutex::new(flags),
+                }))
+            }
+            // Only reached with O_NOFOLLOW
+            Node::Symlink(_) => Err(VfsError::SymlinkLoop),
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = self.translate_to_relative(path)?;
+        let repo = self.repo.lock().unwrap();
+        let (node, resolved) = self.lookup(&repo, &relative_path, true)?;
+        self.node_stat(&repo, node, &resolved)
+    }
+
+    async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = self.translate_to_relative(path)?;
+        let repo = self.repo.lock().unwrap();
+        let (node, resolved) = self.lookup(&repo, &relative_path, false)?;
+        self.node_stat(&repo, node, &resolved)
+    }
+
+    async fn readlink(&self, path: &Path) -> VfsResult<PathBuf> {
+        let relative_path = self.translate_to_relative(path)?;
+        let repo = self.repo.lock().unwrap();
+        match self.lookup(&repo, &relative_path, false)? {
+            (Node::Symlink(oid), _) => {
+                let target = read_blob(&repo, oid)?;
+                Ok(PathBuf::from(OsString::from_vec(target)))
+            }
+            _ => Err(VfsError::InvalidInput("Not a symbolic link".to_string())),
+        }
+    }
+}
+
+/// Resolve a revision to its tree and commit time
+///
+/// A revision that names a tree rather than a commit has a time of 0.
+fn resolve_revision(repo: &Repository, revision: &str) -> VfsResult<(Oid, i64)> {
+    let object = repo.revparse_single(revision).map_err(|e| {
+        VfsError::Other(format!(
+            "Failed to resolve revision '{}': {}",
+            revision,
+            e.message()
+        ))
+    })?;
+    let time = object
+        .peel_to_commit()
+        .map(|commit| commit.time().seconds())
+        .unwrap_or(0);
+    let tree = object.peel_to_tree().map_err(|e| {
+        VfsError::Other(format!(
+            "Revision '{}' has no tree: {}",
+            revision,
+            e.message()
+        ))
+    })?;
+    Ok((tree.id(), time))
+}
+
+/// Map a git2 error to a VFS error
+fn git_error(e: git2::Error) -> VfsError {
+    match e.code() {
+        git2::ErrorCode::NotFound => VfsError::NotFound,
+        _ => VfsError::Other(format!("Git error: {}", e.message())),
+    }
+}
+
+fn read_blob(repo: &Repository, oid: Oid) -> VfsResult<Vec<u8>> {
+    Ok(repo.find_blob(oid).map_err(git_error)?.content().to_vec())
+}
+
+/// Size of a blob, read from its header without loading its contents
+fn blob_size(repo: &Repository, oid: Oid) -> VfsResult<i64> {
+    let odb = repo.odb().map_err(git_error)?;
+    let (size, _) = odb.read_header(oid).map_err(git_error)?;
+    Ok(size as i64)
+}
+
+/// Trees have no inode numbers, so derive a stable one from the path
+fn path_ino(path: &str) -> u64 {
+    if path == "/" {
+        return ROOT_INO;
+    }
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    // Keep clear of the root's inode number
+    hasher.finish().max(ROOT_INO + 1)
+}
+
+/// Build a stat structure for a Git tree entry
+fn make_stat(ino: u64, mode: u32, nlink: u64, size: i64, time: i64) -> libc::stat {
+    // Use MaybeUninit to construct libc::stat safely
+    let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
+    unsafe {
+        let stat_ptr = stat.as_mut_ptr();
+        (*stat_ptr).st_ino = ino;
+        (*stat_ptr).st_nlink = nlink;
+        (*stat_ptr).st_mode = mode;
+        (*stat_ptr).st_uid = libc::getuid();
+        (*stat_ptr).st_gid = libc::getgid();
+        (*stat_ptr).st_size = size;
+        (*stat_ptr).st_blksize = 4096;
+        (*stat_ptr).st_blocks = (size + 511) / 512;
+        (*stat_ptr).st_atime = time;
+        (*stat_ptr).st_mtime = time;
+        (*stat_ptr).st_ctime = time;
+        stat.assume_init()
+    }
+}
+
+/// File operations for blobs
+struct GitFileOps {
+    data: Vec<u8>,
+    stat: libc::stat,
+    offset: Mutex<i64>,
+    flags: Mutex<i32>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for GitFileOps {
+    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut offset = self.offset.lock().unwrap();
+        let start = *offset as usize;
+        if start >= self.data.len() {
+            return Ok(0);
+        }
+
+        let end = std::cmp::min(start + buf.len(), self.data.len());
+        let bytes_read = end - start;
+        buf[..bytes_read].copy_from_slice(&self.data[start..end]);
+        *offset += bytes_read as i64;
+        Ok(bytes_read)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        // Files are only ever opened for reading
+        Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+            libc::EBADF,
+        )))
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut current_offset = self.offset.lock().unwrap();
+        let size = self.data.len() as i64;
+        let new_offset = file_seek_position(*current_offset, size, offset, whence)?;
+        *current_offset = new_offset;
+        Ok(new_offset)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        Ok(self.stat)
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // Nothing to sync, the file is read-only
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // Nothing to sync, the file is read-only
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual file doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual files
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+}
+
+/// Directory operations for trees
+///
+/// A tree never changes, so it is listed once when opened.
+struct GitDirectoryOps {
+    entries: DirEntryList,
+    stat: libc::stat,
+    flags: Mutex<i32>,
+    /// Current position in the directory listing
+    position: Mutex<usize>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for GitDirectoryOps {
+    async fn read(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        // Cannot read from a directory
+        Err(VfsError::Other("Is a directory".to_string()))
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        // Cannot write to a directory
+        Err(VfsError::Other("Is a directory".to_string()))
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut position = self.position.lock().unwrap();
+        *position = dir_seek_position(*position, offset, whence)?;
+        Ok(*position as i64)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        Ok(self.stat)
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // Nothing to sync for directories
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // Nothing to sync for directories
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual directory doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual directories
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        // Nothing to do when closing a directory
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+
+    async fn getdents(&self) -> VfsResult<DirEntryList> {
+        // Return remaining entries and update position
+        let mut position = self.position.lock().unwrap();
+        let remaining = self.entries[(*position).min(self.entries.len())..].to_vec();
+        *position = self.entries.len();
+        Ok(remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Commit `files` as the whole tree of a new commit on HEAD
+    ///
+    /// Each file is `(path, contents, mode)`. Returns the commit id.
+    fn commit(repo: &Repository, files: &[(&str, &str, i32)]) -> Oid {
+        let mut index = repo.index().unwrap();
+        index.clear().unwrap();
+        for (path, contents, mode) in files {
+            let blob = repo.blob(contents.as_bytes()).unwrap();
+            let entry = git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: *mode as u32,
+                uid: 0,
+                gid: 0,
+                file_size: contents.len() as u32,
+                id: blob,
+                flags: 0,
+                flags_extended: 0,
+                path: path.as_bytes().to_vec(),
+            };
+            index.add(&entry).unwrap();
+        }
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::new(
+            "Test",
+            "test@example.com",
+            &git2::Time::new(1_700_000_000, 0),
+        )
+        .unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "commit",
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    async fn read_all(file: &BoxedFileOps) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0; 4];
+        loop {
+            let n = file.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return data;
+            }
+            data.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_git_vfs() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let first = commit(
+            &repo,
+            &[
+                ("README.md", "first version\n", 0o100644),
+                ("src/main.rs", "fn main() {}\n", 0o100644),
+                ("build.sh", "#!/bin/sh\n", GIT_MODE_EXECUTABLE),
+                ("docs", "src", 0o120000),
+            ],
+        );
+        commit(&repo, &[("README.md", "second version\n", 0o100644)]);
+
+        let vfs = GitVfs::new(dir.path(), &first.to_string(), PathBuf::from("/repo")).unwrap();
+        let file = vfs
+            .open(Path::new("/repo/README.md"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(read_all(&file).await, b"first version\n");
+        let stat = file.fstat().await.unwrap();
+        assert_eq!(stat.st_size, 14);
+        assert_eq!(stat.st_mode, libc::S_IFREG | 0o644);
+        assert_eq!(stat.st_mtime, 1_700_000_000);
+
+        let stat = vfs.stat(Path::new("/repo/build.sh")).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFREG | 0o755);
+        let stat = vfs.stat(Path::new("/repo/src")).await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFDIR);
+        assert!(matches!(
+            vfs.stat(Path::new("/repo/missing")).await,
+            Err(VfsError::NotFound)
+        ));
+
+        // Symlinks resolve within the tree
+        let stat = vfs.lstat(Path::new("/repo/docs")).await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFLNK);
+        assert_eq!(
+            vfs.readlink(Path::new("/repo/docs")).await.unwrap(),
+            PathBuf::from("src")
+        );
+        let file = vfs
+            .open(Path::new("/repo/docs/../src/./main.rs"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(read_all(&file).await, b"fn main() {}\n");
+
+        let dir_ops = vfs
+            .open(Path::new("/repo"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        let entries: Vec<(String, u8)> = dir_ops
+            .getdents()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(_, name, d_type)| (name, d_type))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (".".to_string(), libc::DT_DIR),
+                ("..".to_string(), libc::DT_DIR),
+                ("README.md".to_string(), libc::DT_REG),
+                ("build.sh".to_string(), libc::DT_REG),
+                ("docs".to_string(), libc::DT_LNK),
+                ("src".to_string(), libc::DT_DIR),
+            ]
+        );
+
+        assert!(matches!(
+            vfs.open(Path::new("/repo/README.md"), libc::O_WRONLY, 0).await,
+            Err(VfsError::IoError(e)) if e.raw_os_error() == Some(libc::EROFS)
+        ));
+
+        // Other revisions show other trees
+        let vfs = GitVfs::new(dir.path(), "HEAD", PathBuf::from("/repo")).unwrap();
+        let file = vfs
+            .open(Path::new("/repo/README.md"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(read_all(&file).await, b"second version\n");
+        assert!(matches!(
+            vfs.stat(Path::new("/repo/src")).await,
+            Err(VfsError::NotFound)
+        ));
+        assert!(GitVfs::new(dir.path(), "no-such-ref", PathBuf::from("/repo")).is_err());
+    }
+}
@@ -0,0 +1,679 @@
+use super::file::{dir_seek_position, file_seek_position, BoxedFileOps, FileOps};
+use super::{normalize_path, Vfs, VfsError, VfsResult};
+use agentfs_sdk::Filesystem;
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Inode number of the root directory
+const ROOT_INO: u64 = 1;
+
+/// How long a request may take before the file is reported as unreadable
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a path that was not found is reported as missing without asking
+/// the server again
+const NOT_FOUND_TTL: Duration = Duration::from_secs(5);
+
+/// A file fetched over HTTP
+struct RemoteFile {
+    ino: u64,
+    data: Vec<u8>,
+    /// When the file was fetched, reported as all of its times
+    fetched_at: i64,
+}
+
+/// Files looked up so far, by path relative to the mount point
+///
+/// A path that was not found is remembered for a short while, so probing for
+/// files that don't exist, as interpreters do when searching paths, doesn't
+/// repeat requests, while a file uploaded later is still found.
+#[derive(Default)]
+struct RemoteFiles {
+    files: HashMap<String, Arc<RemoteFile>>,
+    /// When each path that was not found was last requested
+    missing: HashMap<String, Instant>,
+    next_ino: u64,
+}
+
+/// A read-only virtual filesystem of files fetched over HTTP
+///
+/// The file at `<mount point>/<path>` holds the body of a GET of
+/// `<base URL>/<path>`. Files are fetched the first time they are looked up
+/// and kept in memory for the lifetime of the mount, so the remote files are
+/// assumed not to change and to be small enough to hold. A 404 response means
+/// the file does not exist, and any other error is reported as `EIO`.
+///
+/// HTTP has no way to list directories, so only the mount point is a
+/// directory, and it lists as empty. Every other path is a regular file.
+///
+/// With a cache database, fetched files are also stored in it under the
+/// same paths and read from it on later runs instead of being fetched again.
+#[derive(Clone)]
+pub struct HttpVfs {
+    /// URL the relative paths are appended to, without a trailing slash
+    base_url: String,
+    /// The virtual path as seen by the sandboxed process
+    mount_point: PathBuf,
+    agent: ureq::Agent,
+    files: Arc<Mutex<RemoteFiles>>,
+    cache: Option<Filesystem>,
+    not_found_ttl: Duration,
+}
+
+impl HttpVfs {
+    /// Create a VFS for the files under `base_url`
+    ///
+    /// # Arguments
+    /// * `base_url` - URL of the directory to mount (e.g., "https://example.com/artifacts")
+    /// * `mount_point` - The virtual path seen by the guest (e.g., "/remote")
+    pub fn new(base_url: &str, mount_point: PathBuf) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            mount_point,
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+            files: Arc::new(Mutex::new(RemoteFiles {
+                files: HashMap::new(),
+                missing: HashMap::new(),
+                next_ino: ROOT_INO + 1,
+            })),
+            cache: None,
+            not_found_ttl: NOT_FOUND_TTL,
+        }
+    }
+
+    /// Store fetched files in `cache` and read files from it before fetching them
+    pub fn with_cache(mut self, cache: Filesystem) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Get the mount point path
+    pub fn mount_point(&self) -> &Path {
+        &self.mount_point
+    }
+
+    /// Translate a sandbox path to a normalized path relative to the mount
+    /// point, which is "/" for the mount point itself
+    fn translate_to_relative(&self, path: &Path) -> VfsResult<String> {
+        let relative = path
+            .strip_prefix(&self.mount_point)
+            .map_err(|_| VfsError::NotFound)?;
+        normalize_path(&Path::new("/").join(relative))
+            .to_str()
+            .map(str::to_string)
+            .ok_or_else(|| VfsError::InvalidInput("Invalid path".to_string()))
+    }
+
+    /// Look up the file at `relative`, fetching it if it was not looked up before
+    async fn lookup(&self, relative: &str) -> VfsResult<Arc<RemoteFile>> {
+        {
+            let mut files = self.files.lock().unwrap();
+            if let Some(file) = files.files.get(relative) {
+                return Ok(file.clone());
+            }
+            match files.missing.get(relative) {
+                Some(at) if at.elapsed() < self.not_found_ttl => return Err(VfsError::NotFound),
+                Some(_) => {
+                    files.missing.remove(relative);
+                }
+                None => {}
+            }
+        }
+
+        let data = match self.read_cache(relative).await {
+            Some(data) => Some(data),
+            None => {
+                let data = self.fetch(relative).await?;
+                if let Some(data) = &data {
+                    self.write_cache(relative, data).await;
+                }
+                data
+            }
+        };
+
+        let mut files = self.files.lock().unwrap();
+        let Some(data) = data else {
+            files.missing.insert(relative.to_string(), Instant::now());
+            return Err(VfsError::NotFound);
+        };
+        // Another lookup may have fetched the file meanwhile
+        if let Some(file) = files.files.get(relative) {
+            return Ok(file.clone());
+        }
+        let ino = files.next_ino;
+        files.next_ino += 1;
+        let file = Arc::new(RemoteFile {
+            ino,
+            data,
+            fetched_at: now(),
+        });
+        files.files.insert(relative.to_string(), file.clone());
+        Ok(file)
+    }
+
+    /// GET the file at `relative`, returning None if the server has no such file
+    async fn fetch(&self, relative: &str) -> VfsResult<Option<Vec<u8>>> {
+        let url = format!("{}{}", self.base_url, encode_path(relative));
+        let agent = self.agent.clone();
+        tokio::task::spawn_blocking(move || {
+            tracing::debug!(url = %url, "http fetch");
+            let response = match agent.get(&url).call() {
+                Ok(response) => response,
+                Err(ureq::Error::Status(404, _)) => return Ok(None),
+                Err(e) => {
+                    tracing::warn!(url = %url, error = %e, "http fetch failed");
+                    return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                        libc::EIO,
+                    )));
+                }
+            };
+            let mut data = Vec::new();
+            response.into_reader().read_to_end(&mut data)?;
+            Ok(Some(data))
+        })
+        .await
+        .map_err(|e| VfsError::Other(format!("HTTP fetch panicked: {}", e)))?
+    }
+
+    /// Read a file from the cache database, if there is one and it has the file
+    async fn read_cache(&self, relative: &str) -> Option<Vec<u8>> {
+        let cache = self.cache.as_ref()?;
+        match cache.read_file(relative).await {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!(path = %relative, error = %e, "http cache read failed");
+                None
+            }
+        }
+    }
+
+    /// Store a fetched file in the cache database, if there is one
+    ///
+    /// A file that can't be cached is still served, so failures are only logged.
+    async fn write_cache(&self, relative: &str, data: &[u8]) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        if let Err(e) = cache
+            .bulk_write([(relative.to_string(), data.to_vec())])
+            .await
+        {
+            tracing::warn!(path = %relative, error = %e, "http cache write failed");
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Vfs for HttpVfs {
+    fn translate_path(&self, path: &Path) -> VfsResult<PathBuf> {
+        // For virtual VFS, we just validate the path is under our mount point
+        if path.starts_with(&self.mount_point) {
+            Ok(path.to_path_buf())
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    async fn open(&self, path: &Path, flags: i32, _mode: u32) -> VfsResult<BoxedFileOps> {
+        let relative_path = self.translate_to_relative(path)?;
+        tracing::debug!(path = %relative_path, flags, "http open");
+        if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0
+        {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EROFS,
+            )));
+        }
+
+        if relative_path == "/" {
+            return Ok(Arc::new(HttpDirectoryOps {
+                flags: Mutex::new(flags),
+                position: Mutex::new(0),
+            }));
+        }
+        let file = self.lookup(&relative_path).await?;
+        if flags & libc::O_DIRECTORY != 0 {
+            return Err(VfsError::Other("Not a directory".to_string()));
+        }
+        Ok(Arc::new(HttpFileOps {
+            file,
+            offset: Mutex::new(0),
+            flags: Mutex::new(flags),
+        }))
+    }
+
+    async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = self.translate_to_relative(path)?;
+        if relative_path == "/" {
+            return Ok(root_stat());
+        }
+        Ok(file_stat(&*self.lookup(&relative_path).await?))
+    }
+
+    async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
+        // There are no symlinks
+        self.stat(path).await
+    }
+
+    async fn readlink(&self, path: &Path) -> VfsResult<PathBuf> {
+        self.lstat(path).await?;
+        Err(VfsError::InvalidInput("Not a symbolic link".to_string()))
+    }
+}
+
+/// Percent-encode the characters of a path that can't appear in a URL path
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Build a stat structure for an HTTP mount entry
+fn make_stat(ino: u64, mode: u32, nlink: u64, size: i64, time: i64) -> libc::stat {
+    // Use MaybeUninit to construct libc::stat safely
+    let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
+    unsafe {
+        let stat_ptr = stat.as_mut_ptr();
+        (*stat_ptr).st_ino = ino;
+        (*stat_ptr).st_nlink = nlink;
+        (*stat_ptr).st_mode = mode;
+        (*stat_ptr).st_uid = libc::getuid();
+        (*stat_ptr).st_gid = libc::getgid();
+        (*stat_ptr).st_size = size;
+        (*stat_ptr).st_blksize = 4096;
+        (*stat_ptr).st_blocks = (size + 4095) / 4096;
+        (*stat_ptr).st_atime = time;
+        (*stat_ptr).st_mtime = time;
+        (*stat_ptr).st_ctime = time;
+        stat.assume_init()
+    }
+}
+
+/// The status of the mount point
+fn root_stat() -> libc::stat {
+    make_stat(ROOT_INO, libc::S_IFDIR | 0o555, 2, 4096, 0)
+}
+
+/// The status of a fetched file
+fn file_stat(file: &RemoteFile) -> libc::stat {
+    make_stat(
+        file.ino,
+        libc::S_IFREG | 0o444,
+        1,
+        file.data.len() as i64,
+        file.fetched_at,
+    )
+}
+
+/// File operations for fetched files
+struct HttpFileOps {
+    file: Arc<RemoteFile>,
+    offset: Mutex<i64>,
+    flags: Mutex<i32>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for HttpFileOps {
+    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut offset = self.offset.lock().unwrap();
+        let data = &self.file.data;
+        let start = *offset as usize;
+        if start >= data.len() {
+            return Ok(0);
+        }
+
+        let end = std::cmp::min(start + buf.len(), data.len());
+        let bytes_read = end - start;
+        buf[..bytes_read].copy_from_slice(&data[start..end]);
+        *offset += bytes_read as i64;
+        Ok(bytes_read)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        // Files are only ever opened for reading
+        Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+            libc::EBADF,
+        )))
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut current_offset = self.offset.lock().unwrap();
+        let size = self.file.data.len() as i64;
+        let new_offset = file_seek_position(*current_offset, size, offset, whence)?;
+        *current_offset = new_offset;
+        Ok(new_offset)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        Ok(file_stat(&self.file))
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // Nothing to sync, the file is read-only
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // Nothing to sync, the file is read-only
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual file doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual files
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+}
+
+/// Directory operations for the mount point, which lists only `.` and `..`
+struct HttpDirectoryOps {
+    flags: Mutex<i32>,
+    /// Current position in the directory listing
+    position: Mutex<usize>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for HttpDirectoryOps {
+    async fn read(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        // Cannot read from a directory
+        Err(VfsError::Other("Is a directory".to_string()))
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        // Cannot write to a directory
+        Err(VfsError::Other("Is a directory".to_string()))
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut position = self.position.lock().unwrap();
+        *position = dir_seek_position(*position, offset, whence)?;
+        Ok(*position as i64)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        Ok(root_stat())
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // Nothing to sync for directories
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // Nothing to sync for directories
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual directory doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual directories
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        // Nothing to do when closing a directory
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+
+    async fn getdents(&self) -> VfsResult<Vec<(u64, String, u8)>> {
+        let entries = [
+            (ROOT_INO, ".".to_string(), libc::DT_DIR),
+            (ROOT_INO, "..".to_string(), libc::DT_DIR),
+        ];
+        let mut position = self.position.lock().unwrap();
+        let remaining = entries[(*position).min(entries.len())..].to_vec();
+        *position = entries.len();
+        Ok(remaining)
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Serve `files` over HTTP on a local port, counting the requests
+    ///
+    /// Returns the base URL and the request counter.
+    fn serve(files: &'static [(&'static str, &'static [u8])]) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let path = request_line.split(' ').nth(1).unwrap_or("");
+                let response = match files.iter().find(|(p, _)| *p == path) {
+                    Some((_, body)) => {
+                        let mut response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            body.len()
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(body);
+                        response
+                    }
+                    None => {
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_vec()
+                    }
+                };
+                stream.write_all(&response).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    const FILES: &[(&str, &[u8])] = &[
+        ("/artifacts/model.json", b"{\"layers\": 3}"),
+        ("/artifacts/data/a%20b.txt", b"spaced"),
+    ];
+
+    async fn read_all(file: &BoxedFileOps) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0; 4];
+        loop {
+            let n = file.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return data;
+            }
+            data.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_vfs() {
+        let (url, requests) = serve(FILES);
+        let mut vfs = HttpVfs::new(&format!("{}/artifacts/", url), PathBuf::from("/remote"));
+
+        let file = vfs
+            .open(Path::new("/remote/model.json"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(read_all(&file).await, b"{\"layers\": 3}");
+        let stat = file.fstat().await.unwrap();
+        assert_eq!(stat.st_size, 13);
+        assert_eq!(stat.st_mode, libc::S_IFREG | 0o444);
+        assert_eq!(file.seek(-3, libc::SEEK_END).await.unwrap(), 10);
+        assert_eq!(read_all(&file).await, b" 3}");
+
+        // The file is fetched only once
+        let stat = vfs.stat(Path::new("/remote/./model.json")).await.unwrap();
+        assert_eq!(stat.st_size, 13);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Paths are percent-encoded
+        let file = vfs
+            .open(Path::new("/remote/data/a b.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(read_all(&file).await, b"spaced");
+
+        // A 404 is ENOENT, and is not requested again right away
+        for _ in 0..2 {
+            assert!(matches!(
+                vfs.stat(Path::new("/remote/missing.json")).await,
+                Err(VfsError::NotFound)
+            ));
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // Once the 404 expires, the path is requested again
+        vfs.not_found_ttl = Duration::ZERO;
+        assert!(matches!(
+            vfs.stat(Path::new("/remote/missing.json")).await,
+            Err(VfsError::NotFound)
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        // The mount point is an empty directory
+        let stat = vfs.stat(Path::new("/remote")).await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFDIR);
+        let dir = vfs
+            .open(Path::new("/remote"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        let names: Vec<String> = dir
+            .getdents()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(_, name, _)| name)
+            .collect();
+        assert_eq!(names, vec![".", ".."]);
+
+        // Nothing can be written
+        for flags in [libc::O_WRONLY, libc::O_RDWR, libc::O_RDONLY | libc::O_CREAT] {
+            assert!(matches!(
+                vfs.open(Path::new("/remote/model.json"), flags, 0o644).await,
+                Err(VfsError::IoError(e)) if e.raw_os_error() == Some(libc::EROFS)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_vfs_cache() {
+        let (url, requests) = serve(FILES);
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("cache.db");
+        let cache = Filesystem::new(cache_path.to_str().unwrap()).await.unwrap();
+
+        let vfs = HttpVfs::new(&format!("{}/artifacts", url), PathBuf::from("/remote"))
+            .with_cache(cache.clone());
+        let file = vfs
+            .open(Path::new("/remote/data/a b.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(read_all(&file).await, b"spaced");
+        assert_eq!(
+            cache.read_file("/data/a b.txt").await.unwrap(),
+            Some(b"spaced".to_vec())
+        );
+
+        // A new mount reads the file from the cache instead of fetching it
+        let vfs =
+            HttpVfs::new(&format!("{}/artifacts", url), PathBuf::from("/remote")).with_cache(cache);
+        let file = vfs
+            .open(Path::new("/remote/data/a b.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(read_all(&file).await, b"spaced");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,798 @@
+use super::file::{dir_seek_position, file_seek_position, BoxedFileOps, FileOps};
+use super::{components, Vfs, VfsError, VfsResult, MAX_SYMLINKS};
+use std::collections::{BTreeMap, HashMap};
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Inode number of the root directory
+const ROOT_INO: u64 = 1;
+
+/// Type alias for directory entry list: (inode, name, type)
+type DirEntryList = Vec<(u64, String, u8)>;
+
+/// Contents of an inode
+enum InodeKind {
+    File(Vec<u8>),
+    Directory {
+        parent: u64,
+        entries: BTreeMap<String, u64>,
+    },
+    Symlink(String),
+}
+
+/// An inode in the in-memory filesystem
+struct Inode {
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    atime: i64,
+    mtime: i64,
+    ctime: i64,
+    kind: InodeKind,
+}
+
+impl Inode {
+    fn new(mode: u32, kind: InodeKind) -> Self {
+        let now = now();
+        Self {
+            mode,
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            atime: now,
+            mtime: now,
+            ctime: now,
+            kind,
+        }
+    }
+
+    fn size(&self) -> i64 {
+        match &self.kind {
+            InodeKind::File(data) => data.len() as i64,
+            InodeKind::Directory { .. } => 4096,
+            InodeKind::Symlink(target) => target.len() as i64,
+        }
+    }
+
+    /// Truncate or extend a regular file to `len` bytes, filling with zeroes
+    fn set_len(&mut self, len: i64) -> VfsResult<()> {
+        let InodeKind::File(data) = &mut self.kind else {
+            return Err(VfsError::InvalidInput("Not a regular file".to_string()));
+        };
+        data.resize(len as usize, 0);
+        let now = now();
+        self.mtime = now;
+        self.ctime = now;
+        Ok(())
+    }
+
+    fn d_type(&self) -> u8 {
+        match &self.kind {
+            InodeKind::File(_) => libc::DT_REG,
+            InodeKind::Directory { .. } => libc::DT_DIR,
+            InodeKind::Symlink(_) => libc::DT_LNK,
+        }
+    }
+
+    fn stat(&self, ino: u64) -> libc::stat {
+        let nlink = match &self.kind {
+            InodeKind::Directory { .. } => 2,
+            _ => 1,
+        };
+        let size = self.size();
+
+        // Use MaybeUninit to construct libc::stat safely
+        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
+        unsafe {
+            let stat_ptr = stat.as_mut_ptr();
+            (*stat_ptr).st_dev = 0;
+            (*stat_ptr).st_ino = ino;
+            (*stat_ptr).st_nlink = nlink;
+            (*stat_ptr).st_mode = self.mode;
+            (*stat_ptr).st_uid = self.uid;
+            (*stat_ptr).st_gid = self.gid;
+            (*stat_ptr).st_rdev = 0;
+            (*stat_ptr).st_size = size;
+            (*stat_ptr).st_blksize = 4096;
+            (*stat_ptr).st_blocks = (size + 4095) / 4096;
+            (*stat_ptr).st_atime = self.atime;
+            (*stat_ptr).st_atime_nsec = 0;
+            (*stat_ptr).st_mtime = self.mtime;
+            (*stat_ptr).st_mtime_nsec = 0;
+            (*stat_ptr).st_ctime = self.ctime;
+            (*stat_ptr).st_ctime_nsec = 0;
+            stat.assume_init()
+        }
+    }
+}
+
+/// The inode table shared by a mount and all of its open files
+struct MemFs {
+    inodes: HashMap<u64, Inode>,
+    next_ino: u64,
+}
+
+impl MemFs {
+    fn new() -> Self {
+        let root = Inode::new(
+            libc::S_IFDIR | 0o755,
+            InodeKind::Directory {
+                parent: ROOT_INO,
+                entries: BTreeMap::new(),
+            },
+        );
+        Self {
+            inodes: HashMap::from([(ROOT_INO, root)]),
+            next_ino: ROOT_INO + 1,
+        }
+    }
+
+    fn inode(&self, ino: u64) -> VfsResult<&Inode> {
+        self.inodes.get(&ino).ok_or(VfsError::NotFound)
+    }
+
+    fn inode_mut(&mut self, ino: u64) -> VfsResult<&mut Inode> {
+        self.inodes.get_mut(&ino).ok_or(VfsError::NotFound)
+    }
+
+    /// Resolve a path relative to the mount point to an inode number
+    ///
+    /// Symbolic links in intermediate components are always followed; the
+    /// last component is followed only if `follow` is set. Absolute link
+    /// targets are sandbox paths and must point into this mount.
+    fn lookup(&self, mount_point: &Path, path: &str, follow: bool) -> VfsResult<u64> {
+        let mut pending = components(path);
+        let mut ino = ROOT_INO;
+        let mut links = 0;
+
+        while let Some(name) = pending.pop() {
+            let (parent, entries) = match &self.inode(ino)?.kind {
+                InodeKind::Directory { parent, entries } => (*parent, entries),
+                _ => return Err(VfsError::Other("Not a directory".to_string())),
+            };
+            match name.as_str() {
+                "." => continue,
+                ".." => {
+                    ino = parent;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let child = *entries.get(&name).ok_or(VfsError::NotFound)?;
+            if let InodeKind::Symlink(target) = &self.inode(child)?.kind {
+                if follow || !pending.is_empty() {
+                    links += 1;
+                    if links > MAX_SYMLINKS {
+                        return Err(VfsError::SymlinkLoop);
+                    }
+                    if target.starts_with('/') {
+                        let relative = Path::new(target)
+                            .strip_prefix(mount_point)
+                            .map_err(|_| VfsError::NotFound)?;
+                        let relative = relative.to_str().ok_or_else(|| {
+                            VfsError::InvalidInput("Invalid symlink target".to_string())
+                        })?;
+                        ino = ROOT_INO;
+                        pending.extend(components(relative));
+                    } else {
+                        pending.extend(components(target));
+                    }
+                    continue;
+                }
+            }
+            ino = child;
+        }
+        Ok(ino)
+    }
+
+    /// Create a new inode named `name` in the directory `parent`
+    fn create(&mut self, parent: u64, name: &str, inode: Inode) -> VfsResult<u64> {
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        let ino = self.next_ino;
+        let now = now();
+        match &mut self.inode_mut(parent)?.kind {
+            InodeKind::Directory { entries, .. } => {
+                if entries.contains_key(name) {
+                    return Err(VfsError::AlreadyExists);
+                }
+                entries.insert(name.to_string(), ino);
+            }
+            _ => return Err(VfsError::Other("Not a directory".to_string())),
+        }
+        let parent = self.inode_mut(parent)?;
+        parent.mtime = now;
+        parent.ctime = now;
+        self.inodes.insert(ino, inode);
+        self.next_ino += 1;
+        Ok(ino)
+    }
+}
+
+/// An in-memory virtual filesystem
+///
+/// Files, directories, and symbolic links are kept in process memory and
+/// disappear when the sandbox exits. This is useful for scratch space that
+/// should not touch the host filesystem or a database.
+#[derive(Clone)]
+pub struct MemVfs {
+    /// The inode table, shared with open files
+    fs: Arc<Mutex<MemFs>>,
+    /// The virtual path as seen by the sandboxed process
+    mount_point: PathBuf,
+}
+
+impl MemVfs {
+    /// Create a new, empty in-memory VFS
+    ///
+    /// # Arguments
+    /// * `mount_point` - The virtual path seen by the guest (e.g., "/scratch")
+    pub fn new(mount_point: PathBuf) -> Self {
+        Self {
+            fs: Arc::new(Mutex::new(MemFs::new())),
+            mount_point,
+        }
+    }
+
+    /// Get the mount point path
+    pub fn mount_point(&self) -> &Path {
+        &self.mount_point
+    }
+
+    /// Translate a sandbox path to a path relative to the mount point
+    fn translate_to_relative(&self, path: &Path) -> VfsResult<String> {
+        let relative = path
+            .strip_prefix(&self.mount_point)
+            .map_err(|_| VfsError::NotFound)?;
+        let relative = relative
+            .to_str()
+            .ok_or_else(|| VfsError::InvalidInput("Invalid path".to_string()))?;
+        Ok(format!("/{}", relative))
+    }
+
+    /// Resolve the directory that would contain `relative` and the name of
+    /// the last component
+    fn lookup_parent(&self, fs: &MemFs, relative: &str) -> VfsResult<(u64, String)> {
+        let (parent, name) = match relative.trim_end_matches('/').rsplit_once('/') {
+            Some((parent, name)) => (parent, name),
+            None => ("", relative),
+        };
+        let parent = fs.lookup(&self.mount_point, parent, true)?;
+        Ok((parent, name.to_string()))
+    }
+}
+
+#[async_trait::async_trait]
+impl Vfs for MemVfs {
+    fn translate_path(&self, path: &Path) -> VfsResult<PathBuf> {
+        // For virtual VFS, we just validate the path is under our mount point
+        if path.starts_with(&self.mount_point) {
+            Ok(path.to_path_buf())
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+
+    async fn open(&self, path: &Path, flags: i32, mode: u32) -> VfsResult<BoxedFileOps> {
+        let relative_path = self.translate_to_relative(path)?;
+        tracing::debug!(path = %relative_path, flags, "mem open");
+        if flags & libc::O_TMPFILE == libc::O_TMPFILE {
+            return Err(VfsError::NotSupported);
+        }
+
+        let mut fs = self.fs.lock().unwrap();
+        let follow = flags & libc::O_NOFOLLOW == 0;
+        let ino = match fs.lookup(&self.mount_point, &relative_path, follow) {
+            Ok(ino) => {
+                if flags & libc::O_CREAT != 0 && flags & libc::O_EXCL != 0 {
+                    return Err(VfsError::AlreadyExists);
+                }
+                ino
+            }
+            Err(VfsError::NotFound) if flags & libc::O_CREAT != 0 => {
+                let (parent, name) = self.lookup_parent(&fs, &relative_path)?;
+                let inode =
+                    Inode::new(libc::S_IFREG | (mode & 0o7777), InodeKind::File(Vec::new()));
+                fs.create(parent, &name, inode)?
+            }
+            Err(e) => return Err(e),
+        };
+
+        let inode = fs.inode_mut(ino)?;
+        match &mut inode.kind {
+            InodeKind::Directory { parent, .. } => Ok(Arc::new(MemDirectoryOps {
+                fs: self.fs.clone(),
+                ino,
+                parent: *parent,
+                flags: Mutex::new(flags),
+                entries: Mutex::new(None),
+                position: Mutex::new(0),
+            })),
+            InodeKind::File(data) => {
+                if flags & libc::O_DIRECTORY != 0 {
+                    return Err(VfsError::Other("Not a directory".to_string()));
+                }
+                if flags & libc::O_TRUNC != 0 && flags & libc::O_ACCMODE != libc::O_RDONLY {
+                    data.clear();
+                    let now = now();
+                    inode.mtime = now;
+                    inode.ctime = now;
+                }
+                Ok(Arc::new(MemFileOps {
+                    fs: self.fs.clone(),
+                    ino,
+                    offset: Mutex::new(0),
+                    flags: Mutex::new(flags),
+                }))
+            }
+            // Only reached with O_NOFOLLOW
+            InodeKind::Symlink(_) => Err(VfsError::SymlinkLoop),
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = self.translate_to_relative(path)?;
+        let fs = self.fs.lock().unwrap();
+        let ino = fs.lookup(&self.mount_point, &relative_path, true)?;
+        Ok(fs.inode(ino)?.stat(ino))
+    }
+
+    async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = self.translate_to_relative(path)?;
+        let fs = self.fs.lock().unwrap();
+        let ino = fs.lookup(&self.mount_point, &relative_path, false)?;
+        Ok(fs.inode(ino)?.stat(ino))
+    }
+
+    async fn symlink(&self, target: &Path, linkpath: &Path) -> VfsResult<()> {
+        let linkpath_rel = self.translate_to_relative(linkpath)?;
+        let target_str = target
+            .to_str()
+            .ok_or_else(|| VfsError::InvalidInput("Invalid target path".to_string()))?;
+
+        let mut fs = self.fs.lock().unwrap();
+        let (parent, name) = self.lookup_parent(&fs, &linkpath_rel)?;
+        let inode = Inode::new(
+            libc::S_IFLNK | 0o777,
+            InodeKind::Symlink(target_str.to_string()),
+        );
+        fs.create(parent, &name, inode)?;
+        Ok(())
+    }
+
+    async fn truncate(&self, path: &Path, len: i64) -> VfsResult<()> {
+        let relative_path = self.translate_to_relative(path)?;
+        let mut fs = self.fs.lock().unwrap();
+        let ino = fs.lookup(&self.mount_point, &relative_path, true)?;
+        let inode = fs.inode_mut(ino)?;
+        if let InodeKind::Directory { .. } = inode.kind {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EISDIR,
+            )));
+        }
+        inode.set_len(len)
+    }
+
+    async fn readlink(&self, path: &Path) -> VfsResult<PathBuf> {
+        let relative_path = self.translate_to_relative(path)?;
+        let fs = self.fs.lock().unwrap();
+        let ino = fs.lookup(&self.mount_point, &relative_path, false)?;
+        match &fs.inode(ino)?.kind {
+            InodeKind::Symlink(target) => Ok(PathBuf::from(target)),
+            _ => Err(VfsError::InvalidInput("Not a symbolic link".to_string())),
+        }
+    }
+}
+
+/// File operations for in-memory files
+///
+/// Reads and writes go straight to the shared inode, so every open file
+/// description of the same file sees the same contents.
+struct MemFileOps {
+    fs: Arc<Mutex<MemFs>>,
+    ino: u64,
+    offset: Mutex<i64>,
+    flags: Mutex<i32>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for MemFileOps {
+    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut fs = self.fs.lock().unwrap();
+        let mut offset = self.offset.lock().unwrap();
+        let inode = fs.inode_mut(self.ino)?;
+        let InodeKind::File(data) = &inode.kind else {
+            return Err(VfsError::Other("Is a directory".to_string()));
+        };
+
+        let start = *offset as usize;
+        if start >= data.len() {
+            return Ok(0);
+        }
+
+        let end = std::cmp::min(start + buf.len(), data.len());
+        let bytes_read = end - start;
+        buf[..bytes_read].copy_from_slice(&data[start..end]);
+        *offset += bytes_read as i64;
+        inode.atime = now();
+
+        Ok(bytes_read)
+    }
+
+    async fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+        let append = self.get_flags() & libc::O_APPEND != 0;
+        let mut fs = self.fs.lock().unwrap();
+        let mut offset = self.offset.lock().unwrap();
+        let inode = fs.inode_mut(self.ino)?;
+        let InodeKind::File(data) = &mut inode.kind else {
+            return Err(VfsError::Other("Is a directory".to_string()));
+        };
+
+        let start = if append { data.len() } else { *offset as usize };
+
+        // Extend the buffer if necessary, filling any hole with zeroes
+        if start + buf.len() > data.len() {
+            data.resize(start + buf.len(), 0);
+        }
+
+        data[start..start + buf.len()].copy_from_slice(buf);
+        *offset = (start + buf.len()) as i64;
+
+        let now = now();
+        inode.mtime = now;
+        inode.ctime = now;
+
+        Ok(buf.len())
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let fs = self.fs.lock().unwrap();
+        let mut current_offset = self.offset.lock().unwrap();
+
+        let size = fs.inode(self.ino)?.size();
+        let new_offset = file_seek_position(*current_offset, size, offset, whence)?;
+        *current_offset = new_offset;
+        Ok(new_offset)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        let fs = self.fs.lock().unwrap();
+        Ok(fs.inode(self.ino)?.stat(self.ino))
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // Nothing to sync, the data only lives in memory
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // Nothing to sync, the data only lives in memory
+        Ok(())
+    }
+
+    async fn set_len(&self, len: i64) -> VfsResult<()> {
+        if self.get_flags() & libc::O_ACCMODE == libc::O_RDONLY {
+            return Err(VfsError::InvalidInput("Not open for writing".to_string()));
+        }
+        let mut fs = self.fs.lock().unwrap();
+        fs.inode_mut(self.ino)?.set_len(len)
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual file doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual files
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+}
+
+/// Directory operations for in-memory directories
+struct MemDirectoryOps {
+    fs: Arc<Mutex<MemFs>>,
+    ino: u64,
+    parent: u64,
+    flags: Mutex<i32>,
+    /// Directory entries, listed on the first getdents call
+    entries: Mutex<Option<DirEntryList>>,
+    /// Current position in the directory listing
+    position: Mutex<usize>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for MemDirectoryOps {
+    async fn read(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        // Cannot read from a directory
+        Err(VfsError::Other("Is a directory".to_string()))
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        // Cannot write to a directory
+        Err(VfsError::Other("Is a directory".to_string()))
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut position = self.position.lock().unwrap();
+        *position = dir_seek_position(*position, offset, whence)?;
+        if *position == 0 {
+            // Rewinding picks up entries added since the listing was taken
+            *entries = None;
+        }
+        Ok(*position as i64)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        let fs = self.fs.lock().unwrap();
+        Ok(fs.inode(self.ino)?.stat(self.ino))
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // Nothing to sync for directories
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // Nothing to sync for directories
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual directory doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual directories
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        // Nothing to do when closing a directory
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+
+    async fn getdents(&self) -> VfsResult<DirEntryList> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.is_none() {
+            let fs = self.fs.lock().unwrap();
+            let InodeKind::Directory { entries: dir, .. } = &fs.inode(self.ino)?.kind else {
+                return Err(VfsError::Other("Not a directory".to_string()));
+            };
+
+            let mut result = vec![
+                (self.ino, ".".to_string(), libc::DT_DIR),
+                (self.parent, "..".to_string(), libc::DT_DIR),
+            ];
+            for (name, &ino) in dir {
+                let d_type = fs.inode(ino)?.d_type();
+                result.push((ino, name.clone(), d_type));
+            }
+            *entries = Some(result);
+        }
+
+        // Return remaining entries and update position
+        let mut position = self.position.lock().unwrap();
+        let all_entries = entries.as_ref().unwrap();
+        let remaining = all_entries[(*position).min(all_entries.len())..].to_vec();
+        *position = all_entries.len();
+        Ok(remaining)
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RDWR_CREAT: i32 = libc::O_RDWR | libc::O_CREAT;
+
+    #[tokio::test]
+    async fn test_read_write_seek() {
+        let vfs = MemVfs::new(PathBuf::from("/scratch"));
+        let path = Path::new("/scratch/file.txt");
+
+        let file = vfs.open(path, RDWR_CREAT, 0o644).await.unwrap();
+        assert_eq!(file.write(b"hello world").await.unwrap(), 11);
+        assert_eq!(file.seek(6, libc::SEEK_SET).await.unwrap(), 6);
+        assert_eq!(file.write(b"there").await.unwrap(), 5);
+        assert_eq!(file.seek(-5, libc::SEEK_END).await.unwrap(), 6);
+        let mut buf = [0u8; 16];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 5);
+        assert_eq!(&buf[..5], b"there");
+        assert_eq!(file.read(&mut buf).await.unwrap(), 0);
+        assert!(file.seek(-1, libc::SEEK_SET).await.is_err());
+
+        // Writing past the end leaves a hole of zeroes
+        assert_eq!(file.seek(2, libc::SEEK_END).await.unwrap(), 13);
+        file.write(b"!").await.unwrap();
+        file.close().await.unwrap();
+
+        let stat = vfs.stat(path).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFREG | 0o644);
+        assert_eq!(stat.st_size, 14);
+
+        // A second open sees the data and O_TRUNC discards it
+        let file = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 14);
+        assert_eq!(&buf[..14], b"hello there\0\0!");
+        let file = vfs
+            .open(path, libc::O_WRONLY | libc::O_TRUNC, 0)
+            .await
+            .unwrap();
+        assert_eq!(file.fstat().await.unwrap().st_size, 0);
+    }
+
+    #[tokio::test]
+    async fn test_open_flags() {
+        let vfs = MemVfs::new(PathBuf::from("/scratch"));
+        let path = Path::new("/scratch/log");
+
+        assert!(matches!(
+            vfs.open(path, libc::O_RDONLY, 0).await,
+            Err(VfsError::NotFound)
+        ));
+        let file = vfs
+            .open(path, RDWR_CREAT | libc::O_EXCL, 0o600)
+            .await
+            .unwrap();
+        file.write(b"one\n").await.unwrap();
+        assert!(matches!(
+            vfs.open(path, RDWR_CREAT | libc::O_EXCL, 0o600).await,
+            Err(VfsError::AlreadyExists)
+        ));
+
+        let file = vfs
+            .open(path, libc::O_WRONLY | libc::O_APPEND, 0)
+            .await
+            .unwrap();
+        file.write(b"two\n").await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 8);
+
+        assert!(vfs
+            .open(Path::new("/scratch/missing/file"), RDWR_CREAT, 0o644)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_getdents() {
+        let vfs = MemVfs::new(PathBuf::from("/scratch"));
+        for name in ["b.txt", "a.txt"] {
+            let path = PathBuf::from("/scratch").join(name);
+            vfs.open(&path, RDWR_CREAT, 0o644).await.unwrap();
+        }
+        vfs.symlink(Path::new("a.txt"), Path::new("/scratch/link"))
+            .await
+            .unwrap();
+
+        let dir = vfs
+            .open(Path::new("/scratch"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            dir.fstat().await.unwrap().st_mode & libc::S_IFMT,
+            libc::S_IFDIR
+        );
+        let entries = dir.getdents().await.unwrap();
+        let names: Vec<_> = entries
+            .iter()
+            .map(|(_, name, d_type)| (name.as_str(), *d_type))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (".", libc::DT_DIR),
+                ("..", libc::DT_DIR),
+                ("a.txt", libc::DT_REG),
+                ("b.txt", libc::DT_REG),
+                ("link", libc::DT_LNK),
+            ]
+        );
+        assert!(dir.getdents().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_symlinks() {
+        let vfs = MemVfs::new(PathBuf::from("/scratch"));
+        let file = vfs
+            .open(Path::new("/scratch/target"), RDWR_CREAT, 0o644)
+            .await
+            .unwrap();
+        file.write(b"data").await.unwrap();
+        vfs.symlink(Path::new("target"), Path::new("/scratch/relative"))
+            .await
+            .unwrap();
+        vfs.symlink(Path::new("/scratch/target"), Path::new("/scratch/absolute"))
+            .await
+            .unwrap();
+        vfs.symlink(Path::new("loop"), Path::new("/scratch/loop"))
+            .await
+            .unwrap();
+
+        for link in ["/scratch/relative", "/scratch/absolute"] {
+            let link = Path::new(link);
+            assert_eq!(vfs.stat(link).await.unwrap().st_size, 4);
+            assert_eq!(
+                vfs.lstat(link).await.unwrap().st_mode & libc::S_IFMT,
+                libc::S_IFLNK
+            );
+        }
+        assert_eq!(
+            vfs.readlink(Path::new("/scratch/relative")).await.unwrap(),
+            PathBuf::from("target")
+        );
+        assert!(vfs.stat(Path::new("/scratch/loop")).await.is_err());
+        assert!(matches!(
+            vfs.symlink(Path::new("x"), Path::new("/scratch/target"))
+                .await,
+            Err(VfsError::AlreadyExists)
+        ));
+    }
+}
@@ -1,7 +1,12 @@
 pub mod bind;
 pub mod fdtable;
 pub mod file;
+pub mod git;
+pub mod http;
+pub mod mem;
 pub mod mount;
+#[cfg(feature = "s3")]
+pub mod s3;
 pub mod sqlite;
 
 use async_trait::async_trait;
@@ -14,6 +19,8 @@ pub enum VfsError {
     NotFound,
     PermissionDenied,
     AlreadyExists,
+    NotSupported,
+    SymlinkLoop,
     InvalidInput(String),
     IoError(std::io::Error),
     Other(String),
@@ -31,6 +38,8 @@ impl std::fmt::Display for VfsError {
             VfsError::NotFound => write!(f, "Not found"),
             VfsError::PermissionDenied => write!(f, "Permission denied"),
             VfsError::AlreadyExists => write!(f, "Already exists"),
+            VfsError::NotSupported => write!(f, "Operation not supported"),
+            VfsError::SymlinkLoop => write!(f, "Too many levels of symbolic links"),
             VfsError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
             VfsError::IoError(err) => write!(f, "IO error: {}", err),
             VfsError::Other(msg) => write!(f, "{}", msg),
@@ -42,6 +51,42 @@ impl std::error::Error for VfsError {}
 
 pub type VfsResult<T> = StdResult<T, VfsError>;
 
+/// Ownership reported for the files of a mount
+///
+/// Files keep their real owner wherever an ID is not set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IdMap {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl IdMap {
+    /// Check if this map leaves ownership unchanged
+    pub fn is_identity(&self) -> bool {
+        self.uid.is_none() && self.gid.is_none()
+    }
+
+    /// Rewrite the owner in a `stat` result
+    pub fn apply(&self, stat: &mut libc::stat) {
+        if let Some(uid) = self.uid {
+            stat.st_uid = uid;
+        }
+        if let Some(gid) = self.gid {
+            stat.st_gid = gid;
+        }
+    }
+
+    /// Rewrite the owner in a `statx` result
+    pub fn apply_statx(&self, statx: &mut libc::statx) {
+        if let Some(uid) = self.uid {
+            statx.stx_uid = uid;
+        }
+        if let Some(gid) = self.gid {
+            statx.stx_gid = gid;
+        }
+    }
+}
+
 use file::BoxedFileOps;
 
 /// Virtual file system trait.
@@ -64,6 +109,23 @@ pub trait Vfs: Send + Sync {
         false
     }
 
+    /// Ownership reported for files in this VFS
+    ///
+    /// Virtual VFS implementations apply the map to their own stat results.
+    /// For passthrough VFS, the syscall handlers apply it to the results the
+    /// kernel returns.
+    fn id_map(&self) -> IdMap {
+        IdMap::default()
+    }
+
+    /// Check if this VFS rejects modifications
+    ///
+    /// Syscalls that would modify a read-only VFS fail with `EROFS` before
+    /// reaching the VFS or the kernel.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
     /// Open a file directly in the VFS (for virtual filesystems)
     ///
     /// This is only called for virtual VFS implementations. For passthrough
@@ -110,7 +172,203 @@ pub trait Vfs: Send + Sync {
             "readlink() not supported by this VFS".to_string(),
         ))
     }
+
+    /// Create a special or regular file (for virtual filesystems)
+    ///
+    /// `mode` holds the file type and permission bits, and `dev` the device
+    /// number of a device node, as passed to `mknodat`.
+    async fn mknod(&self, _path: &Path, _mode: u32, _dev: u64) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Create a directory (for virtual filesystems)
+    async fn mkdir(&self, _path: &Path, _mode: u32) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Remove a directory entry that is not a directory (for virtual filesystems)
+    ///
+    /// The last component is not followed, so a symlink itself is removed.
+    async fn unlink(&self, _path: &Path) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Remove an empty directory (for virtual filesystems)
+    async fn rmdir(&self, _path: &Path) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Rename a file, directory, or symlink within this filesystem (for virtual filesystems)
+    ///
+    /// The last components of the paths are not followed. An existing `to`
+    /// is replaced as with `rename`, unless `noreplace` is set, in which case
+    /// the rename fails as with `RENAME_NOREPLACE`.
+    async fn rename(&self, _from: &Path, _to: &Path, _noreplace: bool) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Change the permission bits of a file without following symlinks (for virtual filesystems)
+    ///
+    /// The file type bits of `mode` are ignored.
+    async fn chmod(&self, _path: &Path, _mode: u32) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Change the owner and group of a file without following symlinks (for virtual filesystems)
+    ///
+    /// A `uid` or `gid` of `u32::MAX` leaves it unchanged, as with `chown`.
+    async fn chown(&self, _path: &Path, _uid: u32, _gid: u32) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Set the access and modification times of a file without following symlinks (for virtual filesystems)
+    ///
+    /// Times are in seconds since the Unix epoch; a time of `None` is left
+    /// unchanged, as with `UTIME_OMIT`.
+    async fn set_times(
+        &self,
+        _path: &Path,
+        _atime: Option<i64>,
+        _mtime: Option<i64>,
+    ) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Truncate or extend a file to `len` bytes, following symlinks (for virtual filesystems)
+    async fn truncate(&self, _path: &Path, _len: i64) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Add a directory entry at `newpath` for the file with inode `ino` (for virtual filesystems)
+    ///
+    /// This is how `linkat` with `AT_EMPTY_PATH` names an `O_TMPFILE` file
+    /// opened in this VFS.
+    async fn link_inode(&self, _ino: u64, _newpath: &Path) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Get an extended attribute without following symlinks (for virtual filesystems)
+    ///
+    /// Returns `None` if the attribute is not set.
+    async fn getxattr(&self, _path: &Path, _name: &str) -> VfsResult<Option<Vec<u8>>> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Set an extended attribute without following symlinks (for virtual filesystems)
+    async fn setxattr(&self, _path: &Path, _name: &str, _value: &[u8]) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// List extended attribute names without following symlinks (for virtual filesystems)
+    async fn listxattr(&self, _path: &Path) -> VfsResult<Vec<String>> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Remove an extended attribute without following symlinks (for virtual filesystems)
+    ///
+    /// Returns whether the attribute was set.
+    async fn removexattr(&self, _path: &Path, _name: &str) -> VfsResult<bool> {
+        Err(VfsError::NotSupported)
+    }
+}
+
+/// Maximum number of symlinks followed while resolving a path, as in Linux
+pub(crate) const MAX_SYMLINKS: usize = 40;
+
+/// Resolve the symlinks at the end of a path in a virtual VFS
+///
+/// Returns the path of the first non-symlink the chain leads to. Relative
+/// targets are resolved against the directory of the link.
+pub(crate) async fn follow_symlinks(vfs: &dyn Vfs, path: &Path) -> VfsResult<PathBuf> {
+    let mut path = path.to_path_buf();
+    for _ in 0..=MAX_SYMLINKS {
+        let stat = vfs.lstat(&path).await?;
+        if stat.st_mode & libc::S_IFMT != libc::S_IFLNK {
+            return Ok(path);
+        }
+        let target = vfs.readlink(&path).await?;
+        let parent = path.parent().unwrap_or(Path::new("/"));
+        path = normalize_path(&parent.join(target));
+    }
+    Err(VfsError::SymlinkLoop)
+}
+
+/// Split a path into its components, last component first
+pub(crate) fn components(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|c| !c.is_empty())
+        .rev()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Remove `.` and `..` components from an absolute path without touching the filesystem
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            std::path::Component::Normal(name) => normalized.push(name),
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+    normalized
 }
 
 /// A boxed VFS trait object for dynamic dispatch
 pub type BoxedVfs = Box<dyn Vfs>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::mem::MemVfs;
+
+    #[tokio::test]
+    async fn test_follow_symlinks() {
+        let vfs = MemVfs::new(PathBuf::from("/scratch"));
+        let file = vfs
+            .open(
+                Path::new("/scratch/file"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        vfs.symlink(Path::new("file"), Path::new("/scratch/relative"))
+            .await
+            .unwrap();
+        vfs.symlink(
+            Path::new("/scratch/./sub/../relative"),
+            Path::new("/scratch/absolute"),
+        )
+        .await
+        .unwrap();
+        vfs.symlink(Path::new("loop"), Path::new("/scratch/loop"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            follow_symlinks(&vfs, Path::new("/scratch/absolute"))
+                .await
+                .unwrap(),
+            PathBuf::from("/scratch/file")
+        );
+        assert_eq!(
+            follow_symlinks(&vfs, Path::new("/scratch/file"))
+                .await
+                .unwrap(),
+            PathBuf::from("/scratch/file")
+        );
+        assert!(matches!(
+            follow_symlinks(&vfs, Path::new("/scratch/loop")).await,
+            Err(VfsError::SymlinkLoop)
+        ));
+        assert!(matches!(
+            follow_symlinks(&vfs, Path::new("/scratch/missing")).await,
+            Err(VfsError::NotFound)
+        ));
+    }
+}
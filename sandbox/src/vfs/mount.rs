@@ -1,4 +1,4 @@
-use super::Vfs;
+use super::{IdMap, Vfs};
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::Reverse,
@@ -42,6 +42,25 @@ impl MountTable {
             .sort_by_key(|m| Reverse(m.sandbox_path.components().count()));
     }
 
+    /// Remove the mount point at `sandbox_path`
+    ///
+    /// Returns whether a mount point was removed. Paths under the removed
+    /// mount point resolve to the next longest matching mount, if any.
+    pub fn remove_mount(&mut self, sandbox_path: &Path) -> bool {
+        match self
+            .mounts
+            .iter()
+            .position(|m| m.sandbox_path == sandbox_path)
+        {
+            Some(index) => {
+                // Removing keeps the remaining mounts sorted deepest first
+                self.mounts.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Resolve a path to a VFS and translated path
     ///
     /// This implements longest-prefix matching - if multiple mount points
@@ -49,15 +68,36 @@ impl MountTable {
     ///
     /// Returns None if no mount point matches the path.
     pub fn resolve(&self, path: &Path) -> Option<(Arc<dyn Vfs>, PathBuf)> {
+        self.resolve_mount(path)
+            .map(|(mount, translated)| (mount.vfs.clone(), translated))
+    }
+
+    /// Resolve a path to the mount point it belongs to and the translated path
+    ///
+    /// Like [`MountTable::resolve`], but returns the whole mount point so
+    /// callers can tell which mount a path resolved to.
+    pub fn resolve_mount(&self, path: &Path) -> Option<(&MountPoint, PathBuf)> {
         for mount in &self.mounts {
             // Try to translate the path using this mount's VFS
             if let Ok(translated) = mount.vfs.translate_path(path) {
-                return Some((mount.vfs.clone(), translated));
+                tracing::trace!(
+                    path = %path.display(),
+                    mount = %mount.sandbox_path.display(),
+                    translated = %translated.display(),
+                    "resolved path"
+                );
+                return Some((mount, translated));
             }
         }
         None
     }
 
+    /// Check if a path resolves to a read-only mount point
+    pub fn is_read_only(&self, path: &Path) -> bool {
+        self.resolve(path)
+            .is_some_and(|(vfs, _)| vfs.is_read_only())
+    }
+
     /// Get all mount points
     pub fn mounts(&self) -> &[MountPoint] {
         &self.mounts
@@ -93,6 +133,14 @@ pub enum MountType {
         /// Source path on the host (canonicalized).
         src: PathBuf,
     },
+    /// Read-only bind mount that passes through to a host path.
+    ///
+    /// Like [`MountType::Bind`], but system calls that would modify files
+    /// under the mount fail with `EROFS`.
+    ReadonlyBind {
+        /// Source path on the host (canonicalized).
+        src: PathBuf,
+    },
     /// SQLite-backed virtual filesystem.
     ///
     /// This mount type creates a full POSIX-like filesystem stored in a
@@ -101,6 +149,55 @@ pub enum MountType {
         /// Path to the SQLite database file.
         src: PathBuf,
     },
+    /// Read-only SQLite-backed virtual filesystem.
+    ///
+    /// Like [`MountType::Sqlite`], but the database is opened with a
+    /// read-only connection and system calls that would modify files under
+    /// the mount fail with `EROFS`.
+    ReadonlySqlite {
+        /// Path to the SQLite database file.
+        src: PathBuf,
+    },
+    /// In-memory virtual filesystem.
+    ///
+    /// This mount type starts out empty and keeps everything in process
+    /// memory, so its contents are discarded when the sandbox exits.
+    Mem,
+    /// Read-only virtual filesystem of files fetched over HTTP.
+    ///
+    /// Each file under the mount is fetched from the corresponding URL under
+    /// the base URL the first time it is accessed. A 404 response means the
+    /// file does not exist.
+    Http {
+        /// URL of the remote directory to mount.
+        base_url: String,
+        /// Path to a SQLite database that keeps fetched files across runs.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache: Option<PathBuf>,
+    },
+    /// Virtual filesystem of the objects in an S3 bucket.
+    ///
+    /// Files are objects whose keys start with the prefix, and files written
+    /// in the sandbox are uploaded when they are closed. Requires the `s3`
+    /// feature.
+    S3 {
+        /// Name of the bucket.
+        bucket: String,
+        /// Key prefix of the objects to mount, empty for the whole bucket.
+        #[serde(default)]
+        prefix: String,
+    },
+    /// Read-only virtual filesystem of a Git repository at a revision.
+    ///
+    /// The files of the commit are read from the repository's object
+    /// database, so nothing is checked out.
+    Git {
+        /// Path to the repository.
+        repo: PathBuf,
+        /// Revision to show, such as a branch, tag, or commit id.
+        #[serde(rename = "ref")]
+        revision: String,
+    },
 }
 
 /// Configuration for a mount point (used for CLI parsing).
@@ -109,12 +206,31 @@ pub enum MountType {
 /// `type=bind,src=/host/path,dst=/sandbox/path`
 ///
 /// Aliases are supported: `source` for `src`, `target` for `dst`.
+///
+/// Bind and SQLite mounts accept `uid=<id>` and `gid=<id>` to report every
+/// file in the mount as owned by the given user and group.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MountConfig {
     /// Type of mount.
     pub mount_type: MountType,
     /// Destination path in the sandbox (must be absolute).
     pub dst: PathBuf,
+    /// User ID reported as the owner of files in the mount.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Group ID reported as the owner of files in the mount.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+}
+
+impl MountConfig {
+    /// The ownership remapping configured for this mount
+    pub fn id_map(&self) -> IdMap {
+        IdMap {
+            uid: self.uid,
+            gid: self.gid,
+        }
+    }
 }
 
 impl std::str::FromStr for MountConfig {
@@ -143,6 +259,23 @@ impl std::str::FromStr for MountConfig {
             }
         }
 
+        // Optional ownership remapping
+        let parse_id = |key: &str| -> Result<Option<u32>, String> {
+            options
+                .get(key)
+                .map(|value| {
+                    value.parse::<u32>().map_err(|_| {
+                        format!(
+                            "Invalid '{}' value '{}'. Expected a numeric ID.",
+                            key, value
+                        )
+                    })
+                })
+                .transpose()
+        };
+        let uid = parse_id("uid")?;
+        let gid = parse_id("gid")?;
+
         // Check for required 'type' field
         let mount_type = options.get("type").ok_or_else(|| {
             "Missing required field 'type'. Example: type=bind,src=/host/path,dst=/sandbox/path."
@@ -150,7 +283,7 @@ impl std::str::FromStr for MountConfig {
         })?;
 
         match mount_type.as_str() {
-            "bind" => {
+            "bind" | "robind" => {
                 // Get src (or source as alias)
                 let src_str = options.get("src")
                     .or_else(|| options.get("source"))
@@ -176,12 +309,19 @@ impl std::str::FromStr for MountConfig {
                     format!("Failed to canonicalize source path '{}': {}.", src_str, e)
                 })?;
 
+                let mount_type = if mount_type == "robind" {
+                    MountType::ReadonlyBind { src }
+                } else {
+                    MountType::Bind { src }
+                };
                 Ok(MountConfig {
-                    mount_type: MountType::Bind { src },
+                    mount_type,
                     dst,
+                    uid,
+                    gid,
                 })
             }
-            "sqlite" => {
+            "sqlite" | "rosqlite" => {
                 // Get src (or source as alias)
                 let src_str = options.get("src")
                     .or_else(|| options.get("source"))
@@ -205,13 +345,167 @@ impl std::str::FromStr for MountConfig {
                 // For SQLite, we use the path as-is (may be relative or absolute)
                 let src = PathBuf::from(src_str);
 
+                let mount_type = if mount_type == "rosqlite" {
+                    MountType::ReadonlySqlite { src }
+                } else {
+                    MountType::Sqlite { src }
+                };
                 Ok(MountConfig {
-                    mount_type: MountType::Sqlite { src },
+                    mount_type,
                     dst,
+                    uid,
+                    gid,
+                })
+            }
+            "mem" => {
+                if options.contains_key("src") || options.contains_key("source") {
+                    return Err(
+                        "Memory mount does not take a 'src' field. Example: type=mem,dst=/scratch."
+                            .to_string(),
+                    );
+                }
+                if uid.is_some() || gid.is_some() {
+                    return Err("Memory mount does not support 'uid' or 'gid'.".to_string());
+                }
+
+                // Get dst (or target as alias)
+                let dst_str = options
+                    .get("dst")
+                    .or_else(|| options.get("target"))
+                    .ok_or_else(|| {
+                        "Memory mount requires 'dst' field. Example: type=mem,dst=/scratch."
+                            .to_string()
+                    })?;
+
+                // Validate destination is absolute
+                let dst = PathBuf::from(dst_str);
+                if !dst.is_absolute() {
+                    return Err(format!("Destination path '{}' must be absolute.", dst_str));
+                }
+
+                Ok(MountConfig {
+                    mount_type: MountType::Mem,
+                    dst,
+                    uid: None,
+                    gid: None,
+                })
+            }
+            "http" => {
+                if uid.is_some() || gid.is_some() {
+                    return Err("HTTP mount does not support 'uid' or 'gid'.".to_string());
+                }
+
+                let base_url = options.get("url").ok_or_else(|| {
+                    "HTTP mount requires 'url' field. Example: type=http,url=https://example.com/data,dst=/remote."
+                        .to_string()
+                })?;
+                if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+                    return Err(format!(
+                        "HTTP mount URL '{}' must start with http:// or https://.",
+                        base_url
+                    ));
+                }
+
+                // Get dst (or target as alias)
+                let dst_str = options
+                    .get("dst")
+                    .or_else(|| options.get("target"))
+                    .ok_or_else(|| {
+                        "HTTP mount requires 'dst' field. Example: type=http,url=https://example.com/data,dst=/remote."
+                            .to_string()
+                    })?;
+
+                // Validate destination is absolute
+                let dst = PathBuf::from(dst_str);
+                if !dst.is_absolute() {
+                    return Err(format!("Destination path '{}' must be absolute.", dst_str));
+                }
+
+                Ok(MountConfig {
+                    mount_type: MountType::Http {
+                        base_url: base_url.clone(),
+                        cache: options.get("cache").map(PathBuf::from),
+                    },
+                    dst,
+                    uid: None,
+                    gid: None,
+                })
+            }
+            "s3" => {
+                if uid.is_some() || gid.is_some() {
+                    return Err("S3 mount does not support 'uid' or 'gid'.".to_string());
+                }
+
+                let bucket = options.get("bucket").ok_or_else(|| {
+                    "S3 mount requires 'bucket' field. Example: type=s3,bucket=my-bucket,prefix=runs,dst=/bucket."
+                        .to_string()
+                })?;
+
+                // Get dst (or target as alias)
+                let dst_str = options
+                    .get("dst")
+                    .or_else(|| options.get("target"))
+                    .ok_or_else(|| {
+                        "S3 mount requires 'dst' field. Example: type=s3,bucket=my-bucket,prefix=runs,dst=/bucket."
+                            .to_string()
+                    })?;
+
+                // Validate destination is absolute
+                let dst = PathBuf::from(dst_str);
+                if !dst.is_absolute() {
+                    return Err(format!("Destination path '{}' must be absolute.", dst_str));
+                }
+
+                Ok(MountConfig {
+                    mount_type: MountType::S3 {
+                        bucket: bucket.clone(),
+                        prefix: options.get("prefix").cloned().unwrap_or_default(),
+                    },
+                    dst,
+                    uid: None,
+                    gid: None,
+                })
+            }
+            "git" => {
+                if uid.is_some() || gid.is_some() {
+                    return Err("Git mount does not support 'uid' or 'gid'.".to_string());
+                }
+
+                let repo = options.get("repo").ok_or_else(|| {
+                    "Git mount requires 'repo' field. Example: type=git,repo=.,ref=main,dst=/repo."
+                        .to_string()
+                })?;
+
+                // Get dst (or target as alias)
+                let dst_str = options
+                    .get("dst")
+                    .or_else(|| options.get("target"))
+                    .ok_or_else(|| {
+                        "Git mount requires 'dst' field. Example: type=git,repo=.,ref=main,dst=/repo."
+                            .to_string()
+                    })?;
+
+                // Validate destination is absolute
+                let dst = PathBuf::from(dst_str);
+                if !dst.is_absolute() {
+                    return Err(format!("Destination path '{}' must be absolute.", dst_str));
+                }
+
+                Ok(MountConfig {
+                    mount_type: MountType::Git {
+                        repo: PathBuf::from(repo),
+                        revision: options
+                            .get("ref")
+                            .cloned()
+                            .unwrap_or_else(|| "HEAD".to_string()),
+                    },
+                    dst,
+                    uid: None,
+                    gid: None,
                 })
             }
             _ => Err(format!(
-                "Unsupported mount type '{}'. Supported types: bind, sqlite.",
+                "Unsupported mount type '{}'. Supported types: bind, robind, sqlite, rosqlite, mem, http, s3, git.",
                 mount_type
             )),
         }
@@ -259,6 +553,38 @@ mod tests {
         assert_eq!(translated, PathBuf::from("/tmp/agent/normal"));
     }
 
+    #[test]
+    fn test_remove_mount_falls_back_to_broader_mount() {
+        let mut table = MountTable::new();
+        table.add_mount(
+            PathBuf::from("/agent"),
+            Arc::new(BindVfs::new(
+                PathBuf::from("/tmp/agent"),
+                PathBuf::from("/agent"),
+            )),
+        );
+        table.add_mount(
+            PathBuf::from("/agent/special"),
+            Arc::new(BindVfs::new(
+                PathBuf::from("/tmp/special"),
+                PathBuf::from("/agent/special"),
+            )),
+        );
+
+        let (_, translated) = table.resolve(Path::new("/agent/special/file")).unwrap();
+        assert_eq!(translated, PathBuf::from("/tmp/special/file"));
+
+        assert!(table.remove_mount(Path::new("/agent/special")));
+        assert!(!table.remove_mount(Path::new("/agent/special")));
+        assert_eq!(table.mounts().len(), 1);
+
+        let (_, translated) = table.resolve(Path::new("/agent/special/file")).unwrap();
+        assert_eq!(translated, PathBuf::from("/tmp/agent/special/file"));
+
+        assert!(table.remove_mount(Path::new("/agent")));
+        assert!(table.resolve(Path::new("/agent/special/file")).is_none());
+    }
+
     #[test]
     fn test_mount_table_no_match() {
         let mut table = MountTable::new();
@@ -287,7 +613,7 @@ mod tests {
                 assert_eq!(src, std::fs::canonicalize("/tmp").unwrap());
                 assert_eq!(config.dst, PathBuf::from("/data"));
             }
-            MountType::Sqlite { .. } => panic!("Expected Bind mount, got Sqlite"),
+            _ => panic!("Expected Bind mount"),
         }
     }
 
@@ -303,7 +629,7 @@ mod tests {
                 assert_eq!(src, std::fs::canonicalize("/tmp").unwrap());
                 assert_eq!(config.dst, PathBuf::from("/data"));
             }
-            MountType::Sqlite { .. } => panic!("Expected Bind mount, got Sqlite"),
+            _ => panic!("Expected Bind mount"),
         }
     }
 
@@ -330,6 +656,159 @@ mod tests {
         assert!(config.unwrap_err().contains("requires 'src' field"));
     }
 
+    #[test]
+    fn test_parse_robind_mount() {
+        let config: MountConfig = "type=robind,src=/tmp,dst=/data".parse().unwrap();
+        match config.mount_type {
+            MountType::ReadonlyBind { src } => {
+                assert_eq!(src, std::fs::canonicalize("/tmp").unwrap());
+                assert_eq!(config.dst, PathBuf::from("/data"));
+            }
+            _ => panic!("Expected ReadonlyBind mount"),
+        }
+
+        let config: Result<MountConfig, _> = "type=robind,dst=/data".parse();
+        assert!(config.unwrap_err().contains("requires 'src' field"));
+    }
+
+    #[test]
+    fn test_parse_rosqlite_mount() {
+        let config: MountConfig = "type=rosqlite,src=agent.db,dst=/agent".parse().unwrap();
+        match config.mount_type {
+            MountType::ReadonlySqlite { src } => {
+                assert_eq!(src, PathBuf::from("agent.db"));
+                assert_eq!(config.dst, PathBuf::from("/agent"));
+            }
+            _ => panic!("Expected ReadonlySqlite mount"),
+        }
+    }
+
+    #[test]
+    fn test_parse_http_mount() {
+        let config: MountConfig = "type=http,url=https://example.com/data,dst=/remote"
+            .parse()
+            .unwrap();
+        match config.mount_type {
+            MountType::Http { base_url, cache } => {
+                assert_eq!(base_url, "https://example.com/data");
+                assert_eq!(cache, None);
+                assert_eq!(config.dst, PathBuf::from("/remote"));
+            }
+            _ => panic!("Expected Http mount"),
+        }
+
+        let config: MountConfig = "type=http,url=http://localhost:8000,dst=/remote,cache=http.db"
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            config.mount_type,
+            MountType::Http { cache: Some(cache), .. } if cache == Path::new("http.db")
+        ));
+
+        let config: Result<MountConfig, _> = "type=http,dst=/remote".parse();
+        assert!(config.unwrap_err().contains("requires 'url' field"));
+        let config: Result<MountConfig, _> = "type=http,url=ftp://example.com,dst=/remote".parse();
+        assert!(config.unwrap_err().contains("must start with http://"));
+    }
+
+    #[test]
+    fn test_parse_s3_mount() {
+        let config: MountConfig = "type=s3,bucket=my-bucket,prefix=runs/42,dst=/bucket"
+            .parse()
+            .unwrap();
+        match config.mount_type {
+            MountType::S3 { bucket, prefix } => {
+                assert_eq!(bucket, "my-bucket");
+                assert_eq!(prefix, "runs/42");
+                assert_eq!(config.dst, PathBuf::from("/bucket"));
+            }
+            _ => panic!("Expected S3 mount"),
+        }
+
+        let config: MountConfig = "type=s3,bucket=my-bucket,dst=/bucket".parse().unwrap();
+        assert!(matches!(config.mount_type, MountType::S3 { prefix, .. } if prefix.is_empty()));
+
+        let config: Result<MountConfig, _> = "type=s3,dst=/bucket".parse();
+        assert!(config.unwrap_err().contains("requires 'bucket' field"));
+    }
+
+    #[test]
+    fn test_parse_git_mount() {
+        let config: MountConfig = "type=git,repo=/src/project,ref=v1.0,dst=/repo"
+            .parse()
+            .unwrap();
+        match config.mount_type {
+            MountType::Git { repo, revision } => {
+                assert_eq!(repo, PathBuf::from("/src/project"));
+                assert_eq!(revision, "v1.0");
+                assert_eq!(config.dst, PathBuf::from("/repo"));
+            }
+            _ => panic!("Expected Git mount"),
+        }
+
+        let config: MountConfig = "type=git,repo=.,dst=/repo".parse().unwrap();
+        assert!(matches!(config.mount_type, MountType::Git { revision, .. } if revision == "HEAD"));
+
+        let config: Result<MountConfig, _> = "type=git,dst=/repo".parse();
+        assert!(config.unwrap_err().contains("requires 'repo' field"));
+    }
+
+    #[test]
+    fn test_parse_uid_gid() {
+        let config: MountConfig = "type=bind,src=/tmp,dst=/data,uid=1000,gid=1001"
+            .parse()
+            .unwrap();
+        assert_eq!(config.uid, Some(1000));
+        assert_eq!(config.gid, Some(1001));
+
+        let config: MountConfig = "type=sqlite,src=agent.db,dst=/agent,gid=5".parse().unwrap();
+        assert_eq!(
+            config.id_map(),
+            IdMap {
+                uid: None,
+                gid: Some(5)
+            }
+        );
+
+        let config: Result<MountConfig, _> = "type=bind,src=/tmp,dst=/data,uid=alice".parse();
+        assert!(config.unwrap_err().contains("Invalid 'uid' value"));
+        let config: Result<MountConfig, _> = "type=mem,dst=/scratch,uid=1000".parse();
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_mount_table_read_only() {
+        let mut table = MountTable::new();
+        table.add_mount(
+            PathBuf::from("/data"),
+            Arc::new(BindVfs::new_read_only(
+                PathBuf::from("/tmp/data"),
+                PathBuf::from("/data"),
+            )),
+        );
+        table.add_mount(
+            PathBuf::from("/data/out"),
+            Arc::new(BindVfs::new(
+                PathBuf::from("/tmp/out"),
+                PathBuf::from("/data/out"),
+            )),
+        );
+
+        assert!(table.is_read_only(Path::new("/data/file")));
+        assert!(!table.is_read_only(Path::new("/data/out/file")));
+        assert!(!table.is_read_only(Path::new("/other")));
+    }
+
+    #[test]
+    fn test_parse_mem_mount() {
+        let config: MountConfig = "type=mem,dst=/scratch".parse().unwrap();
+        assert!(matches!(config.mount_type, MountType::Mem));
+        assert_eq!(config.dst, PathBuf::from("/scratch"));
+
+        let config: Result<MountConfig, _> = "type=mem,src=/tmp,dst=/scratch".parse();
+        assert!(config.unwrap_err().contains("does not take a 'src' field"));
+    }
+
     #[test]
     fn test_invalid_type() {
         let config: Result<MountConfig, _> = "type=foobar,dst=/data".parse();
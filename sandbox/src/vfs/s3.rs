@@ -0,0 +1,1044 @@
+use super::file::{dir_seek_position, file_seek_position, BoxedFileOps, FileOps};
+use super::{normalize_path, Vfs, VfsError, VfsResult};
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectMeta, ObjectStore, WriteMultipart};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Inode number of the root directory
+const ROOT_INO: u64 = 1;
+
+/// Files larger than this are uploaded in parts of this size
+const MULTIPART_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Type alias for directory entry list: (inode, name, type)
+type DirEntryList = Vec<(u64, String, u8)>;
+
+/// A virtual filesystem of the objects in an object store
+///
+/// The file at `<mount point>/<path>` is the object `<prefix>/<path>`.
+/// Object stores have no directories, so a directory exists wherever some
+/// object's key continues past it, and listings come from the store's list
+/// API. Directories made with `mkdir` exist only in memory until a file is
+/// written into them.
+///
+/// Files opened read-only are read with ranged GETs as the process reads
+/// them. Files opened for writing are buffered in memory and uploaded when
+/// they are closed or synced, in parts if they are large, so a written file
+/// appears in the store only once it is closed.
+#[derive(Clone)]
+pub struct S3Vfs {
+    store: Arc<dyn ObjectStore>,
+    /// Key prefix of the objects under the mount point
+    prefix: ObjectPath,
+    /// The virtual path as seen by the sandboxed process
+    mount_point: PathBuf,
+    /// Directories made with `mkdir`, relative to the mount point
+    directories: Arc<Mutex<HashSet<String>>>,
+}
+
+impl S3Vfs {
+    /// Create a VFS for the objects in an S3 bucket
+    ///
+    /// Credentials, region, and endpoint are read from the standard `AWS_*`
+    /// environment variables.
+    ///
+    /// # Arguments
+    /// * `bucket` - Name of the bucket
+    /// * `prefix` - Key prefix of the objects to mount (e.g., "runs/42"), or "" for the whole bucket
+    /// * `mount_point` - The virtual path seen by the guest (e.g., "/bucket")
+    pub fn new(bucket: &str, prefix: &str, mount_point: PathBuf) -> VfsResult<Self> {
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()
+            .map_err(|e| VfsError::Other(format!("Failed to create S3 client: {}", e)))?;
+        Ok(Self::from_store(Arc::new(store), prefix, mount_point))
+    }
+
+    /// Create a VFS for the objects in any object store
+    pub fn from_store(store: Arc<dyn ObjectStore>, prefix: &str, mount_point: PathBuf) -> Self {
+        Self {
+            store,
+            prefix: ObjectPath::from(prefix),
+            mount_point,
+            directories: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Get the mount point path
+    pub fn mount_point(&self) -> &Path {
+        &self.mount_point
+    }
+
+    /// Translate a sandbox path to a normalized path relative to the mount
+    /// point, which is "/" for the mount point itself
+    fn translate_to_relative(&self, path: &Path) -> VfsResult<String> {
+        let relative = path
+            .strip_prefix(&self.mount_point)
+            .map_err(|_| VfsError::NotFound)?;
+        normalize_path(&Path::new("/").join(relative))
+            .to_str()
+            .map(str::to_string)
+            .ok_or_else(|| VfsError::InvalidInput("Invalid path".to_string()))
+    }
+
+    /// The key of the object at `relative`
+    fn key(&self, relative: &str) -> ObjectPath {
+        relative
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .fold(self.prefix.clone(), |key, name| key.child(name))
+    }
+
+    /// The metadata of the object at `relative`, or None if there is none
+    async fn head(&self, relative: &str) -> VfsResult<Option<ObjectMeta>> {
+        if relative == "/" {
+            return Ok(None);
+        }
+        match self.store.head(&self.key(relative)).await {
+            Ok(meta) => Ok(Some(meta)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(store_error(e)),
+        }
+    }
+
+    /// List the directory at `relative` as names of subdirectories and files
+    async fn list(&self, relative: &str) -> VfsResult<(Vec<String>, Vec<ObjectMeta>)> {
+        let key = self.key(relative);
+        let prefix = (key != ObjectPath::default()).then_some(&key);
+        let listing = self
+            .store
+            .list_with_delimiter(prefix)
+            .await
+            .map_err(store_error)?;
+        let directories = listing
+            .common_prefixes
+            .iter()
+            .filter_map(|dir| dir.filename().map(str::to_string))
+            .collect();
+        Ok((directories, listing.objects))
+    }
+
+    /// Check whether `relative` is a directory
+    async fn is_dir(&self, relative: &str) -> VfsResult<bool> {
+        if relative == "/" || self.directories.lock().unwrap().contains(relative) {
+            return Ok(true);
+        }
+        let (directories, objects) = self.list(relative).await?;
+        Ok(!directories.is_empty() || !objects.is_empty())
+    }
+
+    /// Fail unless the parent of `relative` is a directory
+    async fn check_parent(&self, relative: &str) -> VfsResult<()> {
+        let parent = Path::new(relative)
+            .parent()
+            .and_then(Path::to_str)
+            .unwrap_or("/");
+        if self.is_dir(parent).await? {
+            Ok(())
+        } else if self.head(parent).await?.is_some() {
+            Err(errno(libc::ENOTDIR))
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    /// The status of the file or directory at `relative`
+    async fn lookup(&self, relative: &str) -> VfsResult<libc::stat> {
+        if let Some(meta) = self.head(relative).await? {
+            return Ok(file_stat(relative, &meta));
+        }
+        if self.is_dir(relative).await? {
+            return Ok(dir_stat(relative));
+        }
+        Err(VfsError::NotFound)
+    }
+}
+
+#[async_trait::async_trait]
+impl Vfs for S3Vfs {
+    fn translate_path(&self, path: &Path) -> VfsResult<PathBuf> {
+        // For virtual VFS, we just validate the path is under our mount point
+        if path.starts_with(&self.mount_point) {
+            Ok(path.to_path_buf())
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+
+    async fn open(&self, path: &Path, flags: i32, _mode: u32) -> VfsResult<BoxedFileOps> {
+        let relative_path = self.translate_to_relative(path)?;
+        tracing::debug!(path = %relative_path, flags, "s3 open");
+        let writable = flags & libc::O_ACCMODE != libc::O_RDONLY;
+
+        let meta = self.head(&relative_path).await?;
+        if meta.is_none() && self.is_dir(&relative_path).await? {
+            if writable || flags & libc::O_CREAT != 0 {
+                return Err(errno(libc::EISDIR));
+            }
+            return Ok(Arc::new(S3DirectoryOps {
+                vfs: self.clone(),
+                relative_path,
+                flags: Mutex::new(flags),
+                entries: Mutex::new(None),
+                position: Mutex::new(0),
+            }));
+        }
+        if flags & libc::O_DIRECTORY != 0 {
+            return Err(match meta {
+                Some(_) => VfsError::Other("Not a directory".to_string()),
+                None => VfsError::NotFound,
+            });
+        }
+
+        let data = match meta {
+            Some(_) if flags & (libc::O_CREAT | libc::O_EXCL) == libc::O_CREAT | libc::O_EXCL => {
+                return Err(VfsError::AlreadyExists);
+            }
+            Some(meta) if !writable => {
+                return Ok(Arc::new(S3ReadFileOps {
+                    store: self.store.clone(),
+                    stat: file_stat(&relative_path, &meta),
+                    meta,
+                    offset: Mutex::new(0),
+                    flags: Mutex::new(flags),
+                }));
+            }
+            Some(_) if flags & libc::O_TRUNC != 0 => None,
+            Some(meta) => {
+                let result = self.store.get(&meta.location).await.map_err(store_error)?;
+                Some(result.bytes().await.map_err(store_error)?.to_vec())
+            }
+            None if flags & libc::O_CREAT != 0 => {
+                self.check_parent(&relative_path).await?;
+                None
+            }
+            None => return Err(VfsError::NotFound),
+        };
+
+        // A new or truncated file is uploaded even if nothing is written
+        let dirty = data.is_none();
+        Ok(Arc::new(S3WriteFileOps {
+            store: self.store.clone(),
+            key: self.key(&relative_path),
+            ino: path_ino(&relative_path),
+            state: tokio::sync::Mutex::new(WriteState {
+                data: data.unwrap_or_default(),
+                dirty,
+                mtime: now(),
+            }),
+            offset: Mutex::new(0),
+            flags: Mutex::new(flags),
+        }))
+    }
+
+    async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let relative_path = self.translate_to_relative(path)?;
+        self.lookup(&relative_path).await
+    }
+
+    async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
+        // There are no symlinks
+        self.stat(path).await
+    }
+
+    async fn readlink(&self, path: &Path) -> VfsResult<PathBuf> {
+        self.lstat(path).await?;
+        Err(VfsError::InvalidInput("Not a symbolic link".to_string()))
+    }
+
+    async fn mkdir(&self, path: &Path, _mode: u32) -> VfsResult<()> {
+        let relative_path = self.translate_to_relative(path)?;
+        if self.lookup(&relative_path).await.is_ok() {
+            return Err(VfsError::AlreadyExists);
+        }
+        self.check_parent(&relative_path).await?;
+        self.directories.lock().unwrap().insert(relative_path);
+        Ok(())
+    }
+
+    async fn unlink(&self, path: &Path) -> VfsResult<()> {
+        let relative_path = self.translate_to_relative(path)?;
+        match self.head(&relative_path).await? {
+            Some(meta) => self.store.delete(&meta.location).await.map_err(store_error),
+            None if self.is_dir(&relative_path).await? => Err(errno(libc::EISDIR)),
+            None => Err(VfsError::NotFound),
+        }
+    }
+
+    async fn rmdir(&self, path: &Path) -> VfsResult<()> {
+        let relative_path = self.translate_to_relative(path)?;
+        if relative_path == "/" {
+            return Err(errno(libc::EBUSY));
+        }
+        if self.head(&relative_path).await?.is_some() {
+            return Err(errno(libc::ENOTDIR));
+        }
+        let (directories, objects) = self.list(&relative_path).await?;
+        if !directories.is_empty() || !objects.is_empty() {
+            return Err(errno(libc::ENOTEMPTY));
+        }
+        let mut made = self.directories.lock().unwrap();
+        let child_prefix = format!("{}/", relative_path);
+        if made.iter().any(|dir| dir.starts_with(&child_prefix)) {
+            return Err(errno(libc::ENOTEMPTY));
+        }
+        if made.remove(&relative_path) {
+            Ok(())
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    async fn rename(&self, from: &Path, to: &Path, noreplace: bool) -> VfsResult<()> {
+        let from_relative = self.translate_to_relative(from)?;
+        let to_relative = self.translate_to_relative(to)?;
+        let Some(meta) = self.head(&from_relative).await? else {
+            // Renaming a directory would mean copying every object under it
+            return Err(if self.is_dir(&from_relative).await? {
+                errno(libc::EXDEV)
+            } else {
+                VfsError::NotFound
+            });
+        };
+        if from_relative == to_relative {
+            return Ok(());
+        }
+        if self.is_dir(&to_relative).await? {
+            return Err(errno(libc::EISDIR));
+        }
+        self.check_parent(&to_relative).await?;
+
+        let to_key = self.key(&to_relative);
+        if noreplace {
+            self.store
+                .rename_if_not_exists(&meta.location, &to_key)
+                .await
+                .map_err(|e| match e {
+                    object_store::Error::AlreadyExists { .. } => VfsError::AlreadyExists,
+                    e => store_error(e),
+                })
+        } else {
+            self.store
+                .rename(&meta.location, &to_key)
+                .await
+                .map_err(store_error)
+        }
+    }
+}
+
+/// Map an object store error to a VFS error
+fn store_error(e: object_store::Error) -> VfsError {
+    match e {
+        object_store::Error::NotFound { .. } => VfsError::NotFound,
+        object_store::Error::AlreadyExists { .. } => VfsError::AlreadyExists,
+        e => {
+            tracing::warn!(error = %e, "object store request failed");
+            errno(libc::EIO)
+        }
+    }
+}
+
+fn errno(code: i32) -> VfsError {
+    VfsError::IoError(std::io::Error::from_raw_os_error(code))
+}
+
+/// Object stores have no inode numbers, so derive a stable one from the path
+fn path_ino(relative: &str) -> u64 {
+    if relative == "/" {
+        return ROOT_INO;
+    }
+    let mut hasher = DefaultHasher::new();
+    relative.hash(&mut hasher);
+    // Keep clear of the root's inode number
+    hasher.finish().max(ROOT_INO + 1)
+}
+
+/// Build a stat structure for an object store entry
+fn make_stat(ino: u64, mode: u32, nlink: u64, size: i64, time: i64) -> libc::stat {
+    // Use MaybeUninit to construct libc::stat safely
+    let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
+    unsafe {
+        let stat_ptr = stat.as_mut_ptr();
+        (*stat_ptr).st_ino = ino;
+        (*stat_ptr).st_nlink = nlink;
+        (*stat_ptr).st_mode = mode;
+        (*stat_ptr).st_uid = libc::getuid();
+        (*stat_ptr).st_gid = libc::getgid();
+        (*stat_ptr).st_size = size;
+        (*stat_ptr).st_blksize = 4096;
+        (*stat_ptr).st_blocks = (size + 511) / 512;
+        (*stat_ptr).st_atime = time;
+        (*stat_ptr).st_mtime = time;
+        (*stat_ptr).st_ctime = time;
+        stat.assume_init()
+    }
+}
+
+/// The status of a directory
+fn dir_stat(relative: &str) -> libc::stat {
+    make_stat(path_ino(relative), libc::S_IFDIR | 0o755, 2, 4096, 0)
+}
+
+/// The status of an object
+fn file_stat(relative: &str, meta: &ObjectMeta) -> libc::stat {
+    make_stat(
+        path_ino(relative),
+        libc::S_IFREG | 0o644,
+        1,
+        meta.size as i64,
+        meta.last_modified.timestamp(),
+    )
+}
+
+/// File operations for objects opened read-only, which are read in ranges
+struct S3ReadFileOps {
+    store: Arc<dyn ObjectStore>,
+    meta: ObjectMeta,
+    stat: libc::stat,
+    offset: Mutex<i64>,
+    flags: Mutex<i32>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for S3ReadFileOps {
+    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let start = *self.offset.lock().unwrap() as u64;
+        let end = std::cmp::min(start + buf.len() as u64, self.meta.size);
+        if start >= end {
+            return Ok(0);
+        }
+
+        let data = self
+            .store
+            .get_range(&self.meta.location, start..end)
+            .await
+            .map_err(store_error)?;
+        buf[..data.len()].copy_from_slice(&data);
+        *self.offset.lock().unwrap() = (start as usize + data.len()) as i64;
+        Ok(data.len())
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        // The file was opened read-only
+        Err(errno(libc::EBADF))
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut current_offset = self.offset.lock().unwrap();
+        let new_offset =
+            file_seek_position(*current_offset, self.meta.size as i64, offset, whence)?;
+        *current_offset = new_offset;
+        Ok(new_offset)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        Ok(self.stat)
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // Nothing to sync, the file was opened read-only
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // Nothing to sync, the file was opened read-only
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual file doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual files
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+}
+
+/// Contents of a file opened for writing
+struct WriteState {
+    data: Vec<u8>,
+    /// Whether `data` differs from the object in the store
+    dirty: bool,
+    mtime: i64,
+}
+
+/// File operations for objects opened for writing, which are buffered in
+/// memory and uploaded when closed or synced
+struct S3WriteFileOps {
+    store: Arc<dyn ObjectStore>,
+    key: ObjectPath,
+    ino: u64,
+    /// Held across uploads so that a write doesn't race with one
+    state: tokio::sync::Mutex<WriteState>,
+    offset: Mutex<i64>,
+    flags: Mutex<i32>,
+}
+
+impl S3WriteFileOps {
+    /// Upload the file if it changed since it was last uploaded
+    async fn upload(&self) -> VfsResult<()> {
+        let mut state = self.state.lock().await;
+        if !state.dirty {
+            return Ok(());
+        }
+
+        tracing::debug!(key = %self.key, size = state.data.len(), "s3 upload");
+        if state.data.len() > MULTIPART_CHUNK_SIZE {
+            let upload = self
+                .store
+                .put_multipart(&self.key)
+                .await
+                .map_err(store_error)?;
+            let mut writer = WriteMultipart::new_with_chunk_size(upload, MULTIPART_CHUNK_SIZE);
+            writer.write(&state.data);
+            writer.finish().await.map_err(store_error)?;
+        } else {
+            self.store
+                .put(&self.key, state.data.clone().into())
+                .await
+                .map_err(store_error)?;
+        }
+        state.dirty = false;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl FileOps for S3WriteFileOps {
+    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        if self.get_flags() & libc::O_ACCMODE == libc::O_WRONLY {
+            return Err(errno(libc::EBADF));
+        }
+        let state = self.state.lock().await;
+        let mut offset = self.offset.lock().unwrap();
+        let start = *offset as usize;
+        if start >= state.data.len() {
+            return Ok(0);
+        }
+
+        let end = std::cmp::min(start + buf.len(), state.data.len());
+        let bytes_read = end - start;
+        buf[..bytes_read].copy_from_slice(&state.data[start..end]);
+        *offset += bytes_read as i64;
+        Ok(bytes_read)
+    }
+
+    async fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+        let append = self.get_flags() & libc::O_APPEND != 0;
+        let mut state = self.state.lock().await;
+        let mut offset = self.offset.lock().unwrap();
+
+        let start = if append {
+            state.data.len()
+        } else {
+            *offset as usize
+        };
+
+        // Extend the buffer if necessary, filling any hole with zeroes
+        if start + buf.len() > state.data.len() {
+            state.data.resize(start + buf.len(), 0);
+        }
+
+        state.data[start..start + buf.len()].copy_from_slice(buf);
+        state.dirty = true;
+        state.mtime = now();
+        *offset = (start + buf.len()) as i64;
+        Ok(buf.len())
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let size = self.state.lock().await.data.len() as i64;
+        let mut current_offset = self.offset.lock().unwrap();
+        let new_offset = file_seek_position(*current_offset, size, offset, whence)?;
+        *current_offset = new_offset;
+        Ok(new_offset)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        let state = self.state.lock().await;
+        Ok(make_stat(
+            self.ino,
+            libc::S_IFREG | 0o644,
+            1,
+            state.data.len() as i64,
+            state.mtime,
+        ))
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        self.upload().await
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        self.upload().await
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual file doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual files
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        self.upload().await
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+}
+
+/// Directory operations for object store directories
+struct S3DirectoryOps {
+    vfs: S3Vfs,
+    relative_path: String,
+    flags: Mutex<i32>,
+    /// Listing taken on the first getdents, until the directory is rewound
+    entries: Mutex<Option<DirEntryList>>,
+    /// Current position in the directory listing
+    position: Mutex<usize>,
+}
+
+impl S3DirectoryOps {
+    async fn load_entries(&self) -> VfsResult<DirEntryList> {
+        let (directories, objects) = self.vfs.list(&self.relative_path).await?;
+
+        // Sorted by name, with files taking precedence over directories of the same name
+        let mut children: BTreeMap<String, u8> = BTreeMap::new();
+        for name in directories {
+            children.insert(name, libc::DT_DIR);
+        }
+        let child_prefix = if self.relative_path == "/" {
+            "/".to_string()
+        } else {
+            format!("{}/", self.relative_path)
+        };
+        for dir in self.vfs.directories.lock().unwrap().iter() {
+            if let Some(name) = dir.strip_prefix(&child_prefix) {
+                if !name.contains('/') {
+                    children.entry(name.to_string()).or_insert(libc::DT_DIR);
+                }
+            }
+        }
+        for meta in objects {
+            if let Some(name) = meta.location.filename() {
+                children.insert(name.to_string(), libc::DT_REG);
+            }
+        }
+
+        let parent = Path::new(&self.relative_path)
+            .parent()
+            .and_then(Path::to_str)
+            .unwrap_or("/");
+        let mut entries = vec![
+            (path_ino(&self.relative_path), ".".to_string(), libc::DT_DIR),
+            (path_ino(parent), "..".to_string(), libc::DT_DIR),
+        ];
+        for (name, d_type) in children {
+            let ino = path_ino(&format!("{}{}", child_prefix, name));
+            entries.push((ino, name, d_type));
+        }
+        Ok(entries)
+    }
+}
+
+#[async_trait::async_trait]
+impl FileOps for S3DirectoryOps {
+    async fn read(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        // Cannot read from a directory
+        Err(VfsError::Other("Is a directory".to_string()))
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        // Cannot write to a directory
+        Err(VfsError::Other("Is a directory".to_string()))
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut position = self.position.lock().unwrap();
+        *position = dir_seek_position(*position, offset, whence)?;
+        if *position == 0 {
+            // Rewinding picks up entries added since the listing was taken
+            *self.entries.lock().unwrap() = None;
+        }
+        Ok(*position as i64)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        Ok(dir_stat(&self.relative_path))
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // Nothing to sync for directories
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // Nothing to sync for directories
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        // Virtual directory doesn't support ioctl
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual directories
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        // Nothing to do when closing a directory
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+
+    async fn getdents(&self) -> VfsResult<DirEntryList> {
+        let cached = self.entries.lock().unwrap().clone();
+        let all_entries = match cached {
+            Some(entries) => entries,
+            None => {
+                let entries = self.load_entries().await?;
+                *self.entries.lock().unwrap() = Some(entries.clone());
+                entries
+            }
+        };
+
+        // Return remaining entries and update position
+        let mut position = self.position.lock().unwrap();
+        let remaining = all_entries[(*position).min(all_entries.len())..].to_vec();
+        *position = all_entries.len();
+        Ok(remaining)
+    }
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+
+    async fn read_all(file: &BoxedFileOps) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0; 5];
+        loop {
+            let n = file.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return data;
+            }
+            data.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    async fn names(vfs: &S3Vfs, path: &str) -> Vec<(String, u8)> {
+        let dir = vfs
+            .open(Path::new(path), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        dir.getdents()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(_, name, d_type)| (name, d_type))
+            .collect()
+    }
+
+    async fn object(store: &InMemory, key: &str) -> Option<Vec<u8>> {
+        match store.get(&ObjectPath::from(key)).await {
+            Ok(result) => Some(result.bytes().await.unwrap().to_vec()),
+            Err(object_store::Error::NotFound { .. }) => None,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_and_list() {
+        let store = Arc::new(InMemory::new());
+        for (key, data) in [
+            ("runs/42/input.txt", "hello, world"),
+            ("runs/42/logs/a.log", "a"),
+            ("runs/42/logs/b.log", "b"),
+            ("runs/43/input.txt", "other run"),
+        ] {
+            store
+                .put(&ObjectPath::from(key), data.as_bytes().to_vec().into())
+                .await
+                .unwrap();
+        }
+        let vfs = S3Vfs::from_store(store, "runs/42", PathBuf::from("/bucket"));
+
+        let file = vfs
+            .open(Path::new("/bucket/input.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(read_all(&file).await, b"hello, world");
+        assert_eq!(file.seek(7, libc::SEEK_SET).await.unwrap(), 7);
+        assert_eq!(read_all(&file).await, b"world");
+        assert!(file.write(b"x").await.is_err());
+
+        let stat = vfs.stat(Path::new("/bucket/input.txt")).await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFREG);
+        assert_eq!(stat.st_size, 12);
+        let stat = vfs.stat(Path::new("/bucket/logs")).await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFDIR);
+        assert!(matches!(
+            vfs.stat(Path::new("/bucket/missing")).await,
+            Err(VfsError::NotFound)
+        ));
+
+        assert_eq!(
+            names(&vfs, "/bucket").await,
+            vec![
+                (".".to_string(), libc::DT_DIR),
+                ("..".to_string(), libc::DT_DIR),
+                ("input.txt".to_string(), libc::DT_REG),
+                ("logs".to_string(), libc::DT_DIR),
+            ]
+        );
+        assert_eq!(
+            names(&vfs, "/bucket/logs").await[2..],
+            [
+                ("a.log".to_string(), libc::DT_REG),
+                ("b.log".to_string(), libc::DT_REG),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_on_close() {
+        let store = Arc::new(InMemory::new());
+        let vfs = S3Vfs::from_store(store.clone(), "out", PathBuf::from("/bucket"));
+
+        let file = vfs
+            .open(
+                Path::new("/bucket/result.txt"),
+                libc::O_WRONLY | libc::O_CREAT,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"partial").await.unwrap();
+        assert_eq!(file.fstat().await.unwrap().st_size, 7);
+        // Nothing is uploaded until the file is closed
+        assert_eq!(object(&store, "out/result.txt").await, None);
+        file.close().await.unwrap();
+        assert_eq!(
+            object(&store, "out/result.txt").await,
+            Some(b"partial".to_vec())
+        );
+
+        // Appending rewrites the whole object
+        let file = vfs
+            .open(
+                Path::new("/bucket/result.txt"),
+                libc::O_WRONLY | libc::O_APPEND,
+                0,
+            )
+            .await
+            .unwrap();
+        file.write(b" and more").await.unwrap();
+        file.fsync().await.unwrap();
+        assert_eq!(
+            object(&store, "out/result.txt").await,
+            Some(b"partial and more".to_vec())
+        );
+
+        // Creating a file needs its parent directory
+        assert!(matches!(
+            vfs.open(
+                Path::new("/bucket/new/file"),
+                libc::O_WRONLY | libc::O_CREAT,
+                0o644
+            )
+            .await,
+            Err(VfsError::NotFound)
+        ));
+        vfs.mkdir(Path::new("/bucket/new"), 0o755).await.unwrap();
+        assert!(matches!(
+            vfs.mkdir(Path::new("/bucket/new"), 0o755).await,
+            Err(VfsError::AlreadyExists)
+        ));
+        assert!(matches!(
+            vfs.open(
+                Path::new("/bucket/result.txt"),
+                libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL,
+                0o644
+            )
+            .await,
+            Err(VfsError::AlreadyExists)
+        ));
+
+        // An empty new file is uploaded too
+        let file = vfs
+            .open(
+                Path::new("/bucket/new/file"),
+                libc::O_WRONLY | libc::O_CREAT,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        assert_eq!(object(&store, "out/new/file").await, Some(Vec::new()));
+
+        // A large file is uploaded in parts
+        let large: Vec<u8> = (0..MULTIPART_CHUNK_SIZE * 2 + 3)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let file = vfs
+            .open(
+                Path::new("/bucket/large.bin"),
+                libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(&large).await.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(object(&store, "out/large.bin").await, Some(large));
+    }
+
+    #[tokio::test]
+    async fn test_remove_and_rename() {
+        let store = Arc::new(InMemory::new());
+        store
+            .put(&ObjectPath::from("dir/a"), b"a".to_vec().into())
+            .await
+            .unwrap();
+        let vfs = S3Vfs::from_store(store.clone(), "", PathBuf::from("/bucket"));
+
+        assert!(matches!(
+            vfs.rmdir(Path::new("/bucket/dir")).await,
+            Err(VfsError::IoError(e)) if e.raw_os_error() == Some(libc::ENOTEMPTY)
+        ));
+        assert!(matches!(
+            vfs.unlink(Path::new("/bucket/dir")).await,
+            Err(VfsError::IoError(e)) if e.raw_os_error() == Some(libc::EISDIR)
+        ));
+
+        vfs.rename(Path::new("/bucket/dir/a"), Path::new("/bucket/b"), false)
+            .await
+            .unwrap();
+        assert_eq!(object(&store, "dir/a").await, None);
+        assert_eq!(object(&store, "b").await, Some(b"a".to_vec()));
+        // The directory went away with its last object
+        assert!(matches!(
+            vfs.stat(Path::new("/bucket/dir")).await,
+            Err(VfsError::NotFound)
+        ));
+
+        vfs.mkdir(Path::new("/bucket/empty"), 0o755).await.unwrap();
+        let file = vfs
+            .open(
+                Path::new("/bucket/c"),
+                libc::O_WRONLY | libc::O_CREAT,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        assert!(matches!(
+            vfs.rename(Path::new("/bucket/b"), Path::new("/bucket/c"), true)
+                .await,
+            Err(VfsError::AlreadyExists)
+        ));
+
+        vfs.unlink(Path::new("/bucket/b")).await.unwrap();
+        assert_eq!(object(&store, "b").await, None);
+        vfs.rmdir(Path::new("/bucket/empty")).await.unwrap();
+        assert!(matches!(
+            vfs.rmdir(Path::new("/bucket/empty")).await,
+            Err(VfsError::NotFound)
+        ));
+    }
+}
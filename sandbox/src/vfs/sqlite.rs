@@ -1,9 +1,22 @@
-use super::file::{BoxedFileOps, FileOps};
-use super::{Vfs, VfsError, VfsResult};
+use super::file::{dir_seek_position, file_seek_position, BoxedFileOps, FileOps};
+use super::{components, IdMap, Vfs, VfsError, VfsResult, MAX_SYMLINKS};
 use agentfs_sdk::Filesystem;
+use std::collections::HashMap;
 use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Default size of the per-file write-back buffer, see
+/// [`SqliteVfs::with_write_buffer_size`]
+pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How long cached stats are used before being read again, so that changes
+/// made through other connections to the database show up
+const STAT_CACHE_TTL: Duration = Duration::from_secs(1);
+
+/// Number of inodes whose stats are cached
+const STAT_CACHE_CAPACITY: usize = 1024;
 
 /// A SQLite-backed virtual filesystem using the AgentFS SDK
 ///
@@ -15,6 +28,18 @@ pub struct SqliteVfs {
     fs: Arc<Filesystem>,
     /// The virtual path as seen by the sandboxed process
     mount_point: PathBuf,
+    /// Ownership reported for files in the mount
+    id_map: IdMap,
+    /// Size at which the write-back buffer of a file is stored
+    write_buffer_size: usize,
+    /// Limit on the total size of the files in the filesystem
+    quota: Option<u64>,
+    /// Locks serializing the mutations of each inode across open files
+    inode_locks: Arc<InodeLocks>,
+    /// Stats of recently looked up inodes
+    stat_cache: Arc<StatCache>,
+    /// Whether the database was opened read-only
+    read_only: bool,
 }
 
 impl SqliteVfs {
@@ -33,10 +58,98 @@ impl SqliteVfs {
             .await
             .map_err(|e| VfsError::Other(format!("Failed to create filesystem: {}", e)))?;
 
-        Ok(Self {
+        Ok(Self::from_filesystem(fs, mount_point, false))
+    }
+
+    /// Create a SQLite VFS over an existing database that cannot be modified
+    ///
+    /// The database is opened with a read-only connection, so nothing done
+    /// through the mount can change it. Operations that would write fail
+    /// with `EROFS`.
+    ///
+    /// # Arguments
+    /// * `db_path` - Path to the SQLite database file
+    /// * `mount_point` - The virtual path seen by the guest (e.g., "/agent")
+    pub async fn new_read_only(db_path: impl AsRef<Path>, mount_point: PathBuf) -> VfsResult<Self> {
+        let db_path_str = db_path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| VfsError::InvalidInput("Invalid database path".to_string()))?;
+
+        let fs = Filesystem::open_read_only(db_path_str)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to open filesystem: {}", e)))?;
+
+        Ok(Self::from_filesystem(fs, mount_point, true))
+    }
+
+    fn from_filesystem(fs: Filesystem, mount_point: PathBuf, read_only: bool) -> Self {
+        Self {
             fs: Arc::new(fs),
             mount_point,
-        })
+            id_map: IdMap::default(),
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            quota: None,
+            inode_locks: Arc::new(InodeLocks::default()),
+            stat_cache: Arc::new(StatCache::default()),
+            read_only,
+        }
+    }
+
+    /// Mount the same filesystem at another `mount_point`
+    ///
+    /// The returned VFS shares the database connection, inode locks, and
+    /// stat cache of this one, so changes made through either mount are
+    /// immediately visible through the other.
+    pub fn with_mount_point(&self, mount_point: PathBuf) -> Self {
+        Self {
+            mount_point,
+            ..self.clone()
+        }
+    }
+
+    /// Report files in the mount with the ownership of `id_map`
+    pub fn with_id_map(self, id_map: IdMap) -> Self {
+        Self { id_map, ..self }
+    }
+
+    /// Buffer up to `size` bytes of contiguous writes per open file
+    ///
+    /// Buffered writes are stored on fsync, close, a write elsewhere in the
+    /// file, or before the buffer would reach `size`. The buffer is shared by
+    /// all files open on the inode through this VFS, so their reads and stats
+    /// see buffered writes right away, and opening the file with `O_TRUNC`
+    /// discards them. Other connections to the database only see them once
+    /// they are stored. A size of 0 stores every write immediately.
+    pub fn with_write_buffer_size(self, write_buffer_size: usize) -> Self {
+        Self {
+            write_buffer_size,
+            ..self
+        }
+    }
+
+    /// Limit the total size of the files in the filesystem to `quota` bytes
+    ///
+    /// A write that would grow the filesystem past the quota stores only the
+    /// bytes that fit, and fails with `ENOSPC` if none do. Writes buffered for
+    /// other inodes are not counted until they are stored.
+    pub fn with_quota(self, quota: u64) -> Self {
+        Self {
+            quota: Some(quota),
+            ..self
+        }
+    }
+
+    /// Wait up to `timeout` for other processes to release the database
+    ///
+    /// Writes to a database locked by another connection are retried with
+    /// backoff until the timeout runs out, and fail with `EIO` after that.
+    /// See [`Filesystem::set_busy_timeout`].
+    pub fn with_busy_timeout(self, timeout: Duration) -> VfsResult<Self> {
+        self.fs
+            .set_busy_timeout(timeout)
+            .map_err(|e| VfsError::Other(format!("Failed to set busy timeout: {}", e)))?;
+        Ok(self)
     }
 
     /// Get the mount point path
@@ -44,6 +157,211 @@ impl SqliteVfs {
         &self.mount_point
     }
 
+    /// Number of SQL statements the filesystem has executed
+    ///
+    /// See [`Filesystem::query_count`].
+    pub fn query_count(&self) -> u64 {
+        self.fs.query_count()
+    }
+
+    /// Number of write transactions the filesystem has committed
+    ///
+    /// See [`Filesystem::transaction_count`].
+    pub fn transaction_count(&self) -> u64 {
+        self.fs.transaction_count()
+    }
+
+    /// Get the stats of a relative path without following symlinks
+    ///
+    /// The path is looked up in the database every time, but the stats of
+    /// its inode come from the [`StatCache`] if they are there. The size
+    /// includes writes buffered by the files open on the inode.
+    async fn lstat_relative(&self, relative_path: &str) -> VfsResult<Option<agentfs_sdk::Stats>> {
+        let Some(ino) = self
+            .fs
+            .lookup(relative_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
+        else {
+            return Ok(None);
+        };
+        let mut stats = match self.stat_cache.get(ino) {
+            Ok(stats) => stats,
+            Err(generation) => {
+                let stats = self
+                    .fs
+                    .stat_inode(ino)
+                    .await
+                    .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?;
+                let Some(stats) = stats else {
+                    return Ok(None);
+                };
+                self.stat_cache.insert(&stats, generation);
+                stats
+            }
+        };
+        if let Some(inode) = self.inode_locks.get_open(ino) {
+            let buffer = &inode.lock().await.buffer;
+            if !buffer.data.is_empty() {
+                stats.size = stats.size.max(buffer.end());
+            }
+        }
+        Ok(Some(stats))
+    }
+
+    /// Resolve a sandbox path to a relative path for the SDK, following symlinks
+    ///
+    /// Symlinks in parent directories are always followed, and a symlink in
+    /// the last component only if `follow` is set. Relative targets are
+    /// resolved against the directory of the link, absolute targets must
+    /// point into this mount. The last component does not need to exist, so
+    /// the result can be used to create a file.
+    async fn resolve(&self, path: &Path, follow: bool) -> VfsResult<String> {
+        let relative_path = self.translate_to_relative(path)?;
+        // Components left to resolve, next one last
+        let mut pending = components(&relative_path);
+        let mut resolved: Vec<String> = Vec::new();
+        let mut links = 0;
+        while let Some(name) = pending.pop() {
+            match name.as_str() {
+                "." => continue,
+                ".." => {
+                    resolved.pop();
+                    continue;
+                }
+                _ => {}
+            }
+            resolved.push(name);
+            let last = pending.is_empty();
+            if last && !follow {
+                break;
+            }
+
+            let current = format!("/{}", resolved.join("/"));
+            let stats = self.lstat_relative(&current).await?;
+            match stats {
+                Some(stats) if stats.is_symlink() => {}
+                Some(_) => continue,
+                None if last => break,
+                None => return Err(VfsError::NotFound),
+            }
+
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return Err(VfsError::SymlinkLoop);
+            }
+            let target = self
+                .fs
+                .readlink(&current)
+                .await
+                .map_err(|e| VfsError::Other(format!("Failed to read symlink: {}", e)))?
+                .ok_or(VfsError::NotFound)?;
+            resolved.pop();
+            if target.starts_with('/') {
+                let target = Path::new(&target)
+                    .strip_prefix(&self.mount_point)
+                    .map_err(|_| VfsError::NotFound)?;
+                let target = target
+                    .to_str()
+                    .ok_or_else(|| VfsError::InvalidInput("Invalid symlink target".to_string()))?;
+                resolved.clear();
+                pending.extend(components(target));
+            } else {
+                pending.extend(components(&target));
+            }
+        }
+        Ok(format!("/{}", resolved.join("/")))
+    }
+
+    /// Resolve a sandbox path without following a symlink in the last
+    /// component, failing with `NotFound` if nothing exists at the path
+    async fn existing_relative(&self, path: &Path) -> VfsResult<String> {
+        let relative_path = self.resolve(path, false).await?;
+        self.lstat_relative(&relative_path)
+            .await?
+            .ok_or(VfsError::NotFound)?;
+        Ok(relative_path)
+    }
+
+    /// Open an unnamed file in the directory with `stats` for `O_TMPFILE`
+    async fn open_tmpfile(
+        &self,
+        stats: Option<agentfs_sdk::Stats>,
+        flags: i32,
+    ) -> VfsResult<BoxedFileOps> {
+        let stats = stats.ok_or(VfsError::NotFound)?;
+        if !stats.is_directory() {
+            return Err(VfsError::InvalidInput("Not a directory".to_string()));
+        }
+        if flags & libc::O_ACCMODE == libc::O_RDONLY {
+            return Err(VfsError::InvalidInput(
+                "O_TMPFILE requires write access".to_string(),
+            ));
+        }
+
+        let ino = self
+            .fs
+            .create_unlinked()
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to create inode: {}", e)))?;
+        Ok(self.file_ops(ino, flags, true))
+    }
+
+    /// Open a handle that only locates the file at `path` for `O_PATH`
+    ///
+    /// Like on Linux, flags other than `O_NOFOLLOW` and `O_DIRECTORY` are
+    /// ignored, so no write access is needed, and with `O_NOFOLLOW` a symlink
+    /// is opened itself. Any file type can be opened.
+    async fn open_path(&self, path: &Path, flags: i32) -> VfsResult<BoxedFileOps> {
+        let follow = flags & libc::O_NOFOLLOW == 0;
+        let relative_path = self.resolve(path, follow).await?;
+        tracing::debug!(path = %relative_path, flags, "sqlite open (O_PATH)");
+
+        let stats = self
+            .fs
+            .lstat(&relative_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+        if flags & libc::O_DIRECTORY != 0 && !stats.is_directory() {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::ENOTDIR,
+            )));
+        }
+        Ok(Arc::new(SqlitePathOps {
+            fs: self.fs.clone(),
+            id_map: self.id_map,
+            ino: stats.ino,
+            flags: Mutex::new(flags),
+        }))
+    }
+
+    /// Fail with `EROFS` if the database was opened read-only
+    fn check_writable(&self) -> VfsResult<()> {
+        if self.read_only {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EROFS,
+            )));
+        }
+        Ok(())
+    }
+
+    /// Create the file operations for the regular file with inode `ino`
+    fn file_ops(&self, ino: i64, flags: i32, tmpfile: bool) -> BoxedFileOps {
+        Arc::new(SqliteFileOps {
+            fs: self.fs.clone(),
+            id_map: self.id_map,
+            ino,
+            tmpfile,
+            offset: tokio::sync::Mutex::new(0),
+            flags: Mutex::new(flags),
+            write_buffer_size: self.write_buffer_size,
+            quota: self.quota,
+            inode: self.inode_locks.get(ino),
+            stat_cache: self.stat_cache.clone(),
+        })
+    }
+
     /// Translate a sandbox path to a relative path for the SDK
     fn translate_to_relative(&self, path: &Path) -> VfsResult<String> {
         let path_str = path
@@ -92,59 +410,86 @@ impl Vfs for SqliteVfs {
         true
     }
 
+    fn id_map(&self) -> IdMap {
+        self.id_map
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     async fn open(&self, path: &Path, flags: i32, _mode: u32) -> VfsResult<BoxedFileOps> {
-        let relative_path = self.translate_to_relative(path)?;
+        if flags & libc::O_PATH != 0 {
+            return self.open_path(path, flags).await;
+        }
+        if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0
+        {
+            self.check_writable()?;
+        }
+        let follow = flags & libc::O_NOFOLLOW == 0;
+        let relative_path = self.resolve(path, follow).await?;
+        tracing::debug!(path = %relative_path, flags, "sqlite open");
+
+        let stats = self.lstat_relative(&relative_path).await?;
+        if stats.as_ref().is_some_and(|stats| stats.is_symlink()) {
+            // Only reached with O_NOFOLLOW
+            return Err(VfsError::SymlinkLoop);
+        }
 
-        let stats = self
-            .fs
-            .stat(&relative_path)
-            .await
-            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?;
+        if flags & libc::O_TMPFILE == libc::O_TMPFILE {
+            return self.open_tmpfile(stats, flags).await;
+        }
 
         match stats {
             Some(stats) => {
+                let file_type = stats.mode & libc::S_IFMT;
+                if file_type == libc::S_IFCHR || file_type == libc::S_IFBLK {
+                    // Device nodes can be stored but give no access to a device
+                    return Err(VfsError::PermissionDenied);
+                }
+                if !stats.is_directory() && !stats.is_file() {
+                    // Named pipes and sockets can be created but not opened
+                    return Err(VfsError::NotSupported);
+                }
                 if stats.is_directory() {
                     Ok(Arc::new(SqliteDirectoryOps {
                         fs: self.fs.clone(),
+                        id_map: self.id_map,
                         path: relative_path,
                         flags: Mutex::new(flags),
-                        entries: Arc::new(Mutex::new(None)),
-                        position: Arc::new(Mutex::new(0)),
+                        cursor: tokio::sync::Mutex::new(DirCursor::default()),
                     }))
                 } else {
-                    // If O_TRUNC is set, skip reading the file and use empty data
-                    let data = if flags & libc::O_TRUNC != 0 {
-                        Vec::new()
-                    } else {
-                        self.fs
-                            .read_file(&relative_path)
-                            .await
-                            .map_err(|e| VfsError::Other(format!("Failed to read file: {}", e)))?
-                            .ok_or(VfsError::NotFound)?
-                    };
-                    Ok(Arc::new(SqliteFileOps {
-                        fs: self.fs.clone(),
-                        path: relative_path,
-                        data: Arc::new(Mutex::new(data)),
-                        offset: Arc::new(Mutex::new(0)),
-                        flags: Mutex::new(flags),
-                        dirty: Arc::new(Mutex::new(flags & libc::O_TRUNC != 0)),
-                    }))
+                    if flags & libc::O_TRUNC != 0 {
+                        let inode = self.inode_locks.get(stats.ino);
+                        let mut inode = inode.lock().await;
+                        // Writes buffered by other files are truncated away too
+                        inode.buffer = WriteBuffer::default();
+                        let result = self.fs.set_inode_len(stats.ino, 0).await;
+                        self.stat_cache.invalidate(stats.ino);
+                        result
+                            .map_err(|e| VfsError::Other(format!("Failed to truncate: {}", e)))?;
+                    }
+                    Ok(self.file_ops(stats.ino, flags, false))
                 }
             }
             None => {
                 // File doesn't exist - check if O_CREAT is set
                 if flags & libc::O_CREAT != 0 {
-                    let data = Vec::new();
-
-                    Ok(Arc::new(SqliteFileOps {
-                        fs: self.fs.clone(),
-                        path: relative_path,
-                        data: Arc::new(Mutex::new(data)),
-                        offset: Arc::new(Mutex::new(0)),
-                        flags: Mutex::new(flags),
-                        dirty: Arc::new(Mutex::new(true)), // Mark as dirty so it gets written on close
-                    }))
+                    self.fs.write_file(&relative_path, &[]).await.map_err(|e| {
+                        if e.to_string().contains("does not exist") {
+                            VfsError::NotFound
+                        } else {
+                            VfsError::Other(format!("Failed to create file: {}", e))
+                        }
+                    })?;
+                    let stats = self
+                        .fs
+                        .lstat(&relative_path)
+                        .await
+                        .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+                        .ok_or(VfsError::NotFound)?;
+                    Ok(self.file_ops(stats.ino, flags, false))
                 } else {
                     // File doesn't exist and O_CREAT not set
                     Err(VfsError::NotFound)
@@ -154,13 +499,11 @@ impl Vfs for SqliteVfs {
     }
 
     async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
-        let relative_path = self.translate_to_relative(path)?;
+        let relative_path = self.resolve(path, true).await?;
 
         let stats = self
-            .fs
-            .stat(&relative_path)
-            .await
-            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .lstat_relative(&relative_path)
+            .await?
             .ok_or(VfsError::NotFound)?;
 
         // Use MaybeUninit to construct libc::stat safely
@@ -173,7 +516,7 @@ impl Vfs for SqliteVfs {
             (*stat_ptr).st_mode = stats.mode;
             (*stat_ptr).st_uid = stats.uid;
             (*stat_ptr).st_gid = stats.gid;
-            (*stat_ptr).st_rdev = 0;
+            (*stat_ptr).st_rdev = stats.rdev;
             (*stat_ptr).st_size = stats.size;
             (*stat_ptr).st_blksize = 4096;
             (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
@@ -183,18 +526,18 @@ impl Vfs for SqliteVfs {
             (*stat_ptr).st_mtime_nsec = 0;
             (*stat_ptr).st_ctime = stats.ctime;
             (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+            let mut stat = stat.assume_init();
+            self.id_map.apply(&mut stat);
+            Ok(stat)
         }
     }
 
     async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
-        let relative_path = self.translate_to_relative(path)?;
+        let relative_path = self.resolve(path, false).await?;
 
         let stats = self
-            .fs
-            .lstat(&relative_path)
-            .await
-            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
+            .lstat_relative(&relative_path)
+            .await?
             .ok_or(VfsError::NotFound)?;
 
         // Use MaybeUninit to construct libc::stat safely
@@ -207,7 +550,7 @@ impl Vfs for SqliteVfs {
             (*stat_ptr).st_mode = stats.mode;
             (*stat_ptr).st_uid = stats.uid;
             (*stat_ptr).st_gid = stats.gid;
-            (*stat_ptr).st_rdev = 0;
+            (*stat_ptr).st_rdev = stats.rdev;
             (*stat_ptr).st_size = stats.size;
             (*stat_ptr).st_blksize = 4096;
             (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
@@ -217,12 +560,15 @@ impl Vfs for SqliteVfs {
             (*stat_ptr).st_mtime_nsec = 0;
             (*stat_ptr).st_ctime = stats.ctime;
             (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+            let mut stat = stat.assume_init();
+            self.id_map.apply(&mut stat);
+            Ok(stat)
         }
     }
 
     async fn symlink(&self, target: &Path, linkpath: &Path) -> VfsResult<()> {
-        let linkpath_rel = self.translate_to_relative(linkpath)?;
+        self.check_writable()?;
+        let linkpath_rel = self.resolve(linkpath, false).await?;
         let target_str = target
             .to_str()
             .ok_or_else(|| VfsError::InvalidInput("Invalid target path".to_string()))?;
@@ -240,148 +586,695 @@ impl Vfs for SqliteVfs {
             })
     }
 
-    async fn readlink(&self, path: &Path) -> VfsResult<PathBuf> {
-        let relative_path = self.translate_to_relative(path)?;
+    async fn mknod(&self, path: &Path, mode: u32, dev: u64) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.resolve(path, false).await?;
+        self.fs.mknod(&relative_path, mode, dev).await.map_err(|e| {
+            let err_msg = e.to_string();
+            if err_msg.contains("already exists") {
+                VfsError::AlreadyExists
+            } else if err_msg.contains("does not exist") {
+                VfsError::NotFound
+            } else if err_msg.contains("Unsupported file type") {
+                VfsError::NotSupported
+            } else {
+                VfsError::Other(format!("Failed to create file: {}", e))
+            }
+        })
+    }
 
-        let target = self
+    async fn mkdir(&self, path: &Path, _mode: u32) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.resolve(path, false).await?;
+        self.fs.mkdir(&relative_path).await.map_err(|e| {
+            let err_msg = e.to_string();
+            if err_msg.contains("already exists") || err_msg.contains("root directory") {
+                VfsError::AlreadyExists
+            } else if err_msg.contains("does not exist") {
+                VfsError::NotFound
+            } else {
+                VfsError::Other(format!("Failed to create directory: {}", e))
+            }
+        })
+    }
+
+    async fn unlink(&self, path: &Path) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.existing_relative(path).await?;
+        let stats = self
             .fs
-            .readlink(&relative_path)
+            .lstat(&relative_path)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to read symlink: {}", e)))?
+            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
             .ok_or(VfsError::NotFound)?;
+        if stats.is_directory() {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EISDIR,
+            )));
+        }
 
-        Ok(PathBuf::from(target))
+        let result = self.fs.unlink(&relative_path).await;
+        // The link count changed, or the unlink failed partway
+        self.stat_cache.invalidate(stats.ino);
+        let ino = result.map_err(|e| VfsError::Other(format!("Failed to unlink: {}", e)))?;
+        match self.inode_locks.get_open(ino) {
+            // Keep the inode of an open file until its last handle is closed
+            Some(inode) => inode.lock().await.unlinked = true,
+            None => {
+                self.fs
+                    .release_inode(ino)
+                    .await
+                    .map_err(|e| VfsError::Other(format!("Failed to release inode: {}", e)))?;
+            }
+        }
+        Ok(())
     }
-}
-
-/// File operations for SQLite VFS files
-struct SqliteFileOps {
-    fs: Arc<Filesystem>,
-    path: String,
-    data: Arc<Mutex<Vec<u8>>>,
-    offset: Arc<Mutex<i64>>,
-    flags: Mutex<i32>,
-    dirty: Arc<Mutex<bool>>,
-}
-
-#[async_trait::async_trait]
-impl FileOps for SqliteFileOps {
-    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
-        let data = self.data.lock().unwrap();
-        let mut offset = self.offset.lock().unwrap();
 
-        let start = *offset as usize;
-        if start >= data.len() {
-            return Ok(0);
+    async fn rmdir(&self, path: &Path) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.existing_relative(path).await?;
+        if relative_path == "/" {
+            // The mount point itself
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EBUSY,
+            )));
+        }
+        let stats = self
+            .fs
+            .lstat(&relative_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+        if !stats.is_directory() {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::ENOTDIR,
+            )));
         }
 
-        let end = std::cmp::min(start + buf.len(), data.len());
-        let bytes_read = end - start;
-        buf[..bytes_read].copy_from_slice(&data[start..end]);
-        *offset += bytes_read as i64;
-
-        Ok(bytes_read)
+        let result = self.fs.remove(&relative_path).await;
+        self.stat_cache.invalidate(stats.ino);
+        result.map_err(|e| {
+            if e.to_string().contains("not empty") {
+                VfsError::IoError(std::io::Error::from_raw_os_error(libc::ENOTEMPTY))
+            } else {
+                VfsError::Other(format!("Failed to remove directory: {}", e))
+            }
+        })
     }
 
-    async fn write(&self, buf: &[u8]) -> VfsResult<usize> {
-        let mut data = self.data.lock().unwrap();
-        let mut offset = self.offset.lock().unwrap();
-
-        let start = *offset as usize;
-
-        // Extend the buffer if necessary
-        if start + buf.len() > data.len() {
-            data.resize(start + buf.len(), 0);
+    async fn rename(&self, from: &Path, to: &Path, noreplace: bool) -> VfsResult<()> {
+        self.check_writable()?;
+        let from_rel = self.existing_relative(from).await?;
+        let to_rel = self.resolve(to, false).await?;
+        if from_rel == "/" || to_rel == "/" {
+            // The mount point itself
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EBUSY,
+            )));
         }
+        let stats = self
+            .lstat_relative(&from_rel)
+            .await?
+            .ok_or(VfsError::NotFound)?;
+        let dest = self.lstat_relative(&to_rel).await?;
 
-        data[start..start + buf.len()].copy_from_slice(buf);
-        *offset += buf.len() as i64;
-
-        // Mark as dirty since we modified the data
-        *self.dirty.lock().unwrap() = true;
+        // A replaced file that is still open must outlive the rename, so
+        // detach it first and keep its inode until its last handle is closed
+        if let Some(dest) = &dest {
+            if noreplace {
+                return Err(VfsError::AlreadyExists);
+            }
+            if dest.ino != stats.ino && !dest.is_directory() && !stats.is_directory() {
+                if let Some(inode) = self.inode_locks.get_open(dest.ino) {
+                    let result = self.fs.unlink(&to_rel).await;
+                    self.stat_cache.invalidate(dest.ino);
+                    result.map_err(|e| VfsError::Other(format!("Failed to unlink: {}", e)))?;
+                    inode.lock().await.unlinked = true;
+                }
+            }
+        }
 
-        Ok(buf.len())
+        let result = if noreplace {
+            self.fs.rename_noreplace(&from_rel, &to_rel).await
+        } else {
+            self.fs.rename(&from_rel, &to_rel).await
+        };
+        // The renamed inode gets a new change time, and a replaced one may
+        // have lost its last link
+        self.stat_cache.invalidate(stats.ino);
+        if let Some(dest) = &dest {
+            self.stat_cache.invalidate(dest.ino);
+        }
+        result.map_err(|e| {
+            let err_msg = e.to_string();
+            let errno = |errno| VfsError::IoError(std::io::Error::from_raw_os_error(errno));
+            if err_msg.contains("already exists") {
+                VfsError::AlreadyExists
+            } else if err_msg.contains("does not exist") {
+                VfsError::NotFound
+            } else if err_msg.contains("Not a directory") {
+                errno(libc::ENOTDIR)
+            } else if err_msg.contains("Is a directory") {
+                errno(libc::EISDIR)
+            } else if err_msg.contains("not empty") {
+                errno(libc::ENOTEMPTY)
+            } else if err_msg.contains("into itself") {
+                errno(libc::EINVAL)
+            } else {
+                VfsError::Other(format!("Failed to rename: {}", e))
+            }
+        })
     }
 
-    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
-        let data = self.data.lock().unwrap();
-        let mut current_offset = self.offset.lock().unwrap();
-
-        let new_offset = match whence {
-            libc::SEEK_SET => offset,
-            libc::SEEK_CUR => *current_offset + offset,
-            libc::SEEK_END => data.len() as i64 + offset,
-            _ => return Err(VfsError::Other("Invalid whence".to_string())),
-        };
+    async fn readlink(&self, path: &Path) -> VfsResult<PathBuf> {
+        let relative_path = self.resolve(path, false).await?;
 
-        if new_offset < 0 {
-            return Err(VfsError::Other("Invalid offset".to_string()));
+        let stats = self
+            .fs
+            .lstat(&relative_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+        if !stats.is_symlink() {
+            return Err(VfsError::InvalidInput("Not a symbolic link".to_string()));
         }
 
-        *current_offset = new_offset;
-        Ok(new_offset)
+        let target = self
+            .fs
+            .readlink(&relative_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to read symlink: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+
+        Ok(PathBuf::from(target))
     }
 
-    async fn fstat(&self) -> VfsResult<libc::stat> {
-        // Get the actual file stats from the filesystem
+    async fn chmod(&self, path: &Path, mode: u32) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.resolve(path, false).await?;
         let stats = self
             .fs
-            .stat(&self.path)
+            .lstat(&relative_path)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
             .ok_or(VfsError::NotFound)?;
-
-        let data = self.data.lock().unwrap();
-
-        // Use MaybeUninit to construct libc::stat safely
-        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
-        unsafe {
-            let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
-            (*stat_ptr).st_ino = stats.ino as u64;
-            (*stat_ptr).st_nlink = stats.nlink as u64;
-            (*stat_ptr).st_mode = stats.mode;
-            (*stat_ptr).st_uid = stats.uid;
-            (*stat_ptr).st_gid = stats.gid;
-            (*stat_ptr).st_rdev = 0;
-            (*stat_ptr).st_size = data.len() as i64;
-            (*stat_ptr).st_blksize = 4096;
-            (*stat_ptr).st_blocks = (data.len() as i64 + 4095) / 4096;
-            (*stat_ptr).st_atime = stats.atime;
-            (*stat_ptr).st_atime_nsec = 0;
-            (*stat_ptr).st_mtime = stats.mtime;
-            (*stat_ptr).st_mtime_nsec = 0;
-            (*stat_ptr).st_ctime = stats.ctime;
-            (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+        if stats.is_symlink() {
+            // Linux has no permissions on symlinks
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EOPNOTSUPP,
+            )));
         }
+        let result = self.fs.chmod_inode(stats.ino, mode).await;
+        self.stat_cache.invalidate(stats.ino);
+        result.map_err(|e| VfsError::Other(format!("Failed to change mode: {}", e)))
     }
 
-    async fn fsync(&self) -> VfsResult<()> {
-        // For virtual file, sync means write to database
-        let dirty = *self.dirty.lock().unwrap();
-        if !dirty {
-            return Ok(());
+    async fn truncate(&self, path: &Path, len: i64) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.resolve(path, true).await?;
+        let stats = self
+            .lstat_relative(&relative_path)
+            .await?
+            .ok_or(VfsError::NotFound)?;
+        if stats.is_directory() {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::EISDIR,
+            )));
         }
-
-        let data = self.data.lock().unwrap().clone();
-
-        // Write the data to the database
-        self.fs
-            .write_file(&self.path, &data)
+        if !stats.is_file() {
+            return Err(VfsError::InvalidInput("Not a regular file".to_string()));
+        }
+        // Resized through a file, so that writes buffered by the files open
+        // on the inode are truncated with it
+        self.file_ops(stats.ino, libc::O_WRONLY, false)
+            .set_len(len)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to write file: {}", e)))?;
-
-        // Clear dirty flag after successful write
-        *self.dirty.lock().unwrap() = false;
-
-        Ok(())
     }
 
-    async fn fdatasync(&self) -> VfsResult<()> {
-        // For virtual file, same as fsync
-        self.fsync().await
-    }
+    async fn chown(&self, path: &Path, uid: u32, gid: u32) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.resolve(path, false).await?;
+        let stats = self
+            .fs
+            .lstat(&relative_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+        let result = self.fs.chown_inode(stats.ino, uid, gid).await;
+        self.stat_cache.invalidate(stats.ino);
+        result.map_err(|e| VfsError::Other(format!("Failed to change owner: {}", e)))
+    }
+
+    async fn set_times(
+        &self,
+        path: &Path,
+        atime: Option<i64>,
+        mtime: Option<i64>,
+    ) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.resolve(path, false).await?;
+        let stats = self
+            .fs
+            .lstat(&relative_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+        let result = self.fs.set_times_inode(stats.ino, atime, mtime).await;
+        self.stat_cache.invalidate(stats.ino);
+        result.map_err(|e| VfsError::Other(format!("Failed to set times: {}", e)))
+    }
+
+    async fn link_inode(&self, ino: u64, newpath: &Path) -> VfsResult<()> {
+        self.check_writable()?;
+        let newpath_rel = self.resolve(newpath, false).await?;
+        let result = self.fs.link_inode(ino as i64, &newpath_rel).await;
+        self.stat_cache.invalidate(ino as i64);
+        result.map_err(|e| {
+            let err_msg = e.to_string();
+            if err_msg.contains("already exists") {
+                VfsError::AlreadyExists
+            } else if err_msg.contains("does not exist") {
+                VfsError::NotFound
+            } else {
+                VfsError::Other(format!("Failed to link inode: {}", e))
+            }
+        })
+    }
+
+    async fn getxattr(&self, path: &Path, name: &str) -> VfsResult<Option<Vec<u8>>> {
+        let relative_path = self.existing_relative(path).await?;
+        self.fs
+            .getxattr(&relative_path, name)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to get xattr: {}", e)))
+    }
+
+    async fn setxattr(&self, path: &Path, name: &str, value: &[u8]) -> VfsResult<()> {
+        self.check_writable()?;
+        let relative_path = self.existing_relative(path).await?;
+        self.fs
+            .setxattr(&relative_path, name, value)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to set xattr: {}", e)))
+    }
+
+    async fn listxattr(&self, path: &Path) -> VfsResult<Vec<String>> {
+        let relative_path = self.resolve(path, false).await?;
+        self.fs
+            .listxattr(&relative_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to list xattrs: {}", e)))?
+            .ok_or(VfsError::NotFound)
+    }
+
+    async fn removexattr(&self, path: &Path, name: &str) -> VfsResult<bool> {
+        self.check_writable()?;
+        let relative_path = self.existing_relative(path).await?;
+        self.fs
+            .removexattr(&relative_path, name)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to remove xattr: {}", e)))
+    }
+}
+
+/// Writes to a SQLite VFS inode that have not been stored yet
+///
+/// Only contiguous writes are buffered: a write elsewhere in the file
+/// stores the buffer first.
+#[derive(Default)]
+struct WriteBuffer {
+    /// File offset of the first buffered byte
+    offset: i64,
+    data: Vec<u8>,
+}
+
+impl WriteBuffer {
+    /// File offset after the last buffered byte
+    fn end(&self) -> i64 {
+        self.offset + self.data.len() as i64
+    }
+
+    /// Drop the buffered bytes at or past offset `len`
+    fn truncate(&mut self, len: i64) {
+        if len <= self.offset {
+            *self = WriteBuffer::default();
+        } else if len < self.end() {
+            self.data.truncate((len - self.offset) as usize);
+        }
+    }
+}
+
+/// State shared by the files open on an inode
+#[derive(Default)]
+struct OpenInode {
+    /// Whether the inode was unlinked while open, in which case it is
+    /// deleted when the last file open on it is closed
+    unlinked: bool,
+    /// Writes through any of the files that have not been stored yet
+    buffer: WriteBuffer,
+}
+
+/// Per-inode locks, so that files open on the same inode take turns
+/// storing data instead of interleaving their transactions
+///
+/// Each lock guards the [`OpenInode`] state of its inode.
+#[derive(Default)]
+struct InodeLocks(Mutex<HashMap<i64, Weak<tokio::sync::Mutex<OpenInode>>>>);
+
+impl InodeLocks {
+    /// Get the lock of inode `ino` if a file is open on it
+    fn get_open(&self, ino: i64) -> Option<Arc<tokio::sync::Mutex<OpenInode>>> {
+        let locks = self.0.lock().unwrap();
+        locks.get(&ino).and_then(Weak::upgrade)
+    }
+
+    /// Get the lock of inode `ino`, shared with the files open on it
+    fn get(&self, ino: i64) -> Arc<tokio::sync::Mutex<OpenInode>> {
+        let mut locks = self.0.lock().unwrap();
+        if let Some(lock) = locks.get(&ino).and_then(Weak::upgrade) {
+            return lock;
+        }
+        // Forget the locks of inodes that are no longer open
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(tokio::sync::Mutex::new(OpenInode::default()));
+        locks.insert(ino, Arc::downgrade(&lock));
+        lock
+    }
+}
+
+/// Stats of recently looked up inodes of a SQLite VFS
+///
+/// The VFS invalidates the entry of an inode whenever it changes the inode,
+/// and entries expire after [`STAT_CACHE_TTL`] to pick up changes made
+/// outside the VFS. Invalidations bump a generation, so that stats read
+/// while an inode was changing are not cached.
+#[derive(Default)]
+struct StatCache(Mutex<StatCacheEntries>);
+
+#[derive(Default)]
+struct StatCacheEntries {
+    stats: HashMap<i64, (agentfs_sdk::Stats, Instant)>,
+    generation: u64,
+}
+
+impl StatCache {
+    /// Get the cached stats of inode `ino`, or the current generation to
+    /// pass to [`StatCache::insert`] after reading them
+    fn get(&self, ino: i64) -> Result<agentfs_sdk::Stats, u64> {
+        let entries = self.0.lock().unwrap();
+        match entries.stats.get(&ino) {
+            Some((stats, cached_at)) if cached_at.elapsed() < STAT_CACHE_TTL => Ok(stats.clone()),
+            _ => Err(entries.generation),
+        }
+    }
+
+    /// Cache `stats`, read in `generation`, unless an inode was changed since
+    fn insert(&self, stats: &agentfs_sdk::Stats, generation: u64) {
+        let mut entries = self.0.lock().unwrap();
+        if entries.generation != generation {
+            return;
+        }
+        if entries.stats.len() >= STAT_CACHE_CAPACITY {
+            entries
+                .stats
+                .retain(|_, (_, cached_at)| cached_at.elapsed() < STAT_CACHE_TTL);
+            if entries.stats.len() >= STAT_CACHE_CAPACITY {
+                entries.stats.clear();
+            }
+        }
+        entries
+            .stats
+            .insert(stats.ino, (stats.clone(), Instant::now()));
+    }
+
+    /// Drop the cached stats of inode `ino` after it changed
+    fn invalidate(&self, ino: i64) {
+        let mut entries = self.0.lock().unwrap();
+        entries.stats.remove(&ino);
+        entries.generation += 1;
+    }
+}
+
+/// File operations for SQLite VFS files
+///
+/// Reads and writes go to the file's inode at the offset of the handle, with
+/// writes collected in the [`WriteBuffer`] shared by the files open on the
+/// inode.
+struct SqliteFileOps {
+    fs: Arc<Filesystem>,
+    id_map: IdMap,
+    ino: i64,
+    /// Whether this is an `O_TMPFILE` file, deleted on close unless linked
+    tmpfile: bool,
+    /// Position of the handle, locked for the whole of a read, write, or
+    /// seek so that FDs sharing the handle never use the same offset twice
+    offset: tokio::sync::Mutex<i64>,
+    flags: Mutex<i32>,
+    /// Size at which the buffer is stored
+    write_buffer_size: usize,
+    quota: Option<u64>,
+    /// State of the inode, locked while reading, buffering, or storing data
+    inode: Arc<tokio::sync::Mutex<OpenInode>>,
+    /// Stats cache of the VFS, invalidated when data is stored
+    stat_cache: Arc<StatCache>,
+}
+
+impl SqliteFileOps {
+    /// Store the buffered writes
+    async fn flush(&self) -> VfsResult<()> {
+        let mut inode = self.inode.lock().await;
+        self.flush_locked(&mut inode).await
+    }
+
+    /// Store the buffered writes, with the inode lock held
+    async fn flush_locked(&self, inode: &mut OpenInode) -> VfsResult<()> {
+        if inode.buffer.data.is_empty() {
+            return Ok(());
+        }
+        let result = self
+            .fs
+            .write_inode_at(self.ino, inode.buffer.offset, &inode.buffer.data)
+            .await;
+        self.stat_cache.invalidate(self.ino);
+        // On failure, keep the data so a later flush can retry
+        result.map_err(|e| VfsError::Other(format!("Failed to write file: {}", e)))?;
+        inode.buffer = WriteBuffer::default();
+        Ok(())
+    }
+
+    /// Number of bytes of a `len`-byte write at `offset` that fit in `quota`
+    async fn quota_room(
+        &self,
+        inode: &OpenInode,
+        offset: i64,
+        len: usize,
+        quota: u64,
+    ) -> VfsResult<usize> {
+        let stored_size = self
+            .fs
+            .stat_inode(self.ino)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .ok_or(VfsError::NotFound)?
+            .size;
+        let buffered_end = if inode.buffer.data.is_empty() {
+            0
+        } else {
+            inode.buffer.end()
+        };
+        let size = stored_size.max(buffered_end);
+        let growth = (offset + len as i64 - size).max(0) as u64;
+        if growth == 0 {
+            return Ok(len);
+        }
+
+        let used = self
+            .fs
+            .total_size()
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to get filesystem size: {}", e)))?
+            + (size - stored_size) as u64;
+        let room = quota.saturating_sub(used);
+        Ok(len.saturating_sub(growth.saturating_sub(room) as usize))
+    }
+
+    /// Get the stored stats of the file, with the size including buffered writes
+    async fn stats(&self) -> VfsResult<agentfs_sdk::Stats> {
+        let inode = self.inode.lock().await;
+        self.stats_locked(&inode).await
+    }
+
+    /// Get the stats of the file, with the inode lock held
+    async fn stats_locked(&self, inode: &OpenInode) -> VfsResult<agentfs_sdk::Stats> {
+        let mut stats = self
+            .fs
+            .stat_inode(self.ino)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+        if !inode.buffer.data.is_empty() {
+            stats.size = stats.size.max(inode.buffer.end());
+        }
+        Ok(stats)
+    }
+}
+
+#[async_trait::async_trait]
+impl FileOps for SqliteFileOps {
+    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut position = self.offset.lock().await;
+        // Held so that the buffer cannot be stored between reading the
+        // stored data and overlaying the buffer
+        let inode = self.inode.lock().await;
+        let offset = *position;
+        let mut n = self
+            .fs
+            .read_inode_at(self.ino, offset, buf)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to read file: {}", e)))?;
+
+        // Overlay the buffered writes on the stored data
+        let buffer = &inode.buffer;
+        if !buffer.data.is_empty() {
+            let end = (offset + buf.len() as i64).min(buffer.end());
+            if end > offset + n as i64 {
+                // Anything between the stored data and the buffer is a gap of zeros
+                buf[n..(end - offset) as usize].fill(0);
+                n = (end - offset) as usize;
+            }
+            let start = offset.max(buffer.offset);
+            if start < end {
+                buf[(start - offset) as usize..(end - offset) as usize].copy_from_slice(
+                    &buffer.data[(start - buffer.offset) as usize..(end - buffer.offset) as usize],
+                );
+            }
+        }
+        drop(inode);
+
+        *position = offset + n as i64;
+        Ok(n)
+    }
+
+    async fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+        let mut position = self.offset.lock().await;
+        // Held until the write is stored or buffered, so that appends and
+        // quota checks see the size left by other files' writes
+        let mut inode = self.inode.lock().await;
+        let offset = if self.get_flags() & libc::O_APPEND != 0 {
+            self.stats_locked(&inode).await?.size
+        } else {
+            *position
+        };
+
+        let len = match self.quota {
+            Some(quota) => self.quota_room(&inode, offset, buf.len(), quota).await?,
+            None => buf.len(),
+        };
+        if len == 0 && !buf.is_empty() {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::ENOSPC,
+            )));
+        }
+        let buf = &buf[..len];
+
+        // Store the buffer first if this write cannot join it, so a failure
+        // leaves none of this write behind
+        let joins = inode.buffer.data.is_empty()
+            || (inode.buffer.end() == offset
+                && inode.buffer.data.len() + buf.len() < self.write_buffer_size);
+        if !joins {
+            self.flush_locked(&mut inode).await?;
+        }
+
+        if buf.len() >= self.write_buffer_size {
+            let result = self.fs.write_inode_at(self.ino, offset, buf).await;
+            self.stat_cache.invalidate(self.ino);
+            result.map_err(|e| VfsError::Other(format!("Failed to write file: {}", e)))?;
+        } else {
+            if inode.buffer.data.is_empty() {
+                inode.buffer.offset = offset;
+            }
+            inode.buffer.data.extend_from_slice(buf);
+        }
+
+        // Advance only by what was stored or buffered, so a retry resumes there
+        *position = offset + buf.len() as i64;
+        Ok(buf.len())
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut current_offset = self.offset.lock().await;
+        // Only look up the size when the seek is relative to it
+        let size = match whence {
+            libc::SEEK_SET | libc::SEEK_CUR => 0,
+            _ => self.stats().await?.size,
+        };
+        let new_offset = file_seek_position(*current_offset, size, offset, whence)?;
+        *current_offset = new_offset;
+        Ok(new_offset)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        let stats = self.stats().await?;
+
+        // Use MaybeUninit to construct libc::stat safely
+        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
+        unsafe {
+            let stat_ptr = stat.as_mut_ptr();
+            (*stat_ptr).st_dev = 0;
+            (*stat_ptr).st_ino = stats.ino as u64;
+            (*stat_ptr).st_nlink = stats.nlink as u64;
+            (*stat_ptr).st_mode = stats.mode;
+            (*stat_ptr).st_uid = stats.uid;
+            (*stat_ptr).st_gid = stats.gid;
+            (*stat_ptr).st_rdev = stats.rdev;
+            (*stat_ptr).st_size = stats.size;
+            (*stat_ptr).st_blksize = 4096;
+            (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
+            (*stat_ptr).st_atime = stats.atime;
+            (*stat_ptr).st_atime_nsec = 0;
+            (*stat_ptr).st_mtime = stats.mtime;
+            (*stat_ptr).st_mtime_nsec = 0;
+            (*stat_ptr).st_ctime = stats.ctime;
+            (*stat_ptr).st_ctime_nsec = 0;
+            let mut stat = stat.assume_init();
+            self.id_map.apply(&mut stat);
+            Ok(stat)
+        }
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        // For virtual file, sync means storing the buffered writes
+        self.flush().await
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        // For virtual file, same as fsync
+        self.fsync().await
+    }
+
+    async fn set_len(&self, len: i64) -> VfsResult<()> {
+        if self.get_flags() & libc::O_ACCMODE == libc::O_RDONLY {
+            return Err(VfsError::InvalidInput("Not open for writing".to_string()));
+        }
+        // Held so that no write is buffered or stored past the new end
+        // while the inode is resized
+        let mut inode = self.inode.lock().await;
+        if let Some(quota) = self.quota {
+            // Extending a file counts against the quota like writing zeros
+            if self.quota_room(&inode, 0, len as usize, quota).await? < len as usize {
+                return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                    libc::ENOSPC,
+                )));
+            }
+        }
+
+        // Writes buffered by any file on the inode are truncated away too
+        inode.buffer.truncate(len);
+        let result = self.fs.set_inode_len(self.ino, len).await;
+        self.stat_cache.invalidate(self.ino);
+        result.map_err(|e| VfsError::Other(format!("Failed to truncate: {}", e)))
+    }
 
     fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
         match cmd {
@@ -409,7 +1302,18 @@ impl FileOps for SqliteFileOps {
 
     async fn close(&self) -> VfsResult<()> {
         // Ensure all data is written to the database before closing
-        self.fsync().await
+        self.flush().await?;
+
+        // An O_TMPFILE file that was never linked goes away on close, and so
+        // does a file unlinked while open once its last handle is closed
+        let unlinked = self.inode.lock().await.unlinked && Arc::strong_count(&self.inode) == 1;
+        if self.tmpfile || unlinked {
+            self.fs
+                .release_inode(self.ino)
+                .await
+                .map_err(|e| VfsError::Other(format!("Failed to release inode: {}", e)))?;
+        }
+        Ok(())
     }
 
     fn get_flags(&self) -> i32 {
@@ -425,15 +1329,76 @@ impl FileOps for SqliteFileOps {
 /// Type alias for directory entry list: (inode, name, type)
 type DirEntryList = Vec<(u64, String, u8)>;
 
+/// Listing and position of an open SQLite VFS directory
+#[derive(Default)]
+struct DirCursor {
+    /// Cached directory entries
+    entries: Option<DirEntryList>,
+    /// Current position in the directory listing
+    position: usize,
+}
+
 /// Directory operations for SQLite VFS directories
 struct SqliteDirectoryOps {
     fs: Arc<Filesystem>,
+    id_map: IdMap,
     path: String,
     flags: Mutex<i32>,
-    /// Cached directory entries
-    entries: Arc<Mutex<Option<DirEntryList>>>,
-    /// Current position in the directory listing
-    position: Arc<Mutex<usize>>,
+    /// Locked for the whole of a getdents or seek, so a rewind cannot drop
+    /// the listing while it is being read
+    cursor: tokio::sync::Mutex<DirCursor>,
+}
+
+impl SqliteDirectoryOps {
+    /// Read the directory entries, starting with `.` and `..`
+    async fn load_entries(&self) -> VfsResult<DirEntryList> {
+        let dir_entries = self
+            .fs
+            .readdir_entries(&self.path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to read directory: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+
+        // Convert to the format expected by getdents64
+        let mut result = Vec::new();
+
+        // Add . and .. entries with correct inode numbers
+        // Get current directory inode
+        let current_stats = self
+            .fs
+            .stat(&self.path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to stat current dir: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+        let current_ino = current_stats.ino as u64;
+
+        // Get parent directory inode
+        let parent_path = if self.path == "/" {
+            "/".to_string()
+        } else {
+            Path::new(&self.path)
+                .parent()
+                .map(|p| p.to_str().unwrap_or("/").to_string())
+                .unwrap_or("/".to_string())
+        };
+        let parent_stats = self
+            .fs
+            .stat(&parent_path)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to stat parent dir: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
+        let parent_ino = parent_stats.ino as u64;
+
+        result.push((current_ino, ".".to_string(), libc::DT_DIR));
+        result.push((parent_ino, "..".to_string(), libc::DT_DIR));
+
+        // The entry type is stored with the entry, so no per-entry stat is needed
+        for entry in dir_entries {
+            result.push((entry.ino as u64, entry.name, entry.d_type));
+        }
+
+        Ok(result)
+    }
 }
 
 #[async_trait::async_trait]
@@ -448,9 +1413,14 @@ impl FileOps for SqliteDirectoryOps {
         Err(VfsError::Other("Is a directory".to_string()))
     }
 
-    async fn seek(&self, _offset: i64, _whence: i32) -> VfsResult<i64> {
-        // Cannot seek in a directory
-        Err(VfsError::Other("Is a directory".to_string()))
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut cursor = self.cursor.lock().await;
+        cursor.position = dir_seek_position(cursor.position, offset, whence)?;
+        if cursor.position == 0 {
+            // Rewinding picks up entries added since the listing was taken
+            cursor.entries = None;
+        }
+        Ok(cursor.position as i64)
     }
 
     async fn fstat(&self) -> VfsResult<libc::stat> {
@@ -472,7 +1442,7 @@ impl FileOps for SqliteDirectoryOps {
             (*stat_ptr).st_mode = stats.mode;
             (*stat_ptr).st_uid = stats.uid;
             (*stat_ptr).st_gid = stats.gid;
-            (*stat_ptr).st_rdev = 0;
+            (*stat_ptr).st_rdev = stats.rdev;
             (*stat_ptr).st_size = stats.size;
             (*stat_ptr).st_blksize = 4096;
             (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
@@ -482,7 +1452,9 @@ impl FileOps for SqliteDirectoryOps {
             (*stat_ptr).st_mtime_nsec = 0;
             (*stat_ptr).st_ctime = stats.ctime;
             (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+            let mut stat = stat.assume_init();
+            self.id_map.apply(&mut stat);
+            Ok(stat)
         }
     }
 
@@ -535,93 +1507,1433 @@ impl FileOps for SqliteDirectoryOps {
     }
 
     async fn getdents(&self) -> VfsResult<DirEntryList> {
-        // Check if we need to populate the entries cache
-        let needs_populate = {
-            let entries_lock = self.entries.lock().unwrap();
-            entries_lock.is_none()
+        let mut cursor = self.cursor.lock().await;
+        if cursor.entries.is_none() {
+            cursor.entries = Some(self.load_entries().await?);
+        }
+
+        let position = cursor.position;
+        let remaining = match &cursor.entries {
+            Some(entries) if position < entries.len() => entries[position..].to_vec(),
+            // No more entries - return empty to signal EOF
+            _ => return Ok(Vec::new()),
         };
+        cursor.position += remaining.len();
+        Ok(remaining)
+    }
+}
 
-        if needs_populate {
-            // Read directory entries from the filesystem (without holding lock)
-            let dir_entries = self
-                .fs
-                .readdir(&self.path)
-                .await
-                .map_err(|e| VfsError::Other(format!("Failed to read directory: {}", e)))?
-                .ok_or(VfsError::NotFound)?;
+/// File operations for `O_PATH` handles of SQLite VFS files
+///
+/// The handle can be fstat-ed and used as the directory of `*at` calls, which
+/// go by the path kept in the FD table. Everything that would access the
+/// contents fails with `EBADF`, as on Linux.
+struct SqlitePathOps {
+    fs: Arc<Filesystem>,
+    id_map: IdMap,
+    ino: i64,
+    flags: Mutex<i32>,
+}
 
-            // Convert to the format expected by getdents64
-            let mut result = Vec::new();
+/// The error of operations not allowed on `O_PATH` handles
+fn path_handle_error() -> VfsError {
+    VfsError::IoError(std::io::Error::from_raw_os_error(libc::EBADF))
+}
 
-            // Add . and .. entries with correct inode numbers
-            // Get current directory inode
-            let current_stats = self
-                .fs
-                .stat(&self.path)
-                .await
-                .map_err(|e| VfsError::Other(format!("Failed to stat current dir: {}", e)))?
-                .ok_or(VfsError::NotFound)?;
-            let current_ino = current_stats.ino as u64;
+#[async_trait::async_trait]
+impl FileOps for SqlitePathOps {
+    async fn read(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(path_handle_error())
+    }
 
-            // Get parent directory inode
-            let parent_path = if self.path == "/" {
-                "/".to_string()
-            } else {
-                Path::new(&self.path)
-                    .parent()
-                    .map(|p| p.to_str().unwrap_or("/").to_string())
-                    .unwrap_or("/".to_string())
-            };
-            let parent_stats = self
-                .fs
-                .stat(&parent_path)
-                .await
-                .map_err(|e| VfsError::Other(format!("Failed to stat parent dir: {}", e)))?
-                .ok_or(VfsError::NotFound)?;
-            let parent_ino = parent_stats.ino as u64;
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        Err(path_handle_error())
+    }
 
-            result.push((current_ino, ".".to_string(), libc::DT_DIR));
-            result.push((parent_ino, "..".to_string(), libc::DT_DIR));
+    async fn seek(&self, _offset: i64, _whence: i32) -> VfsResult<i64> {
+        Err(path_handle_error())
+    }
 
-            for name in dir_entries {
-                // Construct the full path for this entry
-                let entry_path = if self.path == "/" {
-                    format!("/{}", name)
-                } else {
-                    format!("{}/{}", self.path, name)
-                };
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        let stats = self
+            .fs
+            .stat_inode(self.ino)
+            .await
+            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .ok_or(VfsError::NotFound)?;
 
-                // Get stats to determine type and inode
-                if let Ok(Some(stats)) = self.fs.stat(&entry_path).await {
-                    let d_type = if stats.is_directory() {
-                        libc::DT_DIR
-                    } else if stats.is_symlink() {
-                        libc::DT_LNK
-                    } else {
-                        libc::DT_REG
-                    };
-                    result.push((stats.ino as u64, name, d_type));
-                }
+        // Use MaybeUninit to construct libc::stat safely
+        let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
+        unsafe {
+            let stat_ptr = stat.as_mut_ptr();
+            (*stat_ptr).st_dev = 0;
+            (*stat_ptr).st_ino = stats.ino as u64;
+            (*stat_ptr).st_nlink = stats.nlink as u64;
+            (*stat_ptr).st_mode = stats.mode;
+            (*stat_ptr).st_uid = stats.uid;
+            (*stat_ptr).st_gid = stats.gid;
+            (*stat_ptr).st_rdev = stats.rdev;
+            (*stat_ptr).st_size = stats.size;
+            (*stat_ptr).st_blksize = 4096;
+            (*stat_ptr).st_blocks = (stats.size + 4095) / 4096;
+            (*stat_ptr).st_atime = stats.atime;
+            (*stat_ptr).st_atime_nsec = 0;
+            (*stat_ptr).st_mtime = stats.mtime;
+            (*stat_ptr).st_mtime_nsec = 0;
+            (*stat_ptr).st_ctime = stats.ctime;
+            (*stat_ptr).st_ctime_nsec = 0;
+            let mut stat = stat.assume_init();
+            self.id_map.apply(&mut stat);
+            Ok(stat)
+        }
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        Err(path_handle_error())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        Err(path_handle_error())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
             }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        Err(path_handle_error())
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        // No real kernel FD for virtual files
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        // Nothing was opened but the path
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+
+    async fn getdents(&self) -> VfsResult<DirEntryList> {
+        Err(path_handle_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_id_map() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap()
+            .with_id_map(IdMap {
+                uid: Some(1000),
+                gid: Some(1000),
+            });
+
+        let stat = vfs.stat(Path::new("/agent")).await.unwrap();
+        assert_eq!((stat.st_uid, stat.st_gid), (1000, 1000));
+
+        let file = vfs
+            .open(
+                Path::new("/agent/file"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        let stat = vfs.lstat(Path::new("/agent/file")).await.unwrap();
+        assert_eq!((stat.st_uid, stat.st_gid), (1000, 1000));
+        let file = vfs
+            .open(Path::new("/agent/file"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let stat = file.fstat().await.unwrap();
+        assert_eq!((stat.st_uid, stat.st_gid), (1000, 1000));
+    }
+
+    #[tokio::test]
+    async fn test_xattrs() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        let file = vfs
+            .open(
+                Path::new("/agent/file"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+
+        let path = Path::new("/agent/file");
+        assert_eq!(vfs.getxattr(path, "user.test").await.unwrap(), None);
+        vfs.setxattr(path, "user.test", b"hello").await.unwrap();
+        assert_eq!(
+            vfs.getxattr(path, "user.test").await.unwrap(),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(vfs.listxattr(path).await.unwrap(), vec!["user.test"]);
+        assert!(vfs.removexattr(path, "user.test").await.unwrap());
+        assert!(!vfs.removexattr(path, "user.test").await.unwrap());
+        assert!(vfs.listxattr(path).await.unwrap().is_empty());
+
+        assert!(matches!(
+            vfs.getxattr(Path::new("/agent/missing"), "user.test").await,
+            Err(VfsError::NotFound)
+        ));
+    }
 
-            // Store the results
-            let mut entries_lock = self.entries.lock().unwrap();
-            *entries_lock = Some(result);
+    #[tokio::test]
+    async fn test_readlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.symlink(Path::new("target.txt"), Path::new("/agent/link"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vfs.readlink(Path::new("/agent/link")).await.unwrap(),
+            PathBuf::from("target.txt")
+        );
+        assert!(matches!(
+            vfs.readlink(Path::new("/agent")).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            vfs.readlink(Path::new("/agent/missing")).await,
+            Err(VfsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_follow_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.mkdir("/dir").await.unwrap();
+        vfs.fs.write_file("/dir/file", b"hello").await.unwrap();
+        vfs.symlink(Path::new("/agent/dir/file"), Path::new("/agent/absolute"))
+            .await
+            .unwrap();
+        vfs.symlink(Path::new("../dir/file"), Path::new("/agent/dir/relative"))
+            .await
+            .unwrap();
+        vfs.symlink(Path::new("dir"), Path::new("/agent/dirlink"))
+            .await
+            .unwrap();
+        vfs.symlink(Path::new("b"), Path::new("/agent/a"))
+            .await
+            .unwrap();
+        vfs.symlink(Path::new("a"), Path::new("/agent/b"))
+            .await
+            .unwrap();
+
+        for path in [
+            "/agent/absolute",
+            "/agent/dir/relative",
+            "/agent/dirlink/file",
+        ] {
+            let file = vfs.open(Path::new(path), libc::O_RDONLY, 0).await.unwrap();
+            let mut buf = [0u8; 16];
+            let n = file.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"hello", "{}", path);
+            let stat = vfs.stat(Path::new(path)).await.unwrap();
+            assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFREG);
         }
+        let stat = vfs.lstat(Path::new("/agent/dir/relative")).await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFLNK);
+
+        // Writing through a symlink changes the target
+        let file = vfs
+            .open(
+                Path::new("/agent/absolute"),
+                libc::O_WRONLY | libc::O_TRUNC,
+                0,
+            )
+            .await
+            .unwrap();
+        file.write(b"bye").await.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(
+            vfs.fs.read_file("/dir/file").await.unwrap().unwrap(),
+            b"bye"
+        );
+
+        assert!(matches!(
+            vfs.open(
+                Path::new("/agent/absolute"),
+                libc::O_RDONLY | libc::O_NOFOLLOW,
+                0
+            )
+            .await,
+            Err(VfsError::SymlinkLoop)
+        ));
+        assert!(matches!(
+            vfs.open(Path::new("/agent/a"), libc::O_RDONLY, 0).await,
+            Err(VfsError::SymlinkLoop)
+        ));
+        assert!(matches!(
+            vfs.stat(Path::new("/agent/a")).await,
+            Err(VfsError::SymlinkLoop)
+        ));
+    }
 
-        // Get the current position and return entries
-        let mut position = self.position.lock().unwrap();
-        let entries_lock = self.entries.lock().unwrap();
-        let all_entries = entries_lock.as_ref().unwrap();
+    #[tokio::test]
+    async fn test_mknod() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.mknod(Path::new("/agent/pipe"), libc::S_IFIFO | 0o644, 0)
+            .await
+            .unwrap();
+        let stat = vfs.lstat(Path::new("/agent/pipe")).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFIFO | 0o644);
+        assert!(matches!(
+            vfs.open(Path::new("/agent/pipe"), libc::O_RDONLY, 0).await,
+            Err(VfsError::NotSupported)
+        ));
+        assert!(matches!(
+            vfs.mknod(Path::new("/agent/pipe"), libc::S_IFREG | 0o644, 0)
+                .await,
+            Err(VfsError::AlreadyExists)
+        ));
+        assert!(matches!(
+            vfs.mknod(Path::new("/agent/dir"), libc::S_IFDIR | 0o755, 0)
+                .await,
+            Err(VfsError::NotSupported)
+        ));
+
+        // Device nodes are stored with their device number, but cannot be opened
+        vfs.mknod(
+            Path::new("/agent/null"),
+            libc::S_IFCHR | 0o666,
+            libc::makedev(1, 3),
+        )
+        .await
+        .unwrap();
+        let stat = vfs.stat(Path::new("/agent/null")).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFCHR | 0o666);
+        assert_eq!(stat.st_rdev, libc::makedev(1, 3));
+        assert!(matches!(
+            vfs.open(Path::new("/agent/null"), libc::O_RDWR, 0).await,
+            Err(VfsError::PermissionDenied)
+        ));
+        vfs.mknod(Path::new("/agent/socket"), libc::S_IFSOCK | 0o755, 0)
+            .await
+            .unwrap();
+        let stat = vfs.lstat(Path::new("/agent/socket")).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFSOCK | 0o755);
+
+        // Without a file type, a regular file is created
+        vfs.mknod(Path::new("/agent/file"), 0o600, 0).await.unwrap();
+        let stat = vfs.stat(Path::new("/agent/file")).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFREG | 0o600);
+        assert_eq!(stat.st_size, 0);
+    }
 
-        if *position >= all_entries.len() {
-            // No more entries - return empty to signal EOF
-            Ok(Vec::new())
-        } else {
-            // Return remaining entries and update position
-            let remaining = all_entries[*position..].to_vec();
-            *position = all_entries.len();
-            Ok(remaining)
+    #[tokio::test]
+    async fn test_directories_shared_with_sdk() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let vfs = SqliteVfs::new(&db_path, PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        let sdk = Filesystem::new(db_path.to_str().unwrap()).await.unwrap();
+
+        // Created through the SDK, seen by the VFS
+        sdk.mkdir("/from_sdk").await.unwrap();
+        sdk.write_file("/from_sdk/file", b"sdk").await.unwrap();
+        let stat = vfs.stat(Path::new("/agent/from_sdk")).await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFDIR);
+        let file = vfs
+            .open(Path::new("/agent/from_sdk/file"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 3);
+        assert_eq!(&buf[..3], b"sdk");
+        file.close().await.unwrap();
+
+        // Created through the VFS, seen by the SDK
+        vfs.mkdir(Path::new("/agent/from_vfs"), 0o755)
+            .await
+            .unwrap();
+        vfs.symlink(Path::new("from_vfs"), Path::new("/agent/link"))
+            .await
+            .unwrap();
+        let file = vfs
+            .open(
+                Path::new("/agent/link/file"),
+                libc::O_CREAT | libc::O_WRONLY,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"vfs").await.unwrap();
+        file.close().await.unwrap();
+        assert!(sdk
+            .lstat("/from_vfs")
+            .await
+            .unwrap()
+            .unwrap()
+            .is_directory());
+        assert_eq!(
+            sdk.read_file("/from_vfs/file").await.unwrap().unwrap(),
+            b"vfs"
+        );
+        assert!(matches!(
+            vfs.mkdir(Path::new("/agent/from_sdk"), 0o755).await,
+            Err(VfsError::AlreadyExists)
+        ));
+        assert!(matches!(
+            vfs.mkdir(Path::new("/agent/missing/dir"), 0o755).await,
+            Err(VfsError::NotFound)
+        ));
+
+        // Removed through the VFS, gone for the SDK
+        let errno = |result: VfsResult<()>| match result {
+            Err(VfsError::IoError(e)) => e.raw_os_error(),
+            _ => None,
+        };
+        assert_eq!(
+            errno(vfs.rmdir(Path::new("/agent/from_vfs")).await),
+            Some(libc::ENOTEMPTY)
+        );
+        assert_eq!(
+            errno(vfs.unlink(Path::new("/agent/from_vfs")).await),
+            Some(libc::EISDIR)
+        );
+        assert_eq!(
+            errno(vfs.rmdir(Path::new("/agent/link")).await),
+            Some(libc::ENOTDIR)
+        );
+        assert_eq!(
+            errno(vfs.rmdir(Path::new("/agent")).await),
+            Some(libc::EBUSY)
+        );
+        vfs.unlink(Path::new("/agent/link")).await.unwrap();
+        vfs.unlink(Path::new("/agent/from_vfs/file")).await.unwrap();
+        vfs.rmdir(Path::new("/agent/from_vfs")).await.unwrap();
+        assert_eq!(sdk.readdir("/").await.unwrap().unwrap(), vec!["from_sdk"]);
+        assert!(matches!(
+            vfs.unlink(Path::new("/agent/from_vfs/file")).await,
+            Err(VfsError::NotFound)
+        ));
+
+        // Removed through the SDK, gone for the VFS
+        sdk.remove("/from_sdk/file").await.unwrap();
+        sdk.remove("/from_sdk").await.unwrap();
+        assert!(matches!(
+            vfs.stat(Path::new("/agent/from_sdk")).await,
+            Err(VfsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unlink_open_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.write_file("/file", b"hello").await.unwrap();
+        let ino = vfs.fs.lstat("/file").await.unwrap().unwrap().ino;
+
+        // The data stays readable through the open file until it is closed
+        let file = vfs
+            .open(Path::new("/agent/file"), libc::O_RDWR, 0)
+            .await
+            .unwrap();
+        vfs.unlink(Path::new("/agent/file")).await.unwrap();
+        assert!(vfs.fs.lstat("/file").await.unwrap().is_none());
+        let mut buf = [0; 8];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert!(vfs.fs.stat_inode(ino).await.unwrap().is_some());
+        file.close().await.unwrap();
+        assert!(vfs.fs.stat_inode(ino).await.unwrap().is_none());
+
+        // A file that is not open is deleted right away
+        vfs.fs.write_file("/other", b"bye").await.unwrap();
+        let ino = vfs.fs.lstat("/other").await.unwrap().unwrap().ino;
+        vfs.unlink(Path::new("/agent/other")).await.unwrap();
+        assert!(vfs.fs.stat_inode(ino).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.write_file("/tmp", b"new").await.unwrap();
+        vfs.fs.write_file("/taken", b"old").await.unwrap();
+
+        // With noreplace, a free name is taken and an existing one is kept
+        vfs.rename(Path::new("/agent/tmp"), Path::new("/agent/free"), true)
+            .await
+            .unwrap();
+        assert_eq!(vfs.fs.read_file("/free").await.unwrap().unwrap(), b"new");
+        assert!(matches!(
+            vfs.rename(Path::new("/agent/free"), Path::new("/agent/taken"), true)
+                .await,
+            Err(VfsError::AlreadyExists)
+        ));
+        assert_eq!(vfs.fs.read_file("/taken").await.unwrap().unwrap(), b"old");
+
+        // Without it, the destination is replaced, and stays readable
+        // through a file that has it open
+        let taken = vfs.fs.lstat("/taken").await.unwrap().unwrap().ino;
+        let file = vfs
+            .open(Path::new("/agent/taken"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        vfs.rename(Path::new("/agent/free"), Path::new("/agent/taken"), false)
+            .await
+            .unwrap();
+        assert_eq!(vfs.fs.read_file("/taken").await.unwrap().unwrap(), b"new");
+        assert!(vfs.fs.lstat("/free").await.unwrap().is_none());
+        let mut buf = [0; 8];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 3);
+        assert_eq!(&buf[..3], b"old");
+        file.close().await.unwrap();
+        assert!(vfs.fs.stat_inode(taken).await.unwrap().is_none());
+
+        assert!(matches!(
+            vfs.rename(Path::new("/agent/missing"), Path::new("/agent/x"), false)
+                .await,
+            Err(VfsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_file_seek() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.write_file("/file.txt", b"0123456789").await.unwrap();
+
+        let file = vfs
+            .open(Path::new("/agent/file.txt"), libc::O_RDWR, 0)
+            .await
+            .unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(file.seek(2, libc::SEEK_SET).await.unwrap(), 2);
+        assert_eq!(file.read(&mut buf[..2]).await.unwrap(), 2);
+        assert_eq!(&buf[..2], b"23");
+        assert_eq!(file.seek(3, libc::SEEK_CUR).await.unwrap(), 7);
+        assert_eq!(file.read(&mut buf).await.unwrap(), 3);
+        assert_eq!(&buf[..3], b"789");
+        assert_eq!(file.seek(-4, libc::SEEK_END).await.unwrap(), 6);
+        assert_eq!(file.read(&mut buf).await.unwrap(), 4);
+        assert_eq!(&buf, b"6789");
+
+        // SEEK_END counts writes that are not stored yet
+        assert_eq!(file.seek(0, libc::SEEK_END).await.unwrap(), 10);
+        file.write(b"ab").await.unwrap();
+        assert_eq!(file.seek(0, libc::SEEK_END).await.unwrap(), 12);
+
+        // The file has no holes
+        assert_eq!(file.seek(4, libc::SEEK_DATA).await.unwrap(), 4);
+        assert_eq!(file.seek(4, libc::SEEK_HOLE).await.unwrap(), 12);
+        assert!(matches!(
+            file.seek(12, libc::SEEK_DATA).await,
+            Err(VfsError::IoError(e)) if e.raw_os_error() == Some(libc::ENXIO)
+        ));
+        assert!(matches!(
+            file.seek(-13, libc::SEEK_END).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            file.seek(0, 42).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_write_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        let path = Path::new("/agent/file.txt");
+        let file = vfs
+            .open(path, libc::O_RDWR | libc::O_CREAT, 0o644)
+            .await
+            .unwrap();
+
+        // Tiny writes stay in the buffer until fsync
+        let queries = vfs.query_count();
+        for i in 0..1000 {
+            file.write(&[b'a' + (i % 26) as u8]).await.unwrap();
+        }
+        assert_eq!(vfs.query_count(), queries);
+        assert!(vfs
+            .fs
+            .read_file("/file.txt")
+            .await
+            .unwrap()
+            .unwrap()
+            .is_empty());
+        assert_eq!(file.fstat().await.unwrap().st_size, 1000);
+
+        // The handle reads its own buffered writes
+        let mut buf = [0u8; 4];
+        assert_eq!(file.seek(26, libc::SEEK_SET).await.unwrap(), 26);
+        assert_eq!(file.read(&mut buf).await.unwrap(), 4);
+        assert_eq!(&buf, b"abcd");
+
+        file.fsync().await.unwrap();
+        let data = vfs.fs.read_file("/file.txt").await.unwrap().unwrap();
+        assert_eq!(data.len(), 1000);
+        assert_eq!(&data[..3], b"abc");
+
+        // A write elsewhere stores the buffer first, and a gap reads as zeros
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        file.write(b"XY").await.unwrap();
+        file.seek(1002, libc::SEEK_SET).await.unwrap();
+        file.write(b"!").await.unwrap();
+        assert_eq!(
+            &vfs.fs.read_file("/file.txt").await.unwrap().unwrap()[..2],
+            b"XY"
+        );
+        file.seek(998, libc::SEEK_SET).await.unwrap();
+        assert_eq!(file.read(&mut buf).await.unwrap(), 4);
+        assert_eq!(&buf, b"kl\0\0");
+        assert_eq!(file.read(&mut buf).await.unwrap(), 1);
+        assert_eq!(buf[0], b'!');
+        file.close().await.unwrap();
+        assert_eq!(vfs.fs.stat("/file.txt").await.unwrap().unwrap().size, 1003);
+    }
+
+    #[tokio::test]
+    async fn test_write_buffer_shared_by_handles() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        let path = Path::new("/agent/file.txt");
+        let writer = vfs
+            .open(path, libc::O_RDWR | libc::O_CREAT, 0o644)
+            .await
+            .unwrap();
+        let reader = vfs.open(path, libc::O_RDWR, 0).await.unwrap();
+
+        // Another handle and a path stat see the buffered writes
+        writer.write(b"hello").await.unwrap();
+        assert!(vfs
+            .fs
+            .read_file("/file.txt")
+            .await
+            .unwrap()
+            .unwrap()
+            .is_empty());
+        let mut buf = [0u8; 8];
+        assert_eq!(reader.read(&mut buf).await.unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(reader.fstat().await.unwrap().st_size, 5);
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 5);
+
+        // Both handles write into the same buffer
+        reader.write(b" world").await.unwrap();
+        assert_eq!(writer.fstat().await.unwrap().st_size, 11);
+
+        // Truncating through another open discards the buffered writes, so
+        // storing them later cannot undo the truncation
+        vfs.open(path, libc::O_WRONLY | libc::O_TRUNC, 0)
+            .await
+            .unwrap()
+            .close()
+            .await
+            .unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 0);
+        writer.close().await.unwrap();
+        reader.close().await.unwrap();
+        assert!(vfs
+            .fs
+            .read_file("/file.txt")
+            .await
+            .unwrap()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_len() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        let path = Path::new("/agent/file.txt");
+        let writer = vfs
+            .open(path, libc::O_RDWR | libc::O_CREAT, 0o644)
+            .await
+            .unwrap();
+        let other = vfs.open(path, libc::O_RDWR, 0).await.unwrap();
+
+        // Buffered writes past the new end are dropped, so storing the
+        // buffer later cannot extend the file again
+        writer.write(b"hello world").await.unwrap();
+        assert_eq!(other.fstat().await.unwrap().st_size, 11);
+        other.set_len(5).await.unwrap();
+        assert_eq!(writer.fstat().await.unwrap().st_size, 5);
+        writer.fsync().await.unwrap();
+        assert_eq!(
+            vfs.fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"hello"
+        );
+
+        // Extending by path leaves zeros and refreshes the cached stats
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 5);
+        vfs.truncate(path, 8).await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 8);
+        writer.close().await.unwrap();
+        other.close().await.unwrap();
+        assert_eq!(
+            vfs.fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"hello\0\0\0"
+        );
+
+        // A file open for reading cannot be resized, and neither can a directory
+        let reader = vfs.open(path, libc::O_RDONLY, 0).await.unwrap();
+        assert!(matches!(
+            reader.set_len(0).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+        let err = vfs.truncate(Path::new("/agent"), 0).await.unwrap_err();
+        assert!(matches!(err, VfsError::IoError(ref e) if e.raw_os_error() == Some(libc::EISDIR)));
+    }
+
+    #[tokio::test]
+    async fn test_quota() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap()
+            .with_quota(10);
+        let file = vfs
+            .open(
+                Path::new("/agent/file.txt"),
+                libc::O_RDWR | libc::O_CREAT,
+                0o644,
+            )
+            .await
+            .unwrap();
+        assert_eq!(file.write(b"hello").await.unwrap(), 5);
+
+        // Only the bytes that fit are written, and the offset follows them
+        assert_eq!(file.write(b" world!").await.unwrap(), 5);
+        assert_eq!(file.seek(0, libc::SEEK_CUR).await.unwrap(), 10);
+        let err = file.write(b"!").await.unwrap_err();
+        assert!(matches!(err, VfsError::IoError(ref e) if e.raw_os_error() == Some(libc::ENOSPC)));
+        assert_eq!(file.seek(0, libc::SEEK_CUR).await.unwrap(), 10);
+
+        // Overwriting needs no room
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        assert_eq!(file.write(b"HELLO").await.unwrap(), 5);
+
+        // Extending the file counts against the quota too
+        let err = file.set_len(11).await.unwrap_err();
+        assert!(matches!(err, VfsError::IoError(ref e) if e.raw_os_error() == Some(libc::ENOSPC)));
+        file.close().await.unwrap();
+        assert_eq!(
+            vfs.fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"HELLO worl"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_through() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap()
+            .with_write_buffer_size(0);
+        let file = vfs
+            .open(
+                Path::new("/agent/file.txt"),
+                libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(b"abc").await.unwrap();
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        file.write(b"def").await.unwrap();
+        assert_eq!(
+            vfs.fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"abcdef"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_large_write_is_one_transaction() {
+        const SIZE: usize = 4 * 1024 * 1024;
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let vfs = SqliteVfs::new(&db_path, PathBuf::from("/agent"))
+            .await
+            .unwrap()
+            .with_busy_timeout(Duration::from_secs(10))
+            .unwrap();
+        let file = vfs
+            .open(
+                Path::new("/agent/big.bin"),
+                libc::O_WRONLY | libc::O_CREAT,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(&vec![0u8; SIZE]).await.unwrap();
+
+        // A reader on its own connection must only ever see whole writes
+        let reader = SqliteVfs::new(&db_path, PathBuf::from("/agent"))
+            .await
+            .unwrap()
+            .with_busy_timeout(Duration::from_secs(10))
+            .unwrap();
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let reader_task = {
+            let done = done.clone();
+            tokio::spawn(async move {
+                let mut reads = 0;
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    let data = reader.fs.read_file("/big.bin").await.unwrap().unwrap();
+                    assert_eq!(data.len(), SIZE, "reader saw a partial write");
+                    assert!(
+                        data.iter().all(|&b| b == data[0]),
+                        "reader saw a partial write"
+                    );
+                    reads += 1;
+                    tokio::task::yield_now().await;
+                }
+                reads
+            })
+        };
+
+        let transactions = vfs.transaction_count();
+        for i in 1..=4u8 {
+            file.seek(0, libc::SEEK_SET).await.unwrap();
+            file.write(&vec![i; SIZE]).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(vfs.transaction_count() - transactions, 4);
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert!(reader_task.await.unwrap() > 0);
+
+        assert_eq!(
+            vfs.fs.read_file("/big.bin").await.unwrap().unwrap(),
+            vec![4u8; SIZE]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_writers_sharing_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        SqliteVfs::new(&db_path, PathBuf::from("/agent"))
+            .await
+            .unwrap();
+
+        // One VFS per writer, each with its own connection to the database
+        let writers: Vec<_> = (0..4)
+            .map(|i| {
+                let db_path = db_path.clone();
+                tokio::spawn(async move {
+                    let vfs = SqliteVfs::new(&db_path, PathBuf::from("/agent"))
+                        .await
+                        .unwrap()
+                        .with_write_buffer_size(0)
+                        .with_busy_timeout(Duration::from_secs(10))
+                        .unwrap();
+                    let path = PathBuf::from(format!("/agent/{}.txt", i));
+                    let file = vfs
+                        .open(&path, libc::O_WRONLY | libc::O_CREAT, 0o644)
+                        .await
+                        .unwrap();
+                    for _ in 0..25 {
+                        file.write(b"data").await.unwrap();
+                    }
+                    file.close().await.unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        let vfs = SqliteVfs::new(&db_path, PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        for i in 0..4 {
+            let data = vfs.fs.read_file(&format!("/{}.txt", i)).await.unwrap();
+            assert_eq!(data.unwrap().len(), 100);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let vfs = SqliteVfs::new(&db_path, PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.write_file("/file.txt", b"hello").await.unwrap();
+        assert!(!vfs.is_read_only());
+
+        let vfs = SqliteVfs::new_read_only(&db_path, PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        assert!(vfs.is_read_only());
+        let is_erofs = |e: VfsError| matches!(e, VfsError::IoError(e) if e.raw_os_error() == Some(libc::EROFS));
+
+        // Reads work
+        let file = vfs
+            .open(Path::new("/agent/file.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert!(vfs
+            .open(Path::new("/agent"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .is_ok());
+
+        // Writes fail with EROFS
+        for flags in [libc::O_WRONLY, libc::O_RDWR, libc::O_RDONLY | libc::O_TRUNC] {
+            let result = vfs.open(Path::new("/agent/file.txt"), flags, 0).await;
+            assert!(is_erofs(result.err().unwrap()));
+        }
+        let result = vfs
+            .open(
+                Path::new("/agent/new.txt"),
+                libc::O_WRONLY | libc::O_CREAT,
+                0o644,
+            )
+            .await;
+        assert!(is_erofs(result.err().unwrap()));
+        let result = vfs
+            .symlink(Path::new("file.txt"), Path::new("/agent/link"))
+            .await;
+        assert!(is_erofs(result.unwrap_err()));
+        let result = vfs
+            .setxattr(Path::new("/agent/file.txt"), "user.key", b"value")
+            .await;
+        assert!(is_erofs(result.unwrap_err()));
+
+        // The connection itself refuses writes
+        assert!(vfs.fs.write_file("/file.txt", b"bye").await.is_err());
+        assert_eq!(
+            vfs.fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"hello"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_directory_seek() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.write_file("/a.txt", b"a").await.unwrap();
+        vfs.fs.write_file("/b.txt", b"b").await.unwrap();
+
+        let dir = vfs
+            .open(Path::new("/agent"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        let names = |entries: Vec<(u64, String, u8)>| -> Vec<String> {
+            entries.into_iter().map(|(_, name, _)| name).collect()
+        };
+        let all = names(dir.getdents().await.unwrap());
+        assert_eq!(all, vec![".", "..", "a.txt", "b.txt"]);
+        assert!(dir.getdents().await.unwrap().is_empty());
+        assert_eq!(dir.seek(0, libc::SEEK_CUR).await.unwrap(), 4);
+
+        // seekdir to a d_off value resumes after that entry
+        assert_eq!(dir.seek(3, libc::SEEK_SET).await.unwrap(), 3);
+        assert_eq!(names(dir.getdents().await.unwrap()), vec!["b.txt"]);
+
+        // Rewinding re-reads the directory, including new entries
+        vfs.fs.write_file("/c.txt", b"c").await.unwrap();
+        assert_eq!(dir.seek(0, libc::SEEK_SET).await.unwrap(), 0);
+        assert_eq!(
+            names(dir.getdents().await.unwrap()),
+            vec![".", "..", "a.txt", "b.txt", "c.txt"]
+        );
+
+        assert!(matches!(
+            dir.seek(-1, libc::SEEK_SET).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            dir.seek(0, libc::SEEK_END).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_reads_and_seeks_on_shared_handle() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        let data: Vec<u8> = (0..500u32).flat_map(|i| i.to_le_bytes()).collect();
+        vfs.fs.write_file("/file.bin", &data).await.unwrap();
+
+        // Duplicated FDs share one handle, as after dup() or fork()
+        let file = vfs
+            .open(Path::new("/agent/file.bin"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let file = file.clone();
+                tokio::spawn(async move {
+                    let mut seen = Vec::new();
+                    let mut buf = [0u8; 4];
+                    loop {
+                        let n = file.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            break seen;
+                        }
+                        assert_eq!(n, 4);
+                        seen.push(u32::from_le_bytes(buf));
+                        // Reads advance the offset by whole records
+                        assert_eq!(file.seek(0, libc::SEEK_CUR).await.unwrap() % 4, 0);
+                    }
+                })
+            })
+            .collect();
+        let mut seen = Vec::new();
+        for reader in readers {
+            seen.extend(reader.await.unwrap());
+        }
+        // Every record is read exactly once
+        seen.sort_unstable();
+        assert_eq!(seen, (0..500).collect::<Vec<_>>());
+
+        // Rewinding while another FD lists the directory is safe
+        let dir = vfs
+            .open(Path::new("/agent"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        let listers: Vec<_> = (0..4)
+            .map(|_| {
+                let dir = dir.clone();
+                tokio::spawn(async move {
+                    for _ in 0..20 {
+                        dir.seek(0, libc::SEEK_SET).await.unwrap();
+                        dir.getdents().await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for lister in listers {
+            lister.await.unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_writes_to_same_inode() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap()
+            .with_write_buffer_size(0);
+        vfs.fs.write_file("/file.bin", &[]).await.unwrap();
+
+        // Separate opens, each with its own offset, overwriting the same region
+        let mut files = Vec::new();
+        for _ in 0..2 {
+            let file = vfs
+                .open(Path::new("/agent/file.bin"), libc::O_WRONLY, 0)
+                .await
+                .unwrap();
+            files.push(file);
         }
+        let writers: Vec<_> = files
+            .into_iter()
+            .zip(1..=2u8)
+            .map(|(file, byte)| {
+                tokio::spawn(async move {
+                    let data = vec![byte; 4096];
+                    for _ in 0..50 {
+                        file.seek(0, libc::SEEK_SET).await.unwrap();
+                        assert_eq!(file.write(&data).await.unwrap(), data.len());
+                    }
+                    file.close().await.unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        // The region holds one whole write, stored once
+        let data = vfs.fs.read_file("/file.bin").await.unwrap().unwrap();
+        assert_eq!(data.len(), 4096);
+        assert!(data.iter().all(|&b| b == data[0]));
+        let stats = vfs.fs.stat("/file.bin").await.unwrap().unwrap();
+        assert_eq!(stats.size, 4096);
+    }
+
+    #[tokio::test]
+    async fn test_tmpfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        let tmpfile_flags = libc::O_TMPFILE | libc::O_RDWR;
+
+        let file = vfs
+            .open(Path::new("/agent"), tmpfile_flags, 0o600)
+            .await
+            .unwrap();
+        file.write(b"hello").await.unwrap();
+        let stat = file.fstat().await.unwrap();
+        assert_eq!(stat.st_nlink, 0);
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFREG);
+        assert!(vfs.fs.readdir("/").await.unwrap().unwrap().is_empty());
+
+        file.fsync().await.unwrap();
+        vfs.link_inode(stat.st_ino, Path::new("/agent/named"))
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        assert_eq!(vfs.fs.read_file("/named").await.unwrap().unwrap(), b"hello");
+        assert!(matches!(
+            vfs.link_inode(stat.st_ino, Path::new("/agent/named")).await,
+            Err(VfsError::AlreadyExists)
+        ));
+
+        // An unlinked file is deleted on close
+        let file = vfs
+            .open(Path::new("/agent"), tmpfile_flags, 0o600)
+            .await
+            .unwrap();
+        let ino = file.fstat().await.unwrap().st_ino as i64;
+        file.close().await.unwrap();
+        assert!(vfs.fs.stat_inode(ino).await.unwrap().is_none());
+
+        assert!(matches!(
+            vfs.open(Path::new("/agent"), libc::O_TMPFILE | libc::O_RDONLY, 0)
+                .await,
+            Err(VfsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            vfs.open(Path::new("/agent/named"), tmpfile_flags, 0).await,
+            Err(VfsError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_open_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.write_file("/file.txt", b"hello").await.unwrap();
+        vfs.fs.symlink("file.txt", "/link").await.unwrap();
+        let errno = |e: VfsError| match e {
+            VfsError::IoError(e) => e.raw_os_error(),
+            _ => None,
+        };
+
+        let file = vfs
+            .open(Path::new("/agent/file.txt"), libc::O_PATH, 0)
+            .await
+            .unwrap();
+        let stat = file.fstat().await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFREG);
+        assert_eq!(stat.st_size, 5);
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            errno(file.read(&mut buf).await.unwrap_err()),
+            Some(libc::EBADF)
+        );
+        assert_eq!(
+            errno(file.write(b"x").await.unwrap_err()),
+            Some(libc::EBADF)
+        );
+        file.close().await.unwrap();
+
+        // The access mode and O_TRUNC are ignored
+        vfs.open(
+            Path::new("/agent/file.txt"),
+            libc::O_PATH | libc::O_WRONLY | libc::O_TRUNC,
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            vfs.fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"hello"
+        );
+
+        // With O_NOFOLLOW, the symlink itself is opened
+        let link = vfs
+            .open(Path::new("/agent/link"), libc::O_PATH | libc::O_NOFOLLOW, 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            link.fstat().await.unwrap().st_mode & libc::S_IFMT,
+            libc::S_IFLNK
+        );
+
+        let root = vfs
+            .open(Path::new("/agent"), libc::O_PATH | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            root.fstat().await.unwrap().st_mode & libc::S_IFMT,
+            libc::S_IFDIR
+        );
+        assert_eq!(errno(root.getdents().await.unwrap_err()), Some(libc::EBADF));
+        let err = vfs
+            .open(
+                Path::new("/agent/file.txt"),
+                libc::O_PATH | libc::O_DIRECTORY,
+                0,
+            )
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(errno(err), Some(libc::ENOTDIR));
+    }
+
+    #[tokio::test]
+    async fn test_chmod_and_chown() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.write_file("/file", b"hello").await.unwrap();
+        vfs.fs.symlink("file", "/link").await.unwrap();
+        let path = Path::new("/agent/file");
+
+        vfs.chmod(path, libc::S_IFDIR | 0o600).await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_mode, libc::S_IFREG | 0o600);
+        vfs.chown(path, 1000, u32::MAX).await.unwrap();
+        let stat = vfs.stat(path).await.unwrap();
+        assert_eq!((stat.st_uid, stat.st_gid), (1000, 0));
+
+        // The symlink itself is changed, and has no permissions to change
+        let link = Path::new("/agent/link");
+        vfs.chown(link, 2000, 2000).await.unwrap();
+        let stat = vfs.lstat(link).await.unwrap();
+        assert_eq!((stat.st_uid, stat.st_gid), (2000, 2000));
+        assert_eq!(vfs.stat(path).await.unwrap().st_uid, 1000);
+        assert!(matches!(
+            vfs.chmod(link, 0o600).await,
+            Err(VfsError::IoError(e)) if e.raw_os_error() == Some(libc::EOPNOTSUPP)
+        ));
+        assert!(matches!(
+            vfs.chmod(Path::new("/agent/missing"), 0o600).await,
+            Err(VfsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stat_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.mkdir("/dir").await.unwrap();
+        vfs.fs.write_file("/dir/file", b"hello").await.unwrap();
+        let path = Path::new("/agent/dir/file");
+
+        // Only the lookups are repeated for an unchanged file
+        let queries = vfs.query_count();
+        vfs.stat(path).await.unwrap();
+        let uncached = vfs.query_count() - queries;
+        let queries = vfs.query_count();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 5);
+        let cached = vfs.query_count() - queries;
+        assert!(cached < uncached, "{cached} >= {uncached}");
+
+        // Changes through the VFS are seen
+        let file = vfs
+            .open(path, libc::O_WRONLY | libc::O_APPEND, 0)
+            .await
+            .unwrap();
+        file.write(b", world").await.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 12);
+        vfs.chmod(path, 0o600).await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_mode, libc::S_IFREG | 0o600);
+        vfs.open(path, libc::O_WRONLY | libc::O_TRUNC, 0)
+            .await
+            .unwrap()
+            .close()
+            .await
+            .unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 0);
+        let tmp = vfs
+            .open(
+                Path::new("/agent/dir"),
+                libc::O_TMPFILE | libc::O_RDWR,
+                0o600,
+            )
+            .await
+            .unwrap();
+        let ino = tmp.fstat().await.unwrap().st_ino;
+        vfs.link_inode(ino, Path::new("/agent/dir/link"))
+            .await
+            .unwrap();
+        vfs.link_inode(ino, Path::new("/agent/dir/other"))
+            .await
+            .unwrap();
+        assert_eq!(
+            vfs.stat(Path::new("/agent/dir/link"))
+                .await
+                .unwrap()
+                .st_nlink,
+            2
+        );
+        vfs.unlink(Path::new("/agent/dir/other")).await.unwrap();
+        assert_eq!(
+            vfs.stat(Path::new("/agent/dir/link"))
+                .await
+                .unwrap()
+                .st_nlink,
+            1
+        );
+        tmp.close().await.unwrap();
+        vfs.unlink(path).await.unwrap();
+        assert!(matches!(vfs.stat(path).await, Err(VfsError::NotFound)));
+
+        // Changes made elsewhere are seen once the cache entry expires
+        vfs.stat(Path::new("/agent/dir/link")).await.unwrap();
+        vfs.fs.write_file("/dir/link", b"elsewhere").await.unwrap();
+        tokio::time::sleep(STAT_CACHE_TTL).await;
+        assert_eq!(
+            vfs.stat(Path::new("/agent/dir/link"))
+                .await
+                .unwrap()
+                .st_size,
+            9
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_times() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        vfs.fs.write_file("/file", b"hello").await.unwrap();
+        vfs.fs.symlink("file", "/link").await.unwrap();
+        let path = Path::new("/agent/file");
+
+        vfs.set_times(path, Some(1_000_000_000), Some(1_000_000_000))
+            .await
+            .unwrap();
+        vfs.set_times(path, None, Some(1_500_000_000))
+            .await
+            .unwrap();
+        let stat = vfs.stat(path).await.unwrap();
+        assert_eq!(
+            (stat.st_atime, stat.st_mtime),
+            (1_000_000_000, 1_500_000_000)
+        );
+
+        // The symlink itself is changed
+        vfs.set_times(Path::new("/agent/link"), Some(0), Some(0))
+            .await
+            .unwrap();
+        assert_eq!(
+            vfs.lstat(Path::new("/agent/link")).await.unwrap().st_mtime,
+            0
+        );
+        assert_eq!(vfs.stat(path).await.unwrap().st_mtime, 1_500_000_000);
+        assert!(matches!(
+            vfs.set_times(Path::new("/agent/missing"), None, Some(0))
+                .await,
+            Err(VfsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sparse_write() {
+        const GIB: i64 = 1 << 30;
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let vfs = SqliteVfs::new(&db_path, PathBuf::from("/agent"))
+            .await
+            .unwrap();
+
+        let file = vfs
+            .open(
+                Path::new("/agent/sparse"),
+                libc::O_CREAT | libc::O_RDWR,
+                0o644,
+            )
+            .await
+            .unwrap();
+        assert_eq!(file.seek(GIB, libc::SEEK_SET).await.unwrap(), GIB);
+        assert_eq!(file.write(b"x").await.unwrap(), 1);
+        file.close().await.unwrap();
+        let stat = vfs.stat(Path::new("/agent/sparse")).await.unwrap();
+        assert_eq!(stat.st_size, GIB + 1);
+
+        // Only the written byte is stored
+        let db = turso::Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+        let mut rows = conn
+            .query(
+                "SELECT COALESCE(SUM(size), 0) FROM fs_data WHERE ino = ?",
+                (stat.st_ino as i64,),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap(), turso::Value::Integer(1));
+
+        let file = vfs
+            .open(Path::new("/agent/sparse"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let mut buf = [0xff; 4];
+        assert_eq!(file.read(&mut buf).await.unwrap(), 4);
+        assert_eq!(buf, [0; 4]);
+        file.seek(GIB - 1, libc::SEEK_SET).await.unwrap();
+        assert_eq!(file.read(&mut buf).await.unwrap(), 2);
+        assert_eq!(&buf[..2], b"\0x");
     }
 }
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::{sync::mpsc, task::JoinHandle};
+use turso::{Builder, Connection, Value};
+
+/// Number of writes that can wait for the background task before syscall
+/// handlers block
+///
+/// Handlers record writes in chunks of at most `MAX_IO_CHUNK` bytes, so this
+/// also bounds the memory held by pending records.
+const WRITE_LOG_QUEUE: usize = 16;
+
+/// A write to a file by a sandboxed process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileWrite {
+    /// Process that wrote
+    pub pid: i32,
+    /// Time of the write, in seconds since the Unix epoch
+    pub ts: i64,
+    /// Sandbox path the file was opened with
+    pub path: PathBuf,
+    /// File offset of the first byte written
+    pub offset: i64,
+    /// Bytes written
+    pub data: Vec<u8>,
+}
+
+/// Log of the bytes sandboxed processes write to files
+///
+/// Like the network log, writes are sent over a channel to a background task
+/// that appends them to the `file_writes` table. The channel is bounded, so a
+/// process writing faster than the log keeps up waits for it instead of
+/// buffering its writes in memory. Replaying the writes in `id` order
+/// reproduces the file contents.
+pub struct WriteLog {
+    tx: mpsc::Sender<FileWrite>,
+}
+
+/// Background task writing records to the `file_writes` table
+pub struct WriteLogWriter {
+    handle: JoinHandle<Result<()>>,
+}
+
+impl WriteLog {
+    /// Open the write log in the database at `path`
+    ///
+    /// The `file_writes` table is created if it does not exist.
+    pub async fn open(path: impl AsRef<Path>) -> Result<(WriteLog, WriteLogWriter)> {
+        let path = path.as_ref().to_str().context("Invalid database path")?;
+        let db = Builder::new_local(path).build().await?;
+        let conn = db.connect()?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS file_writes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                pid INTEGER NOT NULL,
+                ts INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                offset INTEGER NOT NULL,
+                data BLOB NOT NULL
+            )",
+            (),
+        )
+        .await?;
+
+        let (tx, rx) = mpsc::channel(WRITE_LOG_QUEUE);
+        let handle = tokio::spawn(write_records(conn, rx));
+        Ok((WriteLog { tx }, WriteLogWriter { handle }))
+    }
+
+    /// Record that `pid` wrote `data` at `offset` of the file at `path`
+    pub(crate) async fn record(&self, pid: i32, path: &Path, offset: i64, data: &[u8]) {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let record = FileWrite {
+            pid,
+            ts,
+            path: path.to_path_buf(),
+            offset,
+            data: data.to_vec(),
+        };
+        if self.tx.send(record).await.is_err() {
+            tracing::warn!("Write log writer has stopped, dropping record");
+        }
+    }
+}
+
+impl WriteLogWriter {
+    /// Wait for all pending records to be written
+    ///
+    /// Completes once every [`WriteLog`] handle has been dropped.
+    pub async fn finish(self) -> Result<()> {
+        self.handle.await.context("Write log writer panicked")?
+    }
+}
+
+async fn write_records(conn: Connection, mut rx: mpsc::Receiver<FileWrite>) -> Result<()> {
+    while let Some(record) = rx.recv().await {
+        conn.execute(
+            "INSERT INTO file_writes (pid, ts, path, offset, data)
+             VALUES (?, ?, ?, ?, ?)",
+            (
+                Value::Integer(record.pid as i64),
+                Value::Integer(record.ts),
+                Value::Text(record.path.display().to_string()),
+                Value::Integer(record.offset),
+                Value::Blob(record.data),
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_records_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("writes.db");
+
+        let (log, writer) = WriteLog::open(&db_path).await.unwrap();
+        log.record(42, Path::new("/data/file"), 0, b"hello").await;
+        log.record(42, Path::new("/data/file"), 5, b"\0world").await;
+        drop(log);
+        writer.finish().await.unwrap();
+
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+        let mut rows = conn
+            .query(
+                "SELECT pid, path, offset, data FROM file_writes ORDER BY id",
+                (),
+            )
+            .await
+            .unwrap();
+
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap(), Value::Integer(42));
+        assert_eq!(
+            row.get_value(1).unwrap(),
+            Value::Text("/data/file".to_string())
+        );
+        assert_eq!(row.get_value(2).unwrap(), Value::Integer(0));
+        assert_eq!(row.get_value(3).unwrap(), Value::Blob(b"hello".to_vec()));
+
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(2).unwrap(), Value::Integer(5));
+        assert_eq!(row.get_value(3).unwrap(), Value::Blob(b"\0world".to_vec()));
+        assert!(rows.next().await.unwrap().is_none());
+    }
+}
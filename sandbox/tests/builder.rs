@@ -0,0 +1,1022 @@
+#![cfg(target_os = "linux")]
+
+use agentfs_sandbox::{MountConfig, Resource, SandboxBuilder};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Interpreter for tests that reach individual syscalls from Python
+const PYTHON: &str = "/usr/bin/python3";
+
+/// Whether [`PYTHON`] is installed, printing that the calling test is
+/// skipped if it is not
+fn has_python() -> bool {
+    let found = Path::new(PYTHON).exists();
+    if !found {
+        eprintln!("skipping: {} is not installed", PYTHON);
+    }
+    found
+}
+
+/// Mount the database `agent.db` in `dir` at `/agent`
+fn sqlite_mount(dir: &TempDir) -> MountConfig {
+    MountConfig {
+        mount_type: agentfs_sandbox::MountType::Sqlite {
+            src: dir.path().join("agent.db"),
+        },
+        dst: PathBuf::from("/agent"),
+        uid: None,
+        gid: None,
+    }
+}
+
+#[tokio::test]
+async fn test_run_trivial_command() {
+    let status = SandboxBuilder::new("/bin/true").run().await.unwrap();
+    assert!(status.success());
+}
+
+#[tokio::test]
+async fn test_run_reports_exit_code() {
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", "exit 3"])
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(3));
+}
+
+#[tokio::test]
+async fn test_run_with_mount_env_and_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("marker"), "hello").unwrap();
+
+    let mount = MountConfig {
+        mount_type: agentfs_sandbox::MountType::Bind {
+            src: dir.path().to_path_buf(),
+        },
+        dst: PathBuf::from("/data"),
+        uid: None,
+        gid: None,
+    };
+    let status = SandboxBuilder::new("/bin/sh")
+        .args([
+            "-c",
+            "test -f /data/marker && test \"$GREETING\" = hello && test \"$(ulimit -n)\" = 64",
+        ])
+        .mount(mount)
+        .env("GREETING", "hello")
+        .limit(Resource::OpenFiles, 64, 64)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_sandboxes_with_different_mounts_run_together() {
+    let dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+    std::fs::write(dirs[0].path().join("first"), "").unwrap();
+    std::fs::write(dirs[1].path().join("second"), "").unwrap();
+
+    // Both guests mount their own directory at /data and are still running
+    // when the other one writes
+    let run = |dir: &Path, own: &str, other: &str| {
+        let mount = MountConfig {
+            mount_type: agentfs_sandbox::MountType::Bind {
+                src: dir.to_path_buf(),
+            },
+            dst: PathBuf::from("/data"),
+            uid: None,
+            gid: None,
+        };
+        let script = format!(
+            "test -e /data/{own} || exit 1; \
+             test ! -e /data/{other} || exit 2; \
+             echo {own} > /data/written; \
+             sleep 1; \
+             test \"$(cat /data/written)\" = {own} || exit 3; \
+             test ! -e /data/{other} || exit 4"
+        );
+        SandboxBuilder::new("/bin/sh")
+            .args(["-c", script.as_str()])
+            .mount(mount)
+            .run()
+    };
+    let (first, second) = tokio::join!(
+        run(dirs[0].path(), "first", "second"),
+        run(dirs[1].path(), "second", "first"),
+    );
+    assert_eq!(first.unwrap().code(), Some(0));
+    assert_eq!(second.unwrap().code(), Some(0));
+    for (dir, own) in dirs.iter().zip(["first", "second"]) {
+        let written = std::fs::read_to_string(dir.path().join("written")).unwrap();
+        assert_eq!(written.trim(), own);
+    }
+}
+
+#[tokio::test]
+async fn test_net_log_records_connection() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("net.db");
+
+    let status = SandboxBuilder::new("/bin/bash")
+        .args(["-c", &format!("exec 3<>/dev/tcp/127.0.0.1/{}", port)])
+        .net_log(&db_path)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    let db = turso::Builder::new_local(db_path.to_str().unwrap())
+        .build()
+        .await
+        .unwrap();
+    let conn = db.connect().unwrap();
+    let mut rows = conn
+        .query("SELECT family, addr, port, result FROM net_connections", ())
+        .await
+        .unwrap();
+    let row = rows.next().await.unwrap().unwrap();
+    assert_eq!(
+        row.get_value(0).unwrap(),
+        turso::Value::Text("AF_INET".to_string())
+    );
+    assert_eq!(
+        row.get_value(1).unwrap(),
+        turso::Value::Text("127.0.0.1".to_string())
+    );
+    assert_eq!(
+        row.get_value(2).unwrap(),
+        turso::Value::Integer(port as i64)
+    );
+    assert_eq!(row.get_value(3).unwrap(), turso::Value::Integer(0));
+}
+
+#[tokio::test]
+async fn test_allow_connect_refuses_other_destinations() {
+    let allowed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let allowed_port = allowed.local_addr().unwrap().port();
+    let other = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let other_port = other.local_addr().unwrap().port();
+
+    let run = |port: u16| {
+        SandboxBuilder::new("/bin/bash")
+            .args(["-c", &format!("exec 3<>/dev/tcp/127.0.0.1/{}", port)])
+            .allow_connect(format!("127.0.0.1:{}", allowed_port).parse().unwrap())
+            .run()
+    };
+    assert!(run(allowed_port).await.unwrap().success());
+    assert!(!run(other_port).await.unwrap().success());
+}
+
+#[tokio::test]
+async fn test_process_tree_records_children() {
+    let (status, tree) = SandboxBuilder::new("/bin/sh")
+        .args(["-c", "/bin/true; /bin/true; exit 0"])
+        .run_with_process_tree()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    let pids: Vec<i32> = tree.pids().collect();
+    assert_eq!(pids.len(), 3, "unexpected process tree:\n{}", tree);
+    let roots: Vec<i32> = tree.roots().collect();
+    assert_eq!(roots.len(), 1);
+    let shell = roots[0];
+    assert_eq!(tree.command(shell).unwrap()[0], "/bin/sh");
+    assert_eq!(tree.children(shell).len(), 2);
+    for &child in tree.children(shell) {
+        assert_eq!(tree.command(child), Some(&["/bin/true".to_string()][..]));
+    }
+}
+
+#[tokio::test]
+async fn test_run_with_summary() {
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    let (status, _, summary) = SandboxBuilder::new("/bin/sh")
+        .args([
+            "-c",
+            "echo hello > /agent/a; echo hi > /agent/b; echo again >> /agent/a; exit 3",
+        ])
+        .mount(mount)
+        .run_with_summary()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(3));
+
+    // Each redirection opens a file, besides what the shell opens itself
+    assert!(summary.files_opened >= 3, "{:?}", summary);
+    assert_eq!(summary.files_written, 2);
+    assert_eq!(summary.bytes_written.get(Path::new("/agent")), Some(&15));
+    // stdin, stdout and stderr, plus the redirected file
+    assert!(summary.peak_fds >= 4, "{:?}", summary);
+}
+
+#[tokio::test]
+async fn test_process_tree_records_exec_command() {
+    // The shell replaces itself with echo, so the same pid executes twice
+    let (status, tree) = SandboxBuilder::new("/bin/sh")
+        .args(["-c", "exec /bin/echo hello"])
+        .strace(true)
+        .run_with_process_tree()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    let roots: Vec<i32> = tree.roots().collect();
+    assert_eq!(roots.len(), 1, "unexpected process tree:\n{}", tree);
+    assert_eq!(
+        tree.command(roots[0]),
+        Some(&["/bin/echo".to_string(), "hello".to_string()][..])
+    );
+}
+
+#[tokio::test]
+async fn test_open_files_limit_applies_to_virtual_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    // FDs 0-3 are allowed, so a child cannot open anything while FD 3 is
+    // taken by a file that exists only in the database, and the file cannot
+    // be duplicated onto a higher FD
+    let status = SandboxBuilder::new("/bin/sh")
+        .args([
+            "-c",
+            "exec 3>/agent/f || exit 1; \
+             cat /agent/f && exit 2; \
+             (exec 9>&3) 2>/dev/null && exit 4; \
+             exec 3>&-; \
+             cat /agent/f || exit 3",
+        ])
+        .mount(mount)
+        .limit(Resource::OpenFiles, 4, 4)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_readlink_in_sqlite_mount() {
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    // `ln -s` uses symlinkat and `readlink` uses readlinkat
+    let status = SandboxBuilder::new("/bin/sh")
+        .args([
+            "-c",
+            "ln -s ../some/target /agent/link || exit 1; \
+             test \"$(readlink /agent/link)\" = ../some/target || exit 2; \
+             echo data > /agent/file; \
+             readlink /agent/file && exit 3; \
+             exit 0",
+        ])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_xattrs_in_sqlite_mount() {
+    // The xattr syscalls are easiest to reach individually from Python
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    let script = r#"
+import os
+path = "/agent/file"
+open(path, "w").close()
+os.symlink("file", "/agent/link")
+os.setxattr(path, "user.a", b"1")
+assert os.getxattr(path, "user.a") == b"1"
+assert os.getxattr("/agent/link", "user.a") == b"1"
+fd = os.open(path, os.O_RDONLY)
+os.setxattr(fd, "user.b", b"2")
+assert os.getxattr(fd, "user.a") == b"1"
+assert os.getxattr(path, "user.b", follow_symlinks=False) == b"2"
+assert sorted(os.listxattr(fd)) == ["user.a", "user.b"]
+os.removexattr("/agent/link", "user.a")
+os.removexattr(fd, "user.b")
+assert os.listxattr(path) == []
+try:
+    os.getxattr(path, "user.a")
+    raise SystemExit(1)
+except OSError:
+    pass
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_truncate_in_sqlite_mount() {
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    // The writes through the first FD are still buffered when the file is
+    // truncated, and must not come back when that FD is closed
+    let script = r#"
+import os
+path = "/agent/file"
+fd = os.open(path, os.O_RDWR | os.O_CREAT, 0o644)
+os.write(fd, b"hello world")
+other = os.open(path, os.O_RDWR)
+os.ftruncate(other, 5)
+assert os.fstat(fd).st_size == 5
+assert os.pread(fd, 16, 0) == b"hello"
+os.close(fd)
+os.close(other)
+assert os.stat(path).st_size == 5
+os.truncate(path, 8)
+assert os.stat(path).st_size == 8
+with open(path, "rb") as f:
+    assert f.read() == b"hello\0\0\0"
+try:
+    os.truncate("/agent", 0)
+    raise SystemExit(1)
+except IsADirectoryError:
+    pass
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_tmpfile_in_sqlite_mount() {
+    // linkat with AT_EMPTY_PATH is reachable from Python through ctypes
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    let script = r#"
+import ctypes, os
+libc = ctypes.CDLL(None, use_errno=True)
+fd = os.open("/agent", os.O_TMPFILE | os.O_RDWR)
+os.write(fd, b"hello")
+assert os.listdir("/agent") == []
+AT_FDCWD, AT_EMPTY_PATH = -100, 0x1000
+assert libc.linkat(fd, b"", AT_FDCWD, b"/agent/named", AT_EMPTY_PATH) == 0, ctypes.get_errno()
+os.close(fd)
+assert os.listdir("/agent") == ["named"]
+assert open("/agent/named", "rb").read() == b"hello"
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_fstatat_empty_path_in_sqlite_mount() {
+    // newfstatat with AT_EMPTY_PATH is reachable from Python through ctypes
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    let script = r#"
+import ctypes, os, stat
+libc = ctypes.CDLL(None, use_errno=True)
+AT_EMPTY_PATH, SYS_newfstatat = 0x1000, 262
+buf = ctypes.create_string_buffer(256)
+with open("/agent/file", "wb") as f:
+    f.write(b"hello")
+fd = os.open("/agent/file", os.O_RDONLY)
+assert libc.syscall(SYS_newfstatat, fd, b"", buf, AT_EMPTY_PATH) == 0, ctypes.get_errno()
+st_mode = int.from_bytes(buf.raw[24:28], "little")
+st_size = int.from_bytes(buf.raw[48:56], "little")
+assert stat.S_ISREG(st_mode) and st_size == 5
+dirfd = os.open("/agent", os.O_RDONLY | os.O_DIRECTORY)
+assert libc.syscall(SYS_newfstatat, dirfd, b"", buf, AT_EMPTY_PATH) == 0
+assert stat.S_ISDIR(int.from_bytes(buf.raw[24:28], "little"))
+assert os.stat(fd).st_size == 5
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_lseek_in_sqlite_mount() {
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    let script = r#"
+import os
+fd = os.open("/agent/file", os.O_RDWR | os.O_CREAT)
+os.write(fd, b"0123456789")
+assert os.lseek(fd, 2, os.SEEK_SET) == 2
+assert os.read(fd, 2) == b"23"
+assert os.lseek(fd, 3, os.SEEK_CUR) == 7
+assert os.read(fd, 8) == b"789"
+assert os.lseek(fd, -4, os.SEEK_END) == 6
+assert os.read(fd, 8) == b"6789"
+try:
+    os.lseek(fd, -1, os.SEEK_SET)
+    raise SystemExit(1)
+except OSError as e:
+    assert e.errno == 22
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_huge_read_length_in_sqlite_mount() {
+    // A read length far beyond the file must not be allocated up front
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    let script = r#"
+import ctypes, os
+libc = ctypes.CDLL(None, use_errno=True)
+libc.read.argtypes = [ctypes.c_int, ctypes.c_void_p, ctypes.c_size_t]
+libc.read.restype = ctypes.c_ssize_t
+with open("/agent/file", "wb") as f:
+    f.write(b"hello")
+fd = os.open("/agent/file", os.O_RDONLY)
+buf = ctypes.create_string_buffer(16)
+assert libc.read(fd, buf, 1 << 40) == 5, ctypes.get_errno()
+assert buf.raw[:5] == b"hello"
+assert libc.read(fd, buf, 1 << 40) == 0
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_mkfifo_in_bind_mount() {
+    use std::os::unix::fs::FileTypeExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mount = MountConfig {
+        mount_type: agentfs_sandbox::MountType::Bind {
+            src: dir.path().to_path_buf(),
+        },
+        dst: PathBuf::from("/data"),
+        uid: None,
+        gid: None,
+    };
+    // `mkfifo` uses mknodat
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", "mkfifo /data/pipe && test -p /data/pipe"])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+    let metadata = std::fs::symlink_metadata(dir.path().join("pipe")).unwrap();
+    assert!(metadata.file_type().is_fifo());
+}
+
+#[tokio::test]
+async fn test_at_syscalls_relative_to_directory_fd() {
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("old"), "hello").unwrap();
+    std::fs::write(dir.path().join("doomed"), "bye").unwrap();
+    let mount = MountConfig {
+        mount_type: agentfs_sandbox::MountType::Bind {
+            src: dir.path().to_path_buf(),
+        },
+        dst: PathBuf::from("/data"),
+        uid: None,
+        gid: None,
+    };
+    // The directory FD is virtualized, so each call must be given the
+    // kernel FD behind it
+    let script = r#"
+import os
+fd = os.open("/data", os.O_RDONLY | os.O_DIRECTORY)
+os.unlink("doomed", dir_fd=fd)
+os.rename("old", "new", src_dir_fd=fd, dst_dir_fd=fd)
+assert os.access("new", os.R_OK, dir_fd=fd)
+assert not os.access("old", os.F_OK, dir_fd=fd)
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+    assert!(!dir.path().join("doomed").exists());
+    assert!(!dir.path().join("old").exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("new")).unwrap(),
+        "hello"
+    );
+}
+
+#[tokio::test]
+async fn test_unlinkat_relative_to_read_only_directory_fd() {
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("marker"), "hello").unwrap();
+    let mount = format!("type=robind,src={},dst=/data", dir.path().display())
+        .parse::<MountConfig>()
+        .unwrap();
+    let script = r#"
+import errno, os
+fd = os.open("/data", os.O_RDONLY | os.O_DIRECTORY)
+try:
+    os.unlink("marker", dir_fd=fd)
+except OSError as e:
+    assert e.errno == errno.EROFS, e
+else:
+    raise SystemExit(1)
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+    assert!(dir.path().join("marker").exists());
+}
+
+#[tokio::test]
+async fn test_openat_relative_to_directory_fd_in_sqlite_mount() {
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("agent.db");
+    let fs = agentfs_sdk::Filesystem::new(db_path.to_str().unwrap())
+        .await
+        .unwrap();
+    fs.mkdir("/sub").await.unwrap();
+    fs.write_file("/sub/file", b"hello").await.unwrap();
+
+    let mount = sqlite_mount(&dir);
+    // Relative paths are resolved against the path each directory FD was
+    // opened with, including a passthrough directory above the mount
+    let script = r#"
+import os
+agent = os.open("/agent", os.O_RDONLY | os.O_DIRECTORY)
+sub = os.open("sub", os.O_RDONLY | os.O_DIRECTORY, dir_fd=agent)
+root = os.open("/", os.O_RDONLY | os.O_DIRECTORY)
+for dir_fd, path in [(agent, "sub/file"), (sub, "file"), (root, "agent/sub/file")]:
+    fd = os.open(path, os.O_RDONLY, dir_fd=dir_fd)
+    assert os.read(fd, 16) == b"hello", path
+    os.close(fd)
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+}
+
+#[tokio::test]
+async fn test_mknod_in_sqlite_mount() {
+    if !has_python() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let mount = sqlite_mount(&dir);
+    let script = r#"
+import os, stat
+os.mknod("/agent/file", 0o600 | stat.S_IFREG)
+st = os.stat("/agent/file")
+assert stat.S_ISREG(st.st_mode) and st.st_size == 0
+assert stat.S_IMODE(st.st_mode) == 0o600
+os.mkfifo("/agent/pipe")
+assert stat.S_ISFIFO(os.lstat("/agent/pipe").st_mode)
+try:
+    os.mknod("/agent/file", 0o600 | stat.S_IFREG)
+    raise SystemExit(1)
+except FileExistsError:
+    pass
+os.mknod("/agent/socket", 0o600 | stat.S_IFSOCK)
+assert stat.S_ISSOCK(os.lstat("/agent/socket").st_mode)
+os.mknod("/agent/dev", 0o600 | stat.S_IFCHR, os.makedev(1, 3))
+st = os.stat("/agent/dev")
+assert stat.S_ISCHR(st.st_mode) and st.st_rdev == os.makedev(1, 3)
+try:
+    open("/agent/dev", "rb")
+    raise SystemExit(2)
+except PermissionError:
+    pass
+"#;
+    let status = SandboxBuilder::new(PYTHON)
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_sync_on_close() {
+    let dir = tempfile::tempdir().unwrap();
+    let mount = MountConfig {
+        mount_type: agentfs_sandbox::MountType::Bind {
+            src: dir.path().to_path_buf(),
+        },
+        dst: PathBuf::from("/data"),
+        uid: None,
+        gid: None,
+    };
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", "echo result > /data/out"])
+        .mount(mount)
+        .sync_on_close(true)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    // Whether the data reached the disk cannot be observed directly, so
+    // check that the host file is complete and syncs cleanly
+    let file = std::fs::File::open(dir.path().join("out")).unwrap();
+    file.sync_data().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("out")).unwrap(),
+        "result\n"
+    );
+}
+
+#[tokio::test]
+async fn test_file_size_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let mount = MountConfig {
+        mount_type: agentfs_sandbox::MountType::Bind {
+            src: dir.path().to_path_buf(),
+        },
+        dst: PathBuf::from("/data"),
+        uid: None,
+        gid: None,
+    };
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", "head -c 65536 /dev/zero > /data/big"])
+        .mount(mount)
+        .limit(Resource::FileSize, 4096, 4096)
+        .run()
+        .await
+        .unwrap();
+    assert!(!status.success());
+    let size = std::fs::metadata(dir.path().join("big")).unwrap().len();
+    assert!(size <= 4096);
+}
+
+#[tokio::test]
+async fn test_duplicate_mount_destination_is_rejected() {
+    let mounts = [
+        "type=bind,src=/tmp,dst=/agent",
+        "type=bind,src=/var,dst=/agent/",
+    ]
+    .map(|spec| spec.parse::<MountConfig>().unwrap());
+    let err = SandboxBuilder::new("/bin/true")
+        .mounts(mounts)
+        .run()
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "destination /agent is already mounted");
+}
+
+#[tokio::test]
+async fn test_trace_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().join("data");
+    std::fs::create_dir(&data_dir).unwrap();
+    let db_path = dir.path().join("writes.db");
+    let mounts = [
+        MountConfig {
+            mount_type: agentfs_sandbox::MountType::Bind { src: data_dir },
+            dst: PathBuf::from("/data"),
+            uid: None,
+            gid: None,
+        },
+        sqlite_mount(&dir),
+    ];
+
+    let status = SandboxBuilder::new("/bin/sh")
+        .args([
+            "-c",
+            "printf hello > /data/file; printf ' world' >> /data/file; printf abc > /agent/file",
+        ])
+        .mounts(mounts)
+        .trace_writes(&db_path)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    let db = turso::Builder::new_local(db_path.to_str().unwrap())
+        .build()
+        .await
+        .unwrap();
+    let conn = db.connect().unwrap();
+    let mut rows = conn
+        .query("SELECT path, offset, data FROM file_writes ORDER BY id", ())
+        .await
+        .unwrap();
+    let mut writes = Vec::new();
+    while let Some(row) = rows.next().await.unwrap() {
+        let (turso::Value::Text(path), turso::Value::Integer(offset), turso::Value::Blob(data)) = (
+            row.get_value(0).unwrap(),
+            row.get_value(1).unwrap(),
+            row.get_value(2).unwrap(),
+        ) else {
+            panic!("unexpected row");
+        };
+        writes.push((path, offset, data));
+    }
+    assert_eq!(
+        writes,
+        vec![
+            ("/data/file".to_string(), 0, b"hello".to_vec()),
+            ("/data/file".to_string(), 5, b" world".to_vec()),
+            ("/agent/file".to_string(), 0, b"abc".to_vec()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_capture_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("output.db");
+
+    let status = SandboxBuilder::new("/bin/sh")
+        .args([
+            "-c",
+            "echo out1; sleep 0.2; echo err1 >&2; sleep 0.2; echo out2",
+        ])
+        .capture_output(&db_path)
+        .run()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    let db = turso::Builder::new_local(db_path.to_str().unwrap())
+        .build()
+        .await
+        .unwrap();
+    let conn = db.connect().unwrap();
+    let mut rows = conn
+        .query("SELECT stream, data FROM output_log ORDER BY id", ())
+        .await
+        .unwrap();
+    let mut chunks = Vec::new();
+    while let Some(row) = rows.next().await.unwrap() {
+        let stream = match row.get_value(0).unwrap() {
+            turso::Value::Text(stream) => stream,
+            value => panic!("unexpected stream {:?}", value),
+        };
+        let data = match row.get_value(1).unwrap() {
+            turso::Value::Blob(data) => String::from_utf8(data).unwrap(),
+            value => panic!("unexpected data {:?}", value),
+        };
+        chunks.push((stream, data));
+    }
+    let expected = [
+        ("stdout", "out1\n"),
+        ("stderr", "err1\n"),
+        ("stdout", "out2\n"),
+    ];
+    assert_eq!(
+        chunks,
+        expected.map(|(stream, data)| (stream.to_string(), data.to_string()))
+    );
+}
+
+#[tokio::test]
+async fn test_robind_mount_rejects_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("marker"), "hello").unwrap();
+
+    let mount = format!("type=robind,src={},dst=/data", dir.path().display())
+        .parse::<MountConfig>()
+        .unwrap();
+    let script = r#"
+        test "$(cat /data/marker)" = hello || exit 1
+        err=$( { echo world > /data/marker; } 2>&1 ) && exit 2
+        case "$err" in *"Read-only file system"*) ;; *) exit 3 ;; esac
+        err=$( { echo world > /data/new; } 2>&1 ) && exit 4
+        case "$err" in *"Read-only file system"*) ;; *) exit 5 ;; esac
+    "#;
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("marker")).unwrap(),
+        "hello"
+    );
+    assert!(!dir.path().join("new").exists());
+}
+
+#[tokio::test]
+async fn test_rosqlite_mount_rejects_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("agent.db");
+    let fs = agentfs_sdk::Filesystem::new(db_path.to_str().unwrap())
+        .await
+        .unwrap();
+    fs.write_file("/marker", b"hello").await.unwrap();
+
+    let mount = format!("type=rosqlite,src={},dst=/agent", db_path.display())
+        .parse::<MountConfig>()
+        .unwrap();
+    let script = r#"
+        test "$(cat /agent/marker)" = hello || exit 1
+        err=$( { echo world > /agent/marker; } 2>&1 ) && exit 2
+        case "$err" in *"Read-only file system"*) ;; *) exit 3 ;; esac
+        mkdir /agent/dir 2>/dev/null && exit 4
+        exit 0
+    "#;
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+    assert_eq!(fs.read_file("/marker").await.unwrap().unwrap(), b"hello");
+    assert!(fs.stat("/dir").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_bind_mount_uid_gid() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("marker"), "hello").unwrap();
+
+    let mount = format!(
+        "type=bind,src={},dst=/data,uid=1000,gid=1000",
+        dir.path().display()
+    )
+    .parse::<MountConfig>()
+    .unwrap();
+    let script = r#"
+        test "$(stat -c '%u %g' /data/marker)" = "1000 1000" || exit 1
+        test "$(stat -c '%u %g' /data)" = "1000 1000" || exit 2
+    "#;
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_chmod_and_chown_in_sqlite_mount() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("agent.db");
+    let fs = agentfs_sdk::Filesystem::new(db_path.to_str().unwrap())
+        .await
+        .unwrap();
+    fs.write_file("/file", b"hello").await.unwrap();
+    fs.symlink("file", "/link").await.unwrap();
+
+    let mount = sqlite_mount(&dir);
+    let script = r#"
+        chmod 600 /agent/link || exit 1
+        test "$(stat -c '%a' /agent/file)" = "600" || exit 2
+        chown 1000:1000 /agent/file || exit 3
+        test "$(stat -c '%u %g' /agent/file)" = "1000 1000" || exit 4
+        chown -h 2000 /agent/link || exit 5
+        test "$(stat -c '%u' /agent/link)" = "2000" || exit 6
+    "#;
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+
+    let stats = fs.stat("/file").await.unwrap().unwrap();
+    assert_eq!((stats.mode, stats.uid, stats.gid), (0o100600, 1000, 1000));
+}
+
+#[tokio::test]
+async fn test_touch_in_sqlite_mount() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("agent.db");
+    let fs = agentfs_sdk::Filesystem::new(db_path.to_str().unwrap())
+        .await
+        .unwrap();
+    fs.write_file("/file", b"hello").await.unwrap();
+
+    let mount = sqlite_mount(&dir);
+    let script = r#"
+        touch -d @1000000000 /agent/file || exit 1
+        test "$(stat -c '%X %Y' /agent/file)" = "1000000000 1000000000" || exit 2
+        touch -m -d @1500000000 /agent/file || exit 3
+        test "$(stat -c '%X %Y' /agent/file)" = "1000000000 1500000000" || exit 4
+        touch /agent/new || exit 5
+    "#;
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+
+    let stats = fs.stat("/file").await.unwrap().unwrap();
+    assert_eq!((stats.atime, stats.mtime), (1_000_000_000, 1_500_000_000));
+}
+
+#[tokio::test]
+async fn test_sqlite_database_mounted_twice() {
+    let dir = tempfile::tempdir().unwrap();
+    let mounts = ["/agent", "/home/agent"].map(|dst| MountConfig {
+        dst: PathBuf::from(dst),
+        ..sqlite_mount(&dir)
+    });
+    let script = r#"
+        echo hello > /agent/file || exit 1
+        test "$(cat /home/agent/file)" = "hello" || exit 2
+        rm /home/agent/file || exit 3
+        test ! -e /agent/file || exit 4
+    "#;
+    let mut builder = SandboxBuilder::new("/bin/sh").args(["-c", script]);
+    for mount in mounts {
+        builder = builder.mount(mount);
+    }
+    let status = builder.run().await.unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn test_getcwd_in_bind_mount() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    let mount = MountConfig {
+        mount_type: agentfs_sandbox::MountType::Bind {
+            src: dir.path().to_path_buf(),
+        },
+        dst: PathBuf::from("/data"),
+        uid: None,
+        gid: None,
+    };
+    let script = r#"
+        cd /data || exit 1
+        test "$(/bin/pwd -P)" = "/data" || exit 2
+        cd sub || exit 3
+        test "$(/bin/pwd -P)" = "/data/sub" || exit 4
+        cd .. && cd .. || exit 5
+        test "$(/bin/pwd -P)" = "/" || exit 6
+    "#;
+    let status = SandboxBuilder::new("/bin/sh")
+        .args(["-c", script])
+        .mount(mount)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
@@ -1,14 +1,29 @@
+use crate::schema::{self, LazySchema};
+use crate::{write_lock, TransactionGuard, TransactionLock};
 use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::io::SeekFrom;
 use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use turso::{Builder, Connection, Value};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use turso::{Builder, Connection, IntoParams, Rows, Value};
 
 // File types for mode field
 const S_IFMT: u32 = 0o170000; // File type mask
 const S_IFREG: u32 = 0o100000; // Regular file
 const S_IFDIR: u32 = 0o040000; // Directory
 const S_IFLNK: u32 = 0o120000; // Symbolic link
+const S_IFIFO: u32 = 0o010000; // Named pipe
+const S_IFSOCK: u32 = 0o140000; // Socket
+const S_IFCHR: u32 = 0o020000; // Character device
+const S_IFBLK: u32 = 0o060000; // Block device
 
 // Default permissions
 const DEFAULT_FILE_MODE: u32 = S_IFREG | 0o644; // Regular file, rw-r--r--
@@ -16,6 +31,33 @@ const DEFAULT_DIR_MODE: u32 = S_IFDIR | 0o755; // Directory, rwxr-xr-x
 
 const ROOT_INO: i64 = 1;
 
+/// Directory that [`Filesystem::recover_orphans`] links orphan inodes into
+const LOST_FOUND: &str = "/lost+found";
+
+/// Size of the data chunks written by [`FileWriter`]
+const WRITER_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Number of files [`Filesystem::bulk_write`] writes per transaction
+const BULK_WRITE_BATCH: usize = 1000;
+
+/// Number of rows [`Filesystem::bulk_write`] inserts per statement
+const BULK_INSERT_ROWS: usize = 100;
+
+/// Default of [`Filesystem::set_busy_timeout`]
+pub const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait between retries of a write transaction on a locked database
+const MAX_BUSY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Whether an error means another connection holds a lock on the database
+fn is_busy(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<turso::Error>(),
+        Some(turso::Error::SqlExecutionFailure(msg))
+            if msg.contains("database is locked") || msg.contains("Database is busy")
+    )
+}
+
 /// File statistics
 #[derive(Debug, Clone)]
 pub struct Stats {
@@ -28,6 +70,8 @@ pub struct Stats {
     pub atime: i64,
     pub mtime: i64,
     pub ctime: i64,
+    /// Device number of a character or block device, 0 for other files
+    pub rdev: u64,
 }
 
 impl Stats {
@@ -44,10 +88,150 @@ impl Stats {
     }
 }
 
+/// A directory entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub ino: i64,
+    /// File type in the encoding of `d_type` in `getdents64` (e.g. `DT_REG`),
+    /// or 0 if unknown
+    pub d_type: u8,
+}
+
+impl DirEntry {
+    pub fn is_file(&self) -> bool {
+        self.d_type == d_type(S_IFREG)
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.d_type == d_type(S_IFDIR)
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.d_type == d_type(S_IFLNK)
+    }
+}
+
+/// An inconsistency found by [`Filesystem::check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityProblem {
+    /// A directory entry refers to an inode or parent that does not exist
+    DanglingDentry {
+        parent_ino: i64,
+        name: String,
+        ino: i64,
+    },
+    /// Data chunks belong to an inode that does not exist
+    DanglingData { ino: i64, chunks: u64 },
+    /// A symlink target belongs to an inode that does not exist
+    DanglingSymlink { ino: i64 },
+    /// A symlink inode has no target
+    MissingSymlinkTarget { ino: i64 },
+    /// An extended attribute belongs to an inode that does not exist
+    DanglingXattr { ino: i64, name: String },
+    /// Two data chunks of an inode cover the same bytes
+    OverlappingChunks { ino: i64, offset: i64 },
+    /// The data of a regular file extends past its size
+    SizeMismatch { ino: i64, size: i64, data_size: i64 },
+    /// An inode that no directory entry refers to
+    OrphanInode { ino: i64 },
+}
+
+/// What [`Filesystem::recover_orphans`] does with inodes that no directory
+/// entry refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanAction {
+    /// Link them into `/lost+found`, named after their inode number
+    Relink,
+    /// Delete them and their data
+    Delete,
+}
+
+impl fmt::Display for IntegrityProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityProblem::DanglingDentry {
+                parent_ino,
+                name,
+                ino,
+            } => write!(
+                f,
+                "dangling directory entry '{}' in inode {} refers to inode {}",
+                name, parent_ino, ino
+            ),
+            IntegrityProblem::DanglingData { ino, chunks } => {
+                write!(f, "{} data chunks of missing inode {}", chunks, ino)
+            }
+            IntegrityProblem::DanglingSymlink { ino } => {
+                write!(f, "symlink target of missing inode {}", ino)
+            }
+            IntegrityProblem::MissingSymlinkTarget { ino } => {
+                write!(f, "symlink inode {} has no target", ino)
+            }
+            IntegrityProblem::DanglingXattr { ino, name } => {
+                write!(f, "extended attribute '{}' of missing inode {}", name, ino)
+            }
+            IntegrityProblem::OverlappingChunks { ino, offset } => {
+                write!(
+                    f,
+                    "overlapping data chunks in inode {} at offset {}",
+                    ino, offset
+                )
+            }
+            IntegrityProblem::SizeMismatch {
+                ino,
+                size,
+                data_size,
+            } => write!(
+                f,
+                "inode {} has size {} but {} bytes of data",
+                ino, size, data_size
+            ),
+            IntegrityProblem::OrphanInode { ino } => {
+                write!(f, "inode {} is not in any directory", ino)
+            }
+        }
+    }
+}
+
+/// Directory entry type for an inode mode, as stored in `fs_dentry.d_type`
+///
+/// The file type bits of the mode shifted down, which is how Linux defines
+/// the `DT_*` constants.
+const fn d_type(mode: u32) -> u8 {
+    ((mode & S_IFMT) >> 12) as u8
+}
+
+/// Tables and indexes created by [`Filesystem::initialize`]
+const SCHEMA_OBJECTS: &[&str] = &[
+    "fs_inode",
+    "fs_dentry",
+    "idx_fs_dentry_parent",
+    "fs_data",
+    "idx_fs_data_ino_offset",
+    "fs_symlink",
+    "fs_xattr",
+];
+
 /// A filesystem backed by SQLite
 #[derive(Clone)]
 pub struct Filesystem {
     conn: Arc<Connection>,
+    schema: LazySchema,
+    /// Number of statements executed, shared by clones
+    query_count: Arc<AtomicU64>,
+    /// Number of write transactions committed, shared by clones
+    transaction_count: Arc<AtomicU64>,
+    /// How long to retry a write transaction while the database is locked,
+    /// in milliseconds, shared by clones
+    busy_timeout_ms: Arc<AtomicU64>,
+    /// Lock taken before every write, shared by clones and by the other
+    /// stores of an [`AgentFS`](crate::AgentFS)
+    transaction_lock: TransactionLock,
+    /// Whether this is a sub-store of an
+    /// [`AgentFS::transaction`](crate::AgentFS::transaction), whose
+    /// operations run in the transaction it opened
+    in_transaction: bool,
 }
 
 impl Filesystem {
@@ -55,98 +239,240 @@ impl Filesystem {
     pub async fn new(db_path: &str) -> Result<Self> {
         let db = Builder::new_local(db_path).build().await?;
         let conn = db.connect()?;
-        let fs = Self {
-            conn: Arc::new(conn),
-        };
-        fs.initialize().await?;
-        Ok(fs)
+        Self::from_connection(Arc::new(conn)).await
+    }
+
+    /// Open an existing filesystem that cannot be modified
+    ///
+    /// The connection runs in SQLite's `query_only` mode, so every statement
+    /// that would write to the database fails, whatever code issues it. The
+    /// database must already contain a filesystem.
+    pub async fn open_read_only(db_path: &str) -> Result<Self> {
+        if !Path::new(db_path).exists() {
+            anyhow::bail!("Database does not exist");
+        }
+        let db = Builder::new_local(db_path).build().await?;
+        let conn = db.connect()?;
+        conn.execute("PRAGMA query_only = 1", ()).await?;
+        Self::from_connection(Arc::new(conn)).await
     }
 
     /// Create a filesystem from an existing connection
+    ///
+    /// The connection's busy timeout is set to [`DEFAULT_BUSY_TIMEOUT`], so
+    /// reads wait out another connection's commit or checkpoint instead of
+    /// failing with "database is locked".
     pub async fn from_connection(conn: Arc<Connection>) -> Result<Self> {
-        let fs = Self { conn };
-        fs.initialize().await?;
+        conn.busy_timeout(DEFAULT_BUSY_TIMEOUT)?;
+        let fs = Self::from_connection_lazy(conn);
+        fs.ensure_schema().await?;
         Ok(fs)
     }
 
+    /// Create a filesystem from an existing connection without initializing the schema
+    ///
+    /// The schema is created on first use.
+    pub(crate) fn from_connection_lazy(conn: Arc<Connection>) -> Self {
+        Self {
+            conn,
+            schema: LazySchema::default(),
+            query_count: Arc::new(AtomicU64::new(0)),
+            transaction_count: Arc::new(AtomicU64::new(0)),
+            busy_timeout_ms: Arc::new(AtomicU64::new(DEFAULT_BUSY_TIMEOUT.as_millis() as u64)),
+            transaction_lock: TransactionLock::default(),
+            in_transaction: false,
+        }
+    }
+
+    /// Take `transaction_lock` before every write
+    pub(crate) fn with_transaction_lock(self, transaction_lock: TransactionLock) -> Self {
+        Self {
+            transaction_lock,
+            ..self
+        }
+    }
+
+    /// Get a clone whose operations run in the transaction open on the connection
+    pub(crate) fn in_transaction(&self) -> Self {
+        Self {
+            in_transaction: true,
+            ..self.clone()
+        }
+    }
+
+    /// Set how long writes wait for other connections to release the database
+    ///
+    /// Statements wait up to `timeout` for a lock, and a write transaction
+    /// that still finds the database locked is retried with backoff for the
+    /// same time. Zero fails immediately. Defaults to
+    /// [`DEFAULT_BUSY_TIMEOUT`].
+    pub fn set_busy_timeout(&self, timeout: Duration) -> Result<()> {
+        self.conn.busy_timeout(timeout)?;
+        self.busy_timeout_ms
+            .store(timeout.as_millis() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// How long writes wait for other connections to release the database
+    pub fn busy_timeout(&self) -> Duration {
+        Duration::from_millis(self.busy_timeout_ms.load(Ordering::Relaxed))
+    }
+
+    /// Number of SQL statements this filesystem and its clones have executed
+    ///
+    /// Counts the statements of filesystem operations, not schema setup or
+    /// the statements of [`FileReader`] and [`FileWriter`]. Tests and
+    /// benchmarks use the difference between two readings to check how many
+    /// queries an operation needs.
+    pub fn query_count(&self) -> u64 {
+        self.query_count.load(Ordering::Relaxed)
+    }
+
+    /// Number of write transactions this filesystem and its clones have
+    /// committed
+    ///
+    /// An operation that joins an enclosing transaction is not counted
+    /// separately. Tests use this to check that an operation stores its
+    /// changes in a single transaction.
+    pub fn transaction_count(&self) -> u64 {
+        self.transaction_count.load(Ordering::Relaxed)
+    }
+
+    /// Run a query, counting it in [`Filesystem::query_count`]
+    async fn query(&self, sql: &str, params: impl IntoParams) -> Result<Rows> {
+        self.query_count.fetch_add(1, Ordering::Relaxed);
+        Ok(self.conn.query(sql, params).await?)
+    }
+
+    /// Execute a statement, counting it in [`Filesystem::query_count`]
+    async fn execute(&self, sql: &str, params: impl IntoParams) -> Result<u64> {
+        self.query_count.fetch_add(1, Ordering::Relaxed);
+        Ok(self.conn.execute(sql, params).await?)
+    }
+
+    /// Flush all changes to the database file and close this filesystem's connection
+    ///
+    /// The connection stays open while other handles share it.
+    pub async fn close(self) -> Result<()> {
+        crate::close_connection(self.conn).await
+    }
+
+    /// Initialize the database schema unless already done
+    pub(crate) async fn ensure_schema(&self) -> Result<()> {
+        self.schema.get_or_init(|| self.initialize()).await
+    }
+
     /// Initialize the database schema
     async fn initialize(&self) -> Result<()> {
-        // Create inode table
-        self.conn
-            .execute(
-                "CREATE TABLE IF NOT EXISTS fs_inode (
-                    ino INTEGER PRIMARY KEY AUTOINCREMENT,
-                    mode INTEGER NOT NULL,
-                    uid INTEGER NOT NULL DEFAULT 0,
-                    gid INTEGER NOT NULL DEFAULT 0,
-                    size INTEGER NOT NULL DEFAULT 0,
-                    atime INTEGER NOT NULL,
-                    mtime INTEGER NOT NULL,
-                    ctime INTEGER NOT NULL
-                )",
-                (),
+        if !schema::exists(&self.conn, SCHEMA_OBJECTS).await? {
+            self.create_tables().await?;
+        }
+
+        // Databases created before directory entries cached the file type
+        if !schema::has_column(&self.conn, "fs_dentry", "d_type").await? {
+            schema::execute_ddl(
+                &self.conn,
+                "ALTER TABLE fs_dentry ADD COLUMN d_type INTEGER",
             )
             .await?;
+        }
 
-        // Create directory entry table
-        self.conn
-            .execute(
-                "CREATE TABLE IF NOT EXISTS fs_dentry (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    name TEXT NOT NULL,
-                    parent_ino INTEGER NOT NULL,
-                    ino INTEGER NOT NULL,
-                    UNIQUE(parent_ino, name)
-                )",
-                (),
+        // Databases created before device nodes could be stored
+        if !schema::has_column(&self.conn, "fs_inode", "rdev").await? {
+            schema::execute_ddl(
+                &self.conn,
+                "ALTER TABLE fs_inode ADD COLUMN rdev INTEGER NOT NULL DEFAULT 0",
             )
             .await?;
+        }
+
+        // Ensure root directory exists
+        self.ensure_root().await
+    }
+
+    /// Create the tables and indexes that do not exist yet
+    async fn create_tables(&self) -> Result<()> {
+        // Create inode table
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE TABLE IF NOT EXISTS fs_inode (
+                ino INTEGER PRIMARY KEY AUTOINCREMENT,
+                mode INTEGER NOT NULL,
+                uid INTEGER NOT NULL DEFAULT 0,
+                gid INTEGER NOT NULL DEFAULT 0,
+                size INTEGER NOT NULL DEFAULT 0,
+                atime INTEGER NOT NULL,
+                mtime INTEGER NOT NULL,
+                ctime INTEGER NOT NULL,
+                rdev INTEGER NOT NULL DEFAULT 0
+            )",
+        )
+        .await?;
+
+        // Create directory entry table
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE TABLE IF NOT EXISTS fs_dentry (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                parent_ino INTEGER NOT NULL,
+                ino INTEGER NOT NULL,
+                d_type INTEGER,
+                UNIQUE(parent_ino, name)
+            )",
+        )
+        .await?;
 
         // Create index for efficient path lookups
-        self.conn
-            .execute(
-                "CREATE INDEX IF NOT EXISTS idx_fs_dentry_parent
-                ON fs_dentry(parent_ino, name)",
-                (),
-            )
-            .await?;
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE INDEX IF NOT EXISTS idx_fs_dentry_parent
+            ON fs_dentry(parent_ino, name)",
+        )
+        .await?;
 
         // Create data blocks table
-        self.conn
-            .execute(
-                "CREATE TABLE IF NOT EXISTS fs_data (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    ino INTEGER NOT NULL,
-                    offset INTEGER NOT NULL,
-                    size INTEGER NOT NULL,
-                    data BLOB NOT NULL
-                )",
-                (),
-            )
-            .await?;
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE TABLE IF NOT EXISTS fs_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ino INTEGER NOT NULL,
+                offset INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                data BLOB NOT NULL
+            )",
+        )
+        .await?;
 
         // Create index for efficient data block lookups
-        self.conn
-            .execute(
-                "CREATE INDEX IF NOT EXISTS idx_fs_data_ino_offset
-                ON fs_data(ino, offset)",
-                (),
-            )
-            .await?;
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE INDEX IF NOT EXISTS idx_fs_data_ino_offset
+            ON fs_data(ino, offset)",
+        )
+        .await?;
 
         // Create symlink table
-        self.conn
-            .execute(
-                "CREATE TABLE IF NOT EXISTS fs_symlink (
-                    ino INTEGER PRIMARY KEY,
-                    target TEXT NOT NULL
-                )",
-                (),
-            )
-            .await?;
-
-        // Ensure root directory exists
-        self.ensure_root().await?;
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE TABLE IF NOT EXISTS fs_symlink (
+                ino INTEGER PRIMARY KEY,
+                target TEXT NOT NULL
+            )",
+        )
+        .await?;
+
+        // Create extended attribute table
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE TABLE IF NOT EXISTS fs_xattr (
+                ino INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                value BLOB NOT NULL,
+                PRIMARY KEY (ino, name)
+            )",
+        )
+        .await?;
 
         Ok(())
     }
@@ -154,19 +480,17 @@ impl Filesystem {
     /// Ensure root directory exists
     async fn ensure_root(&self) -> Result<()> {
         let mut rows = self
-            .conn
             .query("SELECT ino FROM fs_inode WHERE ino = ?", (ROOT_INO,))
             .await?;
 
         if rows.next().await?.is_none() {
             let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-            self.conn
-                .execute(
-                    "INSERT INTO fs_inode (ino, mode, uid, gid, size, atime, mtime, ctime)
+            self.execute(
+                "INSERT INTO fs_inode (ino, mode, uid, gid, size, atime, mtime, ctime)
                     VALUES (?, ?, 0, 0, 0, ?, ?, ?)",
-                    (ROOT_INO, DEFAULT_DIR_MODE as i64, now, now, now),
-                )
-                .await?;
+                (ROOT_INO, DEFAULT_DIR_MODE as i64, now, now, now),
+            )
+            .await?;
         }
 
         Ok(())
@@ -228,7 +552,6 @@ impl Filesystem {
     /// Get link count for an inode
     async fn get_link_count(&self, ino: i64) -> Result<u32> {
         let mut rows = self
-            .conn
             .query(
                 "SELECT COUNT(*) as count FROM fs_dentry WHERE ino = ?",
                 (ino,),
@@ -250,10 +573,18 @@ impl Filesystem {
     /// Build a Stats object from a database row
     ///
     /// The row should contain columns in this order:
-    /// ino, mode, uid, gid, size, atime, mtime, ctime
+    /// ino, mode, uid, gid, size, atime, mtime, ctime, rdev
     async fn build_stats_from_row(&self, row: &turso::Row, ino: i64) -> Result<Stats> {
         let nlink = self.get_link_count(ino).await?;
-        Ok(Stats {
+        Ok(Self::stats_from_row(row, ino, nlink))
+    }
+
+    /// Build a Stats object from a database row and a known link count
+    ///
+    /// The row should contain the same columns as for
+    /// [`Filesystem::build_stats_from_row`].
+    fn stats_from_row(row: &turso::Row, ino: i64, nlink: u32) -> Stats {
+        Stats {
             ino,
             mode: row
                 .get_value(1)
@@ -291,7 +622,12 @@ impl Filesystem {
                 .ok()
                 .and_then(|v| v.as_integer().copied())
                 .unwrap_or(0),
-        })
+            rdev: row
+                .get_value(8)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u64,
+        }
     }
 
     /// Resolve a path to an inode number
@@ -304,7 +640,6 @@ impl Filesystem {
         let mut current_ino = ROOT_INO;
         for component in components {
             let mut rows = self
-                .conn
                 .query(
                     "SELECT ino FROM fs_dentry WHERE parent_ino = ? AND name = ?",
                     (current_ino, component.as_str()),
@@ -325,18 +660,67 @@ impl Filesystem {
         Ok(Some(current_ino))
     }
 
+    /// Look up the inode number of a path without following symlinks
+    ///
+    /// Returns None if nothing exists at the path. Together with
+    /// [`Filesystem::stat_inode`], this lets callers cache stats by inode.
+    pub async fn lookup(&self, path: &str) -> Result<Option<i64>> {
+        self.ensure_schema().await?;
+        let path = self.normalize_path(path);
+        self.resolve_path(&path).await
+    }
+
+    /// Check whether anything exists at a path
+    ///
+    /// Symlinks are not followed, so a dangling symlink exists. This only
+    /// looks up the directory entries, without reading the inode.
+    pub async fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.lookup(path).await?.is_some())
+    }
+
+    /// Check whether a path is a directory, following symlinks
+    ///
+    /// Returns false if nothing exists at the path.
+    pub async fn is_dir(&self, path: &str) -> Result<bool> {
+        Ok(self
+            .stat(path)
+            .await?
+            .is_some_and(|stats| stats.is_directory()))
+    }
+
+    /// Check whether a path is a regular file, following symlinks
+    ///
+    /// Returns false if nothing exists at the path.
+    pub async fn is_file(&self, path: &str) -> Result<bool> {
+        Ok(self.stat(path).await?.is_some_and(|stats| stats.is_file()))
+    }
+
     /// Get file statistics without following symlinks
     pub async fn lstat(&self, path: &str) -> Result<Option<Stats>> {
+        self.ensure_schema().await?;
         let path = self.normalize_path(path);
         let ino = match self.resolve_path(&path).await? {
             Some(ino) => ino,
             None => return Ok(None),
         };
+        self.inode_stats(ino).await
+    }
+
+    /// Get file statistics of an inode
+    ///
+    /// Unlike [`Filesystem::lstat`], this also works for inodes that no
+    /// directory entry refers to, such as ones from
+    /// [`Filesystem::create_unlinked`]. Returns None if the inode does not
+    /// exist.
+    pub async fn stat_inode(&self, ino: i64) -> Result<Option<Stats>> {
+        self.ensure_schema().await?;
+        self.inode_stats(ino).await
+    }
 
+    async fn inode_stats(&self, ino: i64) -> Result<Option<Stats>> {
         let mut rows = self
-            .conn
             .query(
-                "SELECT ino, mode, uid, gid, size, atime, mtime, ctime FROM fs_inode WHERE ino = ?",
+                "SELECT ino, mode, uid, gid, size, atime, mtime, ctime, rdev FROM fs_inode WHERE ino = ?",
                 (ino,),
             )
             .await?;
@@ -357,6 +741,7 @@ impl Filesystem {
 
     /// Get file statistics, following symlinks
     pub async fn stat(&self, path: &str) -> Result<Option<Stats>> {
+        self.ensure_schema().await?;
         let path = self.normalize_path(path);
 
         // Follow symlinks with a maximum depth to prevent infinite loops
@@ -370,9 +755,8 @@ impl Filesystem {
             };
 
             let mut rows = self
-                .conn
                 .query(
-                    "SELECT ino, mode, uid, gid, size, atime, mtime, ctime FROM fs_inode WHERE ino = ?",
+                    "SELECT ino, mode, uid, gid, size, atime, mtime, ctime, rdev FROM fs_inode WHERE ino = ?",
                     (ino,),
                 )
                 .await?;
@@ -424,8 +808,80 @@ impl Filesystem {
         anyhow::bail!("Too many levels of symbolic links")
     }
 
+    /// Get file statistics of several paths, following symlinks
+    ///
+    /// Equivalent to calling [`Filesystem::stat`] on each path, but fetches
+    /// the inodes and their link counts in one query each instead of two
+    /// queries per path. Results are in the same order as `paths`, with None
+    /// for paths that do not exist.
+    pub async fn stat_many(&self, paths: &[String]) -> Result<Vec<Option<Stats>>> {
+        self.ensure_schema().await?;
+
+        let mut inos = Vec::with_capacity(paths.len());
+        for path in paths {
+            let path = self.normalize_path(path);
+            inos.push(self.resolve_path(&path).await?);
+        }
+
+        let mut unique: Vec<i64> = inos.iter().flatten().copied().collect();
+        unique.sort_unstable();
+        unique.dedup();
+        if unique.is_empty() {
+            return Ok(vec![None; paths.len()]);
+        }
+        let placeholders = vec!["?"; unique.len()].join(", ");
+
+        let mut nlinks = HashMap::new();
+        let mut rows = self
+            .query(
+                &format!(
+                    "SELECT ino, COUNT(*) FROM fs_dentry WHERE ino IN ({}) GROUP BY ino",
+                    placeholders
+                ),
+                unique.clone(),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            nlinks.insert(integer_column(&row, 0), integer_column(&row, 1) as u32);
+        }
+
+        let mut stats = HashMap::new();
+        let mut rows = self
+            .query(
+                &format!(
+                    "SELECT ino, mode, uid, gid, size, atime, mtime, ctime, rdev FROM fs_inode
+                     WHERE ino IN ({})",
+                    placeholders
+                ),
+                unique,
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let ino = integer_column(&row, 0);
+            let nlink = nlinks.get(&ino).copied().unwrap_or(0);
+            stats.insert(ino, Self::stats_from_row(&row, ino, nlink));
+        }
+
+        let mut results = Vec::with_capacity(paths.len());
+        for (path, ino) in paths.iter().zip(inos) {
+            let result = match ino.and_then(|ino| stats.get(&ino)) {
+                // Symlinks are rare enough to follow one path at a time
+                Some(st) if st.is_symlink() => self.stat(path).await?,
+                Some(st) => Some(st.clone()),
+                None => None,
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
     /// Create a directory
     pub async fn mkdir(&self, path: &str) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.mkdir_in_tx(path)).await
+    }
+
+    async fn mkdir_in_tx(&self, path: &str) -> Result<()> {
         let path = self.normalize_path(path);
         let components = self.split_path(&path);
 
@@ -453,15 +909,14 @@ impl Filesystem {
 
         // Create inode
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-        self.conn
-            .execute(
-                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
+        self.execute(
+            "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
                 VALUES (?, 0, 0, 0, ?, ?, ?)",
-                (DEFAULT_DIR_MODE as i64, now, now, now),
-            )
-            .await?;
+            (DEFAULT_DIR_MODE as i64, now, now, now),
+        )
+        .await?;
 
-        let mut rows = self.conn.query("SELECT last_insert_rowid()", ()).await?;
+        let mut rows = self.query("SELECT last_insert_rowid()", ()).await?;
         let ino = if let Some(row) = rows.next().await? {
             row.get_value(0)
                 .ok()
@@ -472,23 +927,64 @@ impl Filesystem {
         };
 
         // Create directory entry
-        self.conn
-            .execute(
-                "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
-                (name.as_str(), parent_ino, ino),
-            )
-            .await?;
+        self.execute(
+            "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES (?, ?, ?, ?)",
+            (name.as_str(), parent_ino, ino, d_type(DEFAULT_DIR_MODE)),
+        )
+        .await?;
 
         Ok(())
     }
 
-    /// Write data to a file
-    pub async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
+    /// Create a directory and any missing parent directories, like `mkdir -p`
+    ///
+    /// Succeeds if the directory already exists, and fails if the path or
+    /// one of its parents exists but is not a directory. As with the parent
+    /// directories of other operations, symlinks are not followed. The
+    /// directories are created in a single transaction.
+    pub async fn mkdir_all(&self, path: &str) -> Result<()> {
+        self.ensure_schema().await?;
+        let path = self.normalize_path(path);
+        self.atomically(|| self.mkdir_all_in_tx(&path)).await
+    }
+
+    async fn mkdir_all_in_tx(&self, path: &str) -> Result<()> {
+        let mut current = String::new();
+        for component in self.split_path(path) {
+            current.push('/');
+            current.push_str(&component);
+            match self.lstat(&current).await? {
+                Some(stats) if stats.is_directory() => {}
+                Some(_) => anyhow::bail!("'{}' is not a directory", current),
+                None => self.mkdir_in_tx(&current).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a special file or an empty regular file
+    ///
+    /// `mode` holds the file type and permission bits like in `mknod(2)`, and
+    /// a mode without a file type creates a regular file. Named pipes,
+    /// sockets, and character and block devices are stored as inodes of
+    /// that type, with the device number `dev` kept for devices only.
+    pub async fn mknod(&self, path: &str, mode: u32, dev: u64) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.mknod_in_tx(path, mode, dev)).await
+    }
+
+    async fn mknod_in_tx(&self, path: &str, mode: u32, dev: u64) -> Result<()> {
+        let (mode, rdev) = match mode & S_IFMT {
+            0 => (S_IFREG | (mode & 0o7777), 0),
+            S_IFREG | S_IFIFO | S_IFSOCK => (mode & (S_IFMT | 0o7777), 0),
+            S_IFCHR | S_IFBLK => (mode & (S_IFMT | 0o7777), dev),
+            _ => anyhow::bail!("Unsupported file type"),
+        };
         let path = self.normalize_path(path);
         let components = self.split_path(&path);
 
         if components.is_empty() {
-            anyhow::bail!("Cannot write to root directory");
+            anyhow::bail!("Path already exists");
         }
 
         let parent_path = if components.len() == 1 {
@@ -504,294 +1000,499 @@ impl Filesystem {
 
         let name = components.last().unwrap();
 
-        // Check if file exists
-        let ino = if let Some(ino) = self.resolve_path(&path).await? {
-            // Delete existing data
-            self.conn
-                .execute("DELETE FROM fs_data WHERE ino = ?", (ino,))
-                .await?;
-            ino
-        } else {
-            // Create new inode
-            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-            self.conn
-                .execute(
-                    "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
-                    VALUES (?, 0, 0, ?, ?, ?, ?)",
-                    (DEFAULT_FILE_MODE as i64, data.len() as i64, now, now, now),
-                )
-                .await?;
-
-            let mut rows = self.conn.query("SELECT last_insert_rowid()", ()).await?;
-            let ino = if let Some(row) = rows.next().await? {
-                row.get_value(0)
-                    .ok()
-                    .and_then(|v| v.as_integer().copied())
-                    .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?
-            } else {
-                anyhow::bail!("Failed to get inode");
-            };
-
-            // Create directory entry
-            self.conn
-                .execute(
-                    "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
-                    (name.as_str(), parent_ino, ino),
-                )
-                .await?;
-
-            ino
-        };
-
-        // Write data
-        if !data.is_empty() {
-            self.conn
-                .execute(
-                    "INSERT INTO fs_data (ino, offset, size, data) VALUES (?, 0, ?, ?)",
-                    (ino, data.len() as i64, data),
-                )
-                .await?;
+        if (self.resolve_path(&path).await?).is_some() {
+            anyhow::bail!("Path already exists");
         }
 
-        // Update size and mtime
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-        self.conn
-            .execute(
-                "UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?",
-                (data.len() as i64, now, ino),
-            )
-            .await?;
+        self.execute(
+            "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime, rdev)
+                VALUES (?, 0, 0, 0, ?, ?, ?, ?)",
+            (mode as i64, now, now, now, rdev as i64),
+        )
+        .await?;
+
+        let mut rows = self.query("SELECT last_insert_rowid()", ()).await?;
+        let ino = if let Some(row) = rows.next().await? {
+            row.get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?
+        } else {
+            anyhow::bail!("Failed to get inode");
+        };
+
+        self.execute(
+            "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES (?, ?, ?, ?)",
+            (name.as_str(), parent_ino, ino, d_type(mode)),
+        )
+        .await?;
 
         Ok(())
     }
 
-    /// Read data from a file
-    pub async fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
-        let ino = match self.resolve_path(path).await? {
-            Some(ino) => ino,
-            None => return Ok(None),
-        };
+    /// Create a named pipe with permission bits `mode`, like `mkfifo(3)`
+    pub async fn mkfifo(&self, path: &str, mode: u32) -> Result<()> {
+        self.mknod(path, S_IFIFO | (mode & 0o7777), 0).await
+    }
 
-        let mut rows = self
-            .conn
-            .query(
-                "SELECT data FROM fs_data WHERE ino = ? ORDER BY offset",
-                (ino,),
-            )
-            .await?;
+    /// Write data to a file
+    ///
+    /// The whole write runs in a single transaction, so readers on other
+    /// connections see either the previous contents or the new contents,
+    /// never a partially written file. Inside [`crate::AgentFS::transaction`]
+    /// the write joins the enclosing transaction instead.
+    pub async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.write_file_in_tx(path, data)).await
+    }
 
-        let mut data = Vec::new();
-        while let Some(row) = rows.next().await? {
-            if let Ok(Value::Blob(chunk)) = row.get_value(0) {
-                data.extend_from_slice(&chunk);
+    /// Write many files, creating missing parent directories
+    ///
+    /// The files are written in transactions of up to a thousand files
+    /// rather than one transaction per file, and new files are inserted
+    /// with a few multi-row statements, which makes seeding a filesystem
+    /// with many small files much faster. Existing files are overwritten
+    /// like with [`Filesystem::write_file`], and a later entry for the same
+    /// path wins. If a write fails, the files of earlier transactions stay
+    /// written.
+    pub async fn bulk_write(
+        &self,
+        entries: impl IntoIterator<Item = (String, Vec<u8>)>,
+    ) -> Result<()> {
+        self.ensure_schema().await?;
+        let mut entries = entries.into_iter();
+        loop {
+            let batch: Vec<_> = entries.by_ref().take(BULK_WRITE_BATCH).collect();
+            if batch.is_empty() {
+                return Ok(());
             }
+            self.atomically(|| self.bulk_write_in_tx(&batch)).await?;
         }
-
-        Ok(Some(data))
     }
 
-    /// List directory contents
-    pub async fn readdir(&self, path: &str) -> Result<Option<Vec<String>>> {
-        let ino = match self.resolve_path(path).await? {
-            Some(ino) => ino,
-            None => return Ok(None),
-        };
+    async fn bulk_write_in_tx(&self, batch: &[(String, Vec<u8>)]) -> Result<()> {
+        // Parent directories by path, with the names they already contain
+        let mut parents: HashMap<String, (i64, HashSet<String>)> = HashMap::new();
+        // Files to create, with their position by parent and name, so that
+        // a later entry for the same path replaces an earlier one
+        let mut new_files: Vec<(i64, String, &[u8])> = Vec::new();
+        let mut new_index: HashMap<(i64, String), usize> = HashMap::new();
+
+        for (path, data) in batch {
+            let path = self.normalize_path(path);
+            let Some((parent, name)) = path.rsplit_once('/').filter(|(_, name)| !name.is_empty())
+            else {
+                anyhow::bail!("Cannot write to root directory");
+            };
+            let parent = if parent.is_empty() { "/" } else { parent };
+            if !parents.contains_key(parent) {
+                self.mkdir_all_in_tx(parent).await?;
+                let parent_ino = self
+                    .resolve_path(parent)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+                let mut names = HashSet::new();
+                let mut rows = self
+                    .query(
+                        "SELECT name FROM fs_dentry WHERE parent_ino = ?",
+                        (parent_ino,),
+                    )
+                    .await?;
+                while let Some(row) = rows.next().await? {
+                    if let Ok(Value::Text(name)) = row.get_value(0) {
+                        names.insert(name);
+                    }
+                }
+                parents.insert(parent.to_string(), (parent_ino, names));
+            }
+            let (parent_ino, names) = &parents[parent];
+
+            let key = (*parent_ino, name.to_string());
+            if let Some(&index) = new_index.get(&key) {
+                new_files[index].2 = data;
+            } else if names.contains(name) {
+                // Existing entries are overwritten one at a time
+                self.write_file_in_tx(&path, data).await?;
+            } else {
+                new_index.insert(key, new_files.len());
+                new_files.push((*parent_ino, name.to_string(), data));
+            }
+        }
 
-        let mut rows = self
-            .conn
-            .query(
-                "SELECT name FROM fs_dentry WHERE parent_ino = ? ORDER BY name",
-                (ino,),
+        // Insert the new inodes, dentries and data with one statement per
+        // table and chunk of files, rather than several per file
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        for chunk in new_files.chunks(BULK_INSERT_ROWS) {
+            let mut params = Vec::with_capacity(chunk.len() * 5);
+            for (_, _, data) in chunk {
+                params.extend([
+                    Value::Integer(DEFAULT_FILE_MODE as i64),
+                    Value::Integer(data.len() as i64),
+                    Value::Integer(now),
+                    Value::Integer(now),
+                    Value::Integer(now),
+                ]);
+            }
+            let mut rows = self
+                .query(
+                    &format!(
+                        "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
+                            VALUES {} RETURNING ino",
+                        vec!["(?, 0, 0, ?, ?, ?, ?)"; chunk.len()].join(", ")
+                    ),
+                    params,
+                )
+                .await?;
+            let mut inos = Vec::with_capacity(chunk.len());
+            while let Some(row) = rows.next().await? {
+                inos.push(integer_column(&row, 0));
+            }
+            if inos.len() != chunk.len() {
+                anyhow::bail!("Failed to get inode");
+            }
+            // Rows are inserted in order, so the inode numbers ascend with them
+            inos.sort_unstable();
+
+            let mut params = Vec::with_capacity(chunk.len() * 4);
+            for ((parent_ino, name, _), ino) in chunk.iter().zip(&inos) {
+                params.extend([
+                    Value::Text(name.clone()),
+                    Value::Integer(*parent_ino),
+                    Value::Integer(*ino),
+                    Value::Integer(d_type(DEFAULT_FILE_MODE) as i64),
+                ]);
+            }
+            self.execute(
+                &format!(
+                    "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES {}",
+                    vec!["(?, ?, ?, ?)"; chunk.len()].join(", ")
+                ),
+                params,
             )
             .await?;
 
-        let mut entries = Vec::new();
-        while let Some(row) = rows.next().await? {
-            let name = row
-                .get_value(0)
-                .ok()
-                .and_then(|v| {
-                    if let Value::Text(s) = v {
-                        Some(s.clone())
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or_default();
-            if !name.is_empty() {
-                entries.push(name);
+            let mut params = Vec::new();
+            for ((_, _, data), ino) in chunk.iter().zip(&inos) {
+                if !data.is_empty() {
+                    params.extend([
+                        Value::Integer(*ino),
+                        Value::Integer(data.len() as i64),
+                        Value::Blob(data.to_vec()),
+                    ]);
+                }
+            }
+            if !params.is_empty() {
+                self.execute(
+                    &format!(
+                        "INSERT INTO fs_data (ino, offset, size, data) VALUES {}",
+                        vec!["(?, 0, ?, ?)"; params.len() / 3].join(", ")
+                    ),
+                    params,
+                )
+                .await?;
             }
         }
-
-        Ok(Some(entries))
+        Ok(())
     }
 
-    /// Create a symbolic link
-    pub async fn symlink(&self, target: &str, linkpath: &str) -> Result<()> {
-        let linkpath = self.normalize_path(linkpath);
-        let components = self.split_path(&linkpath);
-
-        if components.is_empty() {
-            anyhow::bail!("Cannot create symlink at root");
-        }
+    /// Write data to a new file and rename it over `path`
+    ///
+    /// Unlike [`Filesystem::write_file`], which rewrites the existing inode,
+    /// this writes the data to a new inode under a temporary name and
+    /// renames it into place, all in one transaction. Readers see either
+    /// the old file or the new one, and the replaced inode is deleted if
+    /// this was its last link. The new file keeps the mode and owner of
+    /// the file it replaces. A symlink at `path` is replaced, not followed.
+    pub async fn write_file_atomic(&self, path: &str, data: &[u8]) -> Result<()> {
+        self.ensure_schema().await?;
+        let path = self.normalize_path(path);
+        self.atomically(|| self.write_file_atomic_in_tx(&path, data))
+            .await
+    }
 
-        // Get parent directory
-        let parent_path = if components.len() == 1 {
-            "/".to_string()
-        } else {
-            format!("/{}", components[..components.len() - 1].join("/"))
+    async fn write_file_atomic_in_tx(&self, path: &str, data: &[u8]) -> Result<()> {
+        let mut components = self.split_path(path);
+        let name = match components.pop() {
+            Some(name) => name,
+            None => anyhow::bail!("Cannot write to root directory"),
         };
 
-        let parent_ino = self
-            .resolve_path(&parent_path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        // Pick a temporary name next to the destination that is not taken
+        let mut tmp_path;
+        let mut attempt = 0;
+        loop {
+            components.push(format!(".{}.tmp{}", name, attempt));
+            tmp_path = format!("/{}", components.join("/"));
+            components.pop();
+            if self.resolve_path(&tmp_path).await?.is_none() {
+                break;
+            }
+            attempt += 1;
+        }
 
-        let name = components.last().unwrap();
+        self.write_file_in_tx(&tmp_path, data).await?;
 
-        // Check if entry already exists
-        if (self.resolve_path(&linkpath).await?).is_some() {
-            anyhow::bail!("Path already exists");
+        let existing = match self.resolve_path(path).await? {
+            Some(ino) => self.inode_stats(ino).await?,
+            None => None,
+        };
+        if let Some(existing) = existing.filter(|stats| stats.is_file()) {
+            let ino = self
+                .resolve_path(&tmp_path)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?;
+            self.execute(
+                "UPDATE fs_inode SET mode = ?, uid = ?, gid = ? WHERE ino = ?",
+                (existing.mode, existing.uid, existing.gid, ino),
+            )
+            .await?;
         }
 
-        // Create inode for symlink
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        self.rename_in_tx(&tmp_path, path, false).await
+    }
 
-        let mode = S_IFLNK | 0o777; // Symlinks typically have 777 permissions
-        let size = target.len() as i64;
+    /// Truncate or extend a file to `len` bytes
+    ///
+    /// Only the chunks past `len` are dropped or split. Extending the file
+    /// stores no data: the new range is a hole that reads back as zeros.
+    /// Symlinks are followed.
+    pub async fn truncate(&self, path: &str, len: u64) -> Result<()> {
+        self.ensure_schema().await?;
+        let stats = self
+            .stat(path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("File does not exist"))?;
+        if stats.is_directory() {
+            anyhow::bail!("Is a directory");
+        }
+        let len = i64::try_from(len).map_err(|_| anyhow::anyhow!("File too large"))?;
+        self.atomically(|| self.set_len_in_tx(stats.ino, len)).await
+    }
 
-        self.conn
-            .execute(
-                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
-                 VALUES (?, 0, 0, ?, ?, ?, ?)",
-                (mode, size, now, now, now),
-            )
-            .await?;
+    /// Change the permission bits of a file
+    ///
+    /// Only the permission bits of `mode` are used, so the file type is kept.
+    /// Symlinks are followed.
+    pub async fn chmod(&self, path: &str, mode: u32) -> Result<()> {
+        self.ensure_schema().await?;
+        let stats = self
+            .stat(path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("File does not exist"))?;
+        self.chmod_inode(stats.ino, mode).await
+    }
 
-        // Get the newly created inode
-        let mut rows = self.conn.query("SELECT last_insert_rowid()", ()).await?;
+    /// Change the permission bits of an inode, keeping its file type
+    pub async fn chmod_inode(&self, ino: i64, mode: u32) -> Result<()> {
+        self.atomically(|| self.chmod_inode_in_tx(ino, mode)).await
+    }
 
-        let ino = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0)
-        } else {
-            anyhow::bail!("Failed to get new inode");
-        };
+    async fn chmod_inode_in_tx(&self, ino: i64, mode: u32) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.execute(
+            "UPDATE fs_inode SET mode = (mode & ?) | ?, ctime = ? WHERE ino = ?",
+            (S_IFMT as i64, (mode & 0o7777) as i64, now, ino),
+        )
+        .await?;
+        Ok(())
+    }
 
-        // Store symlink target
-        self.conn
-            .execute(
-                "INSERT INTO fs_symlink (ino, target) VALUES (?, ?)",
-                (ino, target),
-            )
-            .await?;
+    /// Change the permission bits of a tree
+    ///
+    /// Directories get `dir_mode` and every other entry gets `file_mode`,
+    /// except symlinks, which are left alone. Like [`Filesystem::chmod`],
+    /// `path` itself is followed if it is a symlink, but symlinks inside the
+    /// tree are not. The whole tree is changed in a single transaction.
+    pub async fn chmod_recursive(&self, path: &str, dir_mode: u32, file_mode: u32) -> Result<()> {
+        self.ensure_schema().await?;
+        let stats = self
+            .stat(path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("File does not exist"))?;
+        self.atomically(|| self.chmod_recursive_in_tx(&stats, dir_mode, file_mode))
+            .await
+    }
 
-        // Create directory entry
-        self.conn
-            .execute(
-                "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
-                (name.as_str(), parent_ino, ino),
-            )
-            .await?;
+    async fn chmod_recursive_in_tx(
+        &self,
+        root: &Stats,
+        dir_mode: u32,
+        file_mode: u32,
+    ) -> Result<()> {
+        let mut seen = HashSet::from([root.ino]);
+        let mut pending = vec![(root.ino, root.mode)];
+        while let Some((ino, mode)) = pending.pop() {
+            if (mode & S_IFMT) == S_IFLNK {
+                continue;
+            }
+            if (mode & S_IFMT) != S_IFDIR {
+                self.chmod_inode_in_tx(ino, file_mode).await?;
+                continue;
+            }
+            self.chmod_inode_in_tx(ino, dir_mode).await?;
 
+            let mut rows = self
+                .query(
+                    "SELECT d.ino, i.mode FROM fs_dentry d
+                        JOIN fs_inode i ON i.ino = d.ino
+                        WHERE d.parent_ino = ?",
+                    (ino,),
+                )
+                .await?;
+            while let Some(row) = rows.next().await? {
+                let child = integer_column(&row, 0);
+                // Hard links to the same file only need to be changed once
+                if seen.insert(child) {
+                    pending.push((child, integer_column(&row, 1) as u32));
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Read the target of a symbolic link
-    pub async fn readlink(&self, path: &str) -> Result<Option<String>> {
-        let path = self.normalize_path(path);
+    /// Change the owner and group of a file
+    ///
+    /// Like with chown(2), a `uid` or `gid` of `u32::MAX` (`-1`) leaves it
+    /// unchanged. Symlinks are followed.
+    pub async fn chown(&self, path: &str, uid: u32, gid: u32) -> Result<()> {
+        self.ensure_schema().await?;
+        let stats = self
+            .stat(path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("File does not exist"))?;
+        self.chown_inode(stats.ino, uid, gid).await
+    }
 
-        let ino = match self.resolve_path(&path).await? {
-            Some(ino) => ino,
-            None => return Ok(None),
+    /// Change the owner and group of an inode, leaving those that are
+    /// `u32::MAX` unchanged
+    pub async fn chown_inode(&self, ino: i64, uid: u32, gid: u32) -> Result<()> {
+        let id = |id: u32| {
+            if id == u32::MAX {
+                Value::Null
+            } else {
+                Value::Integer(id as i64)
+            }
         };
-
-        // Check if it's a symlink by querying the inode
-        let mut rows = self
-            .conn
-            .query("SELECT mode FROM fs_inode WHERE ino = ?", (ino,))
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.atomically(|| async {
+            self.execute(
+                "UPDATE fs_inode SET uid = COALESCE(?, uid), gid = COALESCE(?, gid), ctime = ?
+                 WHERE ino = ?",
+                (id(uid), id(gid), Value::Integer(now), Value::Integer(ino)),
+            )
             .await?;
+            Ok(())
+        })
+        .await
+    }
 
-        if let Some(row) = rows.next().await? {
-            let mode = row
-                .get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32;
+    /// Set the access and modification times of a file
+    ///
+    /// Times are in seconds since the Unix epoch. A time of `None` is left
+    /// unchanged, like `UTIME_OMIT` with utimensat(2), and the change time is
+    /// updated unless both are. Symlinks are followed.
+    pub async fn set_times(
+        &self,
+        path: &str,
+        atime: Option<i64>,
+        mtime: Option<i64>,
+    ) -> Result<()> {
+        self.ensure_schema().await?;
+        let stats = self
+            .stat(path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("File does not exist"))?;
+        self.set_times_inode(stats.ino, atime, mtime).await
+    }
 
-            // Check if it's a symlink
-            if (mode & S_IFMT) != S_IFLNK {
-                anyhow::bail!("Not a symbolic link");
-            }
-        } else {
-            return Ok(None);
+    /// Set the access and modification times of an inode, leaving those that
+    /// are `None` unchanged
+    pub async fn set_times_inode(
+        &self,
+        ino: i64,
+        atime: Option<i64>,
+        mtime: Option<i64>,
+    ) -> Result<()> {
+        if atime.is_none() && mtime.is_none() {
+            return Ok(());
         }
-
-        // Read target from fs_symlink table
-        let mut rows = self
-            .conn
-            .query("SELECT target FROM fs_symlink WHERE ino = ?", (ino,))
+        let time = |time: Option<i64>| time.map_or(Value::Null, Value::Integer);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.atomically(|| async {
+            self.execute(
+                "UPDATE fs_inode SET atime = COALESCE(?, atime), mtime = COALESCE(?, mtime),
+                 ctime = ? WHERE ino = ?",
+                (
+                    time(atime),
+                    time(mtime),
+                    Value::Integer(now),
+                    Value::Integer(ino),
+                ),
+            )
             .await?;
+            Ok(())
+        })
+        .await
+    }
 
-        if let Some(row) = rows.next().await? {
-            let target = row
-                .get_value(0)
-                .ok()
-                .and_then(|v| match v {
-                    Value::Text(s) => Some(s.to_string()),
-                    _ => None,
-                })
-                .ok_or_else(|| anyhow::anyhow!("Invalid symlink target"))?;
-            Ok(Some(target))
-        } else {
-            Ok(None)
+    /// Run `op` in a write transaction, joining the enclosing transaction if
+    /// there is one
+    ///
+    /// A transaction that another task started through an
+    /// [`AgentFS`](crate::AgentFS) on the same connection is waited for
+    /// rather than joined. While another connection holds the write lock, the
+    /// transaction is rolled back and retried with exponential backoff until
+    /// the busy timeout runs out.
+    async fn atomically<T, Fut>(&self, op: impl Fn() -> Fut) -> Result<T>
+    where
+        Fut: Future<Output = Result<T>>,
+    {
+        let Some(lock) = write_lock(&self.transaction_lock, self.in_transaction).await else {
+            return op().await;
+        };
+        // Join a transaction the caller started on the connection
+        if !self.conn.is_autocommit()? {
+            return op().await;
+        }
+        let _guard = TransactionGuard::new(self.conn.clone(), lock);
+        let deadline = Instant::now() + self.busy_timeout();
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            let result = match self.execute("BEGIN IMMEDIATE", ()).await {
+                Ok(_) => match op().await {
+                    Ok(value) => self.execute("COMMIT", ()).await.map(|_| {
+                        self.transaction_count.fetch_add(1, Ordering::Relaxed);
+                        value
+                    }),
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            let e = match result {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            if !self.conn.is_autocommit()? {
+                let _ = self.execute("ROLLBACK", ()).await;
+            }
+            let now = Instant::now();
+            if !is_busy(&e) || now >= deadline {
+                return Err(e);
+            }
+            tokio::time::sleep(backoff.min(deadline - now)).await;
+            backoff = (backoff * 2).min(MAX_BUSY_BACKOFF);
         }
     }
 
-    /// Remove a file or empty directory
-    pub async fn remove(&self, path: &str) -> Result<()> {
+    async fn write_file_in_tx(&self, path: &str, data: &[u8]) -> Result<()> {
         let path = self.normalize_path(path);
         let components = self.split_path(&path);
 
         if components.is_empty() {
-            anyhow::bail!("Cannot remove root directory");
-        }
-
-        let ino = self
-            .resolve_path(&path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
-
-        if ino == ROOT_INO {
-            anyhow::bail!("Cannot remove root directory");
-        }
-
-        // Check if directory is empty
-        let mut rows = self
-            .conn
-            .query(
-                "SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?",
-                (ino,),
-            )
-            .await?;
-
-        if let Some(row) = rows.next().await? {
-            let count = row
-                .get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0);
-            if count > 0 {
-                anyhow::bail!("Directory not empty");
-            }
+            anyhow::bail!("Cannot write to root directory");
         }
 
-        // Get parent directory and name
         let parent_path = if components.len() == 1 {
             "/".to_string()
         } else {
@@ -805,34 +1506,2015 @@ impl Filesystem {
 
         let name = components.last().unwrap();
 
-        // Delete the specific directory entry (not all entries pointing to this inode)
-        self.conn
-            .execute(
-                "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?",
-                (parent_ino, name.as_str()),
+        // Check if file exists
+        let ino = if let Some(ino) = self.resolve_path(&path).await? {
+            // Delete existing data
+            self.execute("DELETE FROM fs_data WHERE ino = ?", (ino,))
+                .await?;
+            ino
+        } else {
+            // Create new inode
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+            self.execute(
+                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
+                    VALUES (?, 0, 0, ?, ?, ?, ?)",
+                (DEFAULT_FILE_MODE as i64, data.len() as i64, now, now, now),
             )
             .await?;
 
-        // Check if this was the last link to the inode
-        let link_count = self.get_link_count(ino).await?;
-        if link_count == 0 {
-            // Manually handle cascading deletes since we don't use foreign keys
-            // Delete data blocks
-            self.conn
-                .execute("DELETE FROM fs_data WHERE ino = ?", (ino,))
-                .await?;
+            let mut rows = self.query("SELECT last_insert_rowid()", ()).await?;
+            let ino = if let Some(row) = rows.next().await? {
+                row.get_value(0)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?
+            } else {
+                anyhow::bail!("Failed to get inode");
+            };
 
-            // Delete symlink if exists
-            self.conn
-                .execute("DELETE FROM fs_symlink WHERE ino = ?", (ino,))
-                .await?;
+            // Create directory entry
+            self.execute(
+                "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES (?, ?, ?, ?)",
+                (name.as_str(), parent_ino, ino, d_type(DEFAULT_FILE_MODE)),
+            )
+            .await?;
 
-            // Delete inode
-            self.conn
-                .execute("DELETE FROM fs_inode WHERE ino = ?", (ino,))
-                .await?;
+            ino
+        };
+
+        // Write data
+        if !data.is_empty() {
+            self.execute(
+                "INSERT INTO fs_data (ino, offset, size, data) VALUES (?, 0, ?, ?)",
+                (ino, data.len() as i64, data),
+            )
+            .await?;
         }
 
-        Ok(())
+        // Update size and mtime
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.execute(
+            "UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?",
+            (data.len() as i64, now, ino),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Read data from a file
+    ///
+    /// Holes in a regular file, such as the range added by extending it with
+    /// [`Filesystem::truncate`], read as zeros. The inode and its data are
+    /// read in one statement, so a concurrent write is seen entirely or not
+    /// at all.
+    pub async fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        self.ensure_schema().await?;
+        let mut resolved = None;
+        loop {
+            let ino = match self.resolve_path(path).await? {
+                Some(ino) => ino,
+                None => return Ok(None),
+            };
+            if resolved == Some(ino) {
+                // A dentry without an inode reads as an empty file
+                return Ok(Some(Vec::new()));
+            }
+            resolved = Some(ino);
+
+            let mut rows = self
+                .query(
+                    "SELECT i.mode, i.size, d.offset, d.data FROM fs_inode i
+                        LEFT JOIN fs_data d ON d.ino = i.ino
+                        WHERE i.ino = ? ORDER BY d.offset",
+                    (ino,),
+                )
+                .await?;
+
+            let mut size = None;
+            let mut data = Vec::new();
+            while let Some(row) = rows.next().await? {
+                if size.is_none() {
+                    let is_file = integer_column(&row, 0) as u32 & S_IFMT == S_IFREG;
+                    size = Some(if is_file { integer_column(&row, 1) } else { 0 });
+                }
+                if let Ok(Value::Blob(chunk)) = row.get_value(3) {
+                    let offset = integer_column(&row, 2) as usize;
+                    if data.len() < offset {
+                        data.resize(offset, 0);
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+            }
+            // Without an inode, the file was replaced after resolving its
+            // path, so resolve it again
+            let Some(size) = size else { continue };
+            if data.len() < size as usize {
+                data.resize(size as usize, 0);
+            }
+            return Ok(Some(data));
+        }
+    }
+
+    /// Size up to which an inode reads as zeros past its last chunk
+    ///
+    /// This is the size of a regular file and zero for other inodes, such as
+    /// symlinks, whose size is not the length of their data.
+    async fn hole_fill_size(&self, ino: i64) -> Result<i64> {
+        let mut rows = self
+            .query("SELECT mode, size FROM fs_inode WHERE ino = ?", (ino,))
+            .await?;
+        let Some(row) = rows.next().await? else {
+            return Ok(0);
+        };
+        if integer_column(&row, 0) as u32 & S_IFMT == S_IFREG {
+            Ok(integer_column(&row, 1))
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Open a file for streaming reads
+    ///
+    /// Unlike [`Filesystem::read_file`], the reader fetches one data chunk
+    /// at a time in offset order, so files larger than memory can be read.
+    /// Holes read as zeros like with [`Filesystem::read_file`]. Returns None
+    /// if the file does not exist.
+    pub async fn open_reader(&self, path: &str) -> Result<Option<FileReader>> {
+        self.ensure_schema().await?;
+        let ino = match self.resolve_path(path).await? {
+            Some(ino) => ino,
+            None => return Ok(None),
+        };
+        let size = self.hole_fill_size(ino).await?;
+        Ok(Some(FileReader {
+            conn: self.conn.clone(),
+            ino,
+            size,
+            offset: 0,
+            hole_end: 0,
+            next_offset: 0,
+            chunk: Vec::new(),
+            pos: 0,
+            fetch: None,
+            eof: false,
+        }))
+    }
+
+    /// SHA-256 hash of a file's contents
+    ///
+    /// The data is streamed through the hasher one chunk at a time, so
+    /// comparing hashes is cheaper than reading both files into memory.
+    /// Returns None if the file does not exist.
+    pub async fn content_hash(&self, path: &str) -> Result<Option<[u8; 32]>> {
+        let Some(mut reader) = self.open_reader(path).await? else {
+            return Ok(None);
+        };
+        if let Some(stats) = self.inode_stats(reader.ino).await? {
+            if stats.is_directory() {
+                anyhow::bail!("Is a directory");
+            }
+        }
+
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; WRITER_CHUNK_SIZE];
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(Some(hasher.finalize().into()))
+    }
+
+    /// Create or truncate a file and open it for streaming writes
+    ///
+    /// Writes are buffered and stored in chunks as the buffer fills, so the
+    /// whole file never has to be held in memory. Unlike
+    /// [`Filesystem::write_file`], the write is not atomic: readers can see
+    /// the chunks written so far. Call `shutdown` (or `flush`) to store the
+    /// remaining buffered data.
+    pub async fn open_writer(&self, path: &str) -> Result<FileWriter> {
+        self.write_file(path, &[]).await?;
+        let ino = self
+            .resolve_path(path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
+        Ok(FileWriter {
+            conn: self.conn.clone(),
+            transaction_lock: self.transaction_lock.clone(),
+            in_transaction: self.in_transaction,
+            ino,
+            offset: 0,
+            buf: Vec::with_capacity(WRITER_CHUNK_SIZE),
+            store: None,
+        })
+    }
+
+    /// Open a file with the given options
+    ///
+    /// The returned [`FsFile`] reads and writes at a position, like
+    /// `std::fs::File`, storing only the chunks a write touches. Symlinks
+    /// are followed.
+    pub async fn open(&self, path: &str, opts: OpenOptions) -> Result<FsFile> {
+        self.ensure_schema().await?;
+        let write = opts.write || opts.append;
+        if (opts.create || opts.truncate) && !write {
+            anyhow::bail!("Creating or truncating a file requires write access");
+        }
+
+        let stats = match self.stat(path).await? {
+            Some(stats) => stats,
+            None if opts.create => {
+                self.write_file(path, &[]).await?;
+                self.stat(path)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?
+            }
+            None => anyhow::bail!("File does not exist"),
+        };
+        if stats.is_directory() {
+            anyhow::bail!("Is a directory");
+        }
+        if opts.truncate && stats.size > 0 {
+            self.atomically(|| self.set_len_in_tx(stats.ino, 0)).await?;
+        }
+
+        Ok(FsFile {
+            fs: self.clone(),
+            ino: stats.ino,
+            pos: 0,
+            read: opts.read,
+            write,
+            append: opts.append,
+        })
+    }
+
+    /// Get the size of an inode
+    async fn inode_size(&self, ino: i64) -> Result<i64> {
+        let mut rows = self
+            .query("SELECT size FROM fs_inode WHERE ino = ?", (ino,))
+            .await?;
+        let row = rows
+            .next()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Inode does not exist"))?;
+        row.get_value(0)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .ok_or_else(|| anyhow::anyhow!("Invalid inode size"))
+    }
+
+    /// Get the chunks of an inode that overlap `[start, end)`, in offset order
+    async fn chunks_in_range(&self, ino: i64, start: i64, end: i64) -> Result<Vec<Chunk>> {
+        let mut rows = self
+            .query(
+                "SELECT offset, data FROM fs_data WHERE ino = ? AND offset < ? AND offset + size > ?
+                ORDER BY offset",
+                (ino, end, start),
+            )
+            .await?;
+        let mut chunks = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let offset = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .ok_or_else(|| anyhow::anyhow!("Invalid chunk offset"))?;
+            let data = match row.get_value(1) {
+                Ok(Value::Blob(data)) => data,
+                _ => Vec::new(),
+            };
+            chunks.push((offset, data));
+        }
+        Ok(chunks)
+    }
+
+    /// Insert a chunk unless it is empty
+    async fn insert_chunk(&self, ino: i64, offset: i64, data: &[u8]) -> Result<()> {
+        if !data.is_empty() {
+            self.execute(
+                "INSERT INTO fs_data (ino, offset, size, data) VALUES (?, ?, ?, ?)",
+                (ino, offset, data.len() as i64, data),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Replace the chunks that overlap `[start, end)` with the parts of them
+    /// outside of the range
+    async fn punch_range(&self, ino: i64, start: i64, end: i64) -> Result<()> {
+        for (offset, data) in self.chunks_in_range(ino, start, end).await? {
+            self.execute(
+                "DELETE FROM fs_data WHERE ino = ? AND offset = ?",
+                (ino, offset),
+            )
+            .await?;
+            let chunk_end = offset + data.len() as i64;
+            if offset < start {
+                self.insert_chunk(ino, offset, &data[..(start - offset) as usize])
+                    .await?;
+            }
+            if chunk_end > end {
+                self.insert_chunk(ino, end, &data[(end - offset) as usize..])
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Update the size and modification time of an inode
+    async fn set_size(&self, ino: i64, size: i64) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.execute(
+            "UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?",
+            (size, now, ino),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Read up to `buf.len()` bytes of an inode starting at `offset`
+    async fn read_at(&self, ino: i64, offset: i64, buf: &mut [u8]) -> Result<usize> {
+        let size = self.inode_size(ino).await?;
+        let end = size.min(offset + buf.len() as i64);
+        if end <= offset {
+            return Ok(0);
+        }
+        let n = (end - offset) as usize;
+        // Holes that no chunk covers read as zeros
+        buf[..n].fill(0);
+        for (chunk_offset, data) in self.chunks_in_range(ino, offset, end).await? {
+            let from = offset.max(chunk_offset);
+            let to = end.min(chunk_offset + data.len() as i64);
+            buf[(from - offset) as usize..(to - offset) as usize].copy_from_slice(
+                &data[(from - chunk_offset) as usize..(to - chunk_offset) as usize],
+            );
+        }
+        Ok(n)
+    }
+
+    /// Write `data` to an inode at `offset`, or at the end of the file if
+    /// `offset` is `None`, returning the offset after the written data
+    ///
+    /// Only `data` is stored: a gap between the end of the file and `offset`
+    /// is left as a hole that reads back as zeros, and the size grows to the
+    /// end of the write.
+    async fn write_at_in_tx(&self, ino: i64, offset: Option<i64>, data: &[u8]) -> Result<i64> {
+        let size = self.inode_size(ino).await?;
+        let offset = offset.unwrap_or(size);
+        let end = offset + data.len() as i64;
+        self.punch_range(ino, offset, end).await?;
+        self.insert_chunk(ino, offset, data).await?;
+        self.set_size(ino, size.max(end)).await?;
+        Ok(end)
+    }
+
+    /// Truncate or extend an inode to `len` bytes, leaving an extension as a
+    /// hole
+    async fn set_len_in_tx(&self, ino: i64, len: i64) -> Result<()> {
+        let size = self.inode_size(ino).await?;
+        if len < size {
+            self.punch_range(ino, len, size).await?;
+        }
+        self.set_size(ino, len).await
+    }
+
+    /// List directory contents
+    pub async fn readdir(&self, path: &str) -> Result<Option<Vec<String>>> {
+        self.ensure_schema().await?;
+        let ino = match self.resolve_path(path).await? {
+            Some(ino) => ino,
+            None => return Ok(None),
+        };
+
+        let mut rows = self
+            .query(
+                "SELECT name FROM fs_dentry WHERE parent_ino = ? ORDER BY name",
+                (ino,),
+            )
+            .await?;
+
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let name = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| {
+                    if let Value::Text(s) = v {
+                        Some(s.clone())
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or_default();
+            if !name.is_empty() {
+                entries.push(name);
+            }
+        }
+
+        Ok(Some(entries))
+    }
+
+    /// List directory contents with the inode number and type of each entry
+    ///
+    /// The type comes from the `d_type` column of `fs_dentry`, so listing a
+    /// directory does not need to look up each entry's inode. Entries written
+    /// without a type, for example by older versions, fall back to the inode
+    /// mode.
+    pub async fn readdir_entries(&self, path: &str) -> Result<Option<Vec<DirEntry>>> {
+        self.ensure_schema().await?;
+        let ino = match self.resolve_path(path).await? {
+            Some(ino) => ino,
+            None => return Ok(None),
+        };
+
+        let mut rows = self
+            .query(
+                "SELECT name, ino, d_type FROM fs_dentry WHERE parent_ino = ? ORDER BY name",
+                (ino,),
+            )
+            .await?;
+
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let name = match row.get_value(0)? {
+                Value::Text(name) if !name.is_empty() => name,
+                _ => continue,
+            };
+            let ino = row
+                .get_value(1)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0);
+            let d_type = row
+                .get_value(2)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .map(|v| v as u8);
+            entries.push((name, ino, d_type));
+        }
+
+        let mut result = Vec::with_capacity(entries.len());
+        for (name, ino, cached) in entries {
+            let file_type = match cached {
+                Some(file_type) => file_type,
+                None => {
+                    let mut rows = self
+                        .query("SELECT mode FROM fs_inode WHERE ino = ?", (ino,))
+                        .await?;
+                    match rows.next().await? {
+                        Some(row) => row
+                            .get_value(0)
+                            .ok()
+                            .and_then(|v| v.as_integer().copied())
+                            .map(|mode| d_type(mode as u32))
+                            .unwrap_or(0),
+                        None => 0,
+                    }
+                }
+            };
+            result.push(DirEntry {
+                name,
+                ino,
+                d_type: file_type,
+            });
+        }
+
+        Ok(Some(result))
+    }
+
+    /// List directory contents with the stats of each entry, without
+    /// following symlinks
+    ///
+    /// Each entry reports its own inode, so a symlink is listed as a symlink
+    /// whatever it points to, like `ls -l`.
+    pub async fn readdir_lstats(&self, path: &str) -> Result<Option<Vec<(String, Stats)>>> {
+        self.ensure_schema().await?;
+        let ino = match self.resolve_path(path).await? {
+            Some(ino) => ino,
+            None => return Ok(None),
+        };
+
+        let mut rows = self
+            .query(
+                "SELECT name, ino FROM fs_dentry WHERE parent_ino = ? ORDER BY name",
+                (ino,),
+            )
+            .await?;
+
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let name = match row.get_value(0)? {
+                Value::Text(name) if !name.is_empty() => name,
+                _ => continue,
+            };
+            let ino = row
+                .get_value(1)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0);
+            entries.push((name, ino));
+        }
+
+        let mut result = Vec::with_capacity(entries.len());
+        for (name, ino) in entries {
+            if let Some(stats) = self.inode_stats(ino).await? {
+                result.push((name, stats));
+            }
+        }
+
+        Ok(Some(result))
+    }
+
+    /// Total size in bytes of a file or directory tree
+    ///
+    /// Sizes are the apparent sizes recorded in `fs_inode`. An inode reachable
+    /// through several hard links is counted once, and symlinks are not
+    /// followed. Returns `None` if the path does not exist.
+    pub async fn disk_usage(&self, path: &str) -> Result<Option<u64>> {
+        self.ensure_schema().await?;
+        let path = self.normalize_path(path);
+        let ino = match self.resolve_path(&path).await? {
+            Some(ino) => ino,
+            None => return Ok(None),
+        };
+
+        let mut seen = HashSet::from([ino]);
+        let mut pending = vec![ino];
+        let mut total = 0u64;
+        while let Some(ino) = pending.pop() {
+            let mut rows = self
+                .query("SELECT mode, size FROM fs_inode WHERE ino = ?", (ino,))
+                .await?;
+            let Some(row) = rows.next().await? else {
+                continue;
+            };
+            let mode = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32;
+            let size = row
+                .get_value(1)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0);
+            total += size.max(0) as u64;
+
+            if (mode & S_IFMT) == S_IFDIR {
+                let mut rows = self
+                    .query("SELECT ino FROM fs_dentry WHERE parent_ino = ?", (ino,))
+                    .await?;
+                while let Some(row) = rows.next().await? {
+                    let child = row
+                        .get_value(0)
+                        .ok()
+                        .and_then(|v| v.as_integer().copied())
+                        .unwrap_or(0);
+                    if seen.insert(child) {
+                        pending.push(child);
+                    }
+                }
+            }
+        }
+
+        Ok(Some(total))
+    }
+
+    /// Total size in bytes of all inodes in the filesystem
+    ///
+    /// Unlike [`Filesystem::disk_usage`], this also counts unlinked inodes
+    /// that are still open, and takes a single query.
+    pub async fn total_size(&self) -> Result<u64> {
+        self.ensure_schema().await?;
+        let mut rows = self
+            .query("SELECT COALESCE(SUM(size), 0) FROM fs_inode", ())
+            .await?;
+        let total = match rows.next().await? {
+            Some(row) => row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0),
+            None => 0,
+        };
+        Ok(total.max(0) as u64)
+    }
+
+    /// Create a symbolic link
+    pub async fn symlink(&self, target: &str, linkpath: &str) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.symlink_in_tx(target, linkpath))
+            .await
+    }
+
+    async fn symlink_in_tx(&self, target: &str, linkpath: &str) -> Result<()> {
+        let linkpath = self.normalize_path(linkpath);
+        let components = self.split_path(&linkpath);
+
+        if components.is_empty() {
+            anyhow::bail!("Cannot create symlink at root");
+        }
+
+        // Get parent directory
+        let parent_path = if components.len() == 1 {
+            "/".to_string()
+        } else {
+            format!("/{}", components[..components.len() - 1].join("/"))
+        };
+
+        let parent_ino = self
+            .resolve_path(&parent_path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+
+        let name = components.last().unwrap();
+
+        // Check if entry already exists
+        if (self.resolve_path(&linkpath).await?).is_some() {
+            anyhow::bail!("Path already exists");
+        }
+
+        // Create inode for symlink
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let mode = S_IFLNK | 0o777; // Symlinks typically have 777 permissions
+        let size = target.len() as i64;
+
+        self.execute(
+            "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
+                 VALUES (?, 0, 0, ?, ?, ?, ?)",
+            (mode, size, now, now, now),
+        )
+        .await?;
+
+        // Get the newly created inode
+        let mut rows = self.query("SELECT last_insert_rowid()", ()).await?;
+
+        let ino = if let Some(row) = rows.next().await? {
+            row.get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0)
+        } else {
+            anyhow::bail!("Failed to get new inode");
+        };
+
+        // Store symlink target
+        self.execute(
+            "INSERT INTO fs_symlink (ino, target) VALUES (?, ?)",
+            (ino, target),
+        )
+        .await?;
+
+        // Create directory entry
+        self.execute(
+            "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES (?, ?, ?, ?)",
+            (name.as_str(), parent_ino, ino, d_type(mode)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Read the target of a symbolic link
+    pub async fn readlink(&self, path: &str) -> Result<Option<String>> {
+        self.ensure_schema().await?;
+        let path = self.normalize_path(path);
+
+        let ino = match self.resolve_path(&path).await? {
+            Some(ino) => ino,
+            None => return Ok(None),
+        };
+
+        // Check if it's a symlink by querying the inode
+        let mut rows = self
+            .query("SELECT mode FROM fs_inode WHERE ino = ?", (ino,))
+            .await?;
+
+        if let Some(row) = rows.next().await? {
+            let mode = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32;
+
+            // Check if it's a symlink
+            if (mode & S_IFMT) != S_IFLNK {
+                anyhow::bail!("Not a symbolic link");
+            }
+        } else {
+            return Ok(None);
+        }
+
+        // Read target from fs_symlink table
+        let mut rows = self
+            .query("SELECT target FROM fs_symlink WHERE ino = ?", (ino,))
+            .await?;
+
+        if let Some(row) = rows.next().await? {
+            let target = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| match v {
+                    Value::Text(s) => Some(s.to_string()),
+                    _ => None,
+                })
+                .ok_or_else(|| anyhow::anyhow!("Invalid symlink target"))?;
+            Ok(Some(target))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Recursively copy a directory
+    ///
+    /// Files are copied with their contents and modes, and symlinks are
+    /// recreated with the same target rather than followed. Fails if `to`
+    /// exists, unless `overwrite` is set, in which case the tree is copied
+    /// into the existing directory and entries with the same name are
+    /// replaced.
+    pub async fn copy_dir(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
+        self.ensure_schema().await?;
+        let from = self.normalize_path(from);
+        let to = self.normalize_path(to);
+
+        let stats = self
+            .lstat(&from)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Source directory does not exist"))?;
+        if !stats.is_directory() {
+            anyhow::bail!("Source is not a directory");
+        }
+        if to == from || to.starts_with(&format!("{}/", from.trim_end_matches('/'))) {
+            anyhow::bail!("Cannot copy a directory into itself");
+        }
+        match self.lstat(&to).await? {
+            Some(_) if !overwrite => anyhow::bail!("Destination already exists"),
+            Some(existing) if !existing.is_directory() => {
+                anyhow::bail!("Destination is not a directory")
+            }
+            Some(_) => {}
+            None => self.mkdir(&to).await?,
+        }
+        self.set_mode(&to, stats.mode).await?;
+
+        let mut pending = vec![(from, to)];
+        while let Some((from_dir, to_dir)) = pending.pop() {
+            let entries = self.readdir_entries(&from_dir).await?.unwrap_or_default();
+            for entry in entries {
+                let from_path = format!("{}/{}", from_dir.trim_end_matches('/'), entry.name);
+                let to_path = format!("{}/{}", to_dir.trim_end_matches('/'), entry.name);
+                let Some(stats) = self.lstat(&from_path).await? else {
+                    continue;
+                };
+
+                // Replace an existing entry unless both are directories
+                if let Some(existing) = self.lstat(&to_path).await? {
+                    if !(existing.is_directory() && stats.is_directory()) {
+                        self.remove(&to_path).await?;
+                    }
+                }
+
+                if stats.is_directory() {
+                    if self.resolve_path(&to_path).await?.is_none() {
+                        self.mkdir(&to_path).await?;
+                    }
+                    self.set_mode(&to_path, stats.mode).await?;
+                    pending.push((from_path, to_path));
+                } else if stats.is_symlink() {
+                    let target = self
+                        .readlink(&from_path)
+                        .await?
+                        .ok_or_else(|| anyhow::anyhow!("Symlink has no target"))?;
+                    self.symlink(&target, &to_path).await?;
+                } else {
+                    let data = self.read_file(&from_path).await?.unwrap_or_default();
+                    self.write_file(&to_path, &data).await?;
+                    self.set_mode(&to_path, stats.mode).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set the mode of an inode, including its file type bits
+    async fn set_mode(&self, path: &str, mode: u32) -> Result<()> {
+        self.atomically(|| async {
+            let ino = self
+                .resolve_path(path)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
+            self.execute(
+                "UPDATE fs_inode SET mode = ? WHERE ino = ?",
+                (mode as i64, ino),
+            )
+            .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the value of an extended attribute
+    ///
+    /// Symlinks are not followed. Returns `None` if the attribute is not set.
+    pub async fn getxattr(&self, path: &str, name: &str) -> Result<Option<Vec<u8>>> {
+        let ino = self.resolve_existing(path).await?;
+        let mut rows = self
+            .query(
+                "SELECT value FROM fs_xattr WHERE ino = ? AND name = ?",
+                (ino, name),
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => match row.get_value(0)? {
+                Value::Blob(value) => Ok(Some(value)),
+                Value::Text(value) => Ok(Some(value.into_bytes())),
+                _ => Ok(Some(Vec::new())),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Set an extended attribute, replacing any previous value
+    ///
+    /// Symlinks are not followed.
+    pub async fn setxattr(&self, path: &str, name: &str, value: &[u8]) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| async {
+            let ino = self.resolve_existing(path).await?;
+            self.execute(
+                "DELETE FROM fs_xattr WHERE ino = ? AND name = ?",
+                (ino, name),
+            )
+            .await?;
+            self.execute(
+                "INSERT INTO fs_xattr (ino, name, value) VALUES (?, ?, ?)",
+                (ino, name, value),
+            )
+            .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// List the names of the extended attributes of a file
+    ///
+    /// Symlinks are not followed. Returns `None` if the path does not exist.
+    pub async fn listxattr(&self, path: &str) -> Result<Option<Vec<String>>> {
+        self.ensure_schema().await?;
+        let path = self.normalize_path(path);
+        let ino = match self.resolve_path(&path).await? {
+            Some(ino) => ino,
+            None => return Ok(None),
+        };
+        let mut rows = self
+            .query(
+                "SELECT name FROM fs_xattr WHERE ino = ? ORDER BY name",
+                (ino,),
+            )
+            .await?;
+        let mut names = Vec::new();
+        while let Some(row) = rows.next().await? {
+            if let Value::Text(name) = row.get_value(0)? {
+                names.push(name);
+            }
+        }
+        Ok(Some(names))
+    }
+
+    /// Remove an extended attribute
+    ///
+    /// Symlinks are not followed. Returns whether the attribute was set.
+    pub async fn removexattr(&self, path: &str, name: &str) -> Result<bool> {
+        self.ensure_schema().await?;
+        self.atomically(|| async {
+            let ino = self.resolve_existing(path).await?;
+            let removed = self
+                .execute(
+                    "DELETE FROM fs_xattr WHERE ino = ? AND name = ?",
+                    (ino, name),
+                )
+                .await?;
+            Ok(removed > 0)
+        })
+        .await
+    }
+
+    /// Resolve a path that must exist to an inode number
+    async fn resolve_existing(&self, path: &str) -> Result<i64> {
+        self.ensure_schema().await?;
+        let path = self.normalize_path(path);
+        self.resolve_path(&path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Path does not exist"))
+    }
+
+    /// Remove a file or empty directory
+    pub async fn remove(&self, path: &str) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| async {
+            let ino = self.unlink_in_tx(path).await?;
+
+            // Delete the inode if this was the last link to it
+            self.release_inode_in_tx(ino).await?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove a file, symlink, or empty directory, but keep its inode
+    ///
+    /// Returns the inode, which should be passed to
+    /// [`Filesystem::release_inode`] once it is no longer used, so that a
+    /// file still open elsewhere stays readable until it is closed.
+    pub async fn unlink(&self, path: &str) -> Result<i64> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.unlink_in_tx(path)).await
+    }
+
+    async fn unlink_in_tx(&self, path: &str) -> Result<i64> {
+        let path = self.normalize_path(path);
+        let components = self.split_path(&path);
+
+        if components.is_empty() {
+            anyhow::bail!("Cannot remove root directory");
+        }
+
+        let ino = self
+            .resolve_path(&path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
+
+        if ino == ROOT_INO {
+            anyhow::bail!("Cannot remove root directory");
+        }
+
+        // Check if directory is empty
+        let mut rows = self
+            .query(
+                "SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?",
+                (ino,),
+            )
+            .await?;
+
+        if let Some(row) = rows.next().await? {
+            let count = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0);
+            if count > 0 {
+                anyhow::bail!("Directory not empty");
+            }
+        }
+
+        // Get parent directory and name
+        let parent_path = if components.len() == 1 {
+            "/".to_string()
+        } else {
+            format!("/{}", components[..components.len() - 1].join("/"))
+        };
+
+        let parent_ino = self
+            .resolve_path(&parent_path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+
+        let name = components.last().unwrap();
+
+        // Delete the specific directory entry (not all entries pointing to this inode)
+        self.execute(
+            "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?",
+            (parent_ino, name.as_str()),
+        )
+        .await?;
+
+        Ok(ino)
+    }
+
+    /// Rename a file, directory, or symlink
+    ///
+    /// Like `rename(2)`, an existing destination is replaced: a file or
+    /// symlink by anything but a directory, and an empty directory by a
+    /// directory. The replaced inode is deleted if this was its last link.
+    /// The renamed inode keeps its number and gets a new change time.
+    pub async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.ensure_schema().await?;
+        let from = self.normalize_path(from);
+        let to = self.normalize_path(to);
+        self.atomically(|| self.rename_in_tx(&from, &to, false))
+            .await
+    }
+
+    /// Rename a file, directory, or symlink, failing if the destination exists
+    ///
+    /// Like `renameat2(2)` with `RENAME_NOREPLACE`, checking for the
+    /// destination and renaming happen atomically, so a file prepared under a
+    /// temporary name can be published without clobbering one created
+    /// concurrently.
+    pub async fn rename_noreplace(&self, from: &str, to: &str) -> Result<()> {
+        self.ensure_schema().await?;
+        let from = self.normalize_path(from);
+        let to = self.normalize_path(to);
+        self.atomically(|| self.rename_in_tx(&from, &to, true))
+            .await
+    }
+
+    async fn rename_in_tx(&self, from: &str, to: &str, noreplace: bool) -> Result<()> {
+        let from_components = self.split_path(from);
+        let to_components = self.split_path(to);
+
+        if from_components.is_empty() || to_components.is_empty() {
+            anyhow::bail!("Cannot rename root directory");
+        }
+
+        let ino = self
+            .resolve_path(from)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
+        let stats = self
+            .inode_stats(ino)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
+
+        if stats.is_directory() && to.starts_with(&format!("{}/", from)) {
+            anyhow::bail!("Cannot move a directory into itself");
+        }
+
+        // Get parent directories and names
+        let from_parent_path = if from_components.len() == 1 {
+            "/".to_string()
+        } else {
+            format!(
+                "/{}",
+                from_components[..from_components.len() - 1].join("/")
+            )
+        };
+        let to_parent_path = if to_components.len() == 1 {
+            "/".to_string()
+        } else {
+            format!("/{}", to_components[..to_components.len() - 1].join("/"))
+        };
+
+        let from_parent_ino = self
+            .resolve_path(&from_parent_path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        let to_parent_ino = self
+            .resolve_path(&to_parent_path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        let to_parent = self
+            .inode_stats(to_parent_ino)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        if !to_parent.is_directory() {
+            anyhow::bail!("Not a directory");
+        }
+
+        let from_name = from_components.last().unwrap();
+        let to_name = to_components.last().unwrap();
+
+        // Replace the destination if it exists
+        if let Some(dest_ino) = self.resolve_path(to).await? {
+            if noreplace {
+                anyhow::bail!("Path already exists");
+            }
+            if dest_ino == ino {
+                // Both paths are links to the same inode, nothing to do
+                return Ok(());
+            }
+            let dest = self
+                .inode_stats(dest_ino)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
+            if stats.is_directory() {
+                if !dest.is_directory() {
+                    anyhow::bail!("Not a directory");
+                }
+                let mut rows = self
+                    .query(
+                        "SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?",
+                        (dest_ino,),
+                    )
+                    .await?;
+                if let Some(row) = rows.next().await? {
+                    let count = row
+                        .get_value(0)
+                        .ok()
+                        .and_then(|v| v.as_integer().copied())
+                        .unwrap_or(0);
+                    if count > 0 {
+                        anyhow::bail!("Directory not empty");
+                    }
+                }
+            } else if dest.is_directory() {
+                anyhow::bail!("Is a directory");
+            }
+
+            self.execute(
+                "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?",
+                (to_parent_ino, to_name.as_str()),
+            )
+            .await?;
+
+            // Delete the replaced inode if this was the last link to it
+            self.release_inode_in_tx(dest_ino).await?;
+        }
+
+        // Move the directory entry, which re-parents it if the directories differ
+        self.execute(
+            "UPDATE fs_dentry SET parent_ino = ?, name = ? WHERE parent_ino = ? AND name = ?",
+            (
+                to_parent_ino,
+                to_name.as_str(),
+                from_parent_ino,
+                from_name.as_str(),
+            ),
+        )
+        .await?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.execute("UPDATE fs_inode SET ctime = ? WHERE ino = ?", (now, ino))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Create an empty regular file that no directory entry refers to
+    ///
+    /// This backs `O_TMPFILE`. The returned inode can be given a name with
+    /// [`Filesystem::link_inode`], and should be passed to
+    /// [`Filesystem::release_inode`] once it is no longer used so that it is
+    /// deleted if it was never linked.
+    pub async fn create_unlinked(&self) -> Result<i64> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.create_unlinked_in_tx()).await
+    }
+
+    async fn create_unlinked_in_tx(&self) -> Result<i64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.execute(
+            "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
+                VALUES (?, 0, 0, 0, ?, ?, ?)",
+            (DEFAULT_FILE_MODE as i64, now, now, now),
+        )
+        .await?;
+
+        let mut rows = self.query("SELECT last_insert_rowid()", ()).await?;
+        let row = rows
+            .next()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?;
+        row.get_value(0)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))
+    }
+
+    /// Replace the contents of an inode
+    ///
+    /// Like [`Filesystem::write_file`], but addresses the file by inode, so
+    /// it also works for inodes from [`Filesystem::create_unlinked`].
+    pub async fn write_inode(&self, ino: i64, data: &[u8]) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| async {
+            self.set_len_in_tx(ino, 0).await?;
+            self.write_at_in_tx(ino, Some(0), data).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Read up to `buf.len()` bytes of an inode starting at `offset`
+    ///
+    /// Returns the number of bytes read, which is zero at or past the end of
+    /// the file.
+    pub async fn read_inode_at(&self, ino: i64, offset: i64, buf: &mut [u8]) -> Result<usize> {
+        self.ensure_schema().await?;
+        self.read_at(ino, offset, buf).await
+    }
+
+    /// Write `data` to an inode at `offset`
+    ///
+    /// Only the chunks the write overlaps are replaced, and a gap between
+    /// the end of the file and `offset` is left as a hole.
+    pub async fn write_inode_at(&self, ino: i64, offset: i64, data: &[u8]) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.write_at_in_tx(ino, Some(offset), data))
+            .await?;
+        Ok(())
+    }
+
+    /// Truncate or extend an inode to `len` bytes, leaving an extension as a
+    /// hole that reads back as zeros
+    pub async fn set_inode_len(&self, ino: i64, len: i64) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.set_len_in_tx(ino, len)).await
+    }
+
+    /// Add a directory entry for an existing inode
+    ///
+    /// Fails if `path` already exists or the inode is a directory.
+    pub async fn link_inode(&self, ino: i64, path: &str) -> Result<()> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.link_inode_in_tx(ino, path)).await
+    }
+
+    async fn link_inode_in_tx(&self, ino: i64, path: &str) -> Result<()> {
+        let path = self.normalize_path(path);
+        let components = self.split_path(&path);
+
+        if components.is_empty() {
+            anyhow::bail!("Path already exists");
+        }
+
+        let stats = self
+            .inode_stats(ino)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Inode does not exist"))?;
+        if stats.is_directory() {
+            anyhow::bail!("Cannot link a directory");
+        }
+
+        let parent_path = if components.len() == 1 {
+            "/".to_string()
+        } else {
+            format!("/{}", components[..components.len() - 1].join("/"))
+        };
+
+        let parent_ino = self
+            .resolve_path(&parent_path)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+
+        let name = components.last().unwrap();
+
+        if (self.resolve_path(&path).await?).is_some() {
+            anyhow::bail!("Path already exists");
+        }
+
+        self.execute(
+            "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES (?, ?, ?, ?)",
+            (name.as_str(), parent_ino, ino, d_type(stats.mode)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete an inode and its data if no directory entry refers to it
+    ///
+    /// Returns whether the inode was deleted. The root directory is never
+    /// deleted.
+    pub async fn release_inode(&self, ino: i64) -> Result<bool> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.release_inode_in_tx(ino)).await
+    }
+
+    async fn release_inode_in_tx(&self, ino: i64) -> Result<bool> {
+        if ino == ROOT_INO || self.get_link_count(ino).await? > 0 {
+            return Ok(false);
+        }
+
+        // Manually handle cascading deletes since we don't use foreign keys
+        // Delete data blocks
+        self.execute("DELETE FROM fs_data WHERE ino = ?", (ino,))
+            .await?;
+
+        // Delete symlink if exists
+        self.execute("DELETE FROM fs_symlink WHERE ino = ?", (ino,))
+            .await?;
+
+        // Delete extended attributes
+        self.execute("DELETE FROM fs_xattr WHERE ino = ?", (ino,))
+            .await?;
+
+        // Delete inode
+        let deleted = self
+            .execute("DELETE FROM fs_inode WHERE ino = ?", (ino,))
+            .await?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Check the referential integrity of the filesystem tables
+    ///
+    /// Looks for directory entries, data chunks, symlink targets, and
+    /// extended attributes of missing inodes, symlinks without a target,
+    /// overlapping data chunks, regular files whose size differs from their
+    /// data, and orphan inodes that no directory entry refers to. With
+    /// `repair`, the problems found are also fixed in a single transaction:
+    /// dangling rows and symlinks without a target are deleted, overlapping
+    /// chunks are rewritten keeping the latest write, sizes are set to the
+    /// end of the data, and orphans are linked into `/lost+found`.
+    ///
+    /// Inodes from [`Filesystem::create_unlinked`] are orphans until they
+    /// are linked or released, so this should not run while the filesystem
+    /// is in use.
+    pub async fn check(&self, repair: bool) -> Result<Vec<IntegrityProblem>> {
+        self.ensure_schema().await?;
+        if repair {
+            self.atomically(|| self.check_in_tx(true)).await
+        } else {
+            self.check_in_tx(false).await
+        }
+    }
+
+    async fn check_in_tx(&self, repair: bool) -> Result<Vec<IntegrityProblem>> {
+        let mut problems = Vec::new();
+
+        let mut rows = self
+            .query(
+                "SELECT parent_ino, name, ino FROM fs_dentry
+                 WHERE ino NOT IN (SELECT ino FROM fs_inode)
+                    OR parent_ino NOT IN (SELECT ino FROM fs_inode)
+                 ORDER BY parent_ino, name",
+                (),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            problems.push(IntegrityProblem::DanglingDentry {
+                parent_ino: integer_column(&row, 0),
+                name: text_column(&row, 1),
+                ino: integer_column(&row, 2),
+            });
+        }
+
+        let mut rows = self
+            .query(
+                "SELECT ino, COUNT(*) FROM fs_data
+                 WHERE ino NOT IN (SELECT ino FROM fs_inode)
+                 GROUP BY ino ORDER BY ino",
+                (),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            problems.push(IntegrityProblem::DanglingData {
+                ino: integer_column(&row, 0),
+                chunks: integer_column(&row, 1) as u64,
+            });
+        }
+
+        let mut rows = self
+            .query(
+                "SELECT ino FROM fs_symlink
+                 WHERE ino NOT IN (SELECT ino FROM fs_inode) ORDER BY ino",
+                (),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            problems.push(IntegrityProblem::DanglingSymlink {
+                ino: integer_column(&row, 0),
+            });
+        }
+
+        let mut rows = self
+            .query(
+                "SELECT ino FROM fs_inode
+                 WHERE (mode & ?) = ? AND ino NOT IN (SELECT ino FROM fs_symlink)
+                 ORDER BY ino",
+                (S_IFMT as i64, S_IFLNK as i64),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            problems.push(IntegrityProblem::MissingSymlinkTarget {
+                ino: integer_column(&row, 0),
+            });
+        }
+
+        let mut rows = self
+            .query(
+                "SELECT ino, name FROM fs_xattr
+                 WHERE ino NOT IN (SELECT ino FROM fs_inode) ORDER BY ino, name",
+                (),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            problems.push(IntegrityProblem::DanglingXattr {
+                ino: integer_column(&row, 0),
+                name: text_column(&row, 1),
+            });
+        }
+
+        // Chunks of an inode must not overlap, and none may end past the size
+        // of the file. A file may end in a hole, so its size can be larger.
+        let mut data_sizes = HashMap::new();
+        let mut overlapping = Vec::new();
+        let mut rows = self
+            .query(
+                "SELECT ino, offset, size FROM fs_data
+                 WHERE ino IN (SELECT ino FROM fs_inode) ORDER BY ino, offset",
+                (),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let ino = integer_column(&row, 0);
+            let offset = integer_column(&row, 1);
+            let end = offset + integer_column(&row, 2);
+            let data_size = data_sizes.entry(ino).or_insert(0);
+            if offset < *data_size {
+                problems.push(IntegrityProblem::OverlappingChunks { ino, offset });
+                if overlapping.last() != Some(&ino) {
+                    overlapping.push(ino);
+                }
+            }
+            *data_size = end.max(*data_size);
+        }
+
+        let mut rows = self
+            .query(
+                "SELECT ino, size FROM fs_inode WHERE (mode & ?) = ? ORDER BY ino",
+                (S_IFMT as i64, S_IFREG as i64),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let ino = integer_column(&row, 0);
+            let size = integer_column(&row, 1);
+            let data_size = data_sizes.get(&ino).copied().unwrap_or(0);
+            if size < data_size {
+                problems.push(IntegrityProblem::SizeMismatch {
+                    ino,
+                    size,
+                    data_size,
+                });
+            }
+        }
+
+        // Entries whose parent is missing are dangling, so the inodes they
+        // refer to count as orphans
+        for ino in self.orphan_inodes().await? {
+            problems.push(IntegrityProblem::OrphanInode { ino });
+        }
+
+        if repair {
+            for ino in &overlapping {
+                self.rewrite_chunks(*ino).await?;
+            }
+            for problem in &problems {
+                self.repair(problem).await?;
+            }
+        }
+
+        Ok(problems)
+    }
+
+    /// Fix a problem found by [`Filesystem::check`], except for overlapping
+    /// chunks, which are fixed by [`Filesystem::rewrite_chunks`]
+    async fn repair(&self, problem: &IntegrityProblem) -> Result<()> {
+        match problem {
+            IntegrityProblem::DanglingDentry {
+                parent_ino, name, ..
+            } => {
+                self.execute(
+                    "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?",
+                    (*parent_ino, name.as_str()),
+                )
+                .await?;
+            }
+            IntegrityProblem::DanglingData { ino, .. } => {
+                self.execute("DELETE FROM fs_data WHERE ino = ?", (*ino,))
+                    .await?;
+            }
+            IntegrityProblem::DanglingSymlink { ino } => {
+                self.execute("DELETE FROM fs_symlink WHERE ino = ?", (*ino,))
+                    .await?;
+            }
+            IntegrityProblem::MissingSymlinkTarget { ino } => {
+                self.execute("DELETE FROM fs_dentry WHERE ino = ?", (*ino,))
+                    .await?;
+                self.release_inode_in_tx(*ino).await?;
+            }
+            IntegrityProblem::DanglingXattr { ino, name } => {
+                self.execute(
+                    "DELETE FROM fs_xattr WHERE ino = ? AND name = ?",
+                    (*ino, name.as_str()),
+                )
+                .await?;
+            }
+            IntegrityProblem::OverlappingChunks { .. } => {}
+            IntegrityProblem::SizeMismatch { ino, data_size, .. } => {
+                self.execute(
+                    "UPDATE fs_inode SET size = ? WHERE ino = ?",
+                    (*data_size, *ino),
+                )
+                .await?;
+            }
+            IntegrityProblem::OrphanInode { ino } => {
+                self.relink_orphan(*ino).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find inodes that no directory entry refers to and relink or delete them
+    ///
+    /// A crash between writing an inode and linking it, or between
+    /// unlinking it and deleting it, can leave an inode with data but no
+    /// name. Returns the inodes recovered. Deleting an orphan directory also
+    /// deletes the entries in it, which can orphan more inodes; those are
+    /// deleted too.
+    ///
+    /// Like [`Filesystem::check`], this also recovers inodes from
+    /// [`Filesystem::create_unlinked`], so it should not run while the
+    /// filesystem is in use.
+    pub async fn recover_orphans(&self, action: OrphanAction) -> Result<Vec<i64>> {
+        self.ensure_schema().await?;
+        self.atomically(|| self.recover_orphans_in_tx(action)).await
+    }
+
+    async fn recover_orphans_in_tx(&self, action: OrphanAction) -> Result<Vec<i64>> {
+        let mut recovered = Vec::new();
+        loop {
+            let orphans = self.orphan_inodes().await?;
+            if orphans.is_empty() {
+                return Ok(recovered);
+            }
+            for ino in &orphans {
+                match action {
+                    OrphanAction::Relink => self.relink_orphan(*ino).await?,
+                    OrphanAction::Delete => {
+                        self.execute("DELETE FROM fs_dentry WHERE parent_ino = ?", (*ino,))
+                            .await?;
+                        self.execute("DELETE FROM fs_dentry WHERE ino = ?", (*ino,))
+                            .await?;
+                        self.release_inode_in_tx(*ino).await?;
+                    }
+                }
+            }
+            recovered.extend(orphans);
+            if action == OrphanAction::Relink {
+                return Ok(recovered);
+            }
+        }
+    }
+
+    /// Get the inodes other than the root that no entry of an existing
+    /// directory refers to
+    async fn orphan_inodes(&self) -> Result<Vec<i64>> {
+        let mut rows = self
+            .query(
+                "SELECT ino FROM fs_inode
+                 WHERE ino != ? AND ino NOT IN (
+                     SELECT ino FROM fs_dentry
+                     WHERE parent_ino IN (SELECT ino FROM fs_inode)
+                 )
+                 ORDER BY ino",
+                (ROOT_INO,),
+            )
+            .await?;
+        let mut orphans = Vec::new();
+        while let Some(row) = rows.next().await? {
+            orphans.push(integer_column(&row, 0));
+        }
+        Ok(orphans)
+    }
+
+    /// Link an orphan inode into `/lost+found` as `#<ino>`, creating the
+    /// directory if needed
+    ///
+    /// Does nothing if the inode no longer exists.
+    async fn relink_orphan(&self, ino: i64) -> Result<()> {
+        let Some(stats) = self.inode_stats(ino).await? else {
+            return Ok(());
+        };
+
+        let lost_found = match self.resolve_path(LOST_FOUND).await? {
+            Some(dir) => dir,
+            None => {
+                self.mkdir_in_tx(LOST_FOUND).await?;
+                self.resolve_path(LOST_FOUND)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Failed to create {}", LOST_FOUND))?
+            }
+        };
+        let is_dir = self
+            .inode_stats(lost_found)
+            .await?
+            .is_some_and(|stats| stats.is_directory());
+        if !is_dir {
+            anyhow::bail!("{} is not a directory", LOST_FOUND);
+        }
+
+        // Entries that refer to the inode from a missing directory are
+        // replaced by the new one
+        let mut rows = self
+            .query(
+                "SELECT parent_ino, name FROM fs_dentry
+                 WHERE ino = ? AND parent_ino NOT IN (SELECT ino FROM fs_inode)",
+                (ino,),
+            )
+            .await?;
+        let mut dangling = Vec::new();
+        while let Some(row) = rows.next().await? {
+            dangling.push((integer_column(&row, 0), text_column(&row, 1)));
+        }
+        for (parent_ino, name) in dangling {
+            self.execute(
+                "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?",
+                (parent_ino, name.as_str()),
+            )
+            .await?;
+        }
+        self.execute(
+            "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES (?, ?, ?, ?)",
+            (format!("#{}", ino), lost_found, ino, d_type(stats.mode)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Replace the chunks of an inode with contiguous, non-overlapping ones
+    ///
+    /// Chunks are applied in the order they were written, so where they
+    /// overlap the latest write wins.
+    async fn rewrite_chunks(&self, ino: i64) -> Result<()> {
+        let mut data = Vec::new();
+        let mut rows = self
+            .query(
+                "SELECT offset, data FROM fs_data WHERE ino = ? ORDER BY id",
+                (ino,),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let offset = integer_column(&row, 0) as usize;
+            if let Ok(Value::Blob(chunk)) = row.get_value(1) {
+                let end = offset + chunk.len();
+                if data.len() < end {
+                    data.resize(end, 0);
+                }
+                data[offset..end].copy_from_slice(&chunk);
+            }
+        }
+
+        self.execute("DELETE FROM fs_data WHERE ino = ?", (ino,))
+            .await?;
+        for (i, chunk) in data.chunks(WRITER_CHUNK_SIZE).enumerate() {
+            // Chunks of zeros read back the same as holes
+            if chunk.iter().all(|&b| b == 0) {
+                continue;
+            }
+            self.insert_chunk(ino, (i * WRITER_CHUNK_SIZE) as i64, chunk)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Integer value of a column, or 0 if it is not an integer
+fn integer_column(row: &turso::Row, idx: usize) -> i64 {
+    row.get_value(idx)
+        .ok()
+        .and_then(|v| v.as_integer().copied())
+        .unwrap_or(0)
+}
+
+/// Text value of a column, or an empty string if it is not text
+fn text_column(row: &turso::Row, idx: usize) -> String {
+    match row.get_value(idx) {
+        Ok(Value::Text(s)) => s,
+        _ => String::new(),
+    }
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = Result<T>> + Send>>;
+
+/// Offset and data of a chunk in `fs_data`
+type Chunk = (i64, Vec<u8>);
+
+/// Zeros returned by [`FileReader`] for holes
+const ZEROS: [u8; 8192] = [0; 8192];
+
+/// Streaming reader returned by [`Filesystem::open_reader`]
+pub struct FileReader {
+    conn: Arc<Connection>,
+    ino: i64,
+    /// Size up to which the file reads as zeros past its last chunk
+    size: i64,
+    /// File offset of the next byte to return
+    offset: i64,
+    /// End of the hole of zeros to return before the current chunk
+    hole_end: i64,
+    /// Offset at which the next chunk to fetch starts
+    next_offset: i64,
+    /// Current chunk and the position of the next byte to return from it
+    chunk: Vec<u8>,
+    pos: usize,
+    /// Pending fetch of the next chunk
+    fetch: Option<BoxFuture<Option<Chunk>>>,
+    eof: bool,
+}
+
+impl FileReader {
+    /// Fetch the first chunk at or after `offset`
+    async fn fetch_chunk(conn: Arc<Connection>, ino: i64, offset: i64) -> Result<Option<Chunk>> {
+        let mut rows = conn
+            .query(
+                "SELECT offset, data FROM fs_data WHERE ino = ? AND offset >= ?
+                ORDER BY offset LIMIT 1",
+                (ino, offset),
+            )
+            .await?;
+        let Some(row) = rows.next().await? else {
+            return Ok(None);
+        };
+        let chunk_offset = row
+            .get_value(0)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .ok_or_else(|| anyhow::anyhow!("Invalid chunk offset"))?;
+        let data = match row.get_value(1) {
+            Ok(Value::Blob(data)) => data,
+            _ => Vec::new(),
+        };
+        Ok(Some((chunk_offset, data)))
+    }
+}
+
+impl AsyncRead for FileReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        loop {
+            if self.offset < self.hole_end {
+                let n = (buf.remaining() as i64)
+                    .min(self.hole_end - self.offset)
+                    .min(ZEROS.len() as i64) as usize;
+                buf.put_slice(&ZEROS[..n]);
+                self.offset += n as i64;
+                return Poll::Ready(Ok(()));
+            }
+            if self.pos < self.chunk.len() {
+                let n = buf.remaining().min(self.chunk.len() - self.pos);
+                let pos = self.pos;
+                buf.put_slice(&self.chunk[pos..pos + n]);
+                self.pos += n;
+                self.offset += n as i64;
+                return Poll::Ready(Ok(()));
+            }
+            if self.eof {
+                return Poll::Ready(Ok(()));
+            }
+
+            let this = &mut *self;
+            let fetch = this.fetch.get_or_insert_with(|| {
+                Box::pin(Self::fetch_chunk(
+                    this.conn.clone(),
+                    this.ino,
+                    this.next_offset,
+                ))
+            });
+            let result = ready!(fetch.as_mut().poll(cx));
+            this.fetch = None;
+            match result {
+                Ok(Some((offset, data))) => {
+                    // Skip empty chunks without ending the stream
+                    this.next_offset = offset + data.len().max(1) as i64;
+                    this.hole_end = offset;
+                    this.chunk = data;
+                    this.pos = 0;
+                }
+                Ok(None) => {
+                    this.hole_end = this.size;
+                    this.eof = true;
+                }
+                Err(e) => return Poll::Ready(Err(std::io::Error::other(e))),
+            }
+        }
+    }
+}
+
+/// Streaming writer returned by [`Filesystem::open_writer`]
+pub struct FileWriter {
+    conn: Arc<Connection>,
+    /// Lock of the filesystem, taken while a chunk is stored unless the
+    /// writer runs in a transaction
+    transaction_lock: TransactionLock,
+    in_transaction: bool,
+    ino: i64,
+    /// Offset at which the buffered data starts
+    offset: i64,
+    buf: Vec<u8>,
+    /// Pending store of a chunk
+    store: Option<BoxFuture<()>>,
+}
+
+impl FileWriter {
+    /// Append a chunk at `offset` and update the file size
+    async fn store_chunk(
+        conn: Arc<Connection>,
+        transaction_lock: TransactionLock,
+        in_transaction: bool,
+        ino: i64,
+        offset: i64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let _lock = write_lock(&transaction_lock, in_transaction).await;
+        let size = offset + data.len() as i64;
+        conn.execute(
+            "INSERT INTO fs_data (ino, offset, size, data) VALUES (?, ?, ?, ?)",
+            (ino, offset, data.len() as i64, data),
+        )
+        .await?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        conn.execute(
+            "UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?",
+            (size, now, ino),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Wait for the pending store, if any
+    fn poll_store(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        if let Some(store) = self.store.as_mut() {
+            let result = ready!(store.as_mut().poll(cx));
+            self.store = None;
+            result.map_err(std::io::Error::other)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Start storing the buffered data as a chunk
+    fn start_store(&mut self) {
+        let data = std::mem::replace(&mut self.buf, Vec::with_capacity(WRITER_CHUNK_SIZE));
+        let offset = self.offset;
+        self.offset += data.len() as i64;
+        self.store = Some(Box::pin(Self::store_chunk(
+            self.conn.clone(),
+            self.transaction_lock.clone(),
+            self.in_transaction,
+            self.ino,
+            offset,
+            data,
+        )));
+    }
+}
+
+impl AsyncWrite for FileWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        ready!(self.poll_store(cx))?;
+        let n = data.len().min(WRITER_CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == WRITER_CHUNK_SIZE {
+            self.start_store();
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        ready!(self.poll_store(cx))?;
+        if !self.buf.is_empty() {
+            self.start_store();
+            ready!(self.poll_store(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Options for [`Filesystem::open`], mirroring `std::fs::OpenOptions`
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    create: bool,
+    truncate: bool,
+}
+
+impl OpenOptions {
+    /// Create options with every flag unset
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow reading from the file
+    pub fn read(mut self, read: bool) -> Self {
+        self.read = read;
+        self
+    }
+
+    /// Allow writing to the file
+    pub fn write(mut self, write: bool) -> Self {
+        self.write = write;
+        self
+    }
+
+    /// Write at the end of the file regardless of the position; implies `write`
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Create the file if it does not exist
+    pub fn create(mut self, create: bool) -> Self {
+        self.create = create;
+        self
+    }
+
+    /// Truncate the file to zero length when opening it
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.truncate = truncate;
+        self
+    }
+}
+
+/// File handle returned by [`Filesystem::open`]
+///
+/// Each read or write queries the database directly, so other handles to
+/// the same file see writes as soon as they return.
+pub struct FsFile {
+    fs: Filesystem,
+    ino: i64,
+    /// Position of the next read or write
+    pos: u64,
+    read: bool,
+    write: bool,
+    append: bool,
+}
+
+impl FsFile {
+    /// Read up to `buf.len()` bytes at the current position, returning the
+    /// number of bytes read (0 at the end of the file)
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if !self.read {
+            anyhow::bail!("File not opened for reading");
+        }
+        let n = self.fs.read_at(self.ino, self.pos as i64, buf).await?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    /// Write `buf` at the current position, or at the end of the file in
+    /// append mode, returning the number of bytes written
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if !self.write {
+            anyhow::bail!("File not opened for writing");
+        }
+        let offset = (!self.append).then_some(self.pos as i64);
+        let end = self
+            .fs
+            .atomically(|| self.fs.write_at_in_tx(self.ino, offset, buf))
+            .await?;
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    /// Move the position, returning the new position from the start of the file
+    ///
+    /// Seeking past the end is allowed; a later write leaves the gap as a
+    /// hole that reads back as zeros.
+    pub async fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => {
+                self.pos = offset;
+                return Ok(offset);
+            }
+            SeekFrom::Current(delta) => (self.pos as i64, delta),
+            SeekFrom::End(delta) => (self.fs.inode_size(self.ino).await?, delta),
+        };
+        let new_pos = base
+            .checked_add(delta)
+            .filter(|pos| *pos >= 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid seek to a negative position"))?;
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+
+    /// Flush buffered writes
+    ///
+    /// Writes are stored as they happen, so this only exists for parity with
+    /// `std::fs::File`.
+    pub async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Truncate or extend the file to `len` bytes without moving the position
+    pub async fn set_len(&mut self, len: u64) -> Result<()> {
+        if !self.write {
+            anyhow::bail!("File not opened for writing");
+        }
+        self.fs
+            .atomically(|| self.fs.set_len_in_tx(self.ino, len as i64))
+            .await
     }
 }
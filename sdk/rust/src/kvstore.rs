@@ -1,12 +1,71 @@
+use crate::schema::{self, LazySchema};
+use crate::{write_lock, TransactionGuard, TransactionLock};
 use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::io::{Read, Write};
 use std::sync::Arc;
 use turso::{Builder, Connection};
 
+/// Tables and indexes created by [`KvStore::initialize`]
+const SCHEMA_OBJECTS: &[&str] = &["kv_store", "idx_kv_store_created_at"];
+
+/// Separator between a namespace and the keys in it
+const NAMESPACE_SEPARATOR: char = ':';
+
+/// What [`KvStore::import_json`] does with keys that already exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportPolicy {
+    /// Replace the value and timestamps of the existing key
+    Overwrite,
+    /// Keep the existing key as it is
+    Skip,
+}
+
+/// A key in the JSON written by [`KvStore::export_json`]
+///
+/// Exactly one of `value` and `bytes` is set.
+#[derive(Serialize, Deserialize)]
+struct ExportedKey {
+    key: String,
+    /// A JSON value, kept as it is stored
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_raw"
+    )]
+    value: Option<Box<RawValue>>,
+    /// Raw bytes, base64-encoded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bytes: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+/// Deserialize a present `value`, so that a JSON `null` is not taken for a
+/// missing one
+fn deserialize_raw<'de, D>(deserializer: D) -> std::result::Result<Option<Box<RawValue>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Box::<RawValue>::deserialize(deserializer).map(Some)
+}
+
 /// A key-value store backed by SQLite
 #[derive(Clone)]
 pub struct KvStore {
     conn: Arc<Connection>,
+    schema: LazySchema,
+    /// Prefix of all keys in this store's namespace (empty for the root)
+    prefix: String,
+    /// Lock taken before every write, shared by clones and by the other
+    /// stores of an [`AgentFS`](crate::AgentFS)
+    transaction_lock: TransactionLock,
+    /// Whether this is a sub-store of an
+    /// [`AgentFS::transaction`](crate::AgentFS::transaction), whose
+    /// operations run in the transaction it opened
+    in_transaction: bool,
 }
 
 impl KvStore {
@@ -14,47 +73,105 @@ impl KvStore {
     pub async fn new(db_path: &str) -> Result<Self> {
         let db = Builder::new_local(db_path).build().await?;
         let conn = db.connect()?;
-        let kv = Self {
-            conn: Arc::new(conn),
-        };
-        kv.initialize().await?;
-        Ok(kv)
+        Self::from_connection(Arc::new(conn)).await
     }
 
     /// Create a KV store from an existing connection
     pub async fn from_connection(conn: Arc<Connection>) -> Result<Self> {
-        let kv = Self { conn };
-        kv.initialize().await?;
+        let kv = Self::from_connection_lazy(conn);
+        kv.ensure_schema().await?;
         Ok(kv)
     }
 
+    /// Create a KV store from an existing connection without initializing the schema
+    ///
+    /// The schema is created on first use.
+    pub(crate) fn from_connection_lazy(conn: Arc<Connection>) -> Self {
+        Self {
+            conn,
+            schema: LazySchema::default(),
+            prefix: String::new(),
+            transaction_lock: TransactionLock::default(),
+            in_transaction: false,
+        }
+    }
+
+    /// Take `transaction_lock` before every write
+    pub(crate) fn with_transaction_lock(self, transaction_lock: TransactionLock) -> Self {
+        Self {
+            transaction_lock,
+            ..self
+        }
+    }
+
+    /// Get a clone whose operations run in the transaction open on the connection
+    pub(crate) fn in_transaction(&self) -> Self {
+        Self {
+            in_transaction: true,
+            ..self.clone()
+        }
+    }
+
+    /// Get a store whose keys are scoped to the namespace `name`
+    ///
+    /// Keys are stored as `<name>:<key>`, so `kv.namespace("user").set("theme", ..)`
+    /// writes the key `user:theme`. Namespaces nest, and keys in different
+    /// namespaces never collide as long as namespace names do not contain `:`.
+    pub fn namespace(&self, name: &str) -> KvStore {
+        KvStore {
+            prefix: format!("{}{}{}", self.prefix, name, NAMESPACE_SEPARATOR),
+            ..self.clone()
+        }
+    }
+
+    /// Get the full key stored in the database for `key`
+    fn full_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// Flush all changes to the database file and close this KV store's connection
+    ///
+    /// The connection stays open while other handles share it.
+    pub async fn close(self) -> Result<()> {
+        crate::close_connection(self.conn).await
+    }
+
+    /// Initialize the database schema unless already done
+    pub(crate) async fn ensure_schema(&self) -> Result<()> {
+        self.schema.get_or_init(|| self.initialize()).await
+    }
+
     /// Initialize the database schema
     async fn initialize(&self) -> Result<()> {
-        self.conn
-            .execute(
-                "CREATE TABLE IF NOT EXISTS kv_store (
-                    key TEXT PRIMARY KEY,
-                    value TEXT NOT NULL,
-                    created_at INTEGER DEFAULT (unixepoch()),
-                    updated_at INTEGER DEFAULT (unixepoch())
-                )",
-                (),
-            )
-            .await?;
+        if schema::exists(&self.conn, SCHEMA_OBJECTS).await? {
+            return Ok(());
+        }
 
-        self.conn
-            .execute(
-                "CREATE INDEX IF NOT EXISTS idx_kv_store_created_at
-                ON kv_store(created_at)",
-                (),
-            )
-            .await?;
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE TABLE IF NOT EXISTS kv_store (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                created_at INTEGER DEFAULT (unixepoch()),
+                updated_at INTEGER DEFAULT (unixepoch())
+            )",
+        )
+        .await?;
+
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE INDEX IF NOT EXISTS idx_kv_store_created_at
+            ON kv_store(created_at)",
+        )
+        .await?;
 
         Ok(())
     }
 
     /// Set a key-value pair
     pub async fn set<V: Serialize>(&self, key: &str, value: &V) -> Result<()> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
         let serialized = serde_json::to_string(value)?;
         self.conn
             .execute(
@@ -63,7 +180,7 @@ impl KvStore {
                 ON CONFLICT(key) DO UPDATE SET
                     value = excluded.value,
                     updated_at = unixepoch()",
-                (key, serialized.as_str()),
+                (self.full_key(key), serialized.as_str()),
             )
             .await?;
         Ok(())
@@ -71,9 +188,13 @@ impl KvStore {
 
     /// Get a value by key
     pub async fn get<V: for<'de> Deserialize<'de>>(&self, key: &str) -> Result<Option<V>> {
+        self.ensure_schema().await?;
         let mut rows = self
             .conn
-            .query("SELECT value FROM kv_store WHERE key = ?", (key,))
+            .query(
+                "SELECT value FROM kv_store WHERE key = ?",
+                (self.full_key(key),),
+            )
             .await?;
 
         if let Some(row) = rows.next().await? {
@@ -94,17 +215,114 @@ impl KvStore {
         }
     }
 
+    /// Atomically set a key to `new` if its current value is `expected`
+    ///
+    /// With `expected` set to None, the key is created only if it does not
+    /// exist. Values are compared in their serialized JSON form. Returns
+    /// whether the value was updated.
+    pub async fn compare_and_swap<V: Serialize>(
+        &self,
+        key: &str,
+        expected: Option<&V>,
+        new: &V,
+    ) -> Result<bool> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        let new = serde_json::to_string(new)?;
+        // A single conditional statement runs in its own transaction, so the
+        // check and the update cannot interleave with other writers.
+        let changed = match expected {
+            Some(expected) => {
+                let expected = serde_json::to_string(expected)?;
+                self.conn
+                    .execute(
+                        "UPDATE kv_store SET value = ?, updated_at = unixepoch()
+                        WHERE key = ? AND value = ?",
+                        (new.as_str(), self.full_key(key), expected.as_str()),
+                    )
+                    .await?
+            }
+            None => {
+                self.conn
+                    .execute(
+                        "INSERT INTO kv_store (key, value, updated_at)
+                        VALUES (?, ?, unixepoch())
+                        ON CONFLICT(key) DO NOTHING",
+                        (self.full_key(key), new.as_str()),
+                    )
+                    .await?
+            }
+        };
+        Ok(changed == 1)
+    }
+
+    /// Set a key to raw bytes
+    ///
+    /// The bytes are stored as a BLOB instead of being serialized to JSON,
+    /// so binary data such as embeddings or images is stored as is.
+    pub async fn set_bytes(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        self.conn
+            .execute(
+                "INSERT INTO kv_store (key, value, updated_at)
+                VALUES (?, ?, unixepoch())
+                ON CONFLICT(key) DO UPDATE SET
+                    value = excluded.value,
+                    updated_at = unixepoch()",
+                (self.full_key(key), turso::Value::Blob(value.to_vec())),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Get raw bytes stored with [`KvStore::set_bytes`]
+    ///
+    /// Returns None if the key does not exist or holds a JSON value.
+    pub async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.ensure_schema().await?;
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT value FROM kv_store WHERE key = ?",
+                (self.full_key(key),),
+            )
+            .await?;
+
+        if let Some(row) = rows.next().await? {
+            if let Ok(turso::Value::Blob(bytes)) = row.get_value(0) {
+                return Ok(Some(bytes));
+            }
+        }
+        Ok(None)
+    }
+
     /// Delete a key
     pub async fn delete(&self, key: &str) -> Result<()> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
         self.conn
-            .execute("DELETE FROM kv_store WHERE key = ?", (key,))
+            .execute("DELETE FROM kv_store WHERE key = ?", (self.full_key(key),))
             .await?;
         Ok(())
     }
 
     /// List all keys
+    ///
+    /// In a namespace, this lists the keys of the namespace without its prefix.
     pub async fn keys(&self) -> Result<Vec<String>> {
-        let mut rows = self.conn.query("SELECT key FROM kv_store", ()).await?;
+        self.ensure_schema().await?;
+        let mut rows = match self.prefix_range() {
+            Some((start, end)) => {
+                self.conn
+                    .query(
+                        "SELECT key FROM kv_store WHERE key >= ? AND key < ?",
+                        (start, end),
+                    )
+                    .await?
+            }
+            None => self.conn.query("SELECT key FROM kv_store", ()).await?,
+        };
         let mut keys = Vec::new();
         while let Some(row) = rows.next().await? {
             if let Some(key) = row.get_value(0).ok().and_then(|v| {
@@ -114,9 +332,168 @@ impl KvStore {
                     None
                 }
             }) {
-                keys.push(key);
+                keys.push(key[self.prefix.len()..].to_string());
             }
         }
         Ok(keys)
     }
+
+    /// Delete every key in this store's namespace
+    ///
+    /// Keys of nested namespaces are deleted too. On a store that is not
+    /// scoped to a namespace, this deletes all keys.
+    pub async fn clear_namespace(&self) -> Result<()> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        match self.prefix_range() {
+            Some((start, end)) => {
+                self.conn
+                    .execute(
+                        "DELETE FROM kv_store WHERE key >= ? AND key < ?",
+                        (start, end),
+                    )
+                    .await?;
+            }
+            None => {
+                self.conn.execute("DELETE FROM kv_store", ()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write every key in this store's namespace to `writer` as JSON
+    ///
+    /// The JSON is an array of objects with the key, its value, and its
+    /// creation and update times in seconds since the Unix epoch. JSON values
+    /// are stored under `value` as they are, and raw bytes set with
+    /// [`KvStore::set_bytes`] under `bytes`, base64-encoded. Returns the number
+    /// of keys written.
+    pub async fn export_json<W: Write>(&self, writer: W) -> Result<usize> {
+        self.ensure_schema().await?;
+        const COLUMNS: &str = "SELECT key, value, created_at, updated_at FROM kv_store";
+        let mut rows = match self.prefix_range() {
+            Some((start, end)) => {
+                self.conn
+                    .query(
+                        &format!("{} WHERE key >= ? AND key < ? ORDER BY key", COLUMNS),
+                        (start, end),
+                    )
+                    .await?
+            }
+            None => {
+                self.conn
+                    .query(&format!("{} ORDER BY key", COLUMNS), ())
+                    .await?
+            }
+        };
+        let mut keys = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let key = match row.get_value(0)? {
+                turso::Value::Text(key) => key[self.prefix.len()..].to_string(),
+                _ => continue,
+            };
+            let (value, bytes) = match row.get_value(1)? {
+                turso::Value::Text(json) => (Some(RawValue::from_string(json)?), None),
+                turso::Value::Blob(bytes) => (None, Some(BASE64.encode(bytes))),
+                _ => continue,
+            };
+            let timestamp = |i| match row.get_value(i) {
+                Ok(turso::Value::Integer(t)) => t,
+                _ => 0,
+            };
+            keys.push(ExportedKey {
+                key,
+                value,
+                bytes,
+                created_at: timestamp(2),
+                updated_at: timestamp(3),
+            });
+        }
+        serde_json::to_writer_pretty(writer, &keys)?;
+        Ok(keys.len())
+    }
+
+    /// Load keys written by [`KvStore::export_json`] from `reader`
+    ///
+    /// Keys are imported into this store's namespace with the timestamps they
+    /// were exported with. Keys that already exist are replaced or kept
+    /// according to `policy`. The import is atomic, so a malformed key leaves
+    /// the store unchanged. Returns the number of keys imported.
+    pub async fn import_json<R: Read>(&self, reader: R, policy: ImportPolicy) -> Result<usize> {
+        self.ensure_schema().await?;
+        let keys: Vec<ExportedKey> = serde_json::from_reader(reader)?;
+        let sql = match policy {
+            ImportPolicy::Overwrite => {
+                "INSERT INTO kv_store (key, value, created_at, updated_at)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT(key) DO UPDATE SET
+                    value = excluded.value,
+                    created_at = excluded.created_at,
+                    updated_at = excluded.updated_at"
+            }
+            ImportPolicy::Skip => {
+                "INSERT INTO kv_store (key, value, created_at, updated_at)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT(key) DO NOTHING"
+            }
+        };
+
+        // Wait for the transactions of other tasks, but join a transaction
+        // the caller already started
+        let lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        let guard = match lock {
+            Some(lock) if self.conn.is_autocommit()? => {
+                self.conn.execute("BEGIN IMMEDIATE", ()).await?;
+                Some(TransactionGuard::new(self.conn.clone(), lock))
+            }
+            _ => None,
+        };
+        let result: Result<usize> = async {
+            let mut imported = 0;
+            for exported in keys {
+                let value = match (exported.value, exported.bytes) {
+                    (Some(json), None) => turso::Value::Text(json.get().to_string()),
+                    (None, Some(base64)) => turso::Value::Blob(BASE64.decode(base64)?),
+                    _ => anyhow::bail!(
+                        "Key '{}' must have exactly one of value and bytes",
+                        exported.key
+                    ),
+                };
+                imported += self
+                    .conn
+                    .execute(
+                        sql,
+                        (
+                            self.full_key(&exported.key),
+                            value,
+                            exported.created_at,
+                            exported.updated_at,
+                        ),
+                    )
+                    .await? as usize;
+            }
+            Ok(imported)
+        }
+        .await;
+        if let Some(guard) = guard {
+            match result {
+                Ok(_) => {
+                    self.conn.execute("COMMIT", ()).await?;
+                }
+                Err(_) => guard.rollback().await,
+            }
+        }
+        result
+    }
+
+    /// Get the range of keys that start with this store's prefix
+    ///
+    /// The prefix ends with the namespace separator, so every key in the
+    /// namespace sorts at or after the prefix and before the prefix with the
+    /// separator incremented.
+    fn prefix_range(&self) -> Option<(String, String)> {
+        let parent = self.prefix.strip_suffix(NAMESPACE_SEPARATOR)?;
+        let next = char::from_u32(NAMESPACE_SEPARATOR as u32 + 1)?;
+        Some((self.prefix.clone(), format!("{}{}", parent, next)))
+    }
 }
@@ -1,13 +1,19 @@
 pub mod filesystem;
 pub mod kvstore;
+mod schema;
 pub mod toolcalls;
 
 use anyhow::Result;
+use std::future::Future;
 use std::sync::Arc;
+use tokio::sync::OwnedMutexGuard;
 use turso::{Builder, Connection};
 
-pub use filesystem::{Filesystem, Stats};
-pub use kvstore::KvStore;
+pub use filesystem::{
+    DirEntry, FileReader, FileWriter, Filesystem, FsFile, IntegrityProblem, OpenOptions,
+    OrphanAction, Stats, DEFAULT_BUSY_TIMEOUT,
+};
+pub use kvstore::{ImportPolicy, KvStore};
 pub use toolcalls::{ToolCall, ToolCallStats, ToolCallStatus, ToolCalls};
 
 /// The main AgentFS SDK struct
@@ -16,6 +22,8 @@ pub use toolcalls::{ToolCall, ToolCallStats, ToolCallStatus, ToolCalls};
 /// and tool calls tracking backed by a SQLite database.
 pub struct AgentFS {
     conn: Arc<Connection>,
+    /// Lock held by the transactions started on the connection
+    transaction_lock: TransactionLock,
     pub kv: KvStore,
     pub fs: Filesystem,
     pub tools: ToolCalls,
@@ -27,31 +35,457 @@ impl AgentFS {
     /// # Arguments
     /// * `db_path` - Path to the SQLite database file (use ":memory:" for in-memory database)
     pub async fn new(db_path: &str) -> Result<Self> {
-        let db = Builder::new_local(db_path).build().await?;
-        let conn = db.connect()?;
-        let conn = Arc::new(conn);
+        Self::builder(db_path).build().await
+    }
+
+    /// Create a new AgentFS instance with the given database options
+    ///
+    /// See [`AgentFsOptions`] for the durability trade-offs of each option.
+    pub async fn with_options(db_path: &str, options: AgentFsOptions) -> Result<Self> {
+        Self::builder(db_path).options(options).build().await
+    }
 
-        let kv = KvStore::from_connection(conn.clone()).await?;
-        let fs = Filesystem::from_connection(conn.clone()).await?;
-        let tools = ToolCalls::from_connection(conn.clone()).await?;
+    /// Create an AgentFS instance that only sets up the key-value store
+    ///
+    /// The filesystem and tool call schemas are created only if those
+    /// sub-stores are used.
+    pub async fn kv_only(db_path: &str) -> Result<Self> {
+        Self::builder(db_path).fs(false).tools(false).build().await
+    }
 
-        Ok(Self {
+    /// Create an AgentFS instance on an existing database connection
+    ///
+    /// The AgentFS tables are created next to any tables the database
+    /// already has, so an application can keep its own data in the same
+    /// database. The connection is used as is, so settings such as the busy
+    /// timeout are left to the caller.
+    pub async fn from_connection(conn: Arc<Connection>) -> Result<Self> {
+        let transaction_lock = TransactionLock::default();
+        Ok(AgentFS {
+            kv: KvStore::from_connection(conn.clone())
+                .await?
+                .with_transaction_lock(transaction_lock.clone()),
+            fs: Filesystem::from_connection(conn.clone())
+                .await?
+                .with_transaction_lock(transaction_lock.clone()),
+            tools: ToolCalls::from_connection(conn.clone())
+                .await?
+                .with_transaction_lock(transaction_lock.clone()),
             conn,
-            kv,
-            fs,
-            tools,
+            transaction_lock,
         })
     }
 
+    /// Create a builder for selecting which sub-stores to set up
+    pub fn builder(db_path: &str) -> AgentFSBuilder {
+        AgentFSBuilder {
+            db_path: db_path.to_string(),
+            options: AgentFsOptions::default(),
+            kv: true,
+            fs: true,
+            tools: true,
+        }
+    }
+
     /// Get the underlying database connection
     pub fn get_connection(&self) -> Arc<Connection> {
         self.conn.clone()
     }
+
+    /// Run several operations in a single transaction
+    ///
+    /// The closure gets a [`Transaction`] whose sub-stores share this
+    /// instance's connection. If the closure returns an error, every change it
+    /// made is rolled back; otherwise all of them are committed together.
+    ///
+    /// Transactions on the connection take turns: another `transaction`, or
+    /// any write through this instance's sub-stores, such as
+    /// [`Filesystem::write_file`] or [`KvStore::set`], waits until this one
+    /// is finished instead of joining it. Inside the closure, use the
+    /// sub-stores of the [`Transaction`], as waiting on this instance's would
+    /// never finish.
+    ///
+    /// If the returned future is dropped before it completes, the
+    /// transaction is rolled back on a spawned task, and the connection
+    /// stays locked for other transactions until the rollback has run.
+    ///
+    /// ```no_run
+    /// # async fn example(agentfs: agentfs_sdk::AgentFS) -> anyhow::Result<()> {
+    /// agentfs
+    ///     .transaction(|tx| async move {
+    ///         tx.fs.write_file("/notes.txt", b"hello").await?;
+    ///         tx.kv.set("notes_written", &true).await?;
+    ///         Ok(())
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn transaction<F, Fut, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(Transaction) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        // Create the schemas up front so that a rollback cannot undo them
+        self.kv.ensure_schema().await?;
+        self.fs.ensure_schema().await?;
+        self.tools.ensure_schema().await?;
+
+        let tx = Transaction {
+            kv: self.kv.in_transaction(),
+            fs: self.fs.in_transaction(),
+            tools: self.tools.in_transaction(),
+        };
+        let guard = TransactionGuard::new(
+            self.conn.clone(),
+            self.transaction_lock.clone().lock_owned().await,
+        );
+        self.conn.execute("BEGIN IMMEDIATE", ()).await?;
+        let result = match f(tx).await {
+            Ok(value) => self
+                .conn
+                .execute("COMMIT", ())
+                .await
+                .map(|_| value)
+                .map_err(Into::into),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            guard.rollback().await;
+        }
+        result
+    }
+
+    /// Flush all changes to the database file and close the connection
+    ///
+    /// The connection stays open while clones of the sub-stores or of the
+    /// connection returned by [`AgentFS::get_connection`] are alive, but all
+    /// changes made so far are durable once this returns.
+    pub async fn close(self) -> Result<()> {
+        let AgentFS {
+            conn,
+            kv,
+            fs,
+            tools,
+            ..
+        } = self;
+        drop((kv, fs, tools));
+        close_connection(conn).await
+    }
+}
+
+/// Sub-stores of an [`AgentFS`] inside a transaction
+///
+/// See [`AgentFS::transaction`].
+pub struct Transaction {
+    pub kv: KvStore,
+    pub fs: Filesystem,
+    pub tools: ToolCalls,
+}
+
+/// Lock held while a store writes to a shared connection
+///
+/// Stores take it with [`write_lock`] before every write, so that they wait
+/// for a transaction another task started on the connection instead of
+/// joining it.
+pub(crate) type TransactionLock = Arc<tokio::sync::Mutex<()>>;
+
+/// Take `lock` for a write, waiting for a transaction another task runs on
+/// the connection
+///
+/// Returns `None` without waiting for a store inside an
+/// [`AgentFS::transaction`], whose writes run in the transaction that already
+/// holds the lock. A store holds the lock for the whole of an operation, so
+/// that reads it makes before writing do not see the changes of another
+/// task's transaction either.
+pub(crate) async fn write_lock(
+    lock: &TransactionLock,
+    in_transaction: bool,
+) -> Option<OwnedMutexGuard<()>> {
+    if in_transaction {
+        return None;
+    }
+    Some(lock.clone().lock_owned().await)
+}
+
+/// Holds the [`TransactionLock`] of a transaction until it is finished
+///
+/// A transaction that is still open when the guard is dropped, such as when
+/// the future running it is dropped, is rolled back on a spawned task. The
+/// task holds the lock until the rollback has run, so no other transaction
+/// can join the abandoned one.
+pub(crate) struct TransactionGuard {
+    conn: Arc<Connection>,
+    lock: Option<OwnedMutexGuard<()>>,
+}
+
+impl TransactionGuard {
+    pub(crate) fn new(conn: Arc<Connection>, lock: OwnedMutexGuard<()>) -> Self {
+        Self {
+            conn,
+            lock: Some(lock),
+        }
+    }
+
+    /// Roll back the transaction if it is still open
+    pub(crate) async fn rollback(self) {
+        if !self.conn.is_autocommit().unwrap_or(true) {
+            let _ = self.conn.execute("ROLLBACK", ()).await;
+        }
+    }
+}
+
+impl Drop for TransactionGuard {
+    fn drop(&mut self) {
+        let Some(lock) = self.lock.take() else {
+            return;
+        };
+        if self.conn.is_autocommit().unwrap_or(true) {
+            return;
+        }
+        // Without a runtime, the transaction stays open and the next one
+        // started on the connection joins it
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let conn = self.conn.clone();
+            runtime.spawn(async move {
+                let _ = conn.execute("ROLLBACK", ()).await;
+                drop(lock);
+            });
+        }
+    }
+}
+
+/// Flush and checkpoint a connection, then drop it
+pub(crate) async fn close_connection(conn: Arc<Connection>) -> Result<()> {
+    conn.cacheflush()?;
+    let mut rows = conn.query("PRAGMA wal_checkpoint(TRUNCATE)", ()).await?;
+    while rows.next().await?.is_some() {}
+    drop(rows);
+    drop(conn);
+    Ok(())
+}
+
+/// Builder for [`AgentFS`]
+///
+/// Sub-stores that are not selected are still available, but their schema is
+/// created lazily on first use instead of when the instance is built.
+pub struct AgentFSBuilder {
+    db_path: String,
+    options: AgentFsOptions,
+    kv: bool,
+    fs: bool,
+    tools: bool,
+}
+
+impl AgentFSBuilder {
+    /// Set the database options applied when the database is opened
+    pub fn options(mut self, options: AgentFsOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set up the key-value store when building (default: true)
+    pub fn kv(mut self, enabled: bool) -> Self {
+        self.kv = enabled;
+        self
+    }
+
+    /// Set up the filesystem when building (default: true)
+    pub fn fs(mut self, enabled: bool) -> Self {
+        self.fs = enabled;
+        self
+    }
+
+    /// Set up tool call tracking when building (default: true)
+    pub fn tools(mut self, enabled: bool) -> Self {
+        self.tools = enabled;
+        self
+    }
+
+    /// Open the database and set up the selected sub-stores
+    pub async fn build(self) -> Result<AgentFS> {
+        let db = Builder::new_local(&self.db_path).build().await?;
+        let conn = db.connect()?;
+        conn.busy_timeout(filesystem::DEFAULT_BUSY_TIMEOUT)?;
+        self.options.apply(&conn).await?;
+        let conn = Arc::new(conn);
+
+        let kv = if self.kv {
+            KvStore::from_connection(conn.clone()).await?
+        } else {
+            KvStore::from_connection_lazy(conn.clone())
+        };
+        let fs = if self.fs {
+            Filesystem::from_connection(conn.clone()).await?
+        } else {
+            Filesystem::from_connection_lazy(conn.clone())
+        };
+        let tools = if self.tools {
+            ToolCalls::from_connection(conn.clone()).await?
+        } else {
+            ToolCalls::from_connection_lazy(conn.clone())
+        };
+
+        let transaction_lock = TransactionLock::default();
+        Ok(AgentFS {
+            conn,
+            kv: kv.with_transaction_lock(transaction_lock.clone()),
+            fs: fs.with_transaction_lock(transaction_lock.clone()),
+            tools: tools.with_transaction_lock(transaction_lock.clone()),
+            transaction_lock,
+        })
+    }
+}
+
+/// Journal mode of the database, set with `PRAGMA journal_mode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalMode {
+    /// Write-ahead log: readers don't block writers and commits append to
+    /// the log instead of rewriting pages in place
+    Wal,
+    /// Rollback journal that is deleted at the end of each transaction
+    Delete,
+    /// Rollback journal that is truncated at the end of each transaction
+    Truncate,
+    /// Rollback journal whose header is zeroed at the end of each transaction
+    Persist,
+    /// Rollback journal kept in memory; a crash during a transaction can
+    /// corrupt the database
+    Memory,
+    /// No journal; a crash during a transaction can corrupt the database
+    Off,
+}
+
+impl JournalMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            JournalMode::Wal => "wal",
+            JournalMode::Delete => "delete",
+            JournalMode::Truncate => "truncate",
+            JournalMode::Persist => "persist",
+            JournalMode::Memory => "memory",
+            JournalMode::Off => "off",
+        }
+    }
+}
+
+/// How often the database syncs to disk, set with `PRAGMA synchronous`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synchronous {
+    /// Never sync. An application crash loses nothing, but an operating
+    /// system crash or power loss can lose recent commits or corrupt the
+    /// database.
+    Off = 0,
+    /// Sync at critical moments only. In WAL mode the database stays
+    /// consistent, but a power loss can roll back the most recent commits.
+    Normal = 1,
+    /// Sync on every commit, so committed changes survive a power loss
+    Full = 2,
+    /// Like [`Synchronous::Full`], and also sync the directory of a deleted
+    /// rollback journal
+    Extra = 3,
+}
+
+/// Database options applied when an [`AgentFS`] opens its database
+///
+/// Options left unset keep the database's defaults, which for turso are
+/// [`JournalMode::Wal`], [`Synchronous::Full`] and a 2 MiB page cache.
+/// Loosening durability speeds up writes, at the risk of losing the last
+/// changes on a crash:
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// use agentfs_sdk::{AgentFS, AgentFsOptions, JournalMode, Synchronous};
+///
+/// let options = AgentFsOptions::new()
+///     .journal_mode(JournalMode::Wal)
+///     .synchronous(Synchronous::Normal)
+///     .cache_size(-64 * 1024);
+/// let agentfs = AgentFS::with_options("agent.db", options).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AgentFsOptions {
+    journal_mode: Option<JournalMode>,
+    synchronous: Option<Synchronous>,
+    cache_size: Option<i64>,
+}
+
+impl AgentFsOptions {
+    /// Create options that keep every database default
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the journal mode
+    ///
+    /// Opening the database fails if it does not support the mode. turso
+    /// supports only [`JournalMode::Wal`].
+    pub fn journal_mode(mut self, mode: JournalMode) -> Self {
+        self.journal_mode = Some(mode);
+        self
+    }
+
+    /// Set how often the database syncs to disk
+    ///
+    /// turso implements only [`Synchronous::Off`] and [`Synchronous::Full`]
+    /// and uses the latter for the other levels.
+    pub fn synchronous(mut self, synchronous: Synchronous) -> Self {
+        self.synchronous = Some(synchronous);
+        self
+    }
+
+    /// Set the page cache size: a number of pages if positive, or a number
+    /// of KiB if negative
+    pub fn cache_size(mut self, cache_size: i64) -> Self {
+        self.cache_size = Some(cache_size);
+        self
+    }
+
+    /// Set the selected pragmas on a connection
+    async fn apply(&self, conn: &Connection) -> Result<()> {
+        if let Some(mode) = self.journal_mode {
+            let sql = format!("PRAGMA journal_mode = '{}'", mode.as_str());
+            let mut rows = conn.query(&sql, ()).await?;
+            while rows.next().await?.is_some() {}
+            drop(rows);
+            // An unsupported mode is ignored rather than refused, so check
+            // which mode the database ended up in
+            let mut rows = conn.query("PRAGMA journal_mode", ()).await?;
+            let current = match rows.next().await? {
+                Some(row) => match row.get_value(0)? {
+                    turso::Value::Text(mode) => mode,
+                    _ => String::new(),
+                },
+                None => String::new(),
+            };
+            if !current.eq_ignore_ascii_case(mode.as_str()) {
+                anyhow::bail!(
+                    "journal mode {} is not supported (database uses {})",
+                    mode.as_str(),
+                    current
+                );
+            }
+        }
+        if let Some(synchronous) = self.synchronous {
+            // Numeric levels, since turso does not parse the level names
+            let sql = format!("PRAGMA synchronous = {}", synchronous as i64);
+            conn.execute(&sql, ()).await?;
+        }
+        if let Some(cache_size) = self.cache_size {
+            conn.execute(&format!("PRAGMA cache_size = {}", cache_size), ())
+                .await?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::SeekFrom;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     #[tokio::test]
     async fn test_agentfs_creation() {
@@ -60,6 +494,239 @@ mod tests {
         let _conn = agentfs.get_connection();
     }
 
+    #[tokio::test]
+    async fn test_kv_only_skips_other_schemas() {
+        let agentfs = AgentFS::kv_only(":memory:").await.unwrap();
+        let conn = agentfs.get_connection();
+
+        assert!(schema::exists(&conn, &["kv_store"]).await.unwrap());
+        assert!(!schema::exists(&conn, &["fs_inode"]).await.unwrap());
+        assert!(!schema::exists(&conn, &["tool_calls"]).await.unwrap());
+
+        agentfs.kv.set("key", &"value").await.unwrap();
+        let value: Option<String> = agentfs.kv.get("key").await.unwrap();
+        assert_eq!(value, Some("value".to_string()));
+        assert!(!schema::exists(&conn, &["fs_inode"]).await.unwrap());
+
+        // The filesystem schema is created on first use
+        agentfs.fs.write_file("/hello.txt", b"hello").await.unwrap();
+        assert!(schema::exists(&conn, &["fs_inode"]).await.unwrap());
+        let data = agentfs.fs.read_file("/hello.txt").await.unwrap();
+        assert_eq!(data, Some(b"hello".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_from_connection() {
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        let conn = Arc::new(db.connect().unwrap());
+        conn.execute("CREATE TABLE app_users (name TEXT)", ())
+            .await
+            .unwrap();
+        conn.execute("INSERT INTO app_users VALUES ('alice')", ())
+            .await
+            .unwrap();
+
+        let agentfs = AgentFS::from_connection(conn.clone()).await.unwrap();
+        agentfs.fs.write_file("/a.txt", b"hello").await.unwrap();
+        agentfs.kv.set("key", &"value").await.unwrap();
+        let data = agentfs.fs.read_file("/a.txt").await.unwrap().unwrap();
+        assert_eq!(data, b"hello");
+        let value: Option<String> = agentfs.kv.get("key").await.unwrap();
+        assert_eq!(value.as_deref(), Some("value"));
+
+        // The AgentFS tables live next to the application's own tables
+        let mut rows = conn.query("SELECT name FROM app_users", ()).await.unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(
+            row.get_value(0).unwrap(),
+            turso::Value::Text("alice".to_string())
+        );
+        let mut rows = conn
+            .query("SELECT COUNT(*) FROM fs_dentry WHERE name = 'a.txt'", ())
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap(), turso::Value::Integer(1));
+    }
+
+    #[tokio::test]
+    async fn test_with_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let options = AgentFsOptions::new()
+            .journal_mode(JournalMode::Wal)
+            .synchronous(Synchronous::Off)
+            .cache_size(-4000);
+        let agentfs = AgentFS::with_options(db_path.to_str().unwrap(), options)
+            .await
+            .unwrap();
+        agentfs.fs.write_file("/a.txt", b"hello").await.unwrap();
+
+        let conn = agentfs.get_connection();
+        let pragma = |name: &'static str| {
+            let conn = conn.clone();
+            async move {
+                let mut rows = conn.query(&format!("PRAGMA {}", name), ()).await.unwrap();
+                let row = rows.next().await.unwrap().unwrap();
+                row.get_value(0).unwrap()
+            }
+        };
+        assert_eq!(
+            pragma("journal_mode").await,
+            turso::Value::Text("wal".to_string())
+        );
+        assert_eq!(pragma("synchronous").await, turso::Value::Integer(0));
+        assert_eq!(pragma("cache_size").await, turso::Value::Integer(-4000));
+    }
+
+    #[tokio::test]
+    async fn test_close_persists_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let db_path = db_path.to_str().unwrap();
+
+        let agentfs = AgentFS::new(db_path).await.unwrap();
+        agentfs.kv.set("key", &"value").await.unwrap();
+        agentfs.fs.write_file("/hello.txt", b"hello").await.unwrap();
+        agentfs.tools.start("tool", None, None).await.unwrap();
+        agentfs.close().await.unwrap();
+
+        let agentfs = AgentFS::new(db_path).await.unwrap();
+        let value: Option<String> = agentfs.kv.get("key").await.unwrap();
+        assert_eq!(value, Some("value".to_string()));
+        let data = agentfs.fs.read_file("/hello.txt").await.unwrap();
+        assert_eq!(data, Some(b"hello".to_vec()));
+        assert_eq!(agentfs.tools.recent(None).await.unwrap().len(), 1);
+        agentfs.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commits() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let id = agentfs
+            .transaction(|tx| async move {
+                tx.kv.set("key", &"value").await?;
+                tx.fs.write_file("/a.txt", b"a").await?;
+                tx.tools.start("tool", None, None).await
+            })
+            .await
+            .unwrap();
+
+        let value: Option<String> = agentfs.kv.get("key").await.unwrap();
+        assert_eq!(value, Some("value".to_string()));
+        let data = agentfs.fs.read_file("/a.txt").await.unwrap();
+        assert_eq!(data, Some(b"a".to_vec()));
+        assert!(agentfs.tools.get(id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back_on_error() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let result: Result<()> = agentfs
+            .transaction(|tx| async move {
+                tx.kv.set("key", &"value").await?;
+                tx.fs.write_file("/a.txt", b"a").await?;
+                // The parent directory does not exist
+                tx.fs.write_file("/missing/b.txt", b"b").await?;
+                Ok(())
+            })
+            .await;
+        assert!(result.is_err());
+
+        let value: Option<String> = agentfs.kv.get("key").await.unwrap();
+        assert_eq!(value, None);
+        assert_eq!(agentfs.fs.read_file("/a.txt").await.unwrap(), None);
+
+        // The connection is usable again after the rollback
+        agentfs.fs.write_file("/a.txt", b"ok").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_transaction_is_not_joined_by_other_tasks() {
+        use std::time::Duration;
+
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        // Writes from other tasks wait for the transaction instead of
+        // being rolled back with it, and a second transaction waits too
+        let failing = agentfs.transaction(|tx| async move {
+            tx.fs.write_file("/a.txt", b"a").await?;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            anyhow::bail!("failed")
+        });
+        let write = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            agentfs.fs.write_file("/b.txt", b"b").await
+        };
+        let set = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            agentfs.kv.set("key", &"value").await
+        };
+        let start = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            agentfs.tools.start("tool", None, None).await
+        };
+        let second = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            agentfs
+                .transaction(|tx| async move { tx.fs.write_file("/c.txt", b"c").await })
+                .await
+        };
+        let (failing, write, set, start, second) = tokio::join!(failing, write, set, start, second);
+        let failing: Result<()> = failing;
+        assert!(failing.is_err());
+        write.unwrap();
+        set.unwrap();
+        let id = start.unwrap();
+        second.unwrap();
+        assert_eq!(
+            agentfs.kv.get::<String>("key").await.unwrap(),
+            Some("value".to_string())
+        );
+        let call = agentfs.tools.get(id).await.unwrap().unwrap();
+        assert_eq!(call.name, "tool");
+        assert_eq!(call.status, ToolCallStatus::Pending);
+        assert_eq!(agentfs.fs.read_file("/a.txt").await.unwrap(), None);
+        assert_eq!(
+            agentfs.fs.read_file("/b.txt").await.unwrap(),
+            Some(b"b".to_vec())
+        );
+        assert_eq!(
+            agentfs.fs.read_file("/c.txt").await.unwrap(),
+            Some(b"c".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back_when_dropped() {
+        use std::time::Duration;
+
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let result = tokio::time::timeout(
+            Duration::from_millis(50),
+            agentfs.transaction(|tx| async move {
+                tx.fs.write_file("/a.txt", b"a").await?;
+                std::future::pending::<()>().await;
+                Ok(())
+            }),
+        )
+        .await;
+        assert!(result.is_err());
+
+        // The next write waits for the rollback instead of joining the
+        // abandoned transaction
+        agentfs.fs.write_file("/b.txt", b"b").await.unwrap();
+        assert!(agentfs.get_connection().is_autocommit().unwrap());
+        assert_eq!(agentfs.fs.read_file("/a.txt").await.unwrap(), None);
+        assert_eq!(
+            agentfs.fs.read_file("/b.txt").await.unwrap(),
+            Some(b"b".to_vec())
+        );
+    }
+
     #[tokio::test]
     async fn test_kv_operations() {
         let agentfs = AgentFS::new(":memory:").await.unwrap();
@@ -79,6 +746,252 @@ mod tests {
         assert_eq!(value, None);
     }
 
+    #[tokio::test]
+    async fn test_kv_bytes() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let data: Vec<u8> = vec![0, 159, 146, 150, 0, 255, b'\n', 0];
+        agentfs.kv.set_bytes("blob", &data).await.unwrap();
+        assert_eq!(agentfs.kv.get_bytes("blob").await.unwrap(), Some(data));
+
+        agentfs.kv.set_bytes("empty", &[]).await.unwrap();
+        assert_eq!(agentfs.kv.get_bytes("empty").await.unwrap(), Some(vec![]));
+
+        // JSON values and missing keys are not raw bytes
+        agentfs.kv.set("json", &"value").await.unwrap();
+        assert_eq!(agentfs.kv.get_bytes("json").await.unwrap(), None);
+        assert_eq!(agentfs.kv.get_bytes("missing").await.unwrap(), None);
+
+        // Overwriting replaces the bytes
+        agentfs.kv.set_bytes("blob", b"new").await.unwrap();
+        assert_eq!(
+            agentfs.kv.get_bytes("blob").await.unwrap(),
+            Some(b"new".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kv_namespaces() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let a = agentfs.kv.namespace("a");
+        let b = agentfs.kv.namespace("b");
+
+        a.set("key", &"from a").await.unwrap();
+        b.set("key", &"from b").await.unwrap();
+        b.set_bytes("blob", b"\0b").await.unwrap();
+        a.namespace("nested").set("key", &1).await.unwrap();
+        agentfs.kv.set("ab:key", &"root").await.unwrap();
+
+        let value: Option<String> = a.get("key").await.unwrap();
+        assert_eq!(value, Some("from a".to_string()));
+        let value: Option<String> = b.get("key").await.unwrap();
+        assert_eq!(value, Some("from b".to_string()));
+        let value: Option<String> = agentfs.kv.get("a:key").await.unwrap();
+        assert_eq!(value, Some("from a".to_string()));
+
+        let mut keys = a.keys().await.unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["key", "nested:key"]);
+
+        // Clearing one namespace leaves the others intact
+        a.clear_namespace().await.unwrap();
+        assert!(a.keys().await.unwrap().is_empty());
+        let value: Option<String> = b.get("key").await.unwrap();
+        assert_eq!(value, Some("from b".to_string()));
+        assert_eq!(b.get_bytes("blob").await.unwrap(), Some(b"\0b".to_vec()));
+        let value: Option<String> = agentfs.kv.get("ab:key").await.unwrap();
+        assert_eq!(value, Some("root".to_string()));
+
+        b.delete("key").await.unwrap();
+        assert_eq!(b.keys().await.unwrap(), vec!["blob"]);
+    }
+
+    #[tokio::test]
+    async fn test_kv_compare_and_swap() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let kv = &agentfs.kv;
+
+        // Create if absent
+        assert!(kv.compare_and_swap("counter", None, &1).await.unwrap());
+        assert!(!kv.compare_and_swap("counter", None, &5).await.unwrap());
+        assert_eq!(kv.get::<i32>("counter").await.unwrap(), Some(1));
+
+        // Swap succeeds when the current value matches
+        assert!(kv.compare_and_swap("counter", Some(&1), &2).await.unwrap());
+        assert_eq!(kv.get::<i32>("counter").await.unwrap(), Some(2));
+
+        // Swap fails on mismatch and leaves the value unchanged
+        assert!(!kv.compare_and_swap("counter", Some(&1), &3).await.unwrap());
+        assert_eq!(kv.get::<i32>("counter").await.unwrap(), Some(2));
+
+        // A missing key never matches an expected value
+        assert!(!kv.compare_and_swap("missing", Some(&1), &2).await.unwrap());
+        assert_eq!(kv.get::<i32>("missing").await.unwrap(), None);
+    }
+
+    /// Get every row of the key-value store, in key order
+    async fn kv_rows(agentfs: &AgentFS) -> Vec<Vec<turso::Value>> {
+        let mut rows = agentfs
+            .get_connection()
+            .query(
+                "SELECT key, value, created_at, updated_at FROM kv_store ORDER BY key",
+                (),
+            )
+            .await
+            .unwrap();
+        let mut result = Vec::new();
+        while let Some(row) = rows.next().await.unwrap() {
+            result.push((0..4).map(|i| row.get_value(i).unwrap()).collect());
+        }
+        result
+    }
+
+    #[tokio::test]
+    async fn test_kv_export_import() {
+        let source = AgentFS::new(":memory:").await.unwrap();
+        source.kv.set("string", &"hello").await.unwrap();
+        source.kv.set("null", &None::<i32>).await.unwrap();
+        source
+            .kv
+            .set("object", &serde_json::json!({"b": [1, 2], "a": "x"}))
+            .await
+            .unwrap();
+        source
+            .kv
+            .set_bytes("blob", &[0, 159, 255, b'\n'])
+            .await
+            .unwrap();
+        source.kv.set_bytes("empty", &[]).await.unwrap();
+        // Timestamps are exported as they are
+        source
+            .get_connection()
+            .execute(
+                "UPDATE kv_store SET created_at = 1000, updated_at = 2000 WHERE key = 'string'",
+                (),
+            )
+            .await
+            .unwrap();
+
+        let mut json = Vec::new();
+        assert_eq!(source.kv.export_json(&mut json).await.unwrap(), 5);
+        let parsed: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed[0]["key"], "blob");
+        assert_eq!(parsed[0]["bytes"], "AJ//Cg==");
+
+        let target = AgentFS::new(":memory:").await.unwrap();
+        let imported = target
+            .kv
+            .import_json(json.as_slice(), ImportPolicy::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(imported, 5);
+        assert_eq!(kv_rows(&target).await, kv_rows(&source).await);
+        let value: Option<Option<i32>> = target.kv.get("null").await.unwrap();
+        assert_eq!(value, Some(None));
+
+        // Existing keys are kept or replaced according to the policy
+        target.kv.set("string", &"changed").await.unwrap();
+        let imported = target
+            .kv
+            .import_json(json.as_slice(), ImportPolicy::Skip)
+            .await
+            .unwrap();
+        assert_eq!(imported, 0);
+        let value: Option<String> = target.kv.get("string").await.unwrap();
+        assert_eq!(value.as_deref(), Some("changed"));
+        target
+            .kv
+            .import_json(json.as_slice(), ImportPolicy::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(kv_rows(&target).await, kv_rows(&source).await);
+
+        // A namespace exports and imports its keys without the prefix
+        source.kv.namespace("ns").set("key", &1).await.unwrap();
+        let mut json = Vec::new();
+        assert_eq!(
+            source
+                .kv
+                .namespace("ns")
+                .export_json(&mut json)
+                .await
+                .unwrap(),
+            1
+        );
+        let other = target.kv.namespace("other");
+        other
+            .import_json(json.as_slice(), ImportPolicy::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(other.get::<i32>("key").await.unwrap(), Some(1));
+
+        // A malformed key fails the whole import
+        let bad = br#"[{"key": "good", "value": 1, "created_at": 0, "updated_at": 0},
+            {"key": "bad", "bytes": "not base64!", "created_at": 0, "updated_at": 0}]"#;
+        assert!(target
+            .kv
+            .import_json(bad.as_slice(), ImportPolicy::Overwrite)
+            .await
+            .is_err());
+        assert_eq!(target.kv.get::<i32>("good").await.unwrap(), None);
+    }
+
+    /// FNV-1a hash, computed incrementally over a stream
+    fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+        data.iter()
+            .fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+    }
+
+    #[tokio::test]
+    async fn test_streaming_reader_and_writer() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+        const SIZE: usize = 5 * 1024 * 1024 + 123;
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let content = |i: usize| (i * 31 % 251) as u8;
+
+        // Write in odd-sized pieces so chunks do not line up with writes
+        let mut writer = agentfs.fs.open_writer("/big.bin").await.unwrap();
+        let mut expected = FNV_OFFSET;
+        let mut written = 0;
+        while written < SIZE {
+            let piece: Vec<u8> = (written..SIZE.min(written + 100_003))
+                .map(content)
+                .collect();
+            expected = fnv1a(expected, &piece);
+            writer.write_all(&piece).await.unwrap();
+            written += piece.len();
+        }
+        writer.shutdown().await.unwrap();
+
+        let stats = agentfs.fs.stat("/big.bin").await.unwrap().unwrap();
+        assert_eq!(stats.size, SIZE as i64);
+
+        let mut reader = agentfs.fs.open_reader("/big.bin").await.unwrap().unwrap();
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut hash = FNV_OFFSET;
+        let mut read = 0;
+        loop {
+            let n = reader.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            hash = fnv1a(hash, &buf[..n]);
+            read += n;
+        }
+        assert_eq!(read, SIZE);
+        assert_eq!(hash, expected);
+
+        // Files written with write_file can be streamed too
+        agentfs.fs.write_file("/small.txt", b"hello").await.unwrap();
+        let mut reader = agentfs.fs.open_reader("/small.txt").await.unwrap().unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"hello");
+        assert!(agentfs.fs.open_reader("/missing").await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn test_filesystem_operations() {
         let agentfs = AgentFS::new(":memory:").await.unwrap();
@@ -114,31 +1027,1729 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_tool_calls() {
+    async fn test_readdir_entry_types() {
         let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        agentfs.fs.mkdir("/dir/sub").await.unwrap();
+        agentfs.fs.write_file("/dir/file.txt", b"x").await.unwrap();
+        agentfs.fs.symlink("file.txt", "/dir/link").await.unwrap();
 
-        // Start a tool call
-        let id = agentfs
-            .tools
-            .start("test_tool", Some(serde_json::json!({"param": "value"})))
+        let entries = agentfs.fs.readdir_entries("/dir").await.unwrap().unwrap();
+        let types: Vec<_> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.d_type))
+            .collect();
+        // DT_REG, DT_LNK, DT_DIR
+        assert_eq!(types, vec![("file.txt", 8), ("link", 10), ("sub", 4)]);
+        assert!(entries[0].is_file());
+        assert!(entries[1].is_symlink());
+        assert!(entries[2].is_directory());
+        let stats = agentfs.fs.lstat("/dir/link").await.unwrap().unwrap();
+        assert_eq!(entries[1].ino, stats.ino);
+        assert!(agentfs
+            .fs
+            .readdir_entries("/missing")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_readdir_entry_types_without_cached_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
             .await
             .unwrap();
+        let conn = db.connect().unwrap();
 
-        // Mark it as successful
+        // Schema from before fs_dentry had a d_type column
+        for sql in [
+            "CREATE TABLE fs_inode (ino INTEGER PRIMARY KEY AUTOINCREMENT, mode INTEGER NOT NULL, uid INTEGER NOT NULL DEFAULT 0, gid INTEGER NOT NULL DEFAULT 0, size INTEGER NOT NULL DEFAULT 0, atime INTEGER NOT NULL, mtime INTEGER NOT NULL, ctime INTEGER NOT NULL)",
+            "CREATE TABLE fs_dentry (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, parent_ino INTEGER NOT NULL, ino INTEGER NOT NULL, UNIQUE(parent_ino, name))",
+            "CREATE INDEX idx_fs_dentry_parent ON fs_dentry(parent_ino, name)",
+            "CREATE TABLE fs_data (id INTEGER PRIMARY KEY AUTOINCREMENT, ino INTEGER NOT NULL, offset INTEGER NOT NULL, size INTEGER NOT NULL, data BLOB NOT NULL)",
+            "CREATE INDEX idx_fs_data_ino_offset ON fs_data(ino, offset)",
+            "CREATE TABLE fs_symlink (ino INTEGER PRIMARY KEY, target TEXT NOT NULL)",
+            "INSERT INTO fs_inode VALUES (1, 16877, 0, 0, 0, 0, 0, 0)",
+            "INSERT INTO fs_inode VALUES (2, 16877, 0, 0, 0, 0, 0, 0)",
+            "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES ('old', 1, 2)",
+        ] {
+            conn.execute(sql, ()).await.unwrap();
+        }
+
+        let fs = Filesystem::from_connection(Arc::new(conn)).await.unwrap();
+        fs.write_file("/new.txt", b"x").await.unwrap();
+        let entries = fs.readdir_entries("/").await.unwrap().unwrap();
+        let types: Vec<_> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.d_type))
+            .collect();
+        assert_eq!(types, vec![("new.txt", 8), ("old", 4)]);
+        // The device number column is added too
+        assert_eq!(fs.stat("/old").await.unwrap().unwrap().rdev, 0);
+    }
+
+    #[tokio::test]
+    async fn test_readdir_lstats() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        agentfs.fs.mkdir("/dir/sub").await.unwrap();
         agentfs
-            .tools
-            .success(id, Some(serde_json::json!({"result": "success"})))
+            .fs
+            .write_file("/dir/file.txt", b"abc")
             .await
             .unwrap();
+        agentfs.fs.symlink("sub", "/dir/link").await.unwrap();
 
-        // Get the tool call
-        let call = agentfs.tools.get(id).await.unwrap().unwrap();
-        assert_eq!(call.name, "test_tool");
-        assert_eq!(call.status, ToolCallStatus::Success);
+        let entries = agentfs.fs.readdir_lstats("/dir").await.unwrap().unwrap();
+        let names: Vec<_> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["file.txt", "link", "sub"]);
+        assert!(entries[0].1.is_file());
+        assert_eq!(entries[0].1.size, 3);
+        // The symlink to a directory is reported as the symlink itself
+        assert!(entries[1].1.is_symlink());
+        let link = agentfs.fs.lstat("/dir/link").await.unwrap().unwrap();
+        assert_eq!(entries[1].1.ino, link.ino);
+        assert!(entries[2].1.is_directory());
+        assert!(agentfs
+            .fs
+            .readdir_lstats("/missing")
+            .await
+            .unwrap()
+            .is_none());
+    }
 
-        // Get stats
-        let stats = agentfs.tools.stats_for("test_tool").await.unwrap().unwrap();
-        assert_eq!(stats.total_calls, 1);
-        assert_eq!(stats.successful, 1);
+    #[tokio::test]
+    async fn test_stat_many() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        agentfs.fs.write_file("/dir/a", b"abc").await.unwrap();
+        agentfs.fs.write_file("/dir/b", b"hello").await.unwrap();
+        agentfs.fs.symlink("a", "/dir/link").await.unwrap();
+
+        let paths: Vec<String> = ["/dir/b", "/missing", "/dir/link", "/dir", "/dir/b"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let stats = agentfs.fs.stat_many(&paths).await.unwrap();
+        assert_eq!(stats.len(), paths.len());
+        for (path, st) in paths.iter().zip(&stats) {
+            let expected = agentfs.fs.stat(path).await.unwrap();
+            assert_eq!(st.as_ref().map(|s| s.ino), expected.as_ref().map(|s| s.ino));
+            assert_eq!(
+                st.as_ref().map(|s| (s.mode, s.nlink, s.size)),
+                expected.map(|s| (s.mode, s.nlink, s.size))
+            );
+        }
+        assert_eq!(stats[0].as_ref().unwrap().size, 5);
+        assert!(stats[1].is_none());
+        // The symlink is followed to its target
+        assert_eq!(stats[2].as_ref().unwrap().size, 3);
+        assert!(stats[3].as_ref().unwrap().is_directory());
+        assert!(agentfs.fs.stat_many(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_writers_wait_for_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let db_path = db_path.to_str().unwrap().to_string();
+        Filesystem::new(&db_path).await.unwrap();
+
+        // Each writer has its own connection, like separate processes
+        let writers: Vec<_> = (0..4)
+            .map(|i| {
+                let db_path = db_path.clone();
+                tokio::spawn(async move {
+                    let fs = Filesystem::new(&db_path).await.unwrap();
+                    for j in 0..25 {
+                        fs.write_file(&format!("/{}-{}.txt", i, j), b"data")
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        let fs = Filesystem::new(&db_path).await.unwrap();
+        assert_eq!(fs.readdir("/").await.unwrap().unwrap().len(), 100);
+        assert_eq!(fs.busy_timeout(), DEFAULT_BUSY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_content_hash() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.write_file("/a.txt", b"abc").await.unwrap();
+        agentfs.fs.write_file("/b.txt", b"abc").await.unwrap();
+        agentfs.fs.write_file("/c.txt", b"abd").await.unwrap();
+
+        let a = agentfs.fs.content_hash("/a.txt").await.unwrap().unwrap();
+        assert_eq!(a[..4], [0xba, 0x78, 0x16, 0xbf]);
+        let b = agentfs.fs.content_hash("/b.txt").await.unwrap().unwrap();
+        assert_eq!(a, b);
+        let c = agentfs.fs.content_hash("/c.txt").await.unwrap().unwrap();
+        assert_ne!(a, c);
+        assert!(agentfs.fs.content_hash("/missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_check_reports_dangling_dentry() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.write_file("/ok.txt", b"fine").await.unwrap();
+        assert!(agentfs.fs.check(false).await.unwrap().is_empty());
+
+        let conn = agentfs.get_connection();
+        conn.execute(
+            "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES ('ghost', 1, 999, 8)",
+            (),
+        )
+        .await
+        .unwrap();
+        let problems = agentfs.fs.check(false).await.unwrap();
+        assert_eq!(
+            problems,
+            vec![IntegrityProblem::DanglingDentry {
+                parent_ino: 1,
+                name: "ghost".to_string(),
+                ino: 999,
+            }]
+        );
+        // Checking without repair leaves the entry in place
+        assert_eq!(agentfs.fs.check(false).await.unwrap(), problems);
+
+        assert_eq!(agentfs.fs.check(true).await.unwrap(), problems);
+        assert!(agentfs.fs.check(false).await.unwrap().is_empty());
+        assert_eq!(
+            agentfs.fs.readdir("/").await.unwrap().unwrap(),
+            vec!["ok.txt"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_repairs_chunks_and_sizes() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.write_file("/a.txt", b"hello").await.unwrap();
+        agentfs.fs.write_file("/b.txt", b"world").await.unwrap();
+        let a = agentfs.fs.lstat("/a.txt").await.unwrap().unwrap().ino;
+        let b = agentfs.fs.lstat("/b.txt").await.unwrap().unwrap().ino;
+
+        let conn = agentfs.get_connection();
+        conn.execute(
+            "INSERT INTO fs_data (ino, offset, size, data) VALUES (?, 3, 3, ?)",
+            (a, b"LOW".as_slice()),
+        )
+        .await
+        .unwrap();
+        conn.execute("UPDATE fs_inode SET size = 2 WHERE ino = ?", (b,))
+            .await
+            .unwrap();
+        conn.execute(
+            "INSERT INTO fs_data (ino, offset, size, data) VALUES (999, 0, 1, x'00')",
+            (),
+        )
+        .await
+        .unwrap();
+
+        let problems = agentfs.fs.check(true).await.unwrap();
+        assert_eq!(
+            problems,
+            vec![
+                IntegrityProblem::DanglingData {
+                    ino: 999,
+                    chunks: 1
+                },
+                IntegrityProblem::OverlappingChunks { ino: a, offset: 3 },
+                IntegrityProblem::SizeMismatch {
+                    ino: a,
+                    size: 5,
+                    data_size: 6
+                },
+                IntegrityProblem::SizeMismatch {
+                    ino: b,
+                    size: 2,
+                    data_size: 5
+                },
+            ]
+        );
+        assert!(agentfs.fs.check(false).await.unwrap().is_empty());
+        assert_eq!(
+            agentfs.fs.read_file("/a.txt").await.unwrap().unwrap(),
+            b"helLOW"
+        );
+        assert_eq!(
+            agentfs.fs.read_file("/b.txt").await.unwrap().unwrap(),
+            b"world"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recover_orphans() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let conn = agentfs.get_connection();
+        agentfs
+            .fs
+            .write_file("/lost.txt", b"precious")
+            .await
+            .unwrap();
+        let lost = agentfs.fs.lstat("/lost.txt").await.unwrap().unwrap().ino;
+        conn.execute("DELETE FROM fs_dentry WHERE ino = ?", (lost,))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            agentfs.fs.check(false).await.unwrap(),
+            vec![IntegrityProblem::OrphanInode { ino: lost }]
+        );
+        agentfs.fs.check(true).await.unwrap();
+        assert!(agentfs.fs.check(false).await.unwrap().is_empty());
+        let relinked = format!("/lost+found/#{}", lost);
+        assert_eq!(
+            agentfs.fs.read_file(&relinked).await.unwrap().unwrap(),
+            b"precious"
+        );
+
+        // Deleting an orphan directory also deletes what is in it
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        agentfs.fs.write_file("/dir/file", b"junk").await.unwrap();
+        let dir = agentfs.fs.lstat("/dir").await.unwrap().unwrap().ino;
+        let file = agentfs.fs.lstat("/dir/file").await.unwrap().unwrap().ino;
+        conn.execute("DELETE FROM fs_dentry WHERE ino = ?", (dir,))
+            .await
+            .unwrap();
+        let recovered = agentfs
+            .fs
+            .recover_orphans(OrphanAction::Delete)
+            .await
+            .unwrap();
+        assert_eq!(recovered, vec![dir, file]);
+        assert!(agentfs.fs.stat_inode(dir).await.unwrap().is_none());
+        assert!(agentfs.fs.stat_inode(file).await.unwrap().is_none());
+        assert!(agentfs.fs.check(false).await.unwrap().is_empty());
+        assert_eq!(
+            agentfs.fs.read_file(&relinked).await.unwrap().unwrap(),
+            b"precious"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disk_usage_counts_hard_links_once() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.mkdir("/a").await.unwrap();
+        agentfs.fs.mkdir("/b").await.unwrap();
+        agentfs.fs.write_file("/a/one", &[0; 100]).await.unwrap();
+        agentfs.fs.write_file("/a/two", &[0; 50]).await.unwrap();
+        agentfs.fs.write_file("/b/big", &[0; 1000]).await.unwrap();
+
+        // Hard link /b/one to the inode of /a/one
+        let one = agentfs.fs.lstat("/a/one").await.unwrap().unwrap();
+        let b = agentfs.fs.lstat("/b").await.unwrap().unwrap();
+        agentfs
+            .get_connection()
+            .execute(
+                "INSERT INTO fs_dentry (name, parent_ino, ino, d_type) VALUES ('one', ?, ?, 8)",
+                (b.ino, one.ino),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(agentfs.fs.disk_usage("/a").await.unwrap(), Some(150));
+        assert_eq!(agentfs.fs.disk_usage("/b").await.unwrap(), Some(1100));
+        assert_eq!(agentfs.fs.disk_usage("/b/big").await.unwrap(), Some(1000));
+        assert_eq!(agentfs.fs.disk_usage("/").await.unwrap(), Some(1150));
+        assert_eq!(agentfs.fs.total_size().await.unwrap(), 1150);
+        assert_eq!(agentfs.fs.disk_usage("/missing").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_copy_dir() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.mkdir("/src").await.unwrap();
+        fs.mkdir("/src/nested").await.unwrap();
+        fs.write_file("/src/top.txt", b"top").await.unwrap();
+        fs.write_file("/src/nested/script.sh", b"#!/bin/sh")
+            .await
+            .unwrap();
+        fs.symlink("../top.txt", "/src/nested/link").await.unwrap();
+        let script = fs.lstat("/src/nested/script.sh").await.unwrap().unwrap();
+        agentfs
+            .get_connection()
+            .execute(
+                "UPDATE fs_inode SET mode = ? WHERE ino = ?",
+                (0o100755, script.ino),
+            )
+            .await
+            .unwrap();
+
+        fs.copy_dir("/src", "/dst", false).await.unwrap();
+        assert_eq!(
+            fs.readdir("/dst").await.unwrap().unwrap(),
+            ["nested", "top.txt"]
+        );
+        assert_eq!(
+            fs.readdir("/dst/nested").await.unwrap().unwrap(),
+            ["link", "script.sh"]
+        );
+        assert_eq!(fs.read_file("/dst/top.txt").await.unwrap().unwrap(), b"top");
+        let copy = fs.lstat("/dst/nested/script.sh").await.unwrap().unwrap();
+        assert_ne!(copy.ino, script.ino);
+        assert_eq!(copy.mode, 0o100755);
+        let link = fs.lstat("/dst/nested/link").await.unwrap().unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(
+            fs.readlink("/dst/nested/link").await.unwrap().unwrap(),
+            "../top.txt"
+        );
+
+        // The destination exists now
+        assert!(fs.copy_dir("/src", "/dst", false).await.is_err());
+        fs.write_file("/src/top.txt", b"changed").await.unwrap();
+        fs.copy_dir("/src", "/dst", true).await.unwrap();
+        assert_eq!(
+            fs.read_file("/dst/top.txt").await.unwrap().unwrap(),
+            b"changed"
+        );
+
+        assert!(fs
+            .copy_dir("/src", "/src/nested/copy", false)
+            .await
+            .is_err());
+        assert!(fs.copy_dir("/missing", "/other", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_handle_seek_and_read() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.write_file("/file.txt", b"hello world").await.unwrap();
+
+        let mut file = fs
+            .open("/file.txt", OpenOptions::new().read(true).write(true))
+            .await
+            .unwrap();
+        assert_eq!(file.seek(SeekFrom::Start(6)).await.unwrap(), 6);
+        let mut buf = [0u8; 16];
+        let n = file.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"world");
+        assert_eq!(file.read(&mut buf).await.unwrap(), 0);
+
+        // Overwrite the middle of the file, then read it back from the end
+        file.seek(SeekFrom::Start(4)).await.unwrap();
+        file.write(b"O W").await.unwrap();
+        assert_eq!(file.seek(SeekFrom::End(-7)).await.unwrap(), 4);
+        let n = file.read(&mut buf[..3]).await.unwrap();
+        assert_eq!(&buf[..n], b"O W");
+        assert!(file.seek(SeekFrom::Current(-100)).await.is_err());
+        assert_eq!(
+            fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"hellO World"
+        );
+
+        // Writing past the end leaves a gap that reads as zeros
+        file.seek(SeekFrom::Start(13)).await.unwrap();
+        file.write(b"!").await.unwrap();
+        assert_eq!(
+            fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"hellO World\0\0!"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sparse_write() {
+        const GIB: i64 = 1 << 30;
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+
+        let mut file = fs
+            .open(
+                "/sparse.bin",
+                OpenOptions::new().read(true).write(true).create(true),
+            )
+            .await
+            .unwrap();
+        file.seek(SeekFrom::Start(GIB as u64)).await.unwrap();
+        assert_eq!(file.write(b"x").await.unwrap(), 1);
+
+        let stats = fs.stat("/sparse.bin").await.unwrap().unwrap();
+        assert_eq!(stats.size, GIB + 1);
+        let mut rows = agentfs
+            .get_connection()
+            .query(
+                "SELECT COUNT(*), SUM(size) FROM fs_data WHERE ino = ?",
+                (stats.ino,),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap(), turso::Value::Integer(1));
+        assert_eq!(row.get_value(1).unwrap(), turso::Value::Integer(1));
+
+        let mut buf = [0xff; 4];
+        assert_eq!(fs.read_inode_at(stats.ino, 0, &mut buf).await.unwrap(), 4);
+        assert_eq!(buf, [0; 4]);
+        assert_eq!(
+            fs.read_inode_at(stats.ino, GIB - 2, &mut buf)
+                .await
+                .unwrap(),
+            3
+        );
+        assert_eq!(&buf[..3], b"\0\0x");
+        assert!(fs.check(false).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_file_handle_append() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        assert!(fs
+            .open("/log.txt", OpenOptions::new().append(true))
+            .await
+            .is_err());
+
+        let mut file = fs
+            .open("/log.txt", OpenOptions::new().append(true).create(true))
+            .await
+            .unwrap();
+        file.write(b"one\n").await.unwrap();
+        // Appends ignore the position
+        file.seek(SeekFrom::Start(0)).await.unwrap();
+        file.write(b"two\n").await.unwrap();
+        file.flush().await.unwrap();
+        assert!(file.read(&mut [0u8; 4]).await.is_err());
+        assert_eq!(
+            fs.read_file("/log.txt").await.unwrap().unwrap(),
+            b"one\ntwo\n"
+        );
+
+        let mut file = fs
+            .open("/log.txt", OpenOptions::new().write(true).truncate(true))
+            .await
+            .unwrap();
+        assert_eq!(fs.read_file("/log.txt").await.unwrap().unwrap(), b"");
+        file.write(b"three\n").await.unwrap();
+        assert_eq!(fs.read_file("/log.txt").await.unwrap().unwrap(), b"three\n");
+    }
+
+    #[tokio::test]
+    async fn test_file_handle_set_len() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        let mut file = fs
+            .open(
+                "/file.txt",
+                OpenOptions::new().read(true).write(true).create(true),
+            )
+            .await
+            .unwrap();
+        file.write(b"abc").await.unwrap();
+        file.write(b"def").await.unwrap();
+
+        file.set_len(4).await.unwrap();
+        assert_eq!(fs.read_file("/file.txt").await.unwrap().unwrap(), b"abcd");
+        assert_eq!(fs.stat("/file.txt").await.unwrap().unwrap().size, 4);
+
+        file.set_len(6).await.unwrap();
+        assert_eq!(
+            fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"abcd\0\0"
+        );
+
+        // The position is unchanged by set_len
+        assert_eq!(file.seek(SeekFrom::Current(0)).await.unwrap(), 6);
+        file.set_len(0).await.unwrap();
+        assert_eq!(file.read(&mut [0u8; 4]).await.unwrap(), 0);
+        assert_eq!(fs.stat("/file.txt").await.unwrap().unwrap().size, 0);
+    }
+
+    #[tokio::test]
+    async fn test_exists_is_dir_is_file() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.mkdir("/dir").await.unwrap();
+        fs.write_file("/dir/file", b"x").await.unwrap();
+        fs.symlink("dir", "/link").await.unwrap();
+        fs.symlink("missing", "/dangling").await.unwrap();
+
+        for path in ["/", "/dir", "/dir/file", "/link", "/dangling"] {
+            assert!(fs.exists(path).await.unwrap(), "{path}");
+        }
+        assert!(!fs.exists("/missing").await.unwrap());
+        assert!(!fs.exists("/dir/file/sub").await.unwrap());
+
+        assert!(fs.is_dir("/dir").await.unwrap());
+        assert!(fs.is_dir("/link").await.unwrap());
+        assert!(!fs.is_dir("/dir/file").await.unwrap());
+        assert!(!fs.is_dir("/missing").await.unwrap());
+        assert!(fs.is_file("/dir/file").await.unwrap());
+        assert!(!fs.is_file("/dir").await.unwrap());
+        assert!(!fs.is_file("/dangling").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_mkdir_all() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.mkdir("/a").await.unwrap();
+        fs.mkdir_all("/a/b/c/d").await.unwrap();
+        for path in ["/a/b", "/a/b/c", "/a/b/c/d"] {
+            assert!(fs.stat(path).await.unwrap().unwrap().is_directory());
+        }
+
+        // An existing directory is fine, an existing file is not
+        fs.mkdir_all("/a/b/c/d/").await.unwrap();
+        fs.mkdir_all("/").await.unwrap();
+        fs.write_file("/a/file", b"x").await.unwrap();
+        assert!(fs.mkdir_all("/a/file").await.is_err());
+        assert!(fs.mkdir_all("/a/file/sub").await.is_err());
+        assert!(fs.lstat("/a/file/sub").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mknod() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.mkfifo("/pipe", 0o644).await.unwrap();
+        let stats = fs.lstat("/pipe").await.unwrap().unwrap();
+        assert_eq!(stats.mode, 0o010644);
+        assert!(!stats.is_file());
+        let entries = fs.readdir_entries("/").await.unwrap().unwrap();
+        // DT_FIFO
+        assert_eq!(entries[0].d_type, 1);
+
+        fs.mknod("/file", 0o600, 0).await.unwrap();
+        let stats = fs.lstat("/file").await.unwrap().unwrap();
+        assert!(stats.is_file());
+        assert_eq!(stats.mode & 0o7777, 0o600);
+
+        // The device number is only kept for devices
+        fs.mknod("/socket", 0o140755, 7).await.unwrap();
+        let stats = fs.lstat("/socket").await.unwrap().unwrap();
+        assert_eq!((stats.mode, stats.rdev), (0o140755, 0));
+        fs.mknod("/null", 0o020666, 0x103).await.unwrap();
+        let stats = fs.stat("/null").await.unwrap().unwrap();
+        assert_eq!((stats.mode, stats.rdev), (0o020666, 0x103));
+
+        assert!(fs.mknod("/file", 0o100644, 0).await.is_err());
+        assert!(fs.mknod("/missing/file", 0o100644, 0).await.is_err());
+        assert!(fs.mknod("/dir", 0o040755, 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chmod_and_chown() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.write_file("/file.txt", b"hello").await.unwrap();
+        fs.mkdir("/dir").await.unwrap();
+        fs.symlink("file.txt", "/link").await.unwrap();
+
+        fs.chmod("/file.txt", 0o600).await.unwrap();
+        let stats = fs.stat("/file.txt").await.unwrap().unwrap();
+        assert_eq!(stats.mode, 0o100600);
+        // The file type cannot be changed
+        fs.chmod("/dir", 0o040700 | 0o4000).await.unwrap();
+        assert_eq!(fs.stat("/dir").await.unwrap().unwrap().mode, 0o044700);
+        fs.chmod("/dir", 0o100755).await.unwrap();
+        assert!(fs.stat("/dir").await.unwrap().unwrap().is_directory());
+
+        fs.chown("/file.txt", 1000, 100).await.unwrap();
+        let stats = fs.stat("/file.txt").await.unwrap().unwrap();
+        assert_eq!((stats.uid, stats.gid), (1000, 100));
+        // -1 leaves an ID unchanged
+        fs.chown("/file.txt", u32::MAX, 200).await.unwrap();
+        let stats = fs.stat("/file.txt").await.unwrap().unwrap();
+        assert_eq!((stats.uid, stats.gid), (1000, 200));
+        fs.chown("/file.txt", 0, u32::MAX).await.unwrap();
+        let stats = fs.stat("/file.txt").await.unwrap().unwrap();
+        assert_eq!((stats.uid, stats.gid), (0, 200));
+
+        // Symlinks are followed
+        fs.chmod("/link", 0o640).await.unwrap();
+        fs.chown("/link", 7, 7).await.unwrap();
+        let stats = fs.stat("/file.txt").await.unwrap().unwrap();
+        assert_eq!((stats.mode, stats.uid, stats.gid), (0o100640, 7, 7));
+        let link = fs.lstat("/link").await.unwrap().unwrap();
+        assert!(link.is_symlink());
+        assert_eq!((link.uid, link.gid), (0, 0));
+
+        assert!(fs.chmod("/missing", 0o644).await.is_err());
+        assert!(fs.chown("/missing", 0, 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chmod_recursive() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.mkdir_all("/tree/a/b").await.unwrap();
+        fs.write_file("/tree/top.txt", b"top").await.unwrap();
+        fs.write_file("/tree/a/b/deep.txt", b"deep").await.unwrap();
+        fs.symlink("top.txt", "/tree/a/link").await.unwrap();
+        fs.write_file("/outside.txt", b"outside").await.unwrap();
+        fs.symlink("/outside.txt", "/tree/escape").await.unwrap();
+        let link_mode = fs.lstat("/tree/a/link").await.unwrap().unwrap().mode;
+
+        fs.chmod_recursive("/tree", 0o700, 0o600).await.unwrap();
+
+        for dir in ["/tree", "/tree/a", "/tree/a/b"] {
+            assert_eq!(fs.stat(dir).await.unwrap().unwrap().mode, 0o040700);
+        }
+        for file in ["/tree/top.txt", "/tree/a/b/deep.txt"] {
+            assert_eq!(fs.stat(file).await.unwrap().unwrap().mode, 0o100600);
+        }
+        // Symlinks and their targets outside the tree are untouched
+        for link in ["/tree/a/link", "/tree/escape"] {
+            assert_eq!(fs.lstat(link).await.unwrap().unwrap().mode, link_mode);
+        }
+        let outside = fs.stat("/outside.txt").await.unwrap().unwrap();
+        assert_eq!(outside.mode, 0o100644);
+
+        // A file on its own gets the file mode
+        fs.chmod_recursive("/outside.txt", 0o755, 0o640)
+            .await
+            .unwrap();
+        let outside = fs.stat("/outside.txt").await.unwrap().unwrap();
+        assert_eq!(outside.mode, 0o100640);
+
+        assert!(fs.chmod_recursive("/missing", 0o755, 0o644).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_times() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.write_file("/file.txt", b"hello").await.unwrap();
+        fs.symlink("file.txt", "/link").await.unwrap();
+
+        fs.set_times("/file.txt", Some(1_000_000), Some(2_000_000))
+            .await
+            .unwrap();
+        let stats = fs.stat("/file.txt").await.unwrap().unwrap();
+        assert_eq!((stats.atime, stats.mtime), (1_000_000, 2_000_000));
+
+        // Only the modification time
+        fs.set_times("/file.txt", None, Some(3_000_000))
+            .await
+            .unwrap();
+        let stats = fs.stat("/file.txt").await.unwrap().unwrap();
+        assert_eq!((stats.atime, stats.mtime), (1_000_000, 3_000_000));
+        assert!(stats.ctime > 3_000_000);
+
+        // Leaving both unchanged does not touch the change time
+        agentfs
+            .get_connection()
+            .execute("UPDATE fs_inode SET ctime = 0 WHERE ino = ?", (stats.ino,))
+            .await
+            .unwrap();
+        fs.set_times("/file.txt", None, None).await.unwrap();
+        assert_eq!(fs.stat("/file.txt").await.unwrap().unwrap().ctime, 0);
+
+        // Symlinks are followed
+        fs.set_times("/link", Some(5), None).await.unwrap();
+        assert_eq!(fs.stat("/file.txt").await.unwrap().unwrap().atime, 5);
+        assert_ne!(fs.lstat("/link").await.unwrap().unwrap().atime, 5);
+
+        assert!(fs.set_times("/missing", Some(0), Some(0)).await.is_err());
+    }
+
+    /// Number of data chunks of `ino` that end past `offset`
+    async fn chunks_from(agentfs: &AgentFS, ino: i64, offset: i64) -> i64 {
+        let mut rows = agentfs
+            .get_connection()
+            .query(
+                "SELECT COUNT(*) FROM fs_data WHERE ino = ? AND offset + size > ?",
+                (ino, offset),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        *row.get_value(0).unwrap().as_integer().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_truncate() {
+        use tokio::io::AsyncReadExt;
+
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.write_file("/file.txt", b"hello world").await.unwrap();
+
+        fs.truncate("/file.txt", 5).await.unwrap();
+        assert_eq!(fs.read_file("/file.txt").await.unwrap().unwrap(), b"hello");
+        assert_eq!(fs.stat("/file.txt").await.unwrap().unwrap().size, 5);
+
+        // Growing reads back as zeros without storing them
+        fs.truncate("/file.txt", 8).await.unwrap();
+        assert_eq!(
+            fs.read_file("/file.txt").await.unwrap().unwrap(),
+            b"hello\0\0\0"
+        );
+        assert_eq!(fs.stat("/file.txt").await.unwrap().unwrap().size, 8);
+        let ino = fs.stat("/file.txt").await.unwrap().unwrap().ino;
+        let mut rows = agentfs
+            .get_connection()
+            .query("SELECT SUM(size) FROM fs_data WHERE ino = ?", (ino,))
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap(), turso::Value::Integer(5));
+        assert_eq!(chunks_from(&agentfs, ino, 5).await, 0);
+        assert!(fs.check(false).await.unwrap().is_empty());
+
+        // Holes read as zeros through every read path
+        fs.truncate("/file.txt", 100_000).await.unwrap();
+        assert_eq!(chunks_from(&agentfs, ino, 5).await, 0);
+        let mut expected = b"hello".to_vec();
+        expected.resize(100_000, 0);
+        assert_eq!(fs.read_file("/file.txt").await.unwrap().unwrap(), expected);
+        let mut reader = fs.open_reader("/file.txt").await.unwrap().unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, expected);
+        let mut buf = [0xff; 4];
+        assert_eq!(fs.read_inode_at(ino, 50_000, &mut buf).await.unwrap(), 4);
+        assert_eq!(buf, [0; 4]);
+
+        // Writing into the hole keeps the zeros around it
+        fs.write_inode_at(ino, 10, b"x").await.unwrap();
+        let data = fs.read_file("/file.txt").await.unwrap().unwrap();
+        assert_eq!(&data[..12], b"hello\0\0\0\0\0x\0");
+        assert_eq!(data.len(), 100_000);
+
+        fs.truncate("/file.txt", 3).await.unwrap();
+        assert_eq!(fs.read_file("/file.txt").await.unwrap().unwrap(), b"hel");
+
+        fs.truncate("/file.txt", 0).await.unwrap();
+        assert!(fs.read_file("/file.txt").await.unwrap().unwrap().is_empty());
+        assert_eq!(fs.stat("/file.txt").await.unwrap().unwrap().size, 0);
+
+        assert!(fs.truncate("/missing.txt", 0).await.is_err());
+        fs.mkdir("/dir").await.unwrap();
+        assert!(fs.truncate("/dir", 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rename() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        agentfs.fs.write_file("/file.txt", b"hello").await.unwrap();
+        let ino = agentfs.fs.lstat("/file.txt").await.unwrap().unwrap().ino;
+        let dir = agentfs.fs.lstat("/dir").await.unwrap().unwrap().ino;
+        agentfs
+            .get_connection()
+            .execute("UPDATE fs_inode SET ctime = 0 WHERE ino = ?", (ino,))
+            .await
+            .unwrap();
+
+        agentfs
+            .fs
+            .rename("/file.txt", "/dir/moved.txt")
+            .await
+            .unwrap();
+        assert!(agentfs.fs.lstat("/file.txt").await.unwrap().is_none());
+        let stats = agentfs.fs.lstat("/dir/moved.txt").await.unwrap().unwrap();
+        assert_eq!(stats.ino, ino);
+        assert!(stats.ctime > 0);
+        assert_eq!(
+            agentfs
+                .fs
+                .read_file("/dir/moved.txt")
+                .await
+                .unwrap()
+                .unwrap(),
+            b"hello"
+        );
+        let mut rows = agentfs
+            .get_connection()
+            .query("SELECT parent_ino FROM fs_dentry WHERE ino = ?", (ino,))
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap().as_integer().copied(), Some(dir));
+
+        // A directory moves with its contents, but not into itself
+        agentfs.fs.rename("/dir", "/renamed").await.unwrap();
+        assert!(agentfs
+            .fs
+            .lstat("/renamed/moved.txt")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(agentfs.fs.rename("/renamed", "/renamed/sub").await.is_err());
+
+        // A directory only replaces an empty directory
+        agentfs.fs.mkdir("/empty").await.unwrap();
+        assert!(agentfs.fs.rename("/empty", "/renamed").await.is_err());
+        agentfs.fs.rename("/renamed", "/empty").await.unwrap();
+        assert!(agentfs
+            .fs
+            .lstat("/empty/moved.txt")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(agentfs
+            .fs
+            .rename("/empty/moved.txt", "/empty")
+            .await
+            .is_err());
+        assert!(agentfs.fs.rename("/missing", "/other").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rename_overwrites_file() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        agentfs.fs.write_file("/new.txt", b"new").await.unwrap();
+        agentfs.fs.write_file("/old.txt", b"old").await.unwrap();
+        let old = agentfs.fs.lstat("/old.txt").await.unwrap().unwrap().ino;
+
+        agentfs.fs.rename("/new.txt", "/old.txt").await.unwrap();
+        assert_eq!(
+            agentfs.fs.read_file("/old.txt").await.unwrap().unwrap(),
+            b"new"
+        );
+        assert_eq!(
+            agentfs.fs.readdir("/").await.unwrap().unwrap(),
+            vec!["old.txt"]
+        );
+
+        // The replaced inode was the last link, so it and its data are gone
+        assert!(agentfs.fs.stat_inode(old).await.unwrap().is_none());
+        let mut rows = agentfs
+            .get_connection()
+            .query("SELECT COUNT(*) FROM fs_data WHERE ino = ?", (old,))
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap().as_integer().copied(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_rename_noreplace() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.write_file("/tmp.txt", b"new").await.unwrap();
+        fs.write_file("/taken.txt", b"old").await.unwrap();
+
+        // A free name is taken like with rename
+        fs.rename_noreplace("/tmp.txt", "/free.txt").await.unwrap();
+        assert_eq!(fs.read_file("/free.txt").await.unwrap().unwrap(), b"new");
+        assert!(fs.lstat("/tmp.txt").await.unwrap().is_none());
+
+        // An existing name is left alone
+        let err = fs
+            .rename_noreplace("/free.txt", "/taken.txt")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{}", err);
+        assert_eq!(fs.read_file("/taken.txt").await.unwrap().unwrap(), b"old");
+        assert_eq!(fs.read_file("/free.txt").await.unwrap().unwrap(), b"new");
+
+        // Even when both names are links to the same file
+        let ino = fs.lstat("/free.txt").await.unwrap().unwrap().ino;
+        fs.link_inode(ino, "/hardlink.txt").await.unwrap();
+        assert!(fs
+            .rename_noreplace("/free.txt", "/hardlink.txt")
+            .await
+            .is_err());
+        assert!(fs.lstat("/free.txt").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_unlinked_inode() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+
+        let ino = fs.create_unlinked().await.unwrap();
+        fs.write_inode(ino, b"scratch").await.unwrap();
+        let stats = fs.stat_inode(ino).await.unwrap().unwrap();
+        assert!(stats.is_file());
+        assert_eq!(stats.nlink, 0);
+        assert_eq!(stats.size, 7);
+        assert!(fs.readdir("/").await.unwrap().unwrap().is_empty());
+
+        fs.link_inode(ino, "/named.txt").await.unwrap();
+        assert_eq!(
+            fs.read_file("/named.txt").await.unwrap().unwrap(),
+            b"scratch"
+        );
+        assert_eq!(fs.stat("/named.txt").await.unwrap().unwrap().ino, ino);
+        assert!(fs.link_inode(ino, "/named.txt").await.is_err());
+        assert!(fs.link_inode(ino, "/missing/named.txt").await.is_err());
+
+        fs.write_inode_at(ino, 3, b"ATCH").await.unwrap();
+        fs.write_inode_at(ino, 9, b"!").await.unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(fs.read_inode_at(ino, 0, &mut buf).await.unwrap(), 10);
+        assert_eq!(&buf[..10], b"scrATCH\0\0!");
+        fs.set_inode_len(ino, 3).await.unwrap();
+        assert_eq!(fs.read_inode_at(ino, 0, &mut buf).await.unwrap(), 3);
+        assert_eq!(fs.read_inode_at(ino, 3, &mut buf).await.unwrap(), 0);
+
+        // A linked inode is kept, an unlinked one is deleted
+        assert!(!fs.release_inode(ino).await.unwrap());
+        let orphan = fs.create_unlinked().await.unwrap();
+        fs.write_inode(orphan, b"gone").await.unwrap();
+        assert!(fs.release_inode(orphan).await.unwrap());
+        assert!(fs.stat_inode(orphan).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_xattrs() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.write_file("/file.txt", b"data").await.unwrap();
+
+        assert_eq!(fs.getxattr("/file.txt", "user.test").await.unwrap(), None);
+        fs.setxattr("/file.txt", "user.test", b"hello")
+            .await
+            .unwrap();
+        fs.setxattr("/file.txt", "user.other", b"").await.unwrap();
+        assert_eq!(
+            fs.getxattr("/file.txt", "user.test").await.unwrap(),
+            Some(b"hello".to_vec())
+        );
+        fs.setxattr("/file.txt", "user.test", b"world")
+            .await
+            .unwrap();
+        assert_eq!(
+            fs.getxattr("/file.txt", "user.test").await.unwrap(),
+            Some(b"world".to_vec())
+        );
+        assert_eq!(
+            fs.listxattr("/file.txt").await.unwrap().unwrap(),
+            ["user.other", "user.test"]
+        );
+
+        assert!(fs.removexattr("/file.txt", "user.test").await.unwrap());
+        assert!(!fs.removexattr("/file.txt", "user.test").await.unwrap());
+        assert_eq!(
+            fs.listxattr("/file.txt").await.unwrap().unwrap(),
+            ["user.other"]
+        );
+
+        assert!(fs.listxattr("/missing").await.unwrap().is_none());
+        assert!(fs.setxattr("/missing", "user.test", b"x").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_count() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = agentfs.fs.clone();
+
+        // BEGIN, lookup, inode insert, last_insert_rowid, dentry insert,
+        // data insert, size update, COMMIT
+        let before = fs.query_count();
+        fs.write_file("/file.txt", b"hello").await.unwrap();
+        assert_eq!(fs.query_count() - before, 8);
+
+        // BEGIN, lookup, data delete, data insert, size update, COMMIT
+        let before = agentfs.fs.query_count();
+        fs.write_file("/file.txt", b"world").await.unwrap();
+        assert_eq!(agentfs.fs.query_count() - before, 6);
+
+        // One dentry query per component
+        let before = fs.query_count();
+        let ino = fs.lookup("/file.txt").await.unwrap().unwrap();
+        assert_eq!(fs.query_count() - before, 1);
+        assert_eq!(fs.lstat("/file.txt").await.unwrap().unwrap().ino, ino);
+        assert_eq!(fs.lookup("/missing").await.unwrap(), None);
+    }
+
+    /// Read `path` through `reader` in a loop while `writes` runs, passing
+    /// every version read to `check`, and return the number of reads
+    async fn read_during(
+        reader: Filesystem,
+        path: &'static str,
+        check: impl Fn(&[u8]) + Send + 'static,
+        writes: impl Future<Output = ()>,
+    ) -> usize {
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let reader_task = {
+            let done = done.clone();
+            tokio::spawn(async move {
+                let mut reads = 0;
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    let data = reader.read_file(path).await.unwrap();
+                    check(&data.expect("reader saw the file missing"));
+                    reads += 1;
+                    tokio::task::yield_now().await;
+                }
+                reads
+            })
+        };
+        writes.await;
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        reader_task.await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_write_file_is_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let writer = Filesystem::from_connection(Arc::new(db.connect().unwrap()))
+            .await
+            .unwrap();
+        let reader = Filesystem::from_connection(Arc::new(db.connect().unwrap()))
+            .await
+            .unwrap();
+
+        const SIZE: usize = 1024 * 1024;
+        writer
+            .write_file("/big.bin", &vec![0u8; SIZE])
+            .await
+            .unwrap();
+
+        let check = |data: &[u8]| {
+            assert_eq!(data.len(), SIZE, "reader saw a partial write");
+            assert!(data.iter().all(|&b| b == data[0]), "reader saw mixed data");
+        };
+        let transactions = writer.transaction_count();
+        let reads = read_during(reader, "/big.bin", check, async {
+            for i in 1..=8u8 {
+                writer.write_file("/big.bin", &vec![i; SIZE]).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        })
+        .await;
+        assert!(reads > 0);
+        assert_eq!(writer.transaction_count() - transactions, 8);
+
+        let data = writer.read_file("/big.bin").await.unwrap().unwrap();
+        assert_eq!(data, vec![8u8; SIZE]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_write_file_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let writer = Filesystem::from_connection(Arc::new(db.connect().unwrap()))
+            .await
+            .unwrap();
+        let reader = Filesystem::from_connection(Arc::new(db.connect().unwrap()))
+            .await
+            .unwrap();
+
+        // Alternate between a large and a small file, so a reader that saw a
+        // truncated or partially written file would get an unexpected length
+        const SIZE: usize = 1024 * 1024;
+        let contents = |i: u8| vec![i; if i.is_multiple_of(2) { SIZE } else { SIZE / 8 }];
+        writer.write_file("/big.bin", &contents(0)).await.unwrap();
+        writer.chmod("/big.bin", 0o600).await.unwrap();
+        let old_ino = writer.lookup("/big.bin").await.unwrap().unwrap();
+
+        let check = move |data: &[u8]| {
+            assert_eq!(data, contents(data[0]), "reader saw a partial write");
+        };
+        let reads = read_during(reader, "/big.bin", check, async {
+            for i in 1..=8u8 {
+                writer
+                    .write_file_atomic("/big.bin", &contents(i))
+                    .await
+                    .unwrap();
+                tokio::task::yield_now().await;
+            }
+        })
+        .await;
+        assert!(reads > 0);
+
+        let data = writer.read_file("/big.bin").await.unwrap().unwrap();
+        assert_eq!(data, contents(8));
+        // The file was replaced by a new inode that kept the old mode, and
+        // no temporary file or old inode was left behind
+        let stats = writer.stat("/big.bin").await.unwrap().unwrap();
+        assert_ne!(stats.ino, old_ino);
+        assert_eq!(stats.mode, 0o100600);
+        assert!(writer.stat_inode(old_ino).await.unwrap().is_none());
+        assert_eq!(writer.readdir("/").await.unwrap().unwrap(), vec!["big.bin"]);
+
+        // New files can be written too, but directories are not replaced
+        writer.write_file_atomic("/new.txt", b"new").await.unwrap();
+        let data = writer.read_file("/new.txt").await.unwrap().unwrap();
+        assert_eq!(data, b"new");
+        writer.mkdir("/dir").await.unwrap();
+        assert!(writer.write_file_atomic("/dir", b"x").await.is_err());
+        assert!(writer.write_file_atomic("/missing/a", b"x").await.is_err());
+        assert!(writer.is_dir("/dir").await.unwrap());
+    }
+
+    /// Load `files` files spread over 100 directories with
+    /// [`Filesystem::bulk_write`] and check that all of them were written
+    async fn check_bulk_write(fs: &Filesystem, files: usize) {
+        let path = |i: usize| format!("/seed/dir{}/file{}.txt", i % 100, i);
+        let contents = |i: usize| format!("contents of file {}", i).into_bytes();
+        let start = std::time::Instant::now();
+        fs.bulk_write((0..files).map(|i| (path(i), contents(i))))
+            .await
+            .unwrap();
+        eprintln!("bulk_write of {} files took {:?}", files, start.elapsed());
+
+        assert_eq!(fs.readdir("/seed").await.unwrap().unwrap().len(), 100);
+        for i in 0..files {
+            let data = fs.read_file(&path(i)).await.unwrap().unwrap();
+            assert_eq!(data, contents(i));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bulk_write() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let fs = &agentfs.fs;
+        fs.mkdir_all("/seed/dir0").await.unwrap();
+        fs.write_file("/seed/dir0/file0.txt", b"old").await.unwrap();
+
+        // More files than fit in one transaction, in existing and missing
+        // directories, overwriting an existing file
+        check_bulk_write(fs, 1500).await;
+
+        // A later entry for the same path wins, also within one transaction
+        let entries = vec![
+            ("/top.txt".to_string(), b"first".to_vec()),
+            ("/empty.txt".to_string(), Vec::new()),
+            ("top.txt".to_string(), b"second".to_vec()),
+        ];
+        fs.bulk_write(entries).await.unwrap();
+        let data = fs.read_file("/top.txt").await.unwrap().unwrap();
+        assert_eq!(data, b"second");
+        let stats = fs.stat("/empty.txt").await.unwrap().unwrap();
+        assert!(stats.is_file());
+        assert_eq!(stats.size, 0);
+
+        // A failed write rolls back the rest of its transaction
+        let entries = vec![
+            ("/ok.txt".to_string(), b"ok".to_vec()),
+            ("/top.txt/file.txt".to_string(), b"x".to_vec()),
+        ];
+        assert!(fs.bulk_write(entries).await.is_err());
+        assert!(!fs.exists("/ok.txt").await.unwrap());
+        fs.bulk_write(Vec::new()).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "loads 10k files, which is slow in debug builds"]
+    async fn test_bulk_write_10k_files() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        check_bulk_write(&agentfs.fs, 10_000).await;
+    }
+
+    #[tokio::test]
+    async fn test_write_file_rolls_back_on_error() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        // Parent does not exist, so the write fails and must not leave a transaction open
+        assert!(agentfs.fs.write_file("/missing/a.txt", b"x").await.is_err());
+
+        agentfs.fs.write_file("/a.txt", b"ok").await.unwrap();
+        let data = agentfs.fs.read_file("/a.txt").await.unwrap().unwrap();
+        assert_eq!(data, b"ok");
+    }
+
+    #[tokio::test]
+    async fn test_schema_initialized_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let db_path = db_path.to_str().unwrap();
+
+        let ddl_statements = || schema::DDL_STATEMENTS.with(|count| count.get());
+
+        // A fresh database creates the schema
+        let before = ddl_statements();
+        let agentfs = AgentFS::new(db_path).await.unwrap();
+        agentfs.fs.write_file("/a.txt", b"a").await.unwrap();
+        assert!(ddl_statements() > before);
+        drop(agentfs);
+
+        // Opening an existing database skips it
+        let before = ddl_statements();
+        let agentfs = AgentFS::new(db_path).await.unwrap();
+        assert_eq!(ddl_statements(), before);
+
+        let data = agentfs.fs.read_file("/a.txt").await.unwrap().unwrap();
+        assert_eq!(data, b"a");
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        // Start a tool call
+        let id = agentfs
+            .tools
+            .start(
+                "test_tool",
+                Some(serde_json::json!({"param": "value"})),
+                None,
+            )
+            .await
+            .unwrap();
+
+        // Mark it as successful
+        agentfs
+            .tools
+            .success(id, Some(serde_json::json!({"result": "success"})))
+            .await
+            .unwrap();
+
+        // Get the tool call
+        let call = agentfs.tools.get(id).await.unwrap().unwrap();
+        assert_eq!(call.name, "test_tool");
+        assert_eq!(call.status, ToolCallStatus::Success);
+
+        // Get stats
+        let stats = agentfs.tools.stats_for("test_tool").await.unwrap().unwrap();
+        assert_eq!(stats.total_calls, 1);
+        assert_eq!(stats.successful, 1);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_duration_percentiles() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        // Durations of 1 to 100 seconds, recorded out of order
+        for secs in (1..=100).rev() {
+            agentfs
+                .tools
+                .record("slow_tool", 1000, 1000 + secs, None, None, None)
+                .await
+                .unwrap();
+        }
+        agentfs
+            .tools
+            .record("fast_tool", 1000, 1001, None, None, None)
+            .await
+            .unwrap();
+        // Pending calls have no duration
+        agentfs.tools.start("slow_tool", None, None).await.unwrap();
+
+        let stats = agentfs.tools.stats_for("slow_tool").await.unwrap().unwrap();
+        assert_eq!(stats.total_calls, 101);
+        assert_eq!(stats.min_duration_ms, 1000);
+        assert_eq!(stats.max_duration_ms, 100_000);
+        assert_eq!(stats.p50_duration_ms, 50_000);
+        assert_eq!(stats.p95_duration_ms, 95_000);
+        assert_eq!(stats.p99_duration_ms, 99_000);
+
+        let all = agentfs.tools.stats().await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].name, "slow_tool");
+        assert_eq!(all[0].p95_duration_ms, 95_000);
+        assert_eq!(all[1].name, "fast_tool");
+        assert_eq!(all[1].min_duration_ms, 1000);
+        assert_eq!(all[1].p99_duration_ms, 1000);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_duration_on_completion() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let id = agentfs
+            .tools
+            .start("sleepy_tool", None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            agentfs.tools.get(id).await.unwrap().unwrap().duration_ms,
+            None
+        );
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        agentfs.tools.success(id, None).await.unwrap();
+
+        let call = agentfs.tools.get(id).await.unwrap().unwrap();
+        assert!(call.completed_at.unwrap() >= call.started_at);
+        let duration_ms = call.duration_ms.unwrap();
+        assert!((1100..1500).contains(&duration_ms), "{}", duration_ms);
+
+        let id = agentfs
+            .tools
+            .start("failing_tool", None, None)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        agentfs.tools.error(id, "failed").await.unwrap();
+        let call = agentfs.tools.get(id).await.unwrap().unwrap();
+        assert!(call.completed_at.is_some());
+        let duration_ms = call.duration_ms.unwrap();
+        assert!((50..500).contains(&duration_ms), "{}", duration_ms);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_cancel_and_timeout() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let cancelled = agentfs.tools.start("search", None, None).await.unwrap();
+        agentfs.tools.cancel(cancelled).await.unwrap();
+        let timed_out = agentfs.tools.start("search", None, None).await.unwrap();
+        agentfs.tools.timeout(timed_out).await.unwrap();
+        let ok = agentfs.tools.start("search", None, None).await.unwrap();
+        agentfs.tools.success(ok, None).await.unwrap();
+
+        let call = agentfs.tools.get(cancelled).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Cancelled);
+        assert!(call.completed_at.is_some());
+        assert!(call.duration_ms.is_some());
+        assert!(call.result.is_none());
+        assert!(call.error.is_none());
+        let call = agentfs.tools.get(timed_out).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Timeout);
+        assert!(call.completed_at.is_some());
+        assert!(call.duration_ms.is_some());
+
+        let stats = agentfs.tools.stats_for("search").await.unwrap().unwrap();
+        assert_eq!(stats.total_calls, 3);
+        assert_eq!(stats.successful, 1);
+        assert_eq!(stats.failed, 0);
+        assert_eq!(stats.cancelled, 1);
+        assert_eq!(stats.timed_out, 1);
+        let all = agentfs.tools.stats().await.unwrap();
+        assert_eq!((all[0].cancelled, all[0].timed_out), (1, 1));
+
+        assert!(agentfs.tools.cancel(12345).await.is_err());
+
+        // Completed calls keep their status
+        let err = agentfs.tools.cancel(ok).await.unwrap_err();
+        assert!(err.to_string().contains("no longer pending"));
+        assert!(agentfs.tools.timeout(cancelled).await.is_err());
+        let call = agentfs.tools.get(ok).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Success);
+        let call = agentfs.tools.get(cancelled).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_success_and_error_require_pending() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let cancelled = agentfs.tools.start("search", None, None).await.unwrap();
+        agentfs.tools.cancel(cancelled).await.unwrap();
+        let before = agentfs.tools.get(cancelled).await.unwrap().unwrap();
+        let err = agentfs
+            .tools
+            .success(cancelled, Some(serde_json::json!("late")))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no longer pending"));
+
+        let timed_out = agentfs.tools.start("search", None, None).await.unwrap();
+        agentfs.tools.timeout(timed_out).await.unwrap();
+        let err = agentfs.tools.error(timed_out, "late").await.unwrap_err();
+        assert!(err.to_string().contains("no longer pending"));
+
+        let ok = agentfs.tools.start("search", None, None).await.unwrap();
+        agentfs.tools.success(ok, None).await.unwrap();
+        assert!(agentfs.tools.error(ok, "late").await.is_err());
+        assert!(agentfs.tools.success(ok, None).await.is_err());
+
+        // The completed calls keep their status, result and duration
+        let call = agentfs.tools.get(cancelled).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Cancelled);
+        assert!(call.result.is_none());
+        assert_eq!(call.duration_ms, before.duration_ms);
+        let call = agentfs.tools.get(timed_out).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Timeout);
+        assert!(call.error.is_none());
+        let call = agentfs.tools.get(ok).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Success);
+        assert!(call.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_metadata() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let run = |run_id: &str| Some(serde_json::json!({"run_id": run_id, "model": "small"}));
+        let first = agentfs
+            .tools
+            .start("search", None, run("r1"))
+            .await
+            .unwrap();
+        let other = agentfs
+            .tools
+            .start("search", None, run("r2"))
+            .await
+            .unwrap();
+        let second = agentfs.tools.start("fetch", None, run("r1")).await.unwrap();
+        agentfs.tools.start("fetch", None, None).await.unwrap();
+        agentfs.tools.success(first, None).await.unwrap();
+
+        let calls = agentfs
+            .tools
+            .find_by_metadata("run_id", &serde_json::json!("r1"))
+            .await
+            .unwrap();
+        let ids: Vec<i64> = calls.iter().map(|call| call.id).collect();
+        assert_eq!(ids, vec![first, second]);
+        assert_eq!(calls[0].metadata, run("r1"));
+        assert_eq!(calls[0].status, ToolCallStatus::Success);
+
+        let calls = agentfs
+            .tools
+            .find_by_metadata("model", &serde_json::json!("small"))
+            .await
+            .unwrap();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[1].id, other);
+        assert!(agentfs
+            .tools
+            .find_by_metadata("run_id", &serde_json::json!("r3"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let err = agentfs
+            .tools
+            .start("search", None, Some(serde_json::json!("r1")))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("must be a JSON object"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_metadata_column_added() {
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+
+        // Schema from before tool calls had metadata
+        for sql in [
+            "CREATE TABLE tool_calls (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, parameters TEXT, result TEXT, error TEXT, status TEXT NOT NULL DEFAULT 'pending', started_at INTEGER NOT NULL, completed_at INTEGER, duration_ms INTEGER)",
+            "CREATE INDEX idx_tool_calls_name ON tool_calls(name)",
+            "CREATE INDEX idx_tool_calls_started_at ON tool_calls(started_at)",
+            "CREATE TABLE tool_schemas (name TEXT PRIMARY KEY, schema TEXT NOT NULL)",
+            "INSERT INTO tool_calls (name, status, started_at) VALUES ('old', 'pending', 0)",
+        ] {
+            conn.execute(sql, ()).await.unwrap();
+        }
+
+        let tools = ToolCalls::from_connection(Arc::new(conn)).await.unwrap();
+        assert_eq!(tools.get(1).await.unwrap().unwrap().metadata, None);
+        // The millisecond start time column is added too, and calls started
+        // without it still get a duration in whole seconds
+        tools.success(1, None).await.unwrap();
+        let call = tools.get(1).await.unwrap().unwrap();
+        assert_eq!(
+            call.duration_ms,
+            Some((call.completed_at.unwrap() - call.started_at) * 1000)
+        );
+        let id = tools
+            .start("new", None, Some(serde_json::json!({"run_id": "r1"})))
+            .await
+            .unwrap();
+        let calls = tools
+            .find_by_metadata("run_id", &serde_json::json!("r1"))
+            .await
+            .unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, id);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_update() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+
+        let id = agentfs
+            .tools
+            .start("search", Some(serde_json::json!({"query": "rust"})), None)
+            .await
+            .unwrap();
+        agentfs
+            .tools
+            .update(
+                id,
+                Some(serde_json::json!({"query": "rust", "limit": 10})),
+                None,
+            )
+            .await
+            .unwrap();
+        agentfs
+            .tools
+            .update(id, None, Some(serde_json::json!({"hits": 3})))
+            .await
+            .unwrap();
+        let call = agentfs.tools.get(id).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Pending);
+        assert_eq!(call.result, Some(serde_json::json!({"hits": 3})));
+
+        agentfs
+            .tools
+            .success(id, Some(serde_json::json!({"hits": 7})))
+            .await
+            .unwrap();
+        let call = agentfs.tools.get(id).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Success);
+        assert_eq!(
+            call.parameters,
+            Some(serde_json::json!({"query": "rust", "limit": 10}))
+        );
+        assert_eq!(call.result, Some(serde_json::json!({"hits": 7})));
+
+        // Completed calls can't be updated
+        let err = agentfs
+            .tools
+            .update(id, Some(serde_json::json!({})), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already success"));
+        assert!(agentfs.tools.update(12345, None, None).await.is_err());
+
+        // New parameters are checked against the schema
+        agentfs
+            .tools
+            .register_tool(
+                "fetch",
+                serde_json::json!({"type": "object", "required": ["url"]}),
+            )
+            .await
+            .unwrap();
+        let id = agentfs
+            .tools
+            .start(
+                "fetch",
+                Some(serde_json::json!({"url": "https://example.com"})),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(agentfs
+            .tools
+            .update(id, Some(serde_json::json!({"method": "GET"})), None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_prune() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let day = 24 * 60 * 60;
+
+        let old = agentfs
+            .tools
+            .record(
+                "search",
+                now - 10 * day,
+                now - 10 * day + 1,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let recent = agentfs
+            .tools
+            .record("search", now - day, now - day + 1, None, None, None)
+            .await
+            .unwrap();
+        let pending = agentfs.tools.start("search", None, None).await.unwrap();
+
+        let deleted = agentfs
+            .tools
+            .prune(Duration::from_secs(7 * day as u64))
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert!(agentfs.tools.get(old).await.unwrap().is_none());
+        assert!(agentfs.tools.get(recent).await.unwrap().is_some());
+        assert!(agentfs.tools.get(pending).await.unwrap().is_some());
+
+        // Pending calls are never pruned
+        let deleted = agentfs.tools.prune(Duration::ZERO).await.unwrap();
+        assert_eq!(deleted, 1);
+        assert!(agentfs.tools.get(pending).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_tool_schema() {
+        let agentfs = AgentFS::new(":memory:").await.unwrap();
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "mode": { "enum": ["read", "write"] },
+                "lines": { "type": "array", "items": { "type": "integer" } }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        });
+        assert!(agentfs
+            .tools
+            .get_tool_schema("read_file")
+            .await
+            .unwrap()
+            .is_none());
+        agentfs
+            .tools
+            .register_tool("read_file", schema.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            agentfs.tools.get_tool_schema("read_file").await.unwrap(),
+            Some(schema)
+        );
+
+        let start = |parameters| agentfs.tools.start("read_file", parameters, None);
+        start(Some(
+            serde_json::json!({ "path": "/a", "mode": "read", "lines": [1, 2] }),
+        ))
+        .await
+        .unwrap();
+        for (parameters, message) in [
+            (None, "parameters must be of type object"),
+            (Some(serde_json::json!({})), "parameters.path is required"),
+            (
+                Some(serde_json::json!({ "path": 1 })),
+                "parameters.path must be of type string",
+            ),
+            (
+                Some(serde_json::json!({ "path": "/a", "mode": "append" })),
+                "parameters.mode must be one of the allowed values",
+            ),
+            (
+                Some(serde_json::json!({ "path": "/a", "lines": [1, "2"] })),
+                "parameters.lines[1] must be of type integer",
+            ),
+            (
+                Some(serde_json::json!({ "path": "/a", "extra": true })),
+                "parameters.extra is not allowed",
+            ),
+        ] {
+            let err = start(parameters).await.unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Invalid parameters for tool 'read_file': {}", message)
+            );
+        }
+        assert_eq!(agentfs.tools.recent(None).await.unwrap().len(), 1);
+
+        // Tools without a schema accept any parameters
+        agentfs
+            .tools
+            .start("other", Some(serde_json::json!(42)), None)
+            .await
+            .unwrap();
+
+        // Registering again replaces the schema
+        agentfs
+            .tools
+            .register_tool("read_file", serde_json::json!(true))
+            .await
+            .unwrap();
+        start(None).await.unwrap();
+        assert!(agentfs
+            .tools
+            .register_tool("bad", serde_json::json!("string"))
+            .await
+            .is_err());
     }
 }
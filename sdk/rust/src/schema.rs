@@ -0,0 +1,73 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use turso::{Connection, Value};
+
+/// Schema of a store, created at most once
+///
+/// Stores created eagerly initialize their schema up front; stores created
+/// lazily initialize it on first use.
+#[derive(Clone, Default)]
+pub(crate) struct LazySchema {
+    initialized: Arc<OnceCell<()>>,
+}
+
+impl LazySchema {
+    /// Run `init` unless the schema has already been initialized
+    pub(crate) async fn get_or_init<F, Fut>(&self, init: F) -> Result<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        self.initialized.get_or_try_init(init).await?;
+        Ok(())
+    }
+}
+
+/// Check whether all of the given tables and indexes already exist
+///
+/// Used to skip schema creation when opening an existing database.
+pub(crate) async fn exists(conn: &Connection, names: &[&str]) -> Result<bool> {
+    let mut rows = conn.query("SELECT name FROM sqlite_schema", ()).await?;
+
+    let mut existing = HashSet::new();
+    while let Some(row) = rows.next().await? {
+        if let Ok(Value::Text(name)) = row.get_value(0) {
+            existing.insert(name);
+        }
+    }
+
+    Ok(names.iter().all(|name| existing.contains(*name)))
+}
+
+/// Check whether `table` has a column named `column`
+pub(crate) async fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut rows = conn
+        .query(&format!("PRAGMA table_info({})", table), ())
+        .await?;
+    while let Some(row) = rows.next().await? {
+        if let Ok(Value::Text(name)) = row.get_value(1) {
+            if name == column {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Execute a schema (DDL) statement
+pub(crate) async fn execute_ddl(conn: &Connection, sql: &str) -> Result<()> {
+    #[cfg(test)]
+    DDL_STATEMENTS.with(|count| count.set(count.get() + 1));
+
+    conn.execute(sql, ()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of DDL statements executed on the current thread
+    pub(crate) static DDL_STATEMENTS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
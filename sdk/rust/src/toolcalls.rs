@@ -1,8 +1,11 @@
+use crate::schema::{self, LazySchema};
+use crate::{write_lock, TransactionLock};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use turso::{Builder, Connection, Value};
 
 /// Status of a tool call
@@ -12,6 +15,8 @@ pub enum ToolCallStatus {
     Pending,
     Success,
     Error,
+    Cancelled,
+    Timeout,
 }
 
 impl fmt::Display for ToolCallStatus {
@@ -20,6 +25,8 @@ impl fmt::Display for ToolCallStatus {
             ToolCallStatus::Pending => write!(f, "pending"),
             ToolCallStatus::Success => write!(f, "success"),
             ToolCallStatus::Error => write!(f, "error"),
+            ToolCallStatus::Cancelled => write!(f, "cancelled"),
+            ToolCallStatus::Timeout => write!(f, "timeout"),
         }
     }
 }
@@ -29,6 +36,8 @@ impl From<&str> for ToolCallStatus {
         match s {
             "success" => ToolCallStatus::Success,
             "error" => ToolCallStatus::Error,
+            "cancelled" => ToolCallStatus::Cancelled,
+            "timeout" => ToolCallStatus::Timeout,
             _ => ToolCallStatus::Pending,
         }
     }
@@ -51,6 +60,9 @@ pub struct ToolCall {
     pub completed_at: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration_ms: Option<i64>,
+    /// Labels attached when the call was started, as a JSON object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
 }
 
 /// Statistics for a specific tool
@@ -60,13 +72,63 @@ pub struct ToolCallStats {
     pub total_calls: i64,
     pub successful: i64,
     pub failed: i64,
+    pub cancelled: i64,
+    pub timed_out: i64,
     pub avg_duration_ms: f64,
+    /// Shortest duration of a completed call, or 0 if none completed
+    pub min_duration_ms: i64,
+    /// Longest duration of a completed call, or 0 if none completed
+    pub max_duration_ms: i64,
+    /// Median duration of the completed calls
+    pub p50_duration_ms: i64,
+    /// 95th percentile duration of the completed calls
+    pub p95_duration_ms: i64,
+    /// 99th percentile duration of the completed calls
+    pub p99_duration_ms: i64,
 }
 
+impl ToolCallStats {
+    /// Set the duration distribution from the durations of the completed
+    /// calls, sorted in ascending order
+    ///
+    /// Percentiles use the nearest-rank method, so each one is the duration
+    /// of an actual call.
+    fn set_durations(&mut self, sorted: &[i64]) {
+        if sorted.is_empty() {
+            return;
+        }
+        let percentile = |p: usize| {
+            let rank = (p * sorted.len()).div_ceil(100);
+            sorted[rank.max(1) - 1]
+        };
+        self.min_duration_ms = sorted[0];
+        self.max_duration_ms = sorted[sorted.len() - 1];
+        self.p50_duration_ms = percentile(50);
+        self.p95_duration_ms = percentile(95);
+        self.p99_duration_ms = percentile(99);
+    }
+}
+
+/// Tables and indexes created by [`ToolCalls::initialize`]
+const SCHEMA_OBJECTS: &[&str] = &[
+    "tool_calls",
+    "idx_tool_calls_name",
+    "idx_tool_calls_started_at",
+    "tool_schemas",
+];
+
 /// Tool calls tracker backed by SQLite
 #[derive(Clone)]
 pub struct ToolCalls {
     conn: Arc<Connection>,
+    schema: LazySchema,
+    /// Lock taken before every write, shared by clones and by the other
+    /// stores of an [`AgentFS`](crate::AgentFS)
+    transaction_lock: TransactionLock,
+    /// Whether this is a sub-store of an
+    /// [`AgentFS::transaction`](crate::AgentFS::transaction), whose
+    /// operations run in the transaction it opened
+    in_transaction: bool,
 }
 
 impl ToolCalls {
@@ -74,69 +136,210 @@ impl ToolCalls {
     pub async fn new(db_path: &str) -> Result<Self> {
         let db = Builder::new_local(db_path).build().await?;
         let conn = db.connect()?;
-        let tc = Self {
-            conn: Arc::new(conn),
-        };
-        tc.initialize().await?;
-        Ok(tc)
+        Self::from_connection(Arc::new(conn)).await
     }
 
     /// Create a tool calls tracker from an existing connection
     pub async fn from_connection(conn: Arc<Connection>) -> Result<Self> {
-        let tc = Self { conn };
-        tc.initialize().await?;
+        let tc = Self::from_connection_lazy(conn);
+        tc.ensure_schema().await?;
         Ok(tc)
     }
 
+    /// Create a tool calls tracker from an existing connection without initializing the schema
+    ///
+    /// The schema is created on first use.
+    pub(crate) fn from_connection_lazy(conn: Arc<Connection>) -> Self {
+        Self {
+            conn,
+            schema: LazySchema::default(),
+            transaction_lock: TransactionLock::default(),
+            in_transaction: false,
+        }
+    }
+
+    /// Take `transaction_lock` before every write
+    pub(crate) fn with_transaction_lock(self, transaction_lock: TransactionLock) -> Self {
+        Self {
+            transaction_lock,
+            ..self
+        }
+    }
+
+    /// Get a clone whose operations run in the transaction open on the connection
+    pub(crate) fn in_transaction(&self) -> Self {
+        Self {
+            in_transaction: true,
+            ..self.clone()
+        }
+    }
+
+    /// Flush all changes to the database file and close this tool calls tracker's connection
+    ///
+    /// The connection stays open while other handles share it.
+    pub async fn close(self) -> Result<()> {
+        crate::close_connection(self.conn).await
+    }
+
+    /// Initialize the database schema unless already done
+    pub(crate) async fn ensure_schema(&self) -> Result<()> {
+        self.schema.get_or_init(|| self.initialize()).await
+    }
+
     /// Initialize the database schema
     async fn initialize(&self) -> Result<()> {
-        self.conn
-            .execute(
-                "CREATE TABLE IF NOT EXISTS tool_calls (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    name TEXT NOT NULL,
-                    parameters TEXT,
-                    result TEXT,
-                    error TEXT,
-                    status TEXT NOT NULL DEFAULT 'pending',
-                    started_at INTEGER NOT NULL,
-                    completed_at INTEGER,
-                    duration_ms INTEGER
-                )",
-                (),
+        if !schema::exists(&self.conn, SCHEMA_OBJECTS).await? {
+            self.create_tables().await?;
+        }
+
+        // Databases created before tool calls had metadata
+        if !schema::has_column(&self.conn, "tool_calls", "metadata").await? {
+            schema::execute_ddl(
+                &self.conn,
+                "ALTER TABLE tool_calls ADD COLUMN metadata TEXT",
             )
             .await?;
+        }
 
-        self.conn
-            .execute(
-                "CREATE INDEX IF NOT EXISTS idx_tool_calls_name
-                ON tool_calls(name)",
-                (),
+        // Databases created before start times were recorded in milliseconds
+        if !schema::has_column(&self.conn, "tool_calls", "started_at_ms").await? {
+            schema::execute_ddl(
+                &self.conn,
+                "ALTER TABLE tool_calls ADD COLUMN started_at_ms INTEGER",
             )
             .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Create the tables and indexes that do not exist yet
+    async fn create_tables(&self) -> Result<()> {
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE TABLE IF NOT EXISTS tool_calls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                parameters TEXT,
+                result TEXT,
+                error TEXT,
+                status TEXT NOT NULL DEFAULT 'pending',
+                started_at INTEGER NOT NULL,
+                started_at_ms INTEGER,
+                completed_at INTEGER,
+                duration_ms INTEGER,
+                metadata TEXT
+            )",
+        )
+        .await?;
+
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE INDEX IF NOT EXISTS idx_tool_calls_name
+            ON tool_calls(name)",
+        )
+        .await?;
+
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE INDEX IF NOT EXISTS idx_tool_calls_started_at
+            ON tool_calls(started_at)",
+        )
+        .await?;
+
+        schema::execute_ddl(
+            &self.conn,
+            "CREATE TABLE IF NOT EXISTS tool_schemas (
+                name TEXT PRIMARY KEY,
+                schema TEXT NOT NULL
+            )",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Register the JSON schema of a tool's parameters
+    ///
+    /// Once registered, [`ToolCalls::start`] rejects calls of the tool whose
+    /// parameters don't match the schema. Registering a tool again replaces
+    /// its schema. Schemas can use the `type`, `enum`, `properties`,
+    /// `required`, `additionalProperties`, and `items` keywords, and other
+    /// keywords are ignored.
+    pub async fn register_tool(&self, name: &str, schema: serde_json::Value) -> Result<()> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        if !schema.is_object() && !schema.is_boolean() {
+            anyhow::bail!("Schema of tool '{}' must be an object or a boolean", name);
+        }
+        let serialized_schema = serde_json::to_string(&schema)?;
 
         self.conn
             .execute(
-                "CREATE INDEX IF NOT EXISTS idx_tool_calls_started_at
-                ON tool_calls(started_at)",
-                (),
+                "INSERT INTO tool_schemas (name, schema) VALUES (?, ?)
+                ON CONFLICT(name) DO UPDATE SET schema = excluded.schema",
+                (name, serialized_schema.as_str()),
             )
             .await?;
 
         Ok(())
     }
 
+    /// Get the registered JSON schema of a tool's parameters
+    pub async fn get_tool_schema(&self, name: &str) -> Result<Option<serde_json::Value>> {
+        self.ensure_schema().await?;
+        let mut rows = self
+            .conn
+            .query("SELECT schema FROM tool_schemas WHERE name = ?", (name,))
+            .await?;
+
+        match rows.next().await? {
+            Some(row) => match row.get_value(0)? {
+                Value::Text(schema) => Ok(Some(serde_json::from_str(&schema)?)),
+                _ => anyhow::bail!("Invalid schema of tool '{}'", name),
+            },
+            None => Ok(None),
+        }
+    }
+
     /// Start a new tool call and mark it as pending
     /// Returns the ID of the created tool call record
-    pub async fn start(&self, name: &str, parameters: Option<serde_json::Value>) -> Result<i64> {
+    ///
+    /// If the tool has a registered schema, the parameters must match it,
+    /// with missing parameters checked as JSON `null`. The metadata, if
+    /// any, must be a JSON object of labels to find the call by with
+    /// [`ToolCalls::find_by_metadata`].
+    pub async fn start(
+        &self,
+        name: &str,
+        parameters: Option<serde_json::Value>,
+        metadata: Option<serde_json::Value>,
+    ) -> Result<i64> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        if metadata.as_ref().is_some_and(|m| !m.is_object()) {
+            anyhow::bail!("Tool call metadata must be a JSON object");
+        }
+        if let Some(schema) = self.get_tool_schema(name).await? {
+            let value = parameters.clone().unwrap_or(serde_json::Value::Null);
+            validate(&schema, &value, "parameters")
+                .map_err(|e| anyhow::anyhow!("Invalid parameters for tool '{}': {}", name, e))?;
+        }
         let serialized_params = parameters.map(|p| serde_json::to_string(&p)).transpose()?;
-        let started_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let serialized_metadata = metadata.map(|m| serde_json::to_string(&m)).transpose()?;
+        let started_at_ms = now_ms()?;
 
         self.conn
             .execute(
-                "INSERT INTO tool_calls (name, parameters, status, started_at)
-                VALUES (?, ?, 'pending', ?)",
-                (name, serialized_params.as_deref().unwrap_or(""), started_at),
+                "INSERT INTO tool_calls (name, parameters, status, started_at, started_at_ms, metadata)
+                VALUES (?, ?, 'pending', ?, ?, ?)",
+                (
+                    name,
+                    serialized_params.as_deref().unwrap_or(""),
+                    started_at_ms / 1000,
+                    started_at_ms,
+                    serialized_metadata.as_deref().unwrap_or(""),
+                ),
             )
             .await?;
 
@@ -153,33 +356,75 @@ impl ToolCalls {
         }
     }
 
-    /// Mark a tool call as successful
-    pub async fn success(&self, id: i64, result: Option<serde_json::Value>) -> Result<()> {
-        let serialized_result = result.map(|r| serde_json::to_string(&r)).transpose()?;
-        let completed_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    /// Update the parameters or partial result of a pending tool call
+    ///
+    /// Values of `None` are left unchanged. New parameters are checked
+    /// against the tool's schema as in [`ToolCalls::start`]. A partial result
+    /// is replaced by the result passed to [`ToolCalls::success`]. Fails if
+    /// the call has already completed.
+    pub async fn update(
+        &self,
+        id: i64,
+        params: Option<serde_json::Value>,
+        partial_result: Option<serde_json::Value>,
+    ) -> Result<()> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        let Some(call) = self.get(id).await? else {
+            anyhow::bail!("Tool call not found");
+        };
+        if call.status != ToolCallStatus::Pending {
+            anyhow::bail!("Tool call {} is already {}", id, call.status);
+        }
+        if let Some(value) = &params {
+            if let Some(schema) = self.get_tool_schema(&call.name).await? {
+                validate(&schema, value, "parameters").map_err(|e| {
+                    anyhow::anyhow!("Invalid parameters for tool '{}': {}", call.name, e)
+                })?;
+            }
+        }
 
-        // Get the started_at time to calculate duration
-        let mut rows = self
+        let parameters = params.or(call.parameters);
+        let result = partial_result.or(call.result);
+        let serialized_params = parameters.map(|p| serde_json::to_string(&p)).transpose()?;
+        let serialized_result = result.map(|r| serde_json::to_string(&r)).transpose()?;
+        let updated = self
             .conn
-            .query("SELECT started_at FROM tool_calls WHERE id = ?", (id,))
+            .execute(
+                "UPDATE tool_calls SET parameters = ?, result = ?
+                WHERE id = ? AND status = 'pending'",
+                (
+                    serialized_params.as_deref().unwrap_or(""),
+                    serialized_result.as_deref().unwrap_or(""),
+                    id,
+                ),
+            )
             .await?;
+        if updated == 0 {
+            // Completed since it was read
+            anyhow::bail!("Tool call {} is no longer pending", id);
+        }
 
-        let started_at = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .ok_or_else(|| anyhow::anyhow!("Invalid started_at value"))?
-        } else {
-            anyhow::bail!("Tool call not found");
-        };
+        Ok(())
+    }
 
-        let duration_ms = (completed_at - started_at) * 1000;
+    /// Mark a pending tool call as successful
+    ///
+    /// Fails if the call has already completed.
+    pub async fn success(&self, id: i64, result: Option<serde_json::Value>) -> Result<()> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        let serialized_result = result.map(|r| serde_json::to_string(&r)).transpose()?;
+        let completed_at_ms = now_ms()?;
+        let completed_at = completed_at_ms / 1000;
+        let duration_ms = self.duration_ms(id, completed_at_ms).await?;
 
-        self.conn
+        let updated = self
+            .conn
             .execute(
                 "UPDATE tool_calls
                 SET result = ?, status = 'success', completed_at = ?, duration_ms = ?
-                WHERE id = ?",
+                WHERE id = ? AND status = 'pending'",
                 (
                     serialized_result.as_deref().unwrap_or(""),
                     completed_at,
@@ -188,6 +433,9 @@ impl ToolCalls {
                 ),
             )
             .await?;
+        if updated == 0 {
+            anyhow::bail!("Tool call {} is no longer pending", id);
+        }
 
         Ok(())
     }
@@ -195,6 +443,10 @@ impl ToolCalls {
     /// Record a completed tool call (spec-compliant insert-only method)
     /// Either result or error should be provided, not both
     /// Returns the ID of the created tool call record
+    ///
+    /// The call already happened, so unlike [`ToolCalls::start`], its
+    /// parameters are recorded without checking them against the tool's
+    /// schema.
     pub async fn record(
         &self,
         name: &str,
@@ -204,6 +456,8 @@ impl ToolCalls {
         result: Option<serde_json::Value>,
         error: Option<&str>,
     ) -> Result<i64> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
         let serialized_params = parameters.map(|p| serde_json::to_string(&p)).transpose()?;
         let serialized_result = result.map(|r| serde_json::to_string(&r)).transpose()?;
         let duration_ms = (completed_at - started_at) * 1000;
@@ -239,45 +493,132 @@ impl ToolCalls {
         }
     }
 
-    /// Mark a tool call as failed
+    /// Mark a pending tool call as failed
+    ///
+    /// Fails if the call has already completed.
     pub async fn error(&self, id: i64, error: &str) -> Result<()> {
-        let completed_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        let completed_at_ms = now_ms()?;
+        let completed_at = completed_at_ms / 1000;
+        let duration_ms = self.duration_ms(id, completed_at_ms).await?;
 
-        // Get the started_at time to calculate duration
-        let mut rows = self
+        let updated = self
             .conn
-            .query("SELECT started_at FROM tool_calls WHERE id = ?", (id,))
+            .execute(
+                "UPDATE tool_calls
+                SET error = ?, status = 'error', completed_at = ?, duration_ms = ?
+                WHERE id = ? AND status = 'pending'",
+                (error, completed_at, duration_ms, id),
+            )
             .await?;
+        if updated == 0 {
+            anyhow::bail!("Tool call {} is no longer pending", id);
+        }
 
-        let started_at = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .ok_or_else(|| anyhow::anyhow!("Invalid started_at value"))?
-        } else {
-            anyhow::bail!("Tool call not found");
-        };
+        Ok(())
+    }
 
-        let duration_ms = (completed_at - started_at) * 1000;
+    /// Mark a pending tool call as cancelled
+    ///
+    /// Fails if the call has already completed.
+    pub async fn cancel(&self, id: i64) -> Result<()> {
+        self.finish(id, ToolCallStatus::Cancelled).await
+    }
 
-        self.conn
+    /// Mark a pending tool call as timed out
+    ///
+    /// Fails if the call has already completed.
+    pub async fn timeout(&self, id: i64) -> Result<()> {
+        self.finish(id, ToolCallStatus::Timeout).await
+    }
+
+    /// Move a pending tool call to a terminal status that has no result or error
+    async fn finish(&self, id: i64, status: ToolCallStatus) -> Result<()> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        let completed_at_ms = now_ms()?;
+        let completed_at = completed_at_ms / 1000;
+        let duration_ms = self.duration_ms(id, completed_at_ms).await?;
+
+        let updated = self
+            .conn
             .execute(
                 "UPDATE tool_calls
-                SET error = ?, status = 'error', completed_at = ?, duration_ms = ?
-                WHERE id = ?",
-                (error, completed_at, duration_ms, id),
+                SET status = ?, completed_at = ?, duration_ms = ?
+                WHERE id = ? AND status = 'pending'",
+                (status.to_string(), completed_at, duration_ms, id),
             )
             .await?;
+        if updated == 0 {
+            anyhow::bail!("Tool call {} is no longer pending", id);
+        }
 
         Ok(())
     }
 
+    /// Delete completed tool calls that completed more than `older_than` ago
+    ///
+    /// Pending calls are kept regardless of age. Returns the number of calls
+    /// deleted.
+    pub async fn prune(&self, older_than: Duration) -> Result<u64> {
+        self.ensure_schema().await?;
+        let _lock = write_lock(&self.transaction_lock, self.in_transaction).await;
+        let cutoff = SystemTime::now()
+            .checked_sub(older_than)
+            .unwrap_or(UNIX_EPOCH)
+            .duration_since(UNIX_EPOCH)?
+            .as_secs() as i64;
+
+        let deleted = self
+            .conn
+            .execute(
+                "DELETE FROM tool_calls
+                WHERE status != 'pending' AND completed_at < ?",
+                (cutoff,),
+            )
+            .await?;
+
+        Ok(deleted)
+    }
+
+    /// Compute the duration of a tool call completing at `completed_at_ms`,
+    /// in milliseconds since the Unix epoch
+    ///
+    /// Calls started before start times were recorded in milliseconds only
+    /// have a start time in seconds, so their duration is a whole number of
+    /// seconds, as with `record`.
+    async fn duration_ms(&self, id: i64, completed_at_ms: i64) -> Result<i64> {
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT started_at, started_at_ms FROM tool_calls WHERE id = ?",
+                (id,),
+            )
+            .await?;
+
+        let Some(row) = rows.next().await? else {
+            anyhow::bail!("Tool call not found");
+        };
+        if let Some(started_at_ms) = row.get_value(1).ok().and_then(|v| v.as_integer().copied()) {
+            return Ok(completed_at_ms - started_at_ms);
+        }
+        let started_at = row
+            .get_value(0)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .ok_or_else(|| anyhow::anyhow!("Invalid started_at value"))?;
+
+        Ok((completed_at_ms / 1000 - started_at) * 1000)
+    }
+
     /// Get a tool call by ID
     pub async fn get(&self, id: i64) -> Result<Option<ToolCall>> {
+        self.ensure_schema().await?;
         let mut rows = self
             .conn
             .query(
-                "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms
+                "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms, metadata
                 FROM tool_calls WHERE id = ?",
                 (id,),
             )
@@ -292,11 +633,12 @@ impl ToolCalls {
 
     /// Get recent tool calls with optional limit
     pub async fn recent(&self, limit: Option<i64>) -> Result<Vec<ToolCall>> {
+        self.ensure_schema().await?;
         let limit = limit.unwrap_or(100);
         let mut rows = self
             .conn
             .query(
-                "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms
+                "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms, metadata
                 FROM tool_calls
                 ORDER BY started_at DESC
                 LIMIT ?",
@@ -312,8 +654,39 @@ impl ToolCalls {
         Ok(calls)
     }
 
+    /// Get the tool calls whose metadata has `key` set to `value`, oldest first
+    pub async fn find_by_metadata(
+        &self,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> Result<Vec<ToolCall>> {
+        self.ensure_schema().await?;
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms, metadata
+                FROM tool_calls
+                WHERE metadata IS NOT NULL AND metadata != ''
+                ORDER BY id",
+                (),
+            )
+            .await?;
+
+        // Values are compared as JSON, so the string "1" doesn't match the number 1
+        let mut calls = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let call = self.row_to_tool_call(&row)?;
+            if call.metadata.as_ref().and_then(|m| m.get(key)) == Some(value) {
+                calls.push(call);
+            }
+        }
+
+        Ok(calls)
+    }
+
     /// Get statistics for a specific tool
     pub async fn stats_for(&self, name: &str) -> Result<Option<ToolCallStats>> {
+        self.ensure_schema().await?;
         let mut rows = self
             .conn
             .query(
@@ -322,6 +695,8 @@ impl ToolCalls {
                     COUNT(*) as total_calls,
                     SUM(CASE WHEN status = 'success' THEN 1 ELSE 0 END) as successful,
                     SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as failed,
+                    SUM(CASE WHEN status = 'cancelled' THEN 1 ELSE 0 END) as cancelled,
+                    SUM(CASE WHEN status = 'timeout' THEN 1 ELSE 0 END) as timed_out,
                     AVG(CASE WHEN duration_ms IS NOT NULL THEN duration_ms ELSE 0 END) as avg_duration_ms
                 FROM tool_calls
                 WHERE name = ?
@@ -330,15 +705,35 @@ impl ToolCalls {
             )
             .await?;
 
-        if let Some(row) = rows.next().await? {
-            Ok(Some(self.row_to_stats(&row)?))
-        } else {
-            Ok(None)
+        let mut stats = match rows.next().await? {
+            Some(row) => self.row_to_stats(&row)?,
+            None => return Ok(None),
+        };
+        drop(rows);
+
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT duration_ms FROM tool_calls
+                WHERE name = ? AND duration_ms IS NOT NULL
+                ORDER BY duration_ms",
+                (name,),
+            )
+            .await?;
+        let mut durations = Vec::new();
+        while let Some(row) = rows.next().await? {
+            if let Some(duration) = row.get_value(0).ok().and_then(|v| v.as_integer().copied()) {
+                durations.push(duration);
+            }
         }
+        stats.set_durations(&durations);
+
+        Ok(Some(stats))
     }
 
     /// Get statistics for all tools
     pub async fn stats(&self) -> Result<Vec<ToolCallStats>> {
+        self.ensure_schema().await?;
         let mut rows = self
             .conn
             .query(
@@ -347,6 +742,8 @@ impl ToolCalls {
                     COUNT(*) as total_calls,
                     SUM(CASE WHEN status = 'success' THEN 1 ELSE 0 END) as successful,
                     SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as failed,
+                    SUM(CASE WHEN status = 'cancelled' THEN 1 ELSE 0 END) as cancelled,
+                    SUM(CASE WHEN status = 'timeout' THEN 1 ELSE 0 END) as timed_out,
                     AVG(CASE WHEN duration_ms IS NOT NULL THEN duration_ms ELSE 0 END) as avg_duration_ms
                 FROM tool_calls
                 GROUP BY name
@@ -359,6 +756,31 @@ impl ToolCalls {
         while let Some(row) = rows.next().await? {
             stats.push(self.row_to_stats(&row)?);
         }
+        drop(rows);
+
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT name, duration_ms FROM tool_calls
+                WHERE duration_ms IS NOT NULL
+                ORDER BY name, duration_ms",
+                (),
+            )
+            .await?;
+        let mut durations: HashMap<String, Vec<i64>> = HashMap::new();
+        while let Some(row) = rows.next().await? {
+            if let (Ok(Value::Text(name)), Some(duration)) = (
+                row.get_value(0),
+                row.get_value(1).ok().and_then(|v| v.as_integer().copied()),
+            ) {
+                durations.entry(name).or_default().push(duration);
+            }
+        }
+        for tool in &mut stats {
+            if let Some(durations) = durations.get(&tool.name) {
+                tool.set_durations(durations);
+            }
+        }
 
         Ok(stats)
     }
@@ -440,6 +862,18 @@ impl ToolCalls {
 
         let duration_ms = row.get_value(8).ok().and_then(|v| v.as_integer().copied());
 
+        let metadata = row.get_value(9).ok().and_then(|v| {
+            if let Value::Text(s) = v {
+                if !s.is_empty() {
+                    serde_json::from_str(s.as_str()).ok()
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        });
+
         Ok(ToolCall {
             id,
             name,
@@ -450,6 +884,7 @@ impl ToolCalls {
             started_at,
             completed_at,
             duration_ms,
+            metadata,
         })
     }
 
@@ -484,9 +919,21 @@ impl ToolCalls {
             .and_then(|v| v.as_integer().copied())
             .unwrap_or(0);
 
-        let avg_duration_ms = row
+        let cancelled = row
             .get_value(4)
             .ok()
+            .and_then(|v| v.as_integer().copied())
+            .unwrap_or(0);
+
+        let timed_out = row
+            .get_value(5)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .unwrap_or(0);
+
+        let avg_duration_ms = row
+            .get_value(6)
+            .ok()
             .and_then(|v| match v {
                 Value::Real(f) => Some(f),
                 Value::Integer(i) => Some(i as f64),
@@ -499,7 +946,102 @@ impl ToolCalls {
             total_calls,
             successful,
             failed,
+            cancelled,
+            timed_out,
             avg_duration_ms,
+            min_duration_ms: 0,
+            max_duration_ms: 0,
+            p50_duration_ms: 0,
+            p95_duration_ms: 0,
+            p99_duration_ms: 0,
         })
     }
 }
+
+/// The current time in milliseconds since the Unix epoch
+fn now_ms() -> Result<i64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64)
+}
+
+/// Check a JSON value against a JSON schema
+///
+/// Supports the `type`, `enum`, `properties`, `required`,
+/// `additionalProperties`, and `items` keywords and ignores all others. On a
+/// mismatch, the error names the offending location starting from `path`.
+fn validate(
+    schema: &serde_json::Value,
+    value: &serde_json::Value,
+    path: &str,
+) -> std::result::Result<(), String> {
+    use serde_json::Value as Json;
+
+    let schema = match schema {
+        Json::Bool(true) => return Ok(()),
+        Json::Bool(false) => return Err(format!("{} is not allowed", path)),
+        Json::Object(schema) => schema,
+        _ => return Err(format!("invalid schema for {}", path)),
+    };
+
+    if let Some(ty) = schema.get("type") {
+        let types: Vec<&str> = match ty {
+            Json::String(ty) => vec![ty.as_str()],
+            Json::Array(types) => types.iter().filter_map(|ty| ty.as_str()).collect(),
+            _ => return Err(format!("invalid type in schema for {}", path)),
+        };
+        if !types.iter().any(|ty| type_matches(ty, value)) {
+            return Err(format!("{} must be of type {}", path, types.join(" or ")));
+        }
+    }
+
+    if let Some(Json::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            return Err(format!("{} must be one of the allowed values", path));
+        }
+    }
+
+    if let Json::Object(object) = value {
+        if let Some(Json::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(|name| name.as_str()) {
+                if !object.contains_key(name) {
+                    return Err(format!("{}.{} is required", path, name));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (name, property) in object {
+            let property_path = format!("{}.{}", path, name);
+            match properties.and_then(|p| p.get(name)) {
+                Some(property_schema) => validate(property_schema, property, &property_path)?,
+                None => {
+                    if let Some(additional) = schema.get("additionalProperties") {
+                        validate(additional, property, &property_path)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Json::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            validate(item_schema, item, &format!("{}[{}]", path, i))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check whether a JSON value is of a JSON schema type
+fn type_matches(ty: &str, value: &serde_json::Value) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => false,
+    }
+}